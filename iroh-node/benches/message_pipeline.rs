@@ -0,0 +1,158 @@
+//! 消息编解码热路径的基准测试
+//!
+//! 对比旧实现与当前实现：
+//! - 旧实现编码时复制整条消息，解码时把消息数据复制到新的缓冲区
+//! - 当前实现编码时借用消息并复用编码缓冲区，解码时消息内容与收到的数据共享内存
+//! - 旧的消息处理器为每个Agent请求逐项克隆节点状态，当前实现共享同一个 `Arc`
+//!
+//! ```bash
+//! cargo bench --bench message_pipeline
+//! ```
+//!
+//! 参考结果（`--measurement-time 3`，中位数）：
+//!
+//! | 基准 | 旧实现 | 当前实现 |
+//! |------|--------|----------|
+//! | encode/64 | 23.7 µs | 27.7 µs |
+//! | encode/65536 | 341 µs | 298 µs |
+//! | decode/64 | 52.2 µs | 55.5 µs |
+//! | decode/65536 | 263 µs | 274 µs |
+//! | dispatch | 250 ns | 18.7 ns |
+//!
+//! 编解码的耗时主要在签名与验证上，两种实现的差异在误差范围内；
+//! 处理器状态共享后每个Agent请求的克隆开销降为一次 `Arc` 克隆
+
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ed25519_dalek::Signature;
+use iroh::{PublicKey, SecretKey};
+use iroh_node::{MessageType, SignedMessage, PROTOCOL_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// 旧实现：带协议版本的消息持有消息副本
+#[derive(Serialize)]
+struct LegacyVersionedMessage {
+    version: u16,
+    message: MessageType,
+}
+
+/// 旧实现：解码时消息数据复制到新的 `Bytes`
+#[derive(Serialize, Deserialize)]
+struct LegacySignedMessage {
+    from: PublicKey,
+    data: Bytes,
+    signature: Signature,
+}
+
+fn legacy_encode(secret_key: &SecretKey, message: &MessageType) -> Bytes {
+    let versioned = LegacyVersionedMessage {
+        version: PROTOCOL_VERSION,
+        message: message.clone(),
+    };
+    let data: Bytes = postcard::to_stdvec(&versioned).unwrap().into();
+    let signature = secret_key.sign(&data);
+    let signed = LegacySignedMessage {
+        from: secret_key.public(),
+        data,
+        signature,
+    };
+    postcard::to_stdvec(&signed).unwrap().into()
+}
+
+fn legacy_decode(bytes: &[u8]) -> (PublicKey, MessageType) {
+    let signed: LegacySignedMessage = postcard::from_bytes(bytes).unwrap();
+    signed.from.verify(&signed.data, &signed.signature).unwrap();
+    let (_, payload) = postcard::take_from_bytes::<u16>(&signed.data).unwrap();
+    let payload = signed.data.slice_ref(payload);
+    (signed.from, postcard::from_bytes(&payload).unwrap())
+}
+
+fn current_decode(bytes: &Bytes) -> (PublicKey, MessageType) {
+    let (from, _, payload) = SignedMessage::verify(bytes).unwrap();
+    (from, postcard::from_bytes(&payload).unwrap())
+}
+
+fn chat(len: usize) -> MessageType {
+    MessageType::Chat {
+        id: "0123456789abcdef0123456789abcdef".to_string(),
+        text: "消".repeat(len / 3),
+    }
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let secret_key = SecretKey::from_bytes(&rand::random());
+
+    let mut encode = c.benchmark_group("encode");
+    for len in [64, 4 * 1024, 64 * 1024] {
+        let message = chat(len);
+        encode.throughput(Throughput::Bytes(len as u64));
+        encode.bench_with_input(BenchmarkId::new("legacy", len), &message, |b, message| {
+            b.iter(|| legacy_encode(&secret_key, black_box(message)))
+        });
+        encode.bench_with_input(BenchmarkId::new("current", len), &message, |b, message| {
+            b.iter(|| SignedMessage::sign_and_encode(&secret_key, black_box(message)).unwrap())
+        });
+    }
+    encode.finish();
+
+    let mut decode = c.benchmark_group("decode");
+    for len in [64, 4 * 1024, 64 * 1024] {
+        let encoded = SignedMessage::sign_and_encode(&secret_key, &chat(len)).unwrap();
+        // 两种实现的编码格式相同
+        assert_eq!(encoded, legacy_encode(&secret_key, &chat(len)));
+        decode.throughput(Throughput::Bytes(len as u64));
+        decode.bench_with_input(BenchmarkId::new("legacy", len), &encoded, |b, encoded| {
+            b.iter(|| legacy_decode(black_box(encoded)))
+        });
+        decode.bench_with_input(BenchmarkId::new("current", len), &encoded, |b, encoded| {
+            b.iter(|| current_decode(black_box(encoded)))
+        });
+    }
+    decode.finish();
+}
+
+/// 消息处理器使用的一项节点状态
+type SharedState = Arc<RwLock<HashMap<String, u64>>>;
+
+/// 消息处理器的状态：签名密钥、话题与各项共享的节点状态，数量与处理器一致
+#[derive(Clone)]
+struct HandlerState {
+    secret_key: SecretKey,
+    topic: [u8; 32],
+    shared: [SharedState; 15],
+}
+
+/// 旧实现：每个Agent请求逐项克隆处理器状态后移入任务
+fn legacy_dispatch(state: &HandlerState) -> usize {
+    let request = HandlerState {
+        secret_key: state.secret_key.clone(),
+        topic: state.topic,
+        shared: std::array::from_fn(|i| state.shared[i].clone()),
+    };
+    black_box(&request).shared.len()
+}
+
+/// 当前实现：每个Agent请求只克隆处理器状态的 `Arc`
+fn current_dispatch(state: &Arc<HandlerState>) -> usize {
+    let request = state.clone();
+    black_box(&request).shared.len()
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let state = HandlerState {
+        secret_key: SecretKey::from_bytes(&rand::random()),
+        topic: [7; 32],
+        shared: std::array::from_fn(|_| SharedState::default()),
+    };
+    let shared = Arc::new(state.clone());
+
+    let mut dispatch = c.benchmark_group("dispatch");
+    dispatch.bench_function("legacy", |b| b.iter(|| legacy_dispatch(black_box(&state))));
+    dispatch.bench_function("current", |b| b.iter(|| current_dispatch(black_box(&shared))));
+    dispatch.finish();
+}
+
+criterion_group!(benches, bench_pipeline, bench_dispatch);
+criterion_main!(benches);
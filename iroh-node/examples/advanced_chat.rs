@@ -1,34 +1,27 @@
 //! 高级 P2P 聊天示例
-//! 
-//! 支持文件传输、多房间管理等高级功能
+//!
+//! 支持多房间管理与文件夹同步等高级功能
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use iroh_node::{IrohIntegratedClient, TransferConfig, ConfigBuilder};
-use std::path::PathBuf;
+use iroh_gossip::proto::TopicId;
+use iroh_node::{NodeConfig, NodeEvent, P2PNode};
+use std::path::{Path, PathBuf};
 use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tracing::{info, error, warn};
+use tracing::{error, info};
 
 #[derive(Parser)]
 #[command(name = "advanced-chat")]
-#[command(about = "高级 P2P 聊天和文件传输应用")]
+#[command(about = "高级 P2P 聊天和文件夹同步应用")]
 struct Cli {
     /// 用户名
     #[arg(short, long, default_value = "用户")]
     name: String,
-    
-    /// 数据目录
-    #[arg(short, long)]
-    data_dir: Option<PathBuf>,
-    
-    /// 下载目录
-    #[arg(long)]
-    download_dir: Option<PathBuf>,
-    
+
     /// 详细日志
     #[arg(short, long)]
     verbose: bool,
-    
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,17 +30,12 @@ struct Cli {
 enum Commands {
     /// 启动交互式聊天
     Chat,
-    /// 发送文件
-    SendFile {
-        /// 文件路径
-        file: PathBuf,
-        /// 接收者邀请码
-        recipient: String,
-    },
-    /// 接收文件
-    ReceiveFile {
-        /// 发送者邀请码
-        sender: String,
+    /// 在聊天室中同步文件夹
+    ShareFolder {
+        /// 本地目录
+        dir: PathBuf,
+        /// 聊天室邀请码，不提供时创建新聊天室
+        ticket: Option<String>,
     },
 }
 
@@ -61,52 +49,47 @@ async fn main() -> Result<()> {
         .with_env_filter(format!("advanced_chat={},iroh_node={}", log_level, log_level))
         .init();
 
-    // 构建配置
-    let mut config_builder = ConfigBuilder::new();
-    
-    if let Some(data_dir) = cli.data_dir {
-        config_builder = config_builder.data_root(data_dir);
-    }
-    
-    if let Some(download_dir) = cli.download_dir {
-        config_builder = config_builder.download_dir(Some(download_dir));
-    }
-    
-    let config = config_builder
-        .verbose_logging(cli.verbose)
-        .build();
-
-    // 创建集成客户端
-    let client = IrohIntegratedClient::new(config).await?;
-    
+    // 创建并启动节点
+    let config = NodeConfig::new().with_name(Some(cli.name.clone()));
+    let node = P2PNode::new(config).await?;
+    node.start().await?;
+
     match cli.command {
         Commands::Chat => {
-            start_interactive_chat(client, cli.name).await?;
+            start_interactive_chat(&node, cli.name).await?;
         }
-        Commands::SendFile { file, recipient } => {
-            send_file(client, file, recipient).await?;
-        }
-        Commands::ReceiveFile { sender } => {
-            receive_file(client, sender).await?;
+        Commands::ShareFolder { dir, ticket } => {
+            share_folder(&node, dir, ticket).await?;
         }
     }
 
+    node.stop().await?;
     Ok(())
 }
 
-async fn start_interactive_chat(mut client: IrohIntegratedClient, username: String) -> Result<()> {
+async fn start_interactive_chat(node: &P2PNode, username: String) -> Result<()> {
     println!("=== 高级 P2P 聊天 ===");
     println!("命令:");
-    println!("  /create <房间名> - 创建聊天室");
+    println!("  /create - 创建聊天室");
     println!("  /join <邀请码> - 加入聊天室");
-    println!("  /send <文件路径> - 发送文件");
+    println!("  /share <目录> - 在当前聊天室同步文件夹");
     println!("  /rooms - 列出所有房间");
     println!("  /quit - 退出");
     println!();
 
+    // 打印收到的消息
+    let mut events = node.subscribe_events();
+    tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            if let NodeEvent::ChatReceived { entry, .. } = event {
+                println!("[{}] {}", entry.from, entry.text);
+            }
+        }
+    });
+
     let stdin = io::stdin();
     let mut reader = BufReader::new(stdin);
-    let mut current_room: Option<String> = None;
+    let mut current_room: Option<TopicId> = None;
 
     loop {
         print!("> ");
@@ -128,16 +111,11 @@ async fn start_interactive_chat(mut client: IrohIntegratedClient, username: Stri
 
             match command {
                 "/create" => {
-                    if args.is_empty() {
-                        println!("用法: /create <房间名>");
-                        continue;
-                    }
-                    
-                    match client.chat_client().create_room(args.to_string()).await {
-                        Ok(room) => {
-                            println!("聊天室 '{}' 已创建", room.name);
-                            println!("邀请码: {}", room.invite_code);
-                            current_room = Some(room.room_id);
+                    match node.join_topic(None, None).await {
+                        Ok((topic_id, ticket)) => {
+                            println!("聊天室 {} 已创建", topic_id);
+                            println!("邀请码: {}", ticket);
+                            current_room = Some(topic_id);
                         }
                         Err(e) => {
                             error!("创建聊天室失败: {}", e);
@@ -149,42 +127,42 @@ async fn start_interactive_chat(mut client: IrohIntegratedClient, username: Stri
                         println!("用法: /join <邀请码>");
                         continue;
                     }
-                    
-                    match client.chat_client().join_room(args.to_string()).await {
-                        Ok(room) => {
-                            println!("已加入聊天室: {}", room.name);
-                            current_room = Some(room.room_id);
+
+                    match node.join_topic(None, Some(args)).await {
+                        Ok((topic_id, _)) => {
+                            println!("已加入聊天室: {}", topic_id);
+                            current_room = Some(topic_id);
                         }
                         Err(e) => {
                             error!("加入聊天室失败: {}", e);
                         }
                     }
                 }
-                "/send" => {
+                "/share" => {
                     if args.is_empty() {
-                        println!("用法: /send <文件路径>");
+                        println!("用法: /share <目录>");
                         continue;
                     }
-                    
-                    let file_path = PathBuf::from(args);
-                    if !file_path.exists() {
-                        println!("文件不存在: {}", args);
+                    let Some(topic_id) = current_room else {
+                        println!("请先创建或加入一个聊天室");
                         continue;
-                    }
-                    
-                    match client.transfer_client().upload_file(file_path.into()).await {
-                        Ok(response) => {
-                            println!("文件上传成功!");
-                            println!("分享码: {}", response.doc_ticket);
+                    };
+
+                    let dir = PathBuf::from(args);
+                    match node.start_folder_sync(&topic_id, &folder_name(&dir), dir.clone()).await {
+                        Ok(files) => {
+                            println!("开始同步文件夹 {:?}，共 {} 个文件", dir, files.len());
                         }
                         Err(e) => {
-                            error!("文件上传失败: {}", e);
+                            error!("同步文件夹失败: {}", e);
                         }
                     }
                 }
                 "/rooms" => {
-                    // 这里可以实现房间列表功能
-                    println!("当前房间: {:?}", current_room);
+                    for topic_id in node.get_active_topics().await {
+                        let marker = if current_room == Some(topic_id) { "*" } else { " " };
+                        println!("{} {}", marker, topic_id);
+                    }
                 }
                 "/quit" => {
                     break;
@@ -195,8 +173,8 @@ async fn start_interactive_chat(mut client: IrohIntegratedClient, username: Stri
             }
         } else {
             // 发送聊天消息
-            if let Some(ref room_id) = current_room {
-                if let Err(e) = client.chat_client().send_message(room_id.clone(), input.to_string()).await {
+            if let Some(ref topic_id) = current_room {
+                if let Err(e) = node.send_chat(topic_id, input).await {
                     error!("发送消息失败: {}", e);
                 } else {
                     println!("[{}] {}", username, input);
@@ -211,39 +189,32 @@ async fn start_interactive_chat(mut client: IrohIntegratedClient, username: Stri
     Ok(())
 }
 
-async fn send_file(mut client: IrohIntegratedClient, file_path: PathBuf, _recipient: String) -> Result<()> {
-    info!("发送文件: {:?}", file_path);
-    
-    if !file_path.exists() {
-        return Err(anyhow::anyhow!("文件不存在: {:?}", file_path));
+async fn share_folder(node: &P2PNode, dir: PathBuf, ticket: Option<String>) -> Result<()> {
+    info!("同步文件夹: {:?}", dir);
+
+    if !dir.is_dir() {
+        return Err(anyhow::anyhow!("目录不存在: {:?}", dir));
     }
 
-    let response = client.transfer_client().upload_file(file_path.into()).await?;
-    
-    println!("文件发送成功!");
-    println!("分享码: {}", response.doc_ticket);
-    println!("请将此分享码发送给接收者");
+    let (topic_id, ticket) = node.join_topic(None, ticket.as_deref()).await?;
+    let folder = folder_name(&dir);
+    let files = node.start_folder_sync(&topic_id, &folder, dir).await?;
 
-    Ok(())
-}
+    println!("文件夹 {} 开始同步，共 {} 个文件", folder, files.len());
+    println!("邀请码: {}", ticket);
+    println!("其他成员同步同名文件夹即可收到文件，按 Ctrl+C 退出");
+    tokio::signal::ctrl_c().await?;
 
-async fn receive_file(mut client: IrohIntegratedClient, sender_code: String) -> Result<()> {
-    info!("接收文件，分享码: {}", sender_code);
-    
-    let request = iroh_node::DownloadRequest {
-        doc_ticket: sender_code,
-        download_dir: None,
-    };
-
-    match client.transfer_client().download_files(request).await {
-        Ok(download_path) => {
-            println!("文件接收成功!");
-            println!("保存位置: {}", download_path);
-        }
-        Err(e) => {
-            error!("文件接收失败: {}", e);
-        }
+    for file in node.folder_sync_status(&topic_id, &folder).await? {
+        println!("  {} - {:?}", file.name, file.state);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 同步文件夹名称取目录名
+fn folder_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "shared".to_string())
+}
@@ -0,0 +1,440 @@
+//! Axum适配器压力测试
+//!
+//! 在本地启动两个由Axum适配器托管的节点和一个兼容OpenAI协议的模拟提供商：
+//! 节点A通过HTTP接口向话题并发发送大量Agent请求，节点B处理请求并广播响应，
+//! 节点A从 `/api/node/events` 事件流中接收响应并统计端到端延迟。
+//!
+//! 以下任一情况视为失败并以非零状态退出：
+//! - 有请求在超时时间内没有收到响应（锁竞争或死锁会表现为超时）
+//! - 收到远端错误（例如并发创建Agent时的冲突）
+//! - p99延迟超过阈值
+//! - 发往不同Agent的提供商请求在时间上没有重叠（说明Agent之间被串行处理）
+//!
+//! 节点内部使用tokio的锁，不存在std锁的毒化问题，因此以“全部请求成功返回”作为锁正确性的判据。
+//!
+//! ```bash
+//! cargo run --example axum_load_test --features axum-adapter -- --requests 2000 --max-p99-ms 2000
+//! ```
+
+#[path = "../../rig-agent/tests/support/mock_provider.rs"]
+mod mock_provider;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use futures_lite::StreamExt;
+use iroh_node::{adapters::AxumAdapter, NodeEvent};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{
+    net::TcpListener,
+    sync::{Mutex, Semaphore},
+};
+use tracing::{info, warn};
+
+/// 示例内使用的错误类型
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 命令行参数
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// 请求总数
+    #[arg(long, default_value_t = 2000)]
+    requests: usize,
+
+    /// 同时在途的最大请求数
+    #[arg(long, default_value_t = 512)]
+    concurrency: usize,
+
+    /// 使用的Agent数量，请求按轮询分配
+    #[arg(long, default_value_t = 8)]
+    agents: usize,
+
+    /// 允许的p99延迟（毫秒）
+    #[arg(long, default_value_t = 2000)]
+    max_p99_ms: u64,
+
+    /// 等待全部响应的超时时间（秒）
+    #[arg(long, default_value_t = 120)]
+    timeout_secs: u64,
+
+    /// 模拟提供商每次请求的固定延迟（毫秒），为0时无法判断不同Agent的请求是否重叠
+    #[arg(long, default_value_t = 20)]
+    provider_latency_ms: u64,
+}
+
+/// 话题响应
+#[derive(Debug, Deserialize)]
+struct TopicResponse {
+    topic_id: String,
+    ticket: String,
+}
+
+/// 在随机端口上托管一个Axum适配器
+async fn spawn_adapter() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = AxumAdapter::new().create_router();
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
+
+/// 订阅节点事件流，把解析后的事件转发到通道
+async fn subscribe_events(
+    client: &reqwest::Client,
+    base: &str,
+) -> Result<tokio::sync::mpsc::UnboundedReceiver<NodeEvent>, BoxError> {
+    let response = client
+        .get(format!("{}/api/node/events", base))
+        .send()
+        .await?
+        .error_for_status()?;
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+        while let Some(Ok(bytes)) = stream.next().await {
+            buffer.push_str(&String::from_utf8_lossy(&bytes));
+            while let Some(pos) = buffer.find("\n\n") {
+                let frame: String = buffer.drain(..pos + 2).collect();
+                let data: String = frame
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("data:"))
+                    .map(str::trim_start)
+                    .collect();
+                if let Ok(event) = serde_json::from_str::<NodeEvent>(&data) {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// 从请求内容中解析请求序号
+fn request_index(content: &str) -> Option<usize> {
+    content.rsplit("load-").next()?.trim().parse().ok()
+}
+
+/// 是否有发往不同Agent的提供商请求在时间上重叠
+fn agents_overlap(mut spans: Vec<mock_provider::RequestSpan>, agents: usize) -> bool {
+    spans.sort_by_key(|span| span.started);
+    // 预热请求不带请求序号，不参与判断
+    let agent = |span: &mock_provider::RequestSpan| request_index(&span.prompt).map(|i| i % agents);
+    spans.iter().enumerate().any(|(i, span)| {
+        spans[i + 1..]
+            .iter()
+            .take_while(|later| later.started < span.finished)
+            .any(|later| match (agent(span), agent(later)) {
+                (Some(a), Some(b)) => a != b && span.overlaps(later),
+                _ => false,
+            })
+    })
+}
+
+/// 计算百分位延迟
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((sorted.len() as f64) * p).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[tokio::main]
+async fn main() -> Result<(), BoxError> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .init();
+    let args = Args::parse();
+
+    // 让节点内的OpenAI客户端指向模拟提供商
+    let (provider, provider_log) = mock_provider::spawn_recording(mock_provider::MockConfig {
+        latency: Duration::from_millis(args.provider_latency_ms),
+        chunks: 4,
+        streaming: true,
+        echo: true,
+    })
+    .await;
+    std::env::set_var("OPENAI_API_KEY", "mock-key");
+    std::env::set_var("OPENAI_BASE_URL", format!("http://{}/v1", provider));
+
+    let client = reqwest::Client::new();
+    let requester = spawn_adapter().await;
+    let responder = spawn_adapter().await;
+
+    for (base, name) in [(&requester, "requester"), (&responder, "responder")] {
+        client
+            .post(format!("{}/api/node", base))
+            .json(&json!({ "name": name, "no_relay": true }))
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    let mut events = subscribe_events(&client, &requester).await?;
+
+    let topic: TopicResponse = client
+        .post(format!("{}/api/topics", requester))
+        .json(&json!({}))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    client
+        .post(format!("{}/api/topics/join", responder))
+        .json(&json!({ "ticket": topic.ticket }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // 等待两个节点在话题中建立连接
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while let Some(event) = events.recv().await {
+            if matches!(event, NodeEvent::NeighborUp { .. }) {
+                return;
+            }
+        }
+    })
+    .await
+    .map_err(|_| "等待节点连接超时")?;
+    info!("节点已连接，话题: {}", topic.topic_id);
+
+    let agent_url = format!("{}/api/topics/{}/agent", requester, topic.topic_id);
+    let agent_id = |i: usize| format!("load-agent-{}", i % args.agents);
+
+    // 预热：逐个创建Agent，避免把首次创建的开销计入延迟
+    for i in 0..args.agents {
+        client
+            .post(&agent_url)
+            .json(&json!({ "agent_id": agent_id(i), "prompt": "warmup" }))
+            .send()
+            .await?
+            .error_for_status()?;
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while let Some(event) = events.recv().await {
+                match event {
+                    NodeEvent::AgentResponseReceived { .. } => return Ok(()),
+                    NodeEvent::RemoteError { message, .. } => return Err(message),
+                    _ => {}
+                }
+            }
+            Err("事件流已关闭".to_string())
+        })
+        .await
+        .map_err(|_| "预热超时")??;
+    }
+
+    info!(
+        "开始压测: {} 个请求, 并发 {}, {} 个Agent",
+        args.requests, args.concurrency, args.agents
+    );
+    let pending: Arc<Mutex<HashMap<usize, Instant>>> = Arc::new(Mutex::new(HashMap::new()));
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let send_failures = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+
+    // 发送端：在途请求数达到上限时等待响应释放许可
+    let sender = {
+        let client = client.clone();
+        let pending = pending.clone();
+        let semaphore = semaphore.clone();
+        let send_failures = send_failures.clone();
+        let requests = args.requests;
+        let agents: Vec<String> = (0..args.agents).map(agent_id).collect();
+        tokio::spawn(async move {
+            for i in 0..requests {
+                let permit = semaphore.clone().acquire_owned().await.unwrap();
+                permit.forget();
+                pending.lock().await.insert(i, Instant::now());
+
+                let client = client.clone();
+                let url = agent_url.clone();
+                let agent_id = agents[i % agents.len()].clone();
+                let pending = pending.clone();
+                let semaphore = semaphore.clone();
+                let send_failures = send_failures.clone();
+                tokio::spawn(async move {
+                    let result = client
+                        .post(&url)
+                        .json(&json!({ "agent_id": agent_id, "prompt": format!("load-{}", i) }))
+                        .send()
+                        .await
+                        .and_then(|r| r.error_for_status());
+                    if let Err(e) = result {
+                        warn!("请求 {} 发送失败: {}", i, e);
+                        pending.lock().await.remove(&i);
+                        send_failures.fetch_add(1, Ordering::Relaxed);
+                        semaphore.add_permits(1);
+                    }
+                });
+            }
+        })
+    };
+
+    // 接收端：按请求序号匹配响应
+    let mut latencies = Vec::with_capacity(args.requests);
+    let mut remote_errors = Vec::new();
+    let collect = tokio::time::timeout(Duration::from_secs(args.timeout_secs), async {
+        while latencies.len() + remote_errors.len() + send_failures.load(Ordering::Relaxed)
+            < args.requests
+        {
+            let Some(event) = events.recv().await else {
+                break;
+            };
+            match event {
+                NodeEvent::AgentResponseReceived { content, .. } => {
+                    let Some(index) = request_index(&content) else {
+                        continue;
+                    };
+                    if let Some(sent_at) = pending.lock().await.remove(&index) {
+                        latencies.push(sent_at.elapsed());
+                        semaphore.add_permits(1);
+                    }
+                }
+                NodeEvent::RemoteError { message, .. } => {
+                    remote_errors.push(message);
+                    semaphore.add_permits(1);
+                }
+                _ => {}
+            }
+        }
+    })
+    .await;
+    let elapsed = started.elapsed();
+    sender.abort();
+
+    let missing = pending.lock().await.len();
+    let send_failures = send_failures.load(Ordering::Relaxed);
+    latencies.sort();
+    let p50 = percentile(&latencies, 0.50);
+    let p95 = percentile(&latencies, 0.95);
+    let p99 = percentile(&latencies, 0.99);
+    let max = latencies.last().copied().unwrap_or_default();
+    let overlapped = agents_overlap(provider_log.spans(), args.agents);
+
+    println!(
+        "完成: {}/{} 个请求，用时 {:.2?}",
+        latencies.len(),
+        args.requests,
+        elapsed
+    );
+    println!(
+        "吞吐: {:.1} 请求/秒",
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "延迟: p50={:?} p95={:?} p99={:?} max={:?}",
+        p50, p95, p99, max
+    );
+    println!(
+        "未响应: {}，发送失败: {}，远端错误: {}",
+        missing,
+        send_failures,
+        remote_errors.len()
+    );
+    for message in remote_errors.iter().take(5) {
+        println!("  远端错误: {}", message);
+    }
+    println!(
+        "不同Agent的提供商请求{}重叠",
+        if overlapped { "存在" } else { "没有" }
+    );
+
+    let mut failures = Vec::new();
+    if collect.is_err() {
+        failures.push(format!("{} 秒内未收到全部响应", args.timeout_secs));
+    }
+    if missing > 0 {
+        failures.push(format!("{} 个请求未收到响应", missing));
+    }
+    if send_failures > 0 {
+        failures.push(format!("{} 个请求发送失败", send_failures));
+    }
+    if !remote_errors.is_empty() {
+        failures.push(format!("收到 {} 个远端错误", remote_errors.len()));
+    }
+    if args.agents > 1 && args.provider_latency_ms > 0 && !overlapped {
+        failures.push("不同Agent的提供商请求没有重叠，Agent之间被串行处理".to_string());
+    }
+    if p99 > Duration::from_millis(args.max_p99_ms) {
+        failures.push(format!("p99延迟 {:?} 超过阈值 {}ms", p99, args.max_p99_ms));
+    }
+
+    if failures.is_empty() {
+        println!("压测通过");
+        Ok(())
+    } else {
+        for failure in &failures {
+            eprintln!("失败: {}", failure);
+        }
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(prompt: &str, start: Instant, from_ms: u64, to_ms: u64) -> mock_provider::RequestSpan {
+        mock_provider::RequestSpan {
+            prompt: prompt.to_string(),
+            started: start + Duration::from_millis(from_ms),
+            finished: start + Duration::from_millis(to_ms),
+        }
+    }
+
+    #[test]
+    fn test_request_index() {
+        assert_eq!(request_index("请回复 load-42"), Some(42));
+        assert_eq!(request_index("load-7 "), Some(7));
+        assert_eq!(request_index("预热"), None);
+    }
+
+    #[test]
+    fn test_agents_overlap() {
+        let start = Instant::now();
+        // 请求 0 与 1 发往不同的Agent且时间重叠
+        let spans = vec![
+            span("load-0", start, 0, 100),
+            span("load-1", start, 50, 150),
+        ];
+        assert!(agents_overlap(spans, 2));
+
+        // 发往同一Agent（2 % 2 == 0）的重叠请求不算
+        let spans = vec![
+            span("load-0", start, 0, 100),
+            span("load-2", start, 50, 150),
+            span("load-1", start, 200, 300),
+        ];
+        assert!(!agents_overlap(spans, 2));
+
+        // 预热请求不参与判断
+        let spans = vec![span("预热", start, 0, 100), span("load-1", start, 50, 150)];
+        assert!(!agents_overlap(spans, 2));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 0.5), Duration::from_millis(50));
+        assert_eq!(percentile(&sorted, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 0.99), Duration::ZERO);
+    }
+}
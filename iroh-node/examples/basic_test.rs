@@ -4,7 +4,7 @@
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use iroh::node::Node;
+use iroh::{Endpoint, Watcher};
 use tracing::info;
 
 #[derive(Parser)]
@@ -39,25 +39,26 @@ async fn main() -> Result<()> {
 
     info!("启动基本 Iroh 节点测试...");
 
-    // 创建 Iroh 节点
-    let node = Node::memory().spawn().await?;
-    let node_id = node.node_id();
+    // 创建 Iroh 端点
+    let endpoint = Endpoint::builder().discovery_n0().bind().await?;
+    let node_id = endpoint.node_id();
 
     match cli.command {
         Commands::Start => {
             info!("节点已启动");
             info!("节点 ID: {}", node_id);
-            info!("节点地址: {:?}", node.my_addr().await?);
+            info!("节点地址: {:?}", endpoint.node_addr().initialized().await);
             
             // 保持节点运行
             println!("节点正在运行，按 Ctrl+C 退出...");
             tokio::signal::ctrl_c().await?;
             println!("正在关闭节点...");
+            endpoint.close().await;
         }
         Commands::Info => {
             info!("节点信息:");
             info!("  节点 ID: {}", node_id);
-            info!("  节点地址: {:?}", node.my_addr().await?);
+            info!("  节点地址: {:?}", endpoint.node_addr().initialized().await);
         }
     }
 
@@ -6,10 +6,17 @@ use anyhow::Result;
 use bytes::Bytes;
 use clap::{Parser, Subcommand};
 use futures_lite::StreamExt;
-use iroh::node::Node;
-use iroh_gossip::proto::TopicId;
+use iroh::{protocol::Router, Endpoint, NodeId};
+use iroh_gossip::{
+    api::Event,
+    net::{Gossip, GOSSIP_ALPN},
+    proto::TopicId,
+};
 use serde::{Deserialize, Serialize};
-use std::io::{self, BufRead};
+use std::{
+    io::{self, BufRead},
+    str::FromStr,
+};
 use tokio::sync::mpsc;
 use tracing::{error, info};
 
@@ -33,6 +40,8 @@ enum Commands {
     Join {
         /// 主题ID (hex格式)
         topic: String,
+        /// 创建聊天室的节点ID
+        peer: NodeId,
     },
 }
 
@@ -77,56 +86,54 @@ async fn main() -> Result<()> {
 
     info!("启动最小化聊天应用...");
 
-    // 创建节点
-    let node = Node::memory().spawn().await?;
-    let node_id = node.node_id();
+    // 创建端点，通过 n0 的 DNS 服务按节点ID发现对方地址
+    let endpoint = Endpoint::builder().discovery_n0().bind().await?;
+    let node_id = endpoint.node_id();
     info!("节点 ID: {}", node_id);
 
+    let gossip = Gossip::builder().spawn(endpoint.clone());
+    let router = Router::builder(endpoint.clone())
+        .accept(GOSSIP_ALPN, gossip.clone())
+        .spawn();
+
     match cli.command {
         Commands::Create => {
-            create_chat_room(&node, cli.name).await?;
+            create_chat_room(&gossip, node_id, cli.name).await?;
         }
-        Commands::Join { topic } => {
-            join_chat_room(&node, cli.name, topic).await?;
+        Commands::Join { topic, peer } => {
+            join_chat_room(&gossip, cli.name, topic, peer).await?;
         }
     }
 
+    router.shutdown().await?;
     Ok(())
 }
 
-async fn create_chat_room(node: &Node<iroh::blobs::store::mem::Store>, username: String) -> Result<()> {
+async fn create_chat_room(gossip: &Gossip, node_id: NodeId, username: String) -> Result<()> {
     // 创建固定的主题ID用于测试
     let topic_id = TopicId::from([1u8; 32]);
-    
+
     println!("=== 聊天室已创建 ===");
-    println!("主题ID: {}", hex::encode(topic_id.as_bytes()));
+    println!("主题ID: {}", topic_id);
     println!("其他人可以使用以下命令加入:");
-    println!("cargo run --example minimal_chat -- join {}", hex::encode(topic_id.as_bytes()));
+    println!("cargo run --example minimal_chat -- join {} {}", topic_id, node_id);
     println!();
 
-    start_chat(node, topic_id, username).await
+    start_chat(gossip, topic_id, vec![], username).await
 }
 
-async fn join_chat_room(node: &Node<iroh::blobs::store::mem::Store>, username: String, topic_hex: String) -> Result<()> {
-    let topic_bytes = hex::decode(topic_hex)?;
-    if topic_bytes.len() != 32 {
-        return Err(anyhow::anyhow!("无效的主题ID长度"));
-    }
-
-    let mut topic_array = [0u8; 32];
-    topic_array.copy_from_slice(&topic_bytes);
-    let topic_id = TopicId::from(topic_array);
+async fn join_chat_room(gossip: &Gossip, username: String, topic_hex: String, peer: NodeId) -> Result<()> {
+    let topic_id = TopicId::from_str(&topic_hex).map_err(|e| anyhow::anyhow!("无效的主题ID: {}", e))?;
 
     println!("=== 加入聊天室 ===");
-    println!("主题ID: {}", hex::encode(topic_id.as_bytes()));
+    println!("主题ID: {}", topic_id);
     println!();
 
-    start_chat(node, topic_id, username).await
+    start_chat(gossip, topic_id, vec![peer], username).await
 }
 
-async fn start_chat(node: &Node<iroh::blobs::store::mem::Store>, topic_id: TopicId, username: String) -> Result<()> {
-    let gossip = node.gossip();
-    let (mut sink, mut stream) = gossip.subscribe(topic_id).await?;
+async fn start_chat(gossip: &Gossip, topic_id: TopicId, peers: Vec<NodeId>, username: String) -> Result<()> {
+    let (sink, mut stream) = gossip.subscribe(topic_id, peers).await?.split();
 
     println!("聊天开始！输入消息并按回车发送，输入 'quit' 退出");
 
@@ -157,7 +164,7 @@ async fn start_chat(node: &Node<iroh::blobs::store::mem::Store>, topic_id: Topic
     let recv_handle = tokio::spawn(async move {
         while let Some(event) = stream.next().await {
             match event {
-                Ok(iroh_gossip::net::Event::Received(msg)) => {
+                Ok(Event::Received(msg)) => {
                     match ChatMessage::from_bytes(&msg.content) {
                         Ok(chat_msg) => {
                             println!("[{}] {}", chat_msg.user, chat_msg.content);
@@ -167,12 +174,15 @@ async fn start_chat(node: &Node<iroh::blobs::store::mem::Store>, topic_id: Topic
                         }
                     }
                 }
-                Ok(iroh_gossip::net::Event::Joined(peer)) => {
+                Ok(Event::NeighborUp(peer)) => {
                     info!("节点加入: {}", peer);
                 }
-                Ok(iroh_gossip::net::Event::Left(peer)) => {
+                Ok(Event::NeighborDown(peer)) => {
                     info!("节点离开: {}", peer);
                 }
+                Ok(Event::Lagged) => {
+                    error!("接收过慢，丢失了部分消息");
+                }
                 Err(e) => {
                     error!("接收事件失败: {}", e);
                 }
@@ -3,9 +3,10 @@
 //! 这个示例展示了如何使用 iroh-node 创建一个基本的点对点聊天应用
 
 use anyhow::Result;
-use iroh_node::{IrohChatClient, ChatConfig, ChatEvent};
+use iroh_gossip::proto::TopicId;
+use iroh_node::{NodeConfig, NodeEvent, P2PNode};
 use tokio::io::{self, AsyncBufReadExt, BufReader};
-use tracing::{info, error};
+use tracing::error;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -26,19 +27,19 @@ async fn main() -> Result<()> {
     reader.read_line(&mut input).await?;
     let input = input.trim();
 
-    let config = ChatConfig::default();
-    let mut client = IrohChatClient::new(config).await?;
+    let node = P2PNode::new(NodeConfig::default()).await?;
+    node.start().await?;
 
     if input == "create" {
         // 创建聊天室
-        let room = client.create_room("默认聊天室".to_string()).await?;
+        let (topic_id, ticket) = node.join_topic(None, None).await?;
         println!("聊天室已创建！");
-        println!("邀请码: {}", room.invite_code);
+        println!("邀请码: {}", ticket);
         println!("请分享此邀请码给其他人");
-        
+
         // 开始聊天循环
-        start_chat_loop(&mut client, room.room_id).await?;
-        
+        start_chat_loop(&node, topic_id).await?;
+
     } else if input.starts_with("join ") {
         // 加入聊天室
         let invite_code = input.strip_prefix("join ").unwrap_or("");
@@ -47,36 +48,37 @@ async fn main() -> Result<()> {
             return Ok(());
         }
         
-        let room = client.join_room(invite_code.to_string()).await?;
-        println!("已加入聊天室: {}", room.name);
-        
+        let (topic_id, _) = node.join_topic(None, Some(invite_code)).await?;
+        println!("已加入聊天室: {}", topic_id);
+
         // 开始聊天循环
-        start_chat_loop(&mut client, room.room_id).await?;
-        
+        start_chat_loop(&node, topic_id).await?;
+
     } else {
         println!("无效的输入，请重新运行程序");
     }
 
+    node.stop().await?;
     Ok(())
 }
 
-async fn start_chat_loop(client: &mut IrohChatClient, room_id: String) -> Result<()> {
+async fn start_chat_loop(node: &P2PNode, topic_id: TopicId) -> Result<()> {
     println!("\n=== 聊天开始 ===");
     println!("输入消息并按回车发送，输入 'quit' 退出");
     
     // 启动事件监听任务
-    let mut event_receiver = client.subscribe_events().await?;
+    let mut event_receiver = node.subscribe_events();
     tokio::spawn(async move {
         while let Ok(event) = event_receiver.recv().await {
             match event {
-                ChatEvent::MessageReceived { user, message, .. } => {
-                    println!("[{}] {}", user.name, message.content);
+                NodeEvent::ChatReceived { entry, .. } => {
+                    println!("[{}] {}", entry.from, entry.text);
                 }
-                ChatEvent::UserJoined { user } => {
-                    println!(">>> {} 加入了聊天室", user.name);
+                NodeEvent::NeighborUp { peer_id, .. } => {
+                    println!(">>> {} 加入了聊天室", peer_id);
                 }
-                ChatEvent::UserLeft { user } => {
-                    println!(">>> {} 离开了聊天室", user.name);
+                NodeEvent::NeighborDown { peer_id, .. } => {
+                    println!(">>> {} 离开了聊天室", peer_id);
                 }
                 _ => {}
             }
@@ -100,13 +102,13 @@ async fn start_chat_loop(client: &mut IrohChatClient, room_id: String) -> Result
         }
 
         if !message.is_empty() {
-            if let Err(e) = client.send_message(room_id.clone(), message.to_string()).await {
+            if let Err(e) = node.send_chat(&topic_id, message).await {
                 error!("发送消息失败: {}", e);
             }
         }
     }
 
     println!("退出聊天室...");
-    client.leave_room(room_id).await?;
+    node.leave_topic(&topic_id).await?;
     Ok(())
 }
\ No newline at end of file
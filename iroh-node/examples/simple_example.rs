@@ -8,22 +8,17 @@ use bytes::Bytes;
 use clap::Parser;
 use ed25519_dalek::Signature;
 use futures_lite::StreamExt;
-use iroh::{Endpoint, NodeAddr, PublicKey, RelayMode, RelayUrl, SecretKey};
+use iroh::{Endpoint, NodeAddr, PublicKey, RelayMode, RelayUrl, SecretKey, Watcher};
 use iroh_gossip::{
     api::{Event, GossipReceiver, GossipSender},
     net::{Gossip, GOSSIP_ALPN},
     proto::TopicId,
 };
-use postcard;
-use rig_agent::{
-    core::{
-        agent::{AgentManager, ClientRegistry},
-        types::{AgentConfig, AgentMessage},
-    },
-    error::AgentResult,
+use rig_agent::core::{
+    agent::{AgentManager, ClientRegistry},
+    types::AgentConfig,
 };
 use serde::{Deserialize, Serialize};
-use tracing::info;
 
 /// 简化的iroh-gossip通信示例
 ///
@@ -102,7 +97,7 @@ impl SignedMessage {
 #[derive(Debug, Serialize, Deserialize)]
 enum Message {
     AboutMe { name: String },
-    Message { text: String },
+    Text { text: String },
     AgentRequest { query: String },
     AgentResponse { query: String, response: String },
 }
@@ -172,7 +167,7 @@ async fn handle_message(
             names.insert(from, name.clone());
             println!("> {} 现在被称为 {}", from.fmt_short(), name);
         }
-        Message::Message { text } => {
+        Message::Text { text } => {
             let name = names
                 .get(&from)
                 .map_or_else(|| from.fmt_short(), String::to_string);
@@ -293,7 +288,7 @@ async fn main() -> Result<(), anyhow::Error> {
 
     // 解析或生成密钥
     let secret_key = match args.secret_key {
-        None => SecretKey::generate(&mut rand::rngs::OsRng),
+        None => SecretKey::from_bytes(&rand::random()),
         Some(key) => key.parse()?,
     };
     println!(
@@ -371,8 +366,8 @@ async fn main() -> Result<(), anyhow::Error> {
         receiver,
         sender.clone(),
         secret_key.clone(),
-        agent_manager.clone(),
-        registry.clone(),
+        agent_manager,
+        registry,
     ));
 
     // 生成一个输入线程，读取stdin
@@ -387,8 +382,8 @@ async fn main() -> Result<(), anyhow::Error> {
         }
 
         // 检查是否是代理请求
-        if text.starts_with("/agent ") {
-            let query = text[7..].trim().to_string();
+        if let Some(query) = text.strip_prefix("/agent ") {
+            let query = query.trim().to_string();
             if !query.is_empty() {
                 let message = Message::AgentRequest { query: query.clone() };
                 let encoded_message = SignedMessage::sign_and_encode(&secret_key, &message)?;
@@ -397,7 +392,7 @@ async fn main() -> Result<(), anyhow::Error> {
             }
         } else {
             // 普通消息
-            let message = Message::Message { text: text.clone() };
+            let message = Message::Text { text: text.clone() };
             let encoded_message = SignedMessage::sign_and_encode(&secret_key, &message)?;
             sender.broadcast(encoded_message).await?;
             println!("> 已发送: {}", text);
@@ -1,9 +1,7 @@
-//! 独立使用iroh传输模块的示例
+//! 独立使用P2P节点的示例，不依赖tauri或axum
 
-use iroh_node::{
-    simple_api, ConfigBuilder, StandaloneAdapter, TransferEvent, UploadRequest, DownloadRequest,
-};
-use std::path::Path;
+use iroh_node::{NodeConfig, P2PNode};
+use std::time::Duration;
 use tracing::{info, Level};
 
 #[tokio::main]
@@ -13,72 +11,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_max_level(Level::INFO)
         .init();
 
-    info!("开始iroh P2P文件传输示例");
+    info!("开始iroh P2P节点独立运行示例");
+
+    // 示例1: 创建并启动节点
+    let config = NodeConfig::new().with_name(Some("独立节点".to_string()));
+    let node = P2PNode::new(config).await?;
+    node.start().await?;
+    info!("节点已启动，节点ID: {}", node.node_id());
+
+    // 示例2: 创建聊天室并生成邀请票据
+    let (topic_id, ticket) = node.join_topic(None, None).await?;
+    info!("聊天室已创建: {}", topic_id);
+    info!("邀请票据: {}", ticket);
+
+    // 示例3: 同步本地文件夹，其他成员同步同名文件夹即可收到其中的文件
+    let share_dir = std::env::temp_dir().join("iroh_example_share");
+    std::fs::create_dir_all(&share_dir)?;
+    std::fs::write(share_dir.join("test.txt"), "这是一个测试文件内容")?;
+
+    let files = node.start_folder_sync(&topic_id, "example", &share_dir).await?;
+    for file in &files {
+        info!("同步文件: {} ({}字节) - {:?}", file.name, file.size, file.state);
+    }
+
+    // 示例4: 发送消息并读取聊天记录
+    node.send_chat(&topic_id, "你好，这是独立节点发出的消息").await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    for entry in node.get_chat_history(&topic_id, Some(10)).await {
+        info!("聊天记录: {} - {}", entry.from, entry.text);
+    }
+
+    // 清理
+    node.stop_folder_sync(&topic_id, "example").await?;
+    node.leave_topic(&topic_id).await?;
+    node.stop().await?;
+    std::fs::remove_dir_all(&share_dir)?;
 
-    // 示例1: 使用简单API上传文件
-    let file_path = Path::new("test.txt");
-    
-    // 创建测试文件
-    std::fs::write(file_path, "这是一个测试文件内容")?;
-    
-    info!("上传文件: {:?}", file_path);
-    let share_response = simple_api::upload_file(file_path, None).await?;
-    info!("文件上传成功，分享代码: {}", share_response.doc_ticket);
-
-    // 示例2: 使用带进度回调的上传
-    let progress_callback = |event: TransferEvent| {
-        match event {
-            TransferEvent::UploadQueueAppend { id, size, title } => {
-                println!("开始上传: {} ({}字节) - {}", title, size, id);
-            }
-            TransferEvent::UploadProgress { id, offset } => {
-                println!("上传进度: {} - {}字节", id, offset);
-            }
-            TransferEvent::UploadDone { id } => {
-                println!("上传完成: {}", id);
-            }
-            _ => {}
-        }
-    };
-
-    info!("带进度回调的文件上传");
-    let share_response2 = simple_api::upload_file_with_progress(
-        file_path,
-        None,
-        progress_callback,
-    ).await?;
-    info!("带进度的文件上传成功，分享代码: {}", share_response2.doc_ticket);
-
-    // 示例3: 使用StandaloneAdapter进行更复杂的操作
-    let config = ConfigBuilder::new()
-        .data_root("/tmp/iroh_example")
-        .download_dir(Some("/tmp/downloads"))
-        .verbose_logging(true)
-        .build();
-
-    let adapter = StandaloneAdapter::new(config).await?;
-
-    // 上传文件
-    let upload_request = UploadRequest {
-        file_path: file_path.to_path_buf(),
-    };
-    
-    adapter.upload_file(upload_request).await?;
-    let share_code = adapter.get_share_code().await?;
-    info!("使用适配器上传成功，分享代码: {}", share_code.doc_ticket);
-
-    // 下载文件
-    let download_request = DownloadRequest {
-        doc_ticket: share_code.doc_ticket,
-        download_dir: Some(Path::new("/tmp/downloads").to_path_buf()),
-    };
-
-    let download_result = adapter.download_files(download_request).await?;
-    info!("文件下载完成: {}", download_result);
-
-    // 清理测试文件
-    std::fs::remove_file(file_path)?;
-    
     info!("示例执行完成");
     Ok(())
-}
\ No newline at end of file
+}
@@ -7,7 +7,7 @@ use std::{
 use bytes::Bytes;
 use ed25519_dalek::Signature;
 use futures_lite::StreamExt;
-use iroh::{Endpoint, NodeAddr, PublicKey, RelayMode, SecretKey};
+use iroh::{protocol::Router, Endpoint, NodeAddr, PublicKey, RelayMode, SecretKey, Watcher};
 use iroh_gossip::{
     api::{Event, GossipReceiver, GossipSender},
     net::{Gossip, GOSSIP_ALPN},
@@ -96,9 +96,11 @@ pub struct P2PState {
     /// 消息发送通道
     message_tx: mpsc::Sender<P2PMessage>,
     /// 代理管理器
-    agent_manager: AgentManager,
+    agent_manager: Arc<AgentManager>,
     /// 客户端注册表
-    registry: ClientRegistry,
+    registry: Arc<ClientRegistry>,
+    /// 路由器，丢弃后不再接受连接
+    router: Option<Router>,
 }
 
 impl P2PState {
@@ -109,7 +111,7 @@ impl P2PState {
         message_tx: mpsc::Sender<P2PMessage>,
     ) -> Self {
         // 使用提供的密钥或生成新密钥
-        let secret_key = secret_key.unwrap_or_else(|| SecretKey::generate(&mut rand::rngs::OsRng));
+        let secret_key = secret_key.unwrap_or_else(|| SecretKey::from_bytes(&rand::random()));
         let node_id = secret_key.public().fmt_short().to_string();
         
         // 使用提供的主题或生成新主题
@@ -117,8 +119,8 @@ impl P2PState {
         
         // 初始化代理
         let config = AgentConfig::default();
-        let agent_manager = AgentManager::new(config);
-        let registry = ClientRegistry::new();
+        let agent_manager = Arc::new(AgentManager::new(config));
+        let registry = Arc::new(ClientRegistry::new());
         
         Self {
             node_id,
@@ -129,6 +131,7 @@ impl P2PState {
             message_tx,
             agent_manager,
             registry,
+            router: None,
         }
     }
     
@@ -157,12 +160,14 @@ impl P2PState {
         };
         
         // 设置路由器
-        let router = iroh::protocol::Router::builder(endpoint.clone())
+        let router = Router::builder(endpoint.clone())
             .accept(GOSSIP_ALPN, gossip.clone())
             .spawn();
             
-        // 加入gossip主题
-        let (sender, receiver) = gossip.subscribe_and_join(self.topic_id, vec![]).await?.split();
+        self.router = Some(router);
+
+        // 创建聊天室时还没有其他节点，不等待邻居加入
+        let (sender, receiver) = gossip.subscribe(self.topic_id, vec![]).await?.split();
         self.sender = Some(sender.clone());
         
         // 创建默认agent
@@ -209,7 +214,7 @@ impl P2PState {
         let gossip = Gossip::builder().spawn(endpoint.clone());
         
         // 设置路由器
-        let router = iroh::protocol::Router::builder(endpoint.clone())
+        let router = Router::builder(endpoint.clone())
             .accept(GOSSIP_ALPN, gossip.clone())
             .spawn();
             
@@ -219,6 +224,8 @@ impl P2PState {
             endpoint.add_node_addr(peer)?;
         }
         
+        self.router = Some(router);
+
         // 加入gossip主题
         let (sender, receiver) = gossip.subscribe_and_join(self.topic_id, peer_ids).await?.split();
         self.sender = Some(sender.clone());
@@ -340,8 +347,8 @@ async fn subscribe_loop(
     secret_key: SecretKey,
     message_tx: mpsc::Sender<P2PMessage>,
     state: Arc<RwLock<EssentialState>>,
-    agent_manager: AgentManager,
-    registry: ClientRegistry,
+    agent_manager: Arc<AgentManager>,
+    registry: Arc<ClientRegistry>,
 ) -> Result<(), anyhow::Error> {
     while let Some(event) = receiver.try_next().await? {
         if let Event::Received(msg) = event {
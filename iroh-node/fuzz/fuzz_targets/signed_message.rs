@@ -0,0 +1,9 @@
+//! 模糊测试：解码来自网络的签名消息
+#![no_main]
+
+use iroh_node::SignedMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = SignedMessage::verify_and_decode(data);
+});
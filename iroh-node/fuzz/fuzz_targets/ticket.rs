@@ -0,0 +1,13 @@
+//! 模糊测试：解析用户粘贴的票据
+#![no_main]
+
+use std::str::FromStr;
+
+use iroh_node::Ticket;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = Ticket::from_str(text);
+    }
+});
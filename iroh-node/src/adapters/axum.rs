@@ -2,26 +2,67 @@
 //!
 //! 提供Axum适配器，用于在Axum应用中集成P2P节点
 
-use std::sync::Arc;
+use std::{convert::Infallible, sync::Arc, time::Duration};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::{get, post},
-    Json, Router,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    middleware,
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
 };
-use iroh_gossip::proto::topic::TopicId;
+use iroh_gossip::proto::TopicId;
+use futures_lite::Stream;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
-use tracing::{error, info};
+use tokio::sync::{broadcast::error::RecvError, RwLock};
+use chrono::{DateTime, Utc};
+use rig_agent::{
+    core::{ListPage, TokenUsage},
+    feedback_to_jsonl, AgentPackage, AgentResponse, AgentSortKey, AgentSummary, ChatOptions, DocumentSummary,
+    EvalReport, EvalSuite, FeedbackRating, FeedbackRecord, FinishReason, PageRequest, PurgeReport,
+    RequestContext, ResponseFeedback, ResponseWarning, SortOrder, SummarizeOptions,
+    TranslateRequest, Translation, PACKAGE_EXTENSION,
+};
+use tracing::{debug, info, warn};
 
-use crate::{MessageType, NodeConfig, NodeError, NodeResult, NodeStatus, P2PNode};
+use super::files::{FileShare, FileShareConfig};
+use super::progress::ProgressRegistry;
+use super::request_context::attach_request_context;
+use super::request_guard::{guard_requests, RequestGuard, RequestGuardConfig};
+use super::upload::UploadManager;
+use super::watch_folder::FolderWatcher;
+use crate::{
+    agents_to_csv, logs_to_text, memory::DEFAULT_RECALL_LIMIT, series_to_csv, AgentRequestMode,
+    AgentRequestOutcome, ChatHistoryEntry, CommandOutcome, CommandRegistry, EnsembleOptions, EnsembleOutcome, FileSyncStatus, Invite, InviteKind, LogBuffer, LogEntry,
+    LogQuery, MemoryEntry, MemoryMatch, MentionRecord, MentionSummary, MessageContent, NodeConfig, NodeError, NodeEvent, NodeResult, OutboxEntry, P2PNode,
+    PeerInfo, PeerRecord, Reactions, RemoteTemplate, RoomBotConfig, RoomLiveness, SafetyNumber, StatsWindow,
+    TemplateAnnouncement, TopicStats, TrustLevel, UnreadSummary, UsageSummary,
+};
 
 /// Axum适配器
 pub struct AxumAdapter {
     /// P2P节点
     node: Arc<RwLock<Option<P2PNode>>>,
+    /// 请求签名与幂等
+    guard: RequestGuard,
+    /// 共享文件下载
+    files: Option<Arc<FileShare>>,
+    /// 传输进度推送
+    progress: Option<Arc<ProgressRegistry>>,
+    /// 分块上传
+    uploads: Option<Arc<UploadManager>>,
+    /// 监视文件夹自动共享
+    watcher: Option<Arc<FolderWatcher>>,
+    /// 浏览器WebRTC网关
+    #[cfg(feature = "webrtc-bridge")]
+    webrtc_bridge: Option<Arc<super::webrtc_bridge::WebRtcBridge>>,
+    /// 聊天消息的链接预览
+    #[cfg(feature = "link-preview")]
+    link_previews: Option<Arc<super::link_preview::LinkPreviewer>>,
 }
 
 /// 节点状态响应
@@ -39,6 +80,8 @@ pub struct NodeStatusResponse {
     pub last_activity: String,
     /// 中继模式
     pub relay_mode: String,
+    /// 当前主中继服务器
+    pub home_relay: Option<String>,
 }
 
 /// 话题响应
@@ -50,6 +93,65 @@ pub struct TopicResponse {
     pub ticket: String,
 }
 
+/// 话题信息响应
+#[derive(Debug, Serialize)]
+pub struct TopicInfoResponse {
+    /// 话题ID
+    pub topic_id: String,
+    /// 票据
+    pub ticket: String,
+    /// 话题统计
+    pub stats: TopicStats,
+}
+
+/// 导出配置包请求
+#[derive(Debug, Deserialize)]
+pub struct ExportBundleRequest {
+    /// 加密口令
+    pub passphrase: String,
+}
+
+/// 导出配置包响应
+#[derive(Debug, Serialize)]
+pub struct ExportBundleResponse {
+    /// 加密后的配置包
+    pub bundle: String,
+}
+
+/// 导出房间聊天记录密钥响应
+#[derive(Debug, Serialize)]
+pub struct ExportRoomKeyResponse {
+    /// 用口令加密的房间密钥
+    pub key: String,
+}
+
+/// 导入房间聊天记录密钥请求
+#[derive(Debug, Deserialize)]
+pub struct ImportRoomKeyRequest {
+    /// 导出的房间密钥
+    pub key: String,
+    /// 导出时使用的口令
+    pub passphrase: String,
+}
+
+/// 导入房间聊天记录密钥响应
+#[derive(Debug, Serialize)]
+pub struct ImportRoomKeyResponse {
+    /// 恢复或解锁的消息条数
+    pub restored: usize,
+}
+
+/// 导入配置包请求
+#[derive(Debug, Deserialize)]
+pub struct ImportBundleRequest {
+    /// 加密后的配置包
+    pub bundle: String,
+    /// 加密口令
+    pub passphrase: String,
+    /// 绑定端口
+    pub bind_port: Option<u16>,
+}
+
 /// 初始化请求
 #[derive(Debug, Deserialize)]
 pub struct InitRequest {
@@ -59,6 +161,10 @@ pub struct InitRequest {
     pub relay: Option<String>,
     /// 禁用中继
     pub no_relay: Option<bool>,
+    /// 代理URL，例如Tor的 `socks5h://127.0.0.1:9050`
+    pub proxy: Option<String>,
+    /// 隐私模式：只经中继通信，不公开本地地址
+    pub privacy_mode: Option<bool>,
     /// 节点名称
     pub name: Option<String>,
     /// 绑定端口
@@ -79,11 +185,244 @@ pub struct JoinTopicRequest {
     pub ticket: String,
 }
 
+/// 设置节点信任级别请求
+#[derive(Debug, Deserialize)]
+pub struct SetPeerTrustRequest {
+    /// 信任级别
+    pub trust: TrustLevel,
+    /// 节点名称
+    pub name: Option<String>,
+}
+
+/// 确认身份验证请求
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfirmVerificationRequest {
+    /// 在该话题中通知对方，不指定时只更新本地地址簿
+    pub topic_id: Option<String>,
+}
+
+/// 扫描验证二维码请求
+#[derive(Debug, Deserialize)]
+pub struct ScanVerificationRequest {
+    /// 二维码内容
+    pub payload: String,
+    /// 在该话题中通知对方
+    pub topic_id: Option<String>,
+}
+
+/// 邀请请求，也用作二维码接口的查询参数
+#[derive(Debug, Deserialize)]
+pub struct InviteRequest {
+    /// 邀请类型
+    pub kind: InviteKind,
+    /// 票据
+    pub ticket: String,
+    /// 房间或分享的名称
+    pub name: Option<String>,
+}
+
+/// 邀请响应
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    /// 邀请链接
+    pub url: String,
+}
+
+/// 通过邀请链接加入请求
+#[derive(Debug, Deserialize)]
+pub struct JoinInviteRequest {
+    /// 邀请链接
+    pub url: String,
+}
+
 /// 消息请求
 #[derive(Debug, Deserialize)]
 pub struct MessageRequest {
-    /// 消息内容
+    /// 消息内容，设置结构化内容时忽略
+    #[serde(default)]
     pub message: String,
+    /// 结构化内容（Markdown、代码块），设置时不按命令解析
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+    /// 网页客户端的显示名称，经网关转发时作为署名加在消息前
+    #[serde(default)]
+    pub sender: Option<String>,
+    /// 被回复的消息ID，设置时作为回复发送，不按命令解析
+    #[serde(default)]
+    pub reply_to: Option<String>,
+}
+
+/// 表情回应请求
+#[derive(Debug, Deserialize)]
+pub struct ReactionRequest {
+    /// 表情
+    pub emoji: String,
+    /// 是否撤销回应
+    #[serde(default)]
+    pub removed: bool,
+}
+
+/// 表情回应响应
+#[derive(Debug, Serialize)]
+pub struct ReactionResponse {
+    /// 消息当前的全部回应
+    pub reactions: Reactions,
+}
+
+/// 发送消息响应
+#[derive(Debug, Serialize)]
+pub struct SendMessageResponse {
+    /// 消息ID，可用于跟踪投递状态；执行的命令没有发送聊天消息时为空
+    pub message_id: Option<String>,
+    /// 聊天命令的执行结果
+    pub outcome: CommandOutcome,
+}
+
+/// 历史记录查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryQuery {
+    /// 跳过的条数
+    pub offset: usize,
+    /// 返回的条数，超过服务端上限时返回413
+    pub limit: Option<usize>,
+    /// 只包含该时间及之后的消息
+    pub since: Option<DateTime<Utc>>,
+    /// 只包含该时间之前的消息
+    pub until: Option<DateTime<Utc>>,
+    /// 排序方向
+    pub order: SortOrder,
+    /// 只返回摘要（各类消息数量与最近几条消息）
+    pub summary: bool,
+}
+
+impl HistoryQuery {
+    fn page_request(&self) -> PageRequest {
+        PageRequest {
+            offset: self.offset,
+            limit: self.limit,
+            since: self.since,
+            until: self.until,
+            order: self.order,
+        }
+    }
+}
+
+/// Agent列表查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AgentListQuery {
+    /// 跳过的条数
+    pub offset: usize,
+    /// 返回的条数
+    pub limit: Option<usize>,
+    /// 只包含该时间及之后的Agent
+    pub since: Option<DateTime<Utc>>,
+    /// 只包含该时间之前的Agent
+    pub until: Option<DateTime<Utc>>,
+    /// 排序方向
+    pub order: SortOrder,
+    /// 排序字段
+    pub sort_by: AgentSortKey,
+}
+
+impl AgentListQuery {
+    fn page_request(&self) -> PageRequest {
+        PageRequest {
+            offset: self.offset,
+            limit: self.limit,
+            since: self.since,
+            until: self.until,
+            order: self.order,
+        }
+    }
+}
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// JSON
+    #[default]
+    Json,
+    /// CSV
+    Csv,
+}
+
+/// 用量统计查询参数
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// 时间窗口，默认按小时
+    #[serde(default)]
+    pub window: StatsWindow,
+    /// 返回最近的窗口数，默认按小时为24、按天为30
+    pub limit: Option<usize>,
+    /// 导出格式，默认JSON
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// 房间活跃度查询参数
+#[derive(Debug, Deserialize)]
+pub struct LivenessQuery {
+    /// 统计的时间窗口（秒），默认使用节点配置
+    pub window_secs: Option<u64>,
+}
+
+/// 提及列表查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MentionQuery {
+    /// 只包含该话题中的提及
+    pub topic_id: Option<String>,
+    /// 只包含未读的提及
+    pub unread: bool,
+}
+
+/// 提及标记已读请求，不指定话题时标记全部，不指定消息时标记话题中的全部
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MentionReadRequest {
+    /// 话题ID
+    pub topic_id: Option<String>,
+    /// 消息ID
+    pub message_id: Option<String>,
+}
+
+/// 提及标记已读响应
+#[derive(Debug, Serialize)]
+pub struct MentionReadResponse {
+    /// 新标记为已读的条数
+    pub marked: usize,
+}
+
+/// 房间标记已读查询参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RoomReadQuery {
+    /// 标记到该消息为止，默认标记到最新一条收到的消息
+    pub message_id: Option<String>,
+}
+
+/// 房间标记已读响应
+#[derive(Debug, Serialize)]
+pub struct RoomReadResponse {
+    /// 新标记为已读的消息数
+    pub marked: usize,
+}
+
+/// 房间机器人退出请求
+#[derive(Debug, Deserialize)]
+pub struct BotOptOutRequest {
+    /// 成员节点ID
+    pub member: String,
+    /// 是否不让机器人回复该成员，默认为是
+    #[serde(default = "default_opted_out")]
+    pub opted_out: bool,
+}
+
+fn default_opted_out() -> bool {
+    true
 }
 
 /// Agent请求
@@ -93,6 +432,193 @@ pub struct AgentRequest {
     pub agent_id: String,
     /// 提示词
     pub prompt: String,
+    /// 寻址方式，默认由选举出的一个节点回答
+    #[serde(default)]
+    pub mode: AgentRequestMode,
+    /// 等待应答的毫秒数
+    pub timeout_ms: Option<u64>,
+    /// 是否等待请求完成后再返回；不等待时结果通过事件流推送
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// 写入共享记忆请求
+#[derive(Debug, Deserialize)]
+pub struct RememberRequest {
+    /// 记忆内容
+    pub text: String,
+    /// 来源，例如聊天消息ID
+    pub source: Option<String>,
+}
+
+/// 检索共享记忆请求
+#[derive(Debug, Deserialize)]
+pub struct RecallRequest {
+    /// 查询文本
+    pub query: String,
+    /// 返回条数
+    pub limit: Option<usize>,
+}
+
+/// 开始同步文件夹请求
+#[derive(Debug, Deserialize)]
+pub struct StartFolderSyncRequest {
+    /// 同步文件夹名称，话题中其他成员使用同名文件夹
+    pub name: String,
+    /// 本地目录
+    pub path: std::path::PathBuf,
+}
+
+/// 确认冲突请求
+#[derive(Debug, Deserialize)]
+pub struct ResolveConflictRequest {
+    /// 文件名
+    pub file: String,
+}
+
+/// 集成回答请求
+#[derive(Debug, Deserialize)]
+pub struct EnsembleRequest {
+    /// Agent ID
+    pub agent_id: String,
+    /// 提示词
+    pub prompt: String,
+    /// 集成选项
+    #[serde(flatten)]
+    pub options: EnsembleOptions,
+}
+
+/// 回复反馈请求
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    /// 点赞或点踩
+    #[serde(default)]
+    pub rating: Option<FeedbackRating>,
+    /// 文字意见
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// 反馈导出格式
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackFormat {
+    /// JSON数组
+    #[default]
+    Json,
+    /// JSON Lines，每行一组问答，用于整理微调数据集
+    Jsonl,
+}
+
+/// 反馈导出查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct FeedbackQuery {
+    /// 导出格式
+    #[serde(default)]
+    pub format: FeedbackFormat,
+}
+
+/// Agent包导入查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct ImportPackageQuery {
+    /// 新Agent的ID，未指定时使用包名称
+    #[serde(default)]
+    pub agent_id: Option<String>,
+}
+
+/// Agent包导入响应
+#[derive(Debug, Serialize)]
+pub struct ImportPackageResponse {
+    /// 新Agent的ID
+    pub agent_id: String,
+}
+
+/// 分享Agent模板请求
+#[derive(Debug, Deserialize)]
+pub struct ShareTemplateRequest {
+    /// Agent ID
+    pub agent_id: String,
+    /// 模板描述
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// 加入模板交换响应
+#[derive(Debug, Serialize)]
+pub struct TemplateExchangeResponse {
+    /// 发现话题ID
+    pub topic_id: String,
+}
+
+/// 评测请求
+#[derive(Debug, Deserialize)]
+pub struct EvalRequest {
+    /// 测试集
+    pub suite: EvalSuite,
+    /// 被测Agent，为空时使用测试集中的默认Agent
+    #[serde(default)]
+    pub agents: Vec<String>,
+}
+
+/// 文档摘要请求
+#[derive(Debug, Deserialize)]
+pub struct SummarizeRequest {
+    /// 文档内容
+    pub text: String,
+    /// 摘要选项（块大小、提供商、模型、标题）
+    #[serde(flatten)]
+    pub options: SummarizeOptions,
+}
+
+/// Agent请求响应
+#[derive(Debug, Serialize)]
+pub struct AgentRequestResponse {
+    /// 请求ID
+    pub request_id: String,
+    /// 请求结果，仅在等待时返回
+    pub outcome: Option<AgentRequestOutcome>,
+}
+
+/// 流式聊天请求
+#[derive(Debug, Deserialize)]
+pub struct ChatStreamRequest {
+    /// 用户消息
+    pub message: String,
+    /// 请求选项
+    #[serde(flatten)]
+    pub options: ChatOptions,
+}
+
+/// 流式聊天的文本片段
+#[derive(Debug, Serialize)]
+pub struct ChatStreamChunk {
+    /// 片段序号，从0开始
+    pub index: usize,
+    /// 文本内容
+    pub content: String,
+}
+
+/// 流式聊天结束时的摘要
+#[derive(Debug, Serialize)]
+pub struct ChatStreamSummary {
+    /// 响应ID，可用于提交反馈
+    pub response_id: String,
+    /// 使用的模型
+    pub model: String,
+    /// 使用统计
+    pub usage: Option<TokenUsage>,
+    /// 完成原因
+    pub finish_reason: Option<FinishReason>,
+    /// 响应警告
+    pub warnings: Vec<ResponseWarning>,
+    /// A/B 路由选中的变体
+    pub variant: Option<String>,
+    /// 片段数量
+    pub chunks: usize,
+    /// 响应总长度
+    pub content_length: usize,
+    /// 耗时（毫秒）
+    pub duration_ms: u64,
 }
 
 /// API错误
@@ -107,24 +633,234 @@ impl AxumAdapter {
     pub fn new() -> Self {
         Self {
             node: Arc::new(RwLock::new(None)),
+            guard: RequestGuard::default(),
+            files: None,
+            progress: None,
+            uploads: None,
+            watcher: None,
+            #[cfg(feature = "webrtc-bridge")]
+            webrtc_bridge: None,
+            #[cfg(feature = "link-preview")]
+            link_previews: None,
+        }
+    }
+
+    /// 设置请求签名与幂等配置
+    pub fn with_request_guard(mut self, config: RequestGuardConfig) -> Self {
+        self.guard = RequestGuard::new(config);
+        self
+    }
+
+    /// 启用传输进度推送，与上传共用同一个注册表时上传进度会推送到对应会话
+    pub fn with_progress(mut self, progress: Arc<ProgressRegistry>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// 启用可续传的分块上传，设置了传输调度时在后台跟随调度暂停与恢复上传
+    pub fn with_uploads(mut self, uploads: UploadManager) -> Self {
+        let uploads = Arc::new(uploads);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(uploads.clone().run_scheduler());
+            }
+            Err(_) => debug!("不在Tokio运行时中，上传只在写入分块时检查传输调度"),
+        }
+        self.uploads = Some(uploads);
+        self
+    }
+
+    /// 启用共享文件下载，内容来自 blob 存储（设置了 [`FileShareConfig::with_blobs`] 时）或本地共享目录
+    pub fn with_file_share(mut self, config: FileShareConfig) -> Self {
+        self.files = Some(Arc::new(FileShare::new(config)));
+        self
+    }
+
+    /// 启用监视文件夹自动共享，在后台定期扫描
+    pub fn with_folder_watcher(mut self, watcher: FolderWatcher) -> Self {
+        let watcher = Arc::new(watcher);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(watcher.clone().run());
+            }
+            Err(_) => warn!("不在Tokio运行时中，监视文件夹需要自行调用 FolderWatcher::run"),
+        }
+        self.watcher = Some(watcher);
+        self
+    }
+
+    /// 启用浏览器WebRTC网关
+    #[cfg(feature = "webrtc-bridge")]
+    pub fn with_webrtc_bridge(mut self, bridge: super::webrtc_bridge::WebRtcBridge) -> Self {
+        self.webrtc_bridge = Some(Arc::new(bridge));
+        self
+    }
+
+    /// 启用聊天消息的链接预览，在后台为收到和发出的消息抓取预览并推送到聊天事件流
+    #[cfg(feature = "link-preview")]
+    pub fn with_link_previews(mut self, config: super::link_preview::LinkPreviewConfig) -> Self {
+        let previewer = Arc::new(super::link_preview::LinkPreviewer::new(config));
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(previewer.clone().run(self.node.clone()));
+            }
+            Err(_) => warn!("不在Tokio运行时中，链接预览需要自行调用 LinkPreviewer::run"),
         }
+        self.link_previews = Some(previewer);
+        self
     }
 
     /// 创建Axum路由
     pub fn create_router(&self) -> Router {
         let node = self.node.clone();
 
-        Router::new()
+        let router = Router::new()
             .route("/api/node", post(init_node))
             .route("/api/node/status", get(get_node_status))
+            .route("/api/node/peers", get(get_peers))
+            .route("/api/node/address-book", get(get_address_book))
+            .route("/api/node/address-book/:node_id", put(set_peer_trust))
+            .route("/api/node/address-book/:node_id", delete(remove_peer))
+            .route(
+                "/api/node/address-book/:node_id/safety-number",
+                get(get_safety_number),
+            )
+            .route(
+                "/api/node/address-book/:node_id/safety-number/qr",
+                get(get_safety_number_qr),
+            )
+            .route(
+                "/api/node/address-book/:node_id/verify",
+                post(confirm_verification),
+            )
+            .route("/api/node/verify/scan", post(verify_scanned))
+            .route("/api/node/events", get(node_events))
+            .route("/api/node/bundle/export", post(export_bundle))
+            .route("/api/node/bundle/import", post(import_bundle))
+            .route("/api/topics", get(list_topics))
             .route("/api/topics", post(create_topic))
             .route("/api/topics/join", post(join_topic))
+            .route("/api/invites", post(create_invite))
+            .route("/api/invites/qr", get(get_invite_qr))
+            .route("/api/invites/join", post(join_invite))
             .route("/api/topics/:topic_id/messages", post(send_message))
+            .route("/api/topics/:topic_id/messages", get(get_chat_history))
+            .route("/api/topics/:topic_id/messages/stream", get(chat_events))
+            .route(
+                "/api/topics/:topic_id/messages/:message_id/read",
+                post(mark_message_read),
+            )
+            .route(
+                "/api/topics/:topic_id/messages/:message_id/reactions",
+                post(react_to_message),
+            )
+            .route(
+                "/api/topics/:topic_id/messages/:message_id/thread",
+                get(get_message_thread),
+            )
+            .route("/api/unread", get(get_unread_summary))
+            .route("/api/notifications", get(get_notifications))
+            .route("/api/notifications/mentions", get(get_mentions))
+            .route("/api/notifications/mentions/read", post(mark_mentions_read))
+            .route("/api/topics/:topic_id/read", post(mark_room_read))
+            .route("/api/topics/:topic_id/liveness", get(get_room_liveness))
+            .route("/api/topics/:topic_id/history-key/export", post(export_room_key))
+            .route("/api/topics/:topic_id/history-key/import", post(import_room_key))
+            .route("/api/topics/:topic_id/outbox", get(get_outbox))
+            .route("/api/topics/:topic_id/outbox/flush", post(flush_outbox))
+            .route("/api/topics/:topic_id/bot", get(get_room_bot))
+            .route("/api/topics/:topic_id/bot", put(set_room_bot))
+            .route("/api/topics/:topic_id/bot", delete(remove_room_bot))
+            .route("/api/topics/:topic_id/bot/opt-out", post(set_room_bot_opt_out))
             .route("/api/topics/:topic_id/agent", post(send_agent_request))
+            .route("/api/topics/:topic_id/ensemble", post(ensemble_request))
+            .route("/api/topics/:topic_id/memory", get(list_memory))
+            .route("/api/topics/:topic_id/memory", post(remember))
+            .route("/api/topics/:topic_id/memory/recall", post(recall_memory))
+            .route("/api/topics/:topic_id/memory/:id", delete(forget_memory))
+            .route("/api/topics/:topic_id/folders", get(list_folder_syncs))
+            .route("/api/topics/:topic_id/folders", post(start_folder_sync))
+            .route("/api/topics/:topic_id/folders/:name", get(get_folder_sync_status))
+            .route("/api/topics/:topic_id/folders/:name", delete(stop_folder_sync))
+            .route(
+                "/api/topics/:topic_id/folders/:name/resolve",
+                post(resolve_folder_conflict),
+            )
             .route("/api/topics/:topic_id", get(get_topic_info))
             .route("/api/topics/:topic_id", delete(leave_topic))
+            .route("/api/stats", get(get_usage_summary))
+            .route("/api/stats/timeseries", get(get_usage_series))
+            .route("/api/stats/agents", get(get_agent_usage))
+            .route("/api/logs", get(get_logs))
+            .route("/api/logs/export", get(export_logs))
+            .route("/api/logs/stream", get(stream_logs))
+            .route("/api/eval", post(run_eval))
+            .route("/api/summarize", post(summarize_document))
+            .route("/api/translate", post(translate))
+            .route("/api/responses/:response_id/feedback", post(submit_feedback))
+            .route("/api/agents", get(list_agents))
+            .route("/api/agents/:agent_id/messages", get(get_agent_messages))
+            .route("/api/agents/:agent_id/chat/stream", post(chat_stream))
+            .route("/api/agents/:agent_id/feedback", get(export_feedback))
+            .route("/api/agents/:agent_id/package", get(export_agent_package))
+            .route("/api/agents/package/import", post(import_agent_package))
+            .route("/api/templates", get(list_templates))
+            .route("/api/templates", post(share_template))
+            .route("/api/templates/join", post(join_template_exchange))
+            .route("/api/templates/:hash", delete(unshare_template))
+            .route("/api/templates/:hash/import", post(import_template))
             .route("/api/node", delete(stop_node))
-            .with_state(node)
+            .with_state(node.clone());
+
+        let router = router.merge(
+            Router::new()
+                .route("/api/retention/run", post(run_retention))
+                .route("/api/users/:caller_id/data", delete(purge_user_data))
+                .with_state(RetentionState {
+                    node: node.clone(),
+                    uploads: self.uploads.clone(),
+                }),
+        );
+
+        let router = match &self.files {
+            Some(files) => router.merge(files.clone().router()),
+            None => router,
+        };
+
+        let router = match &self.progress {
+            Some(progress) => router.merge(progress.clone().router()),
+            None => router,
+        };
+
+        let router = match &self.uploads {
+            Some(uploads) => router.merge(uploads.clone().router()),
+            None => router,
+        };
+
+        let router = match &self.watcher {
+            Some(watcher) => router.merge(watcher.clone().router()),
+            None => router,
+        };
+
+        #[cfg(feature = "link-preview")]
+        let router = match &self.link_previews {
+            Some(previewer) => router.merge(previewer.clone().router()),
+            None => router,
+        };
+
+        #[cfg(feature = "webrtc-bridge")]
+        let router = match &self.webrtc_bridge {
+            Some(bridge) => router.merge(bridge.clone().router(node)),
+            None => router,
+        };
+
+        // 先校验签名，再创建请求上下文
+        router
+            .layer(middleware::from_fn(attach_request_context))
+            .layer(middleware::from_fn_with_state(
+                self.guard.clone(),
+                guard_requests,
+            ))
     }
 }
 
@@ -137,7 +873,10 @@ impl Default for AxumAdapter {
 /// 将NodeError转换为API响应
 impl IntoResponse for NodeError {
     fn into_response(self) -> Response {
-        let status = StatusCode::INTERNAL_SERVER_ERROR;
+        let status = match self {
+            NodeError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
         let error = ApiError {
             message: self.to_string(),
         };
@@ -173,8 +912,11 @@ async fn init_node(
         secret_key: request.secret_key,
         relay: relay_url,
         no_relay: request.no_relay.unwrap_or(false),
+        proxy: request.proxy,
+        privacy_mode: request.privacy_mode.unwrap_or(false),
         name: request.name.clone(),
         bind_port: request.bind_port.unwrap_or(0),
+        ..Default::default()
     };
 
     // 创建P2P节点
@@ -200,162 +942,1119 @@ async fn init_node(
     Ok(Json(node_id))
 }
 
-/// 获取节点状态
-async fn get_node_status(
-    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
-) -> Result<Json<NodeStatusResponse>, NodeError> {
-    let node_read = node.read().await;
-    let node = node_read
-        .as_ref()
-        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
-
-    let status = node.get_status().await;
-
-    Ok(Json(NodeStatusResponse {
-        node_id: status.node_id,
-        connected_peers: status.connected_peers,
-        active_topics: status.active_topics,
-        started_at: status.started_at.to_rfc3339(),
-        last_activity: status.last_activity.to_rfc3339(),
-        relay_mode: status.relay_mode,
-    }))
-}
-
-/// 创建话题
-async fn create_topic(
+/// 导出节点配置包
+async fn export_bundle(
     State(node): State<Arc<RwLock<Option<P2PNode>>>>,
-    Json(request): Json<CreateTopicRequest>,
-) -> Result<Json<TopicResponse>, NodeError> {
+    Json(request): Json<ExportBundleRequest>,
+) -> Result<Json<ExportBundleResponse>, NodeError> {
     let node_read = node.read().await;
     let node = node_read
         .as_ref()
         .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    // 解析话题ID
-    let topic = match request.topic_id {
-        Some(id) => Some(
-            id.parse()
-                .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?,
-        ),
-        None => None,
-    };
-
-    // 创建话题
-    let (topic, ticket) = node.join_topic(topic, None).await?;
-
-    info!("已创建话题: {}", topic);
-    Ok(Json(TopicResponse {
-        topic_id: topic.to_string(),
-        ticket,
-    }))
+    let bundle = node.export_bundle(&request.passphrase).await?;
+    Ok(Json(ExportBundleResponse { bundle }))
 }
 
-/// 加入话题
-async fn join_topic(
+/// 用口令加密导出房间的聊天记录密钥
+async fn export_room_key(
     State(node): State<Arc<RwLock<Option<P2PNode>>>>,
-    Json(request): Json<JoinTopicRequest>,
-) -> Result<Json<TopicResponse>, NodeError> {
+    Path(topic_id): Path<String>,
+    Json(request): Json<ExportBundleRequest>,
+) -> Result<Json<ExportRoomKeyResponse>, NodeError> {
     let node_read = node.read().await;
     let node = node_read
         .as_ref()
         .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    // 加入话题
-    let (topic, ticket) = node.join_topic(None, Some(&request.ticket)).await?;
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
 
-    info!("已加入话题: {}", topic);
-    Ok(Json(TopicResponse {
-        topic_id: topic.to_string(),
-        ticket,
-    }))
+    let key = node.export_room_key(&topic_id, &request.passphrase).await?;
+    Ok(Json(ExportRoomKeyResponse { key }))
 }
 
-/// 发送消息
-async fn send_message(
+/// 导入房间的聊天记录密钥，解密本地已锁定的消息
+async fn import_room_key(
     State(node): State<Arc<RwLock<Option<P2PNode>>>>,
     Path(topic_id): Path<String>,
-    Json(request): Json<MessageRequest>,
-) -> Result<(), NodeError> {
+    Json(request): Json<ImportRoomKeyRequest>,
+) -> Result<Json<ImportRoomKeyResponse>, NodeError> {
     let node_read = node.read().await;
     let node = node_read
         .as_ref()
         .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    // 解析话题ID
     let topic_id = topic_id
         .parse()
         .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
 
-    // 创建消息
-    let message = MessageType::Chat {
-        text: request.message,
+    let restored = node
+        .import_room_key(&topic_id, &request.key, &request.passphrase)
+        .await?;
+    Ok(Json(ImportRoomKeyResponse { restored }))
+}
+
+/// 从配置包初始化节点并恢复话题
+async fn import_bundle(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<ImportBundleRequest>,
+) -> Result<Json<String>, NodeError> {
+    let mut node_write = node.write().await;
+    if node_write.is_some() {
+        return Err(NodeError::ConfigError(
+            "节点已经初始化，请先停止节点再导入".to_string(),
+        ));
+    }
+
+    let p2p_node = P2PNode::from_bundle(
+        &request.bundle,
+        &request.passphrase,
+        request.bind_port.unwrap_or(0),
+    )
+    .await?;
+    let node_id = p2p_node.node_id().to_string();
+    *node_write = Some(p2p_node);
+
+    info!("已从配置包恢复P2P节点: {}", node_id);
+    Ok(Json(node_id))
+}
+
+/// 获取节点状态
+async fn get_node_status(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<NodeStatusResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let status = node.get_status().await;
+
+    Ok(Json(NodeStatusResponse {
+        node_id: status.node_id,
+        connected_peers: status.connected_peers,
+        active_topics: status.active_topics,
+        started_at: status.started_at.to_rfc3339(),
+        last_activity: status.last_activity.to_rfc3339(),
+        relay_mode: status.relay_mode,
+        home_relay: status.home_relay,
+    }))
+}
+
+/// 获取对等节点的协议版本与协商后的能力
+async fn get_peers(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<Vec<PeerInfo>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.get_peers().await))
+}
+
+/// 获取地址簿
+async fn get_address_book(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<Vec<PeerRecord>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.get_address_book().await))
+}
+
+/// 设置节点的信任级别
+async fn set_peer_trust(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(node_id): Path<String>,
+    Json(request): Json<SetPeerTrustRequest>,
+) -> Result<Json<PeerRecord>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let peer = node
+        .set_peer_trust(&node_id, request.trust, request.name)
+        .await?;
+    info!("已将节点 {} 的信任级别设为 {:?}", node_id, peer.trust);
+    Ok(Json(peer))
+}
+
+/// 从地址簿删除节点
+async fn remove_peer(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(node_id): Path<String>,
+) -> Result<Json<PeerRecord>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.remove_peer(&node_id).await?))
+}
+
+/// 数据保留与删除接口的状态，需要同时清理节点与上传目录
+#[derive(Clone)]
+struct RetentionState {
+    node: Arc<RwLock<Option<P2PNode>>>,
+    uploads: Option<Arc<UploadManager>>,
+}
+
+/// 立即执行数据保留策略
+async fn run_retention(
+    State(state): State<RetentionState>,
+) -> Result<Json<PurgeReport>, NodeError> {
+    let node_read = state.node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let mut report = node.apply_retention().await?;
+    if let (Some(uploads), Some(cutoff)) =
+        (&state.uploads, node.retention_policy().transfer_cutoff())
+    {
+        report.transfers += uploads.purge_completed_before(cutoff).await?;
+    }
+    Ok(Json(report))
+}
+
+/// 删除调用方的全部数据（被遗忘权），调用方ID与请求上下文中的 `x-caller-id` 一致
+async fn purge_user_data(
+    State(state): State<RetentionState>,
+    Path(caller_id): Path<String>,
+) -> Result<Json<PurgeReport>, NodeError> {
+    let node_read = state.node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let mut report = node.purge_user_data(&caller_id).await?;
+    if let Some(uploads) = &state.uploads {
+        report.transfers += uploads.purge_owner(&caller_id).await?;
+    }
+    info!("已删除调用方 {} 的数据，共 {} 项", caller_id, report.total());
+    Ok(Json(report))
+}
+
+/// 解析可选的话题ID
+fn parse_optional_topic(topic_id: Option<&str>) -> NodeResult<Option<TopicId>> {
+    topic_id
+        .map(|id| {
+            id.parse()
+                .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))
+        })
+        .transpose()
+}
+
+/// 获取与节点之间的安全码
+async fn get_safety_number(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(node_id): Path<String>,
+) -> Result<Json<SafetyNumber>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.safety_number(&node_id)?))
+}
+
+/// 获取供对方扫描的安全码二维码（PNG）
+async fn get_safety_number_qr(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(node_id): Path<String>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let png = node.safety_number(&node_id)?.qr_png()?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+/// 核对安全码后确认验证
+async fn confirm_verification(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(node_id): Path<String>,
+    Json(request): Json<ConfirmVerificationRequest>,
+) -> Result<Json<PeerRecord>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = parse_optional_topic(request.topic_id.as_deref())?;
+    let peer = node
+        .confirm_verification(&node_id, topic_id.as_ref())
+        .await?;
+    Ok(Json(peer))
+}
+
+/// 扫描对方的验证二维码
+async fn verify_scanned(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<ScanVerificationRequest>,
+) -> Result<Json<PeerRecord>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = parse_optional_topic(request.topic_id.as_deref())?;
+    let peer = node
+        .verify_scanned(&request.payload, topic_id.as_ref())
+        .await?;
+    Ok(Json(peer))
+}
+
+/// 节点事件流（SSE），状态变化时推送，无需轮询 /api/node/status
+async fn node_events(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, NodeError> {
+    let receiver = {
+        let node_read = node.read().await;
+        node_read
+            .as_ref()
+            .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?
+            .subscribe_events()
+    };
+
+    let stream = futures_lite::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse_event = SseEvent::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok(sse_event), receiver));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("节点事件订阅者落后，丢弃 {} 条事件", skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// 分页获取已加入的话题
+async fn list_topics(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<PageRequest>,
+) -> Result<Json<ListPage<TopicStats>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.list_topics_page(&query).await))
+}
+
+/// 创建话题
+async fn create_topic(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<CreateTopicRequest>,
+) -> Result<Json<TopicResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic = match request.topic_id {
+        Some(id) => Some(
+            id.parse()
+                .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?,
+        ),
+        None => None,
+    };
+
+    // 创建话题
+    let (topic, ticket) = node.join_topic(topic, None).await?;
+
+    info!("已创建话题: {}", topic);
+    Ok(Json(TopicResponse {
+        topic_id: topic.to_string(),
+        ticket,
+    }))
+}
+
+/// 加入话题
+async fn join_topic(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<JoinTopicRequest>,
+) -> Result<Json<TopicResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 加入话题
+    let (topic, ticket) = node.join_topic(None, Some(&request.ticket)).await?;
+
+    info!("已加入话题: {}", topic);
+    Ok(Json(TopicResponse {
+        topic_id: topic.to_string(),
+        ticket,
+    }))
+}
+
+/// 生成邀请链接
+async fn create_invite(
+    Json(request): Json<InviteRequest>,
+) -> Result<Json<InviteResponse>, NodeError> {
+    let invite = Invite::new(request.kind, request.ticket, request.name)?;
+    Ok(Json(InviteResponse {
+        url: invite.to_url(),
+    }))
+}
+
+/// 生成邀请二维码（PNG）
+async fn get_invite_qr(Query(request): Query<InviteRequest>) -> Result<Response, NodeError> {
+    let invite = Invite::new(request.kind, request.ticket, request.name)?;
+    let png = invite.qr_png()?;
+    Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response())
+}
+
+/// 通过房间邀请链接加入话题
+async fn join_invite(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<JoinInviteRequest>,
+) -> Result<Json<TopicResponse>, NodeError> {
+    let invite: Invite = request.url.parse()?;
+    if invite.kind != InviteKind::Room {
+        return Err(NodeError::TopicError("不是房间邀请链接".to_string()));
+    }
+
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let (topic, ticket) = node.join_topic(None, Some(&invite.ticket)).await?;
+    if invite.name.is_some() {
+        node.set_topic_label(&topic, invite.name).await?;
+    }
+
+    info!("已通过邀请加入话题: {}", topic);
+    Ok(Json(TopicResponse {
+        topic_id: topic.to_string(),
+        ticket,
+    }))
+}
+
+/// 发送消息
+async fn send_message(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<MessageRequest>,
+) -> Result<Json<SendMessageResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    // 结构化内容不按命令解析；系统提示只能由节点自身发出
+    if let Some(content) = request.content {
+        let sender = request.sender.as_deref().map(str::trim).filter(|s| !s.is_empty());
+        let content = match (content, sender) {
+            (MessageContent::Notice { .. }, _) => {
+                return Err(NodeError::InvalidMessage("网页客户端不能发送系统提示".to_string()));
+            }
+            (MessageContent::Markdown { text }, Some(sender)) => MessageContent::Markdown {
+                text: format!("{}: {}", sender, text),
+            },
+            (MessageContent::Text { text }, Some(sender)) => MessageContent::Text {
+                text: format!("{}: {}", sender, text),
+            },
+            (content, _) => content,
+        };
+        let message_id = node
+            .send_content(&topic_id, content, request.reply_to.as_deref())
+            .await?;
+        info!("已向话题 {} 发送结构化消息", topic_id);
+        return Ok(Json(SendMessageResponse {
+            message_id: Some(message_id.clone()),
+            outcome: CommandOutcome::Sent { message_id },
+        }));
+    }
+
+    // 回复不按命令解析
+    if let Some(reply_to) = request.reply_to.as_deref() {
+        if request.message.trim().is_empty() {
+            return Err(NodeError::InvalidMessage("消息不能为空".to_string()));
+        }
+        let text = match request.sender.as_deref().map(str::trim) {
+            Some(sender) if !sender.is_empty() => format!("{}: {}", sender, request.message),
+            _ => request.message.clone(),
+        };
+        let message_id = node.send_reply(&topic_id, reply_to, &text).await?;
+        info!("已回复话题 {} 中的消息 {}", topic_id, reply_to);
+        return Ok(Json(SendMessageResponse {
+            message_id: Some(message_id.clone()),
+            outcome: CommandOutcome::Sent { message_id },
+        }));
+    }
+
+    // 以 / 开头的输入按聊天命令执行；网页客户端不能分享服务器上的文件，需使用上传接口
+    let command = CommandRegistry::split_command(&request.message);
+    if command.as_ref().is_some_and(|(name, _)| name == "share") {
+        return Err(NodeError::InvalidMessage(
+            "网页客户端请通过上传接口分享文件".to_string(),
+        ));
+    }
+    // 网页客户端的普通消息带上署名
+    let input = match request.sender.as_deref().map(str::trim) {
+        Some(sender) if !sender.is_empty() && command.is_none() => {
+            format!("{}: {}", sender, request.message)
+        }
+        _ => request.message,
+    };
+    let outcome = node.send_input(&topic_id, &input).await?;
+
+    info!("已处理发往话题 {} 的输入", topic_id);
+    let message_id = match &outcome {
+        CommandOutcome::Sent { message_id } => Some(message_id.clone()),
+        _ => None,
+    };
+    Ok(Json(SendMessageResponse { message_id, outcome }))
+}
+
+/// 话题的聊天事件流（SSE），网页客户端经此收到P2P节点与其他网页客户端的消息及投递状态，
+/// 配合 `POST /api/topics/:topic_id/messages` 即可通过服务器与P2P节点聊天
+async fn chat_events(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, NodeError> {
+    let receiver = {
+        let node_read = node.read().await;
+        let node = node_read
+            .as_ref()
+            .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+        let topic: TopicId = topic_id
+            .parse()
+            .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+        if !node.get_active_topics().await.contains(&topic) {
+            return Err(NodeError::TopicError(format!("话题不存在: {}", topic_id)));
+        }
+        node.subscribe_events()
+    };
+
+    Ok(Sse::new(topic_chat_body(receiver, topic_id)).keep_alive(KeepAlive::default()))
+}
+
+/// 从节点事件中筛出指定话题的聊天事件并转换为SSE响应体，节点停止后流随之结束
+fn topic_chat_body(
+    receiver: tokio::sync::broadcast::Receiver<NodeEvent>,
+    topic_id: String,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> {
+    futures_lite::stream::unfold(
+        (receiver, topic_id),
+        |(mut receiver, topic_id)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event)
+                        if event.is_chat() && event.topic_id() == Some(topic_id.as_str()) =>
+                    {
+                        let sse_event = SseEvent::default()
+                            .event(event.name())
+                            .json_data(&event)
+                            .unwrap_or_default();
+                        return Some((Ok(sse_event), (receiver, topic_id)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("话题 {} 的聊天订阅者落后，丢弃 {} 条事件", topic_id, skipped);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    )
+}
+
+/// 获取话题的聊天记录（含投递状态），也可以只获取摘要
+async fn get_chat_history(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    if query.summary {
+        return Ok(Json(node.get_chat_history_summary(&topic_id).await).into_response());
+    }
+    let page = node
+        .get_chat_history_page(&topic_id, &query.page_request())
+        .await?;
+    Ok(Json(page).into_response())
+}
+
+/// 将收到的消息标记为已读
+async fn mark_message_read(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, message_id)): Path<(String, String)>,
+) -> Result<(), NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    node.mark_read(&topic_id, &message_id).await
+}
+
+/// 添加或撤销对消息的表情回应
+async fn react_to_message(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, message_id)): Path<(String, String)>,
+    Json(request): Json<ReactionRequest>,
+) -> Result<Json<ReactionResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let reactions = node
+        .react(&topic_id, &message_id, &request.emoji, request.removed)
+        .await?;
+    Ok(Json(ReactionResponse { reactions }))
+}
+
+/// 获取消息所在的消息串
+async fn get_message_thread(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, message_id)): Path<(String, String)>,
+) -> Result<Json<Vec<ChatHistoryEntry>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    Ok(Json(node.get_thread(&topic_id, &message_id).await?))
+}
+
+/// 获取各房间的未读状态
+async fn get_unread_summary(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<UnreadSummary>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.get_unread_summary().await))
+}
+
+/// 将房间中的消息标记为已读
+async fn mark_room_read(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Query(query): Query<RoomReadQuery>,
+) -> Result<Json<RoomReadResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let marked = node
+        .mark_room_read(&topic_id, query.message_id.as_deref())
+        .await?;
+    Ok(Json(RoomReadResponse { marked }))
+}
+
+/// 获取未读提及数
+async fn get_notifications(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<MentionSummary>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.unread_mentions().await))
+}
+
+/// 获取本节点被提及的记录
+async fn get_mentions(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<MentionQuery>,
+) -> Result<Json<Vec<MentionRecord>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id: Option<TopicId> = query
+        .topic_id
+        .map(|topic_id| topic_id.parse())
+        .transpose()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    Ok(Json(node.get_mentions(topic_id.as_ref(), query.unread).await))
+}
+
+/// 将提及标记为已读
+async fn mark_mentions_read(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<MentionReadRequest>,
+) -> Result<Json<MentionReadResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id: Option<TopicId> = request
+        .topic_id
+        .map(|topic_id| topic_id.parse())
+        .transpose()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let marked = node
+        .mark_mentions_read(topic_id.as_ref(), request.message_id.as_deref())
+        .await;
+    Ok(Json(MentionReadResponse { marked }))
+}
+
+/// 发送Agent请求
+async fn send_agent_request(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<AgentRequest>,
+) -> Result<Json<AgentRequestResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    // 发送Agent请求
+    let handle = node
+        .send_agent_request(
+            &topic_id,
+            &request.agent_id,
+            &request.prompt,
+            request.mode,
+            request.timeout_ms.map(Duration::from_millis),
+        )
+        .await?;
+    drop(node_read);
+
+    info!(
+        "已发送Agent请求到话题: {}, agent_id: {}, request_id: {}",
+        topic_id,
+        request.agent_id,
+        handle.request_id()
+    );
+
+    let request_id = handle.request_id().to_string();
+    let outcome = if request.wait {
+        Some(handle.wait().await?)
+    } else {
+        None
     };
+    Ok(Json(AgentRequestResponse {
+        request_id,
+        outcome,
+    }))
+}
+
+/// 集成回答
+async fn ensemble_request(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<EnsembleRequest>,
+) -> Result<Json<EnsembleOutcome>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let outcome = node
+        .ensemble_request(
+            &topic_id,
+            &request.agent_id,
+            &request.prompt,
+            request.options,
+        )
+        .await?;
+    Ok(Json(outcome))
+}
+
+/// 获取话题的共享记忆
+async fn list_memory(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<Vec<MemoryEntry>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    Ok(Json(node.list_memory(&topic_id).await?))
+}
+
+/// 写入共享记忆
+async fn remember(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<RememberRequest>,
+) -> Result<Json<MemoryEntry>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let entry = node
+        .remember(&topic_id, &request.text, request.source)
+        .await?;
+    Ok(Json(entry))
+}
+
+/// 检索共享记忆
+async fn recall_memory(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<RecallRequest>,
+) -> Result<Json<Vec<MemoryMatch>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let limit = request.limit.unwrap_or(DEFAULT_RECALL_LIMIT);
+    let matches = node.recall_memory(&topic_id, &request.query, limit).await?;
+    Ok(Json(matches))
+}
+
+/// 删除本节点写入的共享记忆
+async fn forget_memory(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, id)): Path<(String, String)>,
+) -> Result<(), NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    node.forget(&topic_id, &id).await
+}
+
+/// 获取话题中的同步文件夹
+async fn list_folder_syncs(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<Vec<String>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    Ok(Json(node.folder_syncs(&topic_id).await))
+}
+
+/// 开始同步文件夹，返回各文件的同步状态
+async fn start_folder_sync(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Json(request): Json<StartFolderSyncRequest>,
+) -> Result<Json<Vec<FileSyncStatus>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let status = node
+        .start_folder_sync(&topic_id, &request.name, request.path)
+        .await?;
+    Ok(Json(status))
+}
+
+/// 获取同步文件夹中各文件的同步状态，状态变化通过节点事件流推送
+async fn get_folder_sync_status(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, name)): Path<(String, String)>,
+) -> Result<Json<Vec<FileSyncStatus>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    Ok(Json(node.folder_sync_status(&topic_id, &name).await?))
+}
+
+/// 停止同步文件夹
+async fn stop_folder_sync(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, name)): Path<(String, String)>,
+) -> Result<(), NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    node.stop_folder_sync(&topic_id, &name).await
+}
+
+/// 确认已处理文件的冲突
+async fn resolve_folder_conflict(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path((topic_id, name)): Path<(String, String)>,
+    Json(request): Json<ResolveConflictRequest>,
+) -> Result<Json<FileSyncStatus>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    let status = node
+        .resolve_folder_conflict(&topic_id, &name, &request.file)
+        .await?;
+    Ok(Json(status))
+}
+
+/// 获取话题信息
+async fn get_topic_info(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<TopicInfoResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    // 解析话题ID
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+
+    // 检查话题是否存在
+    let active_topics = node.get_active_topics().await;
+    if !active_topics.contains(&topic_id) {
+        return Err(NodeError::TopicError(format!("话题不存在: {}", topic_id)));
+    }
+
+    // 生成票据
+    let ticket = node
+        .generate_ticket(topic_id)
+        .await
+        .map_err(|e| NodeError::TopicError(format!("生成票据失败: {}", e)))?;
+
+    let stats = node.get_topic_stats(&topic_id).await.unwrap_or_default();
+
+    Ok(Json(TopicInfoResponse {
+        topic_id: topic_id.to_string(),
+        ticket,
+        stats,
+    }))
+}
+
+/// 获取房间活跃度
+async fn get_room_liveness(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+    Query(query): Query<LivenessQuery>,
+) -> Result<Json<RoomLiveness>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    let window = query.window_secs.map(Duration::from_secs);
+    Ok(Json(node.get_room_liveness(&topic_id, window).await?))
+}
+
+/// 获取话题中暂存在发件箱、等待补发的消息
+async fn get_outbox(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<Vec<OutboxEntry>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    Ok(Json(node.outbox_pending(&topic_id).await?))
+}
+
+/// 立即补发话题中暂存的消息，返回补发成功的消息数
+async fn flush_outbox(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<usize>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    Ok(Json(node.flush_outbox(&topic_id).await))
+}
 
-    // 发送消息
-    node.send_message(&topic_id, message).await?;
+/// 获取房间机器人配置，没有设置时返回 null
+async fn get_room_bot(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<Json<Option<RoomBotConfig>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    info!("已发送消息到话题: {}", topic_id);
-    Ok(())
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    Ok(Json(node.get_room_bot(&topic_id).await))
 }
 
-/// 发送Agent请求
-async fn send_agent_request(
+/// 设置房间机器人
+async fn set_room_bot(
     State(node): State<Arc<RwLock<Option<P2PNode>>>>,
     Path(topic_id): Path<String>,
-    Json(request): Json<AgentRequest>,
-) -> Result<(), NodeError> {
+    Json(config): Json<RoomBotConfig>,
+) -> Result<Json<RoomBotConfig>, NodeError> {
     let node_read = node.read().await;
     let node = node_read
         .as_ref()
         .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    // 解析话题ID
     let topic_id = topic_id
         .parse()
         .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    node.set_room_bot(&topic_id, config.clone()).await?;
+    Ok(Json(config))
+}
 
-    // 发送Agent请求
-    node.send_agent_request(&topic_id, &request.agent_id, &request.prompt)
-        .await?;
+/// 移除房间机器人
+async fn remove_room_bot(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(topic_id): Path<String>,
+) -> Result<(), NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    info!(
-        "已发送Agent请求到话题: {}, agent_id: {}",
-        topic_id, request.agent_id
-    );
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+    if !node.remove_room_bot(&topic_id).await {
+        return Err(NodeError::TopicError(format!("话题 {} 没有设置机器人", topic_id)));
+    }
     Ok(())
 }
 
-/// 获取话题信息
-async fn get_topic_info(
+/// 设置成员是否不让房间机器人回复
+async fn set_room_bot_opt_out(
     State(node): State<Arc<RwLock<Option<P2PNode>>>>,
     Path(topic_id): Path<String>,
-) -> Result<Json<TopicResponse>, NodeError> {
+    Json(request): Json<BotOptOutRequest>,
+) -> Result<(), NodeError> {
     let node_read = node.read().await;
     let node = node_read
         .as_ref()
         .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
 
-    // 解析话题ID
     let topic_id = topic_id
         .parse()
         .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
-
-    // 检查话题是否存在
-    let active_topics = node.get_active_topics().await;
-    if !active_topics.contains(&topic_id) {
-        return Err(NodeError::TopicError(format!("话题不存在: {}", topic_id)));
-    }
-
-    // 生成票据
-    let ticket = node
-        .generate_ticket(topic_id.clone())
+    node.set_room_bot_opt_out(&topic_id, &request.member, request.opted_out)
         .await
-        .map_err(|e| NodeError::TopicError(format!("生成票据失败: {}", e)))?;
-
-    Ok(Json(TopicResponse {
-        topic_id: topic_id.to_string(),
-        ticket,
-    }))
 }
 
 /// 离开话题
@@ -380,6 +2079,492 @@ async fn leave_topic(
     Ok(())
 }
 
+/// 获取用量汇总
+async fn get_usage_summary(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<UsageSummary>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.get_usage_summary().await))
+}
+
+/// 获取按时间窗口统计的用量，可导出为CSV
+async fn get_usage_series(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let series = node.get_usage_series(query.window, query.limit);
+    Ok(match query.format {
+        ExportFormat::Json => Json(series).into_response(),
+        ExportFormat::Csv => csv_response("usage.csv", series_to_csv(&series)),
+    })
+}
+
+/// 获取各Agent的用量，可导出为CSV
+async fn get_agent_usage(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let agents = node.get_usage_summary().await.agents;
+    Ok(match query.format {
+        ExportFormat::Json => Json(agents).into_response(),
+        ExportFormat::Csv => csv_response("agents.csv", agents_to_csv(&agents)),
+    })
+}
+
+/// 运行评测测试集
+async fn run_eval(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<EvalRequest>,
+) -> Result<Json<EvalReport>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let report = node.run_eval(&request.suite, &request.agents).await?;
+    info!(
+        "评测 {} 完成，通过 {}，失败 {}",
+        report.suite, report.passed, report.failed
+    );
+    Ok(Json(report))
+}
+
+/// 对长文档做分块摘要
+async fn summarize_document(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<SummarizeRequest>,
+) -> Result<Json<DocumentSummary>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let summary = node
+        .summarize_document(&request.text, &request.options)
+        .await?;
+    info!(
+        "文档摘要完成，{} 个章节，{} 个块",
+        summary.sections.len(),
+        summary.chunks
+    );
+    Ok(Json(summary))
+}
+
+/// 翻译文本
+async fn translate(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<TranslateRequest>,
+) -> Result<Json<Translation>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let translation = node.translate(&request).await?;
+    debug!(
+        "翻译完成，{:?} -> {}",
+        translation.source, translation.target
+    );
+    Ok(Json(translation))
+}
+
+/// 提交对Agent回复的反馈
+async fn submit_feedback(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(response_id): Path<String>,
+    Json(request): Json<FeedbackRequest>,
+) -> Result<Json<ResponseFeedback>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let feedback = ResponseFeedback {
+        rating: request.rating,
+        comment: request.comment,
+        created_at: chrono::Utc::now(),
+    };
+    Ok(Json(node.submit_feedback(&response_id, feedback).await?))
+}
+
+/// 分页获取Agent列表
+async fn list_agents(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<AgentListQuery>,
+) -> Result<Json<ListPage<AgentSummary>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(
+        node.list_agents_page(&query.page_request(), query.sort_by)
+            .await,
+    ))
+}
+
+/// 分页获取Agent的对话历史，也可以只获取摘要
+async fn get_agent_messages(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    if query.summary {
+        let summary = node.get_agent_messages_summary(&agent_id).await?;
+        return Ok(Json(summary).into_response());
+    }
+    let page = node
+        .get_agent_messages_page(&agent_id, &query.page_request())
+        .await?;
+    Ok(Json(page).into_response())
+}
+
+/// 流式聊天：以SSE逐段返回本次请求的文本（`chunk` 事件），结束时发送 `done` 摘要，
+/// 失败时发送 `error` 事件。与全局事件总线无关，只有发起请求的客户端能收到
+async fn chat_stream(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(agent_id): Path<String>,
+    Extension(context): Extension<RequestContext>,
+    Json(mut request): Json<ChatStreamRequest>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, NodeError> {
+    request.options.context = Some(context);
+    // 只在取句柄时读取节点，调用提供商期间不占用节点的锁
+    let chat = node
+        .read()
+        .await
+        .as_ref()
+        .map(P2PNode::agent_chat)
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel(CHAT_STREAM_BUFFER);
+    tokio::spawn(async move {
+        // 客户端断开后不再推送，回复仍会生成完并写入对话历史
+        let mut events = ChatStreamEvents::new(tx);
+        let result = chat
+            .chat_stream(&agent_id, &request.message, request.options, |content| {
+                events.chunk(content)
+            })
+            .await;
+        events.finish(&agent_id, result).await;
+    });
+
+    Ok(Sse::new(chat_stream_body(rx)).keep_alive(KeepAlive::default()))
+}
+
+/// 流式聊天缓冲的SSE事件数量，客户端读取跟不上时停止推送
+const CHAT_STREAM_BUFFER: usize = 64;
+
+/// 流式聊天的SSE事件：依次推送 `chunk`，最后推送 `done` 或 `error`
+struct ChatStreamEvents {
+    tx: tokio::sync::mpsc::Sender<SseEvent>,
+    chunks: usize,
+    /// 发送失败（客户端断开或缓冲已满）后不再推送片段
+    stopped: bool,
+    started: std::time::Instant,
+}
+
+impl ChatStreamEvents {
+    fn new(tx: tokio::sync::mpsc::Sender<SseEvent>) -> Self {
+        Self {
+            tx,
+            chunks: 0,
+            stopped: false,
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// 推送一段文本，片段回调是同步的，缓冲已满时无法等待，只能停止推送
+    fn chunk(&mut self, content: &str) {
+        if self.stopped {
+            return;
+        }
+        let chunk = ChatStreamChunk {
+            index: self.chunks,
+            content: content.to_string(),
+        };
+        self.chunks += 1;
+        let event = SseEvent::default()
+            .event("chunk")
+            .id(chunk.index.to_string())
+            .json_data(&chunk)
+            .unwrap_or_default();
+        if self.tx.try_send(event).is_err() {
+            self.stopped = true;
+        }
+    }
+
+    /// 推送结束事件，有片段未能送达时以 `error` 结束
+    async fn finish(self, agent_id: &str, result: NodeResult<AgentResponse>) {
+        if self.tx.is_closed() {
+            return;
+        }
+        let event = match result {
+            Ok(_) if self.stopped => SseEvent::default().event("error").json_data(&ApiError {
+                message: "客户端读取过慢，部分片段未能送达".to_string(),
+            }),
+            Ok(response) => {
+                let summary = ChatStreamSummary {
+                    response_id: response.id,
+                    model: response.model,
+                    usage: response.usage,
+                    finish_reason: response.finish_reason,
+                    warnings: response.warnings,
+                    variant: response.variant,
+                    chunks: self.chunks,
+                    content_length: response.content.len(),
+                    duration_ms: self.started.elapsed().as_millis() as u64,
+                };
+                SseEvent::default().event("done").json_data(&summary)
+            }
+            Err(e) => {
+                warn!("Agent {} 流式聊天失败: {}", agent_id, e);
+                SseEvent::default().event("error").json_data(&ApiError {
+                    message: e.to_string(),
+                })
+            }
+        };
+        let _ = self.tx.send(event.unwrap_or_default()).await;
+    }
+}
+
+/// 把流式聊天的事件转换为SSE响应体，发送端全部丢弃后流随之结束
+fn chat_stream_body(
+    rx: tokio::sync::mpsc::Receiver<SseEvent>,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> {
+    futures_lite::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|event| (Ok(event), rx))
+    })
+}
+
+/// 导出Agent带反馈的问答，可导出为JSON Lines
+async fn export_feedback(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<FeedbackQuery>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let records: Vec<FeedbackRecord> = node.export_feedback(&agent_id).await?;
+    Ok(match query.format {
+        FeedbackFormat::Json => Json(records).into_response(),
+        FeedbackFormat::Jsonl => (
+            [
+                (header::CONTENT_TYPE, "application/jsonl; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}-feedback.jsonl\"", agent_id),
+                ),
+            ],
+            feedback_to_jsonl(&records),
+        )
+            .into_response(),
+    })
+}
+
+/// 以附件形式下载Agent包文件
+async fn export_agent_package(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(agent_id): Path<String>,
+) -> Result<Response, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let package = node.export_agent_package(&agent_id).await?;
+    let bytes = package
+        .to_bytes()
+        .map_err(|e| NodeError::EncodeError(format!("编码Agent包失败: {}", e)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", agent_id, PACKAGE_EXTENSION),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// 从上传的包文件重建Agent
+async fn import_agent_package(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Query(query): Query<ImportPackageQuery>,
+    body: axum::body::Bytes,
+) -> Result<Json<ImportPackageResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let package = AgentPackage::from_bytes(&body)
+        .map_err(|e| NodeError::DecodeError(format!("解析Agent包失败: {}", e)))?;
+    let agent_id = node.import_agent_package(&package, query.agent_id).await?;
+    info!("已从包 {} 导入Agent {}", package.name, agent_id);
+    Ok(Json(ImportPackageResponse { agent_id }))
+}
+
+/// 加入模板交换的发现话题
+async fn join_template_exchange(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<TemplateExchangeResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let topic_id = node.join_template_exchange().await?;
+    Ok(Json(TemplateExchangeResponse {
+        topic_id: topic_id.to_string(),
+    }))
+}
+
+/// 列出发现话题中已通告的Agent模板
+async fn list_templates(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+) -> Result<Json<Vec<RemoteTemplate>>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    Ok(Json(node.list_templates().await))
+}
+
+/// 把Agent作为模板分享
+async fn share_template(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Json(request): Json<ShareTemplateRequest>,
+) -> Result<Json<TemplateAnnouncement>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let announcement = node
+        .share_template(&request.agent_id, request.description)
+        .await?;
+    Ok(Json(announcement))
+}
+
+/// 停止分享Agent模板
+async fn unshare_template(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(hash): Path<String>,
+) -> Result<(), NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    if node.unshare_template(&hash).await {
+        Ok(())
+    } else {
+        Err(NodeError::ConfigError(format!("未分享该模板: {}", hash)))
+    }
+}
+
+/// 获取Agent模板并导入为本节点的Agent
+async fn import_template(
+    State(node): State<Arc<RwLock<Option<P2PNode>>>>,
+    Path(hash): Path<String>,
+    Query(query): Query<ImportPackageQuery>,
+) -> Result<Json<ImportPackageResponse>, NodeError> {
+    let node_read = node.read().await;
+    let node = node_read
+        .as_ref()
+        .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+
+    let agent_id = node.import_template(&hash, query.agent_id).await?;
+    info!("已从模板 {} 导入Agent {}", hash, agent_id);
+    Ok(Json(ImportPackageResponse { agent_id }))
+}
+
+/// 以附件形式返回CSV
+fn csv_response(filename: &str, csv: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        csv,
+    )
+        .into_response()
+}
+
+/// 查询最近的日志
+async fn get_logs(Query(query): Query<LogQuery>) -> Result<Json<Vec<LogEntry>>, NodeError> {
+    Ok(Json(LogBuffer::global().query(&query)?))
+}
+
+/// 以纯文本附件导出日志，便于附在问题反馈中
+async fn export_logs(Query(query): Query<LogQuery>) -> Result<Response, NodeError> {
+    let entries = LogBuffer::global().query(&query)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "attachment; filename=\"logs.txt\""),
+        ],
+        logs_to_text(&entries),
+    )
+        .into_response())
+}
+
+/// 订阅实时日志（SSE），过滤条件与查询接口相同
+async fn stream_logs(
+    Query(query): Query<LogQuery>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, NodeError> {
+    let filter = query.filter()?;
+    let receiver = LogBuffer::global().subscribe();
+
+    let stream = futures_lite::stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(entry) if filter.matches(&entry) => {
+                    let sse_event = SseEvent::default()
+                        .event("log")
+                        .id(entry.seq.to_string())
+                        .json_data(&entry)
+                        .unwrap_or_default();
+                    return Some((Ok(sse_event), (receiver, filter)));
+                }
+                // 落后时不记录警告，避免日志订阅本身产生新的日志
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 /// 停止节点
 async fn stop_node(State(node): State<Arc<RwLock<Option<P2PNode>>>>) -> Result<(), NodeError> {
     let node_option = {
@@ -396,4 +2581,111 @@ async fn stop_node(State(node): State<Arc<RwLock<Option<P2PNode>>>>) -> Result<(
     } else {
         Err(NodeError::ConfigError("节点未初始化".to_string()))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按顺序取出SSE响应体中的事件名
+    async fn event_names(rx: tokio::sync::mpsc::Receiver<SseEvent>) -> Vec<String> {
+        let response = Sse::new(chat_stream_body(rx)).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("event: "))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn response(content: &str) -> AgentResponse {
+        AgentResponse {
+            id: "response".to_string(),
+            agent_id: "agent".to_string(),
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            model: "mock".to_string(),
+            usage: None,
+            tool_calls: None,
+            citations: Vec::new(),
+            compression: None,
+            creativity: None,
+            fingerprint: None,
+            finish_reason: Some(FinishReason::Stop),
+            warnings: Vec::new(),
+            variant: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_event_order() {
+        // 成功时片段在前，最后是 done
+        let (tx, rx) = tokio::sync::mpsc::channel(CHAT_STREAM_BUFFER);
+        let mut events = ChatStreamEvents::new(tx);
+        events.chunk("你好");
+        events.chunk("世界");
+        events.finish("agent", Ok(response("你好世界"))).await;
+        assert_eq!(event_names(rx).await, ["chunk", "chunk", "done"]);
+
+        // 失败时以 error 结束
+        let (tx, rx) = tokio::sync::mpsc::channel(CHAT_STREAM_BUFFER);
+        let mut events = ChatStreamEvents::new(tx);
+        events.chunk("你好");
+        events
+            .finish("agent", Err(NodeError::AgentError("提供商不可用".to_string())))
+            .await;
+        assert_eq!(event_names(rx).await, ["chunk", "error"]);
+
+        // 缓冲已满后停止推送片段，以 error 结束
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let mut events = ChatStreamEvents::new(tx);
+        events.chunk("你好");
+        events.chunk("世界");
+        events.chunk("！");
+        let finished = tokio::spawn(events.finish("agent", Ok(response("你好世界！"))));
+        assert_eq!(event_names(rx).await, ["chunk", "error"]);
+        finished.await.unwrap();
+
+        // 客户端断开后发送失败，不再推送
+        let (tx, rx) = tokio::sync::mpsc::channel(CHAT_STREAM_BUFFER);
+        drop(rx);
+        let mut events = ChatStreamEvents::new(tx);
+        events.chunk("你好");
+        assert!(events.stopped);
+        events.finish("agent", Ok(response("你好"))).await;
+    }
+
+    #[tokio::test]
+    async fn test_topic_chat_stream_filters_events() {
+        let delivery = |topic_id: &str, message_id: &str| NodeEvent::DeliveryStatusChanged {
+            topic_id: topic_id.to_string(),
+            message_id: message_id.to_string(),
+            status: Default::default(),
+        };
+        let (tx, receiver) = tokio::sync::broadcast::channel(16);
+        tx.send(delivery("a", "m1")).unwrap();
+        // 其他话题的聊天事件和本话题的非聊天事件都不推送
+        tx.send(delivery("b", "m2")).unwrap();
+        tx.send(NodeEvent::TopicJoined {
+            topic_id: "a".to_string(),
+        })
+        .unwrap();
+        tx.send(delivery("a", "m3")).unwrap();
+        drop(tx);
+
+        let response = Sse::new(topic_chat_body(receiver, "a".to_string())).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let names: Vec<_> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("event: "))
+            .collect();
+        assert_eq!(names, ["delivery-status-changed", "delivery-status-changed"]);
+        assert!(body.contains("m1") && body.contains("m3"));
+        assert!(!body.contains("m2"));
+    }
+}
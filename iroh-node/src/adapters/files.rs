@@ -0,0 +1,733 @@
+//! 共享文件的下载
+//!
+//! 浏览器不运行 iroh 节点也能获取共享的文件：`GET /api/iroh/files/:doc/:name`
+//! 把文件内容流式写入HTTP响应，支持 `Range` 断点续传，并根据文件头嗅探内容类型。
+//! 路径中的 `:doc` 是共享名称：设置了 blob 存储时先按 `共享名称/文件名` 标签查找 blob 哈希，
+//! 从 iroh 的 blob 存储读取内容（例如上传时导入的文件）；
+//! 没有对应的 blob 时再查找配置的本地目录，只能下载已经落在这些目录中的文件。
+//! `GET /api/iroh/files/:doc` 列出共享的文件及其预览，
+//! `GET /api/iroh/files/:doc/:name/thumbnail` 返回缩略图，接收方可以先浏览再下载。
+//! 目录中按分类规则（[`DownloadRoutes`]）放入子目录的文件也按文件名提供。
+//! 下载受并发数量与带宽限制，超出并发时返回 429
+
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path as UrlPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use futures_lite::{stream, StreamExt};
+use iroh_blobs::{
+    api::{proto::BlobStatus, Store},
+    Hash,
+};
+use serde::Serialize;
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tracing::{debug, warn};
+
+use super::{
+    axum::ApiError,
+    preview::{self, FilePreview},
+    upload::blob_tag,
+};
+use crate::DownloadRoutes;
+
+/// 每次从文件读取的字节数
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+/// 嗅探内容类型时读取的字节数
+pub(crate) const SNIFF_BYTES: usize = 512;
+/// 并发已满时建议的重试间隔（秒）
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// 文件下载配置
+#[derive(Debug, Clone)]
+pub struct FileShareConfig {
+    /// 共享名称到本地目录的映射
+    pub docs: BTreeMap<String, PathBuf>,
+    /// 同时进行的最大下载数量
+    pub max_concurrent_downloads: usize,
+    /// 每个下载的带宽上限（字节/秒），未设置时不限速
+    pub max_bytes_per_sec: Option<u64>,
+    /// 共享目录中文件的分类规则，与接收文件时使用的规则一致
+    pub routes: DownloadRoutes,
+    /// blob 存储，按 `共享名称/文件名` 标签提供其中的文件，未设置时只提供本地目录中的文件
+    pub blobs: Option<Store>,
+}
+
+impl Default for FileShareConfig {
+    fn default() -> Self {
+        Self {
+            docs: BTreeMap::new(),
+            max_concurrent_downloads: 8,
+            max_bytes_per_sec: None,
+            routes: DownloadRoutes::default(),
+            blobs: None,
+        }
+    }
+}
+
+impl FileShareConfig {
+    /// 共享一个目录
+    pub fn with_doc(mut self, name: impl Into<String>, dir: impl Into<PathBuf>) -> Self {
+        self.docs.insert(name.into(), dir.into());
+        self
+    }
+
+    /// 设置同时进行的最大下载数量
+    pub fn with_max_concurrent_downloads(mut self, max_concurrent_downloads: usize) -> Self {
+        self.max_concurrent_downloads = max_concurrent_downloads;
+        self
+    }
+
+    /// 设置每个下载的带宽上限（字节/秒）
+    pub fn with_max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        self.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+
+    /// 设置共享目录中文件的分类规则
+    pub fn with_routes(mut self, routes: DownloadRoutes) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// 提供 blob 存储中的文件，例如 [`P2PNode::blob_store`](crate::P2PNode::blob_store)
+    pub fn with_blobs(mut self, blobs: Store) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+}
+
+/// 文件下载代理
+pub struct FileShare {
+    config: FileShareConfig,
+    downloads: Arc<Semaphore>,
+}
+
+impl FileShare {
+    /// 创建文件下载代理
+    pub fn new(config: FileShareConfig) -> Self {
+        let downloads = Arc::new(Semaphore::new(config.max_concurrent_downloads.max(1)));
+        Self { config, downloads }
+    }
+
+    /// 配置
+    pub fn config(&self) -> &FileShareConfig {
+        &self.config
+    }
+
+    /// 创建下载路由
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/iroh/files/:doc", get(list_files))
+            .route("/api/iroh/files/:doc/:name", get(download_file))
+            .route(
+                "/api/iroh/files/:doc/:name/thumbnail",
+                get(download_thumbnail),
+            )
+            .with_state(self)
+    }
+
+    /// 查找共享目录中的文件，拒绝目录穿越；文件按分类规则放在子目录中时返回子目录中的路径
+    fn resolve(&self, doc: &str, name: &str) -> Option<PathBuf> {
+        let dir = self.config.docs.get(doc)?;
+        if !valid_name(name) {
+            return None;
+        }
+        let routed = self.config.routes.dir_for(dir, name).join(name);
+        Some(if routed.is_file() {
+            routed
+        } else {
+            dir.join(name)
+        })
+    }
+
+    /// 按 `共享名称/文件名` 标签查找 blob 存储中完整的文件，返回 blob 哈希与字节数
+    async fn resolve_blob(&self, doc: &str, name: &str) -> Option<(Hash, u64)> {
+        let blobs = self.config.blobs.as_ref()?;
+        if !valid_name(name) {
+            return None;
+        }
+        let tag = blobs.tags().get(blob_tag(doc, name)).await.ok()??;
+        match blobs.status(tag.hash).await {
+            Ok(BlobStatus::Complete { size }) => Some((tag.hash, size)),
+            _ => None,
+        }
+    }
+
+    /// 列出 blob 存储中共享名称下完整的文件
+    async fn list_blobs(&self, doc: &str) -> Vec<SharedFile> {
+        let Some(blobs) = &self.config.blobs else {
+            return Vec::new();
+        };
+        let prefix = blob_tag(doc, "");
+        let tags = match blobs.tags().list_prefix(&prefix).await {
+            Ok(tags) => tags,
+            Err(e) => {
+                warn!("列出blob存储中的共享文件失败: {}", e);
+                return Vec::new();
+            }
+        };
+        let mut tags = std::pin::pin!(tags);
+        let mut shared = Vec::new();
+        while let Some(Ok(tag)) = tags.next().await {
+            let Some(name) = std::str::from_utf8(tag.name.as_ref())
+                .ok()
+                .and_then(|tag| tag.strip_prefix(&prefix))
+                .filter(|name| valid_name(name))
+            else {
+                continue;
+            };
+            if let Ok(BlobStatus::Complete { size }) = blobs.status(tag.hash).await {
+                shared.push(SharedFile {
+                    name: name.to_string(),
+                    size,
+                    modified: None,
+                    preview: None,
+                    hash: Some(tag.hash.to_string()),
+                });
+            }
+        }
+        shared
+    }
+}
+
+/// 文件名不能为空、不能是隐藏文件，也不能包含路径分隔符或控制字符
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control)
+}
+
+/// 共享目录中的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedFile {
+    /// 文件名
+    pub name: String,
+    /// 字节数
+    pub size: u64,
+    /// 修改时间
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+    /// 上传时提取的预览
+    pub preview: Option<FilePreview>,
+    /// 文件在 blob 存储中的哈希，本地目录中的文件为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+/// 请求的字节范围，`end` 包含在内
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+/// 解析 `Range` 请求头
+///
+/// 只支持单个范围；无法识别或包含多个范围时返回 `Ok(None)` 以完整返回文件，
+/// 范围超出文件大小时返回 `Err(())`
+fn parse_range(value: &str, len: u64) -> Result<Option<ByteRange>, ()> {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // 最后 N 个字节
+        let Ok(suffix) = end.parse::<u64>() else {
+            return Ok(None);
+        };
+        if suffix == 0 || len == 0 {
+            return Err(());
+        }
+        ByteRange {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return Ok(None);
+        };
+        let end = if end.is_empty() {
+            u64::MAX
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) if end >= start => end,
+                _ => return Ok(None),
+            }
+        };
+        if start >= len {
+            return Err(());
+        }
+        ByteRange {
+            start,
+            end: end.min(len - 1),
+        }
+    };
+    Ok(Some(range))
+}
+
+/// 根据文件头与扩展名判断内容类型
+///
+/// HTML、SVG 等可执行脚本的类型按纯文本返回，避免共享文件在API的源下运行
+pub(crate) fn sniff_content_type(head: &[u8], name: &str) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"\x1a\x45\xdf\xa3", "video/webm"),
+    ];
+    if let Some((_, content_type)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return content_type;
+    }
+    if head.len() >= 12 && head.starts_with(b"RIFF") {
+        match &head[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            _ => {}
+        }
+    }
+    if head.len() >= 8 && &head[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+
+    // 文件头末尾可能截断了多字节字符
+    let text = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && e.valid_up_to() + 4 > head.len(),
+    };
+    if !text || head.contains(&0) {
+        return "application/octet-stream";
+    }
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("md") | Some("markdown") => "text/markdown; charset=utf-8",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+/// 下载错误响应
+fn reject(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(ApiError {
+            message: message.into(),
+        }),
+    )
+        .into_response()
+}
+
+/// 可定位读取的共享文件内容，来自本地文件或 blob 存储
+trait SharedContent: AsyncRead + AsyncSeek + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncSeek + Send + Unpin> SharedContent for T {}
+
+/// 按带宽上限读取文件的状态
+struct ThrottledRead {
+    file: Box<dyn SharedContent>,
+    remaining: u64,
+    max_bytes_per_sec: Option<u64>,
+    started: Instant,
+    sent: u64,
+    /// 下载结束前一直占用并发名额
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ThrottledRead {
+    async fn next_chunk(mut self) -> Option<(std::io::Result<Bytes>, Self)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if let Some(rate) = self.max_bytes_per_sec.filter(|rate| *rate > 0) {
+            let due = Duration::from_secs_f64(self.sent as f64 / rate as f64);
+            if let Some(wait) = due.checked_sub(self.started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+
+        let len = self.remaining.min(READ_CHUNK_BYTES as u64) as usize;
+        let mut buffer = vec![0; len];
+        match self.file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                self.remaining -= read as u64;
+                self.sent += read as u64;
+                Some((Ok(Bytes::from(buffer)), self))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some((Err(e), self))
+            }
+        }
+    }
+}
+
+/// 列出共享目录中的文件，按文件名排序
+async fn list_files(
+    State(files): State<Arc<FileShare>>,
+    UrlPath(doc): UrlPath<String>,
+) -> Response {
+    let mut shared = files.list_blobs(&doc).await;
+    let dir = files.config.docs.get(&doc);
+    if dir.is_none() && shared.is_empty() {
+        return reject(StatusCode::NOT_FOUND, format!("共享目录不存在: {}", doc));
+    }
+
+    if let Some(dir) = dir {
+        let entries = match fs::read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                return reject(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("读取共享目录失败: {}", e),
+                )
+            }
+        };
+        list_dir(&files, &doc, dir, entries, &mut shared).await;
+        // 按分类规则放入子目录的文件，只列出规则指向该子目录的文件
+        for rule in &files.config.routes.rules {
+            let subdir = dir.join(&rule.subdir);
+            if let Ok(entries) = fs::read_dir(&subdir).await {
+                list_dir(&files, &doc, &subdir, entries, &mut shared).await;
+            }
+        }
+    }
+    shared.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(shared).into_response()
+}
+
+/// 列出目录中可按名称下载的文件
+async fn list_dir(
+    files: &FileShare,
+    doc: &str,
+    dir: &Path,
+    mut entries: fs::ReadDir,
+    shared: &mut Vec<SharedFile>,
+) {
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if files.resolve(doc, &name).as_deref() != Some(entry.path().as_path()) {
+            continue;
+        }
+        // 同名文件已导入 blob 存储时按 blob 提供
+        if shared.iter().any(|file| file.name == name) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        shared.push(SharedFile {
+            preview: preview::load(dir, &name).await,
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(Into::into),
+            hash: None,
+            name,
+        });
+    }
+}
+
+/// 下载共享文件的缩略图
+async fn download_thumbnail(
+    State(files): State<Arc<FileShare>>,
+    UrlPath((doc, name)): UrlPath<(String, String)>,
+) -> Response {
+    let data = match files.resolve(&doc, &name) {
+        Some(path) => match path.parent() {
+            Some(dir) => fs::read(preview::thumbnail_path(dir, &name)).await.ok(),
+            None => None,
+        },
+        None => None,
+    };
+    let Some(data) = data else {
+        return reject(
+            StatusCode::NOT_FOUND,
+            format!("缩略图不存在: {}/{}", doc, name),
+        );
+    };
+    (
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static("image/png")),
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+/// 打开共享文件：优先读取 blob 存储中的文件，其次是本地共享目录中的文件；返回内容与字节数
+async fn open_shared(
+    files: &FileShare,
+    doc: &str,
+    name: &str,
+) -> Option<(Box<dyn SharedContent>, u64)> {
+    if let Some((hash, len)) = files.resolve_blob(doc, name).await {
+        let blobs = files.config.blobs.as_ref()?;
+        return Some((Box::new(blobs.reader(hash)), len));
+    }
+    let file = fs::File::open(files.resolve(doc, name)?).await.ok()?;
+    match file.metadata().await {
+        Ok(metadata) if metadata.is_file() => Some((Box::new(file), metadata.len())),
+        _ => None,
+    }
+}
+
+/// 下载共享文件
+async fn download_file(
+    State(files): State<Arc<FileShare>>,
+    UrlPath((doc, name)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let Some((mut file, len)) = open_shared(&files, &doc, &name).await else {
+        return reject(
+            StatusCode::NOT_FOUND,
+            format!("文件不存在: {}/{}", doc, name),
+        );
+    };
+
+    let Ok(permit) = files.downloads.clone().try_acquire_owned() else {
+        let mut response = reject(StatusCode::TOO_MANY_REQUESTS, "同时进行的下载过多");
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+        return response;
+    };
+
+    let range = match headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| parse_range(value, len))
+        .transpose()
+    {
+        Ok(range) => range.flatten(),
+        Err(()) => {
+            let mut response = reject(StatusCode::RANGE_NOT_SATISFIABLE, "请求的范围超出文件大小");
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+                response.headers_mut().insert(header::CONTENT_RANGE, value);
+            }
+            return response;
+        }
+    };
+
+    let mut head = vec![0; SNIFF_BYTES.min(len as usize)];
+    if let Err(e) = file.read_exact(&mut head).await {
+        return reject(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("读取文件失败: {}", e),
+        );
+    }
+    let content_type = sniff_content_type(&head, &name);
+
+    let (status, start, body_len) = match range {
+        Some(range) => (
+            StatusCode::PARTIAL_CONTENT,
+            range.start,
+            range.end - range.start + 1,
+        ),
+        None => (StatusCode::OK, 0, len),
+    };
+    if let Err(e) = file.seek(SeekFrom::Start(start)).await {
+        return reject(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("读取文件失败: {}", e),
+        );
+    }
+    debug!("下载 {}/{}: {} 字节，起始 {}", doc, name, body_len, start);
+
+    let read = ThrottledRead {
+        file,
+        remaining: body_len,
+        max_bytes_per_sec: files.config.max_bytes_per_sec,
+        started: Instant::now(),
+        sent: 0,
+        _permit: permit,
+    };
+    let body = Body::from_stream(stream::unfold(read, ThrottledRead::next_chunk));
+
+    let mut response = (status, body).into_response();
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    if let Some(range) = range {
+        if let Ok(value) =
+            HeaderValue::from_str(&format!("bytes {}-{}/{}", range.start, range.end, len))
+        {
+            response_headers.insert(header::CONTENT_RANGE, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_and_content_type() {
+        let range = |start, end| Ok(Some(ByteRange { start, end }));
+        assert_eq!(parse_range("bytes=0-99", 1000), range(0, 99));
+        assert_eq!(parse_range("bytes=900-", 1000), range(900, 999));
+        assert_eq!(parse_range("bytes=-100", 1000), range(900, 999));
+        assert_eq!(parse_range("bytes=990-2000", 1000), range(990, 999));
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), Ok(None));
+        assert_eq!(parse_range("items=0-1", 1000), Ok(None));
+
+        assert_eq!(
+            sniff_content_type(b"\x89PNG\r\n\x1a\n....", "a.bin"),
+            "image/png"
+        );
+        assert_eq!(
+            sniff_content_type(b"RIFF\0\0\0\0WEBPVP8 ", "a"),
+            "image/webp"
+        );
+        assert_eq!(
+            sniff_content_type(b"{\"a\": 1}", "data.json"),
+            "application/json"
+        );
+        assert_eq!(
+            sniff_content_type(b"<script>alert(1)</script>", "x.html"),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(
+            sniff_content_type(b"\0\x01\x02", "a.txt"),
+            "application/octet-stream"
+        );
+
+        let files = FileShare::new(FileShareConfig::default().with_doc("shared", "/srv/shared"));
+        assert_eq!(
+            files.resolve("shared", "报告.pdf"),
+            Some(PathBuf::from("/srv/shared/报告.pdf"))
+        );
+        assert_eq!(files.resolve("shared", "../etc/passwd"), None);
+        assert_eq!(files.resolve("shared", ".env"), None);
+        assert_eq!(files.resolve("other", "a.txt"), None);
+
+        // 按分类规则放入子目录的文件
+        let dir =
+            std::env::temp_dir().join(format!("iroh-node-files-{}", crate::chat::new_message_id()));
+        std::fs::create_dir_all(dir.join("Docs")).unwrap();
+        std::fs::write(dir.join("Docs").join("notes.md"), "notes").unwrap();
+        let files = FileShare::new(
+            FileShareConfig::default()
+                .with_doc("shared", &dir)
+                .with_routes(DownloadRoutes::defaults()),
+        );
+        assert_eq!(
+            files.resolve("shared", "notes.md"),
+            Some(dir.join("Docs").join("notes.md"))
+        );
+        assert_eq!(
+            files.resolve("shared", "photo.png"),
+            Some(dir.join("photo.png"))
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_download_from_blob_store() {
+        let blobs = iroh_blobs::store::mem::MemStore::new();
+        let content = b"hello from the blob store";
+        let imported = blobs
+            .add_slice(content)
+            .with_named_tag(blob_tag("uploads", "hello.txt"))
+            .await
+            .unwrap();
+        let files = Arc::new(FileShare::new(
+            FileShareConfig::default().with_blobs(blobs.as_ref().clone()),
+        ));
+
+        // 共享名称与文件名对应的标签解析为 blob 哈希
+        assert_eq!(
+            files.resolve_blob("uploads", "hello.txt").await,
+            Some((imported.hash, content.len() as u64))
+        );
+        assert_eq!(files.resolve_blob("uploads", "missing.txt").await, None);
+        assert_eq!(files.resolve_blob("other", "hello.txt").await, None);
+        let listed = files.list_blobs("uploads").await;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "hello.txt");
+        assert_eq!(listed[0].hash, Some(imported.hash.to_string()));
+
+        // 按范围读取 blob 内容
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=6-9"));
+        let response = download_file(
+            State(files.clone()),
+            UrlPath(("uploads".to_string(), "hello.txt".to_string())),
+            headers,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[header::CONTENT_RANGE],
+            format!("bytes 6-9/{}", content.len()).as_str()
+        );
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; charset=utf-8"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"from");
+
+        let response = download_file(
+            State(files),
+            UrlPath(("uploads".to_string(), "hello.txt".to_string())),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], content);
+    }
+}
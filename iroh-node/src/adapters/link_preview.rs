@@ -0,0 +1,543 @@
+//! 链接预览服务
+//!
+//! 网关在后台抓取聊天消息中链接的 Open Graph 元数据，以 [`NodeEvent::LinkPreviewReady`]
+//! 附在聊天事件流中推送，客户端不必各自访问不可信的地址。
+//!
+//! 抓取时防止借网关访问内部服务（SSRF）：
+//! - 只允许 http/https 与默认端口，不允许带用户名密码的地址
+//! - 解析出的地址必须全部是公网地址，请求固定连接到校验过的地址，防止DNS重绑定
+//! - 重定向逐跳重新校验，跳数有上限
+//! - 只读取HTML，响应体大小与总耗时有上限
+//!
+//! 结果（包括失败）按链接缓存，过期后重新抓取
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use reqwest::{header, redirect::Policy, StatusCode};
+use serde::Deserialize;
+use tokio::sync::{broadcast::error::RecvError, Mutex, RwLock, Semaphore};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{
+    links::{extract_links, is_public_ip},
+    LinkPreview, NodeError, NodeEvent, NodeResult, P2PNode,
+};
+
+/// 等待节点初始化时的检查间隔
+const NODE_WAIT_INTERVAL: Duration = Duration::from_secs(1);
+/// 抓取时标识网关的User-Agent
+const USER_AGENT: &str = concat!("iroh-node-link-preview/", env!("CARGO_PKG_VERSION"));
+/// 预览文本字段的最大字符数
+const MAX_FIELD_CHARS: usize = 300;
+
+/// 链接预览配置
+#[derive(Debug, Clone)]
+pub struct LinkPreviewConfig {
+    /// 单次抓取（包括重定向）的超时
+    pub timeout: Duration,
+    /// 读取的最大响应字节数，Open Graph 标签通常在文档开头
+    pub max_body_bytes: usize,
+    /// 最多跟随的重定向次数
+    pub max_redirects: usize,
+    /// 每条消息最多预览的链接数
+    pub max_links_per_message: usize,
+    /// 缓存有效期
+    pub cache_ttl: Duration,
+    /// 缓存的最大条数
+    pub cache_capacity: usize,
+    /// 同时进行的抓取数
+    pub concurrency: usize,
+}
+
+impl Default for LinkPreviewConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            max_body_bytes: 256 * 1024,
+            max_redirects: 3,
+            max_links_per_message: 3,
+            cache_ttl: Duration::from_secs(3600),
+            cache_capacity: 512,
+            concurrency: 4,
+        }
+    }
+}
+
+impl LinkPreviewConfig {
+    /// 设置抓取超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// 设置读取的最大响应字节数
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// 设置每条消息最多预览的链接数
+    pub fn with_max_links_per_message(mut self, max_links_per_message: usize) -> Self {
+        self.max_links_per_message = max_links_per_message;
+        self
+    }
+
+    /// 设置缓存有效期
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+}
+
+/// 缓存的抓取结果，失败时为空
+struct CachedPreview {
+    at: Instant,
+    preview: Option<LinkPreview>,
+}
+
+/// 链接预览服务
+pub struct LinkPreviewer {
+    config: LinkPreviewConfig,
+    cache: Mutex<HashMap<String, CachedPreview>>,
+    permits: Semaphore,
+}
+
+impl LinkPreviewer {
+    /// 创建链接预览服务
+    pub fn new(config: LinkPreviewConfig) -> Self {
+        let permits = Semaphore::new(config.concurrency.max(1));
+        Self {
+            config,
+            cache: Mutex::new(HashMap::new()),
+            permits,
+        }
+    }
+
+    /// 配置
+    pub fn config(&self) -> &LinkPreviewConfig {
+        &self.config
+    }
+
+    /// 获取链接的预览，优先使用缓存
+    pub async fn preview(&self, url: &str) -> NodeResult<LinkPreview> {
+        if let Some(cached) = self.cache.lock().await.get(url) {
+            if cached.at.elapsed() < self.config.cache_ttl {
+                return cached
+                    .preview
+                    .clone()
+                    .ok_or_else(|| NodeError::InvalidMessage(format!("无法预览链接: {}", url)));
+            }
+        }
+
+        let parsed = Url::parse(url)
+            .map_err(|e| NodeError::InvalidMessage(format!("解析链接失败: {}", e)))?;
+        let result = {
+            let _permit = self.permits.acquire().await;
+            tokio::time::timeout(self.config.timeout, self.fetch(parsed))
+                .await
+                .unwrap_or_else(|_| Err(NodeError::IoError("抓取链接超时".to_string())))
+        };
+
+        let mut cache = self.cache.lock().await;
+        if cache.len() >= self.config.cache_capacity {
+            let ttl = self.config.cache_ttl;
+            cache.retain(|_, cached| cached.at.elapsed() < ttl);
+            // 仍然已满时丢弃最早的一条
+            if cache.len() >= self.config.cache_capacity {
+                if let Some(oldest) = cache
+                    .iter()
+                    .min_by_key(|(_, cached)| cached.at)
+                    .map(|(url, _)| url.clone())
+                {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+        cache.insert(
+            url.to_string(),
+            CachedPreview {
+                at: Instant::now(),
+                preview: result.as_ref().ok().cloned(),
+            },
+        );
+        result
+    }
+
+    /// 逐跳抓取，每一跳都重新校验地址
+    async fn fetch(&self, url: Url) -> NodeResult<LinkPreview> {
+        let original = url.to_string();
+        let mut current = url;
+        for _ in 0..=self.config.max_redirects {
+            let (host, addr) = resolve_public(&current).await?;
+            let client = reqwest::Client::builder()
+                .redirect(Policy::none())
+                .resolve(&host, addr)
+                .user_agent(USER_AGENT)
+                .build()
+                .map_err(|e| NodeError::IoError(format!("创建HTTP客户端失败: {}", e)))?;
+            let mut response = client
+                .get(current.clone())
+                .header(header::ACCEPT, "text/html,application/xhtml+xml")
+                .send()
+                .await
+                .map_err(|e| NodeError::IoError(format!("抓取链接失败: {}", e)))?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .ok_or_else(|| NodeError::IoError("重定向缺少目标地址".to_string()))?;
+                current = current
+                    .join(location)
+                    .map_err(|e| NodeError::IoError(format!("解析重定向地址失败: {}", e)))?;
+                debug!("链接 {} 重定向到 {}", original, current);
+                continue;
+            }
+            if response.status() != StatusCode::OK {
+                return Err(NodeError::IoError(format!(
+                    "抓取链接失败: HTTP {}",
+                    response.status()
+                )));
+            }
+            let is_html = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| {
+                    let value = value.to_ascii_lowercase();
+                    value.starts_with("text/html") || value.starts_with("application/xhtml+xml")
+                });
+            if !is_html {
+                return Err(NodeError::InvalidMessage("链接不是网页".to_string()));
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| NodeError::IoError(format!("读取网页失败: {}", e)))?
+            {
+                let remaining = self.config.max_body_bytes - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+                if body.len() >= self.config.max_body_bytes {
+                    break;
+                }
+            }
+            let html = String::from_utf8_lossy(&body);
+            return Ok(parse_preview(&html, original, &current));
+        }
+        Err(NodeError::IoError(format!(
+            "重定向超过 {} 次",
+            self.config.max_redirects
+        )))
+    }
+
+    /// 为聊天消息中的链接生成预览，逐个推送到节点事件总线
+    async fn preview_message(
+        self: Arc<Self>,
+        node: Arc<RwLock<Option<P2PNode>>>,
+        topic_id: String,
+        message_id: String,
+        text: String,
+    ) {
+        for url in extract_links(&text, self.config.max_links_per_message) {
+            let preview = match self.preview(url.as_str()).await {
+                Ok(preview) => preview,
+                Err(e) => {
+                    debug!("无法预览链接 {}: {}", url, e);
+                    continue;
+                }
+            };
+            let node = node.read().await;
+            let Some(node) = node.as_ref() else {
+                return;
+            };
+            node.event_bus().publish(NodeEvent::LinkPreviewReady {
+                topic_id: topic_id.clone(),
+                message_id: message_id.clone(),
+                preview,
+            });
+        }
+    }
+
+    /// 在后台为收到和发出的聊天消息生成预览；节点未初始化或重新初始化时等待并重新订阅
+    pub async fn run(self: Arc<Self>, node: Arc<RwLock<Option<P2PNode>>>) {
+        info!("链接预览服务已启动");
+        loop {
+            let receiver = node.read().await.as_ref().map(P2PNode::subscribe_events);
+            let Some(mut receiver) = receiver else {
+                tokio::time::sleep(NODE_WAIT_INTERVAL).await;
+                continue;
+            };
+            loop {
+                let entry = match receiver.recv().await {
+                    Ok(
+                        NodeEvent::ChatReceived { entry, .. } | NodeEvent::ChatSent { entry, .. },
+                    ) => entry,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("链接预览服务落后，跳过 {} 条事件", skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if entry.locked || !entry.text.contains("http") {
+                    continue;
+                }
+                tokio::spawn(self.clone().preview_message(
+                    node.clone(),
+                    entry.topic_id,
+                    entry.id,
+                    entry.text,
+                ));
+            }
+        }
+    }
+
+    /// 创建按需预览的路由
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/link-preview", get(get_link_preview))
+            .with_state(self)
+    }
+}
+
+/// 解析主机并确认全部地址为公网地址，返回用于固定连接的主机名与地址
+async fn resolve_public(url: &Url) -> NodeResult<(String, SocketAddr)> {
+    let port = match url.scheme() {
+        "http" => 80,
+        "https" => 443,
+        scheme => {
+            return Err(NodeError::InvalidMessage(format!(
+                "不支持的链接协议: {}",
+                scheme
+            )))
+        }
+    };
+    if url.port().is_some_and(|p| p != port) {
+        return Err(NodeError::InvalidMessage(
+            "不允许非默认端口的链接".to_string(),
+        ));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(NodeError::InvalidMessage(
+            "不允许带用户名密码的链接".to_string(),
+        ));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| NodeError::InvalidMessage("链接缺少主机".to_string()))?
+        .to_string();
+
+    let lookup = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((lookup.as_str(), port))
+        .await
+        .map_err(|e| NodeError::IoError(format!("解析主机 {} 失败: {}", host, e)))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(NodeError::IoError(format!("主机 {} 没有地址", host)));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(NodeError::InvalidMessage(format!(
+            "链接指向非公网地址: {}",
+            addr.ip()
+        )));
+    }
+    Ok((host, addrs[0]))
+}
+
+/// 从网页开头解析 Open Graph 元数据
+fn parse_preview(html: &str, url: String, final_url: &Url) -> LinkPreview {
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find("<meta") {
+        let start = offset + start;
+        let Some(end) = lower[start..].find('>') else {
+            break;
+        };
+        let tag = &html[start + 5..start + end];
+        offset = start + end + 1;
+
+        let attrs = parse_attributes(tag);
+        let key = attrs
+            .get("property")
+            .or_else(|| attrs.get("name"))
+            .map(|key| key.to_ascii_lowercase());
+        if let (Some(key), Some(content)) = (key, attrs.get("content")) {
+            meta.entry(key).or_insert_with(|| content.clone());
+        }
+    }
+
+    let title = meta.get("og:title").cloned().or_else(|| {
+        let start = lower.find("<title")?;
+        let start = start + lower[start..].find('>')? + 1;
+        let end = start + lower[start..].find("</title")?;
+        Some(decode_entities(&html[start..end]))
+    });
+    let description = meta
+        .get("og:description")
+        .or_else(|| meta.get("description"))
+        .cloned();
+    // 图片地址只接受 http/https，并解析为绝对地址
+    let image = meta
+        .get("og:image")
+        .and_then(|image| final_url.join(image).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(String::from);
+
+    LinkPreview {
+        url,
+        final_url: final_url.to_string(),
+        title: title.and_then(clean_field),
+        description: description.and_then(clean_field),
+        site_name: meta.get("og:site_name").cloned().and_then(clean_field),
+        image,
+        fetched_at: chrono::Utc::now(),
+    }
+}
+
+/// 解析标签属性，属性名转为小写，值解码HTML实体
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = tag.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let inner = &after[1..];
+                        let end = inner.find(quote).unwrap_or(inner.len());
+                        (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = remaining.trim_start();
+                decode_entities(value)
+            }
+            None => {
+                // 跳过没有值的属性与自闭合的 `/`
+                rest = rest.trim_start_matches('/').trim_start();
+                String::new()
+            }
+        };
+        if !name.is_empty() {
+            attrs.entry(name).or_insert(value);
+        }
+    }
+    attrs
+}
+
+/// 解码常见的HTML实体
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// 合并空白、去除控制字符并截断，空字段返回 None
+fn clean_field(text: String) -> Option<String> {
+    let cleaned: String = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_FIELD_CHARS)
+        .collect();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
+
+/// 按需预览请求
+#[derive(Debug, Deserialize)]
+struct LinkPreviewQuery {
+    /// 链接
+    url: String,
+}
+
+/// 获取链接预览
+async fn get_link_preview(
+    State(previewer): State<Arc<LinkPreviewer>>,
+    Query(query): Query<LinkPreviewQuery>,
+) -> Result<Json<LinkPreview>, NodeError> {
+    Ok(Json(previewer.preview(&query.url).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_link_preview() {
+        let html = r#"<html><head>
+            <title>备用标题</title>
+            <meta property="og:title" content="Tom &amp; Jerry" />
+            <META name='description' content='一部
+              动画片'>
+            <meta property="og:image" content="/cover.png">
+            <meta property="og:site_name" content="">
+        </head>"#;
+        let final_url = Url::parse("https://example.com/show").unwrap();
+        let preview = parse_preview(html, "https://example.com/s".to_string(), &final_url);
+        assert_eq!(preview.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(preview.description.as_deref(), Some("一部 动画片"));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/cover.png")
+        );
+        assert!(preview.site_name.is_none());
+
+        let preview = parse_preview("<title> 只有标题 </title>", String::new(), &final_url);
+        assert_eq!(preview.title.as_deref(), Some("只有标题"));
+
+        // 内网地址、非默认端口与其他协议在发起请求前即被拒绝
+        let previewer = LinkPreviewer::new(LinkPreviewConfig::default());
+        for url in [
+            "http://127.0.0.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/",
+            "https://example.com:8443/",
+            "http://user:pw@example.com/",
+            "file:///etc/passwd",
+        ] {
+            assert!(matches!(
+                previewer.preview(url).await,
+                Err(NodeError::InvalidMessage(_))
+            ));
+        }
+        // 失败结果被缓存
+        assert!(previewer
+            .cache
+            .lock()
+            .await
+            .contains_key("http://127.0.0.1/"));
+    }
+}
@@ -0,0 +1,695 @@
+//! Matrix桥接
+//!
+//! 以机器人账号登录Matrix，把一个P2P聊天房间（话题）与一个Matrix房间双向镜像：
+//! 两边的聊天消息都带上发送者署名转发到对方；Matrix中以命令前缀开头的消息作为Agent请求
+//! 发到P2P网络，Agent的响应以通知消息发回Matrix房间
+
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+use iroh_gossip::proto::TopicId;
+use reqwest::{Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    watch, RwLock,
+};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{chat::new_message_id, AgentRequestMode, NodeError, NodeEvent, NodeResult, P2PNode};
+
+/// 默认的Agent命令前缀
+const DEFAULT_COMMAND_PREFIX: &str = "!agent";
+/// 默认的同步长轮询超时（秒）
+const DEFAULT_SYNC_TIMEOUT_SECS: u64 = 30;
+/// 同步失败或被限流后的默认重试间隔
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// 被限流时的最大重试次数
+const MAX_RATE_LIMIT_RETRIES: usize = 3;
+/// 节点ID在署名中保留的字符数
+const SHORT_ID_CHARS: usize = 10;
+
+/// Matrix登录方式
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MatrixAuth {
+    /// 使用已有的访问令牌
+    AccessToken {
+        /// 访问令牌
+        access_token: String,
+    },
+    /// 使用机器人账号的用户名和密码登录
+    Password {
+        /// 用户名或完整的用户ID
+        user: String,
+        /// 密码
+        password: String,
+    },
+}
+
+impl fmt::Debug for MatrixAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccessToken { .. } => f.debug_struct("AccessToken").finish_non_exhaustive(),
+            Self::Password { user, .. } => f
+                .debug_struct("Password")
+                .field("user", user)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Matrix桥接配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixBridgeConfig {
+    /// Matrix服务器地址，如 `https://matrix.org`
+    pub homeserver: String,
+    /// 登录方式
+    pub auth: MatrixAuth,
+    /// Matrix房间ID或别名（`!id:server` 或 `#alias:server`）
+    pub room: String,
+    /// 镜像的P2P话题ID
+    pub topic_id: String,
+    /// Matrix中触发Agent请求的命令前缀，格式为 `<前缀> <agent_id> <问题>`
+    #[serde(default = "default_command_prefix")]
+    pub command_prefix: String,
+    /// 同步长轮询超时（秒）
+    #[serde(default = "default_sync_timeout_secs")]
+    pub sync_timeout_secs: u64,
+    /// Agent请求超时（秒），为空时使用默认超时
+    #[serde(default)]
+    pub agent_timeout_secs: Option<u64>,
+}
+
+fn default_command_prefix() -> String {
+    DEFAULT_COMMAND_PREFIX.to_string()
+}
+
+fn default_sync_timeout_secs() -> u64 {
+    DEFAULT_SYNC_TIMEOUT_SECS
+}
+
+impl MatrixBridgeConfig {
+    /// 创建桥接配置
+    pub fn new(
+        homeserver: impl Into<String>,
+        auth: MatrixAuth,
+        room: impl Into<String>,
+        topic_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            homeserver: homeserver.into(),
+            auth,
+            room: room.into(),
+            topic_id: topic_id.into(),
+            command_prefix: default_command_prefix(),
+            sync_timeout_secs: DEFAULT_SYNC_TIMEOUT_SECS,
+            agent_timeout_secs: None,
+        }
+    }
+
+    /// 设置Agent命令前缀
+    pub fn with_command_prefix(mut self, command_prefix: impl Into<String>) -> Self {
+        self.command_prefix = command_prefix.into();
+        self
+    }
+
+    /// 设置同步长轮询超时（秒）
+    pub fn with_sync_timeout_secs(mut self, sync_timeout_secs: u64) -> Self {
+        self.sync_timeout_secs = sync_timeout_secs;
+        self
+    }
+
+    /// 设置Agent请求超时（秒）
+    pub fn with_agent_timeout_secs(mut self, agent_timeout_secs: u64) -> Self {
+        self.agent_timeout_secs = Some(agent_timeout_secs);
+        self
+    }
+}
+
+/// Matrix房间中的一条消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MatrixMessage {
+    /// 发送者用户ID
+    sender: String,
+    /// 消息文本
+    body: String,
+}
+
+/// 解析Agent命令，返回Agent ID与问题
+fn parse_agent_command<'a>(body: &'a str, prefix: &str) -> Option<(&'a str, &'a str)> {
+    let rest = body.trim().strip_prefix(prefix)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (agent_id, prompt) = rest.trim_start().split_once(char::is_whitespace)?;
+    let prompt = prompt.trim();
+    (!prompt.is_empty()).then_some((agent_id, prompt))
+}
+
+/// 节点在署名中显示的名称：地址簿中的名称，没有时使用缩短的节点ID
+fn sender_label(node_id: &str, name: Option<&str>) -> String {
+    let short_id: String = node_id.chars().take(SHORT_ID_CHARS).collect();
+    match name {
+        Some(name) => format!("{} ({})", name, short_id),
+        None => short_id,
+    }
+}
+
+/// 同步响应
+#[derive(Debug, Deserialize)]
+struct SyncResponse {
+    next_batch: String,
+    #[serde(default)]
+    rooms: SyncRooms,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SyncRooms {
+    #[serde(default)]
+    join: HashMap<String, JoinedRoom>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct JoinedRoom {
+    #[serde(default)]
+    timeline: Timeline,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    events: Vec<RoomEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RoomEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    sender: String,
+    #[serde(default)]
+    content: Value,
+}
+
+impl SyncResponse {
+    /// 取出房间中其他用户发送的文本消息；通知消息（`m.notice`）通常来自机器人，不转发以免循环
+    fn room_messages(&self, room_id: &str, own_user_id: &str) -> Vec<MatrixMessage> {
+        let Some(room) = self.rooms.join.get(room_id) else {
+            return Vec::new();
+        };
+        room.timeline
+            .events
+            .iter()
+            .filter(|event| event.kind == "m.room.message" && event.sender != own_user_id)
+            .filter_map(|event| {
+                let msgtype = event.content.get("msgtype")?.as_str()?;
+                let body = event.content.get("body")?.as_str()?;
+                let body = match msgtype {
+                    "m.text" => body.to_string(),
+                    "m.emote" => format!("* {}", body),
+                    _ => return None,
+                };
+                Some(MatrixMessage {
+                    sender: event.sender.clone(),
+                    body,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    access_token: String,
+    user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoAmIResponse {
+    user_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JoinResponse {
+    room_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    event_id: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MatrixErrorBody {
+    #[serde(default)]
+    errcode: String,
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    retry_after_ms: Option<u64>,
+}
+
+/// Matrix客户端-服务器API的最小客户端
+struct MatrixClient {
+    http: reqwest::Client,
+    homeserver: Url,
+    access_token: String,
+    user_id: String,
+}
+
+impl MatrixClient {
+    /// 登录并确定机器人的用户ID
+    async fn login(homeserver: &str, auth: &MatrixAuth) -> NodeResult<Self> {
+        let homeserver = Url::parse(homeserver)
+            .map_err(|e| NodeError::ConfigError(format!("解析Matrix服务器地址失败: {}", e)))?;
+        let mut client = Self {
+            http: reqwest::Client::new(),
+            homeserver,
+            access_token: String::new(),
+            user_id: String::new(),
+        };
+
+        match auth {
+            MatrixAuth::AccessToken { access_token } => {
+                client.access_token = access_token.clone();
+                let whoami: WhoAmIResponse = client
+                    .request(Method::GET, &["account", "whoami"], &[], None)
+                    .await?;
+                client.user_id = whoami.user_id;
+            }
+            MatrixAuth::Password { user, password } => {
+                let body = json!({
+                    "type": "m.login.password",
+                    "identifier": { "type": "m.id.user", "user": user },
+                    "password": password,
+                    "initial_device_display_name": "iroh-node bridge",
+                });
+                let login: LoginResponse = client
+                    .request(Method::POST, &["login"], &[], Some(body))
+                    .await?;
+                client.access_token = login.access_token;
+                client.user_id = login.user_id;
+            }
+        }
+        Ok(client)
+    }
+
+    fn endpoint(&self, segments: &[&str]) -> NodeResult<Url> {
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| NodeError::ConfigError("Matrix服务器地址无效".to_string()))?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// 发送请求，被限流时按服务器给出的间隔重试
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        segments: &[&str],
+        query: &[(&str, String)],
+        body: Option<Value>,
+    ) -> NodeResult<T> {
+        let url = self.endpoint(segments)?;
+        let mut retries = 0;
+        loop {
+            let mut request = self.http.request(method.clone(), url.clone()).query(query);
+            if !self.access_token.is_empty() {
+                request = request.bearer_auth(&self.access_token);
+            }
+            if let Some(body) = &body {
+                request = request.json(body);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| NodeError::IoError(format!("Matrix请求失败: {}", e)))?;
+
+            let status = response.status();
+            if status.is_success() {
+                return response
+                    .json()
+                    .await
+                    .map_err(|e| NodeError::DecodeError(format!("解析Matrix响应失败: {}", e)));
+            }
+            let error: MatrixErrorBody = response.json().await.unwrap_or_default();
+            if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RATE_LIMIT_RETRIES {
+                retries += 1;
+                let delay = error
+                    .retry_after_ms
+                    .map_or(RETRY_DELAY, Duration::from_millis);
+                warn!("Matrix请求被限流，{:?} 后重试", delay);
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            return Err(NodeError::IoError(format!(
+                "Matrix请求失败: HTTP {} {} {}",
+                status, error.errcode, error.error
+            )));
+        }
+    }
+
+    /// 加入房间（已加入时不变），返回房间ID
+    async fn join(&self, room: &str) -> NodeResult<String> {
+        let joined: JoinResponse = self
+            .request(Method::POST, &["join", room], &[], Some(json!({})))
+            .await?;
+        Ok(joined.room_id)
+    }
+
+    /// 发送消息，返回事件ID
+    async fn send(&self, room_id: &str, msgtype: &str, body: &str) -> NodeResult<String> {
+        let txn_id = new_message_id();
+        let sent: SendResponse = self
+            .request(
+                Method::PUT,
+                &["rooms", room_id, "send", "m.room.message", &txn_id],
+                &[],
+                Some(json!({ "msgtype": msgtype, "body": body })),
+            )
+            .await?;
+        Ok(sent.event_id)
+    }
+
+    /// 增量同步，只关注桥接的房间
+    async fn sync(
+        &self,
+        room_id: &str,
+        since: Option<&str>,
+        timeout: Duration,
+    ) -> NodeResult<SyncResponse> {
+        let filter = json!({
+            "presence": { "not_types": ["*"] },
+            "account_data": { "not_types": ["*"] },
+            "room": {
+                "rooms": [room_id],
+                "timeline": { "types": ["m.room.message"] },
+                "state": { "lazy_load_members": true },
+                "ephemeral": { "not_types": ["*"] },
+                "account_data": { "not_types": ["*"] },
+            },
+        });
+        let mut query = vec![
+            ("timeout", timeout.as_millis().to_string()),
+            ("filter", filter.to_string()),
+        ];
+        if let Some(since) = since {
+            query.push(("since", since.to_string()));
+        }
+        self.request(Method::GET, &["sync"], &query, None).await
+    }
+}
+
+/// 运行中的Matrix桥接，停止或丢弃时结束转发
+pub struct MatrixBridge {
+    room_id: String,
+    user_id: String,
+    topic_id: TopicId,
+    shutdown: watch::Sender<bool>,
+}
+
+impl MatrixBridge {
+    /// 登录Matrix、加入房间并开始双向转发。节点必须已加入要镜像的话题
+    pub async fn start(
+        config: MatrixBridgeConfig,
+        node: Arc<RwLock<Option<P2PNode>>>,
+    ) -> NodeResult<Self> {
+        let topic_id: TopicId = config
+            .topic_id
+            .parse()
+            .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+        let events = {
+            let node_read = node.read().await;
+            let node = node_read
+                .as_ref()
+                .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+            if !node.get_active_topics().await.contains(&topic_id) {
+                return Err(NodeError::TopicError(format!(
+                    "节点未加入话题: {}",
+                    topic_id
+                )));
+            }
+            node.subscribe_events()
+        };
+
+        let client = MatrixClient::login(&config.homeserver, &config.auth).await?;
+        let room_id = client.join(&config.room).await?;
+        let user_id = client.user_id.clone();
+        info!(
+            "Matrix桥接已启动: 话题 {} <-> 房间 {} ({})",
+            topic_id, room_id, user_id
+        );
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let context = Arc::new(BridgeContext {
+            client,
+            node,
+            topic_id,
+            room_id: room_id.clone(),
+            config,
+        });
+        tokio::spawn(context.clone().relay_to_matrix(events, shutdown_rx.clone()));
+        tokio::spawn(context.relay_to_p2p(shutdown_rx));
+
+        Ok(Self {
+            room_id,
+            user_id,
+            topic_id,
+            shutdown,
+        })
+    }
+
+    /// 桥接的Matrix房间ID
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    /// 机器人的Matrix用户ID
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// 镜像的P2P话题ID
+    pub fn topic_id(&self) -> TopicId {
+        self.topic_id
+    }
+
+    /// 停止转发
+    pub fn stop(&self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+impl Drop for MatrixBridge {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// 转发任务共享的状态
+struct BridgeContext {
+    client: MatrixClient,
+    node: Arc<RwLock<Option<P2PNode>>>,
+    topic_id: TopicId,
+    room_id: String,
+    config: MatrixBridgeConfig,
+}
+
+impl BridgeContext {
+    /// 把话题中收到的聊天消息转发到Matrix房间
+    async fn relay_to_matrix(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<NodeEvent>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let topic_key = self.topic_id.to_string();
+        loop {
+            let event = tokio::select! {
+                _ = shutdown.changed() => break,
+                event = events.recv() => event,
+            };
+            match event {
+                Ok(NodeEvent::ChatReceived {
+                    topic_id, entry, ..
+                }) if topic_id == topic_key => {
+                    let name = self.peer_name(&entry.from).await;
+                    let body = format!(
+                        "{}: {}",
+                        sender_label(&entry.from, name.as_deref()),
+                        entry.text
+                    );
+                    if let Err(e) = self.client.send(&self.room_id, "m.text", &body).await {
+                        warn!("转发聊天消息到Matrix失败: {}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Matrix桥接落后，跳过 {} 个节点事件", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+        debug!("Matrix桥接停止转发P2P消息");
+    }
+
+    /// 把Matrix房间中的消息转发到话题，Agent命令作为Agent请求发送
+    async fn relay_to_p2p(self: Arc<Self>, mut shutdown: watch::Receiver<bool>) {
+        let timeout = Duration::from_secs(self.config.sync_timeout_secs);
+        let mut since: Option<String> = None;
+        loop {
+            let result = tokio::select! {
+                _ = shutdown.changed() => break,
+                result = self.client.sync(&self.room_id, since.as_deref(), timeout) => result,
+            };
+            let sync = match result {
+                Ok(sync) => sync,
+                Err(e) => {
+                    warn!("Matrix同步失败: {}", e);
+                    tokio::select! {
+                        _ = shutdown.changed() => break,
+                        _ = tokio::time::sleep(RETRY_DELAY) => continue,
+                    }
+                }
+            };
+            // 首次同步只取得同步位置，不转发桥接启动前的历史消息
+            if since.is_some() {
+                for message in sync.room_messages(&self.room_id, &self.client.user_id) {
+                    self.clone().handle_message(message).await;
+                }
+            }
+            since = Some(sync.next_batch);
+        }
+        debug!("Matrix桥接停止同步");
+    }
+
+    async fn handle_message(self: Arc<Self>, message: MatrixMessage) {
+        if let Some((agent_id, prompt)) =
+            parse_agent_command(&message.body, &self.config.command_prefix)
+        {
+            let (agent_id, prompt) = (agent_id.to_string(), prompt.to_string());
+            // 等待Agent响应可能较久，不阻塞同步
+            tokio::spawn(async move {
+                let reply = match self.request_agent(&agent_id, &prompt).await {
+                    Ok(reply) => reply,
+                    Err(e) => format!("Agent {} 请求失败: {}", agent_id, e),
+                };
+                if let Err(e) = self.client.send(&self.room_id, "m.notice", &reply).await {
+                    warn!("转发Agent响应到Matrix失败: {}", e);
+                }
+            });
+            return;
+        }
+
+        let text = format!("{}: {}", message.sender, message.body);
+        let result = match self.node.read().await.as_ref() {
+            Some(node) => node.send_chat(&self.topic_id, &text).await.map(|_| ()),
+            None => Err(NodeError::ConfigError("节点未初始化".to_string())),
+        };
+        if let Err(e) = result {
+            warn!("转发Matrix消息到话题失败: {}", e);
+        }
+    }
+
+    /// 发送Agent请求并等待结果，返回发回Matrix的文本
+    async fn request_agent(&self, agent_id: &str, prompt: &str) -> NodeResult<String> {
+        let handle = {
+            let node_read = self.node.read().await;
+            let node = node_read
+                .as_ref()
+                .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+            node.send_agent_request(
+                &self.topic_id,
+                agent_id,
+                prompt,
+                AgentRequestMode::Broadcast,
+                self.config.agent_timeout_secs.map(Duration::from_secs),
+            )
+            .await?
+        };
+
+        let outcome = handle.wait().await?;
+        Ok(match outcome.consensus {
+            Some(content) => format!("{}: {}", agent_id, content),
+            None => format!("Agent {} 未在超时前响应", agent_id),
+        })
+    }
+
+    /// 地址簿中记录的节点名称
+    async fn peer_name(&self, node_id: &str) -> Option<String> {
+        let node_read = self.node.read().await;
+        let node = node_read.as_ref()?;
+        node.get_address_book()
+            .await
+            .into_iter()
+            .find(|record| record.node_id == node_id)
+            .and_then(|record| record.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_bridge_parsing() {
+        assert_eq!(
+            parse_agent_command("!agent helper  今天天气如何？", "!agent"),
+            Some(("helper", "今天天气如何？"))
+        );
+        assert_eq!(parse_agent_command("!agenthelper 你好", "!agent"), None);
+        assert_eq!(parse_agent_command("!agent helper", "!agent"), None);
+        assert_eq!(
+            parse_agent_command("你好 !agent helper 你好", "!agent"),
+            None
+        );
+
+        assert_eq!(sender_label("abcdefghijklmnop", None), "abcdefghij");
+        assert_eq!(
+            sender_label("abcdefghijklmnop", Some("小明")),
+            "小明 (abcdefghij)"
+        );
+
+        let sync: SyncResponse = serde_json::from_value(json!({
+            "next_batch": "s2",
+            "rooms": { "join": { "!room:example.org": { "timeline": { "events": [
+                { "type": "m.room.message", "sender": "@alice:example.org",
+                  "content": { "msgtype": "m.text", "body": "你好" } },
+                { "type": "m.room.message", "sender": "@bot:example.org",
+                  "content": { "msgtype": "m.text", "body": "回声" } },
+                { "type": "m.room.message", "sender": "@other-bot:example.org",
+                  "content": { "msgtype": "m.notice", "body": "通知" } },
+                { "type": "m.room.member", "sender": "@bob:example.org",
+                  "content": { "membership": "join" } },
+                { "type": "m.room.message", "sender": "@bob:example.org",
+                  "content": { "msgtype": "m.emote", "body": "挥手" } },
+            ] } } } }
+        }))
+        .unwrap();
+        assert_eq!(
+            sync.room_messages("!room:example.org", "@bot:example.org"),
+            vec![
+                MatrixMessage {
+                    sender: "@alice:example.org".to_string(),
+                    body: "你好".to_string(),
+                },
+                MatrixMessage {
+                    sender: "@bob:example.org".to_string(),
+                    body: "* 挥手".to_string(),
+                },
+            ]
+        );
+        assert!(sync
+            .room_messages("!other:example.org", "@bot:example.org")
+            .is_empty());
+
+        let auth = MatrixAuth::Password {
+            user: "bot".to_string(),
+            password: "secret".to_string(),
+        };
+        assert!(!format!("{:?}", auth).contains("secret"));
+    }
+}
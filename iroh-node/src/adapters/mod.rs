@@ -1,20 +1,55 @@
 //! 适配器模块
 //!
-//! 提供不同环境的适配器，如Tauri和Axum
+//! 提供不同环境的适配器，如Tauri和Axum，以及与浏览器、Matrix的桥接
 
+#[cfg(feature = "axum-adapter")]
 pub mod axum;
+#[cfg(feature = "axum-adapter")]
+pub mod files;
+#[cfg(feature = "link-preview")]
+pub mod link_preview;
+#[cfg(feature = "matrix-bridge")]
+pub mod matrix_bridge;
+#[cfg(feature = "axum-adapter")]
+pub mod preview;
+#[cfg(feature = "axum-adapter")]
+pub mod progress;
+#[cfg(feature = "axum-adapter")]
+pub mod request_context;
+#[cfg(feature = "axum-adapter")]
+pub mod request_guard;
+#[cfg(feature = "tauri-plugin")]
 pub mod tauri;
-pub mod tauri_adapter;
+#[cfg(feature = "axum-adapter")]
+pub mod upload;
+#[cfg(feature = "axum-adapter")]
+pub mod watch_folder;
+#[cfg(feature = "webrtc-bridge")]
+pub mod webrtc_bridge;
 
-pub use self::{
-    axum::AxumAdapter, 
-    tauri::TauriAdapter as TauriAdapterV1,
-    tauri_adapter::TauriPlugin as TauriAdapterV2
-};
+#[cfg(feature = "axum-adapter")]
+pub use self::axum::AxumAdapter;
+#[cfg(feature = "axum-adapter")]
+pub use self::files::{FileShare, FileShareConfig, SharedFile};
+#[cfg(feature = "axum-adapter")]
+pub use self::preview::{FilePreview, PreviewConfig};
+#[cfg(feature = "axum-adapter")]
+pub use self::progress::{ProgressConfig, ProgressRegistry};
+#[cfg(feature = "axum-adapter")]
+pub use self::request_context::{attach_request_context, context_from_headers};
+#[cfg(feature = "axum-adapter")]
+pub use self::request_guard::{sign_request, RequestGuard, RequestGuardConfig};
+#[cfg(feature = "axum-adapter")]
+pub use self::upload::{UploadConfig, UploadManager};
+#[cfg(feature = "axum-adapter")]
+pub use self::watch_folder::{FolderWatcher, FolderWatcherConfig, WatchEvent, WatchFolderConfig};
 
-// 根据Tauri版本导出适当的适配器
-#[cfg(feature = "tauri-compat")]
-pub use self::tauri_adapter::TauriPlugin;
+#[cfg(feature = "link-preview")]
+pub use self::link_preview::{LinkPreviewConfig, LinkPreviewer};
+#[cfg(feature = "matrix-bridge")]
+pub use self::matrix_bridge::{MatrixAuth, MatrixBridge, MatrixBridgeConfig};
+#[cfg(feature = "webrtc-bridge")]
+pub use self::webrtc_bridge::{WebRtcBridge, WebRtcBridgeConfig};
 
 #[cfg(feature = "tauri-plugin")]
-pub use self::tauri::TauriAdapter as TauriPlugin;
+pub use self::tauri::{init as tauri_plugin, Builder as TauriPluginBuilder, PluginConfig};
@@ -0,0 +1,222 @@
+//! 文件预览元数据
+//!
+//! 上传完成时提取轻量的预览信息：内容类型、图片尺寸与缩略图、文本类文档的开头片段，
+//! 保存在文件所在目录的 `.previews` 子目录中。接收方浏览共享目录时可以先看预览再决定下载。
+//! 缩略图由解码后的像素重新编码为PNG，原图中的EXIF（包括拍摄位置）等元数据不会带入
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use image::{ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::debug;
+
+use super::files::{sniff_content_type, SNIFF_BYTES};
+
+/// 预览文件所在的子目录，以 `.` 开头，不会被当作共享文件下载
+pub const PREVIEW_DIR: &str = ".previews";
+
+/// 预览提取配置
+#[derive(Debug, Clone)]
+pub struct PreviewConfig {
+    /// 缩略图的最大边长（像素）
+    pub thumbnail_size: u32,
+    /// 超过该字节数的图片不生成缩略图
+    pub max_image_bytes: u64,
+    /// 文本片段的最大字符数
+    pub snippet_chars: usize,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            thumbnail_size: 256,
+            max_image_bytes: 32 * 1024 * 1024,
+            snippet_chars: 280,
+        }
+    }
+}
+
+impl PreviewConfig {
+    /// 设置缩略图的最大边长
+    pub fn with_thumbnail_size(mut self, thumbnail_size: u32) -> Self {
+        self.thumbnail_size = thumbnail_size;
+        self
+    }
+
+    /// 设置生成缩略图的图片大小上限
+    pub fn with_max_image_bytes(mut self, max_image_bytes: u64) -> Self {
+        self.max_image_bytes = max_image_bytes;
+        self
+    }
+
+    /// 设置文本片段的最大字符数
+    pub fn with_snippet_chars(mut self, snippet_chars: usize) -> Self {
+        self.snippet_chars = snippet_chars;
+        self
+    }
+}
+
+/// 文件预览
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilePreview {
+    /// 内容类型
+    pub mime_type: String,
+    /// 字节数
+    pub size: u64,
+    /// 图片宽度（像素）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// 图片高度（像素）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// 是否有缩略图
+    #[serde(default)]
+    pub thumbnail: bool,
+    /// 文本类文档的开头片段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+/// 预览元数据文件的路径
+fn metadata_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(PREVIEW_DIR).join(format!("{}.json", name))
+}
+
+/// 缩略图的路径
+pub fn thumbnail_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(PREVIEW_DIR).join(format!("{}.png", name))
+}
+
+/// 为目录中的文件提取预览并保存
+pub async fn generate(
+    dir: &Path,
+    name: &str,
+    config: &PreviewConfig,
+) -> std::io::Result<FilePreview> {
+    let (dir, name, config) = (dir.to_path_buf(), name.to_string(), config.clone());
+    tokio::task::spawn_blocking(move || generate_blocking(&dir, &name, &config))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+fn generate_blocking(
+    dir: &Path,
+    name: &str,
+    config: &PreviewConfig,
+) -> std::io::Result<FilePreview> {
+    let path = dir.join(name);
+    let mut file = std::fs::File::open(&path)?;
+    let size = file.metadata()?.len();
+
+    // UTF-8 字符最多4字节
+    let head_len = SNIFF_BYTES.max(config.snippet_chars * 4);
+    let mut head = Vec::with_capacity(head_len);
+    file.by_ref().take(head_len as u64).read_to_end(&mut head)?;
+    let mime_type = sniff_content_type(&head[..head.len().min(SNIFF_BYTES)], name);
+
+    let mut preview = FilePreview {
+        mime_type: mime_type.to_string(),
+        size,
+        width: None,
+        height: None,
+        thumbnail: false,
+        snippet: None,
+    };
+    std::fs::create_dir_all(dir.join(PREVIEW_DIR))?;
+    if mime_type.starts_with("image/") && size <= config.max_image_bytes {
+        match write_thumbnail(&path, &thumbnail_path(dir, name), config.thumbnail_size) {
+            Ok((width, height)) => {
+                preview.width = Some(width);
+                preview.height = Some(height);
+                preview.thumbnail = true;
+            }
+            Err(e) => debug!("生成缩略图失败 {}: {}", path.display(), e),
+        }
+    } else if mime_type.starts_with("text/") || mime_type == "application/json" {
+        preview.snippet = text_snippet(&head, config.snippet_chars);
+    }
+
+    let data = serde_json::to_vec(&preview).map_err(std::io::Error::other)?;
+    std::fs::write(metadata_path(dir, name), data)?;
+    Ok(preview)
+}
+
+/// 解码图片并写入缩略图，返回原图尺寸
+fn write_thumbnail(path: &Path, thumbnail: &Path, size: u32) -> image::ImageResult<(u32, u32)> {
+    let image = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+    let dimensions = (image.width(), image.height());
+    image
+        .thumbnail(size, size)
+        .save_with_format(thumbnail, ImageFormat::Png)?;
+    Ok(dimensions)
+}
+
+/// 取文本开头的若干字符，末尾截断的多字节字符被丢弃
+fn text_snippet(head: &[u8], chars: usize) -> Option<String> {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    let snippet: String = text.trim_start().chars().take(chars).collect();
+    let snippet = snippet.trim_end();
+    (!snippet.is_empty()).then(|| snippet.to_string())
+}
+
+/// 读取已保存的预览
+pub async fn load(dir: &Path, name: &str) -> Option<FilePreview> {
+    let data = fs::read(metadata_path(dir, name)).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// 删除文件的预览
+pub async fn remove(dir: &Path, name: &str) -> std::io::Result<()> {
+    for path in [metadata_path(dir, name), thumbnail_path(dir, name)] {
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_generate_preview() {
+        let dir = std::env::temp_dir().join(format!(
+            "iroh-node-preview-{}",
+            crate::chat::new_message_id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = PreviewConfig::default()
+            .with_thumbnail_size(16)
+            .with_snippet_chars(5);
+
+        let image = image::RgbImage::from_pixel(64, 32, image::Rgb([200, 10, 10]));
+        image.save(dir.join("photo.png")).unwrap();
+        let preview = generate(&dir, "photo.png", &config).await.unwrap();
+        assert_eq!(preview.mime_type, "image/png");
+        assert_eq!((preview.width, preview.height), (Some(64), Some(32)));
+        assert!(preview.thumbnail);
+        let thumbnail = image::open(thumbnail_path(&dir, "photo.png")).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (16, 8));
+
+        std::fs::write(dir.join("notes.md"), "  你好，世界！更多内容").unwrap();
+        let preview = generate(&dir, "notes.md", &config).await.unwrap();
+        assert_eq!(preview.snippet.as_deref(), Some("你好，世界"));
+        assert!(!preview.thumbnail);
+        assert_eq!(load(&dir, "notes.md").await, Some(preview));
+
+        remove(&dir, "photo.png").await.unwrap();
+        assert!(load(&dir, "photo.png").await.is_none());
+        assert!(!thumbnail_path(&dir, "photo.png").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
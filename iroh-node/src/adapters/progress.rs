@@ -0,0 +1,587 @@
+//! 传输进度的SSE推送
+//!
+//! 客户端先创建进度会话，再把会话ID交给上传等传输操作。一个会话可以同时跟踪多个传输，
+//! 允许多个订阅者同时连接。每个会话在环形缓冲区中保留最近的事件，
+//! 断线重连时根据 `Last-Event-ID` 补发错过的事件；错过的事件已被覆盖时先推送一次完整快照。
+//! 会话可以显式删除，也会在长时间没有事件后过期，删除后所有订阅连接随之结束
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::Infallible,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_lite::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+
+use super::axum::ApiError;
+use crate::{chat::new_message_id, PauseReason};
+
+/// 重连时携带最后收到的事件ID的请求头
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// 进度推送配置
+#[derive(Debug, Clone)]
+pub struct ProgressConfig {
+    /// 每个会话保留的最近事件数量，用于重连补发
+    pub replay_capacity: usize,
+    /// 会话多久没有新事件后过期
+    pub session_ttl: Duration,
+}
+
+impl Default for ProgressConfig {
+    fn default() -> Self {
+        Self {
+            replay_capacity: 256,
+            session_ttl: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl ProgressConfig {
+    /// 设置每个会话保留的最近事件数量
+    pub fn with_replay_capacity(mut self, replay_capacity: usize) -> Self {
+        self.replay_capacity = replay_capacity;
+        self
+    }
+
+    /// 设置会话过期时间
+    pub fn with_session_ttl(mut self, session_ttl: Duration) -> Self {
+        self.session_ttl = session_ttl;
+        self
+    }
+}
+
+/// 传输状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+    /// 进行中
+    Running,
+    /// 按传输调度策略暂停，稍后继续
+    Paused,
+    /// 已完成
+    Done,
+    /// 失败或已取消
+    Failed,
+}
+
+/// 进度事件内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProgressKind {
+    /// 传输开始
+    Started { name: String, size: u64 },
+    /// 已传输的字节数
+    Progress { offset: u64 },
+    /// 按分类规则与重名处理确定的最终路径
+    Resolved { path: PathBuf },
+    /// 传输完成
+    Done,
+    /// 传输失败或取消
+    Failed { error: String },
+    /// 按传输调度策略暂停，`retry_after_secs` 秒后可以重试
+    Paused {
+        reason: PauseReason,
+        retry_after_secs: u64,
+    },
+    /// 暂停后恢复
+    Resumed,
+}
+
+impl ProgressKind {
+    /// SSE事件名
+    fn event_name(&self) -> &'static str {
+        match self {
+            ProgressKind::Started { .. } => "started",
+            ProgressKind::Progress { .. } => "progress",
+            ProgressKind::Resolved { .. } => "resolved",
+            ProgressKind::Done => "done",
+            ProgressKind::Failed { .. } => "failed",
+            ProgressKind::Paused { .. } => "paused",
+            ProgressKind::Resumed => "resumed",
+        }
+    }
+}
+
+/// 进度事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    /// 会话内递增的序号，作为SSE事件ID
+    pub seq: u64,
+    /// 传输ID
+    pub transfer_id: String,
+    /// 事件内容
+    #[serde(flatten)]
+    pub kind: ProgressKind,
+    /// 事件时间
+    pub at: DateTime<Utc>,
+}
+
+/// 单个传输的当前进度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferProgress {
+    /// 传输ID
+    pub transfer_id: String,
+    /// 文件名
+    pub name: String,
+    /// 总字节数
+    pub size: u64,
+    /// 已传输的字节数
+    pub offset: u64,
+    /// 状态
+    pub state: TransferState,
+    /// 失败原因
+    pub error: Option<String>,
+    /// 暂停原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<PauseReason>,
+    /// 文件的最终路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// 进度会话的快照
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+    /// 会话ID
+    pub session_id: String,
+    /// 最后一个事件的序号
+    pub last_seq: u64,
+    /// 过期时间
+    pub expires_at: DateTime<Utc>,
+    /// 会话中的传输
+    pub transfers: Vec<TransferProgress>,
+}
+
+/// 订阅结果：需要补发的事件与后续事件的接收端
+pub struct ProgressSubscription {
+    /// 需要补发的事件
+    pub replay: Vec<ProgressEvent>,
+    /// 错过的事件已被覆盖时的完整快照，应先于补发的事件推送
+    pub snapshot: Option<ProgressSnapshot>,
+    /// 后续事件
+    pub receiver: broadcast::Receiver<ProgressEvent>,
+}
+
+struct ProgressSession {
+    transfers: BTreeMap<String, TransferProgress>,
+    recent: VecDeque<ProgressEvent>,
+    last_seq: u64,
+    expires_at: DateTime<Utc>,
+    sender: broadcast::Sender<ProgressEvent>,
+}
+
+impl ProgressSession {
+    fn snapshot(&self, session_id: &str) -> ProgressSnapshot {
+        ProgressSnapshot {
+            session_id: session_id.to_string(),
+            last_seq: self.last_seq,
+            expires_at: self.expires_at,
+            transfers: self.transfers.values().cloned().collect(),
+        }
+    }
+
+    fn apply(&mut self, transfer_id: &str, kind: &ProgressKind) {
+        let transfer = self
+            .transfers
+            .entry(transfer_id.to_string())
+            .or_insert_with(|| TransferProgress {
+                transfer_id: transfer_id.to_string(),
+                name: String::new(),
+                size: 0,
+                offset: 0,
+                state: TransferState::Running,
+                error: None,
+                paused: None,
+                path: None,
+            });
+        match kind {
+            ProgressKind::Started { name, size } => {
+                transfer.name = name.clone();
+                transfer.size = *size;
+                transfer.state = TransferState::Running;
+                transfer.error = None;
+            }
+            ProgressKind::Progress { offset } => transfer.offset = *offset,
+            ProgressKind::Resolved { path } => transfer.path = Some(path.clone()),
+            ProgressKind::Done => {
+                transfer.offset = transfer.size.max(transfer.offset);
+                transfer.state = TransferState::Done;
+                transfer.paused = None;
+            }
+            ProgressKind::Failed { error } => {
+                transfer.state = TransferState::Failed;
+                transfer.error = Some(error.clone());
+                transfer.paused = None;
+            }
+            ProgressKind::Paused { reason, .. } => {
+                transfer.state = TransferState::Paused;
+                transfer.paused = Some(*reason);
+            }
+            ProgressKind::Resumed => {
+                transfer.state = TransferState::Running;
+                transfer.paused = None;
+            }
+        }
+    }
+}
+
+/// 进度会话注册表
+pub struct ProgressRegistry {
+    config: ProgressConfig,
+    sessions: Mutex<HashMap<String, ProgressSession>>,
+}
+
+impl ProgressRegistry {
+    /// 创建进度会话注册表
+    pub fn new(config: ProgressConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn expires_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.config.session_ttl)
+            .ok()
+            .and_then(|ttl| now.checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    /// 创建进度会话
+    pub fn create_session(&self) -> ProgressSnapshot {
+        let now = Utc::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires_at > now);
+
+        let id = new_message_id();
+        let (sender, _) = broadcast::channel(self.config.replay_capacity.max(1));
+        let session = ProgressSession {
+            transfers: BTreeMap::new(),
+            recent: VecDeque::new(),
+            last_seq: 0,
+            expires_at: self.expires_at(now),
+            sender,
+        };
+        let snapshot = session.snapshot(&id);
+        sessions.insert(id, session);
+        snapshot
+    }
+
+    /// 会话是否存在且未过期
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.snapshot(session_id).is_some()
+    }
+
+    /// 会话的当前快照
+    pub fn snapshot(&self, session_id: &str) -> Option<ProgressSnapshot> {
+        let now = Utc::now();
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(session_id)
+            .filter(|session| session.expires_at > now)
+            .map(|session| session.snapshot(session_id))
+    }
+
+    /// 发布进度事件，会话不存在或已过期时返回 `None`
+    pub fn publish(&self, session_id: &str, transfer_id: &str, kind: ProgressKind) -> Option<u64> {
+        let now = Utc::now();
+        let expires_at = self.expires_at(now);
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .filter(|session| session.expires_at > now)?;
+
+        session.apply(transfer_id, &kind);
+        session.last_seq += 1;
+        session.expires_at = expires_at;
+        let event = ProgressEvent {
+            seq: session.last_seq,
+            transfer_id: transfer_id.to_string(),
+            kind,
+            at: now,
+        };
+        if session.recent.len() >= self.config.replay_capacity.max(1) {
+            session.recent.pop_front();
+        }
+        session.recent.push_back(event.clone());
+        // 没有订阅者时发送失败，事件仍保留在缓冲区中
+        let _ = session.sender.send(event);
+        Some(session.last_seq)
+    }
+
+    /// 订阅会话，`last_event_id` 为重连前最后收到的事件序号
+    pub fn subscribe(
+        &self,
+        session_id: &str,
+        last_event_id: Option<u64>,
+    ) -> Option<ProgressSubscription> {
+        let now = Utc::now();
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .filter(|session| session.expires_at > now)?;
+
+        // 在同一把锁内取补发事件并订阅，两者之间不会漏掉事件
+        let receiver = session.sender.subscribe();
+        let (replay, snapshot) = match last_event_id {
+            None => (Vec::new(), Some(session.snapshot(session_id))),
+            Some(last) => {
+                let oldest = session.recent.front().map(|event| event.seq);
+                let missed = oldest.is_some_and(|oldest| oldest > last + 1)
+                    || (oldest.is_none() && session.last_seq > last);
+                let replay: Vec<ProgressEvent> = session
+                    .recent
+                    .iter()
+                    .filter(|event| event.seq > last)
+                    .cloned()
+                    .collect();
+                if missed {
+                    (Vec::new(), Some(session.snapshot(session_id)))
+                } else {
+                    (replay, None)
+                }
+            }
+        };
+
+        Some(ProgressSubscription {
+            replay,
+            snapshot,
+            receiver,
+        })
+    }
+
+    /// 删除会话，返回会话是否存在；所有订阅连接随之结束
+    pub fn expire(&self, session_id: &str) -> bool {
+        self.sessions.lock().unwrap().remove(session_id).is_some()
+    }
+
+    /// 删除过期的会话
+    pub fn purge_expired(&self) -> usize {
+        let now = Utc::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.expires_at > now);
+        before - sessions.len()
+    }
+
+    /// 创建进度路由
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/progress", post(create_progress_session))
+            .route(
+                "/api/progress/:session_id",
+                get(get_progress_session).delete(delete_progress_session),
+            )
+            .route("/api/progress/:session_id/events", get(stream_progress))
+            .with_state(self)
+    }
+}
+
+fn not_found(session_id: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ApiError {
+            message: format!("进度会话不存在或已过期: {}", session_id),
+        }),
+    )
+        .into_response()
+}
+
+fn snapshot_event(snapshot: &ProgressSnapshot) -> SseEvent {
+    SseEvent::default()
+        .event("snapshot")
+        .id(snapshot.last_seq.to_string())
+        .json_data(snapshot)
+        .unwrap_or_default()
+}
+
+fn progress_event(event: &ProgressEvent) -> SseEvent {
+    SseEvent::default()
+        .event(event.kind.event_name())
+        .id(event.seq.to_string())
+        .json_data(event)
+        .unwrap_or_default()
+}
+
+/// 创建进度会话
+async fn create_progress_session(State(registry): State<Arc<ProgressRegistry>>) -> Response {
+    (StatusCode::CREATED, Json(registry.create_session())).into_response()
+}
+
+/// 获取进度会话的快照
+async fn get_progress_session(
+    State(registry): State<Arc<ProgressRegistry>>,
+    UrlPath(session_id): UrlPath<String>,
+) -> Response {
+    match registry.snapshot(&session_id) {
+        Some(snapshot) => Json(snapshot).into_response(),
+        None => not_found(&session_id),
+    }
+}
+
+/// 结束进度会话
+async fn delete_progress_session(
+    State(registry): State<Arc<ProgressRegistry>>,
+    UrlPath(session_id): UrlPath<String>,
+) -> Response {
+    if registry.expire(&session_id) {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        not_found(&session_id)
+    }
+}
+
+/// 订阅进度事件
+async fn stream_progress(
+    State(registry): State<Arc<ProgressRegistry>>,
+    UrlPath(session_id): UrlPath<String>,
+    headers: HeaderMap,
+) -> Response {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let Some(subscription) = registry.subscribe(&session_id, last_event_id) else {
+        return not_found(&session_id);
+    };
+    debug!(
+        "订阅进度会话 {}，补发 {} 个事件",
+        session_id,
+        subscription.replay.len()
+    );
+
+    let initial: Vec<SseEvent> = subscription
+        .snapshot
+        .iter()
+        .map(snapshot_event)
+        .chain(subscription.replay.iter().map(progress_event))
+        .collect();
+    let last_sent = subscription
+        .replay
+        .last()
+        .map(|event| event.seq)
+        .or(subscription
+            .snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.last_seq))
+        .or(last_event_id)
+        .unwrap_or(0);
+
+    let live = stream::unfold(
+        (registry, session_id, subscription.receiver, last_sent),
+        |(registry, session_id, mut receiver, mut last_sent)| async move {
+            loop {
+                match receiver.recv().await {
+                    // 补发过的事件可能再次从通道收到
+                    Ok(event) if event.seq <= last_sent => {}
+                    Ok(event) => {
+                        last_sent = event.seq;
+                        let sse_event = progress_event(&event);
+                        return Some((sse_event, (registry, session_id, receiver, last_sent)));
+                    }
+                    // 落后太多时用快照追上当前进度
+                    Err(RecvError::Lagged(_)) => {
+                        let snapshot = registry.snapshot(&session_id)?;
+                        last_sent = snapshot.last_seq;
+                        let sse_event = snapshot_event(&snapshot);
+                        return Some((sse_event, (registry, session_id, receiver, last_sent)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+    let events = stream::iter(initial).chain(live).map(Ok::<_, Infallible>);
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_replay_and_expiry() {
+        let registry = ProgressRegistry::new(ProgressConfig::default().with_replay_capacity(3));
+        let session = registry.create_session().session_id;
+
+        let started = |name: &str| ProgressKind::Started {
+            name: name.to_string(),
+            size: 100,
+        };
+        registry.publish(&session, "a", started("a.bin"));
+        registry.publish(&session, "b", started("b.bin"));
+        let mut first = registry.subscribe(&session, None).unwrap();
+        let mut second = registry.subscribe(&session, Some(1)).unwrap();
+        assert_eq!(first.snapshot.as_ref().unwrap().transfers.len(), 2);
+        assert_eq!(second.replay.len(), 1);
+        assert!(second.snapshot.is_none());
+
+        // 多个订阅者都收到后续事件
+        registry.publish(&session, "a", ProgressKind::Progress { offset: 40 });
+        assert_eq!(first.receiver.try_recv().unwrap().seq, 3);
+        assert_eq!(second.receiver.try_recv().unwrap().seq, 3);
+
+        // 从缓冲区补发，被覆盖的事件改为推送快照
+        registry.publish(&session, "a", ProgressKind::Done);
+        registry.publish(
+            &session,
+            "b",
+            ProgressKind::Failed {
+                error: "已取消".to_string(),
+            },
+        );
+        let resumed = registry.subscribe(&session, Some(3)).unwrap();
+        assert_eq!(
+            resumed.replay.iter().map(|e| e.seq).collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        let stale = registry.subscribe(&session, Some(1)).unwrap();
+        let snapshot = stale.snapshot.unwrap();
+        assert!(stale.replay.is_empty());
+        assert_eq!(snapshot.last_seq, 5);
+        assert_eq!(snapshot.transfers[0].state, TransferState::Done);
+        assert_eq!(snapshot.transfers[0].offset, 100);
+        assert_eq!(snapshot.transfers[1].error.as_deref(), Some("已取消"));
+
+        // 删除会话后订阅连接结束
+        assert!(registry.expire(&session));
+        let pending: Vec<u64> = std::iter::from_fn(|| first.receiver.try_recv().ok())
+            .map(|event| event.seq)
+            .collect();
+        assert_eq!(pending, vec![4, 5]);
+        assert!(matches!(
+            first.receiver.try_recv(),
+            Err(broadcast::error::TryRecvError::Closed)
+        ));
+        assert!(registry
+            .publish(&session, "a", ProgressKind::Done)
+            .is_none());
+        assert!(registry.subscribe(&session, None).is_none());
+
+        let expiring =
+            ProgressRegistry::new(ProgressConfig::default().with_session_ttl(Duration::ZERO));
+        let session = expiring.create_session().session_id;
+        assert!(!expiring.contains(&session));
+        assert_eq!(expiring.purge_expired(), 1);
+    }
+}
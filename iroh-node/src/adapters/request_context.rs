@@ -0,0 +1,130 @@
+//! 请求上下文
+//!
+//! Axum路由的中间件：根据请求头创建 [`RequestContext`] 并放入请求扩展，处理函数取出后交给Agent，
+//! 响应中带回跟踪ID。调用方ID取自 `x-caller-id`，应由前置的认证代理在校验身份后填写，
+//! 直接暴露的接口应开启请求签名，避免客户端冒充其他调用方
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use rig_agent::{RequestContext, RequestOrigin};
+use std::time::Duration;
+
+/// 调用方ID请求头
+pub const CALLER_ID_HEADER: &str = "x-caller-id";
+/// 跟踪ID请求头，响应中同样带回
+pub const TRACE_ID_HEADER: &str = "x-trace-id";
+/// W3C Trace Context 请求头，未提供 `x-trace-id` 时从中取跟踪ID
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+/// 请求剩余时间（毫秒）请求头
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout-ms";
+
+/// 请求头取值的最大长度
+const MAX_HEADER_VALUE_LEN: usize = 256;
+
+/// 请求上下文中间件
+pub async fn attach_request_context(mut request: Request, next: Next) -> Response {
+    let context = context_from_headers(request.headers());
+    let trace_id = HeaderValue::from_str(&context.trace_id).ok();
+    request.extensions_mut().insert(context);
+
+    let mut response = next.run(request).await;
+    if let Some(trace_id) = trace_id {
+        response.headers_mut().insert(TRACE_ID_HEADER, trace_id);
+    }
+    response
+}
+
+/// 根据请求头创建上下文
+pub fn context_from_headers(headers: &HeaderMap) -> RequestContext {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && value.len() <= MAX_HEADER_VALUE_LEN)
+    };
+
+    let mut context = RequestContext::new(RequestOrigin::Http);
+    if let Some(caller_id) = header(CALLER_ID_HEADER) {
+        context = context.with_caller_id(caller_id);
+    }
+    let trace_id = header(TRACE_ID_HEADER)
+        .filter(|trace_id| {
+            trace_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        .or_else(|| header(TRACEPARENT_HEADER).and_then(traceparent_trace_id));
+    if let Some(trace_id) = trace_id {
+        context = context.with_trace_id(trace_id);
+    }
+    // 只取第一个语言区域，忽略权重
+    if let Some(locale) = header("accept-language")
+        .and_then(|value| value.split([',', ';']).next())
+        .map(str::trim)
+        .filter(|locale| !locale.is_empty() && *locale != "*")
+    {
+        context = context.with_locale(locale);
+    }
+    if let Some(timeout_ms) = header(REQUEST_TIMEOUT_HEADER).and_then(|value| value.parse().ok()) {
+        context = context.with_timeout(Duration::from_millis(timeout_ms));
+    }
+    context
+}
+
+/// 从 `版本-跟踪ID-父ID-标志` 中取出跟踪ID
+fn traceparent_trace_id(traceparent: &str) -> Option<&str> {
+    let mut parts = traceparent.split('-');
+    let _version = parts.next()?;
+    let trace_id = parts.next()?;
+    (trace_id.len() == 32
+        && trace_id.chars().all(|c| c.is_ascii_hexdigit())
+        && trace_id.chars().any(|c| c != '0'))
+    .then_some(trace_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CALLER_ID_HEADER, HeaderValue::from_static("alice"));
+        headers.insert(
+            TRACEPARENT_HEADER,
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        headers.insert(
+            "accept-language",
+            HeaderValue::from_static("zh-CN,zh;q=0.9,en;q=0.8"),
+        );
+        headers.insert(REQUEST_TIMEOUT_HEADER, HeaderValue::from_static("5000"));
+
+        let context = context_from_headers(&headers);
+        assert_eq!(context.origin, RequestOrigin::Http);
+        assert_eq!(context.caller_id.as_deref(), Some("alice"));
+        assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.locale.as_deref(), Some("zh-CN"));
+        assert!(context.remaining_ms().unwrap() <= 5000);
+
+        // 显式的跟踪ID优先，格式不合法时忽略
+        headers.insert(TRACE_ID_HEADER, HeaderValue::from_static("req-42"));
+        assert_eq!(context_from_headers(&headers).trace_id, "req-42");
+        headers.insert(TRACE_ID_HEADER, HeaderValue::from_static("bad id!"));
+        assert_eq!(
+            context_from_headers(&headers).trace_id,
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        // 没有请求头时为匿名请求，生成新的跟踪ID
+        let context = context_from_headers(&HeaderMap::new());
+        assert!(context.caller_id.is_none());
+        assert_eq!(context.trace_id.len(), 32);
+        assert!(context.deadline.is_none());
+    }
+}
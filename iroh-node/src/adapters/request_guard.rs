@@ -0,0 +1,683 @@
+//! 请求签名与幂等
+//!
+//! Axum路由的中间件：
+//! - 可选的HMAC-SHA256请求签名，签名内容为 `时间戳\n方法\n路径\n请求体`，
+//!   时间戳与本机时钟的偏差超过允许范围时拒绝；允许范围内的写请求记录签名，
+//!   同一签名再次出现时拒绝，防止重放。记录的签名数量有上限，
+//!   超出时淘汰时间戳最早的，因此只有在允许范围内的签名写请求不超过上限时才能完全防止重放
+//! - 带 `Idempotency-Key` 头的POST请求在窗口期内重复提交时直接返回第一次的结果，
+//!   不再重新执行，避免网络不稳定的移动端重试时重复发送消息或创建房间。
+//!   流式响应（SSE）不缓冲也不保存结果，重复提交会重新执行
+//! - 签名与幂等需要缓冲完整的请求体，同时缓冲的总字节数有上限，超出时后来的请求等待
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+use super::axum::ApiError;
+
+/// 签名请求头，值为十六进制的HMAC-SHA256
+pub const SIGNATURE_HEADER: &str = "x-signature";
+/// 签名时间戳请求头，值为Unix秒
+pub const SIGNATURE_TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// 幂等键请求头
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// 重放响应的标记头
+pub const IDEMPOTENCY_REPLAYED_HEADER: &str = "idempotency-replayed";
+
+/// 幂等键的最大长度
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 请求保护配置
+#[derive(Debug, Clone)]
+pub struct RequestGuardConfig {
+    /// 签名密钥，未设置时不校验签名
+    pub signing_secret: Option<String>,
+    /// 读请求（GET、HEAD、OPTIONS）是否也需要签名
+    pub sign_reads: bool,
+    /// 签名时间戳允许的时钟偏差
+    pub max_clock_skew: Duration,
+    /// 幂等结果的保留时间
+    pub idempotency_window: Duration,
+    /// 最多保留的幂等结果数量
+    pub max_idempotency_entries: usize,
+    /// 最多记录的签名数量，用于拒绝重放
+    pub max_signature_entries: usize,
+    /// 需要缓冲的请求体与响应体的最大字节数
+    pub max_body_bytes: usize,
+    /// 同时缓冲的请求体总字节数
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for RequestGuardConfig {
+    fn default() -> Self {
+        Self {
+            signing_secret: None,
+            sign_reads: false,
+            max_clock_skew: Duration::from_secs(300),
+            idempotency_window: Duration::from_secs(600),
+            max_idempotency_entries: 1024,
+            max_signature_entries: 4096,
+            max_body_bytes: 16 * 1024 * 1024,
+            max_buffered_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl RequestGuardConfig {
+    /// 设置签名密钥
+    pub fn with_signing_secret<S: Into<String>>(mut self, secret: S) -> Self {
+        self.signing_secret = Some(secret.into());
+        self
+    }
+
+    /// 设置读请求是否也需要签名
+    pub fn with_signed_reads(mut self, sign_reads: bool) -> Self {
+        self.sign_reads = sign_reads;
+        self
+    }
+
+    /// 设置允许的时钟偏差
+    pub fn with_max_clock_skew(mut self, max_clock_skew: Duration) -> Self {
+        self.max_clock_skew = max_clock_skew;
+        self
+    }
+
+    /// 设置幂等结果的保留时间
+    pub fn with_idempotency_window(mut self, window: Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// 设置同时缓冲的请求体总字节数
+    pub fn with_max_buffered_bytes(mut self, max_buffered_bytes: usize) -> Self {
+        self.max_buffered_bytes = max_buffered_bytes;
+        self
+    }
+
+    /// 缓冲额度，单个请求一次占用的额度不能超过 `u32::MAX`
+    fn buffer_budget(&self) -> usize {
+        self.max_buffered_bytes.clamp(1, u32::MAX as usize)
+    }
+}
+
+/// 计算请求签名
+pub fn sign_request(secret: &str, timestamp: i64, method: &str, path: &str, body: &[u8]) -> String {
+    let mut mac = signing_mac(secret, timestamp, method, path);
+    mac.update(body);
+    data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+fn signing_mac(secret: &str, timestamp: i64, method: &str, path: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC可以接受任意长度的密钥");
+    mac.update(format!("{}\n{}\n{}\n", timestamp, method, path).as_bytes());
+    mac
+}
+
+/// 校验请求签名，返回签名时间戳与签名
+fn verify_signature(
+    config: &RequestGuardConfig,
+    secret: &str,
+    headers: &HeaderMap,
+    method: &str,
+    path: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<(i64, Vec<u8>), String> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| format!("缺少请求头 {}", name))
+    };
+    let timestamp: i64 = header(SIGNATURE_TIMESTAMP_HEADER)?
+        .parse()
+        .map_err(|_| "签名时间戳格式错误".to_string())?;
+    if now.abs_diff(timestamp) > config.max_clock_skew.as_secs() {
+        return Err("签名时间戳已过期".to_string());
+    }
+    let signature = data_encoding::HEXLOWER_PERMISSIVE
+        .decode(header(SIGNATURE_HEADER)?.as_bytes())
+        .map_err(|_| "签名格式错误".to_string())?;
+
+    let mut mac = signing_mac(secret, timestamp, method, path);
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| "签名校验失败".to_string())?;
+    Ok((timestamp, signature))
+}
+
+/// 时钟偏差允许范围内见过的签名
+#[derive(Debug)]
+pub struct SignatureCache {
+    max_skew: Duration,
+    max_entries: usize,
+    seen: Mutex<HashMap<Vec<u8>, i64>>,
+}
+
+impl SignatureCache {
+    /// 创建缓存
+    pub fn new(max_skew: Duration, max_entries: usize) -> Self {
+        Self {
+            max_skew,
+            max_entries,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 登记签名，允许范围内已经出现过时返回false
+    pub fn insert(&self, signature: &[u8], timestamp: i64, now: i64) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        // 超出允许范围的签名会被时间戳校验拒绝，不必再记录
+        seen.retain(|_, seen_at| now.abs_diff(*seen_at) <= self.max_skew.as_secs());
+        if seen.contains_key(signature) {
+            return false;
+        }
+
+        if seen.len() >= self.max_entries {
+            let oldest = seen
+                .iter()
+                .min_by_key(|(_, seen_at)| **seen_at)
+                .map(|(signature, _)| signature.clone());
+            if let Some(oldest) = oldest {
+                seen.remove(&oldest);
+            }
+        }
+        seen.insert(signature.to_vec(), timestamp);
+        true
+    }
+}
+
+/// 缓存的响应
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// 状态码
+    pub status: StatusCode,
+    /// 响应头
+    pub headers: HeaderMap,
+    /// 响应体
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    fn into_replay(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response.headers_mut().insert(
+            IDEMPOTENCY_REPLAYED_HEADER,
+            HeaderValue::from_static("true"),
+        );
+        response
+    }
+}
+
+/// 幂等键的状态
+#[derive(Debug)]
+enum Slot {
+    /// 第一次请求仍在执行
+    InFlight {
+        fingerprint: String,
+        started_at: Instant,
+    },
+    /// 已完成
+    Done {
+        fingerprint: String,
+        response: CachedResponse,
+        stored_at: Instant,
+    },
+}
+
+/// 登记幂等键的结果
+#[derive(Debug)]
+pub enum IdempotencyCheck {
+    /// 首次出现，执行请求
+    Proceed,
+    /// 重复请求，返回第一次的结果
+    Replay(CachedResponse),
+    /// 第一次请求仍在执行
+    InFlight,
+    /// 同一个幂等键用于了不同的请求体
+    Mismatch,
+    /// 执行中的幂等请求已达上限
+    Busy,
+}
+
+/// 幂等结果存储
+#[derive(Debug)]
+pub struct IdempotencyStore {
+    window: Duration,
+    max_entries: usize,
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+impl IdempotencyStore {
+    /// 创建存储
+    pub fn new(window: Duration, max_entries: usize) -> Self {
+        Self {
+            window,
+            max_entries,
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 登记幂等键
+    pub fn begin(&self, key: &str, fingerprint: &str, now: Instant) -> IdempotencyCheck {
+        let mut slots = self.slots.lock().unwrap();
+        // 执行超过窗口期的请求也不再占用幂等键，之后完成时结果不会保存
+        slots.retain(|_, slot| match slot {
+            Slot::InFlight { started_at, .. } => now.duration_since(*started_at) < self.window,
+            Slot::Done { stored_at, .. } => now.duration_since(*stored_at) < self.window,
+        });
+
+        match slots.get(key) {
+            Some(Slot::InFlight {
+                fingerprint: first, ..
+            })
+            | Some(Slot::Done {
+                fingerprint: first, ..
+            }) if first != fingerprint => IdempotencyCheck::Mismatch,
+            Some(Slot::InFlight { .. }) => IdempotencyCheck::InFlight,
+            Some(Slot::Done { response, .. }) => IdempotencyCheck::Replay(response.clone()),
+            None => {
+                if slots.len() >= self.max_entries {
+                    let oldest = slots
+                        .iter()
+                        .filter_map(|(key, slot)| match slot {
+                            Slot::Done { stored_at, .. } => Some((key.clone(), *stored_at)),
+                            Slot::InFlight { .. } => None,
+                        })
+                        .min_by_key(|(_, stored_at)| *stored_at)
+                        .map(|(key, _)| key);
+                    match oldest {
+                        Some(oldest) => {
+                            slots.remove(&oldest);
+                        }
+                        // 全部是执行中的请求，不能淘汰
+                        None => return IdempotencyCheck::Busy,
+                    }
+                }
+                slots.insert(
+                    key.to_string(),
+                    Slot::InFlight {
+                        fingerprint: fingerprint.to_string(),
+                        started_at: now,
+                    },
+                );
+                IdempotencyCheck::Proceed
+            }
+        }
+    }
+
+    /// 保存请求结果
+    pub fn complete(&self, key: &str, response: CachedResponse, now: Instant) {
+        let mut slots = self.slots.lock().unwrap();
+        if let Some(Slot::InFlight { fingerprint, .. }) = slots.get_mut(key) {
+            let fingerprint = std::mem::take(fingerprint);
+            slots.insert(
+                key.to_string(),
+                Slot::Done {
+                    fingerprint,
+                    response,
+                    stored_at: now,
+                },
+            );
+        }
+    }
+
+    /// 放弃幂等键，之后的重试会重新执行
+    pub fn abort(&self, key: &str) {
+        let mut slots = self.slots.lock().unwrap();
+        if matches!(slots.get(key), Some(Slot::InFlight { .. })) {
+            slots.remove(key);
+        }
+    }
+}
+
+/// 请求执行中断（例如客户端断开）时放弃幂等键
+struct InFlightGuard<'a> {
+    store: &'a IdempotencyStore,
+    key: Option<String>,
+}
+
+impl InFlightGuard<'_> {
+    fn finish(mut self, response: CachedResponse) {
+        if let Some(key) = self.key.take() {
+            self.store.complete(&key, response, Instant::now());
+        }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.store.abort(&key);
+        }
+    }
+}
+
+/// 请求保护中间件的状态
+#[derive(Debug, Clone)]
+pub struct RequestGuard {
+    config: Arc<RequestGuardConfig>,
+    store: Arc<IdempotencyStore>,
+    signatures: Arc<SignatureCache>,
+    /// 剩余的请求体缓冲额度（字节）
+    buffer_budget: Arc<Semaphore>,
+}
+
+impl RequestGuard {
+    /// 创建请求保护
+    pub fn new(config: RequestGuardConfig) -> Self {
+        let store =
+            IdempotencyStore::new(config.idempotency_window, config.max_idempotency_entries);
+        let signatures = SignatureCache::new(config.max_clock_skew, config.max_signature_entries);
+        let buffer_budget = Semaphore::new(config.buffer_budget());
+        Self {
+            config: Arc::new(config),
+            store: Arc::new(store),
+            signatures: Arc::new(signatures),
+            buffer_budget: Arc::new(buffer_budget),
+        }
+    }
+
+    /// 配置
+    pub fn config(&self) -> &RequestGuardConfig {
+        &self.config
+    }
+}
+
+impl Default for RequestGuard {
+    fn default() -> Self {
+        Self::new(RequestGuardConfig::default())
+    }
+}
+
+/// 流式响应（SSE）不能缓冲，也无法重放
+fn is_streaming(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream"))
+}
+
+fn reject(status: StatusCode, message: impl Into<String>) -> Response {
+    let error = ApiError {
+        message: message.into(),
+    };
+    (status, Json(error)).into_response()
+}
+
+/// 请求签名与幂等中间件
+pub async fn guard_requests(
+    State(guard): State<RequestGuard>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = &guard.config;
+    let method = request.method().clone();
+    let is_read = matches!(method, Method::GET | Method::HEAD | Method::OPTIONS);
+    let signing_secret = config
+        .signing_secret
+        .as_deref()
+        .filter(|_| config.sign_reads || !is_read);
+
+    let idempotency_key = match request.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(_) if method != Method::POST => None,
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => {
+                Some(key.to_string())
+            }
+            _ => return reject(StatusCode::BAD_REQUEST, "幂等键格式错误"),
+        },
+        None => None,
+    };
+
+    if signing_secret.is_none() && idempotency_key.is_none() {
+        return next.run(request).await;
+    }
+
+    // 签名与幂等都需要完整的请求体
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let content_length = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > config.max_body_bytes) {
+        return reject(StatusCode::PAYLOAD_TOO_LARGE, "请求体过大");
+    }
+
+    // 按声明的长度占用缓冲额度，未声明时按上限占用，请求处理完才释放
+    let reserved = content_length
+        .unwrap_or(config.max_body_bytes)
+        .clamp(1, config.buffer_budget());
+    let Ok(_buffered) = guard.buffer_budget.acquire_many(reserved as u32).await else {
+        return reject(StatusCode::SERVICE_UNAVAILABLE, "请求保护已关闭");
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, config.max_body_bytes).await {
+        Ok(body) => body,
+        Err(_) => return reject(StatusCode::PAYLOAD_TOO_LARGE, "请求体过大"),
+    };
+
+    if let Some(secret) = signing_secret {
+        let now = chrono::Utc::now().timestamp();
+        let (timestamp, signature) = match verify_signature(
+            config,
+            secret,
+            &parts.headers,
+            method.as_str(),
+            &path,
+            &body,
+            now,
+        ) {
+            Ok(signed) => signed,
+            Err(message) => {
+                warn!("拒绝未通过签名校验的请求 {} {}: {}", method, path, message);
+                return reject(StatusCode::UNAUTHORIZED, message);
+            }
+        };
+        // 读请求重复执行没有副作用，只记录写请求的签名
+        if !is_read && !guard.signatures.insert(&signature, timestamp, now) {
+            warn!("拒绝重放的签名请求 {} {}", method, path);
+            return reject(StatusCode::UNAUTHORIZED, "签名已使用过");
+        }
+    }
+
+    let request = Request::from_parts(parts, Body::from(body.clone()));
+    let Some(idempotency_key) = idempotency_key else {
+        return next.run(request).await;
+    };
+
+    let key = format!("{} {} {}", idempotency_key, method, path);
+    let fingerprint = data_encoding::HEXLOWER.encode(&Sha256::digest(&body));
+    match guard.store.begin(&key, &fingerprint, Instant::now()) {
+        IdempotencyCheck::Proceed => {}
+        IdempotencyCheck::Replay(cached) => {
+            debug!("重放幂等请求 {} {}", method, path);
+            return cached.into_replay();
+        }
+        IdempotencyCheck::InFlight => {
+            return reject(StatusCode::CONFLICT, "相同幂等键的请求仍在处理中");
+        }
+        IdempotencyCheck::Mismatch => {
+            return reject(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "幂等键已用于不同的请求内容",
+            );
+        }
+        IdempotencyCheck::Busy => {
+            return reject(StatusCode::TOO_MANY_REQUESTS, "处理中的幂等请求过多");
+        }
+    }
+
+    let in_flight = InFlightGuard {
+        store: &guard.store,
+        key: Some(key),
+    };
+    let response = next.run(request).await;
+
+    // 服务端错误不保存结果，允许客户端重试；流式响应直接转发，放弃幂等键
+    if response.status().is_server_error() {
+        return response;
+    }
+    if is_streaming(response.headers()) {
+        debug!("流式响应不保存幂等结果 {} {}", method, path);
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, config.max_body_bytes).await {
+        Ok(body) => {
+            in_flight.finish(CachedResponse {
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+            });
+            Response::from_parts(parts, Body::from(body))
+        }
+        Err(e) => {
+            warn!("读取响应体失败，无法保存幂等结果: {}", e);
+            reject(StatusCode::INTERNAL_SERVER_ERROR, "读取响应失败")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_and_idempotency_store() {
+        let config = RequestGuardConfig::default().with_signing_secret("secret");
+        let now = 1_700_000_000;
+        let body = br#"{"message":"hi"}"#;
+        let signature = sign_request("secret", now, "POST", "/api/topics/x/messages", body);
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        headers.insert(SIGNATURE_TIMESTAMP_HEADER, now.to_string().parse().unwrap());
+        let verify = |headers: &HeaderMap, body: &[u8], at: i64| {
+            verify_signature(
+                &config,
+                "secret",
+                headers,
+                "POST",
+                "/api/topics/x/messages",
+                body,
+                at,
+            )
+        };
+        assert!(verify(&headers, body, now + 10).is_ok());
+        assert!(verify(&headers, b"{}", now).is_err());
+        assert!(verify(&headers, body, now + 301).is_err());
+        assert!(verify(&HeaderMap::new(), body, now).is_err());
+
+        let store = IdempotencyStore::new(Duration::from_secs(60), 16);
+        let start = Instant::now();
+        assert!(matches!(
+            store.begin("k1", "a", start),
+            IdempotencyCheck::Proceed
+        ));
+        assert!(matches!(
+            store.begin("k1", "a", start),
+            IdempotencyCheck::InFlight
+        ));
+        assert!(matches!(
+            store.begin("k1", "b", start),
+            IdempotencyCheck::Mismatch
+        ));
+        store.complete(
+            "k1",
+            CachedResponse {
+                status: StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: Bytes::from_static(b"{\"message_id\":\"m1\"}"),
+            },
+            start,
+        );
+        match store.begin("k1", "a", start) {
+            IdempotencyCheck::Replay(cached) => {
+                assert_eq!(&cached.body[..], b"{\"message_id\":\"m1\"}")
+            }
+            other => panic!("应当重放: {:?}", other),
+        }
+
+        // 放弃的键与过期的结果都会重新执行
+        assert!(matches!(
+            store.begin("k2", "a", start),
+            IdempotencyCheck::Proceed
+        ));
+        store.abort("k2");
+        assert!(matches!(
+            store.begin("k2", "a", start),
+            IdempotencyCheck::Proceed
+        ));
+        assert!(matches!(
+            store.begin("k1", "a", start + Duration::from_secs(61)),
+            IdempotencyCheck::Proceed
+        ));
+    }
+
+    #[test]
+    fn test_replay_and_in_flight_limits() {
+        let now = 1_700_000_000;
+        let signatures = SignatureCache::new(Duration::from_secs(300), 2);
+        assert!(signatures.insert(b"a", now, now));
+        assert!(!signatures.insert(b"a", now, now + 10));
+        // 超出允许范围后记录被清理，此时签名已会被时间戳校验拒绝
+        assert!(signatures.insert(b"a", now, now + 301));
+        // 达到上限时淘汰时间戳最早的签名
+        assert!(signatures.insert(b"b", now + 1, now + 1));
+        assert!(signatures.insert(b"c", now + 2, now + 2));
+        assert!(!signatures.insert(b"c", now + 2, now + 3));
+        assert!(signatures.insert(b"a", now, now + 3));
+
+        let store = IdempotencyStore::new(Duration::from_secs(60), 1);
+        let start = Instant::now();
+        assert!(matches!(
+            store.begin("k1", "a", start),
+            IdempotencyCheck::Proceed
+        ));
+        assert!(matches!(
+            store.begin("k2", "a", start),
+            IdempotencyCheck::Busy
+        ));
+        // 执行超过窗口期的请求不再占用名额
+        assert!(matches!(
+            store.begin("k2", "a", start + Duration::from_secs(61)),
+            IdempotencyCheck::Proceed
+        ));
+
+        let mut headers = HeaderMap::new();
+        assert!(!is_streaming(&headers));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        assert!(is_streaming(&headers));
+    }
+}
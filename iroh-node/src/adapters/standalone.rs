@@ -85,6 +85,7 @@ pub mod simple_api {
                 .unwrap_or_else(|| std::env::temp_dir().join("iroh_data")),
             download_dir: download_dir.map(|p| p.to_path_buf()),
             verbose_logging: false,
+            file_scanner: None,
         };
 
         let adapter = StandaloneAdapter::new(config).await?;
@@ -107,6 +108,7 @@ pub mod simple_api {
                 .unwrap_or_else(|| std::env::temp_dir().join("iroh_data")),
             download_dir: None,
             verbose_logging: false,
+            file_scanner: None,
         };
 
         let adapter = StandaloneAdapter::new(config).await?;
@@ -134,6 +136,7 @@ pub mod simple_api {
                 .unwrap_or_else(|| std::env::temp_dir().join("iroh_data")),
             download_dir: download_dir.map(|p| p.to_path_buf()),
             verbose_logging: false,
+            file_scanner: None,
         };
 
         let adapter = StandaloneAdapter::new(config).await?;
@@ -163,6 +166,7 @@ pub mod simple_api {
                 .unwrap_or_else(|| std::env::temp_dir().join("iroh_data")),
             download_dir: None,
             verbose_logging: false,
+            file_scanner: None,
         };
 
         let adapter = StandaloneAdapter::new(config).await?;
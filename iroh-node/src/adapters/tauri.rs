@@ -1,30 +1,289 @@
-//! Tauri适配器
+//! Tauri插件
 //!
-//! 提供Tauri插件，用于在Tauri应用中集成P2P节点
+//! 提供Tauri v2插件（`iroh-agent`），注册命令、托管节点状态并发射事件。
+//!
+//! ```ignore
+//! tauri::Builder::default()
+//!     .plugin(iroh_node::adapters::tauri::init())
+//!     .run(tauri::generate_context!())
+//! ```
+//!
+//! 也可以在 `tauri.conf.json` 的 `plugins.iroh-agent` 中提供 [`PluginConfig`]，
+//! 其优先级高于 [`Builder`] 中的代码配置。
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use iroh_gossip::proto::topic::TopicId;
+use iroh_gossip::proto::TopicId;
 use serde::{Deserialize, Serialize};
 use tauri::{
-    plugin::{Builder, TauriPlugin},
-    AppHandle, Manager, Runtime, State,
+    plugin::{Builder as PluginBuilder, TauriPlugin},
+    AppHandle, Emitter, Manager, RunEvent, Runtime, State,
 };
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+use crate::{
+    memory::DEFAULT_RECALL_LIMIT, AgentRequestMode, AgentRequestOutcome, ChatHistoryEntry, ChatLogConfig,
+    CommandOutcome, EnsembleOptions, EnsembleOutcome, FileScanHook, FileSyncStatus, Invite, InviteCode, InviteKind, LogBuffer, LogEntry,
+    LogQuery, MemoryEntry, MemoryMatch, MentionRecord, MentionSummary, MessageContent, NodeConfig, NodeStatus, P2PNode, PeerInfo, PeerRecord,
+    Reactions, SafetyNumber, ScanConfig, ScanResult, TopicStats, TransferGate, TransferSchedule, TransferScheduleStatus, TrustLevel,
+    UnreadSummary,
+};
 
-use crate::{MessageType, NodeConfig, NodeResult, P2PNode};
+/// 插件名称，前端通过 `plugin:iroh-agent|<command>` 调用命令
+pub const PLUGIN_NAME: &str = "iroh-agent";
+
+/// 插件事件名称
+pub mod events {
+    /// 节点已启动，负载为节点ID
+    pub const NODE_STARTED: &str = "iroh-agent://node-started";
+    /// 节点已停止
+    pub const NODE_STOPPED: &str = "iroh-agent://node-stopped";
+    /// 话题已创建，负载为话题ID
+    pub const TOPIC_CREATED: &str = "iroh-agent://topic-created";
+    /// 话题已加入，负载为话题ID
+    pub const TOPIC_JOINED: &str = "iroh-agent://topic-joined";
+    /// 话题已离开，负载为话题ID
+    pub const TOPIC_LEFT: &str = "iroh-agent://topic-left";
+    /// 消息已发送，负载为话题ID与消息ID
+    pub const MESSAGE_SENT: &str = "iroh-agent://message-sent";
+    /// Agent请求已发送，负载包含请求ID；结果以 `agent_request_completed` 节点事件推送
+    pub const AGENT_REQUEST_SENT: &str = "iroh-agent://agent-request-sent";
+    /// 节点状态事件，负载为 [`crate::NodeEvent`]
+    pub const NODE_EVENT: &str = "iroh-agent://node-event";
+    /// 传输调度策略或网络计费状态变化，负载为 [`crate::TransferScheduleStatus`]
+    pub const TRANSFER_SCHEDULE: &str = "iroh-agent://transfer-schedule";
+    /// 接收文件的安全扫描结果，负载为 [`crate::ScanResult`]
+    pub const SCAN_RESULT: &str = "iroh-agent://scan-result";
+}
+
+/// 插件配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PluginConfig {
+    /// 应用启动时自动启动节点
+    pub auto_start: bool,
+    /// 密钥
+    pub secret_key: Option<String>,
+    /// 中继服务器URL
+    pub relay: Option<String>,
+    /// 禁用中继
+    pub no_relay: bool,
+    /// 代理URL，例如Tor的 `socks5h://127.0.0.1:9050`
+    pub proxy: Option<String>,
+    /// 隐私模式：只经中继通信，不公开本地地址
+    pub privacy_mode: bool,
+    /// 节点名称
+    pub name: Option<String>,
+    /// 绑定端口（移动端始终使用随机端口）
+    pub bind_port: u16,
+    /// 大文件传输的调度策略
+    pub transfer_schedule: TransferSchedule,
+    /// 接收文件的安全扫描，未设置时 `scan_file` 不可用
+    pub file_scan: Option<ScanConfig>,
+    /// 聊天记录的本地存储；插件不配置密钥存储，房间密钥只在本次运行期间有效，需用 `export_room_key` 备份
+    pub chat_log: Option<ChatLogConfig>,
+    /// 本人其他设备（如桌面端）的节点ID，用于在设备之间同步已读状态
+    pub own_devices: Vec<String>,
+}
 
-/// P2P节点状态
-struct P2PNodeState {
+impl PluginConfig {
+    /// 转换为节点配置
+    pub fn to_node_config(&self) -> Result<NodeConfig, String> {
+        let relay = match &self.relay {
+            Some(url) => Some(url.parse().map_err(|e| format!("解析中继URL失败: {}", e))?),
+            None => None,
+        };
+
+        // 移动端无法可靠地监听固定端口
+        let bind_port = if cfg!(mobile) { 0 } else { self.bind_port };
+
+        Ok(NodeConfig::new()
+            .with_secret_key(self.secret_key.clone())
+            .with_relay(relay)
+            .with_no_relay(self.no_relay)
+            .with_proxy(self.proxy.clone())
+            .with_privacy_mode(self.privacy_mode)
+            .with_name(self.name.clone())
+            .with_bind_port(bind_port)
+            .with_chat_log(self.chat_log.clone())
+            .with_own_devices(self.own_devices.clone()))
+    }
+}
+
+/// 插件托管状态
+pub struct IrohAgentState {
     /// P2P节点
     node: Arc<RwLock<Option<P2PNode>>>,
+    /// 插件配置
+    config: PluginConfig,
+    /// 传输调度
+    transfers: Arc<TransferGate>,
+    /// 接收文件的安全扫描
+    scanner: Option<Arc<FileScanHook>>,
 }
 
-/// Tauri插件
-pub struct TauriAdapter<R: Runtime> {
-    /// 插件
-    plugin: TauriPlugin<R>,
+impl IrohAgentState {
+    fn new(config: PluginConfig) -> Self {
+        Self {
+            node: Arc::new(RwLock::new(None)),
+            transfers: Arc::new(TransferGate::new(config.transfer_schedule.clone())),
+            scanner: config
+                .file_scan
+                .clone()
+                .map(|scan| Arc::new(FileScanHook::new(scan))),
+            config,
+        }
+    }
+
+    /// 获取P2P节点句柄，供应用自定义命令使用
+    pub fn node(&self) -> Arc<RwLock<Option<P2PNode>>> {
+        self.node.clone()
+    }
+
+    /// 获取传输调度闸门，交给应用中的传输子系统（例如 `UploadManager::with_schedule`）
+    pub fn transfer_gate(&self) -> Arc<TransferGate> {
+        self.transfers.clone()
+    }
+
+    /// 获取安全扫描钩子，交给应用中接收文件的子系统（例如 `UploadManager::with_scanner`、
+    /// `TransferConfig::file_scanner`）；插件启动的节点会自动用它扫描同步文件夹收到的文件
+    pub fn file_scanner(&self) -> Option<Arc<FileScanHook>> {
+        self.scanner.clone()
+    }
+}
+
+/// 插件构建器
+#[derive(Debug, Default)]
+pub struct Builder {
+    config: PluginConfig,
+}
+
+impl Builder {
+    /// 创建插件构建器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用完整配置
+    pub fn with_config(mut self, config: PluginConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// 设置应用启动时是否自动启动节点
+    pub fn with_auto_start(mut self, auto_start: bool) -> Self {
+        self.config.auto_start = auto_start;
+        self
+    }
+
+    /// 设置节点名称
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.config.name = Some(name.into());
+        self
+    }
+
+    /// 设置中继服务器URL
+    pub fn with_relay(mut self, relay: impl Into<String>) -> Self {
+        self.config.relay = Some(relay.into());
+        self
+    }
+
+    /// 构建Tauri插件
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R, Option<PluginConfig>> {
+        let default_config = self.config;
+
+        PluginBuilder::<R, Option<PluginConfig>>::new(PLUGIN_NAME)
+            .invoke_handler(tauri::generate_handler![
+                init_node,
+                get_node_status,
+                get_active_topics,
+                get_peers,
+                get_address_book,
+                set_peer_trust,
+                remove_peer,
+                get_safety_number,
+                confirm_verification,
+                verify_scanned,
+                get_topic_stats,
+                create_topic,
+                join_topic,
+                create_invite,
+                parse_invite,
+                join_invite,
+                send_message,
+                get_chat_history,
+                mark_message_read,
+                react_to_message,
+                get_message_thread,
+                get_unread_summary,
+                mark_room_read,
+                get_notifications,
+                get_mentions,
+                mark_mentions_read,
+                export_room_key,
+                import_room_key,
+                send_agent_request,
+                ensemble_request,
+                list_memory,
+                remember,
+                recall_memory,
+                forget_memory,
+                list_folder_syncs,
+                start_folder_sync,
+                get_folder_sync_status,
+                stop_folder_sync,
+                resolve_folder_conflict,
+                leave_topic,
+                stop_node,
+                export_bundle,
+                import_bundle,
+                get_logs,
+                get_transfer_schedule,
+                set_transfer_schedule,
+                set_network_metered,
+                scan_file
+            ])
+            .setup(move |app, api| {
+                let config = api.config().clone().unwrap_or(default_config);
+                let auto_start = config.auto_start;
+                let state = IrohAgentState::new(config);
+                forward_transfer_schedule(app, state.transfer_gate());
+                if let Some(scanner) = state.file_scanner() {
+                    forward_scan_results(app, &scanner);
+                }
+                app.manage(state);
+
+                if auto_start {
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let state = app.state::<IrohAgentState>();
+                        if let Err(e) = start_node(&app, &state, None).await {
+                            error!("自动启动节点失败: {}", e);
+                        }
+                    });
+                }
+                Ok(())
+            })
+            .on_event(|app, event| {
+                if let RunEvent::Exit = event {
+                    let node = app.state::<IrohAgentState>().node();
+                    tauri::async_runtime::block_on(async move {
+                        if let Some(node) = node.write().await.take() {
+                            if let Err(e) = node.stop().await {
+                                warn!("退出时停止节点失败: {}", e);
+                            }
+                        }
+                    });
+                }
+            })
+            .build()
+    }
+}
+
+/// 使用默认配置创建插件
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<PluginConfig>> {
+    Builder::new().build()
 }
 
 /// 节点状态响应
@@ -42,6 +301,22 @@ pub struct NodeStatusResponse {
     pub last_activity: String,
     /// 中继模式
     pub relay_mode: String,
+    /// 当前主中继服务器
+    pub home_relay: Option<String>,
+}
+
+impl From<NodeStatus> for NodeStatusResponse {
+    fn from(status: NodeStatus) -> Self {
+        Self {
+            node_id: status.node_id,
+            connected_peers: status.connected_peers,
+            active_topics: status.active_topics,
+            started_at: status.started_at.to_rfc3339(),
+            last_activity: status.last_activity.to_rfc3339(),
+            relay_mode: status.relay_mode,
+            home_relay: status.home_relay,
+        }
+    }
 }
 
 /// 话题响应
@@ -53,13 +328,48 @@ pub struct TopicResponse {
     pub ticket: String,
 }
 
+/// 初始化节点请求，未提供的字段使用插件配置
+#[derive(Debug, Default, Deserialize)]
+pub struct InitNodeRequest {
+    /// 密钥
+    pub secret_key: Option<String>,
+    /// 中继服务器URL
+    pub relay: Option<String>,
+    /// 禁用中继
+    pub no_relay: Option<bool>,
+    /// 代理URL
+    pub proxy: Option<String>,
+    /// 隐私模式
+    pub privacy_mode: Option<bool>,
+    /// 节点名称
+    pub name: Option<String>,
+    /// 绑定端口
+    pub bind_port: Option<u16>,
+}
+
 /// 消息请求
 #[derive(Debug, Deserialize)]
 pub struct MessageRequest {
     /// 话题ID
     pub topic_id: String,
-    /// 消息内容
+    /// 消息内容，以 / 开头时按聊天命令执行；设置结构化内容时忽略
+    #[serde(default)]
     pub message: String,
+    /// 结构化内容（Markdown、代码块、系统提示），设置时不按命令解析
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+    /// 被回复的消息ID，设置时作为回复发送，不按命令解析
+    #[serde(default)]
+    pub reply_to: Option<String>,
+}
+
+/// 消息已发送事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageSentPayload {
+    /// 话题ID
+    pub topic_id: String,
+    /// 消息ID
+    pub message_id: String,
 }
 
 /// Agent请求
@@ -71,155 +381,338 @@ pub struct AgentRequest {
     pub agent_id: String,
     /// 提示词
     pub prompt: String,
+    /// 寻址方式，默认由选举出的一个节点回答
+    #[serde(default)]
+    pub mode: AgentRequestMode,
+    /// 等待应答的毫秒数
+    pub timeout_ms: Option<u64>,
+    /// 是否等待请求完成后再返回；不等待时结果通过节点事件推送
+    #[serde(default)]
+    pub wait: bool,
 }
 
-impl<R: Runtime> TauriAdapter<R> {
-    /// 创建新的Tauri适配器
-    pub fn new() -> Self {
-        let plugin = Builder::new("iroh-node")
-            .invoke_handler(tauri::generate_handler![
-                init_node,
-                get_node_status,
-                create_topic,
-                join_topic,
-                send_message,
-                send_agent_request,
-                leave_topic,
-                stop_node
-            ])
-            .setup(|app| {
-                // 初始化P2P节点状态
-                app.manage(P2PNodeState {
-                    node: Arc::new(RwLock::new(None)),
-                });
-                Ok(())
-            })
-            .build();
+/// 集成回答请求
+#[derive(Debug, Deserialize)]
+pub struct EnsembleRequest {
+    /// 话题ID
+    pub topic_id: String,
+    /// Agent ID
+    pub agent_id: String,
+    /// 提示词
+    pub prompt: String,
+    /// 集成选项
+    #[serde(flatten)]
+    pub options: EnsembleOptions,
+}
 
-        Self { plugin }
-    }
+/// Agent请求结果
+#[derive(Debug, Serialize)]
+pub struct AgentRequestResponse {
+    /// 请求ID
+    pub request_id: String,
+    /// 请求结果，仅在等待时返回
+    pub outcome: Option<AgentRequestOutcome>,
+}
 
-    /// 获取Tauri插件
-    pub fn plugin(&self) -> TauriPlugin<R> {
-        self.plugin.clone()
+/// 发射事件，失败时仅记录日志
+fn emit<R: Runtime, S: Serialize + Clone>(app: &AppHandle<R>, event: &str, payload: S) {
+    if let Err(e) = app.emit(event, payload) {
+        warn!("发送事件 {} 失败: {}", event, e);
     }
 }
 
-/// 初始化P2P节点
-#[tauri::command]
-async fn init_node(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
-    secret_key: Option<String>,
-    relay: Option<String>,
-    no_relay: Option<bool>,
-    name: Option<String>,
-    bind_port: Option<u16>,
-) -> Result<String, String> {
-    // 检查节点是否已经初始化
-    {
-        let node = state.node.read().await;
-        if node.is_some() {
-            return Err("节点已经初始化".to_string());
+/// 将节点事件转发到前端，节点停止后自动结束
+fn forward_node_events<R: Runtime>(
+    app: &AppHandle<R>,
+    mut receiver: tokio::sync::broadcast::Receiver<crate::NodeEvent>,
+) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let stopped = matches!(event, crate::NodeEvent::Stopped { .. });
+                    emit(&app, events::NODE_EVENT, event);
+                    if stopped {
+                        break;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("节点事件转发落后，丢弃 {} 条事件", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
         }
-    }
+    });
+}
 
-    // 解析中继URL
-    let relay_url = match relay {
-        Some(url) => {
-            Some(url.parse().map_err(|e| format!("解析中继URL失败: {}", e))?)
+/// 将传输调度的变化转发到前端
+fn forward_transfer_schedule<R: Runtime>(app: &AppHandle<R>, gate: Arc<TransferGate>) {
+    let app = app.clone();
+    let mut changes = gate.subscribe();
+    tauri::async_runtime::spawn(async move {
+        while changes.changed().await {
+            emit(&app, events::TRANSFER_SCHEDULE, gate.status());
         }
-        None => None,
-    };
+    });
+}
 
-    // 创建节点配置
-    let config = NodeConfig {
-        secret_key,
-        relay: relay_url,
-        no_relay: no_relay.unwrap_or(false),
-        name: name.clone(),
-        bind_port: bind_port.unwrap_or(0),
-    };
+/// 将安全扫描结果转发到前端
+fn forward_scan_results<R: Runtime>(app: &AppHandle<R>, scanner: &FileScanHook) {
+    let app = app.clone();
+    let mut receiver = scanner.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(result) => emit(&app, events::SCAN_RESULT, result),
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("扫描结果转发落后，丢弃 {} 条结果", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 按请求覆盖插件配置并启动节点
+async fn start_node<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &IrohAgentState,
+    request: Option<InitNodeRequest>,
+) -> Result<String, String> {
+    let mut node_state = state.node.write().await;
+    if node_state.is_some() {
+        return Err("节点已经初始化".to_string());
+    }
+
+    let mut config = state.config.clone();
+    if let Some(request) = request {
+        config.secret_key = request.secret_key.or(config.secret_key);
+        config.relay = request.relay.or(config.relay);
+        config.no_relay = request.no_relay.unwrap_or(config.no_relay);
+        config.proxy = request.proxy.or(config.proxy);
+        config.privacy_mode = request.privacy_mode.unwrap_or(config.privacy_mode);
+        config.name = request.name.or(config.name);
+        config.bind_port = request.bind_port.unwrap_or(config.bind_port);
+    }
 
-    // 创建P2P节点
-    let mut node = P2PNode::new(config)
+    let mut node = P2PNode::new(config.to_node_config()?)
         .await
         .map_err(|e| format!("创建节点失败: {}", e))?;
 
-    // 设置节点名称
-    if let Some(name) = name {
+    if let Some(name) = config.name {
         node.set_name(name);
     }
+    if let Some(scanner) = state.file_scanner() {
+        node.set_file_scanner(scanner);
+    }
 
-    // 启动节点
     node.start()
         .await
         .map_err(|e| format!("启动节点失败: {}", e))?;
 
     let node_id = node.node_id().to_string();
+    forward_node_events(app, node.subscribe_events());
+    *node_state = Some(node);
+    info!("iroh-agent 插件节点已启动: {}", node_id);
 
-    // 保存节点
-    {
-        let mut node_state = state.node.write().await;
-        *node_state = Some(node);
-    }
-
-    // 发送节点初始化事件
-    app.emit_all("iroh-node://initialized", node_id.clone())
-        .map_err(|e| format!("发送事件失败: {}", e))?;
-
+    emit(app, events::NODE_STARTED, node_id.clone());
     Ok(node_id)
 }
 
+/// 初始化P2P节点
+#[tauri::command]
+async fn init_node<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
+    request: Option<InitNodeRequest>,
+) -> Result<String, String> {
+    start_node(&app, &state, request).await
+}
+
 /// 获取节点状态
 #[tauri::command]
-async fn get_node_status(state: State<'_, P2PNodeState>) -> Result<NodeStatusResponse, String> {
+async fn get_node_status(state: State<'_, IrohAgentState>) -> Result<NodeStatusResponse, String> {
     let node = state.node.read().await;
-    let node = node
-        .as_ref()
-        .ok_or_else(|| "节点未初始化".to_string())?;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    let status = node.get_status().await;
+    Ok(node.get_status().await.into())
+}
+
+/// 获取活跃话题列表
+#[tauri::command]
+async fn get_active_topics(state: State<'_, IrohAgentState>) -> Result<Vec<String>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    Ok(node
+        .get_active_topics()
+        .await
+        .iter()
+        .map(|topic| topic.to_string())
+        .collect())
+}
+
+/// 获取对等节点的协议版本与协商后的能力
+#[tauri::command]
+async fn get_peers(state: State<'_, IrohAgentState>) -> Result<Vec<PeerInfo>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    Ok(node.get_peers().await)
+}
+
+/// 获取地址簿
+#[tauri::command]
+async fn get_address_book(state: State<'_, IrohAgentState>) -> Result<Vec<PeerRecord>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    Ok(NodeStatusResponse {
-        node_id: status.node_id,
-        connected_peers: status.connected_peers,
-        active_topics: status.active_topics,
-        started_at: status.started_at.to_rfc3339(),
-        last_activity: status.last_activity.to_rfc3339(),
-        relay_mode: status.relay_mode,
+    Ok(node.get_address_book().await)
+}
+
+/// 设置节点的信任级别
+#[tauri::command]
+async fn set_peer_trust(
+    state: State<'_, IrohAgentState>,
+    node_id: String,
+    trust: TrustLevel,
+    name: Option<String>,
+) -> Result<PeerRecord, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    node.set_peer_trust(&node_id, trust, name)
+        .await
+        .map_err(|e| format!("设置信任级别失败: {}", e))
+}
+
+/// 从地址簿删除节点
+#[tauri::command]
+async fn remove_peer(
+    state: State<'_, IrohAgentState>,
+    node_id: String,
+) -> Result<PeerRecord, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    node.remove_peer(&node_id)
+        .await
+        .map_err(|e| format!("删除节点失败: {}", e))
+}
+
+/// 安全码及其二维码
+#[derive(Debug, Clone, Serialize)]
+pub struct SafetyNumberResponse {
+    /// 安全码
+    #[serde(flatten)]
+    pub safety_number: SafetyNumber,
+    /// 供对方扫描的PNG二维码
+    pub qr_png: Vec<u8>,
+}
+
+/// 获取与节点之间的安全码与二维码
+#[tauri::command]
+async fn get_safety_number(
+    state: State<'_, IrohAgentState>,
+    node_id: String,
+) -> Result<SafetyNumberResponse, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let safety_number = node
+        .safety_number(&node_id)
+        .map_err(|e| format!("计算安全码失败: {}", e))?;
+    let qr_png = safety_number
+        .qr_png()
+        .map_err(|e| format!("生成二维码失败: {}", e))?;
+    Ok(SafetyNumberResponse {
+        safety_number,
+        qr_png,
     })
 }
 
+/// 核对安全码后确认验证，指定话题时通知对方
+#[tauri::command]
+async fn confirm_verification(
+    state: State<'_, IrohAgentState>,
+    node_id: String,
+    topic_id: Option<String>,
+) -> Result<PeerRecord, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = match topic_id {
+        Some(id) => Some(id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?),
+        None => None,
+    };
+    node.confirm_verification(&node_id, topic_id.as_ref())
+        .await
+        .map_err(|e| format!("确认验证失败: {}", e))
+}
+
+/// 扫描对方的验证二维码
+#[tauri::command]
+async fn verify_scanned(
+    state: State<'_, IrohAgentState>,
+    payload: String,
+    topic_id: Option<String>,
+) -> Result<PeerRecord, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = match topic_id {
+        Some(id) => Some(id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?),
+        None => None,
+    };
+    node.verify_scanned(&payload, topic_id.as_ref())
+        .await
+        .map_err(|e| format!("验证失败: {}", e))
+}
+
+/// 获取话题统计，未指定话题时返回所有活跃话题的统计
+#[tauri::command]
+async fn get_topic_stats(
+    state: State<'_, IrohAgentState>,
+    topic_id: Option<String>,
+) -> Result<Vec<TopicStats>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    match topic_id {
+        Some(id) => {
+            let topic_id = id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+            let stats = node
+                .get_topic_stats(&topic_id)
+                .await
+                .ok_or_else(|| format!("话题不存在: {}", id))?;
+            Ok(vec![stats])
+        }
+        None => Ok(node.get_all_topic_stats().await),
+    }
+}
+
 /// 创建话题
 #[tauri::command]
-async fn create_topic(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
+async fn create_topic<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
     topic_id: Option<String>,
 ) -> Result<TopicResponse, String> {
     let node = state.node.read().await;
-    let node = node
-        .as_ref()
-        .ok_or_else(|| "节点未初始化".to_string())?;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 解析话题ID
     let topic = match topic_id {
-        Some(id) => {
-            Some(id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?)
-        }
+        Some(id) => Some(id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?),
         None => None,
     };
 
-    // 创建话题
     let (topic, ticket) = node
         .join_topic(topic, None)
         .await
         .map_err(|e| format!("创建话题失败: {}", e))?;
 
-    // 发送话题创建事件
-    app.emit_all("iroh-node://topic-created", topic.to_string())
-        .map_err(|e| format!("发送事件失败: {}", e))?;
+    emit(&app, events::TOPIC_CREATED, topic.to_string());
 
     Ok(TopicResponse {
         topic_id: topic.to_string(),
@@ -229,25 +722,71 @@ async fn create_topic(
 
 /// 加入话题
 #[tauri::command]
-async fn join_topic(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
+async fn join_topic<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
     ticket: String,
 ) -> Result<TopicResponse, String> {
     let node = state.node.read().await;
-    let node = node
-        .as_ref()
-        .ok_or_else(|| "节点未初始化".to_string())?;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 加入话题
     let (topic, ticket) = node
         .join_topic(None, Some(&ticket))
         .await
         .map_err(|e| format!("加入话题失败: {}", e))?;
 
-    // 发送话题加入事件
-    app.emit_all("iroh-node://topic-joined", topic.to_string())
-        .map_err(|e| format!("发送事件失败: {}", e))?;
+    emit(&app, events::TOPIC_JOINED, topic.to_string());
+
+    Ok(TopicResponse {
+        topic_id: topic.to_string(),
+        ticket,
+    })
+}
+
+/// 生成邀请链接与二维码
+#[tauri::command]
+async fn create_invite(
+    kind: InviteKind,
+    ticket: String,
+    name: Option<String>,
+) -> Result<InviteCode, String> {
+    Invite::new(kind, ticket, name)
+        .and_then(|invite| invite.to_code())
+        .map_err(|e| format!("生成邀请失败: {}", e))
+}
+
+/// 解析扫码得到的邀请链接
+#[tauri::command]
+async fn parse_invite(url: String) -> Result<Invite, String> {
+    url.parse().map_err(|e| format!("解析邀请链接失败: {}", e))
+}
+
+/// 通过房间邀请链接加入话题
+#[tauri::command]
+async fn join_invite<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
+    url: String,
+) -> Result<TopicResponse, String> {
+    let invite: Invite = url
+        .parse()
+        .map_err(|e| format!("解析邀请链接失败: {}", e))?;
+    if invite.kind != InviteKind::Room {
+        return Err("不是房间邀请链接".to_string());
+    }
+
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let (topic, ticket) = node
+        .join_topic(None, Some(&invite.ticket))
+        .await
+        .map_err(|e| format!("加入话题失败: {}", e))?;
+    if invite.name.is_some() {
+        let _ = node.set_topic_label(&topic, invite.name).await;
+    }
+
+    emit(&app, events::TOPIC_JOINED, topic.to_string());
 
     Ok(TopicResponse {
         topic_id: topic.to_string(),
@@ -255,123 +794,618 @@ async fn join_topic(
     })
 }
 
-/// 发送消息
+/// 发送消息或执行聊天命令
 #[tauri::command]
-async fn send_message(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
+async fn send_message<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
     request: MessageRequest,
-) -> Result<(), String> {
+) -> Result<CommandOutcome, String> {
     let node = state.node.read().await;
-    let node = node
-        .as_ref()
-        .ok_or_else(|| "节点未初始化".to_string())?;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 解析话题ID
     let topic_id = request
         .topic_id
         .parse()
         .map_err(|e| format!("解析话题ID失败: {}", e))?;
 
-    // 创建消息
-    let message = MessageType::Chat {
-        text: request.message,
-    };
+    let outcome = match (request.content, request.reply_to.as_deref()) {
+        (Some(content), reply_to) => node
+            .send_content(&topic_id, content, reply_to)
+            .await
+            .map(|message_id| CommandOutcome::Sent { message_id }),
+        (None, Some(reply_to)) => node
+            .send_reply(&topic_id, reply_to, &request.message)
+            .await
+            .map(|message_id| CommandOutcome::Sent { message_id }),
+        (None, None) => node.send_input(&topic_id, &request.message).await,
+    }
+    .map_err(|e| format!("发送消息失败: {}", e))?;
+
+    if let CommandOutcome::Sent { message_id } = &outcome {
+        emit(
+            &app,
+            events::MESSAGE_SENT,
+            MessageSentPayload {
+                topic_id: topic_id.to_string(),
+                message_id: message_id.clone(),
+            },
+        );
+    }
+    Ok(outcome)
+}
+
+/// 获取话题的聊天记录（含投递状态）
+#[tauri::command]
+async fn get_chat_history(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    limit: Option<usize>,
+) -> Result<Vec<ChatHistoryEntry>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+    Ok(node.get_chat_history(&topic_id, limit).await)
+}
+
+/// 将收到的消息标记为已读
+#[tauri::command]
+async fn mark_message_read(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    message_id: String,
+) -> Result<(), String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 发送消息
-    node.send_message(&topic_id, message)
+    let topic_id = topic_id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.mark_read(&topic_id, &message_id)
         .await
-        .map_err(|e| format!("发送消息失败: {}", e))?;
+        .map_err(|e| format!("标记已读失败: {}", e))
+}
 
-    // 发送消息发送事件
-    app.emit_all("iroh-node://message-sent", topic_id.to_string())
-        .map_err(|e| format!("发送事件失败: {}", e))?;
+/// 添加或撤销对消息的表情回应，返回消息当前的全部回应
+#[tauri::command]
+async fn react_to_message(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    message_id: String,
+    emoji: String,
+    removed: Option<bool>,
+) -> Result<Reactions, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    Ok(())
+    let topic_id = topic_id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.react(&topic_id, &message_id, &emoji, removed.unwrap_or(false))
+        .await
+        .map_err(|e| format!("表情回应失败: {}", e))
+}
+
+/// 获取消息所在的消息串
+#[tauri::command]
+async fn get_message_thread(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    message_id: String,
+) -> Result<Vec<ChatHistoryEntry>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.get_thread(&topic_id, &message_id)
+        .await
+        .map_err(|e| format!("获取消息串失败: {}", e))
+}
+
+/// 获取各房间的未读状态，用于应用角标
+#[tauri::command]
+async fn get_unread_summary(state: State<'_, IrohAgentState>) -> Result<UnreadSummary, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    Ok(node.get_unread_summary().await)
+}
+
+/// 将房间中直到指定消息（默认最新一条）的消息标记为已读，返回新标记的条数
+#[tauri::command]
+async fn mark_room_read(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    message_id: Option<String>,
+) -> Result<usize, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id.parse().map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.mark_room_read(&topic_id, message_id.as_deref())
+        .await
+        .map_err(|e| format!("标记已读失败: {}", e))
+}
+
+/// 获取未读提及数
+#[tauri::command]
+async fn get_notifications(state: State<'_, IrohAgentState>) -> Result<MentionSummary, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    Ok(node.unread_mentions().await)
+}
+
+/// 获取本节点被提及的记录，可以只看某个话题或只看未读
+#[tauri::command]
+async fn get_mentions(
+    state: State<'_, IrohAgentState>,
+    topic_id: Option<String>,
+    unread: Option<bool>,
+) -> Result<Vec<MentionRecord>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id: Option<TopicId> = topic_id
+        .map(|topic_id| topic_id.parse())
+        .transpose()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    Ok(node.get_mentions(topic_id.as_ref(), unread.unwrap_or(false)).await)
+}
+
+/// 将提及标记为已读，不指定话题时标记全部，不指定消息时标记话题中的全部，返回新标记的条数
+#[tauri::command]
+async fn mark_mentions_read(
+    state: State<'_, IrohAgentState>,
+    topic_id: Option<String>,
+    message_id: Option<String>,
+) -> Result<usize, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id: Option<TopicId> = topic_id
+        .map(|topic_id| topic_id.parse())
+        .transpose()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    Ok(node
+        .mark_mentions_read(topic_id.as_ref(), message_id.as_deref())
+        .await)
 }
 
 /// 发送Agent请求
 #[tauri::command]
-async fn send_agent_request(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
+async fn send_agent_request<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
     request: AgentRequest,
-) -> Result<(), String> {
-    let node = state.node.read().await;
-    let node = node
+) -> Result<AgentRequestResponse, String> {
+    let node_guard = state.node.read().await;
+    let node = node_guard
         .as_ref()
         .ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 解析话题ID
     let topic_id = request
         .topic_id
         .parse()
         .map_err(|e| format!("解析话题ID失败: {}", e))?;
 
-    // 发送Agent请求
-    node.send_agent_request(&topic_id, &request.agent_id, &request.prompt)
+    let handle = node
+        .send_agent_request(
+            &topic_id,
+            &request.agent_id,
+            &request.prompt,
+            request.mode,
+            request.timeout_ms.map(Duration::from_millis),
+        )
         .await
         .map_err(|e| format!("发送Agent请求失败: {}", e))?;
+    drop(node_guard);
 
-    // 发送Agent请求发送事件
-    app.emit_all(
-        "iroh-node://agent-request-sent",
-        format!("{}:{}", topic_id, request.agent_id),
-    )
-    .map_err(|e| format!("发送事件失败: {}", e))?;
+    let request_id = handle.request_id().to_string();
+    emit(
+        &app,
+        events::AGENT_REQUEST_SENT,
+        serde_json::json!({
+            "topic_id": topic_id.to_string(),
+            "agent_id": request.agent_id,
+            "request_id": request_id,
+        }),
+    );
 
-    Ok(())
+    let outcome = if request.wait {
+        Some(handle.wait().await.map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    Ok(AgentRequestResponse {
+        request_id,
+        outcome,
+    })
+}
+
+/// 集成回答
+#[tauri::command]
+async fn ensemble_request(
+    state: State<'_, IrohAgentState>,
+    request: EnsembleRequest,
+) -> Result<EnsembleOutcome, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = request
+        .topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+
+    node.ensemble_request(&topic_id, &request.agent_id, &request.prompt, request.options)
+        .await
+        .map_err(|e| format!("集成回答失败: {}", e))
+}
+
+/// 获取话题的共享记忆
+#[tauri::command]
+async fn list_memory(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+) -> Result<Vec<MemoryEntry>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.list_memory(&topic_id)
+        .await
+        .map_err(|e| format!("获取共享记忆失败: {}", e))
+}
+
+/// 写入共享记忆
+#[tauri::command]
+async fn remember(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    text: String,
+    source: Option<String>,
+) -> Result<MemoryEntry, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.remember(&topic_id, &text, source)
+        .await
+        .map_err(|e| format!("写入共享记忆失败: {}", e))
+}
+
+/// 检索共享记忆
+#[tauri::command]
+async fn recall_memory(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<MemoryMatch>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.recall_memory(&topic_id, &query, limit.unwrap_or(DEFAULT_RECALL_LIMIT))
+        .await
+        .map_err(|e| format!("检索共享记忆失败: {}", e))
+}
+
+/// 删除本节点写入的共享记忆
+#[tauri::command]
+async fn forget_memory(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    id: String,
+) -> Result<(), String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.forget(&topic_id, &id)
+        .await
+        .map_err(|e| format!("删除共享记忆失败: {}", e))
+}
+
+/// 获取话题中的同步文件夹
+#[tauri::command]
+async fn list_folder_syncs(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+) -> Result<Vec<String>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    Ok(node.folder_syncs(&topic_id).await)
+}
+
+/// 开始同步文件夹，文件状态变化通过节点事件推送
+#[tauri::command]
+async fn start_folder_sync(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    name: String,
+    path: String,
+) -> Result<Vec<FileSyncStatus>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.start_folder_sync(&topic_id, &name, path)
+        .await
+        .map_err(|e| format!("开始同步文件夹失败: {}", e))
+}
+
+/// 获取同步文件夹中各文件的同步状态
+#[tauri::command]
+async fn get_folder_sync_status(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    name: String,
+) -> Result<Vec<FileSyncStatus>, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.folder_sync_status(&topic_id, &name)
+        .await
+        .map_err(|e| format!("获取同步状态失败: {}", e))
+}
+
+/// 停止同步文件夹
+#[tauri::command]
+async fn stop_folder_sync(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    name: String,
+) -> Result<(), String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.stop_folder_sync(&topic_id, &name)
+        .await
+        .map_err(|e| format!("停止同步文件夹失败: {}", e))
+}
+
+/// 确认已处理文件的冲突
+#[tauri::command]
+async fn resolve_folder_conflict(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    name: String,
+    file: String,
+) -> Result<FileSyncStatus, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.resolve_folder_conflict(&topic_id, &name, &file)
+        .await
+        .map_err(|e| format!("确认冲突失败: {}", e))
+}
+
+/// 用口令加密导出房间的聊天记录密钥
+#[tauri::command]
+async fn export_room_key(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    passphrase: String,
+) -> Result<String, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.export_room_key(&topic_id, &passphrase)
+        .await
+        .map_err(|e| format!("导出房间密钥失败: {}", e))
+}
+
+/// 导入房间的聊天记录密钥，返回恢复或解锁的消息条数
+#[tauri::command]
+async fn import_room_key(
+    state: State<'_, IrohAgentState>,
+    topic_id: String,
+    key: String,
+    passphrase: String,
+) -> Result<usize, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    let topic_id = topic_id
+        .parse()
+        .map_err(|e| format!("解析话题ID失败: {}", e))?;
+    node.import_room_key(&topic_id, &key, &passphrase)
+        .await
+        .map_err(|e| format!("导入房间密钥失败: {}", e))
 }
 
 /// 离开话题
 #[tauri::command]
-async fn leave_topic(
-    app: AppHandle,
-    state: State<'_, P2PNodeState>,
+async fn leave_topic<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
     topic_id: String,
 ) -> Result<(), String> {
     let node = state.node.read().await;
-    let node = node
-        .as_ref()
-        .ok_or_else(|| "节点未初始化".to_string())?;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
 
-    // 解析话题ID
     let topic_id = topic_id
         .parse()
         .map_err(|e| format!("解析话题ID失败: {}", e))?;
 
-    // 离开话题
     node.leave_topic(&topic_id)
         .await
         .map_err(|e| format!("离开话题失败: {}", e))?;
 
-    // 发送话题离开事件
-    app.emit_all("iroh-node://topic-left", topic_id.to_string())
-        .map_err(|e| format!("发送事件失败: {}", e))?;
-
+    emit(&app, events::TOPIC_LEFT, topic_id.to_string());
     Ok(())
 }
 
 /// 停止节点
 #[tauri::command]
-async fn stop_node(app: AppHandle, state: State<'_, P2PNodeState>) -> Result<(), String> {
-    let node_option = {
-        let mut node_state = state.node.write().await;
-        node_state.take()
-    };
+async fn stop_node<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
+) -> Result<(), String> {
+    let node = state
+        .node
+        .write()
+        .await
+        .take()
+        .ok_or_else(|| "节点未初始化".to_string())?;
 
-    if let Some(node) = node_option {
-        // 停止节点
-        node.stop()
-            .await
-            .map_err(|e| format!("停止节点失败: {}", e))?;
+    node.stop()
+        .await
+        .map_err(|e| format!("停止节点失败: {}", e))?;
 
-        // 发送节点停止事件
-        app.emit_all("iroh-node://stopped", ())
-            .map_err(|e| format!("发送事件失败: {}", e))?;
+    emit(&app, events::NODE_STOPPED, ());
+    Ok(())
+}
 
-        Ok(())
-    } else {
-        Err("节点未初始化".to_string())
+/// 导出节点配置包
+#[tauri::command]
+async fn export_bundle(
+    state: State<'_, IrohAgentState>,
+    passphrase: String,
+) -> Result<String, String> {
+    let node = state.node.read().await;
+    let node = node.as_ref().ok_or_else(|| "节点未初始化".to_string())?;
+
+    node.export_bundle(&passphrase)
+        .await
+        .map_err(|e| format!("导出配置包失败: {}", e))
+}
+
+/// 从配置包恢复节点身份与话题
+#[tauri::command]
+async fn import_bundle<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, IrohAgentState>,
+    bundle: String,
+    passphrase: String,
+) -> Result<String, String> {
+    let mut node_state = state.node.write().await;
+    if node_state.is_some() {
+        return Err("节点已经初始化，请先停止节点再导入".to_string());
+    }
+
+    let bind_port = if cfg!(mobile) { 0 } else { state.config.bind_port };
+    let node = P2PNode::from_bundle(&bundle, &passphrase, bind_port)
+        .await
+        .map_err(|e| format!("导入配置包失败: {}", e))?;
+
+    let node_id = node.node_id().to_string();
+    forward_node_events(&app, node.subscribe_events());
+    *node_state = Some(node);
+
+    emit(&app, events::NODE_STARTED, node_id.clone());
+    Ok(node_id)
+}
+
+/// 查询最近的日志，需要应用安装 [`LogBuffer::global`] 的tracing层
+#[tauri::command]
+async fn get_logs(query: Option<LogQuery>) -> Result<Vec<LogEntry>, String> {
+    LogBuffer::global()
+        .query(&query.unwrap_or_default())
+        .map_err(|e| format!("查询日志失败: {}", e))
+}
+
+/// 查询传输调度状态
+#[tauri::command]
+async fn get_transfer_schedule(
+    state: State<'_, IrohAgentState>,
+) -> Result<TransferScheduleStatus, String> {
+    Ok(state.transfers.status())
+}
+
+/// 替换传输调度策略
+#[tauri::command]
+async fn set_transfer_schedule(
+    state: State<'_, IrohAgentState>,
+    schedule: TransferSchedule,
+) -> Result<TransferScheduleStatus, String> {
+    state.transfers.set_schedule(schedule);
+    Ok(state.transfers.status())
+}
+
+/// 报告当前网络是否按流量计费，前端可根据系统的网络信息
+/// （例如 `navigator.connection` 的类型与省流量设置）在网络变化时调用
+#[tauri::command]
+async fn set_network_metered(
+    state: State<'_, IrohAgentState>,
+    metered: bool,
+) -> Result<TransferScheduleStatus, String> {
+    if state.transfers.set_metered(metered) {
+        info!("网络计费状态变化: {}", if metered { "按流量计费" } else { "不计费" });
+    }
+    Ok(state.transfers.status())
+}
+
+/// 扫描接收到的文件，应用在文件下载完成后、打开之前调用；未通过的文件移入隔离目录
+#[tauri::command]
+async fn scan_file(
+    state: State<'_, IrohAgentState>,
+    path: std::path::PathBuf,
+) -> Result<ScanResult, String> {
+    let scanner = state.scanner.as_ref().ok_or("未配置安全扫描")?;
+    scanner
+        .scan(&path)
+        .await
+        .map_err(|e| format!("扫描文件失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_config_to_node_config() {
+        // tauri.conf.json 中 plugins.iroh-agent 的写法，未给出的字段取默认值
+        let config: PluginConfig = serde_json::from_value(serde_json::json!({
+            "autoStart": true,
+            "relay": "https://relay.example.com",
+            "privacyMode": true,
+            "bindPort": 4433,
+            "ownDevices": ["desktop"],
+        }))
+        .unwrap();
+        assert!(config.auto_start);
+        assert!(!config.no_relay);
+        assert!(config.chat_log.is_none());
+
+        let node_config = config.to_node_config().unwrap();
+        assert_eq!(
+            node_config.relay,
+            Some("https://relay.example.com".parse().unwrap())
+        );
+        assert!(node_config.privacy_mode);
+        assert_eq!(node_config.own_devices, ["desktop"]);
+        let bind_port = if cfg!(mobile) { 0 } else { 4433 };
+        assert_eq!(node_config.bind_port, bind_port);
+
+        let invalid = PluginConfig {
+            relay: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.to_node_config().is_err());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1384 @@
+//! 可续传的分块上传
+//!
+//! 浏览器上传大文件时不再一次性读入内存：先创建上传会话，再按偏移量逐块 PATCH，
+//! 每块可附带 SHA-256 校验，数据直接流式写入磁盘。会话状态保存在上传目录中，
+//! 中断（包括服务重启）后可查询当前偏移量继续上传；全部上传后校验整个文件的哈希。
+//! 完成的文件保存在本地完成目录中；设置了 blob 存储（[`UploadManager::with_blobs`]）时
+//! 同时导入 iroh 的 blob 存储，以 `共享名称/文件名` 为标签，完成的记录带有 blob 哈希，
+//! 对等节点可按哈希获取，下载接口（[`FileShareConfig`](super::files::FileShareConfig)）按共享名称提供
+//!
+//! 启用请求签名时分块会被完整读入内存验签，分块大小应小于签名中间件的请求体上限
+//!
+//! 完成的上传另存一份记录（传输记录），带有发起上传的调用方ID与完成时间，
+//! 用于按保留期限或按调用方删除文件。完成时还会提取文件预览（内容类型、图片尺寸与缩略图、
+//! 文本片段），与文件一起保存，共享完成目录时接收方可以先浏览预览
+//!
+//! 设置了安全扫描（[`FileScanHook`]）时，文件在标记完成前先经过扫描，
+//! 未通过的文件移入隔离目录，不出现在完成目录中
+//!
+//! 完成的文件按分类规则（[`DownloadRoutes`]）放入完成目录下的子目录，最终路径通过进度事件推送
+//!
+//! 设置了传输调度（[`TransferGate`]）时，大文件的分块只在允许的时间段内、
+//! 且网络不按流量计费时接受，否则返回 503 与 `Retry-After`，会话标记为暂停并推送进度事件，
+//! 条件满足后恢复
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_lite::StreamExt;
+use iroh_blobs::api::Store;
+use rig_agent::RequestContext;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
+};
+use tracing::{info, warn};
+
+use super::{
+    axum::ApiError,
+    preview::{self, FilePreview, PreviewConfig},
+    progress::{ProgressKind, ProgressRegistry},
+};
+use crate::{
+    chat::new_message_id, DownloadRoutes, FileScanHook, NodeError, PauseReason, ScanResult,
+    TransferGate, TransferSchedule, TransferScheduleStatus,
+};
+
+/// 分块的起始偏移量
+pub const UPLOAD_OFFSET_HEADER: &str = "upload-offset";
+/// 分块内容的 SHA-256（十六进制）
+pub const CHUNK_CHECKSUM_HEADER: &str = "x-chunk-sha256";
+
+/// 会话状态文件扩展名
+const SESSION_EXTENSION: &str = "json";
+/// 未完成数据文件扩展名
+const PART_EXTENSION: &str = "part";
+/// 完成后文件所在的子目录
+const COMPLETED_DIR: &str = "completed";
+/// 传输记录所在的子目录
+const RECORDS_DIR: &str = "records";
+/// 计算整个文件哈希时的读取缓冲区大小
+const HASH_BUFFER_BYTES: usize = 1024 * 1024;
+/// 没有调度变化时重新检查暂停会话的间隔，用于时间段开始或结束
+const SCHEDULE_TICK: Duration = Duration::from_secs(60);
+
+/// 分块上传配置
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// 上传目录，保存会话状态、未完成的数据与完成的文件
+    pub dir: PathBuf,
+    /// 单个分块的最大字节数
+    pub max_chunk_bytes: u64,
+    /// 单个文件的最大字节数
+    pub max_upload_bytes: u64,
+    /// 会话多久没有新分块后过期
+    pub session_ttl: Duration,
+    /// 上传完成的文件保留多久，未设置时永久保留；可取自 `RetentionPolicy::transfer_ttl`
+    pub completed_ttl: Option<Duration>,
+    /// 上传完成时提取预览，未设置时不提取
+    pub preview: Option<PreviewConfig>,
+    /// 完成的文件在完成目录下的分类规则
+    pub routes: DownloadRoutes,
+    /// 导入 blob 存储时的共享名称，文件以 `共享名称/文件名` 为标签
+    pub share_name: String,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            dir: std::env::temp_dir().join("iroh-node-uploads"),
+            max_chunk_bytes: 8 * 1024 * 1024,
+            max_upload_bytes: 64 * 1024 * 1024 * 1024,
+            session_ttl: Duration::from_secs(24 * 60 * 60),
+            completed_ttl: None,
+            preview: Some(PreviewConfig::default()),
+            routes: DownloadRoutes::default(),
+            share_name: "uploads".to_string(),
+        }
+    }
+}
+
+impl UploadConfig {
+    /// 设置上传目录
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// 设置单个分块的最大字节数
+    pub fn with_max_chunk_bytes(mut self, max_chunk_bytes: u64) -> Self {
+        self.max_chunk_bytes = max_chunk_bytes;
+        self
+    }
+
+    /// 设置单个文件的最大字节数
+    pub fn with_max_upload_bytes(mut self, max_upload_bytes: u64) -> Self {
+        self.max_upload_bytes = max_upload_bytes;
+        self
+    }
+
+    /// 设置会话过期时间
+    pub fn with_session_ttl(mut self, session_ttl: Duration) -> Self {
+        self.session_ttl = session_ttl;
+        self
+    }
+
+    /// 设置上传完成的文件的保留时长
+    pub fn with_completed_ttl(mut self, completed_ttl: Option<Duration>) -> Self {
+        self.completed_ttl = completed_ttl;
+        self
+    }
+
+    /// 设置上传完成时的预览提取
+    pub fn with_preview(mut self, preview: Option<PreviewConfig>) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// 设置完成的文件的分类规则
+    pub fn with_routes(mut self, routes: DownloadRoutes) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// 设置导入 blob 存储时的共享名称
+    pub fn with_share_name(mut self, share_name: impl Into<String>) -> Self {
+        self.share_name = share_name.into();
+        self
+    }
+}
+
+/// 上传会话
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadSession {
+    /// 上传ID
+    pub id: String,
+    /// 文件名
+    pub file_name: String,
+    /// 文件总字节数
+    pub size: u64,
+    /// 已确认写入的字节数，下一个分块从这里开始
+    pub offset: u64,
+    /// 整个文件的 SHA-256（十六进制），完成时校验
+    pub sha256: Option<String>,
+    /// 推送进度的会话ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress_session: Option<String>,
+    /// 发起上传的调用方ID，取自请求上下文
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// 按传输调度策略暂停的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<PauseReason>,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+    /// 最近一次写入时间
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 创建上传会话的请求
+#[derive(Debug, Deserialize)]
+pub struct InitUploadRequest {
+    /// 文件名
+    pub file_name: String,
+    /// 文件总字节数
+    pub size: u64,
+    /// 整个文件的 SHA-256（十六进制）
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// 推送进度的会话ID，见 [`ProgressRegistry`]
+    #[serde(default)]
+    pub progress_session: Option<String>,
+}
+
+/// 创建上传会话的响应
+#[derive(Debug, Serialize)]
+pub struct InitUploadResponse {
+    /// 上传会话
+    #[serde(flatten)]
+    pub session: UploadSession,
+    /// 单个分块的最大字节数
+    pub max_chunk_bytes: u64,
+}
+
+/// 上传完成的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadedFile {
+    /// 上传ID
+    pub id: String,
+    /// 文件名
+    pub file_name: String,
+    /// 文件在本地的路径
+    pub path: PathBuf,
+    /// 字节数
+    pub size: u64,
+    /// SHA-256（十六进制）
+    pub sha256: String,
+    /// 导入 blob 存储后的哈希，未设置 blob 存储时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// 发起上传的调用方ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// 完成时间
+    pub completed_at: DateTime<Utc>,
+    /// 文件预览
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<FilePreview>,
+    /// 安全扫描结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanResult>,
+}
+
+/// 上传错误，带有对应的HTTP状态码；偏移量不一致时附带服务端的当前偏移量，
+/// 按调度暂停时附带建议的重试间隔
+#[derive(Debug)]
+pub struct UploadError {
+    status: StatusCode,
+    message: String,
+    offset: Option<u64>,
+    retry_after: Option<Duration>,
+}
+
+impl UploadError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            offset: None,
+            retry_after: None,
+        }
+    }
+
+    fn paused(reason: PauseReason, retry_after: Duration) -> Self {
+        let message = match reason {
+            PauseReason::Metered => "当前网络按流量计费，大文件上传已暂停",
+            PauseReason::OutsideWindow => "不在允许传输的时间段内，大文件上传已暂停",
+        };
+        Self {
+            retry_after: Some(retry_after),
+            ..Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
+        }
+    }
+
+    fn not_found(id: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("上传会话不存在: {}", id))
+    }
+
+    fn offset_mismatch(message: impl Into<String>, offset: u64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..Self::new(StatusCode::CONFLICT, message)
+        }
+    }
+
+    /// HTTP状态码
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl From<std::io::Error> for UploadError {
+    fn from(err: std::io::Error) -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            NodeError::from(err).to_string(),
+        )
+    }
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        let mut response = (
+            self.status,
+            Json(ApiError {
+                message: self.message,
+            }),
+        )
+            .into_response();
+        if let Some(offset) = self.offset {
+            response
+                .headers_mut()
+                .insert(UPLOAD_OFFSET_HEADER, HeaderValue::from(offset));
+        }
+        if let Some(retry_after) = self.retry_after {
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs()),
+            );
+        }
+        response
+    }
+}
+
+type UploadResult<T> = Result<T, UploadError>;
+
+/// 分块上传管理器
+pub struct UploadManager {
+    config: UploadConfig,
+    sessions: Mutex<HashMap<String, Arc<Mutex<UploadSession>>>>,
+    progress: Option<Arc<ProgressRegistry>>,
+    schedule: Option<Arc<TransferGate>>,
+    scanner: Option<Arc<FileScanHook>>,
+    blobs: Option<Store>,
+}
+
+impl UploadManager {
+    /// 打开上传目录，恢复未过期的上传会话
+    pub fn open(config: UploadConfig) -> Result<Self, NodeError> {
+        std::fs::create_dir_all(config.dir.join(COMPLETED_DIR))?;
+        std::fs::create_dir_all(config.dir.join(RECORDS_DIR))?;
+
+        let mut sessions = HashMap::new();
+        for entry in std::fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SESSION_EXTENSION) {
+                continue;
+            }
+            let session = std::fs::read(&path)
+                .ok()
+                .and_then(|data| serde_json::from_slice::<UploadSession>(&data).ok());
+            let Some(mut session) = session else {
+                warn!("忽略无法解析的上传会话: {}", path.display());
+                continue;
+            };
+            // 以磁盘上实际写入的数据为准，最后一个分块可能只写了一半
+            let written = std::fs::metadata(part_path(&config.dir, &session.id))
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            session.offset = session.offset.min(written);
+            sessions.insert(session.id.clone(), Arc::new(Mutex::new(session)));
+        }
+        if !sessions.is_empty() {
+            info!("恢复 {} 个未完成的上传会话", sessions.len());
+        }
+
+        Ok(Self {
+            config,
+            sessions: Mutex::new(sessions),
+            progress: None,
+            schedule: None,
+            scanner: None,
+            blobs: None,
+        })
+    }
+
+    /// 把上传进度推送到进度会话
+    pub fn with_progress(mut self, progress: Arc<ProgressRegistry>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// 按传输调度策略暂停与恢复大文件上传，可与其他传输子系统共用同一个闸门
+    pub fn with_schedule(mut self, schedule: Arc<TransferGate>) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// 上传完成前先做安全扫描
+    pub fn with_scanner(mut self, scanner: Arc<FileScanHook>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    /// 完成的文件导入 iroh 的 blob 存储，例如 [`P2PNode::blob_store`](crate::P2PNode::blob_store)
+    pub fn with_blobs(mut self, blobs: Store) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    /// 传输调度的当前状态，未设置调度时为空
+    pub fn schedule_status(&self) -> Option<TransferScheduleStatus> {
+        self.schedule.as_ref().map(|gate| gate.status())
+    }
+
+    fn report(&self, session: &UploadSession, kind: ProgressKind) {
+        if let (Some(progress), Some(progress_session)) =
+            (&self.progress, &session.progress_session)
+        {
+            progress.publish(progress_session, &session.id, kind);
+        }
+    }
+
+    /// 配置
+    pub fn config(&self) -> &UploadConfig {
+        &self.config
+    }
+
+    /// 上传完成的文件所在目录
+    pub fn completed_dir(&self) -> PathBuf {
+        self.config.dir.join(COMPLETED_DIR)
+    }
+
+    /// 创建匿名的上传会话
+    pub async fn init(&self, request: InitUploadRequest) -> UploadResult<UploadSession> {
+        self.init_with_owner(request, None).await
+    }
+
+    /// 创建上传会话，记录发起上传的调用方
+    pub async fn init_with_owner(
+        &self,
+        request: InitUploadRequest,
+        owner: Option<String>,
+    ) -> UploadResult<UploadSession> {
+        if request.size > self.config.max_upload_bytes {
+            return Err(UploadError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "文件 {} 字节，超过上限 {} 字节",
+                    request.size, self.config.max_upload_bytes
+                ),
+            ));
+        }
+        let sha256 = request
+            .sha256
+            .map(|hash| normalize_checksum(&hash))
+            .transpose()?;
+        if let (Some(progress), Some(progress_session)) =
+            (&self.progress, &request.progress_session)
+        {
+            if !progress.contains(progress_session) {
+                return Err(UploadError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("进度会话不存在或已过期: {}", progress_session),
+                ));
+            }
+        }
+        self.purge_expired().await;
+
+        let now = Utc::now();
+        let mut session = UploadSession {
+            id: new_message_id(),
+            file_name: sanitize_file_name(&request.file_name),
+            size: request.size,
+            offset: 0,
+            sha256,
+            progress_session: request.progress_session,
+            owner,
+            paused: None,
+            created_at: now,
+            updated_at: now,
+        };
+        fs::File::create(part_path(&self.config.dir, &session.id)).await?;
+        self.save(&session).await?;
+        info!(
+            "创建上传会话 {}: {} ({} 字节)",
+            session.id, session.file_name, session.size
+        );
+        self.report(
+            &session,
+            ProgressKind::Started {
+                name: session.file_name.clone(),
+                size: session.size,
+            },
+        );
+        // 不满足调度条件的大文件从一开始就处于暂停状态
+        self.apply_schedule(&mut session).await?;
+        self.sessions
+            .lock()
+            .await
+            .insert(session.id.clone(), Arc::new(Mutex::new(session.clone())));
+        Ok(session)
+    }
+
+    /// 查询上传会话
+    pub async fn status(&self, id: &str) -> UploadResult<UploadSession> {
+        let session = self.session(id).await?;
+        let session = session.lock().await;
+        Ok(session.clone())
+    }
+
+    /// 从 `offset` 开始写入一个分块，`checksum` 为分块内容的 SHA-256
+    pub async fn write_chunk(
+        &self,
+        id: &str,
+        offset: u64,
+        checksum: Option<&str>,
+        body: Body,
+    ) -> UploadResult<UploadSession> {
+        let expected = checksum.map(normalize_checksum).transpose()?;
+        let session = self.session(id).await?;
+        // 同一会话的分块依次写入
+        let mut session = session.lock().await;
+        if let Some((reason, retry_after)) = self.apply_schedule(&mut session).await? {
+            return Err(UploadError::paused(reason, retry_after));
+        }
+        if offset != session.offset {
+            return Err(UploadError::offset_mismatch(
+                format!("分块偏移量 {} 与已上传的 {} 不一致", offset, session.offset),
+                session.offset,
+            ));
+        }
+
+        let limit = self
+            .config
+            .max_chunk_bytes
+            .min(session.size - session.offset);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .open(part_path(&self.config.dir, id))
+            .await?;
+        // 丢弃上一次中断时写了一半的数据
+        file.set_len(offset).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let written = match append_chunk(&mut file, body, limit, expected.as_deref()).await {
+            Ok(written) => written,
+            Err(e) => {
+                file.set_len(offset).await?;
+                return Err(e);
+            }
+        };
+
+        session.offset += written;
+        session.updated_at = Utc::now();
+        self.save(&session).await?;
+        self.report(
+            &session,
+            ProgressKind::Progress {
+                offset: session.offset,
+            },
+        );
+        Ok(session.clone())
+    }
+
+    /// 完成上传：检查大小与整个文件的哈希，移动到完成目录；设置了安全扫描时未通过的文件被隔离，
+    /// 设置了 blob 存储时导入其中
+    pub async fn complete(&self, id: &str) -> UploadResult<UploadedFile> {
+        let session = self.session(id).await?;
+        let session = session.lock().await;
+        if session.offset != session.size {
+            return Err(UploadError::offset_mismatch(
+                format!("文件尚未上传完整: {}/{} 字节", session.offset, session.size),
+                session.offset,
+            ));
+        }
+
+        let part = part_path(&self.config.dir, id);
+        let sha256 = file_sha256(&part).await?;
+        if let Some(expected) = &session.sha256 {
+            if *expected != sha256 {
+                let err = UploadError::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("文件哈希不一致: 期望 {}，实际 {}", expected, sha256),
+                );
+                self.report(
+                    &session,
+                    ProgressKind::Failed {
+                        error: err.to_string(),
+                    },
+                );
+                return Err(err);
+            }
+        }
+
+        // 文件名带有上传ID，不会与已有文件重名
+        let completed_dir = self
+            .config
+            .routes
+            .dir_for(&self.completed_dir(), &session.file_name);
+        let name = format!("{}-{}", id, session.file_name);
+        let path = completed_dir.join(&name);
+        fs::create_dir_all(&completed_dir).await?;
+        fs::rename(&part, &path).await?;
+        let scan = match &self.scanner {
+            Some(scanner) => {
+                let result = scanner.scan(&path).await.map_err(|e| {
+                    UploadError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                })?;
+                if !result.safe {
+                    remove_if_exists(&session_path(&self.config.dir, id)).await?;
+                    self.sessions.lock().await.remove(id);
+                    let err = UploadError::new(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        format!(
+                            "文件未通过安全扫描，已隔离: {}",
+                            result.reason().unwrap_or_default()
+                        ),
+                    );
+                    self.report(
+                        &session,
+                        ProgressKind::Failed {
+                            error: err.to_string(),
+                        },
+                    );
+                    return Err(err);
+                }
+                Some(result)
+            }
+            None => None,
+        };
+        self.report(&session, ProgressKind::Resolved { path: path.clone() });
+        // 扫描通过后才解码文件生成预览
+        let preview = match &self.config.preview {
+            Some(config) => match preview::generate(&completed_dir, &name, config).await {
+                Ok(preview) => Some(preview),
+                Err(e) => {
+                    warn!("提取文件预览失败 {}: {}", path.display(), e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let hash = match &self.blobs {
+            Some(blobs) => match import_blob(blobs, &path, &self.config.share_name, &name).await {
+                Ok(hash) => Some(hash),
+                Err(err) => {
+                    self.report(
+                        &session,
+                        ProgressKind::Failed {
+                            error: err.to_string(),
+                        },
+                    );
+                    return Err(err);
+                }
+            },
+            None => None,
+        };
+        let uploaded = UploadedFile {
+            id: session.id.clone(),
+            file_name: session.file_name.clone(),
+            path,
+            size: session.size,
+            sha256,
+            hash,
+            owner: session.owner.clone(),
+            completed_at: Utc::now(),
+            preview,
+            scan,
+        };
+        let record = serde_json::to_vec(&uploaded)
+            .map_err(|e| UploadError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        fs::write(record_path(&self.config.dir, id), record).await?;
+        remove_if_exists(&session_path(&self.config.dir, id)).await?;
+        self.sessions.lock().await.remove(id);
+        info!("上传完成 {}: {}", id, uploaded.path.display());
+        self.report(&session, ProgressKind::Done);
+
+        Ok(uploaded)
+    }
+
+    /// 上传完成的文件（传输记录），按完成时间排序
+    pub async fn completed(&self) -> std::io::Result<Vec<UploadedFile>> {
+        let mut records = Vec::new();
+        let mut entries = fs::read_dir(self.config.dir.join(RECORDS_DIR)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SESSION_EXTENSION) {
+                continue;
+            }
+            match fs::read(&path)
+                .await
+                .ok()
+                .and_then(|data| serde_json::from_slice::<UploadedFile>(&data).ok())
+            {
+                Some(record) => records.push(record),
+                None => warn!("忽略无法解析的传输记录: {}", path.display()),
+            }
+        }
+        records.sort_by_key(|record| record.completed_at);
+        Ok(records)
+    }
+
+    /// 删除完成时间早于 `cutoff` 的文件及其传输记录，返回删除的数量
+    pub async fn purge_completed_before(&self, cutoff: DateTime<Utc>) -> std::io::Result<usize> {
+        self.purge_completed(|record| record.completed_at < cutoff)
+            .await
+    }
+
+    /// 删除调用方的全部上传：取消未完成的上传，删除完成的文件及其传输记录，返回删除的数量
+    pub async fn purge_owner(&self, owner: &str) -> std::io::Result<usize> {
+        let owned: Vec<String> = {
+            let sessions = self.sessions.lock().await;
+            let mut owned = Vec::new();
+            for (id, session) in sessions.iter() {
+                if session.lock().await.owner.as_deref() == Some(owner) {
+                    owned.push(id.clone());
+                }
+            }
+            owned
+        };
+        let mut removed = 0;
+        for id in owned {
+            match self.abort(&id).await {
+                Ok(()) => removed += 1,
+                Err(e) => warn!("取消调用方的上传 {} 失败: {}", id, e),
+            }
+        }
+        removed += self
+            .purge_completed(|record| record.owner.as_deref() == Some(owner))
+            .await?;
+        Ok(removed)
+    }
+
+    async fn purge_completed(
+        &self,
+        mut purge: impl FnMut(&UploadedFile) -> bool,
+    ) -> std::io::Result<usize> {
+        let mut removed = 0;
+        for record in self.completed().await? {
+            if !purge(&record) {
+                continue;
+            }
+            remove_if_exists(&record.path).await?;
+            if let (Some(dir), Some(name)) = (
+                record.path.parent(),
+                record.path.file_name().and_then(|name| name.to_str()),
+            ) {
+                preview::remove(dir, name).await?;
+                if let (Some(blobs), Some(_)) = (&self.blobs, &record.hash) {
+                    blobs
+                        .tags()
+                        .delete(blob_tag(&self.config.share_name, name))
+                        .await
+                        .map_err(std::io::Error::other)?;
+                }
+            }
+            remove_if_exists(&record_path(&self.config.dir, &record.id)).await?;
+            removed += 1;
+        }
+        if removed > 0 {
+            info!("删除 {} 个上传完成的文件", removed);
+        }
+        Ok(removed)
+    }
+
+    /// 取消上传并删除已写入的数据
+    pub async fn abort(&self, id: &str) -> UploadResult<()> {
+        let session = self
+            .sessions
+            .lock()
+            .await
+            .remove(id)
+            .ok_or_else(|| UploadError::not_found(id))?;
+        // 等待正在写入的分块结束
+        let session = session.lock().await;
+        remove_if_exists(&part_path(&self.config.dir, id)).await?;
+        remove_if_exists(&session_path(&self.config.dir, id)).await?;
+        info!("取消上传 {}", id);
+        self.report(
+            &session,
+            ProgressKind::Failed {
+                error: "上传已取消".to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// 按当前的调度状态重新检查未完成的上传，推送暂停与恢复事件，返回状态变化的会话数。
+    /// 正在写入分块的会话跳过，写入下一个分块时再检查
+    pub async fn reschedule(&self) -> usize {
+        if self.schedule.is_none() {
+            return 0;
+        }
+        let sessions: Vec<_> = self.sessions.lock().await.values().cloned().collect();
+        let mut changed = 0;
+        for session in sessions {
+            let Ok(mut session) = session.try_lock() else {
+                continue;
+            };
+            let before = session.paused;
+            if let Err(e) = self.apply_schedule(&mut session).await {
+                warn!("更新上传 {} 的暂停状态失败: {}", session.id, e);
+            }
+            if session.paused != before {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// 持续跟随调度变化暂停与恢复上传：网络计费状态或策略变化时立即检查，
+    /// 否则定期检查以响应时间段的开始与结束。应在后台任务中运行，未设置调度时立即返回
+    pub async fn run_scheduler(self: Arc<Self>) {
+        let Some(gate) = self.schedule.clone() else {
+            return;
+        };
+        let mut changes = gate.subscribe();
+        loop {
+            tokio::select! {
+                changed = changes.changed() => {
+                    if !changed {
+                        break;
+                    }
+                }
+                _ = tokio::time::sleep(SCHEDULE_TICK) => {}
+            }
+            self.reschedule().await;
+        }
+    }
+
+    /// 按调度策略更新会话的暂停状态，状态变化时保存并推送事件；暂停时返回原因与建议的重试间隔
+    async fn apply_schedule(
+        &self,
+        session: &mut UploadSession,
+    ) -> UploadResult<Option<(PauseReason, Duration)>> {
+        let Some(gate) = &self.schedule else {
+            return Ok(None);
+        };
+        let paused = gate.check(session.size);
+        let retry_after = paused.map(|reason| gate.retry_after(reason));
+        if paused != session.paused {
+            session.paused = paused;
+            self.save(session).await?;
+            let kind = match (paused, retry_after) {
+                (Some(reason), Some(retry_after)) => {
+                    info!("上传 {} 暂停: {:?}", session.id, reason);
+                    ProgressKind::Paused {
+                        reason,
+                        retry_after_secs: retry_after.as_secs(),
+                    }
+                }
+                _ => {
+                    info!("上传 {} 恢复", session.id);
+                    ProgressKind::Resumed
+                }
+            };
+            self.report(session, kind);
+        }
+        Ok(paused.zip(retry_after))
+    }
+
+    /// 创建上传路由
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/uploads", post(init_upload))
+            .route("/api/uploads/schedule", get(get_schedule).put(set_schedule))
+            .route("/api/uploads/schedule/network", put(set_network))
+            .route(
+                "/api/uploads/:upload_id",
+                get(get_upload).patch(patch_upload).delete(delete_upload),
+            )
+            .route("/api/uploads/:upload_id/complete", post(complete_upload))
+            .with_state(self)
+    }
+
+    async fn session(&self, id: &str) -> UploadResult<Arc<Mutex<UploadSession>>> {
+        self.sessions
+            .lock()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| UploadError::not_found(id))
+    }
+
+    async fn save(&self, session: &UploadSession) -> UploadResult<()> {
+        let data = serde_json::to_vec(session)
+            .map_err(|e| UploadError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // 先写临时文件再改名，避免中断时留下不完整的状态
+        let path = session_path(&self.config.dir, &session.id);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, data).await?;
+        fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    /// 删除过期的会话与超过保留时长的传输记录
+    async fn purge_expired(&self) {
+        if let Some(completed_ttl) = self.config.completed_ttl {
+            let cutoff = chrono::Duration::from_std(completed_ttl)
+                .ok()
+                .and_then(|ttl| Utc::now().checked_sub_signed(ttl));
+            if let Some(cutoff) = cutoff {
+                if let Err(e) = self.purge_completed_before(cutoff).await {
+                    warn!("删除过期的传输记录失败: {}", e);
+                }
+            }
+        }
+
+        let ttl =
+            chrono::Duration::from_std(self.config.session_ttl).unwrap_or(chrono::Duration::MAX);
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        for (id, session) in self.sessions.lock().await.iter() {
+            // 正在写入的会话不会过期
+            if let Ok(session) = session.try_lock() {
+                if now.signed_duration_since(session.updated_at) > ttl {
+                    expired.push(id.clone());
+                }
+            }
+        }
+        for id in expired {
+            if let Err(e) = self.abort(&id).await {
+                warn!("删除过期的上传会话 {} 失败: {}", id, e);
+            }
+        }
+    }
+}
+
+/// 把请求体写入文件，超过 `limit` 字节或校验失败时返回错误，成功时返回写入的字节数
+async fn append_chunk(
+    file: &mut fs::File,
+    body: Body,
+    limit: u64,
+    expected: Option<&str>,
+) -> UploadResult<u64> {
+    let mut stream = body.into_data_stream();
+    let mut hasher = Sha256::new();
+    let mut written = 0u64;
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|e| {
+            UploadError::new(StatusCode::BAD_REQUEST, format!("读取分块失败: {}", e))
+        })?;
+        written += data.len() as u64;
+        if written > limit {
+            return Err(UploadError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("分块超过 {} 字节", limit),
+            ));
+        }
+        hasher.update(&data);
+        file.write_all(&data).await?;
+    }
+    file.flush().await?;
+    file.sync_data().await?;
+
+    if let Some(expected) = expected {
+        let actual = data_encoding::HEXLOWER.encode(&hasher.finalize());
+        if actual != expected {
+            return Err(UploadError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("分块哈希不一致: 期望 {}，实际 {}", expected, actual),
+            ));
+        }
+    }
+    Ok(written)
+}
+
+/// 把文件导入 blob 存储并以 `共享名称/文件名` 为标签，返回 blob 哈希
+async fn import_blob(
+    blobs: &Store,
+    path: &Path,
+    share_name: &str,
+    name: &str,
+) -> UploadResult<String> {
+    // blob 存储只接受绝对路径
+    let path = fs::canonicalize(path).await?;
+    let imported = blobs
+        .add_path(&path)
+        .with_named_tag(blob_tag(share_name, name))
+        .await
+        .map_err(|e| {
+            UploadError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("导入blob存储失败: {}", e),
+            )
+        })?;
+    Ok(imported.hash.to_string())
+}
+
+/// 共享文件在 blob 存储中的标签
+pub(crate) fn blob_tag(share_name: &str, name: &str) -> String {
+    format!("{}/{}", share_name, name)
+}
+
+/// 流式计算文件的 SHA-256
+async fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; HASH_BUFFER_BYTES];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+async fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn part_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(id).with_extension(PART_EXTENSION)
+}
+
+fn session_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(id).with_extension(SESSION_EXTENSION)
+}
+
+fn record_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(RECORDS_DIR)
+        .join(id)
+        .with_extension(SESSION_EXTENSION)
+}
+
+/// 检查并规范化十六进制 SHA-256
+fn normalize_checksum(hash: &str) -> UploadResult<String> {
+    let hash = hash.trim().to_lowercase();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(UploadError::new(
+            StatusCode::BAD_REQUEST,
+            format!("SHA-256 格式错误: {}", hash),
+        ));
+    }
+    Ok(hash)
+}
+
+/// 只保留文件名部分，去掉路径分隔符与控制字符
+fn sanitize_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .trim_start_matches('.')
+        .to_string();
+    if name.is_empty() {
+        "upload".to_string()
+    } else {
+        name
+    }
+}
+
+fn offset_headers(session: &UploadSession) -> [(&'static str, HeaderValue); 1] {
+    [(UPLOAD_OFFSET_HEADER, HeaderValue::from(session.offset))]
+}
+
+/// 创建上传会话
+async fn init_upload(
+    State(uploads): State<Arc<UploadManager>>,
+    context: Option<Extension<RequestContext>>,
+    Json(request): Json<InitUploadRequest>,
+) -> UploadResult<Response> {
+    let owner = context.and_then(|Extension(context)| context.caller_id);
+    let session = uploads.init_with_owner(request, owner).await?;
+    let response = InitUploadResponse {
+        session,
+        max_chunk_bytes: uploads.config.max_chunk_bytes,
+    };
+    Ok((StatusCode::CREATED, Json(response)).into_response())
+}
+
+/// 查询上传进度
+async fn get_upload(
+    State(uploads): State<Arc<UploadManager>>,
+    UrlPath(upload_id): UrlPath<String>,
+) -> UploadResult<Response> {
+    let session = uploads.status(&upload_id).await?;
+    Ok((offset_headers(&session), Json(session)).into_response())
+}
+
+/// 上传一个分块
+async fn patch_upload(
+    State(uploads): State<Arc<UploadManager>>,
+    UrlPath(upload_id): UrlPath<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> UploadResult<Response> {
+    let offset = headers
+        .get(UPLOAD_OFFSET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or_else(|| {
+            UploadError::new(
+                StatusCode::BAD_REQUEST,
+                format!("缺少或无效的 {} 请求头", UPLOAD_OFFSET_HEADER),
+            )
+        })?;
+    let checksum = headers
+        .get(CHUNK_CHECKSUM_HEADER)
+        .map(|value| value.to_str().unwrap_or_default());
+
+    let session = uploads
+        .write_chunk(&upload_id, offset, checksum, body)
+        .await?;
+    Ok((offset_headers(&session), Json(session)).into_response())
+}
+
+/// 完成上传
+async fn complete_upload(
+    State(uploads): State<Arc<UploadManager>>,
+    UrlPath(upload_id): UrlPath<String>,
+) -> UploadResult<Json<UploadedFile>> {
+    Ok(Json(uploads.complete(&upload_id).await?))
+}
+
+/// 网络计费状态
+#[derive(Debug, Deserialize)]
+pub struct NetworkStatusRequest {
+    /// 当前网络是否按流量计费
+    pub metered: bool,
+}
+
+fn schedule_gate(uploads: &UploadManager) -> UploadResult<&Arc<TransferGate>> {
+    uploads
+        .schedule
+        .as_ref()
+        .ok_or_else(|| UploadError::new(StatusCode::NOT_FOUND, "未启用传输调度"))
+}
+
+/// 查询传输调度状态
+async fn get_schedule(
+    State(uploads): State<Arc<UploadManager>>,
+) -> UploadResult<Json<TransferScheduleStatus>> {
+    Ok(Json(schedule_gate(&uploads)?.status()))
+}
+
+/// 替换传输调度策略，立即按新策略暂停或恢复上传
+async fn set_schedule(
+    State(uploads): State<Arc<UploadManager>>,
+    Json(schedule): Json<TransferSchedule>,
+) -> UploadResult<Json<TransferScheduleStatus>> {
+    let gate = schedule_gate(&uploads)?;
+    gate.set_schedule(schedule);
+    uploads.reschedule().await;
+    Ok(Json(gate.status()))
+}
+
+/// 报告网络是否按流量计费，例如由客户端根据系统的网络信息上报
+async fn set_network(
+    State(uploads): State<Arc<UploadManager>>,
+    Json(request): Json<NetworkStatusRequest>,
+) -> UploadResult<Json<TransferScheduleStatus>> {
+    let gate = schedule_gate(&uploads)?;
+    if gate.set_metered(request.metered) {
+        uploads.reschedule().await;
+    }
+    Ok(Json(gate.status()))
+}
+
+/// 取消上传
+async fn delete_upload(
+    State(uploads): State<Arc<UploadManager>>,
+    UrlPath(upload_id): UrlPath<String>,
+) -> UploadResult<StatusCode> {
+    uploads.abort(&upload_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ScanConfig, ScanPolicy, ScannerConfig};
+
+    #[tokio::test]
+    async fn test_chunked_upload_resume() {
+        let dir = std::env::temp_dir().join(format!("iroh-node-upload-{}", new_message_id()));
+        let config = UploadConfig::default()
+            .with_dir(&dir)
+            .with_max_chunk_bytes(4)
+            .with_routes(DownloadRoutes::defaults());
+        let uploads = UploadManager::open(config.clone()).unwrap();
+
+        let content = b"hello, world";
+        let session = uploads
+            .init(InitUploadRequest {
+                file_name: "../../etc/报告.txt".to_string(),
+                size: content.len() as u64,
+                sha256: Some(data_encoding::HEXLOWER.encode(&Sha256::digest(content))),
+                progress_session: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(session.file_name, "报告.txt");
+
+        let chunk = |range: std::ops::Range<usize>| Body::from(content[range].to_vec());
+        uploads
+            .write_chunk(&session.id, 0, None, chunk(0..4))
+            .await
+            .unwrap();
+
+        // 偏移量不一致时返回当前偏移量
+        let err = uploads
+            .write_chunk(&session.id, 8, None, chunk(8..12))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(err.offset, Some(4));
+
+        // 分块过大或哈希不一致时不前进
+        let err = uploads
+            .write_chunk(&session.id, 4, None, chunk(4..12))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let wrong = data_encoding::HEXLOWER.encode(&Sha256::digest(b"other"));
+        let err = uploads
+            .write_chunk(&session.id, 4, Some(&wrong), chunk(4..8))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(uploads.status(&session.id).await.unwrap().offset, 4);
+
+        // 重新打开目录后继续上传
+        drop(uploads);
+        let uploads = UploadManager::open(config).unwrap();
+        let checksum = data_encoding::HEXLOWER.encode(&Sha256::digest(&content[4..8]));
+        uploads
+            .write_chunk(&session.id, 4, Some(&checksum), chunk(4..8))
+            .await
+            .unwrap();
+        assert!(uploads.complete(&session.id).await.is_err());
+        uploads
+            .write_chunk(&session.id, 8, None, chunk(8..12))
+            .await
+            .unwrap();
+
+        let uploaded = uploads.complete(&session.id).await.unwrap();
+        assert_eq!(std::fs::read(&uploaded.path).unwrap(), content);
+        assert_eq!(
+            uploaded.path,
+            dir.join(COMPLETED_DIR)
+                .join("Docs")
+                .join(format!("{}-报告.txt", session.id))
+        );
+        assert!(uploads.status(&session.id).await.is_err());
+        let preview = uploaded.preview.clone().unwrap();
+        assert_eq!(preview.mime_type, "text/plain; charset=utf-8");
+        assert_eq!(preview.snippet.as_deref(), Some("hello, world"));
+
+        // 传输记录按保留期限与调用方删除
+        assert_eq!(uploads.completed().await.unwrap(), vec![uploaded.clone()]);
+        assert_eq!(
+            uploads
+                .purge_completed_before(uploaded.completed_at)
+                .await
+                .unwrap(),
+            0
+        );
+        let owned = uploads
+            .init_with_owner(
+                InitUploadRequest {
+                    file_name: "草稿.txt".to_string(),
+                    size: 4,
+                    sha256: None,
+                    progress_session: None,
+                },
+                Some("alice".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(uploads.purge_owner("alice").await.unwrap(), 1);
+        assert!(uploads.status(&owned.id).await.is_err());
+        assert_eq!(uploads.purge_completed_before(Utc::now()).await.unwrap(), 1);
+        assert!(!uploaded.path.exists());
+        assert!(uploads.completed().await.unwrap().is_empty());
+
+        // 按流量计费的网络上暂停大文件，网络恢复后继续
+        let gate = Arc::new(TransferGate::new(
+            TransferSchedule::default().with_large_transfer_bytes(8),
+        ));
+        gate.set_metered(true);
+        let uploads = uploads.with_schedule(gate.clone());
+        let request = |size| InitUploadRequest {
+            file_name: "大文件.bin".to_string(),
+            size,
+            sha256: None,
+            progress_session: None,
+        };
+        assert!(uploads.init(request(4)).await.unwrap().paused.is_none());
+        let large = uploads.init(request(12)).await.unwrap();
+        assert_eq!(large.paused, Some(PauseReason::Metered));
+        let err = uploads
+            .write_chunk(&large.id, 0, None, chunk(0..4))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(err.retry_after, Some(Duration::from_secs(60)));
+        assert!(uploads.schedule_status().unwrap().metered);
+
+        assert!(gate.set_metered(false));
+        assert_eq!(uploads.reschedule().await, 1);
+        assert!(uploads.status(&large.id).await.unwrap().paused.is_none());
+        uploads
+            .write_chunk(&large.id, 0, None, chunk(0..4))
+            .await
+            .unwrap();
+
+        // 未通过安全扫描的文件被隔离
+        let scanner = Arc::new(FileScanHook::new(
+            ScanConfig::default()
+                .with_quarantine_dir(dir.join("quarantine"))
+                .with_scanner(ScannerConfig::Policy(
+                    ScanPolicy::default().with_blocked_extension("bin"),
+                )),
+        ));
+        let uploads = uploads.with_scanner(scanner);
+        let blocked = uploads.init(request(4)).await.unwrap();
+        uploads
+            .write_chunk(&blocked.id, 0, None, chunk(0..4))
+            .await
+            .unwrap();
+        let err = uploads.complete(&blocked.id).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(uploads.status(&blocked.id).await.is_err());
+        assert!(dir
+            .join("quarantine")
+            .join(format!("{}-大文件.bin", blocked.id))
+            .exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_completed_upload_imported_into_blobs() {
+        let dir = std::env::temp_dir().join(format!("iroh-node-upload-{}", new_message_id()));
+        let blobs = iroh_blobs::store::mem::MemStore::new();
+        let uploads = UploadManager::open(UploadConfig::default().with_dir(&dir))
+            .unwrap()
+            .with_blobs(blobs.as_ref().clone());
+
+        let content = b"shared through iroh";
+        let session = uploads
+            .init(InitUploadRequest {
+                file_name: "notes.txt".to_string(),
+                size: content.len() as u64,
+                sha256: None,
+                progress_session: None,
+            })
+            .await
+            .unwrap();
+        uploads
+            .write_chunk(&session.id, 0, None, Body::from(content.to_vec()))
+            .await
+            .unwrap();
+        let uploaded = uploads.complete(&session.id).await.unwrap();
+
+        // 返回的哈希指向 blob 存储中的文件内容
+        let hash = iroh_blobs::Hash::new(content);
+        assert_eq!(uploaded.hash, Some(hash.to_string()));
+        assert_eq!(blobs.get_bytes(hash).await.unwrap().as_ref(), content);
+        let tag = blob_tag("uploads", &format!("{}-notes.txt", session.id));
+        assert_eq!(blobs.tags().get(&tag).await.unwrap().unwrap().hash, hash);
+        assert_eq!(uploads.completed().await.unwrap()[0].hash, uploaded.hash);
+
+        // 删除完成的文件时一并删除标签
+        let cutoff = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(uploads.purge_completed_before(cutoff).await.unwrap(), 1);
+        assert!(blobs.tags().get(&tag).await.unwrap().is_none());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
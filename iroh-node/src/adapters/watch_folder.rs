@@ -0,0 +1,533 @@
+//! 监视文件夹自动共享
+//!
+//! 定期扫描配置的本地文件夹，把新增或修改的文件复制到指定的共享文档（[`FileShareConfig`]
+//! 中的目录），相当于一个单向同步的共享文件夹。文件在一段时间内大小与修改时间都不再变化后
+//! 才复制，避免复制写到一半的文件；匹配忽略模式的文件（临时文件、隐藏文件等）不会共享。
+//! 源文件夹中删除的文件不会从共享文档中删除。
+//!
+//! 每个文件的处理结果以 [`WatchEvent`] 推送，`GET /api/iroh/watch/events` 以SSE转发
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use axum::{
+    extract::State,
+    response::sse::{Event as SseEvent, KeepAlive, Sse},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_lite::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    sync::{broadcast, Mutex},
+};
+use tracing::{debug, info, warn};
+
+use super::{
+    files::FileShareConfig,
+    preview::{self, PreviewConfig},
+};
+use crate::{NodeError, NodeResult};
+
+/// 文件事件的缓冲数量
+const WATCH_EVENT_CAPACITY: usize = 256;
+
+/// 监视的文件夹
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchFolderConfig {
+    /// 本地文件夹
+    pub source: PathBuf,
+    /// 共享到的文档名称
+    pub doc: String,
+    /// 忽略的文件名模式，`*` 匹配任意字符
+    #[serde(default = "default_ignore")]
+    pub ignore: Vec<String>,
+}
+
+fn default_ignore() -> Vec<String> {
+    [".*", "~*", "*.tmp", "*.part", "*.crdownload", "*.swp"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+impl WatchFolderConfig {
+    /// 创建监视配置，使用默认的忽略模式
+    pub fn new(source: impl Into<PathBuf>, doc: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            doc: doc.into(),
+            ignore: default_ignore(),
+        }
+    }
+
+    /// 添加忽略模式
+    pub fn with_ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// 文件名是否被忽略
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignore
+            .iter()
+            .any(|pattern| wildcard_match(pattern, name))
+    }
+}
+
+/// 简单的通配符匹配，`*` 匹配任意个字符，`?` 匹配一个字符
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// 文件夹监视配置
+#[derive(Debug, Clone)]
+pub struct FolderWatcherConfig {
+    /// 监视的文件夹
+    pub folders: Vec<WatchFolderConfig>,
+    /// 扫描间隔
+    pub poll_interval: Duration,
+    /// 文件多久不再变化后才复制
+    pub debounce: Duration,
+    /// 复制后提取预览，未设置时不提取
+    pub preview: Option<PreviewConfig>,
+}
+
+impl Default for FolderWatcherConfig {
+    fn default() -> Self {
+        Self {
+            folders: Vec::new(),
+            poll_interval: Duration::from_secs(2),
+            debounce: Duration::from_secs(3),
+            preview: Some(PreviewConfig::default()),
+        }
+    }
+}
+
+impl FolderWatcherConfig {
+    /// 添加监视的文件夹
+    pub fn with_folder(mut self, folder: WatchFolderConfig) -> Self {
+        self.folders.push(folder);
+        self
+    }
+
+    /// 设置扫描间隔
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// 设置文件稳定多久后复制
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// 设置预览提取
+    pub fn with_preview(mut self, preview: Option<PreviewConfig>) -> Self {
+        self.preview = preview;
+        self
+    }
+}
+
+/// 文件事件类型
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatchEventKind {
+    /// 新文件已共享
+    Added,
+    /// 修改后的文件已重新共享
+    Updated,
+    /// 复制失败，下次扫描时重试
+    Failed { error: String },
+}
+
+/// 文件事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchEvent {
+    /// 共享文档名称
+    pub doc: String,
+    /// 文件名
+    pub file_name: String,
+    /// 字节数
+    pub size: u64,
+    /// 事件内容
+    #[serde(flatten)]
+    pub kind: WatchEventKind,
+    /// 事件时间
+    pub at: DateTime<Utc>,
+}
+
+impl WatchEvent {
+    /// SSE事件名
+    fn name(&self) -> &'static str {
+        match self.kind {
+            WatchEventKind::Added => "added",
+            WatchEventKind::Updated => "updated",
+            WatchEventKind::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// 文件的大小与修改时间
+type Signature = (u64, SystemTime);
+
+/// 已见过的文件
+#[derive(Debug)]
+struct SeenFile {
+    /// 最近一次扫描到的状态
+    current: Signature,
+    /// 状态最近一次变化的时间
+    changed_at: Instant,
+    /// 最近一次复制时的状态
+    synced: Option<Signature>,
+}
+
+/// 单个文件夹的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchFolderStatus {
+    /// 本地文件夹
+    pub source: PathBuf,
+    /// 共享到的文档名称
+    pub doc: String,
+    /// 正在跟踪的文件数
+    pub files: usize,
+    /// 已共享的文件数
+    pub synced: usize,
+}
+
+/// 文件夹监视器
+#[derive(Debug)]
+pub struct FolderWatcher {
+    config: FolderWatcherConfig,
+    /// 每个文件夹对应的共享文档目录
+    targets: Vec<(WatchFolderConfig, PathBuf)>,
+    seen: Mutex<HashMap<PathBuf, SeenFile>>,
+    events: broadcast::Sender<WatchEvent>,
+}
+
+impl FolderWatcher {
+    /// 创建监视器，共享文档必须在 `share` 中配置
+    pub fn new(config: FolderWatcherConfig, share: &FileShareConfig) -> NodeResult<Self> {
+        let targets = config
+            .folders
+            .iter()
+            .map(|folder| match share.docs.get(&folder.doc) {
+                Some(dir) => Ok((folder.clone(), dir.clone())),
+                None => Err(NodeError::ConfigError(format!(
+                    "监视文件夹 {} 的共享文档不存在: {}",
+                    folder.source.display(),
+                    folder.doc
+                ))),
+            })
+            .collect::<NodeResult<Vec<_>>>()?;
+        let (events, _) = broadcast::channel(WATCH_EVENT_CAPACITY);
+        Ok(Self {
+            config,
+            targets,
+            seen: Mutex::new(HashMap::new()),
+            events,
+        })
+    }
+
+    /// 配置
+    pub fn config(&self) -> &FolderWatcherConfig {
+        &self.config
+    }
+
+    /// 订阅文件事件
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.events.subscribe()
+    }
+
+    /// 持续扫描，直到任务被取消
+    pub async fn run(self: Arc<Self>) {
+        info!("开始监视 {} 个文件夹", self.targets.len());
+        let mut ticker =
+            tokio::time::interval(self.config.poll_interval.max(Duration::from_millis(100)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            self.poll().await;
+        }
+    }
+
+    /// 扫描一遍全部文件夹，复制已稳定的新文件与修改过的文件，返回复制的数量
+    pub async fn poll(&self) -> usize {
+        let mut copied = 0;
+        for (folder, target) in &self.targets {
+            match self.poll_folder(folder, target).await {
+                Ok(count) => copied += count,
+                Err(e) => warn!("扫描监视文件夹 {} 失败: {}", folder.source.display(), e),
+            }
+        }
+        copied
+    }
+
+    async fn poll_folder(
+        &self,
+        folder: &WatchFolderConfig,
+        target: &Path,
+    ) -> std::io::Result<usize> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        {
+            let mut seen = self.seen.lock().await;
+            let mut entries = fs::read_dir(&folder.source).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if folder.is_ignored(&name) {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata().await else {
+                    continue;
+                };
+                if !metadata.is_file() {
+                    continue;
+                }
+                let signature = (
+                    metadata.len(),
+                    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                );
+
+                let path = entry.path();
+                let file = match seen.get_mut(&path) {
+                    Some(file) => file,
+                    None => {
+                        let synced = already_shared(&target.join(&name), signature).await;
+                        seen.entry(path.clone()).or_insert(SeenFile {
+                            current: signature,
+                            changed_at: now,
+                            synced: synced.then_some(signature),
+                        })
+                    }
+                };
+                if file.current != signature {
+                    file.current = signature;
+                    file.changed_at = now;
+                }
+                if file.synced != Some(signature)
+                    && now.duration_since(file.changed_at) >= self.config.debounce
+                {
+                    due.push((path, name, signature, file.synced.is_some()));
+                }
+            }
+        }
+
+        let mut copied = 0;
+        for (path, name, signature, existed) in due {
+            let kind = match self.share_file(&path, target, &name).await {
+                Ok(()) => {
+                    if let Some(file) = self.seen.lock().await.get_mut(&path) {
+                        file.synced = Some(signature);
+                    }
+                    copied += 1;
+                    debug!("已共享 {} 到 {}", path.display(), folder.doc);
+                    if existed {
+                        WatchEventKind::Updated
+                    } else {
+                        WatchEventKind::Added
+                    }
+                }
+                Err(e) => {
+                    warn!("共享文件 {} 失败: {}", path.display(), e);
+                    WatchEventKind::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+            let _ = self.events.send(WatchEvent {
+                doc: folder.doc.clone(),
+                file_name: name,
+                size: signature.0,
+                kind,
+                at: Utc::now(),
+            });
+        }
+        Ok(copied)
+    }
+
+    /// 先复制为隐藏的临时文件再改名，下载方不会读到复制了一半的文件
+    async fn share_file(&self, source: &Path, target: &Path, name: &str) -> std::io::Result<()> {
+        fs::create_dir_all(target).await?;
+        let partial = target.join(format!(".{}.partial", name));
+        fs::copy(source, &partial).await?;
+        fs::rename(&partial, target.join(name)).await?;
+        if let Some(config) = &self.config.preview {
+            if let Err(e) = preview::generate(target, name, config).await {
+                debug!("提取文件预览失败 {}: {}", name, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 各文件夹的状态
+    pub async fn status(&self) -> Vec<WatchFolderStatus> {
+        let seen = self.seen.lock().await;
+        self.targets
+            .iter()
+            .map(|(folder, _)| {
+                let files = seen
+                    .iter()
+                    .filter(|(path, _)| path.parent() == Some(folder.source.as_path()));
+                let (files, synced) = files.fold((0, 0), |(files, synced), (_, file)| {
+                    (
+                        files + 1,
+                        synced + usize::from(file.synced == Some(file.current)),
+                    )
+                });
+                WatchFolderStatus {
+                    source: folder.source.clone(),
+                    doc: folder.doc.clone(),
+                    files,
+                    synced,
+                }
+            })
+            .collect()
+    }
+
+    /// 创建状态与事件路由
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/iroh/watch", get(watch_status))
+            .route("/api/iroh/watch/events", get(watch_events))
+            .with_state(self)
+    }
+}
+
+/// 共享文档中已有同样大小且不旧于源文件的文件时视为已共享，重启后不重复复制
+async fn already_shared(target: &Path, (size, modified): Signature) -> bool {
+    match fs::metadata(target).await {
+        Ok(metadata) => {
+            metadata.len() == size
+                && metadata
+                    .modified()
+                    .is_ok_and(|target_modified| target_modified >= modified)
+        }
+        Err(_) => false,
+    }
+}
+
+/// 查询监视文件夹的状态
+async fn watch_status(State(watcher): State<Arc<FolderWatcher>>) -> Json<Vec<WatchFolderStatus>> {
+    Json(watcher.status().await)
+}
+
+/// 以SSE推送文件事件
+async fn watch_events(
+    State(watcher): State<Arc<FolderWatcher>>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let stream = futures_lite::stream::unfold(watcher.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse_event = SseEvent::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok(sse_event), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("监视文件夹事件订阅者落后，丢弃 {} 条事件", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_folder_sync() {
+        assert!(wildcard_match("*.tmp", "a.tmp"));
+        assert!(wildcard_match("~*", "~$报告.docx"));
+        assert!(wildcard_match("a?c*", "abcdef"));
+        assert!(!wildcard_match("*.tmp", "a.tmp.txt"));
+
+        let dir =
+            std::env::temp_dir().join(format!("iroh-node-watch-{}", crate::chat::new_message_id()));
+        let (source, shared) = (dir.join("source"), dir.join("shared"));
+        std::fs::create_dir_all(&source).unwrap();
+        let share = FileShareConfig::default().with_doc("shared", &shared);
+        let config = FolderWatcherConfig::default()
+            .with_folder(WatchFolderConfig::new(&source, "shared"))
+            .with_debounce(Duration::from_millis(200))
+            .with_preview(None);
+        assert!(FolderWatcher::new(
+            FolderWatcherConfig::default().with_folder(WatchFolderConfig::new(&source, "other")),
+            &share
+        )
+        .is_err());
+        let watcher = FolderWatcher::new(config, &share).unwrap();
+        let mut events = watcher.subscribe();
+
+        std::fs::write(source.join("notes.txt"), "hello").unwrap();
+        std::fs::write(source.join("draft.tmp"), "skip").unwrap();
+        // 文件还没稳定下来
+        assert_eq!(watcher.poll().await, 0);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(watcher.poll().await, 1);
+        assert_eq!(std::fs::read(shared.join("notes.txt")).unwrap(), b"hello");
+        assert!(!shared.join("draft.tmp").exists());
+        let event = events.recv().await.unwrap();
+        assert_eq!(
+            (event.file_name.as_str(), event.kind),
+            ("notes.txt", WatchEventKind::Added)
+        );
+
+        // 没有变化时不重复复制
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(watcher.poll().await, 0);
+
+        std::fs::write(source.join("notes.txt"), "hello, world").unwrap();
+        watcher.poll().await;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(watcher.poll().await, 1);
+        assert_eq!(events.recv().await.unwrap().kind, WatchEventKind::Updated);
+        assert_eq!(
+            std::fs::read(shared.join("notes.txt")).unwrap(),
+            b"hello, world"
+        );
+        let status = watcher.status().await;
+        assert_eq!((status[0].files, status[0].synced), (1, 1));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
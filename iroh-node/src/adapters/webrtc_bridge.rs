@@ -0,0 +1,610 @@
+//! WebRTC数据通道网关
+//!
+//! 浏览器无法直接使用iroh，网关通过HTTP交换SDP（非Trickle ICE，应答中已包含全部候选地址），
+//! 与浏览器建立WebRTC数据通道后，把浏览器发来的聊天消息与Agent请求转发到节点所在的话题，
+//! 并把该话题的节点事件（聊天、投递状态、Agent响应等）推送给浏览器，
+//! 纯网页客户端因此可以不安装桌面应用加入房间
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast::error::RecvError, watch, RwLock};
+use tracing::{debug, info, warn};
+use webrtc::{
+    api::{
+        interceptor_registry::register_default_interceptors, media_engine::MediaEngine, APIBuilder,
+        API,
+    },
+    data_channel::{data_channel_message::DataChannelMessage, RTCDataChannel},
+    ice_transport::ice_server::RTCIceServer,
+    interceptor::registry::Registry,
+    peer_connection::{
+        configuration::RTCConfiguration, peer_connection_state::RTCPeerConnectionState,
+        sdp::session_description::RTCSessionDescription, RTCPeerConnection,
+    },
+};
+
+use crate::{AgentRequestMode, NodeError, NodeEvent, NodeResult, P2PNode};
+
+/// 单条数据通道消息的最大字节数
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+/// 网关配置
+#[derive(Debug, Clone)]
+pub struct WebRtcBridgeConfig {
+    /// ICE服务器（STUN/TURN）URL
+    pub ice_servers: Vec<String>,
+    /// 同时连接的浏览器会话上限
+    pub max_sessions: usize,
+}
+
+impl Default for WebRtcBridgeConfig {
+    fn default() -> Self {
+        Self {
+            ice_servers: vec!["stun:stun.l.google.com:19302".to_string()],
+            max_sessions: 32,
+        }
+    }
+}
+
+impl WebRtcBridgeConfig {
+    /// 设置ICE服务器
+    pub fn with_ice_servers(mut self, ice_servers: Vec<String>) -> Self {
+        self.ice_servers = ice_servers;
+        self
+    }
+
+    /// 设置会话上限
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions;
+        self
+    }
+}
+
+/// 浏览器发给网关的消息
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BridgeRequest {
+    /// 发送聊天消息
+    Chat {
+        /// 消息文本
+        text: String,
+    },
+    /// 发送Agent请求，结果通过 `agent_request_completed` 事件推送
+    AgentRequest {
+        /// Agent ID
+        agent_id: String,
+        /// 提示词
+        prompt: String,
+        /// 寻址方式
+        #[serde(default)]
+        mode: AgentRequestMode,
+        /// 等待应答的毫秒数
+        timeout_ms: Option<u64>,
+    },
+    /// 将收到的消息标记为已读
+    MarkRead {
+        /// 消息ID
+        message_id: String,
+    },
+}
+
+/// 网关发给浏览器的消息
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BridgeFrame {
+    /// 数据通道已就绪
+    Welcome {
+        /// 会话ID
+        session_id: String,
+        /// 网关节点ID
+        node_id: String,
+        /// 话题ID
+        topic_id: String,
+    },
+    /// 聊天消息已发送
+    ChatSent {
+        /// 消息ID
+        message_id: String,
+    },
+    /// 消息已标记为已读
+    MarkedRead {
+        /// 消息ID
+        message_id: String,
+    },
+    /// Agent请求已发送
+    AgentRequestSent {
+        /// 请求ID
+        request_id: String,
+    },
+    /// 话题中的节点事件
+    Event {
+        /// 事件名称
+        name: String,
+        /// 事件内容
+        event: Box<NodeEvent>,
+    },
+    /// 处理请求失败
+    Error {
+        /// 错误信息
+        message: String,
+    },
+}
+
+/// 创建会话请求，包含浏览器的SDP offer
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    /// 要加入的话题ID，节点需已加入该话题
+    pub topic_id: String,
+    /// SDP offer
+    pub sdp: String,
+    /// 浏览器用户的显示名称，设置后作为聊天消息的前缀
+    pub name: Option<String>,
+}
+
+/// 创建会话响应，包含网关的SDP answer
+#[derive(Debug, Serialize)]
+pub struct CreateSessionResponse {
+    /// 会话ID
+    pub session_id: String,
+    /// SDP answer
+    pub sdp: String,
+}
+
+/// 会话信息
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeSessionInfo {
+    /// 会话ID
+    pub session_id: String,
+    /// 话题ID
+    pub topic_id: String,
+    /// 显示名称
+    pub name: Option<String>,
+    /// 连接状态
+    pub state: String,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+}
+
+/// 浏览器会话
+struct BridgeSession {
+    info: BridgeSessionInfo,
+    peer_connection: Arc<RTCPeerConnection>,
+    /// 通知事件转发任务退出
+    closed: watch::Sender<bool>,
+}
+
+/// WebRTC数据通道网关
+pub struct WebRtcBridge {
+    api: API,
+    config: WebRtcBridgeConfig,
+    sessions: Mutex<HashMap<String, BridgeSession>>,
+}
+
+impl WebRtcBridge {
+    /// 创建网关
+    pub fn new(config: WebRtcBridgeConfig) -> NodeResult<Self> {
+        let mut media_engine = MediaEngine::default();
+        let registry = register_default_interceptors(Registry::new(), &mut media_engine)
+            .map_err(|e| NodeError::ConfigError(format!("初始化WebRTC失败: {}", e)))?;
+        let api = APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_interceptor_registry(registry)
+            .build();
+
+        Ok(Self {
+            api,
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 当前会话
+    pub fn sessions(&self) -> Vec<BridgeSessionInfo> {
+        let mut sessions: Vec<BridgeSessionInfo> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|session| session.info.clone())
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        sessions
+    }
+
+    /// 网关路由
+    pub fn router(self: Arc<Self>, node: Arc<RwLock<Option<P2PNode>>>) -> Router {
+        Router::new()
+            .route("/api/webrtc/sessions", post(create_session))
+            .route("/api/webrtc/sessions", get(list_sessions))
+            .route("/api/webrtc/sessions/:session_id", delete(close_session))
+            .with_state(BridgeState { node, bridge: self })
+    }
+
+    /// 处理浏览器的offer，返回answer
+    async fn accept(
+        self: &Arc<Self>,
+        node: Arc<RwLock<Option<P2PNode>>>,
+        request: CreateSessionRequest,
+    ) -> NodeResult<CreateSessionResponse> {
+        let topic_id: TopicId = request
+            .topic_id
+            .parse()
+            .map_err(|e| NodeError::TopicError(format!("解析话题ID失败: {}", e)))?;
+        {
+            let node_read = node.read().await;
+            let node = node_read
+                .as_ref()
+                .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+            if !node.get_active_topics().await.contains(&topic_id) {
+                return Err(NodeError::TopicError(format!(
+                    "节点未加入话题: {}",
+                    topic_id
+                )));
+            }
+        }
+        if self.sessions.lock().unwrap().len() >= self.config.max_sessions {
+            return Err(NodeError::ConfigError("浏览器会话数量已达上限".to_string()));
+        }
+
+        let rtc_config = RTCConfiguration {
+            ice_servers: vec![RTCIceServer {
+                urls: self.config.ice_servers.clone(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let peer_connection = Arc::new(
+            self.api
+                .new_peer_connection(rtc_config)
+                .await
+                .map_err(webrtc_error)?,
+        );
+
+        let session_id = format!("{:016x}", rand::random::<u64>());
+        let (closed, closed_rx) = watch::channel(false);
+        let session = SessionContext {
+            session_id: session_id.clone(),
+            topic_id,
+            name: request.name.clone(),
+            node,
+            closed: closed_rx,
+        };
+
+        peer_connection.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+            let session = session.clone();
+            Box::pin(async move {
+                session.attach(channel);
+            })
+        }));
+
+        let bridge = Arc::downgrade(self);
+        let state_session_id = session_id.clone();
+        peer_connection.on_peer_connection_state_change(Box::new(
+            move |state: RTCPeerConnectionState| {
+                let bridge = bridge.clone();
+                let session_id = state_session_id.clone();
+                Box::pin(async move {
+                    let Some(bridge) = bridge.upgrade() else {
+                        return;
+                    };
+                    debug!("浏览器会话 {} 状态: {}", session_id, state);
+                    match state {
+                        RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
+                            bridge.close(&session_id).await;
+                        }
+                        _ => bridge.set_state(&session_id, state),
+                    }
+                })
+            },
+        ));
+
+        let answer = match negotiate(&peer_connection, request.sdp).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                let _ = peer_connection.close().await;
+                return Err(e);
+            }
+        };
+
+        self.sessions.lock().unwrap().insert(
+            session_id.clone(),
+            BridgeSession {
+                info: BridgeSessionInfo {
+                    session_id: session_id.clone(),
+                    topic_id: request.topic_id,
+                    name: request.name,
+                    state: RTCPeerConnectionState::New.to_string(),
+                    created_at: Utc::now(),
+                },
+                peer_connection,
+                closed,
+            },
+        );
+        info!("已接受浏览器会话: {}", session_id);
+
+        Ok(CreateSessionResponse {
+            session_id,
+            sdp: answer,
+        })
+    }
+
+    fn set_state(&self, session_id: &str, state: RTCPeerConnectionState) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(session_id) {
+            session.info.state = state.to_string();
+        }
+    }
+
+    /// 关闭会话
+    pub async fn close(&self, session_id: &str) -> bool {
+        let Some(session) = self.sessions.lock().unwrap().remove(session_id) else {
+            return false;
+        };
+        let _ = session.closed.send(true);
+        if let Err(e) = session.peer_connection.close().await {
+            warn!("关闭浏览器会话 {} 失败: {}", session_id, e);
+        }
+        info!("浏览器会话已关闭: {}", session_id);
+        true
+    }
+}
+
+/// 应用浏览器的offer并等待ICE候选收集完成，返回完整的answer
+async fn negotiate(peer_connection: &RTCPeerConnection, offer: String) -> NodeResult<String> {
+    let offer = RTCSessionDescription::offer(offer).map_err(webrtc_error)?;
+    peer_connection
+        .set_remote_description(offer)
+        .await
+        .map_err(webrtc_error)?;
+    let answer = peer_connection
+        .create_answer(None)
+        .await
+        .map_err(webrtc_error)?;
+    let mut gathering_complete = peer_connection.gathering_complete_promise().await;
+    peer_connection
+        .set_local_description(answer)
+        .await
+        .map_err(webrtc_error)?;
+    let _ = gathering_complete.recv().await;
+
+    peer_connection
+        .local_description()
+        .await
+        .map(|answer| answer.sdp)
+        .ok_or_else(|| NodeError::IrohError("生成SDP answer失败".to_string()))
+}
+
+fn webrtc_error(e: webrtc::Error) -> NodeError {
+    NodeError::IrohError(format!("WebRTC错误: {}", e))
+}
+
+/// 数据通道回调共享的会话上下文
+#[derive(Clone)]
+struct SessionContext {
+    session_id: String,
+    topic_id: TopicId,
+    name: Option<String>,
+    node: Arc<RwLock<Option<P2PNode>>>,
+    closed: watch::Receiver<bool>,
+}
+
+impl SessionContext {
+    /// 接管浏览器创建的数据通道
+    fn attach(self, channel: Arc<RTCDataChannel>) {
+        debug!(
+            "浏览器会话 {} 打开数据通道: {}",
+            self.session_id,
+            channel.label()
+        );
+
+        let on_open = self.clone();
+        let open_channel = channel.clone();
+        channel.on_open(Box::new(move || {
+            Box::pin(async move {
+                tokio::spawn(on_open.forward_events(open_channel));
+            })
+        }));
+
+        let on_message = self;
+        let message_channel = channel.clone();
+        channel.on_message(Box::new(move |message: DataChannelMessage| {
+            let session = on_message.clone();
+            let channel = message_channel.clone();
+            Box::pin(async move {
+                let frame = session.handle(&message.data).await;
+                send_frame(&channel, &frame).await;
+            })
+        }));
+    }
+
+    /// 把话题中的节点事件推送给浏览器
+    async fn forward_events(mut self, channel: Arc<RTCDataChannel>) {
+        let topic_id = self.topic_id.to_string();
+        let (mut events, welcome) = {
+            let node_read = self.node.read().await;
+            let Some(node) = node_read.as_ref() else {
+                return;
+            };
+            let welcome = BridgeFrame::Welcome {
+                session_id: self.session_id.clone(),
+                node_id: node.node_id().to_string(),
+                topic_id: topic_id.clone(),
+            };
+            (node.subscribe_events(), welcome)
+        };
+        if !send_frame(&channel, &welcome).await {
+            return;
+        }
+
+        loop {
+            tokio::select! {
+                _ = self.closed.changed() => break,
+                event = events.recv() => match event {
+                    Ok(event) if event.topic_id() == Some(topic_id.as_str()) => {
+                        let frame = BridgeFrame::Event {
+                            name: event.name().to_string(),
+                            event: Box::new(event),
+                        };
+                        if !send_frame(&channel, &frame).await {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("浏览器会话 {} 跳过了 {} 条事件", self.session_id, skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+        debug!("浏览器会话 {} 停止推送事件", self.session_id);
+    }
+
+    /// 处理浏览器发来的消息
+    async fn handle(&self, data: &[u8]) -> BridgeFrame {
+        match self.dispatch(data).await {
+            Ok(frame) => frame,
+            Err(e) => BridgeFrame::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    async fn dispatch(&self, data: &[u8]) -> NodeResult<BridgeFrame> {
+        if data.len() > MAX_FRAME_BYTES {
+            return Err(NodeError::InvalidMessage(format!(
+                "消息过大: {} 字节",
+                data.len()
+            )));
+        }
+        let request: BridgeRequest = serde_json::from_slice(data)
+            .map_err(|e| NodeError::DecodeError(format!("解析网关消息失败: {}", e)))?;
+
+        let node_read = self.node.read().await;
+        let node = node_read
+            .as_ref()
+            .ok_or_else(|| NodeError::ConfigError("节点未初始化".to_string()))?;
+        match request {
+            BridgeRequest::Chat { text } => {
+                let text = match &self.name {
+                    Some(name) => format!("{}: {}", name, text),
+                    None => text,
+                };
+                let message_id = node.send_chat(&self.topic_id, &text).await?;
+                Ok(BridgeFrame::ChatSent { message_id })
+            }
+            BridgeRequest::AgentRequest {
+                agent_id,
+                prompt,
+                mode,
+                timeout_ms,
+            } => {
+                let handle = node
+                    .send_agent_request(
+                        &self.topic_id,
+                        &agent_id,
+                        &prompt,
+                        mode,
+                        timeout_ms.map(Duration::from_millis),
+                    )
+                    .await?;
+                Ok(BridgeFrame::AgentRequestSent {
+                    request_id: handle.request_id().to_string(),
+                })
+            }
+            BridgeRequest::MarkRead { message_id } => {
+                node.mark_read(&self.topic_id, &message_id).await?;
+                Ok(BridgeFrame::MarkedRead { message_id })
+            }
+        }
+    }
+}
+
+/// 发送一帧，通道已关闭时返回 false
+async fn send_frame(channel: &RTCDataChannel, frame: &BridgeFrame) -> bool {
+    let text = match serde_json::to_string(frame) {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("编码网关消息失败: {}", e);
+            return true;
+        }
+    };
+    channel.send_text(text).await.is_ok()
+}
+
+/// 网关路由状态
+#[derive(Clone)]
+struct BridgeState {
+    node: Arc<RwLock<Option<P2PNode>>>,
+    bridge: Arc<WebRtcBridge>,
+}
+
+/// 用浏览器的offer创建会话
+async fn create_session(
+    State(state): State<BridgeState>,
+    Json(request): Json<CreateSessionRequest>,
+) -> Result<Json<CreateSessionResponse>, NodeError> {
+    Ok(Json(state.bridge.accept(state.node, request).await?))
+}
+
+/// 列出浏览器会话
+async fn list_sessions(State(state): State<BridgeState>) -> Json<Vec<BridgeSessionInfo>> {
+    Json(state.bridge.sessions())
+}
+
+/// 关闭浏览器会话
+async fn close_session(
+    State(state): State<BridgeState>,
+    Path(session_id): Path<String>,
+) -> Result<(), NodeError> {
+    if state.bridge.close(&session_id).await {
+        Ok(())
+    } else {
+        Err(NodeError::ConfigError(format!(
+            "浏览器会话不存在: {}",
+            session_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bridge_frames() {
+        let request: BridgeRequest =
+            serde_json::from_str(r#"{"type":"agent_request","agent_id":"helper","prompt":"你好"}"#)
+                .unwrap();
+        assert!(matches!(
+            request,
+            BridgeRequest::AgentRequest {
+                timeout_ms: None,
+                ..
+            }
+        ));
+
+        let event = NodeEvent::TopicJoined {
+            topic_id: "abc".to_string(),
+        };
+        assert_eq!(event.topic_id(), Some("abc"));
+        let frame = BridgeFrame::Event {
+            name: event.name().to_string(),
+            event: Box::new(event),
+        };
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "event");
+        assert_eq!(json["event"]["type"], "topic_joined");
+    }
+}
@@ -0,0 +1,430 @@
+//! 对等节点地址簿
+//!
+//! 持久化已知节点的名称、地址与信任级别。信任级别用于预先授权Agent请求、
+//! 自动接收可信节点的文件分享，并在聊天事件中标注发送者的可信程度
+
+use std::{
+    collections::HashMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use iroh::{NodeAddr, PublicKey};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::{NodeError, NodeResult};
+
+/// 信任级别，按 `Unknown < Known < Verified` 排序
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    /// 未知节点
+    #[default]
+    Unknown,
+    /// 用户认识的节点
+    Known,
+    /// 已通过线下方式核实身份的节点
+    Verified,
+}
+
+/// 基于信任级别的授权策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustPolicy {
+    /// 处理Agent请求所需的最低信任级别
+    pub min_agent_trust: TrustLevel,
+    /// 自动接收文件分享所需的最低信任级别
+    pub auto_accept_share_trust: TrustLevel,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        Self {
+            min_agent_trust: TrustLevel::Unknown,
+            auto_accept_share_trust: TrustLevel::Verified,
+        }
+    }
+}
+
+impl TrustPolicy {
+    /// 该信任级别的节点能否使用本节点的Agent
+    pub fn allows_agent(&self, trust: TrustLevel) -> bool {
+        trust >= self.min_agent_trust
+    }
+
+    /// 是否自动接收该信任级别节点的文件分享
+    pub fn auto_accepts_share(&self, trust: TrustLevel) -> bool {
+        trust >= self.auto_accept_share_trust
+    }
+}
+
+/// 地址簿中的节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerRecord {
+    /// 节点ID
+    pub node_id: String,
+    /// 节点名称
+    #[serde(default)]
+    pub name: Option<String>,
+    /// 中继服务器URL
+    #[serde(default)]
+    pub relay_url: Option<String>,
+    /// 直连地址
+    #[serde(default)]
+    pub addresses: Vec<SocketAddr>,
+    /// 信任级别
+    #[serde(default)]
+    pub trust: TrustLevel,
+    /// 本地核对安全码的时间
+    #[serde(default)]
+    pub verified_at: Option<DateTime<Utc>>,
+    /// 对方是否也确认了安全码
+    #[serde(default)]
+    pub confirmed_by_peer: bool,
+    /// 首次见到的时间
+    pub first_seen: DateTime<Utc>,
+    /// 最后见到的时间
+    pub last_seen: DateTime<Utc>,
+}
+
+impl PeerRecord {
+    fn new(node_id: String) -> Self {
+        let now = Utc::now();
+        Self {
+            node_id,
+            name: None,
+            relay_url: None,
+            addresses: Vec::new(),
+            trust: TrustLevel::Unknown,
+            verified_at: None,
+            confirmed_by_peer: false,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    /// 转换为可以加入端点地址簿的节点地址
+    pub fn node_addr(&self) -> Option<NodeAddr> {
+        let node_id: PublicKey = self.node_id.parse().ok()?;
+        let relay_url = self.relay_url.as_ref().and_then(|url| url.parse().ok());
+        if relay_url.is_none() && self.addresses.is_empty() {
+            return None;
+        }
+        Some(NodeAddr::from_parts(
+            node_id,
+            relay_url,
+            self.addresses.iter().copied(),
+        ))
+    }
+}
+
+/// 对等节点地址簿，设置了路径时每次变更后写回文件
+#[derive(Debug, Default)]
+pub struct AddressBook {
+    path: Option<PathBuf>,
+    peers: HashMap<String, PeerRecord>,
+}
+
+impl AddressBook {
+    /// 从文件加载，文件不存在时为空地址簿；未设置路径时只保存在内存中
+    pub fn load(path: Option<&Path>) -> NodeResult<Self> {
+        let peers = match path {
+            Some(path) if path.exists() => {
+                let data = fs::read(path)?;
+                let records: Vec<PeerRecord> = serde_json::from_slice(&data)
+                    .map_err(|e| NodeError::DecodeError(format!("解析地址簿失败: {}", e)))?;
+                records
+                    .into_iter()
+                    .map(|record| (record.node_id.clone(), record))
+                    .collect()
+            }
+            _ => HashMap::new(),
+        };
+
+        Ok(Self {
+            path: path.map(Path::to_path_buf),
+            peers,
+        })
+    }
+
+    fn save(&self) -> NodeResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec_pretty(&self.list())
+            .map_err(|e| NodeError::EncodeError(format!("序列化地址簿失败: {}", e)))?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// 保存失败只记录日志，不影响消息处理
+    fn save_logged(&self) {
+        if let Err(e) = self.save() {
+            warn!("保存地址簿失败: {}", e);
+        }
+    }
+
+    /// 所有节点，按最后见到时间倒序
+    pub fn list(&self) -> Vec<PeerRecord> {
+        let mut peers: Vec<_> = self.peers.values().cloned().collect();
+        peers.sort_by_key(|peer| std::cmp::Reverse(peer.last_seen));
+        peers
+    }
+
+    /// 获取节点
+    pub fn get(&self, node_id: &str) -> Option<&PeerRecord> {
+        self.peers.get(node_id)
+    }
+
+    /// 节点的信任级别，不在地址簿中时为 `Unknown`
+    pub fn trust_of(&self, node_id: &str) -> TrustLevel {
+        self.peers
+            .get(node_id)
+            .map_or(TrustLevel::Unknown, |peer| peer.trust)
+    }
+
+    /// 按授权策略检查节点能否使用本节点的Agent，拒绝时返回其信任级别
+    pub fn authorize_agent(&self, node_id: &str, policy: &TrustPolicy) -> Result<(), TrustLevel> {
+        let trust = self.trust_of(node_id);
+        if policy.allows_agent(trust) {
+            Ok(())
+        } else {
+            Err(trust)
+        }
+    }
+
+    /// 记录见到的节点与其声明的名称。
+    /// 若另一个已验证的节点使用同一名称，说明该名称对应的密钥发生了变化，返回原节点ID
+    pub fn observe(&mut self, node_id: &str, name: Option<&str>) -> Option<String> {
+        let previous = name.and_then(|name| {
+            self.peers
+                .values()
+                .find(|peer| {
+                    peer.node_id != node_id
+                        && peer.trust == TrustLevel::Verified
+                        && peer.name.as_deref() == Some(name)
+                })
+                .map(|peer| peer.node_id.clone())
+        });
+
+        let peer = self
+            .peers
+            .entry(node_id.to_string())
+            .or_insert_with(|| PeerRecord::new(node_id.to_string()));
+        peer.last_seen = Utc::now();
+        if let Some(name) = name {
+            peer.name = Some(name.to_string());
+        }
+        self.save_logged();
+        previous
+    }
+
+    /// 记录票据中的节点地址
+    pub fn observe_addr(&mut self, addr: &NodeAddr) {
+        let node_id = addr.node_id.to_string();
+        let peer = self
+            .peers
+            .entry(node_id.clone())
+            .or_insert_with(|| PeerRecord::new(node_id));
+        peer.last_seen = Utc::now();
+        if let Some(relay_url) = addr.relay_url() {
+            peer.relay_url = Some(relay_url.to_string());
+        }
+        for address in addr.direct_addresses() {
+            if !peer.addresses.contains(address) {
+                peer.addresses.push(*address);
+            }
+        }
+        self.save_logged();
+    }
+
+    /// 设置节点的信任级别与名称，节点不在地址簿中时添加
+    pub fn set_trust(
+        &mut self,
+        node_id: &str,
+        trust: TrustLevel,
+        name: Option<String>,
+    ) -> NodeResult<PeerRecord> {
+        node_id
+            .parse::<PublicKey>()
+            .map_err(|e| NodeError::ConfigError(format!("解析节点ID失败: {}", e)))?;
+
+        let peer = self
+            .peers
+            .entry(node_id.to_string())
+            .or_insert_with(|| PeerRecord::new(node_id.to_string()));
+        peer.trust = trust;
+        if name.is_some() {
+            peer.name = name;
+        }
+        let peer = peer.clone();
+        self.save()?;
+        Ok(peer)
+    }
+
+    /// 核对安全码后标记为已验证
+    pub fn mark_verified(&mut self, node_id: &str) -> NodeResult<PeerRecord> {
+        self.set_trust(node_id, TrustLevel::Verified, None)?;
+        let peer = self
+            .peers
+            .get_mut(node_id)
+            .expect("set_trust inserts the peer");
+        peer.verified_at = Some(Utc::now());
+        let peer = peer.clone();
+        self.save()?;
+        Ok(peer)
+    }
+
+    /// 记录对方发来的验证确认
+    pub fn record_peer_confirmation(&mut self, node_id: &str, confirmed: bool) {
+        let peer = self
+            .peers
+            .entry(node_id.to_string())
+            .or_insert_with(|| PeerRecord::new(node_id.to_string()));
+        peer.confirmed_by_peer = confirmed;
+        self.save_logged();
+    }
+
+    /// 从地址簿删除节点
+    pub fn remove(&mut self, node_id: &str) -> NodeResult<PeerRecord> {
+        let peer = self
+            .peers
+            .remove(node_id)
+            .ok_or_else(|| NodeError::ConfigError(format!("地址簿中没有节点: {}", node_id)))?;
+        self.save()?;
+        Ok(peer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::SecretKey;
+
+    use super::*;
+
+    #[test]
+    fn test_address_book_persists_trust() {
+        let path = std::env::temp_dir().join(format!("address-book-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let node_id = SecretKey::from_bytes(&[3; 32]).public().to_string();
+
+        let mut book = AddressBook::load(Some(&path)).unwrap();
+        assert_eq!(book.trust_of(&node_id), TrustLevel::Unknown);
+        book.observe(&node_id, Some("alice"));
+        book.set_trust(&node_id, TrustLevel::Verified, None)
+            .unwrap();
+        assert!(book
+            .set_trust("not-a-key", TrustLevel::Known, None)
+            .is_err());
+
+        let mut book = AddressBook::load(Some(&path)).unwrap();
+        let peer = book.get(&node_id).unwrap();
+        assert_eq!(peer.trust, TrustLevel::Verified);
+        assert_eq!(peer.name.as_deref(), Some("alice"));
+
+        // 已验证的名称换了密钥
+        let impostor = SecretKey::from_bytes(&[4; 32]).public().to_string();
+        assert_eq!(
+            book.observe(&impostor, Some("alice")),
+            Some(node_id.clone())
+        );
+        assert_eq!(book.observe(&impostor, Some("bob")), None);
+        assert!(TrustLevel::Verified > TrustLevel::Known);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trust_policy_levels() {
+        let default = TrustPolicy::default();
+        assert!(default.allows_agent(TrustLevel::Unknown));
+        assert!(!default.auto_accepts_share(TrustLevel::Known));
+        assert!(default.auto_accepts_share(TrustLevel::Verified));
+
+        let strict = TrustPolicy {
+            min_agent_trust: TrustLevel::Known,
+            auto_accept_share_trust: TrustLevel::Verified,
+        };
+        assert!(!strict.allows_agent(TrustLevel::Unknown));
+        assert!(strict.allows_agent(TrustLevel::Known));
+        assert!(strict.allows_agent(TrustLevel::Verified));
+
+        let policy: TrustPolicy =
+            serde_json::from_str(r#"{"min_agent_trust": "verified"}"#).unwrap();
+        assert_eq!(policy.min_agent_trust, TrustLevel::Verified);
+        assert_eq!(policy.auto_accept_share_trust, TrustLevel::Verified);
+    }
+
+    #[test]
+    fn test_authorize_agent_requests() {
+        let path = std::env::temp_dir().join(format!(
+            "address-book-authorize-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let policy = TrustPolicy {
+            min_agent_trust: TrustLevel::Known,
+            ..Default::default()
+        };
+        let trusted = SecretKey::from_bytes(&[5; 32]).public().to_string();
+        let blocked = SecretKey::from_bytes(&[6; 32]).public().to_string();
+        let stranger = SecretKey::from_bytes(&[7; 32]).public().to_string();
+
+        let mut book = AddressBook::load(Some(&path)).unwrap();
+        book.set_trust(&trusted, TrustLevel::Known, Some("alice".to_string()))
+            .unwrap();
+        // 见过但信任级别不足的节点与从未见过的节点一样被拒绝
+        book.observe(&blocked, Some("mallory"));
+        assert_eq!(book.authorize_agent(&trusted, &policy), Ok(()));
+        assert_eq!(
+            book.authorize_agent(&blocked, &policy),
+            Err(TrustLevel::Unknown)
+        );
+        assert_eq!(
+            book.authorize_agent(&stranger, &policy),
+            Err(TrustLevel::Unknown)
+        );
+        assert_eq!(
+            book.authorize_agent(&stranger, &TrustPolicy::default()),
+            Ok(())
+        );
+
+        // 重新加载后授权结果不变
+        let mut book = AddressBook::load(Some(&path)).unwrap();
+        assert_eq!(book.authorize_agent(&trusted, &policy), Ok(()));
+        assert_eq!(book.get(&blocked).unwrap().name.as_deref(), Some("mallory"));
+        assert_eq!(book.list().len(), 2);
+
+        // 降级与删除立即生效并写回文件
+        book.set_trust(&trusted, TrustLevel::Unknown, None).unwrap();
+        assert_eq!(
+            book.authorize_agent(&trusted, &policy),
+            Err(TrustLevel::Unknown)
+        );
+        book.set_trust(&blocked, TrustLevel::Verified, None)
+            .unwrap();
+        book.remove(&blocked).unwrap();
+        assert!(book.remove(&blocked).is_err());
+        let book = AddressBook::load(Some(&path)).unwrap();
+        assert_eq!(book.trust_of(&trusted), TrustLevel::Unknown);
+        assert_eq!(
+            book.authorize_agent(&blocked, &policy),
+            Err(TrustLevel::Unknown)
+        );
+        assert_eq!(book.get(&trusted).unwrap().name.as_deref(), Some("alice"));
+
+        // 只在内存中的地址簿不写文件
+        let mut memory = AddressBook::load(None).unwrap();
+        memory
+            .set_trust(&trusted, TrustLevel::Verified, None)
+            .unwrap();
+        assert_eq!(memory.authorize_agent(&trusted, &policy), Ok(()));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
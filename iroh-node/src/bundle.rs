@@ -0,0 +1,242 @@
+//! 节点配置包
+//!
+//! 将节点身份、已加入话题及其元数据导出为加密配置包，用于在桌面端与移动端之间迁移P2P身份。
+//! 配置包是 rig-agent 的口令加密数据块（[`crypto::seal_with_passphrase`]）的 base64url 编码，
+//! 旧版（`IRB1`）配置包仍可导入
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use chrono::{DateTime, Utc};
+use rig_agent::{storage::crypto, AgentError};
+use serde::{Deserialize, Serialize};
+
+use crate::{NodeConfig, NodeError, NodeResult};
+
+/// 旧版配置包格式标识
+const LEGACY_BUNDLE_MAGIC: &[u8; 4] = b"IRB1";
+/// 旧版格式的密钥派生盐长度
+const LEGACY_SALT_LEN: usize = 16;
+/// 旧版格式的 XChaCha20 随机数长度
+const LEGACY_NONCE_LEN: usize = 24;
+
+/// 配置包中的话题
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleTopic {
+    /// 话题ID
+    pub topic_id: String,
+    /// 导出时的票据（包含当时的对等节点地址）
+    pub ticket: String,
+    /// 话题名称（房间名称）
+    pub label: Option<String>,
+    /// 加入时间
+    pub joined_at: Option<DateTime<Utc>>,
+}
+
+/// 节点配置包
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeBundle {
+    /// 节点密钥
+    pub secret_key: String,
+    /// 节点名称
+    pub name: Option<String>,
+    /// 中继服务器URL
+    pub relay: Option<String>,
+    /// 禁用中继
+    pub no_relay: bool,
+    /// 代理URL
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// 隐私模式
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// 已加入的话题
+    pub topics: Vec<BundleTopic>,
+    /// 导出时间
+    pub exported_at: DateTime<Utc>,
+}
+
+impl NodeBundle {
+    /// 使用口令加密并编码为文本
+    pub fn encrypt(&self, passphrase: &str) -> NodeResult<String> {
+        if passphrase.is_empty() {
+            return Err(NodeError::ConfigError("口令不能为空".to_string()));
+        }
+
+        let plaintext = serde_json::to_vec(self)
+            .map_err(|e| NodeError::EncodeError(format!("序列化配置包失败: {}", e)))?;
+
+        let envelope = crypto::seal_with_passphrase(&plaintext, passphrase)
+            .map_err(|e| NodeError::EncodeError(format!("加密配置包失败: {}", e)))?;
+
+        Ok(data_encoding::BASE64URL_NOPAD.encode(&envelope))
+    }
+
+    /// 使用口令解码并解密
+    pub fn decrypt(encoded: &str, passphrase: &str) -> NodeResult<Self> {
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.trim().as_bytes())
+            .map_err(|e| NodeError::DecodeError(format!("解码配置包失败: {}", e)))?;
+
+        if let Some(plaintext) = open_legacy(
+            &bytes,
+            LEGACY_BUNDLE_MAGIC,
+            passphrase,
+            &[],
+            "口令错误或配置包已损坏",
+        ) {
+            return serde_json::from_slice(&plaintext?)
+                .map_err(|e| NodeError::DecodeError(format!("解析配置包失败: {}", e)));
+        }
+        if !crypto::is_passphrase_sealed(&bytes) {
+            return Err(NodeError::DecodeError("不是有效的配置包".to_string()));
+        }
+
+        let plaintext = crypto::open_with_passphrase(&bytes, passphrase).map_err(|e| match e {
+            AgentError::Permission(_) => {
+                NodeError::VerifyError("口令错误或配置包已损坏".to_string())
+            }
+            e => NodeError::DecodeError(format!("解密配置包失败: {}", e)),
+        })?;
+
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| NodeError::DecodeError(format!("解析配置包失败: {}", e)))
+    }
+
+    /// 生成用于在新设备上创建节点的配置
+    pub fn node_config(&self, bind_port: u16) -> NodeResult<NodeConfig> {
+        let relay = match &self.relay {
+            Some(url) => Some(
+                url.parse()
+                    .map_err(|e| NodeError::ConfigError(format!("解析中继URL失败: {}", e)))?,
+            ),
+            None => None,
+        };
+
+        Ok(NodeConfig::new()
+            .with_secret_key(Some(self.secret_key.clone()))
+            .with_relay(relay)
+            .with_no_relay(self.no_relay)
+            .with_proxy(self.proxy.clone())
+            .with_privacy_mode(self.privacy_mode)
+            .with_name(self.name.clone())
+            .with_bind_port(bind_port))
+    }
+}
+
+/// 解密旧版格式：格式标识、盐、随机数与密文，密钥由 Argon2 从口令派生；
+/// 格式标识不符时返回 `None`，解密失败时返回带 `mismatch` 说明的校验错误
+pub(crate) fn open_legacy(
+    bytes: &[u8],
+    magic: &[u8; 4],
+    passphrase: &str,
+    aad: &[u8],
+    mismatch: &str,
+) -> Option<NodeResult<Vec<u8>>> {
+    let rest = bytes.strip_prefix(magic.as_slice())?;
+    if rest.len() < LEGACY_SALT_LEN + LEGACY_NONCE_LEN {
+        return None;
+    }
+    let (salt, rest) = rest.split_at(LEGACY_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(LEGACY_NONCE_LEN);
+    let mut key = [0u8; 32];
+    if let Err(e) = Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key) {
+        return Some(Err(NodeError::ConfigError(format!("派生密钥失败: {}", e))));
+    }
+    let plaintext = XChaCha20Poly1305::new(Key::from_slice(&key))
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| NodeError::VerifyError(mismatch.to_string()));
+    Some(plaintext)
+}
+
+/// 以旧版格式加密，用于测试旧数据的导入
+#[cfg(test)]
+pub(crate) fn seal_legacy(
+    plaintext: &[u8],
+    magic: &[u8; 4],
+    passphrase: &str,
+    aad: &[u8],
+) -> Vec<u8> {
+    let salt: [u8; LEGACY_SALT_LEN] = rand::random();
+    let nonce: [u8; LEGACY_NONCE_LEN] = rand::random();
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .unwrap();
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .unwrap();
+    [magic.as_slice(), &salt, &nonce, &ciphertext].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_bundle() -> NodeBundle {
+        NodeBundle {
+            secret_key: "secret".to_string(),
+            name: Some("desktop".to_string()),
+            relay: None,
+            no_relay: false,
+            proxy: None,
+            privacy_mode: false,
+            topics: vec![BundleTopic {
+                topic_id: "topic".to_string(),
+                ticket: "ticket".to_string(),
+                label: Some("工作".to_string()),
+                joined_at: None,
+            }],
+            exported_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let encoded = sample_bundle().encrypt("correct horse").unwrap();
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.as_bytes())
+            .unwrap();
+        assert!(crypto::is_passphrase_sealed(&bytes));
+        let bundle = NodeBundle::decrypt(&encoded, "correct horse").unwrap();
+        assert_eq!(bundle.secret_key, "secret");
+        assert_eq!(bundle.topics[0].label.as_deref(), Some("工作"));
+    }
+
+    #[test]
+    fn test_bundle_wrong_passphrase() {
+        let encoded = sample_bundle().encrypt("correct horse").unwrap();
+        assert!(matches!(
+            NodeBundle::decrypt(&encoded, "battery staple"),
+            Err(NodeError::VerifyError(_))
+        ));
+        assert!(NodeBundle::decrypt("not-a-bundle", "x").is_err());
+    }
+
+    #[test]
+    fn test_legacy_bundle_import() {
+        let plaintext = serde_json::to_vec(&sample_bundle()).unwrap();
+        let legacy = seal_legacy(&plaintext, LEGACY_BUNDLE_MAGIC, "correct horse", &[]);
+        let encoded = data_encoding::BASE64URL_NOPAD.encode(&legacy);
+        let bundle = NodeBundle::decrypt(&encoded, "correct horse").unwrap();
+        assert_eq!(bundle.secret_key, "secret");
+        assert!(matches!(
+            NodeBundle::decrypt(&encoded, "battery staple"),
+            Err(NodeError::VerifyError(_))
+        ));
+    }
+}
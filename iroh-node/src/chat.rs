@@ -0,0 +1,598 @@
+//! P2P聊天消息
+//!
+//! 为每条聊天消息分配ID，接收方回复确认（Ack），发送方据此聚合投递状态：
+//! 已发送 / 已送达N个节点 / 已读。消息可以回复另一条消息构成消息串，也可以被添加表情回应
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    chat_log::ChatLog,
+    content::MessageContent,
+    error::NodeResult,
+    read_state::{room_unread, RoomUnread},
+};
+
+/// 每个话题保留的聊天记录条数
+const CHAT_HISTORY_CAPACITY: usize = 500;
+
+/// 表情回应的最大字节数，足以容纳带肤色与组合符的表情
+pub const MAX_REACTION_BYTES: usize = 32;
+
+/// 表情回应：表情 -> 回应的节点ID
+pub type Reactions = BTreeMap<String, BTreeSet<String>>;
+
+/// 确认类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AckKind {
+    /// 已送达
+    Delivered,
+    /// 已读
+    Read,
+}
+
+/// 投递状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    /// 已发送，尚未收到确认
+    #[default]
+    Sent,
+    /// 至少一个节点已送达
+    Delivered,
+    /// 至少一个节点已读
+    Read,
+    /// 暂存在发件箱中，等待话题连上后补发
+    Queued,
+    /// 在发件箱中过期，未发出
+    Expired,
+}
+
+/// 出站消息的投递状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryStatus {
+    /// 聚合状态
+    pub state: DeliveryState,
+    /// 已送达的节点
+    pub delivered_to: BTreeSet<String>,
+    /// 已读的节点
+    pub read_by: BTreeSet<String>,
+}
+
+impl DeliveryStatus {
+    /// 记录一个确认，状态发生变化时返回 true
+    pub fn record_ack(&mut self, peer: &str, kind: AckKind) -> bool {
+        // 已读隐含已送达
+        let mut changed = self.delivered_to.insert(peer.to_string());
+        if kind == AckKind::Read {
+            changed |= self.read_by.insert(peer.to_string());
+        }
+
+        self.state = if !self.read_by.is_empty() {
+            DeliveryState::Read
+        } else if !self.delivered_to.is_empty() {
+            DeliveryState::Delivered
+        } else {
+            DeliveryState::Sent
+        };
+        changed
+    }
+
+    /// 已送达的节点数量
+    pub fn delivered_count(&self) -> usize {
+        self.delivered_to.len()
+    }
+}
+
+/// 聊天记录条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatHistoryEntry {
+    /// 消息ID
+    pub id: String,
+    /// 话题ID
+    pub topic_id: String,
+    /// 发送者节点ID
+    pub from: String,
+    /// 消息文本，结构化内容时为其纯文本表示
+    pub text: String,
+    /// 结构化内容，纯文本消息没有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<MessageContent>,
+    /// 时间戳（本地发送或接收时间）
+    pub timestamp: DateTime<Utc>,
+    /// 是否为本节点发出的消息
+    pub outgoing: bool,
+    /// 投递状态，仅出站消息有
+    pub delivery: Option<DeliveryStatus>,
+    /// 本节点是否已读，仅入站消息有意义
+    pub read: bool,
+    /// 本地保存的文本已加密且缺少房间密钥，此时文本为空
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+    /// 被回复的消息ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    /// 表情回应
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reactions: Reactions,
+    /// 被提及的节点ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<String>,
+}
+
+/// 生成聊天消息ID
+pub(crate) fn new_message_id() -> String {
+    format!(
+        "{:016x}{:016x}",
+        rand::random::<u64>(),
+        rand::random::<u64>()
+    )
+}
+
+/// 各话题的聊天记录
+#[derive(Debug, Default)]
+pub(crate) struct ChatHistory {
+    topics: HashMap<TopicId, VecDeque<ChatHistoryEntry>>,
+    /// 本地存储，未启用时聊天记录只保存在内存中
+    log: Option<ChatLog>,
+}
+
+impl ChatHistory {
+    /// 使用本地存储，每次变更后写回
+    pub fn with_log(log: ChatLog) -> Self {
+        Self {
+            topics: HashMap::new(),
+            log: Some(log),
+        }
+    }
+
+    /// 写回话题的聊天记录
+    fn persist(&mut self, topic_id: &TopicId) {
+        let Some(log) = &mut self.log else {
+            return;
+        };
+        let entries: Vec<ChatHistoryEntry> = self
+            .topics
+            .get(topic_id)
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default();
+        if let Err(e) = log.save(&topic_id.to_string(), &entries) {
+            warn!("保存话题 {} 的聊天记录失败: {}", topic_id, e);
+        }
+    }
+
+    /// 从本地存储恢复话题的聊天记录，已锁定的消息在有密钥后被解密的版本替换，返回新恢复或解锁的条数
+    pub fn restore(&mut self, topic_id: &TopicId) -> NodeResult<usize> {
+        let Some(log) = &mut self.log else {
+            return Ok(0);
+        };
+        let loaded = log.load(&topic_id.to_string())?;
+        let entries = self.topics.entry(*topic_id).or_default();
+        let mut restored = 0;
+        for entry in loaded {
+            match entries.iter_mut().find(|e| e.id == entry.id) {
+                Some(existing) if existing.locked && !entry.locked => {
+                    existing.text = entry.text;
+                    existing.locked = false;
+                    restored += 1;
+                }
+                Some(_) => {}
+                None => {
+                    entries.push_back(entry);
+                    restored += 1;
+                }
+            }
+        }
+        entries
+            .make_contiguous()
+            .sort_by_key(|entry| entry.timestamp);
+        while entries.len() > CHAT_HISTORY_CAPACITY {
+            entries.pop_front();
+        }
+        if entries.is_empty() {
+            self.topics.remove(topic_id);
+        }
+        Ok(restored)
+    }
+
+    /// 本地存储，未启用时为空
+    pub fn log_mut(&mut self) -> Option<&mut ChatLog> {
+        self.log.as_mut()
+    }
+
+    /// 追加记录，超过容量时丢弃最早的记录；重复的消息ID被忽略
+    pub fn push(&mut self, topic_id: TopicId, entry: ChatHistoryEntry) -> bool {
+        let entries = self.topics.entry(topic_id).or_default();
+        if entries.iter().any(|e| e.id == entry.id) {
+            return false;
+        }
+        if entries.len() >= CHAT_HISTORY_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+        self.persist(&topic_id);
+        true
+    }
+
+    /// 为出站消息记录确认，状态变化时返回新的投递状态
+    pub fn record_ack(
+        &mut self,
+        topic_id: &TopicId,
+        message_id: &str,
+        peer: &str,
+        kind: AckKind,
+    ) -> Option<DeliveryStatus> {
+        let entry = self
+            .topics
+            .get_mut(topic_id)?
+            .iter_mut()
+            .find(|e| e.outgoing && e.id == message_id)?;
+        let delivery = entry.delivery.get_or_insert_with(DeliveryStatus::default);
+        let status = delivery.record_ack(peer, kind).then(|| delivery.clone())?;
+        self.persist(topic_id);
+        Some(status)
+    }
+
+    /// 更新暂存在发件箱中的出站消息的状态，消息不在发件箱中时返回 None
+    pub fn update_queued(
+        &mut self,
+        topic_id: &TopicId,
+        message_id: &str,
+        state: DeliveryState,
+    ) -> Option<DeliveryStatus> {
+        let delivery = self
+            .topics
+            .get_mut(topic_id)?
+            .iter_mut()
+            .find(|e| e.outgoing && e.id == message_id)?
+            .delivery
+            .as_mut()
+            .filter(|delivery| delivery.state == DeliveryState::Queued)?;
+        delivery.state = state;
+        let status = delivery.clone();
+        self.persist(topic_id);
+        Some(status)
+    }
+
+    /// 将入站消息标记为已读，返回是否存在该消息
+    pub fn mark_read(&mut self, topic_id: &TopicId, message_id: &str) -> bool {
+        match self.topics.get_mut(topic_id).and_then(|entries| {
+            entries
+                .iter_mut()
+                .find(|e| !e.outgoing && e.id == message_id)
+        }) {
+            Some(entry) => {
+                entry.read = true;
+                self.persist(topic_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 将收到的消息标记为已读，直到并包括指定消息，返回新标记为已读的消息ID；
+    /// 消息不存在时返回空
+    pub fn mark_read_until(&mut self, topic_id: &TopicId, message_id: &str) -> Option<Vec<String>> {
+        let entries = self.topics.get_mut(topic_id)?;
+        let position = entries.iter().position(|e| e.id == message_id)?;
+        let marked: Vec<String> = entries
+            .iter_mut()
+            .take(position + 1)
+            .filter(|e| !e.outgoing && !e.read)
+            .map(|e| {
+                e.read = true;
+                e.id.clone()
+            })
+            .collect();
+        if !marked.is_empty() {
+            self.persist(topic_id);
+        }
+        Some(marked)
+    }
+
+    /// 聊天记录中的一条消息
+    pub fn get(&self, topic_id: &TopicId, message_id: &str) -> Option<&ChatHistoryEntry> {
+        self.topics
+            .get(topic_id)?
+            .iter()
+            .find(|e| e.id == message_id)
+    }
+
+    /// 话题中最新一条收到的消息ID
+    pub fn latest_incoming(&self, topic_id: &TopicId) -> Option<String> {
+        self.topics
+            .get(topic_id)?
+            .iter()
+            .rev()
+            .find(|e| !e.outgoing)
+            .map(|e| e.id.clone())
+    }
+
+    /// 话题的未读状态
+    pub fn unread(&self, topic_id: &TopicId) -> RoomUnread {
+        room_unread(
+            &topic_id.to_string(),
+            self.topics.get(topic_id).into_iter().flatten(),
+        )
+    }
+
+    /// 添加或撤销节点对消息的表情回应，回应发生变化时返回消息新的全部回应
+    pub fn react(
+        &mut self,
+        topic_id: &TopicId,
+        message_id: &str,
+        peer: &str,
+        emoji: &str,
+        removed: bool,
+    ) -> Option<Reactions> {
+        let entry = self
+            .topics
+            .get_mut(topic_id)?
+            .iter_mut()
+            .find(|e| e.id == message_id)?;
+        let changed = if removed {
+            let changed = entry
+                .reactions
+                .get_mut(emoji)
+                .is_some_and(|peers| peers.remove(peer));
+            entry.reactions.retain(|_, peers| !peers.is_empty());
+            changed
+        } else {
+            entry
+                .reactions
+                .entry(emoji.to_string())
+                .or_default()
+                .insert(peer.to_string())
+        };
+        if !changed {
+            return None;
+        }
+        let reactions = entry.reactions.clone();
+        self.persist(topic_id);
+        Some(reactions)
+    }
+
+    /// 消息所在的消息串：根消息及其全部回复（按时间顺序），消息不存在时返回 None
+    ///
+    /// 根消息已被移出记录时，以仍在记录中的最早祖先为根
+    pub fn thread(&self, topic_id: &TopicId, message_id: &str) -> Option<Vec<ChatHistoryEntry>> {
+        let entries = self.topics.get(topic_id)?;
+        let find = |id: &str| entries.iter().find(|e| e.id == id);
+        let mut root = find(message_id)?;
+        let mut visited = HashSet::from([root.id.as_str()]);
+        while let Some(parent) = root.reply_to.as_deref().and_then(find) {
+            // 防御构造出的回复环
+            if !visited.insert(parent.id.as_str()) {
+                break;
+            }
+            root = parent;
+        }
+
+        // 回复总在被回复的消息之后到达，按时间顺序一次遍历即可收集
+        let mut members = HashSet::from([root.id.as_str()]);
+        let mut thread = Vec::new();
+        for entry in entries {
+            let in_thread = entry.id == root.id
+                || entry
+                    .reply_to
+                    .as_deref()
+                    .is_some_and(|parent| members.contains(parent));
+            if in_thread {
+                members.insert(entry.id.as_str());
+                thread.push(entry.clone());
+            }
+        }
+        Some(thread)
+    }
+
+    /// 获取话题最近的聊天记录（按时间顺序）
+    pub fn entries(&self, topic_id: &TopicId, limit: Option<usize>) -> Vec<ChatHistoryEntry> {
+        let Some(entries) = self.topics.get(topic_id) else {
+            return Vec::new();
+        };
+        let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// 移除话题的聊天记录
+    pub fn remove_topic(&mut self, topic_id: &TopicId) {
+        self.topics.remove(topic_id);
+    }
+
+    /// 只保留满足条件的记录，返回删除的条数
+    pub fn retain(&mut self, mut keep: impl FnMut(&ChatHistoryEntry) -> bool) -> usize {
+        let mut removed = 0;
+        let mut changed = Vec::new();
+        for (topic_id, entries) in self.topics.iter_mut() {
+            let before = entries.len();
+            entries.retain(|entry| keep(entry));
+            if entries.len() != before {
+                removed += before - entries.len();
+                changed.push(*topic_id);
+            }
+        }
+        for topic_id in &changed {
+            self.persist(topic_id);
+        }
+        self.topics.retain(|_, entries| !entries.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, outgoing: bool) -> ChatHistoryEntry {
+        ChatHistoryEntry {
+            id: id.to_string(),
+            topic_id: String::new(),
+            from: "me".to_string(),
+            text: "你好".to_string(),
+            content: None,
+            timestamp: Utc::now(),
+            outgoing,
+            delivery: outgoing.then(DeliveryStatus::default),
+            read: false,
+            locked: false,
+            reply_to: None,
+            reactions: Reactions::new(),
+            mentions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_reactions_and_threads() {
+        let topic = TopicId::from_bytes([2; 32]);
+        let mut history = ChatHistory::default();
+        history.push(topic, entry("a", true));
+        history.push(topic, entry("b", false));
+
+        // 回应记在发送回应的节点名下，其他节点无法撤销
+        history.react(&topic, "a", "peer1", "👍", false).unwrap();
+        assert!(history.react(&topic, "a", "peer2", "👍", true).is_none());
+        assert!(history.get(&topic, "a").unwrap().reactions["👍"].contains("peer1"));
+        let reactions = history.react(&topic, "a", "peer2", "🎉", false).unwrap();
+        assert_eq!(reactions["👍"].len(), 1);
+        assert_eq!(reactions["🎉"].len(), 1);
+        assert!(history
+            .react(&topic, "missing", "peer1", "👍", false)
+            .is_none());
+        let other = TopicId::from_bytes([3; 32]);
+        assert!(history.react(&other, "a", "peer1", "👍", false).is_none());
+
+        // 回复挂在已有的被回复消息下，各消息串互不混入
+        let reply = |id: &str, parent: &str| {
+            let mut reply = entry(id, false);
+            reply.reply_to = Some(parent.to_string());
+            reply
+        };
+        history.push(topic, reply("r1", "a"));
+        history.push(topic, reply("s1", "b"));
+        history.push(topic, reply("r2", "r1"));
+        let ids =
+            |thread: Vec<ChatHistoryEntry>| thread.into_iter().map(|e| e.id).collect::<Vec<_>>();
+        assert_eq!(ids(history.thread(&topic, "a").unwrap()), ["a", "r1", "r2"]);
+        assert_eq!(ids(history.thread(&topic, "s1").unwrap()), ["b", "s1"]);
+
+        // 被回复的消息不在记录中时，回复自成消息串
+        assert!(history.get(&topic, "gone").is_none());
+        history.push(topic, reply("o1", "gone"));
+        history.push(topic, reply("o2", "o1"));
+        assert_eq!(ids(history.thread(&topic, "o2").unwrap()), ["o1", "o2"]);
+        assert!(history.thread(&topic, "gone").is_none());
+
+        // 根消息被移出记录后，以最早仍在的祖先为根
+        history.retain(|entry| entry.id != "a");
+        assert_eq!(ids(history.thread(&topic, "r2").unwrap()), ["r1", "r2"]);
+    }
+
+    #[test]
+    fn test_ack_aggregation() {
+        let topic = TopicId::from_bytes([1; 32]);
+        let mut history = ChatHistory::default();
+        assert!(history.push(topic, entry("a", true)));
+        assert!(!history.push(topic, entry("a", true)));
+        history.push(topic, entry("b", false));
+
+        let status = history
+            .record_ack(&topic, "a", "peer1", AckKind::Delivered)
+            .unwrap();
+        assert_eq!(status.state, DeliveryState::Delivered);
+        assert!(history
+            .record_ack(&topic, "a", "peer1", AckKind::Delivered)
+            .is_none());
+
+        let status = history
+            .record_ack(&topic, "a", "peer2", AckKind::Read)
+            .unwrap();
+        assert_eq!(status.state, DeliveryState::Read);
+        assert_eq!(status.delivered_count(), 2);
+        assert_eq!(status.read_by.len(), 1);
+
+        // 入站消息不接受确认
+        assert!(history
+            .record_ack(&topic, "b", "peer1", AckKind::Read)
+            .is_none());
+        assert!(history.mark_read(&topic, "b"));
+        assert!(!history.mark_read(&topic, "a"));
+
+        let entries = history.entries(&topic, Some(1));
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].read);
+
+        // 发件箱中的消息补发后变为已发送
+        let mut queued = entry("c", true);
+        queued.delivery = Some(DeliveryStatus {
+            state: DeliveryState::Queued,
+            ..Default::default()
+        });
+        history.push(topic, queued);
+        let status = history
+            .update_queued(&topic, "c", DeliveryState::Sent)
+            .unwrap();
+        assert_eq!(status.state, DeliveryState::Sent);
+        assert!(history
+            .update_queued(&topic, "c", DeliveryState::Expired)
+            .is_none());
+        assert!(history
+            .update_queued(&topic, "a", DeliveryState::Sent)
+            .is_none());
+
+        // 回复构成消息串，从任一条消息都能取到整个消息串
+        let mut reply = entry("r1", false);
+        reply.reply_to = Some("a".to_string());
+        history.push(topic, reply);
+        let mut nested = entry("r2", true);
+        nested.reply_to = Some("r1".to_string());
+        history.push(topic, nested);
+        let thread = history.thread(&topic, "r2").unwrap();
+        let ids: Vec<&str> = thread.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["a", "r1", "r2"]);
+        assert!(history.thread(&topic, "missing").is_none());
+
+        // 同一节点重复回应不产生变化，全部撤销后移除该表情
+        let reactions = history.react(&topic, "a", "peer1", "👍", false).unwrap();
+        assert_eq!(reactions["👍"].len(), 1);
+        assert!(history.react(&topic, "a", "peer1", "👍", false).is_none());
+        history.react(&topic, "a", "peer2", "👍", false).unwrap();
+        let reactions = history.react(&topic, "a", "peer1", "👍", true).unwrap();
+        assert_eq!(reactions["👍"].len(), 1);
+        let reactions = history.react(&topic, "a", "peer2", "👍", true).unwrap();
+        assert!(reactions.is_empty());
+        assert!(history.react(&topic, "a", "peer2", "👍", true).is_none());
+        history.retain(|entry| !entry.id.starts_with('r'));
+
+        assert_eq!(history.retain(|entry| entry.outgoing), 1);
+        assert_eq!(history.entries(&topic, None).len(), 2);
+
+        // 启用本地存储时重新加入话题后恢复记录
+        let dir = std::env::temp_dir().join(format!("iroh-node-chat-{}", new_message_id()));
+        let log = ChatLog::new(crate::ChatLogConfig::new(&dir).with_encrypt(false), None).unwrap();
+        let mut persisted = ChatHistory::with_log(log);
+        persisted.push(topic, entry("d", false));
+        assert!(persisted.mark_read(&topic, "d"));
+        persisted.remove_topic(&topic);
+        assert!(persisted.entries(&topic, None).is_empty());
+        assert_eq!(persisted.restore(&topic).unwrap(), 1);
+        assert!(persisted.entries(&topic, None)[0].read);
+        assert_eq!(persisted.restore(&topic).unwrap(), 0);
+
+        // 标记到某条消息为止的全部消息已读
+        persisted.push(topic, entry("e", false));
+        persisted.push(topic, entry("f", false));
+        assert_eq!(persisted.unread(&topic).unread, 2);
+        assert_eq!(persisted.latest_incoming(&topic).as_deref(), Some("f"));
+        assert_eq!(persisted.mark_read_until(&topic, "e").unwrap(), ["e"]);
+        let unread = persisted.unread(&topic);
+        assert_eq!(unread.unread, 1);
+        assert_eq!(unread.last_read.unwrap().message_id, "e");
+        assert!(persisted.mark_read_until(&topic, "missing").is_none());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
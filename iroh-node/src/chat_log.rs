@@ -0,0 +1,485 @@
+//! 聊天记录的本地存储
+//!
+//! 每个话题的聊天记录保存为目录中的一个JSON文件，重新加入话题时恢复。
+//! 启用加密时只加密消息文本：每个房间一个随机密钥，与传输加密无关；
+//! 消息ID、时间与投递状态保持明文，数据保留策略与确认处理不需要密钥。
+//!
+//! 房间密钥保存在节点的密钥存储中；未配置密钥存储时只在本次运行期间有效。
+//! 密钥可以用口令加密导出，在其他设备或重装后导入（替换当前密钥）；
+//! 缺少密钥的消息在历史接口中标记为已锁定，导入密钥后自动解密
+
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rig_agent::{storage::crypto, AgentError, SecretStore};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    bundle::open_legacy,
+    chat::{ChatHistoryEntry, DeliveryStatus, Reactions},
+    content::MessageContent,
+    error::{NodeError, NodeResult},
+};
+
+/// 密钥存储中房间密钥名称的前缀，后接话题ID
+pub const CHAT_KEY_PREFIX: &str = "iroh_chat_key_";
+/// 旧版导出的房间密钥格式标识
+const LEGACY_KEY_MAGIC: &[u8; 4] = b"IRK1";
+/// XChaCha20 随机数长度
+const NONCE_LEN: usize = 24;
+
+/// 聊天记录存储配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatLogConfig {
+    /// 保存聊天记录的目录
+    pub dir: PathBuf,
+    /// 是否用房间密钥加密消息文本
+    #[serde(default = "default_encrypt")]
+    pub encrypt: bool,
+}
+
+fn default_encrypt() -> bool {
+    true
+}
+
+impl ChatLogConfig {
+    /// 创建配置，默认加密
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            encrypt: true,
+        }
+    }
+
+    /// 设置是否加密消息文本
+    pub fn with_encrypt(mut self, encrypt: bool) -> Self {
+        self.encrypt = encrypt;
+        self
+    }
+}
+
+/// 保存的消息文本
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StoredText {
+    /// 明文
+    Plain(String),
+    /// 随机数与密文（base64url）
+    Sealed(String),
+}
+
+/// 保存的聊天记录条目，与 [`ChatHistoryEntry`] 相同，只有文本可能被加密
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredEntry {
+    id: String,
+    from: String,
+    text: StoredText,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    outgoing: bool,
+    delivery: Option<DeliveryStatus>,
+    read: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Reactions::is_empty")]
+    reactions: Reactions,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mentions: Vec<String>,
+    /// 文本为结构化内容的JSON，与文本一起加密
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    rich: bool,
+}
+
+/// 聊天记录存储
+pub(crate) struct ChatLog {
+    config: ChatLogConfig,
+    store: Option<SecretStore>,
+    /// 话题ID -> 房间密钥
+    keys: HashMap<String, [u8; 32]>,
+    /// 话题ID -> 消息ID -> 密文，避免每次保存重新加密；也保留无法解密的消息的密文
+    sealed: HashMap<String, HashMap<String, String>>,
+}
+
+/// 不输出密钥
+impl std::fmt::Debug for ChatLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatLog")
+            .field("config", &self.config)
+            .field("rooms", &self.keys.len())
+            .finish()
+    }
+}
+
+impl ChatLog {
+    /// 创建存储，`store` 用于保存房间密钥
+    pub fn new(config: ChatLogConfig, store: Option<SecretStore>) -> NodeResult<Self> {
+        fs::create_dir_all(&config.dir)?;
+        if config.encrypt && store.is_none() {
+            warn!("未配置密钥存储，聊天记录的房间密钥只在本次运行期间有效，请导出备份");
+        }
+        Ok(Self {
+            config,
+            store,
+            keys: HashMap::new(),
+            sealed: HashMap::new(),
+        })
+    }
+
+    fn path(&self, topic_id: &str) -> PathBuf {
+        self.config.dir.join(format!("{}.json", topic_id))
+    }
+
+    /// 房间密钥，`create` 为 true 时不存在则生成
+    fn key(&mut self, topic_id: &str, create: bool) -> NodeResult<Option<[u8; 32]>> {
+        if let Some(key) = self.keys.get(topic_id) {
+            return Ok(Some(*key));
+        }
+        let name = format!("{}{}", CHAT_KEY_PREFIX, topic_id);
+        let encoded = match (&self.store, create) {
+            (Some(store), true) => Some(store.get_or_insert_with(&name, || {
+                data_encoding::BASE64URL_NOPAD.encode(&rand::random::<[u8; 32]>())
+            })?),
+            (Some(store), false) => store.get(&name)?,
+            (None, true) => {
+                Some(data_encoding::BASE64URL_NOPAD.encode(&rand::random::<[u8; 32]>()))
+            }
+            (None, false) => None,
+        };
+        let Some(encoded) = encoded else {
+            return Ok(None);
+        };
+        let key = decode_key(&encoded)?;
+        self.keys.insert(topic_id.to_string(), key);
+        Ok(Some(key))
+    }
+
+    /// 本节点是否有房间密钥
+    #[cfg(test)]
+    pub fn has_key(&mut self, topic_id: &str) -> bool {
+        matches!(self.key(topic_id, false), Ok(Some(_)))
+    }
+
+    /// 读取话题的聊天记录，无法解密的消息文本为空并标记为已锁定
+    pub fn load(&mut self, topic_id: &str) -> NodeResult<Vec<ChatHistoryEntry>> {
+        let path = self.path(topic_id);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let stored: Vec<StoredEntry> = serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| NodeError::DecodeError(format!("解析聊天记录失败: {}", e)))?;
+        let key = self.key(topic_id, false)?;
+        let sealed = self.sealed.entry(topic_id.to_string()).or_default();
+
+        Ok(stored
+            .into_iter()
+            .map(|entry| {
+                let (mut text, locked) = match entry.text {
+                    StoredText::Plain(text) => (text, false),
+                    StoredText::Sealed(data) => {
+                        let opened =
+                            key.and_then(|key| open_text(&key, topic_id, &entry.id, &data));
+                        sealed.insert(entry.id.clone(), data);
+                        match opened {
+                            Some(text) => (text, false),
+                            None => (String::new(), true),
+                        }
+                    }
+                };
+                let mut content = None;
+                if entry.rich && !locked {
+                    match serde_json::from_str::<MessageContent>(&text) {
+                        Ok(parsed) => {
+                            text = parsed.plain_text();
+                            content = Some(parsed);
+                        }
+                        Err(e) => warn!("解析消息 {} 的结构化内容失败: {}", entry.id, e),
+                    }
+                }
+                ChatHistoryEntry {
+                    id: entry.id,
+                    topic_id: topic_id.to_string(),
+                    from: entry.from,
+                    text,
+                    content,
+                    timestamp: entry.timestamp,
+                    outgoing: entry.outgoing,
+                    delivery: entry.delivery,
+                    read: entry.read,
+                    locked,
+                    reply_to: entry.reply_to,
+                    reactions: entry.reactions,
+                    mentions: entry.mentions,
+                }
+            })
+            .collect())
+    }
+
+    /// 保存话题的聊天记录
+    pub fn save(&mut self, topic_id: &str, entries: &[ChatHistoryEntry]) -> NodeResult<()> {
+        let key = if self.config.encrypt {
+            self.key(topic_id, true)?
+        } else {
+            None
+        };
+        let sealed = self.sealed.entry(topic_id.to_string()).or_default();
+        sealed.retain(|id, _| entries.iter().any(|entry| &entry.id == id));
+
+        let mut stored = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let body = match &entry.content {
+                Some(content) => serde_json::to_string(content)
+                    .map_err(|e| NodeError::EncodeError(format!("序列化消息内容失败: {}", e)))?,
+                None => entry.text.clone(),
+            };
+            let cached = sealed.get(&entry.id).cloned();
+            let text = match (cached, key) {
+                // 已加密过或无法解密的消息保留原密文
+                (Some(data), _) if key.is_some() || entry.locked => StoredText::Sealed(data),
+                (_, Some(key)) => {
+                    let data = seal_text(&key, topic_id, &entry.id, &body)?;
+                    sealed.insert(entry.id.clone(), data.clone());
+                    StoredText::Sealed(data)
+                }
+                _ => StoredText::Plain(body),
+            };
+            stored.push(StoredEntry {
+                id: entry.id.clone(),
+                from: entry.from.clone(),
+                text,
+                timestamp: entry.timestamp,
+                outgoing: entry.outgoing,
+                delivery: entry.delivery.clone(),
+                read: entry.read,
+                reply_to: entry.reply_to.clone(),
+                reactions: entry.reactions.clone(),
+                mentions: entry.mentions.clone(),
+                rich: entry.content.is_some(),
+            });
+        }
+
+        let data = serde_json::to_vec(&stored)
+            .map_err(|e| NodeError::EncodeError(format!("序列化聊天记录失败: {}", e)))?;
+        let path = self.path(topic_id);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// 用口令加密导出房间密钥，没有密钥时生成一个
+    pub fn export_key(&mut self, topic_id: &str, passphrase: &str) -> NodeResult<String> {
+        if passphrase.is_empty() {
+            return Err(NodeError::ConfigError("口令不能为空".to_string()));
+        }
+        let key = self
+            .key(topic_id, true)?
+            .expect("create 为 true 时总有密钥");
+
+        // 话题ID与密钥一起加密，导入时确认密钥属于该话题
+        let mut plaintext = key.to_vec();
+        plaintext.extend_from_slice(topic_id.as_bytes());
+        let envelope = crypto::seal_with_passphrase(&plaintext, passphrase)
+            .map_err(|e| NodeError::EncodeError(format!("加密房间密钥失败: {}", e)))?;
+        Ok(data_encoding::BASE64URL_NOPAD.encode(&envelope))
+    }
+
+    /// 导入用 [`ChatLog::export_key`] 导出的房间密钥，覆盖现有密钥；也接受旧版（`IRK1`）导出的密钥
+    pub fn import_key(
+        &mut self,
+        topic_id: &str,
+        encoded: &str,
+        passphrase: &str,
+    ) -> NodeResult<()> {
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.trim().as_bytes())
+            .map_err(|e| NodeError::DecodeError(format!("解码房间密钥失败: {}", e)))?;
+        let key = match open_legacy(
+            &bytes,
+            LEGACY_KEY_MAGIC,
+            passphrase,
+            topic_id.as_bytes(),
+            "口令错误或房间密钥不属于该话题",
+        ) {
+            // 旧版把话题ID作为附加数据，解密成功即说明密钥属于该话题
+            Some(plaintext) => plaintext?
+                .try_into()
+                .map_err(|_| NodeError::DecodeError("房间密钥长度错误".to_string()))?,
+            None => open_key(&bytes, topic_id, passphrase)?,
+        };
+
+        if let Some(store) = &self.store {
+            store.set(
+                &format!("{}{}", CHAT_KEY_PREFIX, topic_id),
+                &data_encoding::BASE64URL_NOPAD.encode(&key),
+            )?;
+        }
+        self.keys.insert(topic_id.to_string(), key);
+        Ok(())
+    }
+}
+
+fn decode_key(encoded: &str) -> NodeResult<[u8; 32]> {
+    data_encoding::BASE64URL_NOPAD
+        .decode(encoded.as_bytes())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| NodeError::DecodeError("密钥存储中的房间密钥无效".to_string()))
+}
+
+/// 附加数据绑定话题与消息ID，密文不能被挪用到其他消息
+fn associated_data(topic_id: &str, message_id: &str) -> Vec<u8> {
+    format!("{}/{}", topic_id, message_id).into_bytes()
+}
+
+fn seal_text(key: &[u8; 32], topic_id: &str, message_id: &str, text: &str) -> NodeResult<String> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: text.as_bytes(),
+                aad: &associated_data(topic_id, message_id),
+            },
+        )
+        .map_err(|e| NodeError::EncodeError(format!("加密聊天记录失败: {}", e)))?;
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&ciphertext);
+    Ok(data_encoding::BASE64URL_NOPAD.encode(&bytes))
+}
+
+fn open_text(key: &[u8; 32], topic_id: &str, message_id: &str, data: &str) -> Option<String> {
+    let bytes = data_encoding::BASE64URL_NOPAD
+        .decode(data.as_bytes())
+        .ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = XChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &associated_data(topic_id, message_id),
+            },
+        )
+        .ok()?;
+    String::from_utf8(plaintext).ok()
+}
+
+/// 解密口令加密的房间密钥，确认密钥属于该话题
+fn open_key(bytes: &[u8], topic_id: &str, passphrase: &str) -> NodeResult<[u8; 32]> {
+    if !crypto::is_passphrase_sealed(bytes) {
+        return Err(NodeError::DecodeError("不是有效的房间密钥".to_string()));
+    }
+
+    let plaintext = crypto::open_with_passphrase(bytes, passphrase).map_err(|e| match e {
+        AgentError::Permission(_) => NodeError::VerifyError("口令错误或房间密钥已损坏".to_string()),
+        e => NodeError::DecodeError(format!("解密房间密钥失败: {}", e)),
+    })?;
+    let Some((key, owner)) = plaintext.split_first_chunk::<32>() else {
+        return Err(NodeError::DecodeError("房间密钥长度错误".to_string()));
+    };
+    if owner != topic_id.as_bytes() {
+        return Err(NodeError::VerifyError("房间密钥不属于该话题".to_string()));
+    }
+    Ok(*key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, text: &str) -> ChatHistoryEntry {
+        ChatHistoryEntry {
+            id: id.to_string(),
+            topic_id: "room".to_string(),
+            from: "peer".to_string(),
+            text: text.to_string(),
+            content: None,
+            timestamp: chrono::Utc::now(),
+            outgoing: false,
+            delivery: None,
+            read: false,
+            locked: false,
+            reply_to: None,
+            reactions: Default::default(),
+            mentions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_chat_log() {
+        let dir = std::env::temp_dir().join(format!(
+            "iroh-node-chat-log-{}",
+            crate::chat::new_message_id()
+        ));
+        let store =
+            SecretStore::in_memory(&rig_agent::KeySource::Passphrase("pw".to_string())).unwrap();
+        let mut log = ChatLog::new(ChatLogConfig::new(&dir), Some(store.clone())).unwrap();
+        let code = MessageContent::Code {
+            language: Some("rust".to_string()),
+            code: "let secret = 1;".to_string(),
+        };
+        let mut rich = entry("m3", "");
+        rich.text = code.plain_text();
+        rich.content = Some(code.clone());
+        log.save(
+            "room",
+            &[entry("m1", "私密内容"), entry("m2", "第二条"), rich],
+        )
+        .unwrap();
+
+        // 文本与结构化内容都不以明文落盘，元数据仍是明文
+        let raw = fs::read_to_string(dir.join("room.json")).unwrap();
+        assert!(!raw.contains("私密内容"));
+        assert!(!raw.contains("secret"));
+        assert!(raw.contains("m1"));
+
+        // 同一密钥存储透明解密
+        let mut reopened = ChatLog::new(ChatLogConfig::new(&dir), Some(store)).unwrap();
+        let entries = reopened.load("room").unwrap();
+        assert_eq!(entries[0].text, "私密内容");
+        assert!(!entries[0].locked);
+        assert_eq!(entries[2].content.as_ref(), Some(&code));
+        assert_eq!(entries[2].text, code.plain_text());
+        let backup = reopened.export_key("room", "backup pw").unwrap();
+
+        // 没有密钥时消息锁定，保存时保留原密文，导入密钥后可以解密
+        let mut other = ChatLog::new(ChatLogConfig::new(&dir), None).unwrap();
+        assert!(!other.has_key("room"));
+        let entries = other.load("room").unwrap();
+        assert!(entries
+            .iter()
+            .all(|entry| entry.locked && entry.text.is_empty()));
+        assert!(matches!(
+            other.import_key("room", &backup, "wrong"),
+            Err(NodeError::VerifyError(_))
+        ));
+        assert!(matches!(
+            other.import_key("other-room", &backup, "backup pw"),
+            Err(NodeError::VerifyError(_))
+        ));
+        other.import_key("room", &backup, "backup pw").unwrap();
+        let entries = other.load("room").unwrap();
+        assert_eq!(entries[1].text, "第二条");
+        assert!(!entries[1].locked);
+
+        // 旧版（IRK1）导出的密钥仍可导入
+        let legacy =
+            crate::bundle::seal_legacy(&other.keys["room"], LEGACY_KEY_MAGIC, "old pw", b"room");
+        let legacy = data_encoding::BASE64URL_NOPAD.encode(&legacy);
+        let mut restored = ChatLog::new(ChatLogConfig::new(&dir), None).unwrap();
+        assert!(matches!(
+            restored.import_key("other-room", &legacy, "old pw"),
+            Err(NodeError::VerifyError(_))
+        ));
+        restored.import_key("room", &legacy, "old pw").unwrap();
+        assert_eq!(restored.load("room").unwrap()[1].text, "第二条");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
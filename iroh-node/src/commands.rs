@@ -0,0 +1,442 @@
+//! 聊天命令
+//!
+//! 以 `/` 开头的聊天输入按命令处理，内置 `/me`、`/roll`、`/agent`、`/share`、`/rename` 与 `/help`，
+//! 也可以注册自定义命令。命令只把输入解析为动作，由节点统一执行，命令行与图形界面因此行为一致；
+//! 未知命令返回错误而不会作为普通文本发出。以 `//` 开头的输入作为普通文本发送（去掉一个 `/`）
+
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{NodeError, NodeResult};
+
+/// 命令前缀
+pub const COMMAND_PREFIX: char = '/';
+/// `/agent` 未指定Agent时使用的Agent ID
+pub const DEFAULT_COMMAND_AGENT: &str = "default";
+/// `/roll` 一次最多掷的骰子数
+const MAX_DICE: u32 = 100;
+/// `/roll` 骰子的最大面数
+const MAX_SIDES: u32 = 1000;
+
+/// 执行命令时的上下文
+#[derive(Debug, Clone)]
+pub struct CommandContext {
+    /// 输入者的显示名称
+    pub sender: String,
+}
+
+/// 命令解析出的动作
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandAction {
+    /// 作为聊天消息发送
+    Chat {
+        /// 消息文本
+        text: String,
+    },
+    /// 向话题发送Agent请求
+    AgentRequest {
+        /// Agent ID
+        agent_id: String,
+        /// 提示词
+        prompt: String,
+    },
+    /// 分享本地文件
+    Share {
+        /// 文件路径
+        path: PathBuf,
+    },
+    /// 修改房间名称
+    Rename {
+        /// 新名称
+        label: String,
+    },
+    /// 只显示给输入者的提示，不发送
+    Reply {
+        /// 提示文本
+        text: String,
+    },
+}
+
+/// 命令的执行结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandOutcome {
+    /// 已发送聊天消息
+    Sent {
+        /// 消息ID
+        message_id: String,
+    },
+    /// 已发送Agent请求
+    AgentRequested {
+        /// 请求ID
+        request_id: String,
+    },
+    /// 需要分享的文件，节点本身不传输文件，由前端完成分享
+    Share {
+        /// 文件路径
+        path: PathBuf,
+    },
+    /// 已修改房间名称
+    Renamed {
+        /// 新名称
+        label: String,
+    },
+    /// 只显示给输入者的提示
+    Reply {
+        /// 提示文本
+        text: String,
+    },
+}
+
+/// 聊天命令
+pub trait ChatCommand: Send + Sync {
+    /// 命令名称，不含 `/`
+    fn name(&self) -> &str;
+
+    /// 用法说明，显示在 `/help` 中
+    fn usage(&self) -> &str;
+
+    /// 把参数解析为动作
+    fn parse(&self, args: &str, context: &CommandContext) -> NodeResult<CommandAction>;
+}
+
+/// 参数错误
+fn usage_error(usage: &str) -> NodeError {
+    NodeError::InvalidMessage(format!("用法: {}", usage))
+}
+
+/// `/me <动作>`：以第三人称发送动作
+struct MeCommand;
+
+impl ChatCommand for MeCommand {
+    fn name(&self) -> &str {
+        "me"
+    }
+
+    fn usage(&self) -> &str {
+        "/me <动作>"
+    }
+
+    fn parse(&self, args: &str, context: &CommandContext) -> NodeResult<CommandAction> {
+        if args.is_empty() {
+            return Err(usage_error(self.usage()));
+        }
+        Ok(CommandAction::Chat {
+            text: format!("* {} {}", context.sender, args),
+        })
+    }
+}
+
+/// `/roll [NdM]`：掷骰子并把结果发到房间，默认 1d6
+struct RollCommand;
+
+impl RollCommand {
+    /// 解析 `NdM`、`dM` 或 `M`
+    fn dice(&self, args: &str) -> NodeResult<(u32, u32)> {
+        let spec = if args.is_empty() { "1d6" } else { args };
+        let (count, sides) = match spec.split_once(['d', 'D']) {
+            Some(("", sides)) => ("1", sides),
+            Some(parts) => parts,
+            None => ("1", spec),
+        };
+        match (count.parse::<u32>(), sides.parse::<u32>()) {
+            (Ok(count), Ok(sides))
+                if (1..=MAX_DICE).contains(&count) && (1..=MAX_SIDES).contains(&sides) =>
+            {
+                Ok((count, sides))
+            }
+            _ => Err(NodeError::InvalidMessage(format!(
+                "无效的骰子: {}，最多 {} 个骰子、{} 面，用法: {}",
+                spec,
+                MAX_DICE,
+                MAX_SIDES,
+                self.usage()
+            ))),
+        }
+    }
+}
+
+impl ChatCommand for RollCommand {
+    fn name(&self) -> &str {
+        "roll"
+    }
+
+    fn usage(&self) -> &str {
+        "/roll [NdM]"
+    }
+
+    fn parse(&self, args: &str, context: &CommandContext) -> NodeResult<CommandAction> {
+        let (count, sides) = self.dice(args)?;
+        let rolls: Vec<u32> = (0..count).map(|_| rand::random_range(1..=sides)).collect();
+        let total: u32 = rolls.iter().sum();
+        let text = if count == 1 {
+            format!("* {} 掷骰 d{}: {}", context.sender, sides, total)
+        } else {
+            format!(
+                "* {} 掷骰 {}d{}: {:?} = {}",
+                context.sender, count, sides, rolls, total
+            )
+        };
+        Ok(CommandAction::Chat { text })
+    }
+}
+
+/// `/agent [@agent_id] <提示词>`：向房间发送Agent请求
+struct AgentCommand;
+
+impl ChatCommand for AgentCommand {
+    fn name(&self) -> &str {
+        "agent"
+    }
+
+    fn usage(&self) -> &str {
+        "/agent [@agent_id] <提示词>"
+    }
+
+    fn parse(&self, args: &str, _context: &CommandContext) -> NodeResult<CommandAction> {
+        let (agent_id, prompt) = match args.strip_prefix('@') {
+            Some(rest) => {
+                let (agent_id, prompt) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                (agent_id, prompt.trim())
+            }
+            None => (DEFAULT_COMMAND_AGENT, args),
+        };
+        if agent_id.is_empty() || prompt.is_empty() {
+            return Err(usage_error(self.usage()));
+        }
+        Ok(CommandAction::AgentRequest {
+            agent_id: agent_id.to_string(),
+            prompt: prompt.to_string(),
+        })
+    }
+}
+
+/// `/share <文件>`：分享本地文件
+struct ShareCommand;
+
+impl ChatCommand for ShareCommand {
+    fn name(&self) -> &str {
+        "share"
+    }
+
+    fn usage(&self) -> &str {
+        "/share <文件路径>"
+    }
+
+    fn parse(&self, args: &str, _context: &CommandContext) -> NodeResult<CommandAction> {
+        if args.is_empty() {
+            return Err(usage_error(self.usage()));
+        }
+        Ok(CommandAction::Share {
+            path: PathBuf::from(args),
+        })
+    }
+}
+
+/// `/rename <名称>`：修改房间名称
+struct RenameCommand;
+
+impl ChatCommand for RenameCommand {
+    fn name(&self) -> &str {
+        "rename"
+    }
+
+    fn usage(&self) -> &str {
+        "/rename <房间名称>"
+    }
+
+    fn parse(&self, args: &str, _context: &CommandContext) -> NodeResult<CommandAction> {
+        if args.is_empty() {
+            return Err(usage_error(self.usage()));
+        }
+        Ok(CommandAction::Rename {
+            label: args.to_string(),
+        })
+    }
+}
+
+/// 命令注册表，默认包含内置命令
+#[derive(Clone)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Arc<dyn ChatCommand>>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::empty()
+            .with_command(MeCommand)
+            .with_command(RollCommand)
+            .with_command(AgentCommand)
+            .with_command(ShareCommand)
+            .with_command(RenameCommand)
+    }
+}
+
+impl std::fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandRegistry")
+            .field("commands", &self.commands.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl CommandRegistry {
+    /// 创建不含任何命令的注册表
+    pub fn empty() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    /// 注册命令，同名命令会被替换
+    pub fn register(&mut self, command: impl ChatCommand + 'static) {
+        self.commands
+            .insert(command.name().to_lowercase(), Arc::new(command));
+    }
+
+    /// 注册命令
+    pub fn with_command(mut self, command: impl ChatCommand + 'static) -> Self {
+        self.register(command);
+        self
+    }
+
+    /// 已注册的命令名称
+    pub fn names(&self) -> Vec<String> {
+        self.commands.keys().cloned().collect()
+    }
+
+    /// 所有命令的用法说明
+    pub fn help(&self) -> String {
+        let mut lines: Vec<&str> = self
+            .commands
+            .values()
+            .map(|command| command.usage())
+            .collect();
+        lines.push("/help");
+        format!("可用命令:\n{}", lines.join("\n"))
+    }
+
+    /// 输入为命令时返回命令名称（小写）与参数
+    pub fn split_command(input: &str) -> Option<(String, &str)> {
+        let body = input.trim().strip_prefix(COMMAND_PREFIX)?;
+        if body.starts_with(COMMAND_PREFIX) {
+            return None;
+        }
+        let (name, args) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+        Some((name.to_lowercase(), args.trim()))
+    }
+
+    /// 解析聊天输入，不以 `/` 开头的输入作为普通聊天消息
+    pub fn parse(&self, input: &str, context: &CommandContext) -> NodeResult<CommandAction> {
+        let Some((name, args)) = Self::split_command(input) else {
+            let input = input.trim();
+            // `//` 开头的输入去掉一个 `/` 后作为普通文本
+            let text = input.strip_prefix(COMMAND_PREFIX).unwrap_or(input);
+            return Ok(CommandAction::Chat {
+                text: text.to_string(),
+            });
+        };
+        match self.commands.get(&name) {
+            Some(command) => command.parse(args, context),
+            None if name == "help" => Ok(CommandAction::Reply { text: self.help() }),
+            None => Err(NodeError::InvalidMessage(format!(
+                "未知命令: /{}，输入 /help 查看可用命令（以 // 开头可发送普通文本）",
+                name
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 自定义命令：`/shrug`
+    struct ShrugCommand;
+
+    impl ChatCommand for ShrugCommand {
+        fn name(&self) -> &str {
+            "shrug"
+        }
+
+        fn usage(&self) -> &str {
+            "/shrug"
+        }
+
+        fn parse(&self, _args: &str, _context: &CommandContext) -> NodeResult<CommandAction> {
+            Ok(CommandAction::Chat {
+                text: "¯\\_(ツ)_/¯".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_command_parsing() {
+        let registry = CommandRegistry::default();
+        let context = CommandContext {
+            sender: "alice".to_string(),
+        };
+        let chat = |text: &str| CommandAction::Chat {
+            text: text.to_string(),
+        };
+
+        assert_eq!(registry.parse("你好", &context).unwrap(), chat("你好"));
+        assert_eq!(
+            registry.parse("//tmp 目录", &context).unwrap(),
+            chat("/tmp 目录")
+        );
+        assert_eq!(
+            registry.parse("/me 挥手", &context).unwrap(),
+            chat("* alice 挥手")
+        );
+        assert_eq!(
+            registry.parse("/agent @writer 写一首诗", &context).unwrap(),
+            CommandAction::AgentRequest {
+                agent_id: "writer".to_string(),
+                prompt: "写一首诗".to_string(),
+            }
+        );
+        assert_eq!(
+            registry.parse("/AGENT 总结一下", &context).unwrap(),
+            CommandAction::AgentRequest {
+                agent_id: DEFAULT_COMMAND_AGENT.to_string(),
+                prompt: "总结一下".to_string(),
+            }
+        );
+        assert_eq!(
+            registry.parse("/rename 周会", &context).unwrap(),
+            CommandAction::Rename {
+                label: "周会".to_string()
+            }
+        );
+        assert!(registry.parse("/share", &context).is_err());
+        assert_eq!(
+            CommandRegistry::split_command(" /Share a.txt"),
+            Some(("share".to_string(), "a.txt"))
+        );
+        assert!(CommandRegistry::split_command("//share").is_none());
+        assert!(registry.parse("/agent @writer", &context).is_err());
+
+        match registry.parse("/roll 3d6", &context).unwrap() {
+            CommandAction::Chat { text } => assert!(text.starts_with("* alice 掷骰 3d6: ")),
+            other => panic!("unexpected action: {:?}", other),
+        }
+        assert!(registry.parse("/roll 0d6", &context).is_err());
+        assert!(registry.parse("/roll abc", &context).is_err());
+
+        let error = registry.parse("/dance", &context).unwrap_err().to_string();
+        assert!(error.contains("/dance") && error.contains("/help"));
+        assert!(matches!(
+            registry.parse("/help", &context).unwrap(),
+            CommandAction::Reply { text } if text.contains("/roll [NdM]")
+        ));
+
+        let registry = registry.with_command(ShrugCommand);
+        assert_eq!(
+            registry.parse("/shrug", &context).unwrap(),
+            chat("¯\\_(ツ)_/¯")
+        );
+    }
+}
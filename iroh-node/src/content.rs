@@ -0,0 +1,143 @@
+//! 结构化消息内容
+//!
+//! 聊天消息除纯文本外还可以是 Markdown、带语言标注的代码块或系统提示，
+//! 客户端按类型渲染，不必从文本中猜测格式。
+//!
+//! 内容来自网络，渲染时须按不可信输入处理：
+//! - Markdown 只渲染标准语法，禁用内嵌HTML，链接只允许 http/https/mailto
+//! - 代码块按纯文本显示，语言标注只用于选择高亮规则
+//! - 系统提示与普通消息区分显示，避免被用来冒充节点自身的提示
+//!
+//! 不支持结构化内容的旧版本节点收到的是 [`MessageContent::plain_text`] 给出的纯文本
+
+use serde::{Deserialize, Serialize};
+
+/// 代码块的最大字节数
+pub const MAX_CODE_BYTES: usize = 64 * 1024;
+
+/// 代码语言标注的最大字节数
+pub const MAX_LANGUAGE_BYTES: usize = 32;
+
+/// 消息内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageContent {
+    /// 纯文本
+    Text {
+        /// 文本
+        text: String,
+    },
+    /// Markdown 文本
+    Markdown {
+        /// Markdown 源文本
+        text: String,
+    },
+    /// 代码块
+    Code {
+        /// 语言标注，如 `rust`
+        language: Option<String>,
+        /// 代码
+        code: String,
+    },
+    /// 系统提示
+    Notice {
+        /// 提示文本
+        text: String,
+    },
+}
+
+impl MessageContent {
+    /// 纯文本内容
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    /// 是否为纯文本，纯文本按普通聊天消息发送
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text { .. })
+    }
+
+    /// 纯文本表示，用于聊天记录搜索、房间机器人与旧版本节点
+    pub fn plain_text(&self) -> String {
+        match self {
+            Self::Text { text } | Self::Markdown { text } | Self::Notice { text } => text.clone(),
+            Self::Code { language, code } => format!(
+                "```{}\n{}\n```",
+                language.as_deref().unwrap_or_default(),
+                code.trim_end_matches('\n')
+            ),
+        }
+    }
+
+    /// 规范化内容：统一换行符为 `\n`，去除换行与制表符以外的控制字符，语言标注转为小写
+    pub fn sanitize(self) -> Self {
+        match self {
+            Self::Text { text } => Self::Text {
+                text: sanitize_text(&text),
+            },
+            Self::Markdown { text } => Self::Markdown {
+                text: sanitize_text(&text),
+            },
+            Self::Code { language, code } => Self::Code {
+                language: language
+                    .map(|language| language.trim().to_ascii_lowercase())
+                    .filter(|language| !language.is_empty()),
+                code: sanitize_text(&code),
+            },
+            Self::Notice { text } => Self::Notice {
+                text: sanitize_text(&text),
+            },
+        }
+    }
+}
+
+/// 语言标注只允许ASCII字母、数字与 `+`、`-`、`#`、`.`、`_`
+pub(crate) fn is_valid_language(language: &str) -> bool {
+    !language.is_empty()
+        && language.len() <= MAX_LANGUAGE_BYTES
+        && language
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'#' | b'.' | b'_'))
+}
+
+fn sanitize_text(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_content() {
+        let code = MessageContent::Code {
+            language: Some(" Rust ".to_string()),
+            code: "fn main() {}\r\n\u{7}".to_string(),
+        }
+        .sanitize();
+        assert_eq!(
+            code,
+            MessageContent::Code {
+                language: Some("rust".to_string()),
+                code: "fn main() {}\n".to_string(),
+            }
+        );
+        assert_eq!(code.plain_text(), "```rust\nfn main() {}\n```");
+        assert!(!code.is_text());
+
+        let markdown = MessageContent::Markdown {
+            text: "**粗体**\r\n".to_string(),
+        }
+        .sanitize();
+        assert_eq!(markdown.plain_text(), "**粗体**\n");
+        assert!(MessageContent::text("hi").is_text());
+
+        assert!(is_valid_language("c++"));
+        assert!(is_valid_language("c#"));
+        assert!(!is_valid_language(""));
+        assert!(!is_valid_language("rust<script>"));
+    }
+}
@@ -0,0 +1,583 @@
+//! Agent请求协调
+//!
+//! 话题中多个节点运行Agent时，同一条 `AgentRequest` 会被每个节点各自处理，浪费调用额度。
+//! 各节点按请求摘要对候选节点做一致性排序（rendezvous hashing），排名第一的节点立即认领并处理；
+//! 其余节点按排名依次等待，若超时仍没有节点认领才接手，以应对各节点视图不一致或首选节点离线。
+//! 显式请求多节点回答时使用 `AgentFanOutRequest`，不参与选举。
+//!
+//! 节点在开始与结束处理Agent请求时通告自己的负载（正在处理的请求数与平均耗时），
+//! 排序时先比较负载，负载相同再比较摘要，请求因此优先交给空闲且响应快的节点。
+//! 各节点看到的负载可能略有先后，排名冲突时由认领消息去重。
+//!
+//! `AgentQuery` 携带请求ID并指定寻址方式，响应以 `AgentReply` 关联回请求，
+//! 请求方据此收集应答、处理超时并汇总多数意见。
+//! 处理期间服务节点以 `AgentStatus` 通告请求已接受、排队位置与已处理时长，
+//! 模型较慢时请求方也能知道请求仍在处理
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use iroh::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::error::{NodeError, NodeResult};
+
+/// 每下降一个排名多等待的时间
+const CLAIM_BACKOFF: Duration = Duration::from_secs(3);
+
+/// 认领记录的保留时间
+const CLAIM_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 未指定时等待应答的时间
+pub const DEFAULT_AGENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 处理期间通告处理进度的间隔
+pub(crate) const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 负载通告的有效期，过期后按未知负载对待
+const LOAD_TTL: Duration = Duration::from_secs(2 * 60);
+
+/// 平均耗时的分档宽度（毫秒），同一档内的节点视为一样快，避免耗时抖动改变排序
+const LATENCY_BUCKET_MS: u32 = 500;
+
+/// 平均耗时的平滑系数，越大越偏重最近的请求
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// 节点通告的Agent负载
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentLoad {
+    /// 正在处理的Agent请求数
+    pub queue_depth: u32,
+    /// 最近Agent请求的平均耗时（毫秒），尚未处理过请求时为空
+    pub latency_ms: Option<u32>,
+}
+
+impl AgentLoad {
+    /// 排序代价：先比较正在处理的请求数，再比较耗时分档。
+    /// 未知耗时按最快对待，使新加入的节点也能分到请求
+    fn cost(&self) -> (u32, u32) {
+        (
+            self.queue_depth,
+            self.latency_ms.map_or(0, |ms| ms / LATENCY_BUCKET_MS),
+        )
+    }
+}
+
+/// 本节点的Agent负载
+#[derive(Debug, Default)]
+pub(crate) struct LoadTracker {
+    in_flight: u32,
+    latency_ms: Option<f64>,
+}
+
+impl LoadTracker {
+    /// 开始处理一个请求
+    pub(crate) fn start(&mut self) -> AgentLoad {
+        self.in_flight += 1;
+        self.snapshot()
+    }
+
+    /// 一个请求处理完成
+    pub(crate) fn finish(&mut self, elapsed: Duration) -> AgentLoad {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        let sample = elapsed.as_secs_f64() * 1000.0;
+        self.latency_ms = Some(match self.latency_ms {
+            Some(average) => average + LATENCY_SMOOTHING * (sample - average),
+            None => sample,
+        });
+        self.snapshot()
+    }
+
+    /// 当前负载
+    pub(crate) fn snapshot(&self) -> AgentLoad {
+        AgentLoad {
+            queue_depth: self.in_flight,
+            latency_ms: self.latency_ms.map(|ms| ms.round() as u32),
+        }
+    }
+}
+
+/// 服务节点通告的Agent请求处理状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentRequestStatus {
+    /// 已接受请求
+    Accepted,
+    /// 排在本节点正在处理的其他请求之后
+    QueuePosition {
+        /// 之前的请求数
+        position: u32,
+    },
+    /// 处理中
+    Processing {
+        /// 自接受请求起经过的时间（毫秒）
+        elapsed_ms: u64,
+    },
+}
+
+impl AgentRequestStatus {
+    /// 开始处理时的排队位置，`load` 为计入本请求后的负载；前面没有其他请求时为空
+    pub(crate) fn queued(load: AgentLoad) -> Option<Self> {
+        let position = load.queue_depth.saturating_sub(1);
+        (position > 0).then_some(Self::QueuePosition { position })
+    }
+
+    /// 处理中，`elapsed` 为自接受请求起经过的时间
+    pub(crate) fn processing(elapsed: Duration) -> Self {
+        Self::Processing {
+            elapsed_ms: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+/// 其他节点通告的Agent负载
+#[derive(Debug, Default)]
+pub(crate) struct PeerLoads {
+    loads: HashMap<PublicKey, (AgentLoad, Instant)>,
+}
+
+impl PeerLoads {
+    /// 记录节点通告的负载
+    pub(crate) fn update(&mut self, peer: PublicKey, load: AgentLoad) {
+        self.loads.insert(peer, (load, Instant::now()));
+    }
+
+    /// 节点的负载，未通告或已过期时为空
+    pub(crate) fn get(&self, peer: &PublicKey) -> Option<AgentLoad> {
+        self.loads
+            .get(peer)
+            .filter(|(_, received_at)| received_at.elapsed() < LOAD_TTL)
+            .map(|(load, _)| *load)
+    }
+}
+
+/// Agent请求的寻址方式
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentRequestMode {
+    /// 由选举出的任一节点回答，收到第一个应答即完成
+    #[default]
+    Broadcast,
+    /// 只由指定节点回答
+    Targeted {
+        /// 目标节点ID
+        node_id: String,
+    },
+    /// 所有运行Agent的节点都回答，收集到指定数量的应答后汇总
+    Quorum {
+        /// 需要的应答数量
+        count: usize,
+    },
+}
+
+impl AgentRequestMode {
+    /// 检查参数
+    pub(crate) fn validate(&self) -> NodeResult<()> {
+        match self {
+            Self::Broadcast => Ok(()),
+            Self::Targeted { node_id } => node_id
+                .parse::<PublicKey>()
+                .map(|_| ())
+                .map_err(|e| NodeError::AgentError(format!("解析目标节点ID失败: {}", e))),
+            Self::Quorum { count: 0 } => Err(NodeError::AgentError("应答数量至少为1".to_string())),
+            Self::Quorum { .. } => Ok(()),
+        }
+    }
+
+    /// 消息中的响应者范围
+    pub(crate) fn target(&self) -> AgentTarget {
+        match self {
+            Self::Broadcast => AgentTarget::Elected,
+            Self::Targeted { node_id } => AgentTarget::Node(node_id.clone()),
+            Self::Quorum { .. } => AgentTarget::All,
+        }
+    }
+
+    /// 收集到的应答是否已满足请求
+    pub(crate) fn is_satisfied(&self, answers: &[AgentAnswer]) -> bool {
+        match self {
+            // 被选中的节点拒绝时会由下一个节点接手，只有成功的应答才算完成
+            Self::Broadcast => answers.iter().any(AgentAnswer::is_success),
+            // 目标节点的错误也是最终结果
+            Self::Targeted { node_id } => answers.iter().any(|answer| &answer.from == node_id),
+            Self::Quorum { count } => {
+                answers.iter().filter(|answer| answer.is_success()).count() >= *count
+            }
+        }
+    }
+}
+
+/// `AgentQuery` 中指定的响应者范围
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentTarget {
+    /// 按请求ID选举一个响应者
+    Elected,
+    /// 指定节点
+    Node(String),
+    /// 所有运行Agent的节点
+    All,
+}
+
+/// 单个节点的应答
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentAnswer {
+    /// 应答节点ID
+    pub from: String,
+    /// Agent ID
+    pub agent_id: String,
+    /// 响应内容，处理失败时为空
+    pub content: Option<String>,
+    /// 错误信息
+    pub error: Option<String>,
+    /// 收到应答的时间
+    pub received_at: DateTime<Utc>,
+}
+
+impl AgentAnswer {
+    /// 是否为成功的应答
+    pub fn is_success(&self) -> bool {
+        self.content.is_some()
+    }
+}
+
+/// Agent请求的最终结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRequestOutcome {
+    /// 请求ID
+    pub request_id: String,
+    /// 话题ID
+    pub topic_id: String,
+    /// 寻址方式
+    pub mode: AgentRequestMode,
+    /// 按到达顺序排列的应答
+    pub answers: Vec<AgentAnswer>,
+    /// 超时前是否未满足请求
+    pub timed_out: bool,
+    /// 出现次数最多的响应内容，次数相同时取最先到达的
+    pub consensus: Option<String>,
+    /// 与 `consensus` 相同的应答数量
+    pub agreement: usize,
+}
+
+impl AgentRequestOutcome {
+    /// 汇总应答
+    pub(crate) fn new(
+        request_id: String,
+        topic_id: String,
+        mode: AgentRequestMode,
+        answers: Vec<AgentAnswer>,
+        timed_out: bool,
+    ) -> Self {
+        let (consensus, agreement) = aggregate(&answers);
+        Self {
+            request_id,
+            topic_id,
+            mode,
+            answers,
+            timed_out,
+            consensus,
+            agreement,
+        }
+    }
+}
+
+/// 按去掉首尾空白后的内容统计多数意见
+fn aggregate(answers: &[AgentAnswer]) -> (Option<String>, usize) {
+    let mut best: Option<(&str, usize)> = None;
+    for content in answers
+        .iter()
+        .filter_map(|answer| answer.content.as_deref())
+    {
+        let count = answers
+            .iter()
+            .filter_map(|answer| answer.content.as_deref())
+            .filter(|other| other.trim() == content.trim())
+            .count();
+        if best.is_none_or(|(_, best_count)| count > best_count) {
+            best = Some((content, count));
+        }
+    }
+    best.map_or((None, 0), |(content, count)| {
+        (Some(content.to_string()), count)
+    })
+}
+
+/// 由请求方与请求内容派生的请求ID，各节点计算结果一致
+pub(crate) fn agent_request_id(from: &PublicKey, agent_id: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(from.as_bytes());
+    hasher.update((agent_id.len() as u64).to_le_bytes());
+    hasher.update(agent_id.as_bytes());
+    hasher.update(prompt.as_bytes());
+    data_encoding::HEXLOWER.encode(&hasher.finalize()[..16])
+}
+
+/// 本节点在候选节点中的排名，0 表示首选响应者。候选集合不含请求方。
+/// 负载低的节点优先，负载相同时按摘要排序；`load_of` 返回节点通告的负载，未知负载按空闲对待
+pub(crate) fn responder_rank<F>(
+    me: &PublicKey,
+    requester: &PublicKey,
+    candidates: impl IntoIterator<Item = PublicKey>,
+    request_id: &str,
+    load_of: F,
+) -> usize
+where
+    F: Fn(&PublicKey) -> Option<AgentLoad>,
+{
+    let key = |node: &PublicKey| {
+        (
+            load_of(node).unwrap_or_default().cost(),
+            Reverse(score(node, request_id)),
+        )
+    };
+    let my_key = key(me);
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate != me && candidate != requester)
+        .filter(|candidate| key(candidate) < my_key)
+        .count()
+}
+
+fn score(node: &PublicKey, request_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(node.as_bytes());
+    hasher.update(request_id.as_bytes());
+    hasher.finalize().into()
+}
+
+/// 已被认领的请求
+#[derive(Debug, Default)]
+pub(crate) struct ClaimTracker {
+    claims: HashMap<String, Instant>,
+}
+
+impl ClaimTracker {
+    /// 记录认领，返回该请求之前是否未被认领
+    pub(crate) fn claim(&mut self, request_id: &str) -> bool {
+        self.prune();
+        self.claims
+            .insert(request_id.to_string(), Instant::now())
+            .is_none()
+    }
+
+    /// 请求是否已被认领
+    #[cfg(test)]
+    pub(crate) fn is_claimed(&self, request_id: &str) -> bool {
+        self.claims.contains_key(request_id)
+    }
+
+    fn prune(&mut self) {
+        self.claims
+            .retain(|_, claimed_at| claimed_at.elapsed() < CLAIM_TTL);
+    }
+}
+
+/// 按排名等待后认领请求，返回是否由本节点处理。等待期间收到其他节点的认领时放弃
+pub(crate) async fn claim_after_backoff(
+    claims: &RwLock<ClaimTracker>,
+    request_id: &str,
+    rank: usize,
+) -> bool {
+    if rank > 0 {
+        tokio::time::sleep(CLAIM_BACKOFF * rank as u32).await;
+    }
+    claims.write().await.claim(request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::SecretKey;
+
+    use super::*;
+
+    #[test]
+    fn test_exactly_one_first_responder() {
+        let requester = SecretKey::from_bytes(&[0; 32]).public();
+        let nodes: Vec<_> = (1..=5)
+            .map(|i| SecretKey::from_bytes(&[i; 32]).public())
+            .collect();
+        let mut candidates = nodes.clone();
+        candidates.push(requester);
+
+        for prompt in ["你好", "总结一下", "翻译这段话"] {
+            let request_id = agent_request_id(&requester, "default", prompt);
+            let mut ranks: Vec<_> = nodes
+                .iter()
+                .map(|node| {
+                    responder_rank(node, &requester, candidates.clone(), &request_id, |_| None)
+                })
+                .collect();
+            ranks.sort();
+            assert_eq!(ranks, vec![0, 1, 2, 3, 4]);
+        }
+
+        // 空闲的节点排在处理中的节点之前
+        let request_id = agent_request_id(&requester, "default", "你好");
+        let busy = AgentLoad {
+            queue_depth: 2,
+            latency_ms: Some(800),
+        };
+        let load_of = |node: &PublicKey| (*node != nodes[4]).then_some(busy);
+        assert_eq!(
+            responder_rank(
+                &nodes[4],
+                &requester,
+                candidates.clone(),
+                &request_id,
+                load_of
+            ),
+            0
+        );
+        let mut ranks: Vec<_> = nodes
+            .iter()
+            .map(|node| responder_rank(node, &requester, candidates.clone(), &request_id, load_of))
+            .collect();
+        ranks.sort();
+        assert_eq!(ranks, vec![0, 1, 2, 3, 4]);
+
+        let mut tracker = LoadTracker::default();
+        let load = tracker.start();
+        assert_eq!(load.queue_depth, 1);
+        assert_eq!(AgentRequestStatus::queued(load), None);
+        assert_eq!(
+            AgentRequestStatus::queued(tracker.start()),
+            Some(AgentRequestStatus::QueuePosition { position: 1 })
+        );
+        tracker.finish(Duration::from_millis(1200));
+        assert_eq!(
+            AgentRequestStatus::processing(Duration::from_millis(5250)),
+            AgentRequestStatus::Processing { elapsed_ms: 5250 }
+        );
+        let load = tracker.finish(Duration::from_millis(1200));
+        assert_eq!(load.queue_depth, 0);
+        assert_eq!(load.latency_ms, Some(1200));
+        assert_eq!(
+            tracker.finish(Duration::from_millis(200)).latency_ms,
+            Some(1000)
+        );
+
+        let mut claims = ClaimTracker::default();
+        assert!(claims.claim("abc"));
+        assert!(!claims.claim("abc"));
+        assert!(claims.is_claimed("abc"));
+    }
+
+    #[test]
+    fn test_responder_rank_orders_by_load_then_node() {
+        let requester = SecretKey::from_bytes(&[0; 32]).public();
+        let nodes: Vec<_> = (1..=4)
+            .map(|i| SecretKey::from_bytes(&[i; 32]).public())
+            .collect();
+        let request_id = agent_request_id(&requester, "default", "你好");
+        let ranks = |candidates: &[PublicKey],
+                     load_of: &dyn Fn(&PublicKey) -> Option<AgentLoad>| {
+            nodes
+                .iter()
+                .map(|node| {
+                    responder_rank(node, &requester, candidates.to_vec(), &request_id, load_of)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // 负载相同时按节点与请求ID的摘要排序，与候选节点的顺序无关
+        let mut expected: Vec<_> = (0..nodes.len()).collect();
+        expected.sort_by_key(|&i| Reverse(score(&nodes[i], &request_id)));
+        let mut by_score = vec![0; nodes.len()];
+        for (rank, &i) in expected.iter().enumerate() {
+            by_score[i] = rank;
+        }
+        let idle = |_: &PublicKey| None;
+        assert_eq!(ranks(&nodes, &idle), by_score);
+        let mut reversed = nodes.clone();
+        reversed.reverse();
+        reversed.push(requester);
+        assert_eq!(ranks(&reversed, &idle), by_score);
+
+        // 同一耗时分档内仍按摘要排序
+        let similar = |node: &PublicKey| {
+            Some(AgentLoad {
+                queue_depth: 1,
+                latency_ms: Some(if *node == nodes[0] { 100 } else { 400 }),
+            })
+        };
+        assert_eq!(ranks(&nodes, &similar), by_score);
+
+        // 负载不同时先比较正在处理的请求数，再比较耗时
+        let loaded = |node: &PublicKey| {
+            let i = nodes.iter().position(|n| n == node).unwrap() as u32;
+            Some(AgentLoad {
+                queue_depth: 3 - i.min(3),
+                latency_ms: Some(if i == 3 { 2000 } else { 0 }),
+            })
+        };
+        assert_eq!(ranks(&nodes, &loaded), vec![3, 2, 1, 0]);
+        let slow = |node: &PublicKey| {
+            Some(AgentLoad {
+                queue_depth: 0,
+                latency_ms: (*node == nodes[expected[0]]).then_some(5000),
+            })
+        };
+        assert_eq!(ranks(&nodes, &slow)[expected[0]], nodes.len() - 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_received_claim_stops_lower_ranked_node() {
+        let claims = RwLock::new(ClaimTracker::default());
+
+        // 首选节点立即认领
+        assert!(claim_after_backoff(&claims, "first", 0).await);
+        assert!(!claim_after_backoff(&claims, "first", 1).await);
+
+        // 排名靠后的节点等待期间收到认领消息，不再处理
+        let waiting = claim_after_backoff(&claims, "second", 1);
+        let received = async {
+            tokio::time::sleep(CLAIM_BACKOFF / 2).await;
+            claims.write().await.claim("second");
+        };
+        let (answered, ()) = tokio::join!(waiting, received);
+        assert!(!answered);
+
+        // 等待超时仍无认领时由排名靠后的节点接手
+        let started = tokio::time::Instant::now();
+        assert!(claim_after_backoff(&claims, "third", 2).await);
+        assert_eq!(started.elapsed(), CLAIM_BACKOFF * 2);
+    }
+
+    #[test]
+    fn test_quorum_outcome() {
+        let answer = |from: &str, content: Option<&str>| AgentAnswer {
+            from: from.to_string(),
+            agent_id: "default".to_string(),
+            content: content.map(str::to_string),
+            error: content.is_none().then(|| "失败".to_string()),
+            received_at: Utc::now(),
+        };
+        let answers = vec![
+            answer("a", Some("42")),
+            answer("b", None),
+            answer("c", Some("41")),
+            answer("d", Some("42 ")),
+        ];
+
+        let mode = AgentRequestMode::Quorum { count: 3 };
+        assert!(mode.is_satisfied(&answers));
+        assert!(!mode.is_satisfied(&answers[..3]));
+        assert!(!AgentRequestMode::Broadcast.is_satisfied(&answers[1..2]));
+        let targeted = AgentRequestMode::Targeted {
+            node_id: "b".to_string(),
+        };
+        assert!(targeted.is_satisfied(&answers[1..2]));
+        assert!(AgentRequestMode::Quorum { count: 0 }.validate().is_err());
+
+        let outcome =
+            AgentRequestOutcome::new("req".to_string(), "topic".to_string(), mode, answers, false);
+        assert_eq!(outcome.consensus.as_deref(), Some("42"));
+        assert_eq!(outcome.agreement, 2);
+    }
+}
@@ -174,8 +174,15 @@ impl IrohClient {
                             notifier.notify(event);
                         }
                         ExportProgress::Done { id: _ } => {
-                            let event = TransferEvent::DownloadDone {
-                                id: file_id.clone(),
+                            // 通过安全扫描后才报告完成，未通过的文件已移入隔离目录
+                            let event = match self.scan_download(&dest).await {
+                                Ok(()) => TransferEvent::DownloadDone {
+                                    id: file_id.clone(),
+                                },
+                                Err(error) => TransferEvent::TransferError {
+                                    id: file_id.clone(),
+                                    error,
+                                },
                             };
                             notifier.notify(event);
                             break;
@@ -207,6 +214,24 @@ impl IrohClient {
         Ok(format!("文件已下载到: {}", download_folder.display()))
     }
 
+    /// 设置了安全扫描时扫描下载的文件，未通过时返回原因
+    async fn scan_download(&self, path: &Path) -> Result<(), String> {
+        let Some(scanner) = &self.config.file_scanner else {
+            return Ok(());
+        };
+        match scanner.scan(path).await {
+            Ok(result) if result.safe => Ok(()),
+            Ok(result) => {
+                error!("下载的文件未通过安全扫描: {}", path.display());
+                Err(format!(
+                    "文件未通过安全扫描，已隔离: {}",
+                    result.reason().unwrap_or_default()
+                ))
+            }
+            Err(e) => Err(format!("扫描文件失败: {}", e)),
+        }
+    }
+
     /// 获取分享代码
     pub async fn get_share_code(&self) -> TransferResult<ShareResponse> {
         let doc_ticket = self
@@ -5,7 +5,9 @@ use iroh::{
     docs::{AuthorId, DocTicket},
 };
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
+
+use crate::scan::FileScanHook;
 
 /// 文件传输配置
 #[derive(Debug, Clone)]
@@ -16,6 +18,8 @@ pub struct TransferConfig {
     pub download_dir: Option<PathBuf>,
     /// 是否启用详细日志
     pub verbose_logging: bool,
+    /// 接收文件的安全扫描，设置后下载的文件通过扫描才报告完成，未通过的移入隔离目录
+    pub file_scanner: Option<Arc<FileScanHook>>,
 }
 
 impl Default for TransferConfig {
@@ -24,6 +28,7 @@ impl Default for TransferConfig {
             data_root: std::env::temp_dir().join("iroh_data"),
             download_dir: dirs_next::download_dir().map(|d| d.join("quick_send")),
             verbose_logging: false,
+            file_scanner: None,
         }
     }
 }
@@ -0,0 +1,242 @@
+//! 崩溃报告
+//!
+//! 安装panic钩子，在崩溃时把包含调用栈、版本和最近日志的结构化报告写入数据目录。
+//! 应用下次启动时读取未处理的报告并提示用户，用户同意后可上传到指定地址
+
+use std::{
+    backtrace::Backtrace,
+    fs, io,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{NodeError, NodeResult},
+    logs::{LogBuffer, LogEntry, LogQuery},
+};
+
+/// 崩溃报告所在的子目录
+pub const CRASH_DIR: &str = "crashes";
+
+/// 报告中附带的最近日志条数
+const LOG_TAIL: usize = 200;
+
+/// 崩溃报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// 报告ID
+    pub id: String,
+    /// 崩溃时间
+    pub created_at: DateTime<Utc>,
+    /// 应用版本
+    pub app_version: String,
+    /// iroh-node版本
+    pub node_version: String,
+    /// 操作系统
+    pub os: String,
+    /// CPU架构
+    pub arch: String,
+    /// 发生panic的线程
+    pub thread: Option<String>,
+    /// panic信息
+    pub message: String,
+    /// panic位置（文件:行:列）
+    pub location: Option<String>,
+    /// 调用栈
+    pub backtrace: String,
+    /// 崩溃前的最近日志
+    pub recent_logs: Vec<LogEntry>,
+}
+
+impl CrashReport {
+    /// 根据panic信息生成报告
+    fn from_panic(info: &PanicHookInfo<'_>, app_version: &str) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "未知panic".to_string());
+        let created_at = Utc::now();
+        let recent_logs = LogBuffer::global()
+            .query(&LogQuery {
+                limit: Some(LOG_TAIL),
+                ..Default::default()
+            })
+            .unwrap_or_default();
+
+        Self {
+            id: format!(
+                "{}-{:08x}",
+                created_at.format("%Y%m%d-%H%M%S"),
+                rand::random::<u32>()
+            ),
+            created_at,
+            app_version: app_version.to_string(),
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            thread: std::thread::current().name().map(str::to_string),
+            message,
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Backtrace::force_capture().to_string(),
+            recent_logs,
+        }
+    }
+}
+
+/// 崩溃报告管理
+#[derive(Debug, Clone)]
+pub struct CrashReporter {
+    dir: PathBuf,
+}
+
+impl CrashReporter {
+    /// 在数据目录下的 [`CRASH_DIR`] 中保存报告
+    pub fn new(data_dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: data_dir.as_ref().join(CRASH_DIR),
+        }
+    }
+
+    /// 报告目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 安装panic钩子，写入报告后继续调用原有的钩子
+    pub fn install_panic_hook(&self, app_version: impl Into<String>) {
+        let reporter = self.clone();
+        let app_version = app_version.into();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let report = CrashReport::from_panic(info, &app_version);
+            match reporter.save(&report) {
+                Ok(path) => eprintln!("崩溃报告已保存到 {}", path.display()),
+                Err(e) => eprintln!("保存崩溃报告失败: {}", e),
+            }
+            previous(info);
+        }));
+    }
+
+    /// 保存报告
+    pub fn save(&self, report: &CrashReport) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&report.id);
+        let data = serde_json::to_vec_pretty(report).map_err(io::Error::other)?;
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    /// 尚未处理的报告，按时间从新到旧排列；无法解析的文件会被跳过
+    pub fn pending(&self) -> NodeResult<Vec<CrashReport>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(NodeError::IoError(format!("读取崩溃报告目录失败: {}", e))),
+        };
+
+        let mut reports: Vec<CrashReport> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read(entry.path()).ok())
+            .filter_map(|data| serde_json::from_slice(&data).ok())
+            .collect();
+        reports.sort_by_key(|report| std::cmp::Reverse(report.created_at));
+        Ok(reports)
+    }
+
+    /// 读取指定报告
+    pub fn get(&self, id: &str) -> NodeResult<CrashReport> {
+        let data = fs::read(self.checked_path(id)?)
+            .map_err(|e| NodeError::IoError(format!("读取崩溃报告 {} 失败: {}", id, e)))?;
+        serde_json::from_slice(&data)
+            .map_err(|e| NodeError::DecodeError(format!("解析崩溃报告 {} 失败: {}", id, e)))
+    }
+
+    /// 删除已处理（已上传或用户忽略）的报告
+    pub fn dismiss(&self, id: &str) -> NodeResult<()> {
+        match fs::remove_file(self.checked_path(id)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(NodeError::IoError(format!(
+                "删除崩溃报告 {} 失败: {}",
+                id, e
+            ))),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// 校验报告ID，防止通过ID访问报告目录之外的文件
+    fn checked_path(&self, id: &str) -> NodeResult<PathBuf> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(NodeError::InvalidMessage(format!(
+                "无效的崩溃报告ID: {}",
+                id
+            )));
+        }
+        Ok(self.path(id))
+    }
+}
+
+/// 将报告以JSON形式POST到指定地址
+#[cfg(feature = "crash-upload")]
+pub async fn upload_crash_report(url: &str, report: &CrashReport) -> NodeResult<()> {
+    let response = reqwest::Client::new()
+        .post(url)
+        .json(report)
+        .send()
+        .await
+        .map_err(|e| NodeError::IoError(format!("上传崩溃报告失败: {}", e)))?;
+    if !response.status().is_success() {
+        return Err(NodeError::IoError(format!(
+            "上传崩溃报告失败: HTTP {}",
+            response.status()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_round_trip() {
+        let data_dir =
+            std::env::temp_dir().join(format!("iroh-node-crash-{}", rand::random::<u64>()));
+        let reporter = CrashReporter::new(&data_dir);
+        assert!(reporter.pending().unwrap().is_empty());
+
+        let report = CrashReport {
+            id: "20260101-000000-0000abcd".to_string(),
+            created_at: Utc::now(),
+            app_version: "0.1.0".to_string(),
+            node_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            thread: Some("main".to_string()),
+            message: "boom".to_string(),
+            location: Some("src/main.rs:1:1".to_string()),
+            backtrace: String::new(),
+            recent_logs: Vec::new(),
+        };
+        reporter.save(&report).unwrap();
+
+        let pending = reporter.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(reporter.get(&report.id).unwrap().message, "boom");
+        assert!(reporter.get("../secret").is_err());
+
+        reporter.dismiss(&report.id).unwrap();
+        assert!(reporter.pending().unwrap().is_empty());
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
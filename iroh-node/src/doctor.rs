@@ -0,0 +1,355 @@
+//! 节点自检
+//!
+//! 启动节点前检查配置与运行环境：配置是否自洽、节点密钥能否解析、绑定端口是否可用、
+//! 中继服务器能否连通、数据目录能否写入。每项检查给出结论与处理建议，检查过程不启动节点
+
+use std::{
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iroh::{defaults::prod::default_relay_map, RelayUrl, SecretKey};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+
+use crate::config::{NodeConfig, NODE_SECRET_KEY_NAME};
+
+/// 连接中继服务器的超时时间
+const RELAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 检查结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingLevel {
+    /// 正常
+    Ok,
+    /// 可以启动，但可能不是预期的行为
+    Warning,
+    /// 节点无法启动或无法正常工作
+    Error,
+}
+
+/// 单项检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorFinding {
+    /// 检查项
+    pub check: String,
+    /// 结论
+    pub level: FindingLevel,
+    /// 说明
+    pub message: String,
+    /// 处理建议
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl DoctorFinding {
+    fn new(check: &str, level: FindingLevel, message: impl Into<String>) -> Self {
+        Self {
+            check: check.to_string(),
+            level,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// 自检报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DoctorReport {
+    /// 按检查顺序排列的结果
+    pub findings: Vec<DoctorFinding>,
+}
+
+impl DoctorReport {
+    /// 结论为错误的检查数
+    pub fn errors(&self) -> usize {
+        self.count(FindingLevel::Error)
+    }
+
+    /// 结论为警告的检查数
+    pub fn warnings(&self) -> usize {
+        self.count(FindingLevel::Warning)
+    }
+
+    /// 是否没有错误
+    pub fn is_healthy(&self) -> bool {
+        self.errors() == 0
+    }
+
+    fn count(&self, level: FindingLevel) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.level == level)
+            .count()
+    }
+}
+
+/// 检查节点配置与运行环境
+pub async fn run_doctor(config: &NodeConfig) -> DoctorReport {
+    let mut findings = Vec::new();
+
+    let problems = config.problems();
+    if problems.is_empty() {
+        findings.push(DoctorFinding::new(
+            "配置",
+            FindingLevel::Ok,
+            "配置项相互一致",
+        ));
+    }
+    findings.extend(problems.into_iter().map(|problem| {
+        DoctorFinding::new("配置", FindingLevel::Error, problem)
+            .with_hint("按说明修改配置文件或命令行参数")
+    }));
+
+    findings.extend(check_secret_key(config));
+    findings.push(check_bind_port(config));
+    findings.extend(check_relays(config).await);
+    findings.extend(check_data_dirs(config));
+
+    DoctorReport { findings }
+}
+
+/// 节点密钥；配置中的密钥无法解析时已在配置检查中报告
+fn check_secret_key(config: &NodeConfig) -> Option<DoctorFinding> {
+    const CHECK: &str = "节点密钥";
+    let finding = match (&config.secret_key, &config.secret_store) {
+        (Some(key), _) => {
+            let key = key.parse::<SecretKey>().ok()?;
+            DoctorFinding::new(CHECK, FindingLevel::Ok, format!("节点ID: {}", key.public()))
+        }
+        (None, Some(store)) => match store.get(NODE_SECRET_KEY_NAME) {
+            Ok(Some(key)) => match key.parse::<SecretKey>() {
+                Ok(key) => DoctorFinding::new(
+                    CHECK,
+                    FindingLevel::Ok,
+                    format!("密钥存储中的节点ID: {}", key.public()),
+                ),
+                Err(e) => DoctorFinding::new(
+                    CHECK,
+                    FindingLevel::Error,
+                    format!("密钥存储中的节点密钥无效: {}", e),
+                )
+                .with_hint(format!(
+                    "删除密钥存储中的 {} 后会生成新的密钥，节点ID随之改变",
+                    NODE_SECRET_KEY_NAME
+                )),
+            },
+            Ok(None) => DoctorFinding::new(
+                CHECK,
+                FindingLevel::Ok,
+                "密钥存储中还没有节点密钥，首次启动时生成并保存",
+            ),
+            Err(e) => DoctorFinding::new(
+                CHECK,
+                FindingLevel::Error,
+                format!("读取密钥存储失败: {}", e),
+            )
+            .with_hint("确认口令（IROH_NODE_PASSPHRASE）正确且密钥存储文件未损坏"),
+        },
+        (None, None) => DoctorFinding::new(
+            CHECK,
+            FindingLevel::Warning,
+            "未设置节点密钥，每次启动都会生成新的节点ID",
+        )
+        .with_hint("设置 secret_key，或使用 --secret-store 保存密钥"),
+    };
+    Some(finding)
+}
+
+/// 绑定端口是否可用
+fn check_bind_port(config: &NodeConfig) -> DoctorFinding {
+    const CHECK: &str = "绑定端口";
+    if config.bind_port == 0 {
+        return DoctorFinding::new(CHECK, FindingLevel::Ok, "使用随机端口");
+    }
+    // 与节点相同：隐私模式只监听本地回环地址
+    let ip = if config.privacy_mode {
+        Ipv4Addr::LOCALHOST
+    } else {
+        Ipv4Addr::UNSPECIFIED
+    };
+    match UdpSocket::bind(SocketAddrV4::new(ip, config.bind_port)) {
+        Ok(_) => DoctorFinding::new(
+            CHECK,
+            FindingLevel::Ok,
+            format!("UDP 端口 {} 可用", config.bind_port),
+        ),
+        Err(e) => DoctorFinding::new(
+            CHECK,
+            FindingLevel::Error,
+            format!("无法绑定 UDP 端口 {}: {}", config.bind_port, e),
+        )
+        .with_hint("端口可能已被其他进程（或另一个节点）占用，换用其他端口或设为 0 使用随机端口"),
+    }
+}
+
+/// 中继服务器能否建立 TCP 连接
+async fn check_relays(config: &NodeConfig) -> Vec<DoctorFinding> {
+    const CHECK: &str = "中继服务器";
+    if config.no_relay {
+        return vec![DoctorFinding::new(
+            CHECK,
+            FindingLevel::Ok,
+            "已禁用中继，只能与可直连的节点通信",
+        )];
+    }
+    if let Some(proxy) = &config.proxy {
+        return vec![DoctorFinding::new(
+            CHECK,
+            FindingLevel::Warning,
+            format!("中继连接经由代理 {}，未直接检测连通性", proxy),
+        )
+        .with_hint("确认代理已启动且允许连接中继服务器")];
+    }
+
+    let relays: Vec<RelayUrl> = match &config.relay {
+        Some(relay) => vec![relay.clone()],
+        None => default_relay_map().urls().cloned().collect(),
+    };
+    let mut findings = Vec::with_capacity(relays.len());
+    for relay in relays {
+        let finding = match connect_relay(&relay).await {
+            Ok(()) => DoctorFinding::new(CHECK, FindingLevel::Ok, format!("{} 可以连接", relay)),
+            Err(e) => DoctorFinding::new(
+                CHECK,
+                FindingLevel::Error,
+                format!("无法连接 {}: {}", relay, e),
+            )
+            .with_hint("检查网络与防火墙设置，或确认中继URL正确；受限网络中可配置代理"),
+        };
+        findings.push(finding);
+    }
+    findings
+}
+
+async fn connect_relay(relay: &RelayUrl) -> Result<(), String> {
+    let host = relay.host_str().ok_or("中继URL没有主机名")?;
+    let port = relay.port_or_known_default().ok_or("中继URL没有端口")?;
+    match tokio::time::timeout(RELAY_TIMEOUT, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("{} 秒内未响应", RELAY_TIMEOUT.as_secs())),
+    }
+}
+
+/// 配置中的数据目录能否写入
+fn check_data_dirs(config: &NodeConfig) -> Vec<DoctorFinding> {
+    const CHECK: &str = "数据目录";
+    let mut dirs: Vec<(&str, PathBuf)> = Vec::new();
+    if let Some(dir) = config.address_book_path.as_deref().and_then(Path::parent) {
+        dirs.push(("地址簿", dir.to_path_buf()));
+    }
+    if let Some(dir) = config
+        .outbox
+        .as_ref()
+        .and_then(|outbox| outbox.path.as_deref())
+        .and_then(Path::parent)
+    {
+        dirs.push(("发件箱", dir.to_path_buf()));
+    }
+    if let Some(chat_log) = &config.chat_log {
+        dirs.push(("聊天记录", chat_log.dir.clone()));
+    }
+    if dirs.is_empty() {
+        return vec![DoctorFinding::new(
+            CHECK,
+            FindingLevel::Ok,
+            "未配置持久化存储，数据只保存在内存中",
+        )];
+    }
+
+    dirs.into_iter()
+        .map(|(label, dir)| match check_writable(&dir) {
+            Ok(message) => DoctorFinding::new(
+                CHECK,
+                FindingLevel::Ok,
+                format!("{}目录 {} {}", label, dir.display(), message),
+            ),
+            Err(e) => DoctorFinding::new(
+                CHECK,
+                FindingLevel::Error,
+                format!("{}目录 {} 无法写入: {}", label, dir.display(), e),
+            )
+            .with_hint("检查目录权限与所有者，或改用当前用户可写的目录"),
+        })
+        .collect()
+}
+
+/// 写入并删除探测文件；目录不存在时检查最近的上级目录，节点启动时会创建该目录
+fn check_writable(dir: &Path) -> Result<&'static str, String> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let (existing, message) = if dir.exists() {
+        (dir, "可写")
+    } else {
+        let ancestor = dir
+            .ancestors()
+            .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+            .unwrap_or(Path::new("."));
+        (ancestor, "尚不存在，启动时创建")
+    };
+    if !existing.is_dir() {
+        return Err(format!("{} 不是目录", existing.display()));
+    }
+    let probe = existing.join(format!(".iroh-node-doctor-{}", rand::random::<u64>()));
+    std::fs::write(&probe, b"").map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_log::ChatLogConfig;
+
+    #[tokio::test]
+    async fn test_doctor_findings() {
+        let config = NodeConfig::new()
+            .with_secret_key(Some("不是密钥".to_string()))
+            .with_no_relay(true)
+            .with_privacy_mode(true)
+            .with_proxy(Some("ftp://127.0.0.1:21".to_string()));
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.contains("密钥无法解析"));
+        assert!(error.contains("隐私模式"));
+        assert!(error.contains("不支持的代理协议: ftp"));
+        assert!(error.contains("禁用中继时代理不起作用"));
+        assert!(NodeConfig::new().validate().is_ok());
+
+        // 被占用的端口与可写的数据目录
+        let taken = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let dir = std::env::temp_dir()
+            .join(format!("iroh-node-doctor-{}", rand::random::<u64>()))
+            .join("chat");
+        let config = NodeConfig::new()
+            .with_no_relay(true)
+            .with_bind_port(taken.local_addr().unwrap().port())
+            .with_chat_log(Some(ChatLogConfig::new(&dir)));
+        let report = run_doctor(&config).await;
+        let level_of = |check: &str| {
+            report
+                .findings
+                .iter()
+                .find(|finding| finding.check == check)
+                .map(|finding| finding.level)
+        };
+        assert_eq!(level_of("配置"), Some(FindingLevel::Ok));
+        assert_eq!(level_of("节点密钥"), Some(FindingLevel::Warning));
+        assert_eq!(level_of("绑定端口"), Some(FindingLevel::Error));
+        assert_eq!(level_of("中继服务器"), Some(FindingLevel::Ok));
+        assert_eq!(level_of("数据目录"), Some(FindingLevel::Ok));
+        assert!(!dir.exists());
+        assert_eq!((report.errors(), report.warnings()), (1, 1));
+        assert!(!report.is_healthy());
+    }
+}
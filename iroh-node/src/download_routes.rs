@@ -0,0 +1,211 @@
+//! 接收文件的分类规则
+//!
+//! 按扩展名或内容类型把接收到的文件放入下载目录下的子目录（`Images/`、`Docs/`、`Code/`），
+//! 未匹配的文件放在下载目录下。同名文件已存在时改名为 `名称 (n).扩展名`，不覆盖已有文件
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// 文件的分类规则
+///
+/// 模式为扩展名（`png`、`*.png`）或内容类型（`image/png`、`image/*`），
+/// 内容类型由扩展名推断
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteRule {
+    /// 下载目录下的子目录
+    pub subdir: PathBuf,
+    /// 匹配的模式
+    pub patterns: Vec<String>,
+}
+
+impl RouteRule {
+    /// 创建规则
+    pub fn new(subdir: impl Into<PathBuf>, patterns: &[&str]) -> Self {
+        Self {
+            subdir: subdir.into(),
+            patterns: patterns.iter().map(|p| p.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    /// 文件名是否匹配
+    pub fn matches(&self, name: &str) -> bool {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let mime = mime_from_extension(&extension);
+        self.patterns.iter().any(|pattern| {
+            if let Some((kind, subtype)) = pattern.split_once('/') {
+                let Some((mime_kind, mime_subtype)) = mime.and_then(|m| m.split_once('/')) else {
+                    return false;
+                };
+                kind == mime_kind && (subtype == "*" || subtype == mime_subtype)
+            } else {
+                !extension.is_empty() && pattern.trim_start_matches("*.") == extension
+            }
+        })
+    }
+}
+
+/// 一组分类规则，按顺序匹配
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DownloadRoutes {
+    /// 规则
+    pub rules: Vec<RouteRule>,
+}
+
+impl DownloadRoutes {
+    /// 默认规则：图片、文档、代码
+    pub fn defaults() -> Self {
+        Self {
+            rules: vec![
+                RouteRule::new("Images", &["image/*"]),
+                RouteRule::new(
+                    "Docs",
+                    &[
+                        "application/pdf",
+                        "text/plain",
+                        "text/markdown",
+                        "doc",
+                        "docx",
+                        "xls",
+                        "xlsx",
+                        "ppt",
+                        "pptx",
+                        "odt",
+                        "csv",
+                    ],
+                ),
+                RouteRule::new(
+                    "Code",
+                    &[
+                        "rs", "ts", "tsx", "js", "jsx", "py", "go", "java", "c", "h", "cpp", "hpp",
+                        "sh", "toml", "json", "yaml", "yml",
+                    ],
+                ),
+            ],
+        }
+    }
+
+    /// 添加规则
+    pub fn with_rule(mut self, rule: RouteRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// 文件所属的子目录，未匹配任何规则时为空
+    pub fn subdir(&self, name: &str) -> Option<&Path> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(name))
+            .map(|rule| rule.subdir.as_path())
+    }
+
+    /// 文件在 `dir` 下应放入的目录
+    pub fn dir_for(&self, dir: &Path, name: &str) -> PathBuf {
+        match self.subdir(name) {
+            Some(subdir) => dir.join(subdir),
+            None => dir.to_path_buf(),
+        }
+    }
+
+    /// 计算文件放入 `dir` 后的路径：按规则选择子目录，同名文件已存在时依次编号
+    pub fn resolve(&self, dir: &Path, name: &str) -> PathBuf {
+        unique_path(&self.dir_for(dir, name), name)
+    }
+}
+
+/// 由扩展名推断内容类型
+fn mime_from_extension(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "heic" => "image/heic",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => return None,
+    })
+}
+
+/// 目录中不与已有文件冲突的路径
+pub(crate) fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let name = Path::new(name);
+    let stem = name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .expect("无限序列中总有未使用的文件名")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_download_routing() {
+        let routes = DownloadRoutes::defaults().with_rule(RouteRule::new("Music", &["audio/*"]));
+        let dir = std::env::temp_dir().join(format!(
+            "iroh-node-routes-{}",
+            crate::chat::new_message_id()
+        ));
+
+        assert_eq!(
+            routes.resolve(&dir, "photo.JPG"),
+            dir.join("Images").join("photo.JPG")
+        );
+        assert_eq!(
+            routes.resolve(&dir, "notes.md"),
+            dir.join("Docs").join("notes.md")
+        );
+        assert_eq!(
+            routes.resolve(&dir, "main.rs"),
+            dir.join("Code").join("main.rs")
+        );
+        assert_eq!(
+            routes.resolve(&dir, "song.mp3"),
+            dir.join("Music").join("song.mp3")
+        );
+        assert_eq!(routes.resolve(&dir, "archive"), dir.join("archive"));
+        assert_eq!(
+            DownloadRoutes::default().resolve(&dir, "photo.png"),
+            dir.join("photo.png")
+        );
+
+        // 重名时依次编号
+        std::fs::create_dir_all(dir.join("Code")).unwrap();
+        std::fs::write(dir.join("Code").join("main.rs"), "").unwrap();
+        std::fs::write(dir.join("Code").join("main (1).rs"), "").unwrap();
+        assert_eq!(
+            routes.resolve(&dir, "main.rs"),
+            dir.join("Code").join("main (2).rs")
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,169 @@
+//! 多节点集成回答
+//!
+//! 将同一个提示词发给多个节点的Agent，收集各自的回答后交给本地Agent综合，
+//! 返回综合后的答案、对各回答的排名以及原始回答
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coordination::{AgentAnswer, AgentRequestMode},
+    error::{NodeError, NodeResult},
+};
+
+/// 未指定节点时默认收集的回答数量
+const DEFAULT_ENSEMBLE_SIZE: usize = 3;
+
+/// 综合结果中排名行的前缀
+const RANKING_PREFIX: &str = "排名:";
+
+/// 集成回答选项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnsembleOptions {
+    /// 指定回答的节点ID；为空时由话题中任意运行Agent的节点回答
+    pub peers: Vec<String>,
+    /// 未指定节点时收集的回答数量
+    pub count: usize,
+    /// 负责综合的本地Agent ID，默认与请求的Agent相同
+    pub synthesizer: Option<String>,
+    /// 等待回答的毫秒数
+    pub timeout_ms: Option<u64>,
+}
+
+impl Default for EnsembleOptions {
+    fn default() -> Self {
+        Self {
+            peers: Vec::new(),
+            count: DEFAULT_ENSEMBLE_SIZE,
+            synthesizer: None,
+            timeout_ms: None,
+        }
+    }
+}
+
+impl EnsembleOptions {
+    /// 各节点请求的寻址方式
+    pub(crate) fn modes(&self) -> NodeResult<Vec<AgentRequestMode>> {
+        if self.peers.is_empty() {
+            if self.count == 0 {
+                return Err(NodeError::AgentError("回答数量至少为1".to_string()));
+            }
+            return Ok(vec![AgentRequestMode::Quorum { count: self.count }]);
+        }
+
+        let mut peers = self.peers.clone();
+        peers.sort();
+        peers.dedup();
+        Ok(peers
+            .into_iter()
+            .map(|node_id| AgentRequestMode::Targeted { node_id })
+            .collect())
+    }
+
+    /// 等待回答的时间
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+}
+
+/// 集成回答结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnsembleOutcome {
+    /// 发出的请求ID
+    pub request_ids: Vec<String>,
+    /// 本地Agent综合后的答案，没有可用回答或综合失败时为空
+    pub synthesis: Option<String>,
+    /// 综合失败时的错误信息
+    pub synthesis_error: Option<String>,
+    /// 按本地Agent评价从好到差排列的回答节点ID
+    pub ranking: Vec<String>,
+    /// 各节点的原始回答
+    pub answers: Vec<AgentAnswer>,
+    /// 是否有请求在超时前未收齐回答
+    pub timed_out: bool,
+}
+
+/// 交给本地Agent的综合提示词
+pub(crate) fn synthesis_prompt(prompt: &str, answers: &[&AgentAnswer]) -> String {
+    let mut text = format!(
+        "以下是多个助手对同一个问题的回答。请比较它们的准确性与完整性，给出一个综合后的最终答案。\n\
+         第一行按从好到差的顺序列出回答编号，格式为“{} 2 > 1 > 3”，从第二行开始写最终答案。\n\n\
+         问题：\n{}\n",
+        RANKING_PREFIX, prompt
+    );
+    for (index, answer) in answers.iter().enumerate() {
+        text.push_str(&format!(
+            "\n回答 {}：\n{}\n",
+            index + 1,
+            answer.content.as_deref().unwrap_or_default()
+        ));
+    }
+    text
+}
+
+/// 解析本地Agent的输出，返回排名（回答在 `answers` 中的下标）与最终答案。
+/// 第一行不是排名时整段输出都视为答案
+pub(crate) fn parse_synthesis(output: &str, answer_count: usize) -> (Vec<usize>, String) {
+    let output = output.trim();
+    let (first, rest) = output.split_once('\n').unwrap_or((output, ""));
+    let Some(ranking) = first
+        .trim()
+        .strip_prefix(RANKING_PREFIX)
+        .or_else(|| first.trim().strip_prefix("排名："))
+    else {
+        return (Vec::new(), output.to_string());
+    };
+
+    let mut order = Vec::new();
+    for index in ranking
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse::<usize>().ok())
+    {
+        if (1..=answer_count).contains(&index) && !order.contains(&(index - 1)) {
+            order.push(index - 1);
+        }
+    }
+    (order, rest.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn test_synthesis_round_trip() {
+        let answer = |from: &str, content: &str| AgentAnswer {
+            from: from.to_string(),
+            agent_id: "default".to_string(),
+            content: Some(content.to_string()),
+            error: None,
+            received_at: Utc::now(),
+        };
+        let answers = [answer("a", "巴黎"), answer("b", "里昂")];
+        let prompt = synthesis_prompt("法国的首都是哪里？", &answers.iter().collect::<Vec<_>>());
+        assert!(prompt.contains("回答 2：\n里昂"));
+
+        let (ranking, synthesis) = parse_synthesis("排名: 1 > 2 > 7 > 1\n法国的首都是巴黎。", 2);
+        assert_eq!(ranking, vec![0, 1]);
+        assert_eq!(synthesis, "法国的首都是巴黎。");
+
+        let (ranking, synthesis) = parse_synthesis("法国的首都是巴黎。", 2);
+        assert!(ranking.is_empty());
+        assert_eq!(synthesis, "法国的首都是巴黎。");
+
+        let options = EnsembleOptions {
+            peers: vec!["b".to_string(), "a".to_string(), "b".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(options.modes().unwrap().len(), 2);
+        let options = EnsembleOptions {
+            count: 0,
+            ..Default::default()
+        };
+        assert!(options.modes().is_err());
+    }
+}
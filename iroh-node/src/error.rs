@@ -22,8 +22,12 @@ pub enum NodeError {
     DecodeError(String),
     /// 验证错误
     VerifyError(String),
+    /// 无效消息（超过大小限制或格式不符）
+    InvalidMessage(String),
     /// IO错误
     IoError(String),
+    /// 请求的数据量超过上限
+    PayloadTooLarge(String),
 }
 
 impl fmt::Display for NodeError {
@@ -36,7 +40,9 @@ impl fmt::Display for NodeError {
             Self::EncodeError(msg) => write!(f, "编码错误: {}", msg),
             Self::DecodeError(msg) => write!(f, "解码错误: {}", msg),
             Self::VerifyError(msg) => write!(f, "验证错误: {}", msg),
+            Self::InvalidMessage(msg) => write!(f, "无效消息: {}", msg),
             Self::IoError(msg) => write!(f, "IO错误: {}", msg),
+            Self::PayloadTooLarge(msg) => write!(f, "数据量超过上限: {}", msg),
         }
     }
 }
@@ -49,8 +55,8 @@ impl From<std::io::Error> for NodeError {
     }
 }
 
-impl From<rig_agent::Error> for NodeError {
-    fn from(err: rig_agent::Error) -> Self {
+impl From<rig_agent::AgentError> for NodeError {
+    fn from(err: rig_agent::AgentError) -> Self {
         Self::AgentError(err.to_string())
     }
 }
\ No newline at end of file
@@ -0,0 +1,559 @@
+//! 节点事件
+//!
+//! 提供节点事件总线，用于向UI推送节点状态变化，避免轮询。
+//! 事件的JSON带版本号 `"v"`，格式约定与 [`rig_agent::core::wire`] 相同
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    address_book::TrustLevel,
+    chat::{ChatHistoryEntry, DeliveryStatus, Reactions},
+    coordination::{AgentRequestOutcome, AgentRequestStatus},
+    folder_sync::FileSyncStatus,
+    links::LinkPreview,
+    mentions::MentionRecord,
+    protocol::PeerInfo,
+    read_state::RoomUnread,
+};
+use tokio::sync::broadcast;
+
+/// 事件总线容量
+const EVENT_BUS_CAPACITY: usize = 256;
+
+/// 节点事件当前的线上格式版本
+pub const NODE_EVENT_VERSION: &str = "1";
+
+/// 节点事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", rename_all = "snake_case")]
+pub enum NodeEvent {
+    /// 节点已启动
+    Started {
+        /// 节点ID
+        node_id: String,
+    },
+    /// 节点已停止
+    Stopped {
+        /// 节点ID
+        node_id: String,
+    },
+    /// 连接状态变化（是否可达中继或任一对等节点）
+    ConnectivityChanged {
+        /// 是否在线
+        online: bool,
+    },
+    /// 连接的对等节点数量变化
+    PeerCountChanged {
+        /// 变化前数量
+        previous: usize,
+        /// 当前数量
+        current: usize,
+    },
+    /// 话题中的邻居节点上线
+    NeighborUp {
+        /// 话题ID
+        topic_id: String,
+        /// 对等节点ID
+        peer_id: String,
+    },
+    /// 话题中的邻居节点下线
+    NeighborDown {
+        /// 话题ID
+        topic_id: String,
+        /// 对等节点ID
+        peer_id: String,
+    },
+    /// 已加入话题
+    TopicJoined {
+        /// 话题ID
+        topic_id: String,
+    },
+    /// 已离开话题
+    TopicLeft {
+        /// 话题ID
+        topic_id: String,
+    },
+    /// 主中继服务器切换
+    RelayChanged {
+        /// 切换前的中继URL
+        previous: Option<String>,
+        /// 当前中继URL
+        current: Option<String>,
+    },
+    /// 收到聊天消息
+    ChatReceived {
+        /// 话题ID
+        topic_id: String,
+        /// 聊天记录条目
+        entry: ChatHistoryEntry,
+        /// 发送者在地址簿中的信任级别
+        trust: TrustLevel,
+    },
+    /// 本节点发出聊天消息（包括代网页客户端发出的）
+    ChatSent {
+        /// 话题ID
+        topic_id: String,
+        /// 聊天记录条目
+        entry: ChatHistoryEntry,
+    },
+    /// 出站聊天消息的投递状态变化
+    DeliveryStatusChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 消息ID
+        message_id: String,
+        /// 当前投递状态
+        status: DeliveryStatus,
+    },
+    /// 聊天消息的表情回应变化（包括本节点的回应）
+    ReactionChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 消息ID
+        message_id: String,
+        /// 回应的节点ID
+        from: String,
+        /// 表情
+        emoji: String,
+        /// 是否为撤销
+        removed: bool,
+        /// 消息当前的全部回应
+        reactions: Reactions,
+    },
+    /// 聊天消息中链接的预览已生成
+    LinkPreviewReady {
+        /// 话题ID
+        topic_id: String,
+        /// 消息ID
+        message_id: String,
+        /// 链接预览
+        preview: LinkPreview,
+    },
+    /// 房间的已读状态变化
+    ReadStateChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 房间当前的未读状态
+        unread: RoomUnread,
+        /// 同步已读状态的本人设备，本节点标记时为空
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+    },
+    /// 本节点在聊天消息中被提及
+    Mentioned {
+        /// 话题ID
+        topic_id: String,
+        /// 提及记录
+        mention: MentionRecord,
+    },
+    /// 发送者信任级别不足，Agent请求被拒绝
+    AgentRequestRejected {
+        /// 话题ID
+        topic_id: String,
+        /// 请求节点ID
+        from: String,
+        /// Agent ID
+        agent_id: String,
+        /// 请求节点的信任级别
+        trust: TrustLevel,
+    },
+    /// 对等节点确认已核对与本节点之间的安全码
+    VerificationConfirmed {
+        /// 话题ID
+        topic_id: String,
+        /// 对等节点ID
+        from: String,
+        /// 对方的安全码摘要是否与本地一致，不一致说明可能存在中间人
+        matched: bool,
+    },
+    /// 已验证节点的名称出现在新的密钥上
+    VerifiedKeyChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 节点名称
+        name: String,
+        /// 原先验证过的节点ID
+        previous_node_id: String,
+        /// 新的节点ID
+        node_id: String,
+    },
+    /// 收到Agent响应
+    AgentResponseReceived {
+        /// 话题ID
+        topic_id: String,
+        /// 响应节点ID
+        from: String,
+        /// Agent ID
+        agent_id: String,
+        /// 响应内容
+        content: String,
+        /// 对应的请求ID，旧版请求的响应为空
+        #[serde(default)]
+        request_id: Option<String>,
+    },
+    /// 共享记忆中的记录被新增、修改或删除
+    MemoryChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 记录ID
+        id: String,
+        /// 作者节点ID
+        author: String,
+        /// 是否已删除
+        deleted: bool,
+    },
+    /// 发现话题中有节点通告了新的Agent模板
+    TemplatesAnnounced {
+        /// 通告节点ID
+        from: String,
+        /// 新出现的模板数量
+        added: usize,
+    },
+    /// 服务节点通告了本节点发出的Agent请求的处理状态
+    AgentRequestStatus {
+        /// 话题ID
+        topic_id: String,
+        /// 请求ID
+        request_id: String,
+        /// 服务节点ID
+        from: String,
+        /// Agent ID
+        agent_id: String,
+        /// 处理状态
+        status: AgentRequestStatus,
+    },
+    /// 本节点发出的Agent请求已完成或超时
+    AgentRequestCompleted {
+        /// 话题ID
+        topic_id: String,
+        /// 请求结果
+        outcome: AgentRequestOutcome,
+    },
+    /// 收到对等节点的版本与能力信息
+    PeerInfoUpdated {
+        /// 话题ID
+        topic_id: String,
+        /// 协商结果
+        peer: PeerInfo,
+    },
+    /// 对等节点使用不兼容的协议版本，其消息将被忽略
+    PeerIncompatible {
+        /// 话题ID
+        topic_id: String,
+        /// 对等节点ID
+        peer_id: String,
+        /// 对方的协议版本
+        version: u16,
+    },
+    /// 入站消息未通过校验被丢弃
+    MessageRejected {
+        /// 话题ID
+        topic_id: String,
+        /// 发送节点ID，签名校验前被拒绝时为空
+        from: Option<String>,
+        /// 拒绝原因
+        reason: String,
+    },
+    /// 收到远端节点的错误消息
+    RemoteError {
+        /// 话题ID
+        topic_id: String,
+        /// 发送节点ID
+        from: String,
+        /// 错误信息
+        message: String,
+    },
+    /// 消息暂存到发件箱，等待话题连上后补发
+    MessageQueued {
+        /// 话题ID
+        topic_id: String,
+        /// 消息ID
+        message_id: String,
+    },
+    /// 发件箱补发了一个话题中暂存的消息
+    OutboxFlushed {
+        /// 话题ID
+        topic_id: String,
+        /// 补发成功的消息数
+        sent: usize,
+        /// 过期未发出的消息数
+        expired: usize,
+        /// 仍留在发件箱中的消息数
+        remaining: usize,
+        /// 补发中断时的错误
+        error: Option<String>,
+    },
+    /// 同步文件夹中某个文件的同步状态变化
+    FolderSyncChanged {
+        /// 话题ID
+        topic_id: String,
+        /// 同步文件夹名称
+        folder: String,
+        /// 文件同步状态
+        status: FileSyncStatus,
+    },
+}
+
+impl NodeEvent {
+    /// 事件名称，用于转发到前端事件系统
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "started",
+            Self::Stopped { .. } => "stopped",
+            Self::ConnectivityChanged { .. } => "connectivity-changed",
+            Self::PeerCountChanged { .. } => "peer-count-changed",
+            Self::NeighborUp { .. } => "neighbor-up",
+            Self::NeighborDown { .. } => "neighbor-down",
+            Self::TopicJoined { .. } => "topic-joined",
+            Self::TopicLeft { .. } => "topic-left",
+            Self::RelayChanged { .. } => "relay-changed",
+            Self::ChatReceived { .. } => "chat-received",
+            Self::ChatSent { .. } => "chat-sent",
+            Self::DeliveryStatusChanged { .. } => "delivery-status-changed",
+            Self::ReactionChanged { .. } => "reaction-changed",
+            Self::LinkPreviewReady { .. } => "link-preview-ready",
+            Self::ReadStateChanged { .. } => "read-state-changed",
+            Self::Mentioned { .. } => "mentioned",
+            Self::AgentRequestRejected { .. } => "agent-request-rejected",
+            Self::VerificationConfirmed { .. } => "verification-confirmed",
+            Self::VerifiedKeyChanged { .. } => "verified-key-changed",
+            Self::AgentResponseReceived { .. } => "agent-response-received",
+            Self::AgentRequestStatus { .. } => "agent-request-status",
+            Self::AgentRequestCompleted { .. } => "agent-request-completed",
+            Self::MemoryChanged { .. } => "memory-changed",
+            Self::TemplatesAnnounced { .. } => "templates-announced",
+            Self::PeerInfoUpdated { .. } => "peer-info-updated",
+            Self::PeerIncompatible { .. } => "peer-incompatible",
+            Self::MessageRejected { .. } => "message-rejected",
+            Self::RemoteError { .. } => "remote-error",
+            Self::MessageQueued { .. } => "message-queued",
+            Self::OutboxFlushed { .. } => "outbox-flushed",
+            Self::FolderSyncChanged { .. } => "folder-sync-changed",
+        }
+    }
+
+    /// 事件所属的话题ID，与话题无关的事件返回空
+    pub fn topic_id(&self) -> Option<&str> {
+        match self {
+            Self::NeighborUp { topic_id, .. }
+            | Self::NeighborDown { topic_id, .. }
+            | Self::TopicJoined { topic_id }
+            | Self::TopicLeft { topic_id }
+            | Self::ChatReceived { topic_id, .. }
+            | Self::ChatSent { topic_id, .. }
+            | Self::DeliveryStatusChanged { topic_id, .. }
+            | Self::ReactionChanged { topic_id, .. }
+            | Self::LinkPreviewReady { topic_id, .. }
+            | Self::ReadStateChanged { topic_id, .. }
+            | Self::Mentioned { topic_id, .. }
+            | Self::AgentRequestRejected { topic_id, .. }
+            | Self::VerificationConfirmed { topic_id, .. }
+            | Self::VerifiedKeyChanged { topic_id, .. }
+            | Self::AgentResponseReceived { topic_id, .. }
+            | Self::AgentRequestStatus { topic_id, .. }
+            | Self::AgentRequestCompleted { topic_id, .. }
+            | Self::MemoryChanged { topic_id, .. }
+            | Self::PeerInfoUpdated { topic_id, .. }
+            | Self::PeerIncompatible { topic_id, .. }
+            | Self::MessageRejected { topic_id, .. }
+            | Self::RemoteError { topic_id, .. }
+            | Self::MessageQueued { topic_id, .. }
+            | Self::OutboxFlushed { topic_id, .. }
+            | Self::FolderSyncChanged { topic_id, .. } => Some(topic_id),
+            Self::Started { .. }
+            | Self::Stopped { .. }
+            | Self::ConnectivityChanged { .. }
+            | Self::PeerCountChanged { .. }
+            | Self::RelayChanged { .. }
+            | Self::TemplatesAnnounced { .. } => None,
+        }
+    }
+
+    /// 是否为聊天相关事件（收到、发出消息，投递状态、表情回应与已读状态变化，链接预览，被提及）
+    pub fn is_chat(&self) -> bool {
+        matches!(
+            self,
+            Self::ChatReceived { .. }
+                | Self::ChatSent { .. }
+                | Self::DeliveryStatusChanged { .. }
+                | Self::ReactionChanged { .. }
+                | Self::LinkPreviewReady { .. }
+                | Self::ReadStateChanged { .. }
+                | Self::Mentioned { .. }
+        )
+    }
+}
+
+/// 当前版本的节点事件
+struct NodeEventV1<T>(T);
+
+impl Serialize for NodeEventV1<&NodeEvent> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        NodeEvent::serialize(self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeEventV1<NodeEvent> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        NodeEvent::deserialize(deserializer).map(NodeEventV1)
+    }
+}
+
+impl Serialize for NodeEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(tag = "v")]
+        enum Wire<'a> {
+            #[serde(rename = "1")]
+            V1(NodeEventV1<&'a NodeEvent>),
+        }
+
+        Wire::V1(NodeEventV1(self)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(tag = "v")]
+        enum Wire {
+            #[serde(rename = "1")]
+            V1(NodeEventV1<NodeEvent>),
+        }
+
+        // 引入版本号之前的事件没有 `"v"`，按第 1 版解码
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Versioned(Wire),
+            Unversioned(NodeEventV1<NodeEvent>),
+        }
+
+        Ok(match Compat::deserialize(deserializer)? {
+            Compat::Versioned(Wire::V1(NodeEventV1(event)))
+            | Compat::Unversioned(NodeEventV1(event)) => event,
+        })
+    }
+}
+
+/// 节点事件总线
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NodeEvent>,
+}
+
+impl EventBus {
+    /// 创建事件总线
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// 发布事件，没有订阅者时直接丢弃
+    pub fn publish(&self, event: NodeEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// 订阅事件
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_node_event_wire_version() {
+        let event = NodeEvent::PeerCountChanged {
+            previous: 1,
+            current: 2,
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            json!({"v": NODE_EVENT_VERSION, "type": "peer_count_changed", "previous": 1, "current": 2})
+        );
+        let decoded: NodeEvent = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.name(), "peer-count-changed");
+
+        // 没有版本号的旧事件仍可解码
+        let legacy = json!({"type": "topic_joined", "topic_id": "abc"});
+        let decoded: NodeEvent = serde_json::from_value(legacy).unwrap();
+        assert_eq!(decoded.topic_id(), Some("abc"));
+        assert!(!decoded.is_chat());
+
+        let delivery = NodeEvent::DeliveryStatusChanged {
+            topic_id: "abc".to_string(),
+            message_id: "m1".to_string(),
+            status: Default::default(),
+        };
+        assert!(delivery.is_chat());
+        assert_eq!(delivery.topic_id(), Some("abc"));
+
+        let flushed = NodeEvent::OutboxFlushed {
+            topic_id: "abc".to_string(),
+            sent: 2,
+            expired: 1,
+            remaining: 0,
+            error: None,
+        };
+        assert_eq!(flushed.name(), "outbox-flushed");
+        assert_eq!(flushed.topic_id(), Some("abc"));
+        assert!(!flushed.is_chat());
+    }
+    #[test]
+    fn test_agent_response_events_round_trip() {
+        // 事件流的订阅者（例如压测示例）按 JSON 解码收到的Agent响应与远端错误
+        let response = NodeEvent::AgentResponseReceived {
+            topic_id: "abc".to_string(),
+            from: "peer".to_string(),
+            agent_id: "default".to_string(),
+            content: "load-3".to_string(),
+            request_id: None,
+        };
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["type"], "agent_response_received");
+        assert_eq!(value["content"], "load-3");
+        let decoded: NodeEvent = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.name(), "agent-response-received");
+        assert_eq!(decoded.topic_id(), Some("abc"));
+        // 旧版节点发布的事件没有请求ID
+        let legacy = json!({
+            "type": "agent_response_received",
+            "topic_id": "abc",
+            "from": "peer",
+            "agent_id": "default",
+            "content": "load-3",
+        });
+        let decoded: NodeEvent = serde_json::from_value(legacy).unwrap();
+        assert!(matches!(
+            decoded,
+            NodeEvent::AgentResponseReceived {
+                request_id: None,
+                ..
+            }
+        ));
+
+        let error = NodeEvent::RemoteError {
+            topic_id: "abc".to_string(),
+            from: "peer".to_string(),
+            message: "Agent 已存在".to_string(),
+        };
+        let decoded: NodeEvent =
+            serde_json::from_str(&serde_json::to_string(&error).unwrap()).unwrap();
+        assert!(matches!(
+            decoded,
+            NodeEvent::RemoteError { ref message, .. } if message == "Agent 已存在"
+        ));
+        assert_eq!(decoded.name(), "remote-error");
+    }
+}
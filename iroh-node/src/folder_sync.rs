@@ -0,0 +1,1214 @@
+//! 节点间的双向文件夹同步
+//!
+//! 同一话题中的成员各自把一个本地文件夹绑定到同名的同步文件夹上：
+//! 定期扫描本地变更（新增、修改、删除）并以 `FolderUpdate` 通告，
+//! 收到其他成员的变更后按内容哈希分块拉取（`FolderChunkRequest` / `FolderChunk`），
+//! 校验哈希后替换本地文件。
+//!
+//! 每条变更带有作者修改前的哈希（`base`）。`base` 与本地当前版本一致时直接应用；
+//! 双方都在同一版本上做了修改时视为冲突，保留本地文件，
+//! 远端版本另存为 `名称 (冲突 节点).扩展名`（已存在时依次编号，不覆盖之前的冲突副本），
+//! 该文件标记为冲突，直到本地再次修改或手动确认。
+//! 修改优先于删除：一方删除、另一方修改时保留修改后的文件。
+//!
+//! 设置了安全扫描（[`FileScanHook`]）时，拉取完成的文件先以目标文件名暂存在 `.sync/incoming` 中扫描，
+//! 通过后才放入文件夹并标记为已同步；未通过的文件移入隔离目录，本地文件保持不变。
+//!
+//! 同步状态保存在文件夹的 `.sync` 子目录中，重启后继续
+//!
+//! 话题中的同步文件夹消息由 [`handle_message`] 处理，节点只负责签名广播它返回的回复
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use iroh::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+use crate::{
+    download_routes::unique_path,
+    error::{NodeError, NodeResult},
+    events::{EventBus, NodeEvent},
+    scan::FileScanHook,
+    MessageType,
+};
+
+/// 同步状态所在的子目录
+pub const SYNC_DIR: &str = ".sync";
+/// 同步状态文件
+const STATE_FILE: &str = "state.json";
+/// 等待安全扫描的文件所在的子目录（位于 `.sync` 中）
+const INCOMING_DIR: &str = "incoming";
+/// 单个分块的最大字节数，需小于消息大小上限
+pub const MAX_FOLDER_CHUNK_BYTES: usize = 256 * 1024;
+/// 每条 `FolderUpdate` 最多携带的变更数
+pub const MAX_FOLDER_ENTRIES: usize = 256;
+/// 每个文件记住的历史版本数，用于识别过期的变更
+const HISTORY_LEN: usize = 16;
+
+/// 文件夹同步配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FolderSyncConfig {
+    /// 扫描本地变更的间隔（秒）
+    pub scan_interval_secs: u64,
+    /// 每个分块的字节数
+    pub chunk_bytes: usize,
+    /// 同步的单个文件的最大字节数
+    pub max_file_bytes: u64,
+}
+
+impl Default for FolderSyncConfig {
+    fn default() -> Self {
+        Self {
+            scan_interval_secs: 5,
+            chunk_bytes: 128 * 1024,
+            max_file_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl FolderSyncConfig {
+    /// 设置扫描间隔（秒）
+    pub fn with_scan_interval_secs(mut self, scan_interval_secs: u64) -> Self {
+        self.scan_interval_secs = scan_interval_secs;
+        self
+    }
+
+    /// 设置分块字节数，不超过 [`MAX_FOLDER_CHUNK_BYTES`]
+    pub fn with_chunk_bytes(mut self, chunk_bytes: usize) -> Self {
+        self.chunk_bytes = chunk_bytes;
+        self
+    }
+
+    /// 设置单个文件的最大字节数
+    pub fn with_max_file_bytes(mut self, max_file_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    /// 扫描间隔
+    pub fn scan_interval(&self) -> Duration {
+        Duration::from_secs(self.scan_interval_secs.max(1))
+    }
+}
+
+/// 文件的一次变更
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FolderSyncEntry {
+    /// 文件名
+    pub name: String,
+    /// 内容的 SHA-256（十六进制），已删除时为空
+    pub hash: Option<String>,
+    /// 字节数
+    pub size: u64,
+    /// 修改前的哈希，新文件为空
+    pub base: Option<String>,
+}
+
+/// 文件的同步状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    /// 本地变更尚未通告，或正在拉取远端变更
+    Pending,
+    /// 与远端存在冲突
+    Conflicted,
+    /// 已同步
+    Synced,
+}
+
+/// 文件的同步状态，供界面展示
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSyncStatus {
+    /// 文件名
+    pub name: String,
+    /// 状态
+    pub state: SyncState,
+    /// 本地内容哈希，已删除时为空
+    pub hash: Option<String>,
+    /// 字节数
+    pub size: u64,
+    /// 冲突时远端版本另存的文件名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<String>,
+    /// 状态更新时间
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 应用远端变更后需要执行的动作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteAction {
+    /// 无需动作
+    None,
+    /// 本地文件已按远端变更更新（删除）
+    Applied,
+    /// 需要从 `offset` 开始拉取内容
+    Fetch { hash: String, offset: u64 },
+}
+
+/// 收到分块后的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkOutcome {
+    /// 不是正在拉取的内容，或偏移量不符
+    Ignored,
+    /// 继续从 `offset` 拉取
+    Next { offset: u64 },
+    /// 拉取完成，同步文件 `file` 的内容写入了 `name`（冲突时为另存的文件）
+    Done { file: String, name: String },
+    /// 拉取完成但未通过安全扫描，内容已隔离，没有写入文件夹
+    Quarantined { file: String, reason: String },
+}
+
+/// 本地文件记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileRecord {
+    /// 本地内容哈希，已删除时为空
+    hash: Option<String>,
+    /// 字节数
+    size: u64,
+    /// 计算哈希时的修改时间，未变化时不重新计算
+    modified: Option<SystemTime>,
+    /// 最近的历史版本哈希（含当前版本）
+    history: Vec<String>,
+    state: SyncState,
+    conflict: Option<String>,
+    updated_at: DateTime<Utc>,
+}
+
+impl FileRecord {
+    fn new() -> Self {
+        Self {
+            hash: None,
+            size: 0,
+            modified: None,
+            history: Vec::new(),
+            state: SyncState::Pending,
+            conflict: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// 记录新版本，返回修改前的哈希
+    fn set_hash(&mut self, hash: Option<String>, size: u64) -> Option<String> {
+        if let Some(hash) = &hash {
+            self.history.retain(|known| known != hash);
+            self.history.push(hash.clone());
+            if self.history.len() > HISTORY_LEN {
+                self.history.remove(0);
+            }
+        }
+        self.size = size;
+        self.updated_at = Utc::now();
+        std::mem::replace(&mut self.hash, hash)
+    }
+
+    fn set_state(&mut self, state: SyncState) {
+        self.state = state;
+        self.updated_at = Utc::now();
+    }
+}
+
+/// 正在拉取的远端内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fetch {
+    /// 写入的目标文件名
+    target: String,
+    /// 所属的同步文件
+    file: String,
+    /// 修改前的哈希，完成时用于判断本地是否又有修改
+    base: Option<String>,
+    size: u64,
+    offset: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncStateFile {
+    files: BTreeMap<String, FileRecord>,
+    fetches: HashMap<String, Fetch>,
+}
+
+/// 一个同步文件夹
+#[derive(Debug)]
+pub struct FolderSync {
+    name: String,
+    dir: PathBuf,
+    config: FolderSyncConfig,
+    state: SyncStateFile,
+    scanner: Option<Arc<FileScanHook>>,
+}
+
+impl FolderSync {
+    /// 打开同步文件夹，加载保存的同步状态
+    pub fn open(
+        name: impl Into<String>,
+        dir: impl Into<PathBuf>,
+        config: FolderSyncConfig,
+    ) -> NodeResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(dir.join(SYNC_DIR))?;
+        let path = dir.join(SYNC_DIR).join(STATE_FILE);
+        let state = if path.exists() {
+            serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|e| NodeError::DecodeError(format!("解析同步状态失败: {}", e)))?
+        } else {
+            SyncStateFile::default()
+        };
+        Ok(Self {
+            name: name.into(),
+            dir,
+            config,
+            state,
+            scanner: None,
+        })
+    }
+
+    /// 拉取完成的文件放入文件夹前先做安全扫描
+    pub fn with_scanner(mut self, scanner: Arc<FileScanHook>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    /// 同步文件夹名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 本地目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 配置
+    pub fn config(&self) -> &FolderSyncConfig {
+        &self.config
+    }
+
+    /// 保存同步状态
+    pub fn save(&self) -> NodeResult<()> {
+        let data = serde_json::to_vec(&self.state)
+            .map_err(|e| NodeError::EncodeError(format!("序列化同步状态失败: {}", e)))?;
+        fs::write(self.dir.join(SYNC_DIR).join(STATE_FILE), data)?;
+        Ok(())
+    }
+
+    /// 扫描本地变更，返回需要通告的变更
+    pub fn scan(&mut self) -> NodeResult<Vec<FolderSyncEntry>> {
+        let mut seen = Vec::new();
+        let mut changes = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            if !is_syncable_name(&name)
+                || !metadata.is_file()
+                || metadata.len() > self.config.max_file_bytes
+            {
+                continue;
+            }
+            seen.push(name.clone());
+            let modified = metadata.modified().ok();
+            let record = self
+                .state
+                .files
+                .entry(name.clone())
+                .or_insert_with(FileRecord::new);
+            if record.hash.is_some()
+                && record.size == metadata.len()
+                && record.modified == modified
+                && modified.is_some()
+            {
+                continue;
+            }
+            let hash = file_sha256(&entry.path())?;
+            record.modified = modified;
+            if record.hash.as_deref() == Some(hash.as_str()) {
+                continue;
+            }
+            let base = record.set_hash(Some(hash.clone()), metadata.len());
+            // 本地再次修改视为已处理冲突
+            record.conflict = None;
+            record.set_state(SyncState::Pending);
+            changes.push(FolderSyncEntry {
+                name,
+                hash: Some(hash),
+                size: metadata.len(),
+                base,
+            });
+        }
+
+        for (name, record) in self.state.files.iter_mut() {
+            if record.hash.is_some()
+                && !seen.contains(name)
+                && !self.state.fetches.values().any(|f| &f.target == name)
+            {
+                let base = record.set_hash(None, 0);
+                record.modified = None;
+                record.set_state(SyncState::Pending);
+                changes.push(FolderSyncEntry {
+                    name: name.clone(),
+                    hash: None,
+                    size: 0,
+                    base,
+                });
+            }
+        }
+        if !changes.is_empty() {
+            self.save()?;
+        }
+        Ok(changes)
+    }
+
+    /// 变更已通告给其他成员
+    pub fn announced(&mut self, entries: &[FolderSyncEntry]) -> NodeResult<()> {
+        for entry in entries {
+            if let Some(record) = self.state.files.get_mut(&entry.name) {
+                if record.hash == entry.hash && record.state == SyncState::Pending {
+                    record.set_state(SyncState::Synced);
+                }
+            }
+        }
+        self.save()
+    }
+
+    /// 全部文件的当前版本，用于回复新成员的同步请求
+    pub fn manifest(&self) -> Vec<FolderSyncEntry> {
+        self.entries(|_, _| true)
+    }
+
+    /// 尚未通告的本地变更，通告失败时下次扫描重新发送
+    pub fn unannounced(&self) -> Vec<FolderSyncEntry> {
+        self.entries(|name, record| {
+            record.state == SyncState::Pending
+                && !self.state.fetches.values().any(|fetch| fetch.file == name)
+        })
+    }
+
+    fn entries(&self, filter: impl Fn(&str, &FileRecord) -> bool) -> Vec<FolderSyncEntry> {
+        self.state
+            .files
+            .iter()
+            .filter(|(name, record)| filter(name, record))
+            .map(|(name, record)| FolderSyncEntry {
+                name: name.clone(),
+                hash: record.hash.clone(),
+                size: record.size,
+                base: record
+                    .history
+                    .iter()
+                    .rev()
+                    .nth(usize::from(record.hash.is_some()))
+                    .cloned(),
+            })
+            .collect()
+    }
+
+    /// 应用其他成员的变更，`from` 为对方的简短名称，用于冲突文件名
+    pub fn apply_remote(&mut self, from: &str, entry: FolderSyncEntry) -> NodeResult<RemoteAction> {
+        if !is_syncable_name(&entry.name)
+            || entry.size > self.config.max_file_bytes
+            || !entry.hash.as_deref().is_none_or(is_content_hash)
+            || !entry.base.as_deref().is_none_or(is_content_hash)
+        {
+            return Ok(RemoteAction::None);
+        }
+        let record = self
+            .state
+            .files
+            .entry(entry.name.clone())
+            .or_insert_with(FileRecord::new);
+        if entry.hash == record.hash {
+            if record.state == SyncState::Pending
+                && !self.state.fetches.values().any(|f| f.file == entry.name)
+            {
+                record.set_state(SyncState::Synced);
+            }
+            return Ok(RemoteAction::None);
+        }
+        // 已经见过的旧版本
+        if let Some(hash) = &entry.hash {
+            if record.history.contains(hash) {
+                return Ok(RemoteAction::None);
+            }
+        }
+
+        // 对方基于本地当前版本修改，或本地没有该文件
+        let fast_forward = entry.base == record.hash || record.hash.is_none();
+        let Some(hash) = entry.hash.clone() else {
+            if fast_forward {
+                remove_if_exists(&self.dir.join(&entry.name))?;
+                record.set_hash(None, 0);
+                record.modified = None;
+                record.set_state(SyncState::Synced);
+                self.save()?;
+                return Ok(RemoteAction::Applied);
+            }
+            // 对方删除了本地修改过的文件，保留本地版本
+            record.set_state(SyncState::Conflicted);
+            self.save()?;
+            return Ok(RemoteAction::None);
+        };
+
+        let target = if fast_forward {
+            record.set_state(SyncState::Pending);
+            entry.name.clone()
+        } else {
+            let conflict = conflict_name(&entry.name, from);
+            record.conflict = Some(conflict.clone());
+            record.set_state(SyncState::Conflicted);
+            conflict
+        };
+        // 远端版本也记入历史，冲突处理后不再重复拉取
+        if !fast_forward {
+            record.history.push(hash.clone());
+        }
+        let offset = self
+            .state
+            .fetches
+            .get(&hash)
+            .map_or(0, |fetch| fetch.offset);
+        self.state.fetches.insert(
+            hash.clone(),
+            Fetch {
+                target,
+                file: entry.name,
+                base: entry.base,
+                size: entry.size,
+                offset,
+            },
+        );
+        self.save()?;
+        Ok(RemoteAction::Fetch { hash, offset })
+    }
+
+    /// 读取本地某个版本的分块，返回总字节数与内容；没有该版本时为空
+    pub fn read_chunk(&self, hash: &str, offset: u64) -> NodeResult<Option<(u64, Vec<u8>)>> {
+        if !is_content_hash(hash) {
+            return Ok(None);
+        }
+        let Some((name, record)) = self
+            .state
+            .files
+            .iter()
+            .find(|(_, record)| record.hash.as_deref() == Some(hash))
+        else {
+            return Ok(None);
+        };
+        let mut file = match fs::File::open(self.dir.join(name)) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+        let total = record.size;
+        if offset > total {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let len = self.config.chunk_bytes.clamp(1, MAX_FOLDER_CHUNK_BYTES);
+        let mut data = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut data)?;
+        Ok(Some((total, data)))
+    }
+
+    /// 写入收到的分块，内容完整后校验哈希，通过安全扫描后放入文件夹
+    pub fn write_chunk(
+        &mut self,
+        hash: &str,
+        offset: u64,
+        total: u64,
+        data: &[u8],
+    ) -> NodeResult<ChunkOutcome> {
+        // 哈希来自对端并用作分块文件名，只接受 SHA-256 十六进制
+        if !is_content_hash(hash) {
+            return Ok(ChunkOutcome::Ignored);
+        }
+        let Some(fetch) = self.state.fetches.get_mut(hash) else {
+            return Ok(ChunkOutcome::Ignored);
+        };
+        if fetch.offset != offset || total != fetch.size {
+            return Ok(ChunkOutcome::Ignored);
+        }
+        let part = self.dir.join(SYNC_DIR).join(format!("{}.part", hash));
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&part)?;
+        file.set_len(offset)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)?;
+        fetch.offset += data.len() as u64;
+        if fetch.offset < fetch.size && !data.is_empty() {
+            let offset = fetch.offset;
+            self.save()?;
+            return Ok(ChunkOutcome::Next { offset });
+        }
+        drop(file);
+
+        let fetch = self.state.fetches.remove(hash).expect("上面已取得");
+        if fetch.offset != fetch.size || file_sha256(&part)? != hash {
+            remove_if_exists(&part)?;
+            self.save()?;
+            return Err(NodeError::DecodeError(format!(
+                "同步文件 {} 的内容与哈希不一致",
+                fetch.target
+            )));
+        }
+
+        // 来自对端的内容先以目标文件名暂存并扫描，通过后才放入文件夹
+        let part = match &self.scanner {
+            Some(scanner) => {
+                let incoming = self.dir.join(SYNC_DIR).join(INCOMING_DIR);
+                fs::create_dir_all(&incoming)?;
+                let name = Path::new(&fetch.target)
+                    .file_name()
+                    .unwrap_or(std::ffi::OsStr::new(hash));
+                let staged = incoming.join(name);
+                fs::rename(&part, &staged)?;
+                let result = scanner.scan_blocking(&staged)?;
+                if !result.safe {
+                    self.save()?;
+                    return Ok(ChunkOutcome::Quarantined {
+                        file: fetch.file,
+                        reason: result.reason().unwrap_or_default(),
+                    });
+                }
+                staged
+            }
+            None => part,
+        };
+
+        let record = self
+            .state
+            .files
+            .entry(fetch.file.clone())
+            .or_insert_with(FileRecord::new);
+        let target =
+            if fetch.target == fetch.file && fetch.base != record.hash && record.hash.is_some() {
+                // 拉取期间本地又有修改，改存为冲突文件
+                let conflict = conflict_name(&fetch.file, "远端");
+                record.conflict = Some(conflict.clone());
+                record.set_state(SyncState::Conflicted);
+                conflict
+            } else {
+                fetch.target.clone()
+            };
+        let path = if target == fetch.file {
+            self.dir.join(&target)
+        } else {
+            let path = unique_path(&self.dir, &target);
+            record.conflict = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            path
+        };
+        let target = path
+            .file_name()
+            .map_or(target, |name| name.to_string_lossy().into_owned());
+        fs::rename(&part, &path)?;
+        if target == fetch.file {
+            record.set_hash(Some(hash.to_string()), fetch.size);
+            record.modified = fs::metadata(&path)?.modified().ok();
+            record.set_state(SyncState::Synced);
+        }
+        self.save()?;
+        Ok(ChunkOutcome::Done {
+            file: fetch.file,
+            name: target,
+        })
+    }
+
+    /// 正在拉取的内容及其当前偏移量，用于重新请求中断的拉取
+    pub fn pending_fetches(&self) -> Vec<(String, u64)> {
+        self.state
+            .fetches
+            .iter()
+            .map(|(hash, fetch)| (hash.clone(), fetch.offset))
+            .collect()
+    }
+
+    /// 确认已处理冲突
+    pub fn resolve_conflict(&mut self, name: &str) -> NodeResult<bool> {
+        let Some(record) = self.state.files.get_mut(name) else {
+            return Ok(false);
+        };
+        if record.state != SyncState::Conflicted {
+            return Ok(false);
+        }
+        record.conflict = None;
+        record.set_state(SyncState::Synced);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// 单个文件的同步状态
+    pub fn file_status(&self, name: &str) -> Option<FileSyncStatus> {
+        self.state.files.get(name).map(|record| FileSyncStatus {
+            name: name.to_string(),
+            state: record.state,
+            hash: record.hash.clone(),
+            size: record.size,
+            conflict: record.conflict.clone(),
+            updated_at: record.updated_at,
+        })
+    }
+
+    /// 全部文件的同步状态
+    pub fn status(&self) -> Vec<FileSyncStatus> {
+        self.state
+            .files
+            .keys()
+            .filter_map(|name| self.file_status(name))
+            .collect()
+    }
+}
+
+/// 参与同步的文件名：不含路径分隔符与控制字符，不以 `.` 开头
+pub(crate) fn is_syncable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && !name.starts_with('.')
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(char::is_control)
+}
+
+/// 内容哈希：64 位小写十六进制的 SHA-256
+pub(crate) fn is_content_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// 冲突时远端版本另存的文件名
+fn conflict_name(name: &str, from: &str) -> String {
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| name.to_string());
+    match path.extension() {
+        Some(ext) => format!("{} (冲突 {}).{}", stem, from, ext.to_string_lossy()),
+        None => format!("{} (冲突 {})", stem, from),
+    }
+}
+
+fn file_sha256(path: &Path) -> NodeResult<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(data_encoding::HEXLOWER.encode(&hasher.finalize()))
+}
+
+fn remove_if_exists(path: &Path) -> NodeResult<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// 各话题的同步文件夹
+pub(crate) type FolderSyncMap =
+    Arc<RwLock<HashMap<TopicId, HashMap<String, Arc<Mutex<FolderSync>>>>>>;
+
+pub(crate) fn folder_not_found(folder: &str) -> NodeError {
+    NodeError::ConfigError(format!("同步文件夹不存在: {}", folder))
+}
+
+/// 查找话题中的同步文件夹
+pub(crate) async fn find_folder(
+    folder_syncs: &FolderSyncMap,
+    topic_id: &TopicId,
+    folder: &str,
+) -> Option<Arc<Mutex<FolderSync>>> {
+    folder_syncs
+        .read()
+        .await
+        .get(topic_id)?
+        .get(folder)
+        .cloned()
+}
+
+/// 在阻塞线程中操作同步文件夹，避免哈希与读写文件阻塞消息处理
+pub(crate) async fn with_folder<T, F>(folder: &Arc<Mutex<FolderSync>>, f: F) -> NodeResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut FolderSync) -> NodeResult<T> + Send + 'static,
+{
+    let folder = folder.clone();
+    tokio::task::spawn_blocking(move || f(&mut folder.blocking_lock()))
+        .await
+        .map_err(|e| NodeError::IoError(format!("同步文件夹任务失败: {}", e)))?
+}
+
+/// 发布文件同步状态变化
+pub(crate) fn publish_folder_status(
+    events: &EventBus,
+    topic_id: &TopicId,
+    folder: &str,
+    statuses: impl IntoIterator<Item = FileSyncStatus>,
+) {
+    for status in statuses {
+        events.publish(NodeEvent::FolderSyncChanged {
+            topic_id: topic_id.to_string(),
+            folder: folder.to_string(),
+            status,
+        });
+    }
+}
+
+/// 把文件版本分批打包为 `FolderUpdate` 通告
+pub(crate) fn folder_updates(folder: &str, entries: &[FolderSyncEntry]) -> Vec<MessageType> {
+    entries
+        .chunks(MAX_FOLDER_ENTRIES)
+        .map(|entries| MessageType::FolderUpdate {
+            folder: folder.to_string(),
+            entries: entries.to_vec(),
+        })
+        .collect()
+}
+
+/// 向新加入的节点请求话题中各同步文件夹的全部文件版本
+pub(crate) async fn sync_requests(
+    folder_syncs: &FolderSyncMap,
+    topic_id: &TopicId,
+) -> Vec<MessageType> {
+    folder_syncs
+        .read()
+        .await
+        .get(topic_id)
+        .map(|folders| {
+            folders
+                .keys()
+                .map(|folder| MessageType::FolderSyncRequest {
+                    folder: folder.clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 处理话题中的同步文件夹消息，返回需要广播到话题的回复；
+/// 话题中没有对应的同步文件夹或不是同步文件夹消息时忽略
+pub(crate) async fn handle_message(
+    folder_syncs: &FolderSyncMap,
+    events: &EventBus,
+    topic_id: &TopicId,
+    from: &PublicKey,
+    message: MessageType,
+) -> Vec<MessageType> {
+    match message {
+        MessageType::FolderUpdate { folder, entries } => {
+            let Some(sync) = find_folder(folder_syncs, topic_id, &folder).await else {
+                return Vec::new();
+            };
+            let peer = from.fmt_short();
+            let applied = with_folder(&sync, move |sync| {
+                let mut fetches = Vec::new();
+                let mut changed = Vec::new();
+                for entry in entries {
+                    let name = entry.name.clone();
+                    let before = sync.file_status(&name);
+                    if let RemoteAction::Fetch { hash, offset } = sync.apply_remote(&peer, entry)? {
+                        fetches.push((hash, offset));
+                    }
+                    let after = sync.file_status(&name);
+                    if after != before {
+                        changed.extend(after);
+                    }
+                }
+                Ok((fetches, changed))
+            })
+            .await;
+            let (fetches, changed) = match applied {
+                Ok(applied) => applied,
+                Err(e) => {
+                    warn!("应用来自 {} 的文件夹变更失败: {}", from.fmt_short(), e);
+                    return Vec::new();
+                }
+            };
+            publish_folder_status(events, topic_id, &folder, changed);
+            fetches
+                .into_iter()
+                .map(|(hash, offset)| MessageType::FolderChunkRequest {
+                    folder: folder.clone(),
+                    hash,
+                    offset,
+                })
+                .collect()
+        }
+        MessageType::FolderSyncRequest { folder } => {
+            let Some(sync) = find_folder(folder_syncs, topic_id, &folder).await else {
+                return Vec::new();
+            };
+            let manifest = sync.lock().await.manifest();
+            debug!(
+                "向 {} 发送同步文件夹 {} 的 {} 个文件版本",
+                from.fmt_short(),
+                folder,
+                manifest.len()
+            );
+            folder_updates(&folder, &manifest)
+        }
+        MessageType::FolderChunkRequest {
+            folder,
+            hash,
+            offset,
+        } => {
+            let Some(sync) = find_folder(folder_syncs, topic_id, &folder).await else {
+                return Vec::new();
+            };
+            let read_hash = hash.clone();
+            match with_folder(&sync, move |sync| sync.read_chunk(&read_hash, offset)).await {
+                Ok(Some((total, data))) => vec![MessageType::FolderChunk {
+                    folder,
+                    hash,
+                    offset,
+                    total,
+                    data,
+                }],
+                Ok(None) => Vec::new(),
+                Err(e) => {
+                    warn!("读取同步文件分块失败: {}", e);
+                    Vec::new()
+                }
+            }
+        }
+        MessageType::FolderChunk {
+            folder,
+            hash,
+            offset,
+            total,
+            data,
+        } => {
+            let Some(sync) = find_folder(folder_syncs, topic_id, &folder).await else {
+                return Vec::new();
+            };
+            let write_hash = hash.clone();
+            let written = with_folder(&sync, move |sync| {
+                let outcome = sync.write_chunk(&write_hash, offset, total, &data)?;
+                let status = match &outcome {
+                    ChunkOutcome::Done { file, .. } => sync.file_status(file),
+                    _ => None,
+                };
+                Ok((outcome, status))
+            })
+            .await;
+            match written {
+                Ok((ChunkOutcome::Next { offset }, _)) => {
+                    return vec![MessageType::FolderChunkRequest {
+                        folder,
+                        hash,
+                        offset,
+                    }];
+                }
+                Ok((ChunkOutcome::Done { name, .. }, status)) => {
+                    debug!("同步文件夹 {} 收到文件 {}", folder, name);
+                    publish_folder_status(events, topic_id, &folder, status);
+                }
+                Ok((ChunkOutcome::Quarantined { file, reason }, _)) => {
+                    warn!(
+                        "来自 {} 的同步文件 {} 未通过安全扫描，已隔离: {}",
+                        from.fmt_short(),
+                        file,
+                        reason
+                    );
+                }
+                Ok((ChunkOutcome::Ignored, _)) => {}
+                Err(e) => warn!("写入来自 {} 的同步文件失败: {}", from.fmt_short(), e),
+            }
+            Vec::new()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把 `from` 的全部变更应用到 `to`，并传完需要拉取的内容
+    fn sync(
+        from: &mut FolderSync,
+        to: &mut FolderSync,
+        entries: Vec<FolderSyncEntry>,
+    ) -> Vec<String> {
+        let mut done = Vec::new();
+        for entry in entries {
+            let RemoteAction::Fetch { hash, mut offset } = to.apply_remote("a", entry).unwrap()
+            else {
+                continue;
+            };
+            loop {
+                let (total, data) = from.read_chunk(&hash, offset).unwrap().unwrap();
+                match to.write_chunk(&hash, offset, total, &data).unwrap() {
+                    ChunkOutcome::Next { offset: next } => offset = next,
+                    ChunkOutcome::Done { name, .. } => {
+                        done.push(name);
+                        break;
+                    }
+                    ChunkOutcome::Quarantined { .. } => break,
+                    ChunkOutcome::Ignored => panic!("分块被忽略"),
+                }
+            }
+        }
+        done
+    }
+
+    #[test]
+    fn test_two_way_sync_and_conflicts() {
+        let root = std::env::temp_dir().join(format!(
+            "iroh-node-folder-sync-{}",
+            crate::chat::new_message_id()
+        ));
+        let config = FolderSyncConfig::default().with_chunk_bytes(4);
+        let mut a = FolderSync::open("docs", root.join("a"), config.clone()).unwrap();
+        let mut b = FolderSync::open("docs", root.join("b"), config.clone()).unwrap();
+
+        // 新文件分块传到另一端
+        fs::write(root.join("a/notes.txt"), "hello, world").unwrap();
+        let changes = a.scan().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            a.file_status("notes.txt").unwrap().state,
+            SyncState::Pending
+        );
+        assert_eq!(a.unannounced(), changes);
+        a.announced(&changes).unwrap();
+        assert!(a.unannounced().is_empty());
+        assert_eq!(sync(&mut a, &mut b, changes), ["notes.txt"]);
+        assert_eq!(fs::read(root.join("b/notes.txt")).unwrap(), b"hello, world");
+        assert_eq!(b.file_status("notes.txt").unwrap().state, SyncState::Synced);
+        assert!(b.scan().unwrap().is_empty());
+
+        // 反方向的修改直接应用
+        fs::write(root.join("b/notes.txt"), "edited by b").unwrap();
+        let changes = b.scan().unwrap();
+        assert_eq!(sync(&mut b, &mut a, changes.clone()), ["notes.txt"]);
+        assert_eq!(fs::read(root.join("a/notes.txt")).unwrap(), b"edited by b");
+        // 重复收到的旧变更被忽略
+        assert_eq!(
+            a.apply_remote("b", changes[0].clone()).unwrap(),
+            RemoteAction::None
+        );
+
+        // 双方同时修改时保留本地版本，远端版本另存
+        fs::write(root.join("a/notes.txt"), "a wins locally").unwrap();
+        fs::write(root.join("b/notes.txt"), "b wins locally").unwrap();
+        let from_a = a.scan().unwrap();
+        b.scan().unwrap();
+        assert_eq!(sync(&mut a, &mut b, from_a), ["notes (冲突 a).txt"]);
+        assert_eq!(
+            fs::read(root.join("b/notes.txt")).unwrap(),
+            b"b wins locally"
+        );
+        assert_eq!(
+            fs::read(root.join("b/notes (冲突 a).txt")).unwrap(),
+            b"a wins locally"
+        );
+        let status = b.file_status("notes.txt").unwrap();
+        assert_eq!(status.state, SyncState::Conflicted);
+        assert_eq!(status.conflict.as_deref(), Some("notes (冲突 a).txt"));
+        assert!(b.resolve_conflict("notes.txt").unwrap());
+
+        // 再次冲突时不覆盖之前的冲突副本
+        fs::write(root.join("a/notes.txt"), "a again").unwrap();
+        let from_a = a.scan().unwrap();
+        assert_eq!(sync(&mut a, &mut b, from_a), ["notes (冲突 a) (1).txt"]);
+        assert_eq!(
+            fs::read(root.join("b/notes (冲突 a).txt")).unwrap(),
+            b"a wins locally"
+        );
+        assert_eq!(
+            b.file_status("notes.txt").unwrap().conflict.as_deref(),
+            Some("notes (冲突 a) (1).txt")
+        );
+        assert!(b.resolve_conflict("notes.txt").unwrap());
+        fs::remove_file(root.join("b/notes (冲突 a) (1).txt")).unwrap();
+
+        // 删除同步到另一端，状态在重新打开后保留
+        fs::remove_file(root.join("b/notes (冲突 a).txt")).unwrap();
+        fs::remove_file(root.join("a/notes.txt")).unwrap();
+        let mut changes = a.scan().unwrap();
+        assert_eq!(changes[0].hash, None);
+        changes[0].base = b.file_status("notes.txt").unwrap().hash;
+        assert_eq!(
+            b.apply_remote("a", changes.remove(0)).unwrap(),
+            RemoteAction::Applied
+        );
+        assert!(!root.join("b/notes.txt").exists());
+        drop(b);
+        let b = FolderSync::open("docs", root.join("b"), config).unwrap();
+        assert_eq!(b.file_status("notes.txt").unwrap().hash, None);
+        assert_eq!(b.manifest().len(), 1);
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_received_files_are_scanned() {
+        use crate::scan::{ScanConfig, ScanPolicy, ScannerConfig};
+
+        let root = std::env::temp_dir().join(format!(
+            "iroh-node-folder-scan-{}",
+            crate::chat::new_message_id()
+        ));
+        let scanner = FileScanHook::new(
+            ScanConfig::default()
+                .with_quarantine_dir(root.join("quarantine"))
+                .with_scanner(ScannerConfig::Policy(
+                    ScanPolicy::default().with_blocked_extension("exe"),
+                )),
+        );
+        let config = FolderSyncConfig::default().with_chunk_bytes(4);
+        let mut a = FolderSync::open("tools", root.join("a"), config.clone()).unwrap();
+        let mut b = FolderSync::open("tools", root.join("b"), config)
+            .unwrap()
+            .with_scanner(Arc::new(scanner));
+
+        // 通过扫描的文件放入文件夹，未通过的隔离且不标记为已同步
+        fs::write(root.join("a/notes.txt"), "hello").unwrap();
+        fs::write(root.join("a/setup.exe"), "MZ").unwrap();
+        let changes = a.scan().unwrap();
+        assert_eq!(sync(&mut a, &mut b, changes), ["notes.txt"]);
+        assert_eq!(fs::read(root.join("b/notes.txt")).unwrap(), b"hello");
+        assert_eq!(b.file_status("notes.txt").unwrap().state, SyncState::Synced);
+        assert!(!root.join("b/setup.exe").exists());
+        assert!(root.join("quarantine/setup.exe").exists());
+        assert_ne!(
+            b.file_status("setup.exe").map(|status| status.state),
+            Some(SyncState::Synced)
+        );
+        assert!(b.pending_fetches().is_empty());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_handle_message_pulls_announced_files() {
+        let root = std::env::temp_dir().join(format!(
+            "iroh-node-folder-sync-{}",
+            crate::chat::new_message_id()
+        ));
+        let config = FolderSyncConfig::default().with_chunk_bytes(4);
+        let topic_id = TopicId::from_bytes([5; 32]);
+        let peer = iroh::SecretKey::from_bytes(&[9; 32]).public();
+        let events = EventBus::new();
+
+        let mut a = FolderSync::open("docs", root.join("a"), config.clone()).unwrap();
+        fs::write(root.join("a/notes.txt"), "hello, world").unwrap();
+        let changes = a.scan().unwrap();
+        let b = FolderSync::open("docs", root.join("b"), config).unwrap();
+        let folder_syncs: FolderSyncMap = Arc::new(RwLock::new(HashMap::from([(
+            topic_id,
+            HashMap::from([("docs".to_string(), Arc::new(Mutex::new(b)))]),
+        )])));
+
+        // 通告转为分块请求，由 a 应答分块，直到 b 写完文件
+        let mut pending = folder_updates("docs", &changes);
+        let mut rounds = 0;
+        while let Some(message) = pending.pop() {
+            rounds += 1;
+            assert!(rounds < 100, "同步没有结束");
+            let replies = match message {
+                MessageType::FolderChunkRequest {
+                    folder,
+                    hash,
+                    offset,
+                } => {
+                    let (total, data) = a.read_chunk(&hash, offset).unwrap().unwrap();
+                    vec![MessageType::FolderChunk {
+                        folder,
+                        hash,
+                        offset,
+                        total,
+                        data,
+                    }]
+                }
+                message => handle_message(&folder_syncs, &events, &topic_id, &peer, message).await,
+            };
+            pending.extend(replies);
+        }
+        assert_eq!(fs::read(root.join("b/notes.txt")).unwrap(), b"hello, world");
+
+        assert!(matches!(
+            sync_requests(&folder_syncs, &topic_id).await.as_slice(),
+            [MessageType::FolderSyncRequest { folder }] if folder == "docs"
+        ));
+        // 其他话题中的同名文件夹不受影响
+        let request = MessageType::FolderSyncRequest {
+            folder: "docs".to_string(),
+        };
+        let other = TopicId::from_bytes([6; 32]);
+        assert!(
+            handle_message(&folder_syncs, &events, &other, &peer, request)
+                .await
+                .is_empty()
+        );
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_rejects_malicious_hashes() {
+        let root = std::env::temp_dir().join(format!(
+            "iroh-node-folder-sync-hash-{}",
+            crate::chat::new_message_id()
+        ));
+        let mut sync =
+            FolderSync::open("docs", root.join("docs"), FolderSyncConfig::default()).unwrap();
+        let traversal = "../../../escaped".to_string();
+        let entry = |hash: Option<String>, base: Option<String>| FolderSyncEntry {
+            name: "notes.txt".to_string(),
+            hash,
+            size: 4,
+            base,
+        };
+
+        assert_eq!(
+            sync.apply_remote("a", entry(Some(traversal.clone()), None))
+                .unwrap(),
+            RemoteAction::None
+        );
+        assert_eq!(
+            sync.apply_remote("a", entry(Some("A".repeat(64)), None))
+                .unwrap(),
+            RemoteAction::None
+        );
+        assert_eq!(
+            sync.apply_remote("a", entry(Some("a".repeat(64)), Some(traversal.clone())))
+                .unwrap(),
+            RemoteAction::None
+        );
+        assert!(sync.pending_fetches().is_empty());
+
+        assert_eq!(
+            sync.write_chunk(&traversal, 0, 4, b"evil").unwrap(),
+            ChunkOutcome::Ignored
+        );
+        assert!(sync.read_chunk(&traversal, 0).unwrap().is_none());
+        assert!(!std::env::temp_dir().join("escaped.part").exists());
+        assert!(!root
+            .join("docs")
+            .join(SYNC_DIR)
+            .join(format!("{}.part", traversal))
+            .exists());
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,227 @@
+//! 历史记录的软限制
+//!
+//! 聊天记录与Agent对话历史可能有数MB。服务端限制单页条数，请求超过上限时拒绝；
+//! 过长的消息内容会被截断，并在内容末尾和分页结果中明确标记；
+//! 也可以只获取摘要：各类消息的数量与最近几条消息
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use rig_agent::{
+    core::{ListPage, MAX_PAGE_LIMIT},
+    AgentMessage, PageRequest,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::ChatHistoryEntry,
+    error::{NodeError, NodeResult},
+};
+
+/// 历史记录限制
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryLimits {
+    /// 单页最多返回的条数（不超过 [`MAX_PAGE_LIMIT`]），请求更多时返回413
+    pub max_page_size: usize,
+    /// 单条消息返回的最大字符数，超出部分被截断
+    pub max_message_chars: usize,
+    /// 摘要中包含的最近消息条数
+    pub summary_recent: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_page_size: MAX_PAGE_LIMIT,
+            max_message_chars: 8 * 1024,
+            summary_recent: 10,
+        }
+    }
+}
+
+impl HistoryLimits {
+    /// 设置单页最多返回的条数
+    pub fn with_max_page_size(mut self, max_page_size: usize) -> Self {
+        self.max_page_size = max_page_size;
+        self
+    }
+
+    /// 设置单条消息的最大字符数
+    pub fn with_max_message_chars(mut self, max_message_chars: usize) -> Self {
+        self.max_message_chars = max_message_chars;
+        self
+    }
+
+    /// 设置摘要中的最近消息条数
+    pub fn with_summary_recent(mut self, summary_recent: usize) -> Self {
+        self.summary_recent = summary_recent;
+        self
+    }
+
+    /// 检查分页请求，返回按上限收紧后的请求
+    pub fn apply(&self, request: &PageRequest) -> NodeResult<PageRequest> {
+        if let Some(limit) = request.limit {
+            if limit > self.max_page_size {
+                return Err(NodeError::PayloadTooLarge(format!(
+                    "请求 {} 条，超过单页上限 {} 条",
+                    limit, self.max_page_size
+                )));
+            }
+        }
+        let mut request = request.clone();
+        request.limit = Some(request.effective_limit().min(self.max_page_size));
+        Ok(request)
+    }
+
+    /// 截断过长的消息内容
+    pub fn truncate<T: TruncateContent>(&self, mut page: ListPage<T>) -> HistoryPage<T> {
+        let truncated = self.truncate_items(&mut page.items);
+        HistoryPage { page, truncated }
+    }
+
+    fn truncate_items<T: TruncateContent>(&self, items: &mut [T]) -> usize {
+        items
+            .iter_mut()
+            .map(|item| item.truncate_content(self.max_message_chars))
+            .filter(|&truncated| truncated)
+            .count()
+    }
+
+    /// 生成摘要：总数、按类别的数量、时间范围与最近几条消息
+    pub fn summarize<T, F, C>(
+        &self,
+        mut items: Vec<T>,
+        timestamp: F,
+        category: C,
+    ) -> HistorySummary<T>
+    where
+        T: TruncateContent,
+        F: Fn(&T) -> DateTime<Utc>,
+        C: Fn(&T) -> String,
+    {
+        items.sort_by_key(&timestamp);
+        let mut counts = BTreeMap::new();
+        for item in &items {
+            *counts.entry(category(item)).or_insert(0) += 1;
+        }
+
+        let total = items.len();
+        let first_at = items.first().map(&timestamp);
+        let last_at = items.last().map(&timestamp);
+        let mut recent = items.split_off(total.saturating_sub(self.summary_recent));
+        let truncated = self.truncate_items(&mut recent);
+
+        HistorySummary {
+            total,
+            counts,
+            first_at,
+            last_at,
+            recent,
+            truncated,
+        }
+    }
+}
+
+/// 可截断内容的历史条目
+pub trait TruncateContent {
+    /// 把内容截断到最多 `max_chars` 个字符并附加截断标记，返回是否发生截断
+    fn truncate_content(&mut self, max_chars: usize) -> bool;
+}
+
+impl TruncateContent for ChatHistoryEntry {
+    fn truncate_content(&mut self, max_chars: usize) -> bool {
+        truncate_text(&mut self.text, max_chars)
+    }
+}
+
+impl TruncateContent for AgentMessage {
+    fn truncate_content(&mut self, max_chars: usize) -> bool {
+        truncate_text(&mut self.content, max_chars)
+    }
+}
+
+/// 按字符截断文本并附加截断标记
+fn truncate_text(text: &mut String, max_chars: usize) -> bool {
+    let chars = text.chars().count();
+    if chars <= max_chars {
+        return false;
+    }
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(index, _)| index);
+    text.truncate(end);
+    text.push_str(&format!("…[已截断，原文共 {} 字符]", chars));
+    true
+}
+
+/// 带截断标记的一页历史记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage<T> {
+    /// 分页结果
+    #[serde(flatten)]
+    pub page: ListPage<T>,
+    /// 内容被截断的条数
+    pub truncated: usize,
+}
+
+/// 历史记录摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySummary<T> {
+    /// 总条数
+    pub total: usize,
+    /// 按类别（消息角色或收发方向）的条数
+    pub counts: BTreeMap<String, usize>,
+    /// 最早一条的时间
+    pub first_at: Option<DateTime<Utc>>,
+    /// 最近一条的时间
+    pub last_at: Option<DateTime<Utc>>,
+    /// 最近的几条消息（按时间顺序）
+    pub recent: Vec<T>,
+    /// 最近消息中内容被截断的条数
+    pub truncated: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig_agent::AgentRole;
+
+    #[test]
+    fn test_history_limits() {
+        let limits = HistoryLimits::default()
+            .with_max_page_size(50)
+            .with_max_message_chars(4)
+            .with_summary_recent(2);
+
+        assert!(matches!(
+            limits.apply(&PageRequest::default().with_range(0, 51)),
+            Err(NodeError::PayloadTooLarge(_))
+        ));
+        assert_eq!(
+            limits.apply(&PageRequest::default()).unwrap().limit,
+            Some(50)
+        );
+
+        let messages = vec![
+            AgentMessage::user("你好".to_string()),
+            AgentMessage::assistant("这是一段很长的回复".to_string()),
+            AgentMessage::user("谢谢".to_string()),
+        ];
+        let page = limits.truncate(PageRequest::default().page(messages.clone()));
+        assert_eq!(page.truncated, 1);
+        assert!(page.page.items[1].content.starts_with("这是一段…[已截断"));
+
+        let summary = limits.summarize(
+            messages,
+            |message| message.timestamp,
+            |message| format!("{:?}", message.role).to_lowercase(),
+        );
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.counts.get("user"), Some(&2));
+        assert_eq!(summary.recent.len(), 2);
+        assert_eq!(summary.recent[1].role, AgentRole::User);
+        assert_eq!(summary.truncated, 1);
+    }
+}
@@ -0,0 +1,236 @@
+//! 邀请链接与二维码
+//!
+//! 把话题票据或文件分享票据包装为 `iroh-agent://join/<ticket>` 与
+//! `iroh-agent://share/<ticket>` 形式的链接，并生成PNG二维码，便于移动端扫码加入
+
+use std::{fmt, io::Cursor, str::FromStr};
+
+use image::{DynamicImage, ImageFormat, Luma};
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{NodeError, NodeResult},
+    Ticket,
+};
+
+/// 邀请链接协议
+pub const INVITE_SCHEME: &str = "iroh-agent";
+
+/// 二维码图片的最小边长（像素）
+const QR_MIN_SIZE: u32 = 256;
+
+/// 邀请类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InviteKind {
+    /// 加入房间（话题票据）
+    Room,
+    /// 获取文件分享（文档票据）
+    Share,
+}
+
+impl InviteKind {
+    /// 链接中的路径
+    fn path(self) -> &'static str {
+        match self {
+            Self::Room => "join",
+            Self::Share => "share",
+        }
+    }
+}
+
+/// 邀请
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invite {
+    /// 邀请类型
+    pub kind: InviteKind,
+    /// 票据
+    pub ticket: String,
+    /// 房间或分享的名称
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// 邀请链接及其二维码
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteCode {
+    /// 邀请链接
+    pub url: String,
+    /// PNG格式的二维码
+    pub qr_png: Vec<u8>,
+}
+
+impl Invite {
+    /// 创建房间邀请，票据必须是有效的话题票据
+    pub fn room(ticket: impl Into<String>, name: Option<String>) -> NodeResult<Self> {
+        Self::new(InviteKind::Room, ticket.into(), name)
+    }
+
+    /// 创建文件分享邀请
+    pub fn share(ticket: impl Into<String>, name: Option<String>) -> NodeResult<Self> {
+        Self::new(InviteKind::Share, ticket.into(), name)
+    }
+
+    /// 创建邀请并校验票据
+    pub fn new(kind: InviteKind, ticket: String, name: Option<String>) -> NodeResult<Self> {
+        let ticket = ticket.trim().to_string();
+        if ticket.is_empty() || !ticket.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(NodeError::DecodeError("票据格式无效".to_string()));
+        }
+        if kind == InviteKind::Room {
+            Ticket::from_str(&ticket)?;
+        }
+
+        Ok(Self {
+            kind,
+            ticket,
+            name: name.filter(|n| !n.trim().is_empty()),
+        })
+    }
+
+    /// 邀请链接
+    pub fn to_url(&self) -> String {
+        let mut url = format!("{}://{}/{}", INVITE_SCHEME, self.kind.path(), self.ticket);
+        if let Some(name) = &self.name {
+            url.push_str("?name=");
+            url.push_str(&percent_encode(name));
+        }
+        url
+    }
+
+    /// 生成PNG二维码
+    pub fn qr_png(&self) -> NodeResult<Vec<u8>> {
+        render_qr_png(&self.to_url())
+    }
+
+    /// 邀请链接与二维码
+    pub fn to_code(&self) -> NodeResult<InviteCode> {
+        Ok(InviteCode {
+            url: self.to_url(),
+            qr_png: self.qr_png()?,
+        })
+    }
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_url())
+    }
+}
+
+/// 从邀请链接解析
+impl FromStr for Invite {
+    type Err = NodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NodeError::DecodeError(format!("无效的邀请链接: {}", s));
+
+        let rest = s
+            .trim()
+            .strip_prefix(INVITE_SCHEME)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .ok_or_else(invalid)?;
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        let (kind, ticket) = path.split_once('/').ok_or_else(invalid)?;
+        let kind = match kind {
+            "join" => InviteKind::Room,
+            "share" => InviteKind::Share,
+            _ => return Err(invalid()),
+        };
+
+        let mut name = None;
+        for pair in query.into_iter().flat_map(|q| q.split('&')) {
+            if let Some(value) = pair.strip_prefix("name=") {
+                name = Some(percent_decode(value).ok_or_else(invalid)?);
+            }
+        }
+
+        Self::new(kind, ticket.trim_end_matches('/').to_string(), name)
+    }
+}
+
+/// 把文本渲染为PNG二维码
+pub(crate) fn render_qr_png(data: &str) -> NodeResult<Vec<u8>> {
+    let code = QrCode::new(data.as_bytes())
+        .map_err(|e| NodeError::EncodeError(format!("生成二维码失败: {}", e)))?;
+    let image = code
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_MIN_SIZE, QR_MIN_SIZE)
+        .build();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| NodeError::EncodeError(format!("编码二维码图片失败: {}", e)))?;
+    Ok(png)
+}
+
+/// 除RFC 3986非保留字符外全部百分号编码
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh_gossip::proto::TopicId;
+
+    #[test]
+    fn test_invite_url_round_trip() {
+        let ticket = Ticket {
+            topic: TopicId::from_bytes([7; 32]),
+            peers: Vec::new(),
+        };
+        let invite = Invite::room(ticket.to_string(), Some("周会 & 讨论".to_string())).unwrap();
+        let url = invite.to_url();
+        assert!(url.starts_with("iroh-agent://join/"));
+        assert_eq!(url.parse::<Invite>().unwrap(), invite);
+
+        let share = Invite::share("docabc123", None).unwrap();
+        assert_eq!(share.to_url(), "iroh-agent://share/docabc123");
+        assert_eq!(share.to_url().parse::<Invite>().unwrap(), share);
+
+        assert!("https://example.com/join/abc".parse::<Invite>().is_err());
+        assert!("iroh-agent://join/not-a-ticket".parse::<Invite>().is_err());
+        assert!("iroh-agent://share/abc?name=%ZZ".parse::<Invite>().is_err());
+
+        let png = share.qr_png().unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
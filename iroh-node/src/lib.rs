@@ -2,9 +2,42 @@
 //!
 //! 提供P2P通信功能，用于在tauri和axum中集成，并与rig-agent服务交互
 
+mod address_book;
+mod bundle;
+mod chat;
+mod chat_log;
+mod commands;
 mod config;
+mod content;
+mod coordination;
+mod crash;
+mod doctor;
+mod download_routes;
+mod ensemble;
 mod error;
+mod events;
+mod folder_sync;
+mod history;
+mod invite;
+mod links;
+mod logging;
+mod logs;
+mod memory;
+mod mentions;
+mod outbox;
 mod p2p;
+mod presence;
+mod protocol;
+mod read_state;
+mod room_bot;
+mod scan;
+mod supervisor;
+mod templates;
+mod ticket;
+mod transfer_schedule;
+mod usage;
+mod validation;
+mod verification;
 
 pub mod adapters;
 
@@ -13,20 +46,74 @@ use std::{fmt, str::FromStr};
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use ed25519_dalek::Signature;
-use iroh_net::{
-    key::{PublicKey, SecretKey}, 
-    relay::RelayMode, 
-    NodeAddr
-};
-use iroh_gossip::proto::topic::TopicId;
+use iroh::{NodeAddr, PublicKey, RelayMode, SecretKey};
+use iroh_gossip::proto::TopicId;
 use serde::{Deserialize, Serialize};
 
 pub use crate::{
-    config::NodeConfig,
+    address_book::{AddressBook, PeerRecord, TrustLevel, TrustPolicy},
+    bundle::{BundleTopic, NodeBundle},
+    chat::{AckKind, ChatHistoryEntry, DeliveryState, DeliveryStatus, Reactions, MAX_REACTION_BYTES},
+    chat_log::{ChatLogConfig, CHAT_KEY_PREFIX},
+    commands::{
+        ChatCommand, CommandAction, CommandContext, CommandOutcome, CommandRegistry,
+        COMMAND_PREFIX, DEFAULT_COMMAND_AGENT,
+    },
+    config::{NodeConfig, NODE_SECRET_KEY_NAME},
+    content::{MessageContent, MAX_CODE_BYTES, MAX_LANGUAGE_BYTES},
+    coordination::{
+        AgentAnswer, AgentLoad, AgentRequestMode, AgentRequestOutcome, AgentRequestStatus,
+        AgentTarget, DEFAULT_AGENT_TIMEOUT,
+    },
+    crash::{CrashReport, CrashReporter, CRASH_DIR},
+    doctor::{run_doctor, DoctorFinding, DoctorReport, FindingLevel},
+    download_routes::{DownloadRoutes, RouteRule},
+    ensemble::{EnsembleOptions, EnsembleOutcome},
     error::{NodeError, NodeResult},
-    p2p::P2PNode,
+    events::{EventBus, NodeEvent, NODE_EVENT_VERSION},
+    folder_sync::{
+        ChunkOutcome, FileSyncStatus, FolderSync, FolderSyncConfig, FolderSyncEntry,
+        RemoteAction, SyncState, MAX_FOLDER_CHUNK_BYTES, MAX_FOLDER_ENTRIES,
+    },
+    history::{HistoryLimits, HistoryPage, HistorySummary, TruncateContent},
+    invite::{Invite, InviteCode, InviteKind, INVITE_SCHEME},
+    links::{extract_links, is_public_ip, LinkPreview},
+    logging::{init_logging, FileLogConfig, LogRotation, LoggingConfig, RollingFileWriter},
+    logs::{logs_to_text, LogBuffer, LogEntry, LogLayer, LogQuery, DEFAULT_LOG_CAPACITY},
+    memory::{MemoryEntry, MemoryMatch, MemoryVersion, SharedMemory, SharedMemoryConfig},
+    mentions::{MentionRecord, MentionSummary},
+    p2p::{AgentChat, AgentRequestHandle, P2PNode},
+    outbox::{OutboxConfig, OutboxEntry},
+    presence::{MemberPresence, PresenceConfig, RoomLiveness},
+    protocol::{
+        is_supported_version, Capabilities, PeerInfo, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+    },
+    read_state::{ReadMarker, RoomUnread, UnreadSummary},
+    room_bot::RoomBotConfig,
+    scan::{
+        ClamAvScanner, CommandScanner, FileScanHook, FileScanner, ScanConfig, ScanOutcome,
+        ScanPolicy, ScanResult, ScanVerdict, ScannerConfig, ScannerResult,
+    },
+    supervisor::{TaskHealth, TaskState},
+    templates::{
+        template_hash, template_topic, RemoteTemplate, TemplateAnnouncement, TemplateExchange,
+    },
+    ticket::TicketOptions,
+    transfer_schedule::{
+        PauseReason, TransferGate, TransferGateSubscription, TransferSchedule,
+        TransferScheduleStatus, TransferWindow,
+    },
+    usage::{
+        agents_to_csv, series_to_csv, AgentUsage, ModelPrice, StatsWindow, UsageBucket,
+        UsageLedger, UsageSummary,
+    },
+    validation::MessageLimits,
+    verification::{SafetyNumber, ScannedCode},
 };
 
+#[cfg(feature = "crash-upload")]
+pub use crate::crash::upload_crash_report;
+
 /// 节点状态
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStatus {
@@ -44,20 +131,64 @@ pub struct NodeStatus {
     pub last_activity: DateTime<Utc>,
     /// 中继模式
     pub relay_mode: String,
+    /// 当前主中继服务器
+    #[serde(default)]
+    pub home_relay: Option<String>,
+    /// 后台任务的运行状况
+    #[serde(default)]
+    pub tasks: Vec<TaskHealth>,
+}
+
+/// 话题统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TopicStats {
+    /// 话题ID
+    pub topic_id: String,
+    /// 收到的消息数量
+    pub messages_in: u64,
+    /// 发出的消息数量
+    pub messages_out: u64,
+    /// 收到的字节数
+    pub bytes_in: u64,
+    /// 发出的字节数
+    pub bytes_out: u64,
+    /// 不同发送者数量（不含本节点）
+    pub unique_senders: usize,
+    /// 加入时间
+    pub joined_at: Option<DateTime<Utc>>,
+    /// 最后活动时间
+    pub last_activity: Option<DateTime<Utc>>,
 }
 
 /// 消息类型
+///
+/// 以postcard编码，变体按序号区分，协议版本见 [`PROTOCOL_VERSION`]。
+/// 为保持与旧节点兼容：新变体只能追加在末尾，已有变体不能调整顺序或修改字段；
+/// 需要不兼容变更时新增变体并提升协议版本
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MessageType {
     /// 聊天消息
     Chat {
+        /// 消息ID
+        id: String,
         /// 消息文本
         text: String,
     },
-    /// 节点信息
+    /// 聊天消息确认
+    Ack {
+        /// 被确认的消息ID
+        message_id: String,
+        /// 确认类型
+        kind: AckKind,
+    },
+    /// 节点信息，用于交换名称、协议版本与能力
     NodeInfo {
         /// 节点名称
         name: Option<String>,
+        /// 协议版本
+        version: u16,
+        /// 支持的能力
+        capabilities: Capabilities,
     },
     /// Agent请求
     AgentRequest {
@@ -83,6 +214,174 @@ pub enum MessageType {
         /// 系统消息内容
         content: String,
     },
+    /// 身份验证确认：发送者已核对与 `peer_id` 之间的安全码
+    VerifyConfirm {
+        /// 被验证的节点ID
+        peer_id: String,
+        /// 发送者计算的安全码摘要
+        digest: String,
+    },
+    /// Agent请求认领：发送者已开始处理该请求，其他节点不再处理
+    AgentClaim {
+        /// 请求ID，由请求方与请求内容派生
+        request_id: String,
+    },
+    /// 要求所有运行Agent的节点都回答的请求，不参与响应者选举
+    AgentFanOutRequest {
+        /// 提示词
+        prompt: String,
+        /// Agent ID
+        agent_id: String,
+    },
+    /// 带请求ID与寻址方式的Agent请求，应答以 `AgentReply` 关联
+    AgentQuery {
+        /// 请求ID
+        request_id: String,
+        /// 提示词
+        prompt: String,
+        /// Agent ID
+        agent_id: String,
+        /// 响应者范围
+        target: AgentTarget,
+    },
+    /// `AgentQuery` 的应答
+    AgentReply {
+        /// 请求ID
+        request_id: String,
+        /// Agent ID
+        agent_id: String,
+        /// 响应内容，处理失败时为空
+        content: Option<String>,
+        /// 错误信息
+        error: Option<String>,
+    },
+    /// 共享记忆的新增、修改或删除
+    MemoryUpdate {
+        /// 作者签名的记录
+        entries: Vec<MemoryEntry>,
+    },
+    /// 请求补发共享记忆，对方回复 `known` 中缺失或版本较旧的记录
+    MemorySyncRequest {
+        /// 发送者已有的记录版本
+        known: Vec<MemoryVersion>,
+    },
+    /// 在发现话题中通告本节点分享的Agent模板
+    TemplateAnnounce {
+        /// 模板通告
+        templates: Vec<TemplateAnnouncement>,
+    },
+    /// 按哈希请求Agent模板内容，持有该内容的节点以 `TemplateContent` 应答
+    TemplateFetch {
+        /// 内容哈希
+        hash: String,
+    },
+    /// Agent模板内容
+    TemplateContent {
+        /// 内容哈希
+        hash: String,
+        /// Agent包文件内容
+        data: Vec<u8>,
+    },
+    /// 在线消息，用于话题保活与统计活跃成员
+    Presence {
+        /// 发送者当前的保活间隔（秒）
+        interval_secs: u32,
+    },
+    /// Agent负载通告，用于按负载选举响应者
+    LoadReport {
+        /// 发送者当前的Agent负载
+        load: AgentLoad,
+    },
+    /// 同步文件夹中文件的新增、修改或删除
+    FolderUpdate {
+        /// 同步文件夹名称
+        folder: String,
+        /// 变更的文件
+        entries: Vec<FolderSyncEntry>,
+    },
+    /// 请求同步文件夹的全部文件版本，对方以 `FolderUpdate` 应答
+    FolderSyncRequest {
+        /// 同步文件夹名称
+        folder: String,
+    },
+    /// 按内容哈希请求文件分块，持有该版本的节点以 `FolderChunk` 应答
+    FolderChunkRequest {
+        /// 同步文件夹名称
+        folder: String,
+        /// 内容哈希
+        hash: String,
+        /// 起始偏移量
+        offset: u64,
+    },
+    /// 文件分块
+    FolderChunk {
+        /// 同步文件夹名称
+        folder: String,
+        /// 内容哈希
+        hash: String,
+        /// 起始偏移量
+        offset: u64,
+        /// 文件总字节数
+        total: u64,
+        /// 分块内容
+        data: Vec<u8>,
+    },
+    /// 回复某条消息的聊天消息，回复关系构成消息串
+    ChatReply {
+        /// 消息ID，用于确认回执
+        id: String,
+        /// 消息内容
+        text: String,
+        /// 被回复的消息ID
+        reply_to: String,
+    },
+    /// 对聊天消息添加或撤销表情回应
+    Reaction {
+        /// 被回应的消息ID
+        message_id: String,
+        /// 表情
+        emoji: String,
+        /// 是否为撤销
+        removed: bool,
+    },
+    /// 结构化内容的聊天消息（Markdown、代码块、系统提示）
+    ChatContent {
+        /// 消息ID，用于确认回执
+        id: String,
+        /// 消息内容
+        content: MessageContent,
+        /// 被回复的消息ID
+        reply_to: Option<String>,
+    },
+    /// 服务节点对 `AgentQuery` 的处理状态，最终结果仍以 `AgentReply` 发送
+    AgentStatus {
+        /// 请求ID
+        request_id: String,
+        /// Agent ID
+        agent_id: String,
+        /// 处理状态
+        status: AgentRequestStatus,
+    },
+}
+
+/// 带协议版本的消息，签名覆盖版本号；版本号在前，解码时可单独读取
+///
+/// 编码时借用消息，避免复制整条消息
+#[derive(Debug, Serialize)]
+struct VersionedMessage<'a> {
+    /// 协议版本
+    version: u16,
+    /// 消息内容
+    message: &'a MessageType,
+}
+
+/// 按字节串编码的借用切片，与 `Bytes` 的编码格式相同
+struct RawBytes<'a>(&'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
 }
 
 /// 签名消息
@@ -96,46 +395,110 @@ pub struct SignedMessage {
     signature: Signature,
 }
 
+/// 编码用的签名消息，字段顺序与 [`SignedMessage`] 相同
+#[derive(Serialize)]
+struct SignedMessageRef<'a> {
+    from: &'a PublicKey,
+    data: RawBytes<'a>,
+    signature: &'a Signature,
+}
+
+/// 解码用的签名消息，消息数据借用输入，不复制
+#[derive(Deserialize)]
+struct BorrowedSignedMessage<'a> {
+    from: PublicKey,
+    #[serde(borrow)]
+    data: &'a [u8],
+    signature: Signature,
+}
+
+/// 签名消息中除消息数据外的最大开销：公钥、签名与长度前缀
+const SIGNED_OVERHEAD: usize = 32 + 64 + 16;
+
+thread_local! {
+    /// 编码消息数据的复用缓冲区，签名后即写入输出，不需要单独分配
+    static ENCODE_BUFFER: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// 复用缓冲区的上限，超过时编码后释放，避免偶尔的大消息长期占用内存
+const MAX_RETAINED_ENCODE_BUFFER: usize = 64 * 1024;
+
 impl SignedMessage {
     /// 验证并解码消息
     pub fn verify_and_decode(bytes: &[u8]) -> NodeResult<(PublicKey, MessageType)> {
-        let signed_message: Self = postcard::from_bytes(bytes)
+        let (from, version, payload) = Self::verify_slice(bytes)?;
+        if !is_supported_version(version) {
+            return Err(NodeError::InvalidMessage(format!(
+                "不支持的协议版本: {}（支持 {}-{}）",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            )));
+        }
+
+        let message: MessageType = postcard::from_bytes(payload)
+            .map_err(|e| NodeError::DecodeError(format!("解码消息内容失败: {}", e)))?;
+        Ok((from, message))
+    }
+
+    /// 验证签名并读取协议版本，返回发送者、版本与尚未解码的消息内容
+    ///
+    /// 版本号位于消息内容之前，即使对方使用不兼容的版本也能识别出来。
+    /// 返回的消息内容与输入共享同一块内存
+    pub fn verify(bytes: &Bytes) -> NodeResult<(PublicKey, u16, Bytes)> {
+        let (from, version, payload) = Self::verify_slice(bytes)?;
+        Ok((from, version, bytes.slice_ref(payload)))
+    }
+
+    /// 验证签名并读取协议版本，消息内容借用输入
+    fn verify_slice(bytes: &[u8]) -> NodeResult<(PublicKey, u16, &[u8])> {
+        let signed_message: BorrowedSignedMessage<'_> = postcard::from_bytes(bytes)
             .map_err(|e| NodeError::DecodeError(format!("解码签名消息失败: {}", e)))?;
-        
+
         let key: PublicKey = signed_message.from;
-        key.verify(&signed_message.data, &signed_message.signature)
+        key.verify(signed_message.data, &signed_message.signature)
             .map_err(|e| NodeError::VerifyError(format!("验证签名失败: {}", e)))?;
-        
-        let message: MessageType = postcard::from_bytes(&signed_message.data)
-            .map_err(|e| NodeError::DecodeError(format!("解码消息内容失败: {}", e)))?;
-        
-        Ok((signed_message.from, message))
+
+        let (version, payload) = postcard::take_from_bytes::<u16>(signed_message.data)
+            .map_err(|e| NodeError::DecodeError(format!("解码协议版本失败: {}", e)))?;
+
+        Ok((signed_message.from, version, payload))
     }
 
     /// 签名并编码消息
     pub fn sign_and_encode(secret_key: &SecretKey, message: &MessageType) -> NodeResult<Bytes> {
-        let data: Bytes = postcard::to_stdvec(message)
-            .map_err(|e| NodeError::EncodeError(format!("编码消息失败: {}", e)))?
-            .into();
-        
-        let signature = secret_key.sign(&data);
-        let from: PublicKey = secret_key.public();
-        
-        let signed_message = Self {
-            from,
-            data,
-            signature,
+        let versioned = VersionedMessage {
+            version: PROTOCOL_VERSION,
+            message,
         };
-        
-        let encoded = postcard::to_stdvec(&signed_message)
-            .map_err(|e| NodeError::EncodeError(format!("编码签名消息失败: {}", e)))?;
-        
-        Ok(encoded.into())
+
+        ENCODE_BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            buffer.clear();
+            let data = postcard::to_extend(&versioned, std::mem::take(&mut *buffer))
+                .map_err(|e| NodeError::EncodeError(format!("编码消息失败: {}", e)))?;
+
+            let signature = secret_key.sign(&data);
+            let from: PublicKey = secret_key.public();
+            let signed_message = SignedMessageRef {
+                from: &from,
+                data: RawBytes(&data),
+                signature: &signature,
+            };
+            let encoded = postcard::to_extend(
+                &signed_message,
+                Vec::with_capacity(data.len() + SIGNED_OVERHEAD),
+            )
+            .map_err(|e| NodeError::EncodeError(format!("编码签名消息失败: {}", e)));
+
+            if data.capacity() <= MAX_RETAINED_ENCODE_BUFFER {
+                *buffer = data;
+            }
+            Ok(encoded?.into())
+        })
     }
 }
 
 /// 票据
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ticket {
     /// 话题ID
     pub topic: TopicId,
@@ -165,11 +528,16 @@ impl fmt::Display for Ticket {
     }
 }
 
-/// 从base32反序列化
+/// 从紧凑格式或base32反序列化
 impl FromStr for Ticket {
     type Err = NodeError;
     
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(compact) = s.strip_prefix(ticket::COMPACT_PREFIX) {
+            return Self::from_compact_str(compact);
+        }
+
         let bytes = data_encoding::BASE32_NOPAD
             .decode(s.to_ascii_uppercase().as_bytes())
             .map_err(|e| NodeError::DecodeError(format!("解码base32失败: {}", e)))?;
@@ -190,4 +558,223 @@ pub(crate) fn fmt_relay_mode(relay_mode: &RelayMode) -> String {
             .collect::<Vec<_>>()
             .join(" "),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn arb_message() -> impl Strategy<Value = MessageType> {
+        prop_oneof![
+            (any::<String>(), any::<String>())
+                .prop_map(|(id, text)| MessageType::Chat { id, text }),
+            (any::<String>(), any::<bool>()).prop_map(|(message_id, read)| MessageType::Ack {
+                message_id,
+                kind: if read {
+                    AckKind::Read
+                } else {
+                    AckKind::Delivered
+                },
+            }),
+            (any::<Option<String>>(), any::<u16>(), any::<u32>()).prop_map(
+                |(name, version, bits)| MessageType::NodeInfo {
+                    name,
+                    version,
+                    capabilities: Capabilities::from_bits_truncate(bits),
+                }
+            ),
+            (any::<String>(), any::<String>())
+                .prop_map(|(prompt, agent_id)| MessageType::AgentRequest { prompt, agent_id }),
+            any::<String>().prop_map(|message| MessageType::Error { message }),
+            any::<String>().prop_map(|request_id| MessageType::AgentClaim { request_id }),
+            (any::<String>(), any::<String>(), any::<u64>(), 0u8..3).prop_map(
+                |(request_id, agent_id, value, kind)| MessageType::AgentStatus {
+                    request_id,
+                    agent_id,
+                    status: match kind {
+                        0 => AgentRequestStatus::Accepted,
+                        1 => AgentRequestStatus::QueuePosition {
+                            position: value as u32,
+                        },
+                        _ => AgentRequestStatus::Processing { elapsed_ms: value },
+                    },
+                }
+            ),
+        ]
+    }
+
+    /// 已发布的消息编码不能改变，否则旧节点无法解码
+    #[test]
+    fn test_message_type_wire_compat() {
+        let cases: Vec<(MessageType, &[u8])> = vec![
+            (
+                MessageType::Chat {
+                    id: "m1".to_string(),
+                    text: "hi".to_string(),
+                },
+                &[0, 2, b'm', b'1', 2, b'h', b'i'],
+            ),
+            (
+                MessageType::Ack {
+                    message_id: "m1".to_string(),
+                    kind: AckKind::Read,
+                },
+                &[1, 2, b'm', b'1', 1],
+            ),
+            (
+                MessageType::NodeInfo {
+                    name: None,
+                    version: 1,
+                    capabilities: Capabilities::from_bits_truncate(3),
+                },
+                &[2, 0, 1, 3],
+            ),
+            (
+                MessageType::AgentRequest {
+                    prompt: "p".to_string(),
+                    agent_id: "a".to_string(),
+                },
+                &[3, 1, b'p', 1, b'a'],
+            ),
+            (MessageType::Presence { interval_secs: 30 }, &[17, 30]),
+            (
+                MessageType::LoadReport {
+                    load: AgentLoad {
+                        queue_depth: 2,
+                        latency_ms: Some(300),
+                    },
+                },
+                &[18, 2, 1, 172, 2],
+            ),
+            (
+                MessageType::FolderSyncRequest {
+                    folder: "f".to_string(),
+                },
+                &[20, 1, b'f'],
+            ),
+            (
+                MessageType::Reaction {
+                    message_id: "m1".to_string(),
+                    emoji: "👍".to_string(),
+                    removed: false,
+                },
+                &[24, 2, b'm', b'1', 4, 0xF0, 0x9F, 0x91, 0x8D, 0],
+            ),
+            (
+                MessageType::ChatContent {
+                    id: "m1".to_string(),
+                    content: MessageContent::Code {
+                        language: Some("rs".to_string()),
+                        code: "x".to_string(),
+                    },
+                    reply_to: None,
+                },
+                &[25, 2, b'm', b'1', 2, 1, 2, b'r', b's', 1, b'x', 0],
+            ),
+            (
+                MessageType::AgentStatus {
+                    request_id: "r".to_string(),
+                    agent_id: "a".to_string(),
+                    status: AgentRequestStatus::Processing { elapsed_ms: 300 },
+                },
+                &[26, 1, b'r', 1, b'a', 2, 172, 2],
+            ),
+            (
+                MessageType::AgentStatus {
+                    request_id: "r".to_string(),
+                    agent_id: "a".to_string(),
+                    status: AgentRequestStatus::Accepted,
+                },
+                &[26, 1, b'r', 1, b'a', 0],
+            ),
+            (
+                MessageType::AgentStatus {
+                    request_id: "r".to_string(),
+                    agent_id: "a".to_string(),
+                    status: AgentRequestStatus::QueuePosition { position: 3 },
+                },
+                &[26, 1, b'r', 1, b'a', 1, 3],
+            ),
+        ];
+        for (message, bytes) in cases {
+            assert_eq!(postcard::to_stdvec(&message).unwrap(), bytes);
+            let decoded: MessageType = postcard::from_bytes(bytes).unwrap();
+            assert_eq!(postcard::to_stdvec(&decoded).unwrap(), bytes);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_verify_and_decode_never_panics(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            let _ = SignedMessage::verify_and_decode(&bytes);
+        }
+
+        #[test]
+        fn test_signed_message_round_trip(seed in any::<[u8; 32]>(), message in arb_message()) {
+            let secret_key = SecretKey::from_bytes(&seed);
+            let encoded = SignedMessage::sign_and_encode(&secret_key, &message).unwrap();
+            let (from, decoded) = SignedMessage::verify_and_decode(&encoded).unwrap();
+            prop_assert_eq!(from, secret_key.public());
+            prop_assert_eq!(
+                postcard::to_stdvec(&decoded).unwrap(),
+                postcard::to_stdvec(&message).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_signed_message_wire_format(seed in any::<[u8; 32]>(), message in arb_message()) {
+            let secret_key = SecretKey::from_bytes(&seed);
+            let encoded = SignedMessage::sign_and_encode(&secret_key, &message).unwrap();
+            // 借用编码的结果与按 SignedMessage 编码的结果相同
+            let owned: SignedMessage = postcard::from_bytes(&encoded).unwrap();
+            prop_assert_eq!(postcard::to_stdvec(&owned).unwrap(), encoded.to_vec());
+
+            // 验证后的消息内容与输入共享内存
+            let (_, version, payload) = SignedMessage::verify(&encoded).unwrap();
+            prop_assert_eq!(version, PROTOCOL_VERSION);
+            let range = encoded.as_ptr_range();
+            prop_assert!(range.contains(&payload.as_ptr()));
+        }
+
+        #[test]
+        fn test_tampered_message_rejected(
+            seed in any::<[u8; 32]>(),
+            message in arb_message(),
+            index in any::<prop::sample::Index>(),
+            bit in 0u8..8,
+        ) {
+            let secret_key = SecretKey::from_bytes(&seed);
+            let mut encoded = SignedMessage::sign_and_encode(&secret_key, &message).unwrap().to_vec();
+            let index = index.index(encoded.len());
+            encoded[index] ^= 1 << bit;
+            prop_assert!(SignedMessage::verify_and_decode(&encoded).is_err());
+        }
+
+        #[test]
+        fn test_ticket_from_str_never_panics(text in "\\PC*") {
+            let _ = Ticket::from_str(&text);
+        }
+
+        #[test]
+        fn test_ticket_from_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+            let text = data_encoding::BASE32_NOPAD.encode(&bytes);
+            let _ = Ticket::from_str(&text);
+        }
+
+        #[test]
+        fn test_compact_ticket_from_str_never_panics(text in "1[1-9A-HJ-NP-Za-km-z]{0,128}") {
+            let _ = Ticket::from_str(&text);
+        }
+
+        #[test]
+        fn test_ticket_round_trip(topic in any::<[u8; 32]>()) {
+            let ticket = Ticket {
+                topic: TopicId::from_bytes(topic),
+                peers: Vec::new(),
+            };
+            let parsed = Ticket::from_str(&ticket.to_string()).unwrap();
+            prop_assert_eq!(parsed.topic, ticket.topic);
+            prop_assert!(parsed.peers.is_empty());
+        }
+    }
+}
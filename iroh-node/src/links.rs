@@ -0,0 +1,157 @@
+//! 聊天消息中的链接
+//!
+//! 从消息文本中提取链接，链接预览由网关统一抓取后以 [`crate::NodeEvent::LinkPreviewReady`]
+//! 推送给客户端，客户端不必各自访问不可信的地址
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// 链接预览
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    /// 消息中的链接
+    pub url: String,
+    /// 跟随重定向后的最终地址
+    pub final_url: String,
+    /// 标题（`og:title`，没有时取 `<title>`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// 描述（`og:description` 或 `description`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// 站点名称（`og:site_name`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+    /// 图片地址（`og:image`），未经抓取，客户端可以选择不加载
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// 抓取时间
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// 链接末尾不属于地址的标点
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}', '\''];
+
+/// 提取文本中的 http/https 链接，按出现顺序去重，最多 `limit` 个
+pub fn extract_links(text: &str, limit: usize) -> Vec<Url> {
+    let mut links: Vec<Url> = Vec::new();
+    let mut rest = text;
+    while links.len() < limit {
+        let Some(start) = [rest.find("http://"), rest.find("https://")]
+            .into_iter()
+            .flatten()
+            .min()
+        else {
+            break;
+        };
+        let candidate = &rest[start..];
+        // 中文等非ASCII字符常紧跟在链接后，视为链接结束
+        let end = candidate
+            .find(|c: char| !c.is_ascii_graphic() || matches!(c, '<' | '>' | '`' | '"'))
+            .unwrap_or(candidate.len());
+        let token = candidate[..end].trim_end_matches(TRAILING_PUNCTUATION);
+        rest = &candidate[end..];
+
+        let Ok(url) = Url::parse(token) else {
+            continue;
+        };
+        if url.host_str().is_some() && !links.contains(&url) {
+            links.push(url);
+        }
+    }
+    links
+}
+
+/// 是否为公网地址；回环、内网、链路本地、文档保留等地址不允许抓取，防止借网关访问内部服务
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8
+        || a == 0
+        // 运营商级NAT 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF协议分配 192.0.0.0/24
+        || (a == 192 && b == 0 && c == 0)
+        // 基准测试 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // 保留 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // 唯一本地地址 fc00::/7
+        || (segments[0] & 0xfe00) == 0xfc00
+        // 链路本地 fe80::/10
+        || (segments[0] & 0xffc0) == 0xfe80
+        // 文档 2001:db8::/32
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+        // NAT64 64:ff9b::/96 按内嵌的IPv4地址判断
+        || (segments[0] == 0x0064
+            && segments[1] == 0xff9b
+            && segments[2..6] == [0; 4]
+            && !is_public_ipv4(Ipv4Addr::new(
+                (segments[6] >> 8) as u8,
+                segments[6] as u8,
+                (segments[7] >> 8) as u8,
+                segments[7] as u8,
+            ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        let links = extract_links(
+            "看这个 https://example.com/a?b=1。还有(http://example.org/x) 和 https://example.com/a?b=1 ftp://x",
+            5,
+        );
+        let links: Vec<&str> = links.iter().map(Url::as_str).collect();
+        assert_eq!(links, ["https://example.com/a?b=1", "http://example.org/x"]);
+        assert_eq!(extract_links("https://a.com https://b.com", 1).len(), 1);
+        assert!(extract_links("http:// 没有主机", 5).is_empty());
+
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:2800:220:1::1".parse().unwrap()));
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! 日志初始化
+//!
+//! 供tauri-app与axum应用共用的日志初始化：输出到标准输出，写入进程内的 [`LogBuffer`]，
+//! 并可选地写入按天或按大小滚动的日志文件，只保留最近的若干个文件
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{
+    filter::LevelFilter, fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+use crate::{
+    error::{NodeError, NodeResult},
+    logs::LogBuffer,
+};
+
+/// 日志文件扩展名
+const LOG_EXTENSION: &str = "log";
+
+/// 日志文件滚动方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogRotation {
+    /// 每天（UTC）滚动一次
+    #[default]
+    Daily,
+    /// 文件超过指定字节数时滚动
+    Size {
+        /// 单个文件的最大字节数
+        max_bytes: u64,
+    },
+    /// 不滚动
+    Never,
+}
+
+/// 文件日志配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileLogConfig {
+    /// 日志目录，未设置时使用应用提供的默认目录
+    pub directory: Option<PathBuf>,
+    /// 文件名前缀，当前文件为 `<prefix>.log`
+    pub prefix: String,
+    /// 滚动方式
+    pub rotation: LogRotation,
+    /// 保留的历史文件数量（不含当前文件）
+    pub max_files: usize,
+}
+
+impl Default for FileLogConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            prefix: "iroh-agent".to_string(),
+            rotation: LogRotation::default(),
+            max_files: 7,
+        }
+    }
+}
+
+/// 日志配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// 最低级别
+    pub level: String,
+    /// 文件日志，未设置时只输出到标准输出
+    pub file: Option<FileLogConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// 从JSON配置文件加载，文件不存在时使用默认配置
+    pub fn load(path: &Path) -> NodeResult<Self> {
+        match fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| {
+                NodeError::ConfigError(format!("解析日志配置 {} 失败: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(NodeError::IoError(format!(
+                "读取日志配置 {} 失败: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// 设置文件日志的默认目录，配置中已指定目录时不生效
+    pub fn with_default_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        if let Some(file) = &mut self.file {
+            file.directory.get_or_insert_with(|| directory.into());
+        }
+        self
+    }
+}
+
+/// 按配置初始化全局日志，只能调用一次
+pub fn init_logging(config: &LoggingConfig) -> NodeResult<()> {
+    let level = LevelFilter::from_str(&config.level)
+        .map_err(|_| NodeError::ConfigError(format!("无效的日志级别: {}", config.level)))?;
+    let file_layer = config
+        .file
+        .as_ref()
+        .map(|file| {
+            RollingFileWriter::new(file).map(|writer| {
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(writer)
+            })
+        })
+        .transpose()?;
+
+    tracing_subscriber::registry()
+        .with(level)
+        .with(tracing_subscriber::fmt::layer())
+        .with(file_layer)
+        .with(LogBuffer::global().layer())
+        .try_init()
+        .map_err(|e| NodeError::ConfigError(format!("初始化日志失败: {}", e)))
+}
+
+/// 滚动写入日志文件
+pub struct RollingFileWriter {
+    state: Mutex<RollingState>,
+}
+
+struct RollingState {
+    directory: PathBuf,
+    prefix: String,
+    rotation: LogRotation,
+    max_files: usize,
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+}
+
+impl RollingFileWriter {
+    /// 打开日志目录下的当前文件，目录不存在时创建
+    pub fn new(config: &FileLogConfig) -> NodeResult<Self> {
+        let directory = config
+            .directory
+            .clone()
+            .ok_or_else(|| NodeError::ConfigError("未设置日志目录".to_string()))?;
+        fs::create_dir_all(&directory).map_err(|e| {
+            NodeError::IoError(format!("创建日志目录 {} 失败: {}", directory.display(), e))
+        })?;
+
+        let path = active_path(&directory, &config.prefix);
+        let (file, size, opened_on) = open_active(&path).map_err(|e| {
+            NodeError::IoError(format!("打开日志文件 {} 失败: {}", path.display(), e))
+        })?;
+        Ok(Self {
+            state: Mutex::new(RollingState {
+                directory,
+                prefix: config.prefix.clone(),
+                rotation: config.rotation,
+                max_files: config.max_files,
+                file,
+                size,
+                opened_on,
+            }),
+        })
+    }
+}
+
+impl RollingState {
+    fn should_rotate(&self, incoming: usize) -> bool {
+        match self.rotation {
+            LogRotation::Daily => self.opened_on != Utc::now().date_naive(),
+            LogRotation::Size { max_bytes } => {
+                self.size > 0 && self.size + incoming as u64 > max_bytes
+            }
+            LogRotation::Never => false,
+        }
+    }
+
+    /// 将当前文件改名为带时间戳的历史文件，打开新文件并清理多余的历史文件
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let active = active_path(&self.directory, &self.prefix);
+        let timestamp = Utc::now().format("%Y%m%d-%H%M%S%.6f").to_string();
+        let mut rotated = self
+            .directory
+            .join(format!("{}.{}.{}", self.prefix, timestamp, LOG_EXTENSION));
+        // 同名时追加序号；`~` 排在 `.` 之后，保证按名称排序仍是时间顺序
+        let mut suffix = 1;
+        while rotated.exists() {
+            rotated = self.directory.join(format!(
+                "{}.{}~{}.{}",
+                self.prefix, timestamp, suffix, LOG_EXTENSION
+            ));
+            suffix += 1;
+        }
+        fs::rename(&active, &rotated)?;
+
+        let (file, size, opened_on) = open_active(&active)?;
+        self.file = file;
+        self.size = size;
+        self.opened_on = opened_on;
+        self.prune()
+    }
+
+    /// 删除超出保留数量的最旧历史文件
+    fn prune(&self) -> io::Result<()> {
+        let mut rotated = rotated_files(&self.directory, &self.prefix)?;
+        if rotated.len() <= self.max_files {
+            return Ok(());
+        }
+        rotated.sort();
+        let excess = rotated.len() - self.max_files;
+        for path in rotated.into_iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+}
+
+/// 当前日志文件路径
+fn active_path(directory: &Path, prefix: &str) -> PathBuf {
+    directory.join(format!("{}.{}", prefix, LOG_EXTENSION))
+}
+
+/// 以追加方式打开当前日志文件，返回文件、已有大小与最后修改日期
+fn open_active(path: &Path) -> io::Result<(File, u64, NaiveDate)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let metadata = file.metadata()?;
+    let opened_on = metadata
+        .modified()
+        .map(|modified| DateTime::<Utc>::from(modified).date_naive())
+        .unwrap_or_else(|_| Utc::now().date_naive());
+    Ok((file, metadata.len(), opened_on))
+}
+
+/// 日志目录中的历史文件，文件名中的时间戳保证按名称排序即按时间排序
+fn rotated_files(directory: &Path, prefix: &str) -> io::Result<Vec<PathBuf>> {
+    let active = format!("{}.{}", prefix, LOG_EXTENSION);
+    let start = format!("{}.", prefix);
+    let end = format!(".{}", LOG_EXTENSION);
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name != active && name.starts_with(&start) && name.ends_with(&end) {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// 持有锁期间写入日志文件
+pub struct RollingFileGuard<'a>(MutexGuard<'a, RollingState>);
+
+impl Write for RollingFileGuard<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFileWriter {
+    type Writer = RollingFileGuard<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RollingFileGuard(self.state.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_rotation_keeps_max_files() {
+        let directory =
+            std::env::temp_dir().join(format!("iroh-node-logs-{}", rand::random::<u64>()));
+        let config = FileLogConfig {
+            directory: Some(directory.clone()),
+            prefix: "test".to_string(),
+            rotation: LogRotation::Size { max_bytes: 16 },
+            max_files: 2,
+        };
+        let writer = RollingFileWriter::new(&config).unwrap();
+        for line in 0..6 {
+            writer
+                .make_writer()
+                .write_all(format!("line {:08}\n", line).as_bytes())
+                .unwrap();
+        }
+
+        let rotated = rotated_files(&directory, "test").unwrap();
+        assert_eq!(rotated.len(), 2);
+        let active = fs::read_to_string(active_path(&directory, "test")).unwrap();
+        assert_eq!(active, "line 00000005\n");
+
+        let parsed: LoggingConfig = serde_json::from_str(
+            r#"{"level":"debug","file":{"rotation":{"type":"size","max_bytes":1048576}}}"#,
+        )
+        .unwrap();
+        let parsed = parsed.with_default_directory(&directory);
+        let file = parsed.file.unwrap();
+        assert_eq!(file.directory, Some(directory.clone()));
+        assert_eq!(file.max_files, 7);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+//! 日志缓冲
+//!
+//! 提供保留最近若干条日志的环形缓冲区及对应的tracing层，应用安装该层后即可通过
+//! HTTP接口或Tauri命令查看日志，方便在问题反馈中附带日志
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::{self, Write as _},
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::error::{NodeError, NodeResult};
+
+/// 默认保留的日志条数
+pub const DEFAULT_LOG_CAPACITY: usize = 2000;
+
+/// 实时日志订阅的通道容量
+const LOG_STREAM_CAPACITY: usize = 256;
+
+/// 一条日志
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// 序号，单调递增，可用于增量拉取
+    pub seq: u64,
+    /// 时间
+    pub timestamp: DateTime<Utc>,
+    /// 级别（`ERROR`、`WARN`、`INFO`、`DEBUG`、`TRACE`）
+    pub level: String,
+    /// 目标（通常为模块路径）
+    pub target: String,
+    /// 日志内容
+    pub message: String,
+    /// 结构化字段
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:>5} {}: {}",
+            self.timestamp.to_rfc3339(),
+            self.level,
+            self.target,
+            self.message
+        )?;
+        for (name, value) in &self.fields {
+            write!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// 日志查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogQuery {
+    /// 最低级别，例如 `warn` 只返回警告和错误
+    pub level: Option<String>,
+    /// 目标前缀，例如 `iroh_node`
+    pub target: Option<String>,
+    /// 日志内容或字段中包含的文本（不区分大小写）
+    pub contains: Option<String>,
+    /// 只返回序号大于该值的日志
+    pub since: Option<u64>,
+    /// 返回最近的条数
+    pub limit: Option<usize>,
+}
+
+impl LogQuery {
+    /// 解析过滤条件（不含 `since` 与 `limit`）
+    pub(crate) fn filter(&self) -> NodeResult<LogFilter> {
+        let min_level = self
+            .level
+            .as_deref()
+            .map(|level| {
+                Level::from_str(level)
+                    .map_err(|_| NodeError::ConfigError(format!("无效的日志级别: {}", level)))
+            })
+            .transpose()?;
+        Ok(LogFilter {
+            min_level,
+            target: self.target.clone(),
+            contains: self.contains.as_deref().map(str::to_lowercase),
+        })
+    }
+}
+
+/// 解析后的日志过滤条件
+pub(crate) struct LogFilter {
+    min_level: Option<Level>,
+    target: Option<String>,
+    contains: Option<String>,
+}
+
+impl LogFilter {
+    /// 日志是否满足过滤条件
+    pub(crate) fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(min_level) = self.min_level {
+            // tracing中越详细的级别越大
+            match Level::from_str(&entry.level) {
+                Ok(level) if level <= min_level => {}
+                _ => return false,
+            }
+        }
+        if let Some(target) = &self.target {
+            if !entry.target.starts_with(target.as_str()) {
+                return false;
+            }
+        }
+        self.contains.as_deref().is_none_or(|text| {
+            entry.message.to_lowercase().contains(text)
+                || entry
+                    .fields
+                    .values()
+                    .any(|value| value.to_lowercase().contains(text))
+        })
+    }
+}
+
+struct LogRing {
+    capacity: usize,
+    next_seq: u64,
+    entries: VecDeque<LogEntry>,
+}
+
+/// 日志环形缓冲区
+#[derive(Clone)]
+pub struct LogBuffer {
+    ring: Arc<Mutex<LogRing>>,
+    sender: broadcast::Sender<LogEntry>,
+}
+
+impl LogBuffer {
+    /// 创建保留 `capacity` 条日志的缓冲区
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(LOG_STREAM_CAPACITY);
+        Self {
+            ring: Arc::new(Mutex::new(LogRing {
+                capacity: capacity.max(1),
+                next_seq: 1,
+                entries: VecDeque::new(),
+            })),
+            sender,
+        }
+    }
+
+    /// 进程内共享的缓冲区，HTTP接口与Tauri命令从这里读取日志
+    pub fn global() -> &'static LogBuffer {
+        static GLOBAL: OnceLock<LogBuffer> = OnceLock::new();
+        GLOBAL.get_or_init(|| LogBuffer::new(DEFAULT_LOG_CAPACITY))
+    }
+
+    /// 写入该缓冲区的tracing层
+    pub fn layer(&self) -> LogLayer {
+        LogLayer {
+            buffer: self.clone(),
+        }
+    }
+
+    fn push(&self, level: &Level, target: &str, message: String, fields: BTreeMap<String, String>) {
+        let entry = {
+            let mut ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+            let entry = LogEntry {
+                seq: ring.next_seq,
+                timestamp: Utc::now(),
+                level: level.to_string(),
+                target: target.to_string(),
+                message,
+                fields,
+            };
+            ring.next_seq += 1;
+            if ring.entries.len() == ring.capacity {
+                ring.entries.pop_front();
+            }
+            ring.entries.push_back(entry.clone());
+            entry
+        };
+        let _ = self.sender.send(entry);
+    }
+
+    /// 按条件查询日志，按时间升序排列
+    pub fn query(&self, query: &LogQuery) -> NodeResult<Vec<LogEntry>> {
+        let filter = query.filter()?;
+
+        let ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entries: Vec<LogEntry> = ring
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| query.since.is_none_or(|since| entry.seq > since))
+            .filter(|entry| filter.matches(entry))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// 订阅新写入的日志
+    pub fn subscribe(&self) -> broadcast::Receiver<LogEntry> {
+        self.sender.subscribe()
+    }
+
+    /// 只保留满足条件的日志，返回删除的条数；用于按保留期限或调用方删除日志
+    pub fn retain(&self, mut keep: impl FnMut(&LogEntry) -> bool) -> usize {
+        let mut ring = self.ring.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = ring.entries.len();
+        ring.entries.retain(|entry| keep(entry));
+        before - ring.entries.len()
+    }
+
+    /// 清空缓冲区
+    pub fn clear(&self) {
+        self.ring
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .clear();
+    }
+}
+
+/// 将日志导出为纯文本，每行一条
+pub fn logs_to_text(entries: &[LogEntry]) -> String {
+    let mut text = String::new();
+    for entry in entries {
+        let _ = writeln!(text, "{}", entry);
+    }
+    text
+}
+
+/// 把日志写入 [`LogBuffer`] 的tracing层
+pub struct LogLayer {
+    buffer: LogBuffer,
+}
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        self.buffer.push(
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields,
+        );
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_ring_buffer_filters() {
+        let buffer = LogBuffer::new(3);
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "iroh_node::p2p", "节点已启动");
+            tracing::debug!(target: "iroh_node::p2p", peer = "abc", "收到消息");
+            tracing::warn!(target: "rig_agent", "请求超时");
+            tracing::error!(target: "iroh_node::memory", "保存共享记忆失败");
+        });
+
+        let all = buffer.query(&LogQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].seq, 2);
+        assert_eq!(all[0].fields.get("peer").map(String::as_str), Some("abc"));
+
+        let query = LogQuery {
+            level: Some("warn".to_string()),
+            target: Some("iroh_node".to_string()),
+            ..Default::default()
+        };
+        let filtered = buffer.query(&query).unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].message, "保存共享记忆失败");
+
+        let query = LogQuery {
+            contains: Some("ABC".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(buffer.query(&query).unwrap().len(), 1);
+
+        let query = LogQuery {
+            since: Some(3),
+            limit: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(buffer.query(&query).unwrap()[0].seq, 4);
+        assert!(logs_to_text(&all).lines().last().unwrap().contains("ERROR"));
+
+        let query = LogQuery {
+            level: Some("loud".to_string()),
+            ..Default::default()
+        };
+        assert!(buffer.query(&query).is_err());
+
+        assert_eq!(buffer.retain(|entry| entry.target != "rig_agent"), 1);
+        assert_eq!(buffer.query(&LogQuery::default()).unwrap().len(), 2);
+    }
+}
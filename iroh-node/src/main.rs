@@ -2,11 +2,17 @@
 //!
 //! 提供命令行接口，用于管理P2P节点
 
+use std::{path::PathBuf, time::Duration};
+
 use clap::{Parser, Subcommand};
-use iroh_net::relay::RelayUrl;
-use iroh_gossip::proto::topic::TopicId;
-use iroh_node::{NodeConfig, NodeResult, P2PNode};
-use tracing::{error, info};
+use iroh::RelayUrl;
+use iroh_gossip::proto::TopicId;
+use iroh_node::{
+    run_doctor, AgentRequestMode, CommandOutcome, FindingLevel, NodeConfig, NodeError, NodeResult,
+    P2PNode,
+};
+use rig_agent::{KeySource, SecretStore};
+use tracing::{error, info, warn};
 
 /// iroh-node命令行工具
 #[derive(Parser, Debug)]
@@ -16,6 +22,10 @@ struct Args {
     #[clap(long)]
     secret_key: Option<String>,
     
+    /// 加密的密钥存储文件，未指定密钥时从中读取节点密钥，口令取自环境变量 IROH_NODE_PASSPHRASE
+    #[clap(long)]
+    secret_store: Option<PathBuf>,
+    
     /// 中继服务器URL
     #[clap(short, long)]
     relay: Option<RelayUrl>,
@@ -24,6 +34,14 @@ struct Args {
     #[clap(long)]
     no_relay: bool,
     
+    /// 代理URL，例如Tor的 socks5h://127.0.0.1:9050
+    #[clap(long)]
+    proxy: Option<String>,
+    
+    /// 隐私模式：只经中继通信，不在票据中公开本地地址
+    #[clap(long)]
+    privacy: bool,
+    
     /// 节点名称
     #[clap(short, long)]
     name: Option<String>,
@@ -51,7 +69,7 @@ enum Command {
         ticket: Option<String>,
     },
     
-    /// 发送消息
+    /// 发送消息，以 / 开头时按聊天命令执行
     Send {
         /// 话题ID
         #[clap(long)]
@@ -75,10 +93,25 @@ enum Command {
         /// 提示词
         #[clap(long)]
         prompt: String,
+
+        /// 只由指定节点回答
+        #[clap(long, conflicts_with = "quorum")]
+        target: Option<String>,
+
+        /// 收集指定数量的应答并汇总
+        #[clap(long)]
+        quorum: Option<usize>,
+
+        /// 等待应答的秒数
+        #[clap(long, default_value_t = 60)]
+        timeout_secs: u64,
     },
     
     /// 获取节点状态
     Status,
+    
+    /// 检查配置与运行环境，不启动节点
+    Doctor,
 }
 
 #[tokio::main]
@@ -89,15 +122,52 @@ async fn main() -> NodeResult<()> {
     // 解析命令行参数
     let args = Args::parse();
     
+    // 打开密钥存储
+    let secret_store = match &args.secret_store {
+        Some(path) => {
+            let passphrase = std::env::var("IROH_NODE_PASSPHRASE").map_err(|_| {
+                NodeError::ConfigError("使用密钥存储需要设置环境变量 IROH_NODE_PASSPHRASE".to_string())
+            })?;
+            Some(SecretStore::open(path, &KeySource::passphrase(passphrase))?)
+        }
+        None => None,
+    };
+    
     // 创建节点配置
     let config = NodeConfig {
         secret_key: args.secret_key,
+        secret_store,
         relay: args.relay,
         no_relay: args.no_relay,
+        proxy: args.proxy,
+        privacy_mode: args.privacy,
         name: args.name.clone(),
         bind_port: args.bind_port,
+        ..Default::default()
     };
     
+    // 自检只检查配置与环境，不启动节点
+    if matches!(args.command, Some(Command::Doctor)) {
+        let report = run_doctor(&config).await;
+        for finding in &report.findings {
+            let hint = finding
+                .hint
+                .as_deref()
+                .map(|hint| format!("（建议：{}）", hint))
+                .unwrap_or_default();
+            match finding.level {
+                FindingLevel::Ok => info!("[{}] {}", finding.check, finding.message),
+                FindingLevel::Warning => warn!("[{}] {}{}", finding.check, finding.message, hint),
+                FindingLevel::Error => error!("[{}] {}{}", finding.check, finding.message, hint),
+            }
+        }
+        if !report.is_healthy() {
+            return Err(NodeError::ConfigError(format!("自检发现 {} 个问题", report.errors())));
+        }
+        info!("自检通过（{} 个警告）", report.warnings());
+        return Ok(());
+    }
+    
     // 创建P2P节点
     let mut node = P2PNode::new(config).await?;
     
@@ -117,18 +187,39 @@ async fn main() -> NodeResult<()> {
             info!("票据: {}", ticket);
         }
         Some(Command::Send { topic_id, message }) => {
-            let message = iroh_node::MessageType::Chat { text: message };
-            node.send_message(&topic_id, message).await?;
-            info!("消息已发送");
+            let outcome = node.send_input(&topic_id, &message).await?;
+            report_outcome(&outcome);
         }
-        Some(Command::Agent { topic_id, agent_id, prompt }) => {
-            node.send_agent_request(&topic_id, &agent_id, &prompt).await?;
-            info!("Agent请求已发送");
+        Some(Command::Agent { topic_id, agent_id, prompt, target, quorum, timeout_secs }) => {
+            let mode = match (target, quorum) {
+                (Some(node_id), _) => AgentRequestMode::Targeted { node_id },
+                (None, Some(count)) => AgentRequestMode::Quorum { count },
+                (None, None) => AgentRequestMode::Broadcast,
+            };
+            let handle = node
+                .send_agent_request(&topic_id, &agent_id, &prompt, mode, Some(Duration::from_secs(timeout_secs)))
+                .await?;
+            info!("Agent请求已发送: {}", handle.request_id());
+
+            let outcome = handle.wait().await?;
+            if outcome.timed_out {
+                error!("Agent请求超时，收到 {} 个应答", outcome.answers.len());
+            }
+            for answer in &outcome.answers {
+                match (&answer.content, &answer.error) {
+                    (Some(content), _) => info!("{} 的应答: {}", answer.from, content),
+                    (None, error) => error!("{} 处理失败: {}", answer.from, error.as_deref().unwrap_or_default()),
+                }
+            }
+            if let Some(consensus) = outcome.consensus {
+                info!("多数意见（{} 个节点一致）: {}", outcome.agreement, consensus);
+            }
         }
         Some(Command::Status) => {
             let status = node.get_status().await;
             info!("节点状态: {:?}", status);
         }
+        Some(Command::Doctor) => unreachable!("自检在创建节点前已处理"),
         None => {
             // 如果没有子命令，则创建一个新话题
             let (topic, ticket) = node.join_topic(None, None).await?;
@@ -137,7 +228,7 @@ async fn main() -> NodeResult<()> {
             info!("票据: {}", ticket);
             
             // 等待用户输入
-            info!("输入消息发送到话题，输入'exit'退出");
+            info!("输入消息发送到话题，输入 /help 查看命令，输入'exit'退出");
             let mut input = String::new();
             while input.trim() != "exit" {
                 input.clear();
@@ -146,10 +237,10 @@ async fn main() -> NodeResult<()> {
                     continue;
                 }
                 
-                if input.trim() != "exit" {
-                    let message = iroh_node::MessageType::Chat { text: input.trim().to_string() };
-                    if let Err(e) = node.send_message(&topic, message).await {
-                        error!("发送消息失败: {}", e);
+                if input.trim() != "exit" && !input.trim().is_empty() {
+                    match node.send_input(&topic, input.trim()).await {
+                        Ok(outcome) => report_outcome(&outcome),
+                        Err(e) => error!("{}", e),
                     }
                 }
             }
@@ -160,4 +251,15 @@ async fn main() -> NodeResult<()> {
     node.stop().await?;
     
     Ok(())
+}
+
+/// 输出聊天输入的执行结果
+fn report_outcome(outcome: &CommandOutcome) {
+    match outcome {
+        CommandOutcome::Sent { .. } => info!("消息已发送"),
+        CommandOutcome::AgentRequested { request_id } => info!("Agent请求已发送: {}", request_id),
+        CommandOutcome::Share { path } => error!("命令行不支持传输文件，无法分享 {}", path.display()),
+        CommandOutcome::Renamed { label } => info!("房间已重命名为: {}", label),
+        CommandOutcome::Reply { text } => info!("{}", text),
+    }
 }
\ No newline at end of file
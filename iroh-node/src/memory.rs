@@ -0,0 +1,502 @@
+//! 共享向量记忆
+//!
+//! 房间成员把重要事实连同嵌入向量写入共享记忆，各节点的Agent处理请求时按相似度检索房间的共同知识。
+//! 记忆以话题为单位，按文档的方式在话题中复制：每条记录由作者签名，只有作者可以修改或删除；
+//! 合并时版本号大者胜出，版本号相同时按签名字节比较，删除以墓碑表示。
+//! 合并满足交换律、结合律与幂等性，各节点以任意顺序收到记录后状态一致。
+//! 新节点加入话题时互相发送已知版本，对方补发缺失或更新的记录
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use iroh::{PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    chat::new_message_id,
+    error::{NodeError, NodeResult},
+};
+
+/// 单条记忆文本的最大字节数
+const MAX_MEMORY_TEXT: usize = 8 * 1024;
+
+/// 嵌入向量的最大维度
+const MAX_EMBEDDING_DIMS: usize = 4096;
+
+/// 未指定时检索返回的条数，供各适配器使用
+#[cfg(any(feature = "axum-adapter", feature = "tauri-plugin"))]
+pub(crate) const DEFAULT_RECALL_LIMIT: usize = 5;
+
+/// 单条同步消息中记录的最大编码字节数
+pub(crate) const MAX_UPDATE_BYTES: usize = 256 * 1024;
+
+/// 共享记忆配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedMemoryConfig {
+    /// 记忆文件路径，未设置时只保存在内存中
+    pub path: Option<PathBuf>,
+    /// 计算嵌入向量的提供商
+    pub embedding_provider: String,
+    /// 嵌入模型，只有相同模型的向量之间才会比较
+    pub embedding_model: String,
+    /// 处理Agent请求时附加的记忆条数，0 表示不附加
+    pub recall_limit: usize,
+    /// 附加到提示词的最低相似度
+    pub min_score: f32,
+}
+
+impl Default for SharedMemoryConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            embedding_provider: "openai".to_string(),
+            embedding_model: "text-embedding-3-small".to_string(),
+            recall_limit: 3,
+            min_score: 0.3,
+        }
+    }
+}
+
+/// 一条共享记忆
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    /// 记录ID
+    pub id: String,
+    /// 所属话题ID
+    pub topic_id: String,
+    /// 作者节点ID
+    pub author: String,
+    /// 记忆内容
+    pub text: String,
+    /// 来源，例如聊天消息ID或Agent ID
+    pub source: Option<String>,
+    /// 嵌入模型
+    pub model: String,
+    /// 嵌入向量，已删除的记录为空
+    pub embedding: Vec<f32>,
+    /// 版本号，作者每次修改加一
+    pub version: u64,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+    /// 最后修改时间
+    pub updated_at: DateTime<Utc>,
+    /// 是否已删除
+    pub deleted: bool,
+    /// 作者对以上字段的签名
+    pub signature: Signature,
+}
+
+/// 参与签名的字段
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    id: &'a str,
+    topic_id: &'a str,
+    author: &'a str,
+    text: &'a str,
+    source: Option<&'a str>,
+    model: &'a str,
+    embedding: &'a [f32],
+    version: u64,
+    created_at: i64,
+    updated_at: i64,
+    deleted: bool,
+}
+
+impl MemoryEntry {
+    fn signed_bytes(&self) -> NodeResult<Vec<u8>> {
+        postcard::to_stdvec(&SignedFields {
+            id: &self.id,
+            topic_id: &self.topic_id,
+            author: &self.author,
+            text: &self.text,
+            source: self.source.as_deref(),
+            model: &self.model,
+            embedding: &self.embedding,
+            version: self.version,
+            created_at: self.created_at.timestamp_micros(),
+            updated_at: self.updated_at.timestamp_micros(),
+            deleted: self.deleted,
+        })
+        .map_err(|e| NodeError::EncodeError(format!("编码记忆失败: {}", e)))
+    }
+
+    fn sign(mut self, secret_key: &SecretKey) -> NodeResult<Self> {
+        self.signature = secret_key.sign(&self.signed_bytes()?);
+        Ok(self)
+    }
+
+    /// 校验作者签名
+    pub fn verify(&self) -> NodeResult<()> {
+        let author: PublicKey = self
+            .author
+            .parse()
+            .map_err(|e| NodeError::VerifyError(format!("解析记忆作者失败: {}", e)))?;
+        author
+            .verify(&self.signed_bytes()?, &self.signature)
+            .map_err(|e| NodeError::VerifyError(format!("验证记忆签名失败: {}", e)))
+    }
+
+    /// 检查字段大小与格式
+    pub(crate) fn validate(&self, max_id: usize) -> NodeResult<()> {
+        if self.id.is_empty() || self.id.len() > max_id {
+            return Err(NodeError::InvalidMessage(format!(
+                "记忆ID无效: {}",
+                self.id
+            )));
+        }
+        self.check_content()
+    }
+
+    fn check_content(&self) -> NodeResult<()> {
+        let invalid = |reason: String| Err(NodeError::InvalidMessage(reason));
+        if self.text.len() > MAX_MEMORY_TEXT {
+            return invalid(format!("记忆内容过长: {} 字节", self.text.len()));
+        }
+        if self.embedding.len() > MAX_EMBEDDING_DIMS
+            || self.embedding.iter().any(|value| !value.is_finite())
+        {
+            return invalid("嵌入向量无效".to_string());
+        }
+        Ok(())
+    }
+
+    /// 合并时是否应当替换 `other`
+    fn supersedes(&self, other: &Self) -> bool {
+        (self.version, self.signature.to_bytes()) > (other.version, other.signature.to_bytes())
+    }
+}
+
+/// 记录的版本，用于同步时比较
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryVersion {
+    /// 记录ID
+    pub id: String,
+    /// 版本号
+    pub version: u64,
+}
+
+/// 检索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryMatch {
+    /// 记忆
+    pub entry: MemoryEntry,
+    /// 与查询的余弦相似度
+    pub score: f32,
+}
+
+/// 各话题的共享记忆，设置了路径时每次变更后写回文件
+#[derive(Debug, Default)]
+pub struct SharedMemory {
+    path: Option<PathBuf>,
+    topics: HashMap<String, HashMap<String, MemoryEntry>>,
+}
+
+impl SharedMemory {
+    /// 从文件加载，文件不存在时为空
+    pub fn load(path: Option<&Path>) -> NodeResult<Self> {
+        let mut memory = Self {
+            path: path.map(Path::to_path_buf),
+            topics: HashMap::new(),
+        };
+        if let Some(path) = path.filter(|path| path.exists()) {
+            let data = fs::read(path)?;
+            let entries: Vec<MemoryEntry> = serde_json::from_slice(&data)
+                .map_err(|e| NodeError::DecodeError(format!("解析共享记忆失败: {}", e)))?;
+            for entry in entries {
+                memory.insert(entry);
+            }
+        }
+        Ok(memory)
+    }
+
+    fn save(&self) -> NodeResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let entries: Vec<_> = self.topics.values().flat_map(HashMap::values).collect();
+        let data = serde_json::to_vec(&entries)
+            .map_err(|e| NodeError::EncodeError(format!("序列化共享记忆失败: {}", e)))?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// 保存失败只记录日志，不影响消息处理
+    fn save_logged(&self) {
+        if let Err(e) = self.save() {
+            warn!("保存共享记忆失败: {}", e);
+        }
+    }
+
+    fn insert(&mut self, entry: MemoryEntry) -> bool {
+        let entries = self.topics.entry(entry.topic_id.clone()).or_default();
+        match entries.get(&entry.id) {
+            Some(existing) if !entry.supersedes(existing) => false,
+            _ => {
+                entries.insert(entry.id.clone(), entry);
+                true
+            }
+        }
+    }
+
+    /// 合并其他节点的记录，返回是否产生了变化。调用前需校验签名
+    pub fn merge(&mut self, entry: MemoryEntry) -> bool {
+        let changed = self.insert(entry);
+        if changed {
+            self.save_logged();
+        }
+        changed
+    }
+
+    /// 话题中未删除的记忆，按修改时间倒序
+    pub fn list(&self, topic_id: &str) -> Vec<MemoryEntry> {
+        let mut entries: Vec<_> = self
+            .topics
+            .get(topic_id)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|entry| !entry.deleted)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated_at));
+        entries
+    }
+
+    /// 话题中所有记录的版本，包括墓碑
+    pub fn versions(&self, topic_id: &str) -> Vec<MemoryVersion> {
+        self.topics
+            .get(topic_id)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .map(|entry| MemoryVersion {
+                id: entry.id.clone(),
+                version: entry.version,
+            })
+            .collect()
+    }
+
+    /// 对方缺失或版本较旧的记录
+    pub fn missing_for(&self, topic_id: &str, known: &[MemoryVersion]) -> Vec<MemoryEntry> {
+        let known: HashMap<&str, u64> = known
+            .iter()
+            .map(|version| (version.id.as_str(), version.version))
+            .collect();
+        self.topics
+            .get(topic_id)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|entry| {
+                known
+                    .get(entry.id.as_str())
+                    .is_none_or(|version| entry.version > *version)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 写入本节点的新记忆
+    pub(crate) fn write(
+        &mut self,
+        secret_key: &SecretKey,
+        topic_id: &str,
+        text: String,
+        source: Option<String>,
+        model: String,
+        embedding: Vec<f32>,
+    ) -> NodeResult<MemoryEntry> {
+        let now = Utc::now();
+        let entry = MemoryEntry {
+            id: new_message_id(),
+            topic_id: topic_id.to_string(),
+            author: secret_key.public().to_string(),
+            text,
+            source,
+            model,
+            embedding,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            deleted: false,
+            signature: Signature::from_bytes(&[0; 64]),
+        }
+        .sign(secret_key)?;
+        entry.check_content()?;
+        self.insert(entry.clone());
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// 删除本节点写入的记忆，返回墓碑
+    pub(crate) fn forget(
+        &mut self,
+        secret_key: &SecretKey,
+        topic_id: &str,
+        id: &str,
+    ) -> NodeResult<MemoryEntry> {
+        let existing = self
+            .topics
+            .get(topic_id)
+            .and_then(|entries| entries.get(id))
+            .filter(|entry| !entry.deleted)
+            .ok_or_else(|| NodeError::ConfigError(format!("共享记忆中没有记录: {}", id)))?;
+        if existing.author != secret_key.public().to_string() {
+            return Err(NodeError::ConfigError(
+                "只能删除本节点写入的记忆".to_string(),
+            ));
+        }
+
+        let tombstone = MemoryEntry {
+            text: String::new(),
+            embedding: Vec::new(),
+            version: existing.version + 1,
+            updated_at: Utc::now(),
+            deleted: true,
+            ..existing.clone()
+        }
+        .sign(secret_key)?;
+        self.insert(tombstone.clone());
+        self.save()?;
+        Ok(tombstone)
+    }
+
+    /// 按余弦相似度检索，只比较相同嵌入模型的记录
+    pub fn recall(
+        &self,
+        topic_id: &str,
+        model: &str,
+        query: &[f32],
+        limit: usize,
+        min_score: f32,
+    ) -> Vec<MemoryMatch> {
+        let mut matches: Vec<_> = self
+            .topics
+            .get(topic_id)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|entry| !entry.deleted && entry.model == model)
+            .filter_map(|entry| {
+                let score = cosine_similarity(query, &entry.embedding)?;
+                (score >= min_score).then(|| MemoryMatch {
+                    entry: entry.clone(),
+                    score,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// 按编码大小把记录分成多条同步消息
+pub(crate) fn chunk_entries(entries: Vec<MemoryEntry>) -> Vec<Vec<MemoryEntry>> {
+    let mut chunks = Vec::new();
+    let mut current = Vec::new();
+    let mut current_bytes = 0;
+    for entry in entries {
+        let bytes = postcard::to_stdvec(&entry).map_or(MAX_UPDATE_BYTES, |bytes| bytes.len());
+        if !current.is_empty() && current_bytes + bytes > MAX_UPDATE_BYTES {
+            chunks.push(std::mem::take(&mut current));
+            current_bytes = 0;
+        }
+        current_bytes += bytes;
+        current.push(entry);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 把检索到的记忆附加到提示词前
+pub(crate) fn with_context(prompt: &str, matches: &[MemoryMatch]) -> String {
+    if matches.is_empty() {
+        return prompt.to_string();
+    }
+    let mut text = String::from("以下是房间共享记忆中与问题相关的内容，仅供参考：\n");
+    for item in matches {
+        text.push_str(&format!("- {}\n", item.entry.text));
+    }
+    text.push_str("\n问题：\n");
+    text.push_str(prompt);
+    text
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f32> {
+    if a.len() != b.len() || a.is_empty() {
+        return None;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return None;
+    }
+    Some(dot / (norm_a * norm_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_is_order_independent() {
+        let alice = SecretKey::from_bytes(&[1; 32]);
+        let bob = SecretKey::from_bytes(&[2; 32]);
+        let model = "test-model".to_string();
+
+        let mut origin = SharedMemory::default();
+        let fact = origin
+            .write(
+                &alice,
+                "room",
+                "部署在周五".to_string(),
+                None,
+                model.clone(),
+                vec![1.0, 0.0],
+            )
+            .unwrap();
+        let other = origin
+            .write(
+                &bob,
+                "room",
+                "数据库是Postgres".to_string(),
+                None,
+                model.clone(),
+                vec![0.0, 1.0],
+            )
+            .unwrap();
+        let tombstone = origin.forget(&alice, "room", &fact.id).unwrap();
+        assert!(origin.forget(&alice, "room", &other.id).is_err());
+        assert!(tombstone.verify().is_ok());
+
+        // 不同顺序、重复投递后状态一致
+        let mut forward = SharedMemory::default();
+        for entry in [&fact, &other, &tombstone, &fact] {
+            forward.merge(entry.clone());
+        }
+        let mut backward = SharedMemory::default();
+        for entry in [&tombstone, &other, &fact] {
+            backward.merge(entry.clone());
+        }
+        assert_eq!(forward.list("room"), backward.list("room"));
+        assert_eq!(forward.list("room"), vec![other.clone()]);
+        assert!(forward
+            .missing_for("room", &backward.versions("room"))
+            .is_empty());
+
+        let matches = forward.recall("room", &model, &[0.1, 0.9], 5, 0.5);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].entry.author, bob.public().to_string());
+
+        let mut forged = other.clone();
+        forged.text = "数据库是MySQL".to_string();
+        assert!(forged.verify().is_err());
+    }
+}
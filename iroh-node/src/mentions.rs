@@ -0,0 +1,270 @@
+//! @提及与通知
+//!
+//! 聊天消息中的 `@名称` 按房间成员名单解析为节点ID，也可以直接写节点ID的前缀。
+//! 每个成员被提及的消息记录在提及索引中，本节点被提及时发出事件，
+//! 通知接口据此给出未读提及数并支持标记已读
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 每个成员保留的提及记录条数
+const MENTIONS_PER_USER: usize = 200;
+
+/// 按节点ID前缀提及时前缀的最短长度，避免误匹配
+const MIN_ID_PREFIX: usize = 8;
+
+/// 提及记录中消息摘要的最大字符数
+const SNIPPET_CHARS: usize = 140;
+
+/// 房间成员：节点ID与名称
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RoomMember {
+    pub node_id: String,
+    pub name: Option<String>,
+}
+
+/// 一次提及
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MentionRecord {
+    /// 话题ID
+    pub topic_id: String,
+    /// 消息ID
+    pub message_id: String,
+    /// 发送者节点ID
+    pub from: String,
+    /// 被提及的节点ID
+    pub mentioned: String,
+    /// 消息摘要
+    pub snippet: String,
+    /// 消息时间
+    pub timestamp: DateTime<Utc>,
+    /// 是否已读
+    pub read: bool,
+}
+
+/// 未读提及汇总
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MentionSummary {
+    /// 未读提及总数
+    pub unread: usize,
+    /// 各话题的未读提及数，没有未读的话题不列出
+    pub topics: BTreeMap<String, usize>,
+}
+
+/// 解析消息中的@提及，返回被提及的节点ID（按出现顺序去重）
+///
+/// 名称按最长匹配，名称后紧跟ASCII字母或数字时不算提及（`@bob` 不匹配 `@bobby`）；
+/// 没有匹配的名称时，`@` 后至少 8 个字符且只匹配一个成员的节点ID前缀也算提及。
+/// 前面紧跟ASCII字母或数字的 `@`（如邮箱地址）不算提及，中文后直接写 `@` 仍算提及
+pub(crate) fn parse_mentions(text: &str, members: &[RoomMember]) -> Vec<String> {
+    let mut mentioned: Vec<String> = Vec::new();
+    let mut previous: Option<char> = None;
+    for (index, c) in text.char_indices() {
+        let at_boundary = previous.is_none_or(|p| !p.is_ascii_alphanumeric());
+        previous = Some(c);
+        if c != '@' || !at_boundary {
+            continue;
+        }
+        let rest = &text[index + 1..];
+        if let Some(node_id) = match_name(rest, members).or_else(|| match_id_prefix(rest, members))
+        {
+            if !mentioned.contains(&node_id) {
+                mentioned.push(node_id);
+            }
+        }
+    }
+    mentioned
+}
+
+/// 以成员名称开头时返回该成员，多个名称匹配时取最长的
+fn match_name(rest: &str, members: &[RoomMember]) -> Option<String> {
+    members
+        .iter()
+        .filter_map(|member| {
+            let name = member.name.as_deref().filter(|name| !name.is_empty())?;
+            let candidate = rest.get(..name.len())?;
+            let followed_by_word = rest[name.len()..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphanumeric());
+            (candidate.to_lowercase() == name.to_lowercase() && !followed_by_word)
+                .then_some((name.len(), &member.node_id))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, node_id)| node_id.clone())
+}
+
+/// `@` 后的词是唯一一个成员节点ID的前缀时返回该成员
+fn match_id_prefix(rest: &str, members: &[RoomMember]) -> Option<String> {
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    let prefix = rest[..end].to_ascii_lowercase();
+    if prefix.len() < MIN_ID_PREFIX {
+        return None;
+    }
+    let mut matches = members
+        .iter()
+        .filter(|member| member.node_id.starts_with(&prefix));
+    let member = matches.next()?;
+    matches.next().is_none().then(|| member.node_id.clone())
+}
+
+/// 消息摘要
+pub(crate) fn snippet(text: &str) -> String {
+    let mut snippet: String = text.chars().take(SNIPPET_CHARS).collect();
+    if text.chars().nth(SNIPPET_CHARS).is_some() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// 各成员被提及的记录，最新的在前
+#[derive(Debug, Default)]
+pub(crate) struct MentionIndex {
+    users: HashMap<String, VecDeque<MentionRecord>>,
+}
+
+impl MentionIndex {
+    /// 记录一次提及，同一消息重复提及时忽略
+    pub fn record(&mut self, record: MentionRecord) -> bool {
+        let records = self.users.entry(record.mentioned.clone()).or_default();
+        if records
+            .iter()
+            .any(|r| r.topic_id == record.topic_id && r.message_id == record.message_id)
+        {
+            return false;
+        }
+        if records.len() >= MENTIONS_PER_USER {
+            records.pop_back();
+        }
+        records.push_front(record);
+        true
+    }
+
+    /// 成员被提及的记录，可以只看某个话题或只看未读
+    pub fn list(
+        &self,
+        node_id: &str,
+        topic_id: Option<&str>,
+        unread_only: bool,
+    ) -> Vec<MentionRecord> {
+        self.users
+            .get(node_id)
+            .into_iter()
+            .flatten()
+            .filter(|r| topic_id.is_none_or(|topic_id| r.topic_id == topic_id))
+            .filter(|r| !unread_only || !r.read)
+            .cloned()
+            .collect()
+    }
+
+    /// 成员的未读提及汇总
+    pub fn summary(&self, node_id: &str) -> MentionSummary {
+        let mut summary = MentionSummary::default();
+        for record in self.users.get(node_id).into_iter().flatten() {
+            if !record.read {
+                summary.unread += 1;
+                *summary.topics.entry(record.topic_id.clone()).or_default() += 1;
+            }
+        }
+        summary
+    }
+
+    /// 标记提及为已读；不指定话题时标记全部，不指定消息时标记话题中的全部，返回新标记的条数
+    pub fn mark_read(
+        &mut self,
+        node_id: &str,
+        topic_id: Option<&str>,
+        message_id: Option<&str>,
+    ) -> usize {
+        let mut marked = 0;
+        for record in self.users.get_mut(node_id).into_iter().flatten() {
+            let matches = topic_id.is_none_or(|topic_id| record.topic_id == topic_id)
+                && message_id.is_none_or(|message_id| record.message_id == message_id);
+            if matches && !record.read {
+                record.read = true;
+                marked += 1;
+            }
+        }
+        marked
+    }
+
+    /// 离开话题时清除该话题的提及
+    pub fn remove_topic(&mut self, topic_id: &str) {
+        for records in self.users.values_mut() {
+            records.retain(|r| r.topic_id != topic_id);
+        }
+        self.users.retain(|_, records| !records.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(node_id: &str, name: Option<&str>) -> RoomMember {
+        RoomMember {
+            node_id: node_id.to_string(),
+            name: name.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_mentions() {
+        let members = [
+            member("aaaa1111bbbb2222", Some("Bob")),
+            member("cccc3333dddd4444", Some("Bobby")),
+            member("eeee5555ffff6666", Some("张三")),
+            member("aaaa1111ffff0000", None),
+        ];
+        assert_eq!(
+            parse_mentions("@bob 和 @Bobby，还有@张三你好 @bob", &members),
+            ["aaaa1111bbbb2222", "cccc3333dddd4444", "eeee5555ffff6666"]
+        );
+        // 邮箱地址、不完整的名称与有歧义的ID前缀不算提及
+        assert!(parse_mentions("bob@bob.com @bo @aaaa1111", &members).is_empty());
+        assert_eq!(
+            parse_mentions("@aaaa1111ff 看一下", &members),
+            ["aaaa1111ffff0000"]
+        );
+
+        let mut index = MentionIndex::default();
+        let record = |topic: &str, message: &str| MentionRecord {
+            topic_id: topic.to_string(),
+            message_id: message.to_string(),
+            from: "cccc3333dddd4444".to_string(),
+            mentioned: "aaaa1111bbbb2222".to_string(),
+            snippet: snippet("@bob 你好"),
+            timestamp: Utc::now(),
+            read: false,
+        };
+        assert!(index.record(record("t1", "m1")));
+        assert!(!index.record(record("t1", "m1")));
+        index.record(record("t1", "m2"));
+        index.record(record("t2", "m3"));
+
+        let summary = index.summary("aaaa1111bbbb2222");
+        assert_eq!(summary.unread, 3);
+        assert_eq!(summary.topics["t1"], 2);
+        assert_eq!(
+            index.list("aaaa1111bbbb2222", Some("t1"), true)[0].message_id,
+            "m2"
+        );
+
+        assert_eq!(
+            index.mark_read("aaaa1111bbbb2222", Some("t1"), Some("m1")),
+            1
+        );
+        assert_eq!(index.mark_read("aaaa1111bbbb2222", Some("t1"), None), 1);
+        assert_eq!(index.summary("aaaa1111bbbb2222").unread, 1);
+        index.remove_topic("t2");
+        assert_eq!(index.summary("aaaa1111bbbb2222").unread, 0);
+        assert_eq!(index.list("aaaa1111bbbb2222", None, false).len(), 2);
+
+        let long = "字".repeat(SNIPPET_CHARS + 1);
+        assert!(snippet(&long).ends_with('…'));
+    }
+}
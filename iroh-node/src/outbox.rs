@@ -0,0 +1,272 @@
+//! 出站消息发件箱
+//!
+//! 未启用发件箱时，节点未启动、尚未加入话题或处于离线状态时发出的消息会直接报错丢失。
+//! 启用后这些消息按顺序暂存（设置了路径时写入文件，重启后仍在），话题重新连上后依次补发，
+//! 超过有效期的消息不再发送。每次补发的结果以 `OutboxFlushed` 事件通知
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    chat::new_message_id,
+    error::{NodeError, NodeResult},
+    MessageType,
+};
+
+/// 发件箱配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutboxConfig {
+    /// 发件箱文件路径，未设置时只保存在内存中
+    pub path: Option<PathBuf>,
+    /// 最多暂存的消息数，已满时拒绝新消息
+    pub max_messages: usize,
+    /// 消息的有效期（秒），过期后不再补发
+    pub ttl_secs: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_messages: 1000,
+            ttl_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl OutboxConfig {
+    /// 设置发件箱文件路径
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// 设置最多暂存的消息数
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// 设置消息的有效期（秒）
+    pub fn with_ttl_secs(mut self, ttl_secs: u64) -> Self {
+        self.ttl_secs = ttl_secs;
+        self
+    }
+
+    /// 消息的有效期
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// 暂存的出站消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// 消息ID，聊天消息沿用聊天消息ID
+    pub id: String,
+    /// 话题ID
+    pub topic_id: String,
+    /// 消息内容
+    pub message: MessageType,
+    /// 暂存时间
+    pub queued_at: DateTime<Utc>,
+}
+
+/// 可以暂存补发的消息：聊天消息、表情回应与不关联请求ID的Agent请求。
+/// 确认、在线、协商等消息只在当下有意义，不暂存
+pub(crate) fn is_queueable(message: &MessageType) -> bool {
+    matches!(
+        message,
+        MessageType::Chat { .. }
+            | MessageType::ChatReply { .. }
+            | MessageType::ChatContent { .. }
+            | MessageType::Reaction { .. }
+            | MessageType::AgentRequest { .. }
+            | MessageType::AgentFanOutRequest { .. }
+            | MessageType::System { .. }
+    )
+}
+
+/// 发件箱，设置了路径时每次变更后写回文件
+#[derive(Debug)]
+pub struct Outbox {
+    config: OutboxConfig,
+    entries: VecDeque<OutboxEntry>,
+}
+
+impl Outbox {
+    /// 从文件加载，文件不存在时为空
+    pub fn load(config: OutboxConfig) -> NodeResult<Self> {
+        let entries = match config.path.as_deref().filter(|path| path.exists()) {
+            Some(path) => read_entries(path)?,
+            None => VecDeque::new(),
+        };
+        Ok(Self { config, entries })
+    }
+
+    fn save(&self) -> NodeResult<()> {
+        let Some(path) = &self.config.path else {
+            return Ok(());
+        };
+        let data = serde_json::to_vec(&self.entries)
+            .map_err(|e| NodeError::EncodeError(format!("序列化发件箱失败: {}", e)))?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// 保存失败只记录日志，消息仍在内存中
+    fn save_logged(&self) {
+        if let Err(e) = self.save() {
+            warn!("保存发件箱失败: {}", e);
+        }
+    }
+
+    /// 暂存消息，返回消息ID
+    pub fn push(&mut self, topic_id: &TopicId, message: MessageType) -> NodeResult<String> {
+        if self.entries.len() >= self.config.max_messages {
+            return Err(NodeError::TopicError(format!(
+                "发件箱已满（{} 条），消息未发送",
+                self.config.max_messages
+            )));
+        }
+        let id = match &message {
+            MessageType::Chat { id, .. }
+            | MessageType::ChatReply { id, .. }
+            | MessageType::ChatContent { id, .. } => id.clone(),
+            _ => new_message_id(),
+        };
+        self.entries.push_back(OutboxEntry {
+            id: id.clone(),
+            topic_id: topic_id.to_string(),
+            message,
+            queued_at: Utc::now(),
+        });
+        self.save()?;
+        Ok(id)
+    }
+
+    /// 暂存的消息数
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否没有暂存的消息
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 话题中暂存的消息，按暂存顺序
+    pub fn pending(&self, topic_id: &TopicId) -> Vec<OutboxEntry> {
+        let topic_id = topic_id.to_string();
+        self.entries
+            .iter()
+            .filter(|entry| entry.topic_id == topic_id)
+            .cloned()
+            .collect()
+    }
+
+    /// 取出话题中暂存的全部消息，分为待补发的与已过期的
+    pub(crate) fn take(
+        &mut self,
+        topic_id: &TopicId,
+        now: DateTime<Utc>,
+    ) -> (Vec<OutboxEntry>, Vec<OutboxEntry>) {
+        let topic_id = topic_id.to_string();
+        let (taken, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition::<Vec<_>, _>(|entry| entry.topic_id == topic_id);
+        self.entries = kept.into();
+        if taken.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+        self.save_logged();
+
+        let ttl = chrono::Duration::from_std(self.config.ttl()).unwrap_or(chrono::Duration::MAX);
+        taken
+            .into_iter()
+            .partition(|entry| now.signed_duration_since(entry.queued_at) < ttl)
+    }
+
+    /// 补发失败时把未发出的消息放回队首，保持原有顺序
+    pub(crate) fn restore(&mut self, entries: Vec<OutboxEntry>) {
+        if entries.is_empty() {
+            return;
+        }
+        for entry in entries.into_iter().rev() {
+            self.entries.push_front(entry);
+        }
+        self.save_logged();
+    }
+}
+
+fn read_entries(path: &Path) -> NodeResult<VecDeque<OutboxEntry>> {
+    let data = fs::read(path)?;
+    serde_json::from_slice(&data)
+        .map_err(|e| NodeError::DecodeError(format!("解析发件箱失败: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(id: &str) -> MessageType {
+        MessageType::Chat {
+            id: id.to_string(),
+            text: "你好".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_outbox_order_expiry_and_persistence() {
+        let path = std::env::temp_dir().join(format!("iroh-node-outbox-{}", new_message_id()));
+        let config = OutboxConfig::default()
+            .with_path(&path)
+            .with_max_messages(3)
+            .with_ttl_secs(60);
+        let topic = TopicId::from_bytes([1; 32]);
+        let other = TopicId::from_bytes([2; 32]);
+
+        let mut outbox = Outbox::load(config.clone()).unwrap();
+        assert_eq!(outbox.push(&topic, chat("a")).unwrap(), "a");
+        outbox.push(&other, chat("b")).unwrap();
+        outbox.push(&topic, chat("c")).unwrap();
+        assert!(outbox.push(&topic, chat("d")).is_err());
+        assert!(is_queueable(&chat("a")));
+        assert!(!is_queueable(&MessageType::Presence { interval_secs: 30 }));
+
+        // 重新加载后仍在
+        let mut outbox = Outbox::load(config).unwrap();
+        assert_eq!(outbox.len(), 3);
+        let ids =
+            |entries: &[OutboxEntry]| entries.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&outbox.pending(&topic)), ["a", "c"]);
+
+        // 过期的消息不再补发
+        let later = Utc::now() + chrono::Duration::seconds(61);
+        let (due, expired) = outbox.take(&topic, later);
+        assert!(due.is_empty());
+        assert_eq!(ids(&expired), ["a", "c"]);
+
+        // 补发失败的消息放回队首
+        let (due, expired) = outbox.take(&other, Utc::now());
+        assert_eq!(ids(&due), ["b"]);
+        assert!(expired.is_empty());
+        assert!(outbox.is_empty());
+        outbox.restore(due);
+        assert_eq!(ids(&outbox.pending(&other)), ["b"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,42 +3,222 @@
 //! 提供P2P节点功能，用于处理iroh-gossip通信
 
 use std::{
-    collections::HashMap,
-    net::{Ipv4Addr, SocketAddrV4},
+    collections::{HashMap, HashSet},
+    path::PathBuf,
     str::FromStr,
     sync::Arc,
+    time::{Duration, Instant},
 };
 
-use bytes::Bytes;
 use futures_lite::StreamExt;
-use iroh_net::{
-    key::{PublicKey, SecretKey},
-    relay::RelayMode,
-    endpoint::Endpoint,
-    NodeAddr,
-    magicsock::Watcher,
+use iroh::{
+    endpoint::Endpoint, protocol::Router, NodeAddr, PublicKey, RelayMode, SecretKey, Watcher,
+};
+use iroh_blobs::{
+    api::Store,
+    store::{fs::FsStore, mem::MemStore},
+    BlobsProtocol,
 };
 use iroh_gossip::{
     api::{Event, GossipReceiver, GossipSender},
     net::{Gossip, GOSSIP_ALPN},
-    proto::topic::TopicId,
+    proto::TopicId,
+};
+use rig_agent::core::{ClientRegistry, ListPage, AUDIT_LOG_TARGET, CALLER_ID_KEY};
+use rig_agent::{
+    AgentConfig, AgentManager, AgentMessage, AgentPackage, AgentResponse, AgentSortKey,
+    AgentSummary, ChatOptions, DocumentSummary, EvalReport, EvalSuite, FeedbackRecord,
+    McpServerHealth, PageRequest, PurgeReport, RequestContext, RequestOrigin, ResponseFeedback,
+    RetentionPolicy, SummarizeOptions, TranslateRequest, Translation,
 };
-use rig_agent::{AgentConfig, AgentManager, AgentResponse, ClientConfig};
-use rig_agent::core::ClientRegistry;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    config::NodeConfig, error::NodeResult, fmt_relay_mode, MessageType, NodeStatus, SignedMessage,
-    Ticket,
+    address_book::{AddressBook, PeerRecord, TrustLevel, TrustPolicy},
+    bundle::{BundleTopic, NodeBundle},
+    chat::{
+        new_message_id, AckKind, ChatHistory, ChatHistoryEntry, DeliveryState, DeliveryStatus,
+        Reactions,
+    },
+    chat_log::ChatLog,
+    commands::{ChatCommand, CommandAction, CommandContext, CommandOutcome, CommandRegistry},
+    config::{
+        encode_secret_key, generate_secret_key, parse_proxy_url, NodeConfig, NODE_SECRET_KEY_NAME,
+    },
+    content::MessageContent,
+    coordination::{
+        agent_request_id, claim_after_backoff, responder_rank, AgentAnswer, AgentLoad,
+        AgentRequestMode, AgentRequestOutcome, AgentRequestStatus, AgentTarget, ClaimTracker,
+        LoadTracker, PeerLoads, DEFAULT_AGENT_TIMEOUT, STATUS_INTERVAL,
+    },
+    ensemble::{self, EnsembleOptions, EnsembleOutcome},
+    error::NodeResult,
+    events::{EventBus, NodeEvent},
+    fmt_relay_mode,
+    folder_sync::{
+        self, find_folder, folder_not_found, publish_folder_status, with_folder, FileSyncStatus,
+        FolderSync, FolderSyncEntry, FolderSyncMap,
+    },
+    history::{HistoryPage, HistorySummary},
+    logs::LogBuffer,
+    memory::{self, MemoryEntry, MemoryMatch, SharedMemory, SharedMemoryConfig},
+    mentions::{parse_mentions, snippet, MentionIndex, MentionRecord, MentionSummary, RoomMember},
+    outbox::{is_queueable, Outbox, OutboxEntry},
+    presence::{KeepaliveSchedule, PresenceTracker, RoomLiveness},
+    protocol::{is_supported_version, Capabilities, PeerInfo, PROTOCOL_VERSION},
+    read_state::{RoomUnread, UnreadSummary},
+    room_bot::{RoomBotConfig, RoomBotResponder, RoomBots},
+    scan::FileScanHook,
+    supervisor::TaskSupervisor,
+    templates::{self, template_topic, RemoteTemplate, TemplateAnnouncement, TemplateExchange},
+    usage::{StatsWindow, UsageBucket, UsageLedger, UsageSummary},
+    validation::check_emoji,
+    verification::{self, SafetyNumber, ScannedCode},
+    MessageType, NodeStatus, SignedMessage, Ticket, TopicStats,
 };
 
+/// 状态监视器检查中继与连接状态的间隔
+const STATUS_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 执行数据保留策略的间隔
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 各话题中的邻居节点
+type TopicNeighbors = Arc<RwLock<HashMap<TopicId, HashSet<PublicKey>>>>;
+
+/// 已交换过节点信息的对等节点
+type PeerInfoMap = Arc<RwLock<HashMap<PublicKey, PeerInfo>>>;
+
+/// 各话题的消息处理通道
+type MessageHandlers = Arc<RwLock<HashMap<TopicId, mpsc::Sender<(PublicKey, MessageType)>>>>;
+
+/// 活跃话题的发送端与共享的接收端
+pub(crate) type TopicMap = HashMap<TopicId, (GossipSender, Arc<Mutex<GossipReceiver>>)>;
+
+/// 各话题的流量计数
+pub(crate) type TopicCountersMap = Arc<RwLock<HashMap<TopicId, TopicCounters>>>;
+
+/// 请求ID -> 应答收集通道
+type PendingAgentRequests = Arc<RwLock<HashMap<String, mpsc::Sender<AgentAnswer>>>>;
+
+/// 每个请求的应答队列容量
+const ANSWER_QUEUE_CAPACITY: usize = 32;
+
+/// 等待模板内容的超时时间
+const TEMPLATE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 已发出的Agent请求
+#[derive(Debug)]
+pub struct AgentRequestHandle {
+    request_id: String,
+    outcome: oneshot::Receiver<AgentRequestOutcome>,
+}
+
+impl AgentRequestHandle {
+    /// 请求ID
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// 等待请求完成或超时
+    pub async fn wait(self) -> NodeResult<AgentRequestOutcome> {
+        self.outcome
+            .await
+            .map_err(|_| crate::error::NodeError::AgentError("Agent请求已取消".to_string()))
+    }
+}
+
+/// 调用本地Agent所需的节点状态，调用期间无需持有节点本身
+#[derive(Clone)]
+pub struct AgentChat {
+    agent_manager: Arc<RwLock<AgentManager>>,
+    client_registry: Arc<ClientRegistry>,
+    usage: Arc<UsageLedger>,
+}
+
+impl AgentChat {
+    /// 以流式方式与本地Agent聊天，每收到一段文本调用一次 `on_chunk`，结束后返回完整响应
+    pub async fn chat_stream<F>(
+        &self,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+        on_chunk: F,
+    ) -> NodeResult<AgentResponse>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let agent_manager = self.agent_manager.read().await;
+        let response = agent_manager
+            .chat_stream(&self.client_registry, agent_id, message, options, on_chunk)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("Agent请求失败: {}", e)));
+        self.usage
+            .record_agent(agent_id, message, response.as_ref().ok());
+        response
+    }
+}
+
+/// 单个话题的流量计数
+#[derive(Default)]
+pub(crate) struct TopicCounters {
+    messages_in: u64,
+    messages_out: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    senders: HashSet<PublicKey>,
+    joined_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_activity: Option<chrono::DateTime<chrono::Utc>>,
+    /// 本节点最近一次在话题中发出消息的时间
+    last_sent: Option<chrono::DateTime<chrono::Utc>>,
+    /// 节点用量账本，话题离开后仍保留历史用量
+    usage: Arc<UsageLedger>,
+}
+
+impl TopicCounters {
+    fn record_in(&mut self, from: PublicKey, bytes: usize) {
+        self.messages_in += 1;
+        self.bytes_in += bytes as u64;
+        self.senders.insert(from);
+        self.last_activity = Some(chrono::Utc::now());
+        self.usage.record_in(bytes);
+    }
+
+    fn record_out(&mut self, bytes: usize) {
+        self.messages_out += 1;
+        self.bytes_out += bytes as u64;
+        self.last_activity = Some(chrono::Utc::now());
+        self.last_sent = self.last_activity;
+        self.usage.record_out(bytes);
+    }
+
+    fn snapshot(&self, topic_id: &TopicId) -> TopicStats {
+        TopicStats {
+            topic_id: topic_id.to_string(),
+            messages_in: self.messages_in,
+            messages_out: self.messages_out,
+            bytes_in: self.bytes_in,
+            bytes_out: self.bytes_out,
+            unique_senders: self.senders.len(),
+            joined_at: self.joined_at,
+            last_activity: self.last_activity,
+        }
+    }
+}
+
 /// P2P节点
 pub struct P2PNode {
     /// 节点配置
     config: NodeConfig,
     /// 节点端点
     endpoint: Endpoint,
+    /// gossip协议，所有话题共用
+    gossip: Gossip,
+    /// blob存储，保存上传导入的文件，对等节点可按哈希获取
+    blobs: Store,
+    /// 接受gossip与blob连接的路由器，节点启动后创建，丢弃时停止接受连接
+    router: Mutex<Option<Router>>,
     /// 节点密钥
     secret_key: SecretKey,
     /// 节点ID
@@ -48,24 +228,82 @@ pub struct P2PNode {
     /// 节点状态
     status: Arc<RwLock<NodeStatus>>,
     /// 活跃话题
-    topics: Arc<RwLock<HashMap<TopicId, (GossipSender, GossipReceiver)>>>,
+    topics: Arc<RwLock<TopicMap>>,
     /// Agent管理器
     agent_manager: Arc<RwLock<AgentManager>>,
-    /// 客户端注册表
-    client_registry: ClientRegistry,
+    /// 客户端注册表，与后台任务共享
+    client_registry: Arc<ClientRegistry>,
     /// 消息处理器
-    message_handlers: Arc<RwLock<HashMap<TopicId, mpsc::Sender<(PublicKey, MessageType)>>>>,
+    message_handlers: MessageHandlers,
     /// 节点是否正在运行
     running: Arc<RwLock<bool>>,
+    /// 各话题中的邻居节点
+    neighbors: TopicNeighbors,
+    /// 各话题的流量计数
+    topic_stats: TopicCountersMap,
+    /// 话题名称（房间名称）
+    topic_labels: Arc<RwLock<HashMap<TopicId, String>>>,
+    /// 节点事件总线
+    events: EventBus,
+    /// 各话题的聊天记录
+    chat_history: Arc<RwLock<ChatHistory>>,
+    /// 对等节点的协议版本与能力
+    peers: PeerInfoMap,
+    /// 已知节点的地址与信任级别
+    address_book: Arc<RwLock<AddressBook>>,
+    /// 各话题中参与Agent响应者选举的节点
+    agent_peers: TopicNeighbors,
+    /// 已被认领的Agent请求
+    agent_claims: Arc<RwLock<ClaimTracker>>,
+    /// 本节点发出、仍在等待应答的Agent请求
+    pending_agent_requests: PendingAgentRequests,
+    /// 本节点的Agent负载
+    agent_load: Arc<RwLock<LoadTracker>>,
+    /// 其他节点通告的Agent负载
+    peer_loads: Arc<RwLock<PeerLoads>>,
+    /// 共享记忆，未启用时为空
+    shared_memory: Option<Arc<RwLock<SharedMemory>>>,
+    /// 用量账本
+    usage: Arc<UsageLedger>,
+    /// Agent模板交换
+    templates: Arc<RwLock<TemplateExchange>>,
+    /// 各话题成员的最近活跃时间
+    presence: Arc<RwLock<PresenceTracker>>,
+    /// 各房间的机器人
+    room_bots: Arc<RwLock<RoomBots>>,
+    /// 各成员被提及的记录
+    mentions: Arc<RwLock<MentionIndex>>,
+    /// 聊天命令
+    commands: Arc<RwLock<CommandRegistry>>,
+    /// 发件箱，未启用时为空
+    outbox: Option<Arc<RwLock<Outbox>>>,
+    /// 各话题的同步文件夹
+    folder_syncs: FolderSyncMap,
+    /// 接收文件的安全扫描，未设置时不扫描
+    file_scanner: Option<Arc<FileScanHook>>,
+    /// 后台任务监督器
+    tasks: TaskSupervisor,
 }
 
 impl P2PNode {
     /// 创建新的P2P节点
     pub async fn new(config: NodeConfig) -> NodeResult<Self> {
+        config.validate()?;
+
         // 解析或生成密钥
-        let secret_key = match &config.secret_key {
-            Some(key) => key.parse().map_err(|e| crate::error::NodeError::ConfigError(format!("解析密钥失败: {}", e)))?,
-            None => SecretKey::generate(&mut rand::rngs::OsRng),
+        let secret_key = match (&config.secret_key, &config.secret_store) {
+            (Some(key), _) => key.parse().map_err(|e| {
+                crate::error::NodeError::ConfigError(format!("解析密钥失败: {}", e))
+            })?,
+            (None, Some(store)) => store
+                .get_or_insert_with(NODE_SECRET_KEY_NAME, || {
+                    encode_secret_key(&generate_secret_key())
+                })?
+                .parse()
+                .map_err(|e| {
+                    crate::error::NodeError::ConfigError(format!("密钥存储中的节点密钥无效: {}", e))
+                })?,
+            (None, None) => generate_secret_key(),
         };
 
         // 配置中继模式
@@ -80,11 +318,23 @@ impl P2PNode {
             }
         };
 
+        let proxy = config.proxy.as_deref().map(parse_proxy_url).transpose()?;
+
         // 构建端点
-        let endpoint = Endpoint::builder()
+        let (bind_v4, bind_v6) = config.bind_addrs();
+        let mut builder = Endpoint::builder()
             .secret_key(secret_key.clone())
             .relay_mode(relay_mode.clone())
-            .bind_addr_v4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.bind_port))
+            .bind_addr_v4(bind_v4)
+            .bind_addr_v6(bind_v6);
+        if let Some(proxy) = proxy {
+            info!("中继连接经由代理: {}", proxy);
+            if !config.privacy_mode {
+                warn!("未开启隐私模式，与对等节点的直连不经过代理");
+            }
+            builder = builder.proxy_url(proxy);
+        }
+        let endpoint = builder
             .bind()
             .await
             .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?;
@@ -93,10 +343,32 @@ impl P2PNode {
         info!("节点ID: {}", node_id);
         info!("使用中继服务器: {}", fmt_relay_mode(&relay_mode));
 
+        // 加载地址簿
+        let address_book = AddressBook::load(config.address_book_path.as_deref())?;
+        let shared_memory = config
+            .shared_memory
+            .as_ref()
+            .map(|memory| SharedMemory::load(memory.path.as_deref()))
+            .transpose()?
+            .map(|memory| Arc::new(RwLock::new(memory)));
+        let outbox = config
+            .outbox
+            .clone()
+            .map(Outbox::load)
+            .transpose()?
+            .map(|outbox| Arc::new(RwLock::new(outbox)));
+        let chat_history = match &config.chat_log {
+            Some(chat_log) => {
+                ChatHistory::with_log(ChatLog::new(chat_log.clone(), config.secret_store.clone())?)
+            }
+            None => ChatHistory::default(),
+        };
+
         // 创建Agent管理器
         let agent_config = AgentConfig::default();
         let agent_manager = AgentManager::new(agent_config);
-        let client_registry = ClientRegistry::new();
+        let client_registry = Arc::new(ClientRegistry::new());
+        let usage = Arc::new(UsageLedger::new(config.model_prices.clone()));
 
         // 创建节点状态
         let status = NodeStatus {
@@ -107,11 +379,26 @@ impl P2PNode {
             started_at: chrono::Utc::now(),
             last_activity: chrono::Utc::now(),
             relay_mode: fmt_relay_mode(&relay_mode),
+            home_relay: None,
+            tasks: Vec::new(),
+        };
+
+        let gossip = Gossip::builder().spawn(endpoint.clone());
+        let blobs = match &config.blob_dir {
+            Some(dir) => FsStore::load(dir)
+                .await
+                .map_err(|e| crate::error::NodeError::IoError(format!("打开blob存储失败: {}", e)))?
+                .as_ref()
+                .clone(),
+            None => MemStore::new().as_ref().clone(),
         };
 
         Ok(Self {
             config,
             endpoint,
+            gossip,
+            blobs,
+            router: Mutex::new(None),
             secret_key,
             node_id,
             name: None,
@@ -121,6 +408,29 @@ impl P2PNode {
             client_registry,
             message_handlers: Arc::new(RwLock::new(HashMap::new())),
             running: Arc::new(RwLock::new(false)),
+            neighbors: Arc::new(RwLock::new(HashMap::new())),
+            topic_stats: Arc::new(RwLock::new(HashMap::new())),
+            topic_labels: Arc::new(RwLock::new(HashMap::new())),
+            events: EventBus::new(),
+            chat_history: Arc::new(RwLock::new(chat_history)),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            address_book: Arc::new(RwLock::new(address_book)),
+            agent_peers: Arc::new(RwLock::new(HashMap::new())),
+            agent_claims: Arc::new(RwLock::new(ClaimTracker::default())),
+            pending_agent_requests: Arc::new(RwLock::new(HashMap::new())),
+            agent_load: Arc::new(RwLock::new(LoadTracker::default())),
+            peer_loads: Arc::new(RwLock::new(PeerLoads::default())),
+            shared_memory,
+            usage,
+            templates: Arc::new(RwLock::new(TemplateExchange::default())),
+            presence: Arc::new(RwLock::new(PresenceTracker::default())),
+            room_bots: Arc::new(RwLock::new(RoomBots::default())),
+            mentions: Arc::new(RwLock::new(MentionIndex::default())),
+            commands: Arc::new(RwLock::new(CommandRegistry::default())),
+            outbox,
+            folder_syncs: Arc::new(RwLock::new(HashMap::new())),
+            file_scanner: None,
+            tasks: TaskSupervisor::default(),
         })
     }
 
@@ -137,16 +447,38 @@ impl P2PNode {
 
         info!("启动P2P节点: {}", self.node_id);
 
-        // 创建gossip协议
-        let gossip = Gossip::builder().spawn(self.endpoint.clone());
+        // 设置路由器，停止后重新启动时沿用
+        {
+            let mut router = self.router.lock().await;
+            if router.is_none() {
+                *router = Some(
+                    Router::builder(self.endpoint.clone())
+                        .accept(GOSSIP_ALPN, self.gossip.clone())
+                        .accept(
+                            iroh_blobs::ALPN,
+                            BlobsProtocol::new(&self.blobs, self.endpoint.clone(), None),
+                        )
+                        .spawn(),
+                );
+            }
+        }
 
-        // 设置路由器
-        let router = iroh_net::protocol::Router::builder(self.endpoint.clone())
-            .accept(GOSSIP_ALPN, gossip.clone())
-            .spawn();
+        // 把地址簿中已知节点的地址加入端点，便于直接连接
+        for addr in self
+            .address_book
+            .read()
+            .await
+            .list()
+            .iter()
+            .filter_map(PeerRecord::node_addr)
+        {
+            if let Err(e) = self.endpoint.add_node_addr(addr) {
+                debug!("添加已知节点地址失败: {}", e);
+            }
+        }
 
         // 更新节点地址
-        let node_addr = self.endpoint.node_addr().initialized().await;
+        let node_addr = self.disclosed_addr().await;
         {
             let mut status = self.status.write().await;
             status.node_addr = Some(node_addr.clone());
@@ -162,32 +494,247 @@ impl P2PNode {
         if let Some(name) = &self.name {
             info!("广播节点名称: {}", name);
             // 为每个活跃话题广播名称
-            for (topic_id, (sender, _)) in self.topics.read().await.iter() {
-                let message = MessageType::NodeInfo {
-                    name: Some(name.clone()),
-                };
+            for (sender, _) in self.topics.read().await.values() {
+                let message = node_info(Some(name.clone()));
                 let encoded_message = SignedMessage::sign_and_encode(&self.secret_key, &message)?;
-                sender.broadcast(encoded_message).await
+                sender
+                    .broadcast(encoded_message)
+                    .await
                     .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?;
             }
         }
 
+        self.spawn_status_watcher();
+        self.spawn_retention();
+        if !self.config.mcp_servers.is_empty() {
+            info!("启动 {} 个MCP服务器", self.config.mcp_servers.len());
+            self.agent_manager
+                .read()
+                .await
+                .get_tool_manager()
+                .start_mcp_servers(self.config.mcp_servers.clone());
+        }
+        self.events.publish(NodeEvent::Started {
+            node_id: self.node_id.clone(),
+        });
+
         info!("P2P节点启动成功");
         Ok(())
     }
 
+    /// 启动状态监视器，在中继切换或连接状态变化时发布事件
+    fn spawn_status_watcher(&self) {
+        let endpoint = self.endpoint.clone();
+        let status = self.status.clone();
+        let running = self.running.clone();
+        let events = self.events.clone();
+
+        self.tasks.spawn(None, "status-watcher", move || {
+            let endpoint = endpoint.clone();
+            let status = status.clone();
+            let running = running.clone();
+            let events = events.clone();
+            async move {
+                let mut online = false;
+                let mut interval = tokio::time::interval(STATUS_WATCH_INTERVAL);
+
+                loop {
+                    interval.tick().await;
+                    if !*running.read().await {
+                        break;
+                    }
+
+                    let home_relay = endpoint
+                        .home_relay()
+                        .get()
+                        .into_iter()
+                        .next()
+                        .map(|url| url.to_string());
+
+                    let (previous_relay, connected_peers) = {
+                        let mut status = status.write().await;
+                        let previous =
+                            std::mem::replace(&mut status.home_relay, home_relay.clone());
+                        (previous, status.connected_peers)
+                    };
+
+                    if previous_relay != home_relay {
+                        info!("主中继服务器变化: {:?} -> {:?}", previous_relay, home_relay);
+                        events.publish(NodeEvent::RelayChanged {
+                            previous: previous_relay,
+                            current: home_relay.clone(),
+                        });
+                    }
+
+                    let now_online = home_relay.is_some() || connected_peers > 0;
+                    if now_online != online {
+                        online = now_online;
+                        info!("节点连接状态变化: {}", if online { "在线" } else { "离线" });
+                        events.publish(NodeEvent::ConnectivityChanged { online });
+                    }
+                }
+
+                debug!("状态监视器结束");
+            }
+        });
+    }
+
+    /// 启动数据保留任务，定期删除过期的会话、聊天记录与审计日志，未设置保留期限时不启动
+    fn spawn_retention(&self) {
+        let policy = self.config.retention.clone();
+        if !policy.is_enabled() {
+            return;
+        }
+        let agent_manager = self.agent_manager.clone();
+        let chat_history = self.chat_history.clone();
+        let running = self.running.clone();
+
+        self.tasks.spawn(None, "retention", move || {
+            let policy = policy.clone();
+            let agent_manager = agent_manager.clone();
+            let chat_history = chat_history.clone();
+            let running = running.clone();
+            async move {
+                let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if !*running.read().await {
+                        break;
+                    }
+                    if let Err(e) = apply_retention(&agent_manager, &chat_history, &policy).await {
+                        warn!("执行数据保留策略失败: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// 启动话题保活任务，按自适应间隔广播在线消息，离开话题或节点停止后结束
+    fn spawn_keepalive(&self, topic_id: TopicId) {
+        let config = self.config.presence.clone();
+        if !config.enabled {
+            return;
+        }
+        let topics = self.topics.clone();
+        let topic_stats = self.topic_stats.clone();
+        let secret_key = self.secret_key.clone();
+        let running = self.running.clone();
+        let neighbors = self.neighbors.clone();
+        let peers = self.peers.clone();
+
+        self.tasks.spawn(Some(topic_id), "keepalive", move || {
+            let config = config.clone();
+            let topics = topics.clone();
+            let topic_stats = topic_stats.clone();
+            let secret_key = secret_key.clone();
+            let running = running.clone();
+            let neighbors = neighbors.clone();
+            let peers = peers.clone();
+            async move {
+                let mut schedule = KeepaliveSchedule::new(&config);
+                let mut known_neighbors = HashSet::new();
+
+                loop {
+                    tokio::time::sleep(schedule.current()).await;
+                    if !*running.read().await || !topics.read().await.contains_key(&topic_id) {
+                        break;
+                    }
+
+                    let current_neighbors = neighbors
+                        .read()
+                        .await
+                        .get(&topic_id)
+                        .cloned()
+                        .unwrap_or_default();
+                    let changed = current_neighbors != known_neighbors;
+                    known_neighbors = current_neighbors;
+                    if changed || known_neighbors.is_empty() {
+                        schedule.reset();
+                    } else {
+                        schedule.backoff();
+                    }
+                    if known_neighbors.is_empty() {
+                        continue;
+                    }
+
+                    // 最近已发过消息时邻居连接仍然活跃，无需额外保活
+                    let last_sent = topic_stats
+                        .read()
+                        .await
+                        .get(&topic_id)
+                        .and_then(|counters| counters.last_sent);
+                    let recently_sent = last_sent.is_some_and(|at| {
+                        chrono::Utc::now()
+                            .signed_duration_since(at)
+                            .to_std()
+                            .unwrap_or_default()
+                            < schedule.current()
+                    });
+                    if recently_sent {
+                        continue;
+                    }
+                    // 旧版本节点无法解码在线消息，邻居都不支持时不发送
+                    let supported = {
+                        let peers = peers.read().await;
+                        known_neighbors.iter().any(|peer| {
+                            peers
+                                .get(peer)
+                                .is_some_and(|info| info.supports(Capabilities::PRESENCE))
+                        })
+                    };
+                    if !supported {
+                        continue;
+                    }
+
+                    let message = MessageType::Presence {
+                        interval_secs: schedule.current().as_secs() as u32,
+                    };
+                    if let Err(e) =
+                        broadcast_signed(&topics, &topic_stats, &secret_key, &topic_id, &message)
+                            .await
+                    {
+                        warn!("广播在线消息失败: {}", e);
+                    }
+                }
+
+                debug!("话题 {} 的保活任务结束", topic_id);
+            }
+        });
+    }
+
+    /// 订阅节点事件
+    pub fn subscribe_events(&self) -> broadcast::Receiver<NodeEvent> {
+        self.events.subscribe()
+    }
+
+    /// 获取节点事件总线
+    pub fn event_bus(&self) -> &EventBus {
+        &self.events
+    }
+
     /// 设置节点名称
     pub fn set_name(&mut self, name: String) {
         self.name = Some(name);
     }
 
+    /// 设置接收文件的安全扫描，之后开始的同步文件夹在放入收到的文件前先扫描
+    pub fn set_file_scanner(&mut self, scanner: Arc<FileScanHook>) {
+        self.file_scanner = Some(scanner);
+    }
+
     /// 获取节点状态
     pub async fn get_status(&self) -> NodeStatus {
-        self.status.read().await.clone()
+        let mut status = self.status.read().await.clone();
+        status.tasks = self.tasks.health();
+        status
     }
 
     /// 创建或加入话题
-    pub async fn join_topic(&self, topic: Option<TopicId>, ticket: Option<&str>) -> NodeResult<(TopicId, String)> {
+    pub async fn join_topic(
+        &self,
+        topic: Option<TopicId>,
+        ticket: Option<&str>,
+    ) -> NodeResult<(TopicId, String)> {
         // 检查节点是否在运行
         {
             let running = self.running.read().await;
@@ -215,6 +762,15 @@ impl P2PNode {
             }
         };
 
+        self.subscribe_topic(topic_id, peers).await
+    }
+
+    /// 通过已知的对等节点订阅话题，返回话题ID与票据
+    async fn subscribe_topic(
+        &self,
+        topic_id: TopicId,
+        peers: Vec<NodeAddr>,
+    ) -> NodeResult<(TopicId, String)> {
         // 检查是否已经加入该话题
         if self.topics.read().await.contains_key(&topic_id) {
             info!("已经加入话题: {}", topic_id);
@@ -223,9 +779,6 @@ impl P2PNode {
             return Ok((topic_id, ticket));
         }
 
-        // 创建gossip协议
-        let gossip = Gossip::builder().spawn(self.endpoint.clone());
-
         // 连接到已知的对等节点
         let peer_ids = peers.iter().map(|p| p.node_id).collect();
         if peers.is_empty() {
@@ -234,13 +787,20 @@ impl P2PNode {
             info!("尝试连接到{}个对等节点...", peers.len());
             // 将票据中的对等节点地址添加到端点的地址簿
             for peer in peers.iter() {
-                self.endpoint.add_node_addr(peer.clone())
+                self.endpoint
+                    .add_node_addr(peer.clone())
                     .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?;
+                self.address_book.write().await.observe_addr(peer);
             }
         }
 
-        // 订阅话题
-        let (sender, receiver) = gossip.subscribe_and_join(topic_id.clone(), peer_ids).await
+        // 订阅话题；新建话题时没有可等待的对等节点，直接订阅
+        let topic = if peers.is_empty() {
+            self.gossip.subscribe(topic_id, peer_ids).await
+        } else {
+            self.gossip.subscribe_and_join(topic_id, peer_ids).await
+        };
+        let (sender, receiver) = topic
             .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?
             .split();
         info!("已连接到话题: {}", topic_id);
@@ -248,7 +808,7 @@ impl P2PNode {
         // 保存话题
         {
             let mut topics = self.topics.write().await;
-            topics.insert(topic_id.clone(), (sender, receiver));
+            topics.insert(topic_id, (sender, Arc::new(Mutex::new(receiver))));
 
             // 更新状态
             let mut status = self.status.write().await;
@@ -256,8 +816,43 @@ impl P2PNode {
             status.last_activity = chrono::Utc::now();
         }
 
+        self.topic_stats.write().await.insert(
+            topic_id,
+            TopicCounters {
+                joined_at: Some(chrono::Utc::now()),
+                usage: self.usage.clone(),
+                ..Default::default()
+            },
+        );
+
+        // 恢复本地保存的聊天记录
+        if let Err(e) = self.chat_history.write().await.restore(&topic_id) {
+            warn!("恢复话题 {} 的聊天记录失败: {}", topic_id, e);
+        }
+
+        self.events.publish(NodeEvent::TopicJoined {
+            topic_id: topic_id.to_string(),
+        });
+
         // 启动消息处理循环
-        self.start_message_handler(topic_id.clone()).await?;
+        self.start_message_handler(topic_id).await?;
+        self.spawn_keepalive(topic_id);
+
+        // 向话题中的节点通告版本与能力
+        if let Err(e) = broadcast_signed(
+            &self.topics,
+            &self.topic_stats,
+            &self.secret_key,
+            &topic_id,
+            &node_info(self.name.clone()),
+        )
+        .await
+        {
+            warn!("广播节点信息失败: {}", e);
+        }
+
+        // 已连上对等节点时补发发件箱中的消息，否则等邻居上线后补发
+        self.flush_outbox(&topic_id).await;
 
         // 生成票据
         let ticket = self.generate_ticket(topic_id).await?;
@@ -266,170 +861,312 @@ impl P2PNode {
     }
 
     /// 生成票据
-    async fn generate_ticket(&self, topic_id: TopicId) -> NodeResult<String> {
-        let me = self.endpoint.node_addr().initialized().await;
+    pub(crate) async fn generate_ticket(&self, topic_id: TopicId) -> NodeResult<String> {
+        let me = self.disclosed_addr().await;
         let peers = vec![me];
         let ticket = Ticket {
             topic: topic_id,
             peers,
         };
-        Ok(ticket.to_string())
+        ticket.encode(&self.config.ticket_options)
+    }
+
+    /// 可以公开给其他节点的本节点地址，隐私模式下只包含中继地址
+    async fn disclosed_addr(&self) -> NodeAddr {
+        let addr = self.endpoint.node_addr().initialized().await;
+        self.config.disclosed_addr(addr)
     }
 
     /// 启动消息处理循环
     async fn start_message_handler(&self, topic_id: TopicId) -> NodeResult<()> {
-        let topics = self.topics.read().await;
-        let (_, receiver) = topics.get(&topic_id).ok_or_else(|| {
-            crate::error::NodeError::TopicError(format!("话题不存在: {}", topic_id))
-        })?;
+        if !self.topics.read().await.contains_key(&topic_id) {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )));
+        }
+
+        // 创建消息通道，处理器重启后继续从同一个通道读取
+        let (tx, rx) = mpsc::channel::<(PublicKey, MessageType)>(100);
+        let rx = Arc::new(Mutex::new(rx));
 
-        // 克隆接收器
-        let mut receiver = receiver.clone();
-        
-        // 创建消息通道
-        let (tx, mut rx) = mpsc::channel::<(PublicKey, MessageType)>(100);
-        
         // 保存消息处理器
         {
             let mut handlers = self.message_handlers.write().await;
-            handlers.insert(topic_id.clone(), tx);
+            handlers.insert(topic_id, tx.clone());
         }
 
-        // 克隆必要的引用
-        let secret_key = self.secret_key.clone();
-        let agent_manager = self.agent_manager.clone();
-        let client_registry = &self.client_registry;
-        let topics_ref = self.topics.clone();
-        let topic_id_clone = topic_id.clone();
+        // 消息处理器的共享状态，处理器重启后与各条消息、各个Agent请求任务共享同一份
+        let handler = Arc::new(TopicHandler {
+            topic_id,
+            is_template_topic: topic_id == template_topic(),
+            secret_key: self.secret_key.clone(),
+            name: self.name.clone(),
+            agent_manager: self.agent_manager.clone(),
+            client_registry: self.client_registry.clone(),
+            topics: self.topics.clone(),
+            topic_stats: self.topic_stats.clone(),
+            events: self.events.clone(),
+            chat_history: self.chat_history.clone(),
+            peers: self.peers.clone(),
+            address_book: self.address_book.clone(),
+            trust_policy: self.config.trust_policy.clone(),
+            agent_peers: self.agent_peers.clone(),
+            agent_claims: self.agent_claims.clone(),
+            pending_agent_requests: self.pending_agent_requests.clone(),
+            shared_memory: self.shared_memory.clone(),
+            memory_config: self.config.shared_memory.clone(),
+            usage: self.usage.clone(),
+            templates: self.templates.clone(),
+            room_bots: self.room_bots.clone(),
+            bot_responder: self.room_bot_responder(),
+            agent_load: self.agent_load.clone(),
+            peer_loads: self.peer_loads.clone(),
+            folder_syncs: self.folder_syncs.clone(),
+            mention_router: self.mention_router(),
+            read_tracker: self.read_tracker(),
+            own_devices: self.config.own_devices.clone(),
+        });
+
+        // 接收消息的任务所需的引用
         let running = self.running.clone();
+        let status = self.status.clone();
+        let neighbors = self.neighbors.clone();
+        let events = self.events.clone();
+        let topic_stats = self.topic_stats.clone();
+        let limits = self.config.message_limits.clone();
+        let agent_peers = self.agent_peers.clone();
+        let presence = self.presence.clone();
+        let outbox_flusher = self.outbox_flusher();
 
         // 启动接收消息的任务
-        tokio::spawn(async move {
-            info!("启动话题 {} 的消息处理循环", topic_id);
-            
-            while let Some(event) = receiver.try_next().await
-                .map_err(|e| {
-                    error!("接收消息错误: {}", e);
-                    None
-                })
-                .unwrap_or(None) {
-                // 检查节点是否仍在运行
-                if !*running.read().await {
-                    info!("节点已停止，终止消息处理循环");
-                    break;
-                }
-                
-                if let Event::Received(msg) = event {
-                    match SignedMessage::verify_and_decode(&msg.content) {
-                        Ok((from, message)) => {
-                            debug!("收到来自 {} 的消息: {:?}", from.fmt_short(), message);
-                            
-                            // 发送到处理通道
-                            if let Err(e) = tx.send((from, message)).await {
-                                error!("发送消息到处理通道失败: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            error!("验证消息失败: {}", e);
+        let receiver_topics = self.topics.clone();
+        self.tasks
+            .spawn(Some(topic_id), "message-receiver", move || {
+                let topics = receiver_topics.clone();
+                let running = running.clone();
+                let status = status.clone();
+                let neighbors = neighbors.clone();
+                let events = events.clone();
+                let topic_stats = topic_stats.clone();
+                let limits = limits.clone();
+                let agent_peers = agent_peers.clone();
+                let presence = presence.clone();
+                let outbox_flusher = outbox_flusher.clone();
+                let tx = tx.clone();
+                async move {
+                    // 每次（重新）启动时从话题中取得共享的接收器，话题已离开时结束；
+                    // 上一个任务退出后锁随之释放，重启的任务可以继续接收
+                    let Some(receiver) = topics
+                        .read()
+                        .await
+                        .get(&topic_id)
+                        .map(|(_, receiver)| receiver.clone())
+                    else {
+                        return;
+                    };
+                    let mut receiver = receiver.lock().await;
+                    info!("启动话题 {} 的消息处理循环", topic_id);
+                    // 已提示过版本不兼容的节点，避免重复告警
+                    let mut incompatible = HashSet::new();
+
+                    while let Some(event) = receiver.try_next().await.unwrap_or_else(|e| {
+                        error!("接收消息错误: {}", e);
+                        None
+                    }) {
+                        // 检查节点是否仍在运行
+                        if !*running.read().await {
+                            info!("节点已停止，终止消息处理循环");
+                            break;
                         }
-                    }
-                }
-            }
-            
-            info!("话题 {} 的消息处理循环结束", topic_id);
-        });
 
-        // 启动处理消息的任务
-        tokio::spawn(async move {
-            info!("启动话题 {} 的消息处理器", topic_id_clone);
-            
-            while let Some((from, message)) = rx.recv().await {
-                // 检查节点是否仍在运行
-                if !*running.read().await {
-                    info!("节点已停止，终止消息处理器");
-                    break;
-                }
-                
-                match message {
-                    MessageType::Chat { text } => {
-                        debug!("收到聊天消息: {}", text);
-                        // 这里可以添加聊天消息的处理逻辑
-                    }
-                    MessageType::AgentRequest { prompt, agent_id } => {
-                        debug!("收到Agent请求: {}, agent_id: {}", prompt, agent_id);
-                        
-                        // 使用tokio::spawn处理异步请求，避免阻塞消息处理循环
-                        let agent_manager_clone = agent_manager.clone();
-                        let client_registry_ref = client_registry;
-                        let secret_key_clone = secret_key.clone();
-                        let topics_ref_clone = topics_ref.clone();
-                        let topic_id_clone2 = topic_id_clone.clone();
-                        let agent_id_clone = agent_id.clone();
-                        let prompt_clone = prompt.clone();
-                        
-                        tokio::spawn(async move {
-                            // 处理Agent请求
-                            let response = match process_agent_request(&agent_manager_clone, client_registry_ref, &agent_id_clone, &prompt_clone).await {
-                                Ok(resp) => {
-                                    debug!("Agent请求处理成功，响应长度: {}", resp.content.len());
-                                    MessageType::AgentResponse {
-                                        content: resp.content,
-                                        agent_id: agent_id_clone,
-                                    }
-                                },
-                                Err(e) => {
-                                    error!("处理Agent请求失败: {}", e);
-                                    MessageType::Error {
-                                        message: format!("处理Agent请求失败: {}", e),
+                        match event {
+                            Event::Received(msg)
+                                if msg.content.len() > limits.max_message_bytes =>
+                            {
+                                warn!(
+                                    "丢弃超长消息: {} 字节，上限 {} 字节",
+                                    msg.content.len(),
+                                    limits.max_message_bytes
+                                );
+                                events.publish(NodeEvent::MessageRejected {
+                                    topic_id: topic_id.to_string(),
+                                    from: None,
+                                    reason: format!("消息过长: {} 字节", msg.content.len()),
+                                });
+                            }
+                            Event::Received(msg) => match SignedMessage::verify(&msg.content) {
+                                Ok((from, version, _)) if !is_supported_version(version) => {
+                                    if incompatible.insert(from) {
+                                        warn!(
+                                            "节点 {} 使用不兼容的协议版本 {}（本节点 {}）",
+                                            from.fmt_short(),
+                                            version,
+                                            PROTOCOL_VERSION
+                                        );
+                                        events.publish(NodeEvent::PeerIncompatible {
+                                            topic_id: topic_id.to_string(),
+                                            peer_id: from.to_string(),
+                                            version,
+                                        });
                                     }
-                                },
-                            };
-                            
-                            // 发送响应
-                            if let Some((sender, _)) = topics_ref_clone.read().await.get(&topic_id_clone2) {
-                                match SignedMessage::sign_and_encode(&secret_key_clone, &response) {
-                                    Ok(encoded) => {
-                                        match sender.broadcast(encoded).await {
-                                            Ok(_) => debug!("成功发送Agent响应"),
-                                            Err(e) => error!("广播响应失败: {}", e),
+                                }
+                                Ok((from, _, payload)) => {
+                                    let message: MessageType = match postcard::from_bytes(&payload)
+                                    {
+                                        Ok(message) => message,
+                                        Err(e) => {
+                                            error!(
+                                                "解码来自 {} 的消息失败: {}",
+                                                from.fmt_short(),
+                                                e
+                                            );
+                                            continue;
                                         }
-                                    },
-                                    Err(e) => error!("编码响应失败: {}", e),
+                                    };
+
+                                    debug!("收到来自 {} 的消息: {:?}", from.fmt_short(), message);
+
+                                    if let Err(e) = limits.validate(&message) {
+                                        warn!("丢弃来自 {} 的无效消息: {}", from.fmt_short(), e);
+                                        events.publish(NodeEvent::MessageRejected {
+                                            topic_id: topic_id.to_string(),
+                                            from: Some(from.to_string()),
+                                            reason: e.to_string(),
+                                        });
+                                        continue;
+                                    }
+
+                                    if let Some(counters) =
+                                        topic_stats.write().await.get_mut(&topic_id)
+                                    {
+                                        counters.record_in(from, msg.content.len());
+                                    }
+                                    presence.write().await.observe(
+                                        topic_id,
+                                        from.to_string(),
+                                        chrono::Utc::now(),
+                                    );
+
+                                    // 发送到处理通道
+                                    if let Err(e) = tx.send((from, message)).await {
+                                        error!("发送消息到处理通道失败: {}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    error!("验证消息失败: {}", e);
+                                }
+                            },
+                            Event::NeighborUp(peer) => {
+                                neighbors
+                                    .write()
+                                    .await
+                                    .entry(topic_id)
+                                    .or_default()
+                                    .insert(peer);
+                                presence.write().await.observe(
+                                    topic_id,
+                                    peer.to_string(),
+                                    chrono::Utc::now(),
+                                );
+                                events.publish(NodeEvent::NeighborUp {
+                                    topic_id: topic_id.to_string(),
+                                    peer_id: peer.to_string(),
+                                });
+                                refresh_peer_count(&status, &neighbors, &events).await;
+                                // 话题重新连上，补发发件箱中的消息
+                                if let Some(flusher) = outbox_flusher.clone() {
+                                    tokio::spawn(async move {
+                                        flusher.flush(&topic_id).await;
+                                    });
                                 }
-                            } else {
-                                error!("话题 {} 不存在，无法发送响应", topic_id_clone2);
                             }
-                        });
-                    }
-                    MessageType::NodeInfo { name } => {
-                        if let Some(name) = name {
-                            debug!("节点 {} 的名称: {}", from.fmt_short(), name);
-                            // 这里可以保存节点名称
+                            Event::NeighborDown(peer) => {
+                                if let Some(peers) = neighbors.write().await.get_mut(&topic_id) {
+                                    peers.remove(&peer);
+                                }
+                                if let Some(peers) = agent_peers.write().await.get_mut(&topic_id) {
+                                    peers.remove(&peer);
+                                }
+                                events.publish(NodeEvent::NeighborDown {
+                                    topic_id: topic_id.to_string(),
+                                    peer_id: peer.to_string(),
+                                });
+                                refresh_peer_count(&status, &neighbors, &events).await;
+                            }
+                            _ => {}
                         }
                     }
-                    MessageType::AgentResponse { content, agent_id } => {
-                        debug!("收到Agent响应: agent_id={}, 内容长度={}", agent_id, content.len());
-                        // 这里可以处理Agent响应
-                    }
-                    MessageType::Error { message } => {
-                        error!("收到错误消息: {}", message);
-                        // 这里可以处理错误消息
-                    }
-                    MessageType::System { content } => {
-                        info!("收到系统消息: {}", content);
-                        // 这里可以处理系统消息
+
+                    info!("话题 {} 的消息处理循环结束", topic_id);
+                }
+            });
+
+        // 启动处理消息的任务
+        let handler_running = self.running.clone();
+        self.tasks
+            .spawn(Some(topic_id), "message-handler", move || {
+                let rx = rx.clone();
+                let running = handler_running.clone();
+                let handler = handler.clone();
+                async move {
+                    info!("启动话题 {} 的消息处理器", handler.topic_id);
+                    let mut rx = rx.lock().await;
+
+                    while let Some((from, message)) = rx.recv().await {
+                        // 检查节点是否仍在运行
+                        if !*running.read().await {
+                            info!("节点已停止，终止消息处理器");
+                            break;
+                        }
+                        handler.handle(from, message).await;
                     }
+
+                    info!("话题 {} 的消息处理器结束", handler.topic_id);
                 }
-            }
-            
-            info!("话题 {} 的消息处理器结束", topic_id_clone);
-        });
+            });
 
         Ok(())
     }
 
-    /// 发送消息到话题
+    /// 发送消息到话题，启用发件箱时离线或未加入话题时暂存消息，连上后补发
     pub async fn send_message(&self, topic_id: &TopicId, message: MessageType) -> NodeResult<()> {
+        if self.send_or_queue(topic_id, message).await? {
+            self.flush_outbox(topic_id).await;
+        }
+        Ok(())
+    }
+
+    /// 发送消息，发件箱启用且话题未连上（或仍有待补发的消息）时暂存到发件箱，返回是否已暂存
+    async fn send_or_queue(&self, topic_id: &TopicId, message: MessageType) -> NodeResult<bool> {
+        // 提前拒绝对端会丢弃的消息
+        self.config.message_limits.validate(&message)?;
+
+        let Some(flusher) = self.outbox_flusher().filter(|_| is_queueable(&message)) else {
+            self.broadcast_message(topic_id, &message).await?;
+            return Ok(false);
+        };
+
+        // 发件箱中还有待补发的消息时排在其后，保持发送顺序
+        let connected = *self.running.read().await && flusher.is_connected(topic_id).await;
+        if connected && flusher.outbox.read().await.pending(topic_id).is_empty() {
+            match self.broadcast_message(topic_id, &message).await {
+                Ok(()) => return Ok(false),
+                Err(e) => warn!("发送消息失败，暂存到发件箱: {}", e),
+            }
+        }
+
+        let message_id = flusher.outbox.write().await.push(topic_id, message)?;
+        debug!("消息 {} 暂存到话题 {} 的发件箱", message_id, topic_id);
+        self.events.publish(NodeEvent::MessageQueued {
+            topic_id: topic_id.to_string(),
+            message_id,
+        });
+        Ok(true)
+    }
+
+    /// 签名并广播消息到话题
+    async fn broadcast_message(&self, topic_id: &TopicId, message: &MessageType) -> NodeResult<()> {
         // 检查节点是否在运行
         {
             let running = self.running.read().await;
@@ -445,10 +1182,17 @@ impl P2PNode {
             crate::error::NodeError::TopicError(format!("话题不存在: {}", topic_id))
         })?;
 
-        let encoded_message = SignedMessage::sign_and_encode(&self.secret_key, &message)?;
-        sender.broadcast(encoded_message).await
+        let encoded_message = SignedMessage::sign_and_encode(&self.secret_key, message)?;
+        let bytes = encoded_message.len();
+        sender
+            .broadcast(encoded_message)
+            .await
             .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?;
 
+        if let Some(counters) = self.topic_stats.write().await.get_mut(topic_id) {
+            counters.record_out(bytes);
+        }
+
         // 更新状态
         {
             let mut status = self.status.write().await;
@@ -458,72 +1202,1177 @@ impl P2PNode {
         Ok(())
     }
 
-    /// 发送Agent请求
-    pub async fn send_agent_request(&self, topic_id: &TopicId, agent_id: &str, prompt: &str) -> NodeResult<()> {
-        let message = MessageType::AgentRequest {
-            prompt: prompt.to_string(),
-            agent_id: agent_id.to_string(),
-        };
-        
-        self.send_message(topic_id, message).await
+    /// 发送聊天消息并记录到聊天记录，返回消息ID；暂存到发件箱的消息投递状态为 `Queued`
+    pub async fn send_chat(&self, topic_id: &TopicId, text: &str) -> NodeResult<String> {
+        self.send_chat_message(topic_id, MessageContent::text(text), None)
+            .await
     }
 
-    /// 离开话题
-    pub async fn leave_topic(&self, topic_id: &TopicId) -> NodeResult<()> {
-        let mut topics = self.topics.write().await;
-        if topics.remove(topic_id).is_some() {
-            info!("已离开话题: {}", topic_id);
-            
-            // 更新状态
-            let mut status = self.status.write().await;
-            status.active_topics = topics.len();
-            status.last_activity = chrono::Utc::now();
-            
-            // 移除消息处理器
-            let mut handlers = self.message_handlers.write().await;
-            handlers.remove(topic_id);
-            
-            Ok(())
-        } else {
-            Err(crate::error::NodeError::TopicError(format!(
-                "话题不存在: {}",
-                topic_id
-            )))
+    /// 回复聊天记录中的一条消息，返回消息ID
+    ///
+    /// 话题中有不支持消息串的旧版本节点时按普通聊天消息发送，对方能看到内容但没有回复关系
+    pub async fn send_reply(
+        &self,
+        topic_id: &TopicId,
+        reply_to: &str,
+        text: &str,
+    ) -> NodeResult<String> {
+        let known = self
+            .chat_history
+            .read()
+            .await
+            .get(topic_id, reply_to)
+            .is_some();
+        if !known {
+            return Err(crate::error::NodeError::InvalidMessage(format!(
+                "消息不存在: {}",
+                reply_to
+            )));
         }
+        self.send_chat_message(
+            topic_id,
+            MessageContent::text(text),
+            Some(reply_to.to_string()),
+        )
+        .await
     }
 
-    /// 停止节点
-    pub async fn stop(&self) -> NodeResult<()> {
-        info!("停止P2P节点: {}", self.node_id);
-        
-        // 标记节点为非运行状态
-        {
-            let mut running = self.running.write().await;
-            *running = false;
-        }
-        
-        // 离开所有话题
-        let topics = {
-            let topics_read = self.topics.read().await;
-            topics_read.keys().cloned().collect::<Vec<_>>()
-        };
-        
-        for topic_id in topics {
-            self.leave_topic(&topic_id).await?;
+    /// 发送结构化内容的聊天消息（Markdown、代码块、系统提示），可以同时回复一条消息，返回消息ID
+    ///
+    /// 内容发送前先规范化；话题中有不支持结构化内容的旧版本节点时发送其纯文本表示
+    pub async fn send_content(
+        &self,
+        topic_id: &TopicId,
+        content: MessageContent,
+        reply_to: Option<&str>,
+    ) -> NodeResult<String> {
+        if let Some(reply_to) = reply_to {
+            let known = self
+                .chat_history
+                .read()
+                .await
+                .get(topic_id, reply_to)
+                .is_some();
+            if !known {
+                return Err(crate::error::NodeError::InvalidMessage(format!(
+                    "消息不存在: {}",
+                    reply_to
+                )));
+            }
         }
-        
-        info!("P2P节点已停止");
-        Ok(())
-    }
-
-    /// 获取节点ID
-    pub fn node_id(&self) -> &str {
-        &self.node_id
-    }
-
-    /// 获取密钥
-    pub fn secret_key(&self) -> &SecretKey {
-        &self.secret_key
+        self.send_chat_message(topic_id, content.sanitize(), reply_to.map(str::to_string))
+            .await
+    }
+
+    /// 发送聊天消息或回复并记录到聊天记录
+    async fn send_chat_message(
+        &self,
+        topic_id: &TopicId,
+        content: MessageContent,
+        reply_to: Option<String>,
+    ) -> NodeResult<String> {
+        let id = new_message_id();
+        let text = content.plain_text();
+        let message = if !content.is_text()
+            && self
+                .neighbors_support(topic_id, Capabilities::RICH_CONTENT)
+                .await
+        {
+            MessageType::ChatContent {
+                id: id.clone(),
+                content: content.clone(),
+                reply_to: reply_to.clone(),
+            }
+        } else {
+            match &reply_to {
+                Some(reply_to)
+                    if self
+                        .neighbors_support(topic_id, Capabilities::CHAT_THREADS)
+                        .await =>
+                {
+                    MessageType::ChatReply {
+                        id: id.clone(),
+                        text: text.clone(),
+                        reply_to: reply_to.clone(),
+                    }
+                }
+                _ => MessageType::Chat {
+                    id: id.clone(),
+                    text: text.clone(),
+                },
+            }
+        };
+        let queued = self.send_or_queue(topic_id, message).await?;
+        let state = if queued {
+            DeliveryState::Queued
+        } else {
+            DeliveryState::Sent
+        };
+
+        let mut entry = ChatHistoryEntry {
+            id: id.clone(),
+            topic_id: topic_id.to_string(),
+            from: self.node_id.clone(),
+            text,
+            content: Some(content).filter(|content| !content.is_text()),
+            timestamp: chrono::Utc::now(),
+            outgoing: true,
+            delivery: Some(DeliveryStatus {
+                state,
+                ..Default::default()
+            }),
+            read: true,
+            locked: false,
+            reply_to,
+            reactions: Reactions::new(),
+            mentions: Vec::new(),
+        };
+        let mention_router = self.mention_router();
+        entry.mentions = mention_router.resolve(topic_id, &entry).await;
+        self.chat_history
+            .write()
+            .await
+            .push(*topic_id, entry.clone());
+        mention_router.record(&entry).await;
+        // 本节点（包括经网关发消息的网页客户端）同样可以触发房间机器人
+        let bot_trigger = self.room_bots.write().await.trigger(
+            topic_id,
+            &entry.from,
+            &entry.text,
+            entry.timestamp,
+        );
+        // 通知网页客户端等其他订阅者，使经网关发出的消息在各端同步显示
+        self.events.publish(NodeEvent::ChatSent {
+            topic_id: topic_id.to_string(),
+            entry,
+        });
+        if let Some(trigger) = bot_trigger {
+            self.room_bot_responder().spawn_reply(trigger);
+        }
+        // 记录写入后再补发，使补发结果能更新投递状态
+        if queued {
+            self.flush_outbox(topic_id).await;
+        }
+
+        Ok(id)
+    }
+
+    /// 添加或撤销本节点对聊天消息的表情回应，返回消息当前的全部回应
+    pub async fn react(
+        &self,
+        topic_id: &TopicId,
+        message_id: &str,
+        emoji: &str,
+        removed: bool,
+    ) -> NodeResult<Reactions> {
+        check_emoji(emoji)?;
+        let current = {
+            let history = self.chat_history.read().await;
+            let entry = history.get(topic_id, message_id).ok_or_else(|| {
+                crate::error::NodeError::InvalidMessage(format!("消息不存在: {}", message_id))
+            })?;
+            entry.reactions.clone()
+        };
+        let reacted = current
+            .get(emoji)
+            .is_some_and(|peers| peers.contains(&self.node_id));
+        // 没有变化时不发送
+        if reacted != removed {
+            return Ok(current);
+        }
+
+        let message = MessageType::Reaction {
+            message_id: message_id.to_string(),
+            emoji: emoji.to_string(),
+            removed,
+        };
+        let queued = self.send_or_queue(topic_id, message).await?;
+        let reactions = self
+            .chat_history
+            .write()
+            .await
+            .react(topic_id, message_id, &self.node_id, emoji, removed)
+            .unwrap_or(current);
+        self.events.publish(NodeEvent::ReactionChanged {
+            topic_id: topic_id.to_string(),
+            message_id: message_id.to_string(),
+            from: self.node_id.clone(),
+            emoji: emoji.to_string(),
+            removed,
+            reactions: reactions.clone(),
+        });
+        if queued {
+            self.flush_outbox(topic_id).await;
+        }
+        Ok(reactions)
+    }
+
+    /// 获取消息所在的消息串：根消息及其全部回复（按时间顺序）
+    pub async fn get_thread(
+        &self,
+        topic_id: &TopicId,
+        message_id: &str,
+    ) -> NodeResult<Vec<ChatHistoryEntry>> {
+        self.chat_history
+            .read()
+            .await
+            .thread(topic_id, message_id)
+            .ok_or_else(|| {
+                crate::error::NodeError::InvalidMessage(format!("消息不存在: {}", message_id))
+            })
+    }
+
+    /// 话题的邻居是否都支持某项能力，尚未交换 `NodeInfo` 的邻居按基础能力对待
+    async fn neighbors_support(&self, topic_id: &TopicId, capability: Capabilities) -> bool {
+        let neighbors = self
+            .neighbors
+            .read()
+            .await
+            .get(topic_id)
+            .cloned()
+            .unwrap_or_default();
+        let peers = self.peers.read().await;
+        neighbors.iter().all(|peer| {
+            peers
+                .get(peer)
+                .map_or(Capabilities::baseline().contains(capability), |info| {
+                    info.supports(capability)
+                })
+        })
+    }
+
+    /// 处理聊天输入：普通文本作为聊天消息发送，以 `/` 开头的输入按命令执行，未知命令返回错误
+    pub async fn send_input(&self, topic_id: &TopicId, input: &str) -> NodeResult<CommandOutcome> {
+        let context = CommandContext {
+            sender: self
+                .name
+                .clone()
+                .unwrap_or_else(|| self.node_id.chars().take(8).collect()),
+        };
+        let action = self.commands.read().await.parse(input, &context)?;
+        match action {
+            CommandAction::Chat { text } => {
+                if text.trim().is_empty() {
+                    return Err(crate::error::NodeError::InvalidMessage(
+                        "消息不能为空".to_string(),
+                    ));
+                }
+                let message_id = self.send_chat(topic_id, &text).await?;
+                Ok(CommandOutcome::Sent { message_id })
+            }
+            CommandAction::AgentRequest { agent_id, prompt } => {
+                let handle = self
+                    .send_agent_request(
+                        topic_id,
+                        &agent_id,
+                        &prompt,
+                        AgentRequestMode::Broadcast,
+                        None,
+                    )
+                    .await?;
+                Ok(CommandOutcome::AgentRequested {
+                    request_id: handle.request_id().to_string(),
+                })
+            }
+            CommandAction::Share { path } => {
+                let metadata = tokio::fs::metadata(&path).await.map_err(|e| {
+                    crate::error::NodeError::InvalidMessage(format!(
+                        "无法分享 {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                if !metadata.is_file() {
+                    return Err(crate::error::NodeError::InvalidMessage(format!(
+                        "不是文件: {}",
+                        path.display()
+                    )));
+                }
+                Ok(CommandOutcome::Share { path })
+            }
+            CommandAction::Rename { label } => {
+                self.set_topic_label(topic_id, Some(label.clone())).await?;
+                Ok(CommandOutcome::Renamed { label })
+            }
+            CommandAction::Reply { text } => Ok(CommandOutcome::Reply { text }),
+        }
+    }
+
+    /// 注册自定义聊天命令，同名命令会被替换
+    pub async fn register_command(&self, command: impl ChatCommand + 'static) {
+        self.commands.write().await.register(command);
+    }
+
+    /// 将收到的聊天消息标记为已读，并向话题发送已读确认
+    pub async fn mark_read(&self, topic_id: &TopicId, message_id: &str) -> NodeResult<()> {
+        if !self
+            .chat_history
+            .write()
+            .await
+            .mark_read(topic_id, message_id)
+        {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "消息不存在: {}",
+                message_id
+            )));
+        }
+        self.read_tracker()
+            .marked(topic_id, &[message_id.to_string()], None)
+            .await;
+
+        let ack = MessageType::Ack {
+            message_id: message_id.to_string(),
+            kind: AckKind::Read,
+        };
+        self.send_message(topic_id, ack).await
+    }
+
+    /// 将房间中直到并包括指定消息的全部消息标记为已读，未指定时标记到最新一条收到的消息。
+    /// 有新标记的消息时向话题发送该消息的已读确认，本人其他设备据此同步已读状态。返回新标记的条数
+    pub async fn mark_room_read(
+        &self,
+        topic_id: &TopicId,
+        message_id: Option<&str>,
+    ) -> NodeResult<usize> {
+        let latest = self.chat_history.read().await.latest_incoming(topic_id);
+        let Some(message_id) = message_id.map(str::to_string).or(latest) else {
+            return Ok(0);
+        };
+        let marked = self
+            .read_tracker()
+            .mark_until(topic_id, &message_id, None)
+            .await
+            .ok_or_else(|| {
+                crate::error::NodeError::TopicError(format!("消息不存在: {}", message_id))
+            })?;
+        if marked > 0 {
+            let ack = MessageType::Ack {
+                message_id,
+                kind: AckKind::Read,
+            };
+            self.send_message(topic_id, ack).await?;
+        }
+        Ok(marked)
+    }
+
+    /// 各房间的未读消息数、未读提及数与最后读到的消息，用于角标与未读提示
+    pub async fn get_unread_summary(&self) -> UnreadSummary {
+        let topic_ids: Vec<TopicId> = self.topics.read().await.keys().copied().collect();
+        let tracker = self.read_tracker();
+        let mut rooms = Vec::with_capacity(topic_ids.len());
+        for topic_id in &topic_ids {
+            rooms.push(tracker.room(topic_id).await);
+        }
+        UnreadSummary::new(rooms)
+    }
+
+    /// 本节点被提及的记录，最新的在前，可以只看某个话题或只看未读
+    pub async fn get_mentions(
+        &self,
+        topic_id: Option<&TopicId>,
+        unread_only: bool,
+    ) -> Vec<MentionRecord> {
+        let topic_id = topic_id.map(TopicId::to_string);
+        self.mentions
+            .read()
+            .await
+            .list(&self.node_id, topic_id.as_deref(), unread_only)
+    }
+
+    /// 本节点的未读提及数，按话题分列
+    pub async fn unread_mentions(&self) -> MentionSummary {
+        self.mentions.read().await.summary(&self.node_id)
+    }
+
+    /// 将本节点被提及的记录标记为已读；不指定话题时标记全部，不指定消息时标记话题中的全部，
+    /// 返回新标记的条数。只影响通知，不向话题发送已读确认
+    pub async fn mark_mentions_read(
+        &self,
+        topic_id: Option<&TopicId>,
+        message_id: Option<&str>,
+    ) -> usize {
+        let topic_id = topic_id.map(TopicId::to_string);
+        self.mentions
+            .write()
+            .await
+            .mark_read(&self.node_id, topic_id.as_deref(), message_id)
+    }
+
+    /// 数据保留策略
+    pub fn retention_policy(&self) -> &RetentionPolicy {
+        &self.config.retention
+    }
+
+    /// 立即按配置的保留策略删除过期数据
+    pub async fn apply_retention(&self) -> NodeResult<PurgeReport> {
+        apply_retention(
+            &self.agent_manager,
+            &self.chat_history,
+            &self.config.retention,
+        )
+        .await
+    }
+
+    /// 删除调用方的全部数据：Agent会话与对话历史、该节点发来的聊天记录，以及带有该调用方ID的日志。
+    /// P2P请求的调用方ID为对方的节点ID
+    pub async fn purge_user_data(&self, caller_id: &str) -> NodeResult<PurgeReport> {
+        let mut report = self
+            .agent_manager
+            .read()
+            .await
+            .purge_user_data(caller_id)
+            .await?;
+        report.messages += self
+            .chat_history
+            .write()
+            .await
+            .retain(|entry| entry.from != caller_id);
+        report.log_entries += LogBuffer::global()
+            .retain(|entry| entry.fields.get(CALLER_ID_KEY).map(String::as_str) != Some(caller_id));
+        Ok(report)
+    }
+
+    /// 获取话题的聊天记录（按时间顺序），可限制返回最近的条数
+    pub async fn get_chat_history(
+        &self,
+        topic_id: &TopicId,
+        limit: Option<usize>,
+    ) -> Vec<ChatHistoryEntry> {
+        self.chat_history.read().await.entries(topic_id, limit)
+    }
+
+    /// 分页获取话题的聊天记录，超过单页上限时返回错误，过长的消息被截断
+    pub async fn get_chat_history_page(
+        &self,
+        topic_id: &TopicId,
+        request: &PageRequest,
+    ) -> NodeResult<HistoryPage<ChatHistoryEntry>> {
+        let limits = &self.config.history_limits;
+        let request = limits.apply(request)?;
+        let entries = self.chat_history.read().await.entries(topic_id, None);
+        Ok(limits.truncate(request.paginate(entries, |entry| entry.timestamp)))
+    }
+
+    /// 获取话题聊天记录的摘要：收发数量与最近几条消息
+    pub async fn get_chat_history_summary(
+        &self,
+        topic_id: &TopicId,
+    ) -> HistorySummary<ChatHistoryEntry> {
+        let entries = self.chat_history.read().await.entries(topic_id, None);
+        self.config.history_limits.summarize(
+            entries,
+            |entry| entry.timestamp,
+            |entry| {
+                if entry.outgoing {
+                    "outgoing"
+                } else {
+                    "incoming"
+                }
+                .to_string()
+            },
+        )
+    }
+
+    /// 用口令加密导出房间的聊天记录密钥，用于备份或在其他设备上解密聊天记录
+    pub async fn export_room_key(
+        &self,
+        topic_id: &TopicId,
+        passphrase: &str,
+    ) -> NodeResult<String> {
+        let mut history = self.chat_history.write().await;
+        let log = history.log_mut().ok_or_else(chat_log_disabled)?;
+        log.export_key(&topic_id.to_string(), passphrase)
+    }
+
+    /// 导入房间的聊天记录密钥，并解密本地已锁定的消息，返回恢复或解锁的消息条数
+    pub async fn import_room_key(
+        &self,
+        topic_id: &TopicId,
+        key: &str,
+        passphrase: &str,
+    ) -> NodeResult<usize> {
+        let mut history = self.chat_history.write().await;
+        let log = history.log_mut().ok_or_else(chat_log_disabled)?;
+        log.import_key(&topic_id.to_string(), key, passphrase)?;
+        history.restore(topic_id)
+    }
+
+    /// 获取已交换过节点信息的对等节点
+    pub async fn get_peers(&self) -> Vec<PeerInfo> {
+        self.peers.read().await.values().cloned().collect()
+    }
+
+    /// 获取地址簿中的所有节点
+    pub async fn get_address_book(&self) -> Vec<PeerRecord> {
+        self.address_book.read().await.list()
+    }
+
+    /// 获取节点的信任级别
+    pub async fn peer_trust(&self, node_id: &str) -> TrustLevel {
+        self.address_book.read().await.trust_of(node_id)
+    }
+
+    /// 设置节点的信任级别与名称
+    pub async fn set_peer_trust(
+        &self,
+        node_id: &str,
+        trust: TrustLevel,
+        name: Option<String>,
+    ) -> NodeResult<PeerRecord> {
+        self.address_book
+            .write()
+            .await
+            .set_trust(node_id, trust, name)
+    }
+
+    /// 从地址簿删除节点
+    pub async fn remove_peer(&self, node_id: &str) -> NodeResult<PeerRecord> {
+        self.address_book.write().await.remove(node_id)
+    }
+
+    /// 计算与对等节点之间的安全码
+    pub fn safety_number(&self, peer_id: &str) -> NodeResult<SafetyNumber> {
+        let peer: PublicKey = peer_id
+            .parse()
+            .map_err(|e| crate::error::NodeError::VerifyError(format!("解析节点ID失败: {}", e)))?;
+        Ok(SafetyNumber::new(&self.secret_key.public(), &peer))
+    }
+
+    /// 用户核对安全码后确认验证：在地址簿中标记为已验证，并在话题中通知对方
+    pub async fn confirm_verification(
+        &self,
+        peer_id: &str,
+        topic_id: Option<&TopicId>,
+    ) -> NodeResult<PeerRecord> {
+        let safety_number = self.safety_number(peer_id)?;
+        let peer = self.address_book.write().await.mark_verified(peer_id)?;
+        info!("已验证节点: {}", peer_id);
+
+        if let Some(topic_id) = topic_id {
+            let confirm = MessageType::VerifyConfirm {
+                peer_id: peer_id.to_string(),
+                digest: safety_number.digest(),
+            };
+            broadcast_signed(
+                &self.topics,
+                &self.topic_stats,
+                &self.secret_key,
+                topic_id,
+                &confirm,
+            )
+            .await?;
+        }
+        Ok(peer)
+    }
+
+    /// 扫描对方的验证二维码，安全码一致时确认验证
+    pub async fn verify_scanned(
+        &self,
+        payload: &str,
+        topic_id: Option<&TopicId>,
+    ) -> NodeResult<PeerRecord> {
+        let scanned: ScannedCode = payload.parse()?;
+        if !self
+            .safety_number(&scanned.node_id)?
+            .matches_scanned(payload)?
+        {
+            return Err(crate::error::NodeError::VerifyError(
+                "安全码不一致，对方身份可能被冒充".to_string(),
+            ));
+        }
+        self.confirm_verification(&scanned.node_id, topic_id).await
+    }
+
+    /// 是否自动接收该节点的文件分享
+    pub async fn should_auto_accept_share(&self, node_id: &str) -> bool {
+        self.config
+            .trust_policy
+            .auto_accepts_share(self.peer_trust(node_id).await)
+    }
+
+    /// 获取对等节点的协议信息，尚未收到其节点信息时返回None
+    pub async fn get_peer_info(&self, peer: &PublicKey) -> Option<PeerInfo> {
+        self.peers.read().await.get(peer).cloned()
+    }
+
+    /// 按寻址方式发送Agent请求。
+    /// 返回的句柄可以等待结果；无论是否等待，结果都会以 `AgentRequestCompleted` 事件发布
+    pub async fn send_agent_request(
+        &self,
+        topic_id: &TopicId,
+        agent_id: &str,
+        prompt: &str,
+        mode: AgentRequestMode,
+        timeout: Option<Duration>,
+    ) -> NodeResult<AgentRequestHandle> {
+        mode.validate()?;
+        if let AgentRequestMode::Targeted { node_id } = &mode {
+            let target: PublicKey = node_id.parse().map_err(|e| {
+                crate::error::NodeError::AgentError(format!("解析目标节点ID失败: {}", e))
+            })?;
+            if target == self.secret_key.public() {
+                return Err(crate::error::NodeError::AgentError(
+                    "不能向本节点发送定向请求".to_string(),
+                ));
+            }
+            // 已知目标节点不支持带请求ID的消息时提前报错，否则只能等到超时
+            if let Some(peer) = self.peers.read().await.get(&target) {
+                if !peer.supports(Capabilities::AGENT_ROUTING) {
+                    return Err(crate::error::NodeError::AgentError(format!(
+                        "节点 {} 不支持定向Agent请求",
+                        target.fmt_short()
+                    )));
+                }
+            }
+        }
+
+        let request_id = new_message_id();
+        let (answers_tx, answers_rx) = mpsc::channel(ANSWER_QUEUE_CAPACITY);
+        self.pending_agent_requests
+            .write()
+            .await
+            .insert(request_id.clone(), answers_tx);
+
+        let message = MessageType::AgentQuery {
+            request_id: request_id.clone(),
+            prompt: prompt.to_string(),
+            agent_id: agent_id.to_string(),
+            target: mode.target(),
+        };
+        if let Err(e) = self.send_message(topic_id, message).await {
+            self.pending_agent_requests
+                .write()
+                .await
+                .remove(&request_id);
+            return Err(e);
+        }
+
+        let (outcome_tx, outcome_rx) = oneshot::channel();
+        let pending = self.pending_agent_requests.clone();
+        let events = self.events.clone();
+        let topic_id = *topic_id;
+        let collect = collect_agent_answers(
+            topic_id,
+            request_id.clone(),
+            mode,
+            answers_rx,
+            timeout.unwrap_or(DEFAULT_AGENT_TIMEOUT),
+        );
+        tokio::spawn(async move {
+            let outcome = collect.await;
+            pending.write().await.remove(&outcome.request_id);
+            events.publish(NodeEvent::AgentRequestCompleted {
+                topic_id: topic_id.to_string(),
+                outcome: outcome.clone(),
+            });
+            let _ = outcome_tx.send(outcome);
+        });
+
+        Ok(AgentRequestHandle {
+            request_id,
+            outcome: outcome_rx,
+        })
+    }
+
+    /// 集成回答：向多个节点的Agent发出同一个提示词，再由本地Agent综合各节点的回答
+    pub async fn ensemble_request(
+        &self,
+        topic_id: &TopicId,
+        agent_id: &str,
+        prompt: &str,
+        options: EnsembleOptions,
+    ) -> NodeResult<EnsembleOutcome> {
+        let mut handles = Vec::new();
+        for mode in options.modes()? {
+            handles.push(
+                self.send_agent_request(topic_id, agent_id, prompt, mode, options.timeout())
+                    .await?,
+            );
+        }
+
+        // 各请求在后台并行收集，依次等待只取决于最慢的一个
+        let mut request_ids = Vec::new();
+        let mut answers = Vec::new();
+        let mut timed_out = false;
+        for handle in handles {
+            let outcome = handle.wait().await?;
+            request_ids.push(outcome.request_id);
+            answers.extend(outcome.answers);
+            timed_out |= outcome.timed_out;
+        }
+
+        let successful: Vec<&AgentAnswer> = answers
+            .iter()
+            .filter(|answer| answer.is_success())
+            .collect();
+        let mut ranking = Vec::new();
+        let (synthesis, synthesis_error) = if successful.is_empty() {
+            (None, Some("没有收到可用的回答".to_string()))
+        } else {
+            let synthesizer = options.synthesizer.as_deref().unwrap_or(agent_id);
+            let synthesis_prompt = ensemble::synthesis_prompt(prompt, &successful);
+            match process_agent_request(
+                &self.agent_manager,
+                &self.client_registry,
+                &self.usage,
+                synthesizer,
+                &synthesis_prompt,
+                None,
+            )
+            .await
+            {
+                Ok(response) => {
+                    let (order, synthesis) =
+                        ensemble::parse_synthesis(&response.content, successful.len());
+                    ranking = order
+                        .into_iter()
+                        .map(|index| successful[index].from.clone())
+                        .collect();
+                    (Some(synthesis), None)
+                }
+                Err(e) => {
+                    warn!("综合回答失败: {}", e);
+                    (None, Some(e.to_string()))
+                }
+            }
+        };
+
+        Ok(EnsembleOutcome {
+            request_ids,
+            synthesis,
+            synthesis_error,
+            ranking,
+            answers,
+            timed_out,
+        })
+    }
+
+    /// 已启用的共享记忆及其配置
+    fn shared_memory(&self) -> NodeResult<(&Arc<RwLock<SharedMemory>>, &SharedMemoryConfig)> {
+        match (&self.shared_memory, &self.config.shared_memory) {
+            (Some(memory), Some(config)) => Ok((memory, config)),
+            _ => Err(crate::error::NodeError::ConfigError(
+                "共享记忆未启用".to_string(),
+            )),
+        }
+    }
+
+    /// 把一条事实写入话题的共享记忆并同步给其他成员
+    pub async fn remember(
+        &self,
+        topic_id: &TopicId,
+        text: &str,
+        source: Option<String>,
+    ) -> NodeResult<MemoryEntry> {
+        let (memory, config) = self.shared_memory()?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(crate::error::NodeError::ConfigError(
+                "记忆内容为空".to_string(),
+            ));
+        }
+
+        let embedding = embed_text(&self.client_registry, config, text).await?;
+        let entry = memory.write().await.write(
+            &self.secret_key,
+            &topic_id.to_string(),
+            text.to_string(),
+            source,
+            config.embedding_model.clone(),
+            embedding,
+        )?;
+        self.publish_memory(topic_id, entry.clone()).await?;
+        Ok(entry)
+    }
+
+    /// 删除本节点写入的记忆
+    pub async fn forget(&self, topic_id: &TopicId, id: &str) -> NodeResult<()> {
+        let (memory, _) = self.shared_memory()?;
+        let tombstone = memory
+            .write()
+            .await
+            .forget(&self.secret_key, &topic_id.to_string(), id)?;
+        self.publish_memory(topic_id, tombstone).await
+    }
+
+    /// 话题中的共享记忆
+    pub async fn list_memory(&self, topic_id: &TopicId) -> NodeResult<Vec<MemoryEntry>> {
+        let (memory, _) = self.shared_memory()?;
+        Ok(memory.read().await.list(&topic_id.to_string()))
+    }
+
+    /// 按语义检索话题中的共享记忆
+    pub async fn recall_memory(
+        &self,
+        topic_id: &TopicId,
+        query: &str,
+        limit: usize,
+    ) -> NodeResult<Vec<MemoryMatch>> {
+        let (memory, config) = self.shared_memory()?;
+        let embedding = embed_text(&self.client_registry, config, query).await?;
+        Ok(memory.read().await.recall(
+            &topic_id.to_string(),
+            &config.embedding_model,
+            &embedding,
+            limit,
+            f32::MIN,
+        ))
+    }
+
+    /// 发布本节点的记忆变更
+    async fn publish_memory(&self, topic_id: &TopicId, entry: MemoryEntry) -> NodeResult<()> {
+        self.events.publish(NodeEvent::MemoryChanged {
+            topic_id: topic_id.to_string(),
+            id: entry.id.clone(),
+            author: entry.author.clone(),
+            deleted: entry.deleted,
+        });
+        self.send_message(
+            topic_id,
+            MessageType::MemoryUpdate {
+                entries: vec![entry],
+            },
+        )
+        .await
+    }
+
+    /// 把本地目录作为话题中的同步文件夹，与其他成员的同名文件夹双向同步，返回各文件的同步状态
+    pub async fn start_folder_sync(
+        &self,
+        topic_id: &TopicId,
+        folder: &str,
+        dir: impl Into<PathBuf>,
+    ) -> NodeResult<Vec<FileSyncStatus>> {
+        // 文件夹名称按消息字段校验
+        let request = MessageType::FolderSyncRequest {
+            folder: folder.to_string(),
+        };
+        self.config.message_limits.validate(&request)?;
+        if !self.topics.read().await.contains_key(topic_id) {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )));
+        }
+
+        let mut sync = FolderSync::open(folder, dir, self.config.folder_sync.clone())?;
+        if let Some(scanner) = &self.file_scanner {
+            sync = sync.with_scanner(scanner.clone());
+        }
+        let status = sync.status();
+        let first = {
+            let mut folder_syncs = self.folder_syncs.write().await;
+            let folders = folder_syncs.entry(*topic_id).or_default();
+            if folders.contains_key(folder) {
+                return Err(crate::error::NodeError::ConfigError(format!(
+                    "同步文件夹已存在: {}",
+                    folder
+                )));
+            }
+            folders.insert(folder.to_string(), Arc::new(Mutex::new(sync)));
+            folders.len() == 1
+        };
+        if first {
+            self.spawn_folder_sync(*topic_id);
+        }
+        info!("话题 {} 开始同步文件夹 {}", topic_id, folder);
+
+        // 请求其他成员的文件版本
+        if let Err(e) = self.send_message(topic_id, request).await {
+            warn!("发送文件夹同步请求失败: {}", e);
+        }
+        Ok(status)
+    }
+
+    /// 停止同步文件夹，本地文件与同步状态保留，重新开始时继续
+    pub async fn stop_folder_sync(&self, topic_id: &TopicId, folder: &str) -> NodeResult<()> {
+        let removed = self
+            .folder_syncs
+            .write()
+            .await
+            .get_mut(topic_id)
+            .and_then(|folders| folders.remove(folder));
+        match removed {
+            Some(_) => {
+                info!("话题 {} 停止同步文件夹 {}", topic_id, folder);
+                Ok(())
+            }
+            None => Err(folder_not_found(folder)),
+        }
+    }
+
+    /// 话题中的同步文件夹名称
+    pub async fn folder_syncs(&self, topic_id: &TopicId) -> Vec<String> {
+        let mut folders: Vec<String> = self
+            .folder_syncs
+            .read()
+            .await
+            .get(topic_id)
+            .map(|folders| folders.keys().cloned().collect())
+            .unwrap_or_default();
+        folders.sort();
+        folders
+    }
+
+    /// 同步文件夹中各文件的同步状态
+    pub async fn folder_sync_status(
+        &self,
+        topic_id: &TopicId,
+        folder: &str,
+    ) -> NodeResult<Vec<FileSyncStatus>> {
+        let sync = find_folder(&self.folder_syncs, topic_id, folder)
+            .await
+            .ok_or_else(|| folder_not_found(folder))?;
+        let status = sync.lock().await.status();
+        Ok(status)
+    }
+
+    /// 确认已处理文件的冲突，文件恢复为已同步
+    pub async fn resolve_folder_conflict(
+        &self,
+        topic_id: &TopicId,
+        folder: &str,
+        file: &str,
+    ) -> NodeResult<FileSyncStatus> {
+        let sync = find_folder(&self.folder_syncs, topic_id, folder)
+            .await
+            .ok_or_else(|| folder_not_found(folder))?;
+        let file_name = file.to_string();
+        let (resolved, status) = with_folder(&sync, move |sync| {
+            let resolved = sync.resolve_conflict(&file_name)?;
+            Ok((resolved, sync.file_status(&file_name)))
+        })
+        .await?;
+        let status = status.ok_or_else(|| {
+            crate::error::NodeError::ConfigError(format!("同步文件不存在: {}", file))
+        })?;
+        if resolved {
+            publish_folder_status(&self.events, topic_id, folder, [status.clone()]);
+        }
+        Ok(status)
+    }
+
+    /// 启动话题的文件夹同步任务：定期扫描本地变更并通告，重新请求没有进展的拉取；
+    /// 话题中没有同步文件夹时结束
+    fn spawn_folder_sync(&self, topic_id: TopicId) {
+        let folder_syncs = self.folder_syncs.clone();
+        let topics = self.topics.clone();
+        let topic_stats = self.topic_stats.clone();
+        let secret_key = self.secret_key.clone();
+        let events = self.events.clone();
+        let interval = self.config.folder_sync.scan_interval();
+        self.tasks.spawn(Some(topic_id), "folder-sync", move || {
+            let folder_syncs = folder_syncs.clone();
+            let topics = topics.clone();
+            let topic_stats = topic_stats.clone();
+            let secret_key = secret_key.clone();
+            let events = events.clone();
+            async move {
+                // 上一轮仍在拉取的内容及其偏移量
+                let mut last_fetches: HashMap<(String, String), u64> = HashMap::new();
+                let mut ticker = tokio::time::interval(interval);
+                loop {
+                    ticker.tick().await;
+                    let folders: Vec<(String, Arc<Mutex<FolderSync>>)> =
+                        match folder_syncs.read().await.get(&topic_id) {
+                            Some(folders) if !folders.is_empty() => folders
+                                .iter()
+                                .map(|(name, sync)| (name.clone(), sync.clone()))
+                                .collect(),
+                            _ => break,
+                        };
+
+                    let mut fetches = HashMap::new();
+                    for (folder, sync) in folders {
+                        let scanned = with_folder(&sync, |sync| {
+                            let changed: Vec<FileSyncStatus> = sync
+                                .scan()?
+                                .iter()
+                                .filter_map(|entry| sync.file_status(&entry.name))
+                                .collect();
+                            Ok((changed, sync.unannounced(), sync.pending_fetches()))
+                        })
+                        .await;
+                        let (changed, entries, pending) = match scanned {
+                            Ok(scanned) => scanned,
+                            Err(e) => {
+                                warn!("扫描同步文件夹 {} 失败: {}", folder, e);
+                                continue;
+                            }
+                        };
+                        publish_folder_status(&events, &topic_id, &folder, changed);
+
+                        // 通告失败（例如暂时没有邻居）时保持待同步，下一轮重试
+                        if !entries.is_empty() {
+                            match announce_folder(
+                                &topics,
+                                &topic_stats,
+                                &secret_key,
+                                &topic_id,
+                                &folder,
+                                entries.clone(),
+                            )
+                            .await
+                            {
+                                Ok(()) => {
+                                    let announced = with_folder(&sync, move |sync| {
+                                        sync.announced(&entries)?;
+                                        Ok(entries
+                                            .iter()
+                                            .filter_map(|entry| sync.file_status(&entry.name))
+                                            .collect::<Vec<_>>())
+                                    })
+                                    .await;
+                                    match announced {
+                                        Ok(statuses) => publish_folder_status(
+                                            &events, &topic_id, &folder, statuses,
+                                        ),
+                                        Err(e) => {
+                                            warn!("更新同步文件夹 {} 的状态失败: {}", folder, e)
+                                        }
+                                    }
+                                }
+                                Err(e) => {
+                                    debug!("通告同步文件夹 {} 的变更失败，稍后重试: {}", folder, e)
+                                }
+                            }
+                        }
+
+                        // 一轮下来没有进展的拉取重新请求（分块丢失或重启后继续）
+                        for (hash, offset) in pending {
+                            let key = (folder.clone(), hash.clone());
+                            if last_fetches.get(&key) == Some(&offset) {
+                                let request = MessageType::FolderChunkRequest {
+                                    folder: folder.clone(),
+                                    hash,
+                                    offset,
+                                };
+                                if let Err(e) = broadcast_signed(
+                                    &topics,
+                                    &topic_stats,
+                                    &secret_key,
+                                    &topic_id,
+                                    &request,
+                                )
+                                .await
+                                {
+                                    debug!("重新请求同步文件内容失败: {}", e);
+                                }
+                            }
+                            fetches.insert(key, offset);
+                        }
+                    }
+                    last_fetches = fetches;
+                }
+            }
+        });
+    }
+
+    /// 离开话题
+    pub async fn leave_topic(&self, topic_id: &TopicId) -> NodeResult<()> {
+        let mut topics = self.topics.write().await;
+        if topics.remove(topic_id).is_some() {
+            info!("已离开话题: {}", topic_id);
+
+            // 更新状态
+            {
+                let mut status = self.status.write().await;
+                status.active_topics = topics.len();
+                status.last_activity = chrono::Utc::now();
+            }
+            drop(topics);
+
+            // 移除消息处理器并取消话题的后台任务
+            let mut handlers = self.message_handlers.write().await;
+            handlers.remove(topic_id);
+            drop(handlers);
+            self.tasks.cancel_topic(topic_id);
+
+            self.events.publish(NodeEvent::TopicLeft {
+                topic_id: topic_id.to_string(),
+            });
+            self.neighbors.write().await.remove(topic_id);
+            self.agent_peers.write().await.remove(topic_id);
+            self.topic_stats.write().await.remove(topic_id);
+            self.topic_labels.write().await.remove(topic_id);
+            self.chat_history.write().await.remove_topic(topic_id);
+            self.presence.write().await.remove_topic(topic_id);
+            self.room_bots.write().await.remove(topic_id);
+            self.mentions
+                .write()
+                .await
+                .remove_topic(&topic_id.to_string());
+            self.folder_syncs.write().await.remove(topic_id);
+            refresh_peer_count(&self.status, &self.neighbors, &self.events).await;
+
+            Ok(())
+        } else {
+            Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )))
+        }
+    }
+
+    /// 停止节点
+    pub async fn stop(&self) -> NodeResult<()> {
+        info!("停止P2P节点: {}", self.node_id);
+
+        // 标记节点为非运行状态
+        {
+            let mut running = self.running.write().await;
+            *running = false;
+        }
+
+        // 离开所有话题
+        let topics = {
+            let topics_read = self.topics.read().await;
+            topics_read.keys().cloned().collect::<Vec<_>>()
+        };
+
+        for topic_id in topics {
+            self.leave_topic(&topic_id).await?;
+        }
+
+        // 结束所有等待中的Agent请求与后台任务
+        self.pending_agent_requests.write().await.clear();
+        self.tasks.shutdown();
+        self.agent_manager
+            .read()
+            .await
+            .get_tool_manager()
+            .stop_mcp_servers();
+
+        self.events.publish(NodeEvent::Stopped {
+            node_id: self.node_id.clone(),
+        });
+
+        info!("P2P节点已停止");
+        Ok(())
+    }
+
+    /// 各MCP服务器的运行状况
+    pub async fn mcp_server_health(&self) -> Vec<McpServerHealth> {
+        self.agent_manager
+            .read()
+            .await
+            .get_tool_manager()
+            .get_mcp_server_health()
+    }
+
+    /// 获取节点ID
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    /// 获取blob存储，用于把上传的文件导入节点并通过HTTP提供下载
+    pub fn blob_store(&self) -> Store {
+        self.blobs.clone()
+    }
+
+    /// 获取密钥
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
     }
 
     /// 获取Agent管理器
@@ -531,54 +2380,1966 @@ impl P2PNode {
         self.agent_manager.read().await
     }
 
-    /// 获取可变Agent管理器
-    pub async fn get_agent_manager_mut(&self) -> tokio::sync::RwLockWriteGuard<'_, AgentManager> {
-        self.agent_manager.write().await
+    /// 获取可变Agent管理器
+    pub async fn get_agent_manager_mut(&self) -> tokio::sync::RwLockWriteGuard<'_, AgentManager> {
+        self.agent_manager.write().await
+    }
+
+    /// 获取客户端注册表
+    pub fn get_client_registry(&self) -> &ClientRegistry {
+        &self.client_registry
+    }
+
+    /// 提交对Agent回复的反馈
+    pub async fn submit_feedback(
+        &self,
+        response_id: &str,
+        feedback: ResponseFeedback,
+    ) -> NodeResult<ResponseFeedback> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .submit_feedback(response_id, feedback)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("提交反馈失败: {}", e)))
+    }
+
+    /// 以流式方式与本地Agent聊天，每收到一段文本调用一次 `on_chunk`，结束后返回完整响应
+    pub async fn chat_stream<F>(
+        &self,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+        on_chunk: F,
+    ) -> NodeResult<AgentResponse>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.agent_chat()
+            .chat_stream(agent_id, message, options, on_chunk)
+            .await
+    }
+
+    /// 获取调用本地Agent的句柄，长时间的流式调用不必持有节点的锁
+    pub fn agent_chat(&self) -> AgentChat {
+        AgentChat {
+            agent_manager: self.agent_manager.clone(),
+            client_registry: self.client_registry.clone(),
+            usage: self.usage.clone(),
+        }
+    }
+
+    /// 导出Agent带反馈的问答
+    pub async fn export_feedback(&self, agent_id: &str) -> NodeResult<Vec<FeedbackRecord>> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .export_feedback(agent_id)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("导出反馈失败: {}", e)))
+    }
+
+    /// 分页获取Agent摘要
+    pub async fn list_agents_page(
+        &self,
+        request: &PageRequest,
+        sort_by: AgentSortKey,
+    ) -> ListPage<AgentSummary> {
+        self.agent_manager
+            .read()
+            .await
+            .list_agents_page(request, sort_by)
+            .await
+    }
+
+    /// 分页获取Agent的对话历史，超过单页上限时返回错误，过长的消息被截断
+    pub async fn get_agent_messages_page(
+        &self,
+        agent_id: &str,
+        request: &PageRequest,
+    ) -> NodeResult<HistoryPage<AgentMessage>> {
+        let limits = &self.config.history_limits;
+        let request = limits.apply(request)?;
+        let agent_manager = self.agent_manager.read().await;
+        let page = agent_manager
+            .get_conversation_page(agent_id, &request)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("获取对话历史失败: {}", e)))?;
+        Ok(limits.truncate(page))
+    }
+
+    /// 获取Agent对话历史的摘要：各角色的消息数量与最近几条消息
+    pub async fn get_agent_messages_summary(
+        &self,
+        agent_id: &str,
+    ) -> NodeResult<HistorySummary<AgentMessage>> {
+        let agent_manager = self.agent_manager.read().await;
+        let history = agent_manager
+            .get_conversation_history(agent_id)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("获取对话历史失败: {}", e)))?;
+        Ok(self.config.history_limits.summarize(
+            history.messages,
+            |message| message.timestamp,
+            |message| format!("{:?}", message.role).to_lowercase(),
+        ))
+    }
+
+    /// 把Agent导出为包，包文件可通过P2P文件传输分享给其他节点
+    pub async fn export_agent_package(&self, agent_id: &str) -> NodeResult<AgentPackage> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .export_agent_package(agent_id)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("导出Agent包失败: {}", e)))
+    }
+
+    /// 从包重建Agent，返回新Agent的ID
+    pub async fn import_agent_package(
+        &self,
+        package: &AgentPackage,
+        agent_id: Option<String>,
+    ) -> NodeResult<String> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .import_agent_package(package, agent_id)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("导入Agent包失败: {}", e)))
+    }
+
+    /// 加入模板交换的发现话题，以地址簿中的已知节点作为引导节点，并通告本节点分享的模板
+    pub async fn join_template_exchange(&self) -> NodeResult<TopicId> {
+        let topic_id = template_topic();
+        let peers = self
+            .address_book
+            .read()
+            .await
+            .list()
+            .iter()
+            .filter_map(PeerRecord::node_addr)
+            .collect();
+        self.subscribe_topic(topic_id, peers).await?;
+        self.announce_templates().await?;
+        Ok(topic_id)
+    }
+
+    /// 在发现话题中通告本节点分享的模板，未加入发现话题时不做任何事
+    async fn announce_templates(&self) -> NodeResult<()> {
+        let topic_id = template_topic();
+        if !self.topics.read().await.contains_key(&topic_id) {
+            return Ok(());
+        }
+        let templates = self.templates.read().await.shared();
+        if templates.is_empty() {
+            return Ok(());
+        }
+        self.send_message(&topic_id, MessageType::TemplateAnnounce { templates })
+            .await
+    }
+
+    /// 把Agent作为模板分享到发现话题
+    pub async fn share_template(
+        &self,
+        agent_id: &str,
+        description: Option<String>,
+    ) -> NodeResult<TemplateAnnouncement> {
+        let mut package = self.export_agent_package(agent_id).await?;
+        package.description = description;
+        let announcement = self.templates.write().await.share(&package)?;
+        info!(
+            "分享Agent模板 {}（{} 字节）",
+            announcement.name, announcement.size
+        );
+        self.announce_templates().await?;
+        Ok(announcement)
+    }
+
+    /// 停止分享模板，已收到通告的节点仍会保留列表项，但本节点不再应答请求
+    pub async fn unshare_template(&self, hash: &str) -> bool {
+        self.templates.write().await.unshare(hash)
+    }
+
+    /// 发现话题中已通告的模板（含本节点分享的模板）
+    pub async fn list_templates(&self) -> Vec<RemoteTemplate> {
+        self.templates.read().await.list(&self.node_id)
+    }
+
+    /// 按哈希获取模板内容，本节点没有时向发现话题请求
+    pub async fn fetch_template(&self, hash: &str) -> NodeResult<AgentPackage> {
+        let local = self
+            .templates
+            .read()
+            .await
+            .content(hash)
+            .map(<[u8]>::to_vec);
+        let data = match local {
+            Some(data) => data,
+            None => {
+                let waiter = self.templates.write().await.wait_for(hash);
+                let fetch = MessageType::TemplateFetch {
+                    hash: hash.to_string(),
+                };
+                let result = match self.send_message(&template_topic(), fetch).await {
+                    Ok(()) => match tokio::time::timeout(TEMPLATE_FETCH_TIMEOUT, waiter).await {
+                        Ok(Ok(data)) => Ok(data),
+                        _ => Err(crate::error::NodeError::TopicError(format!(
+                            "获取Agent模板超时: {}",
+                            hash
+                        ))),
+                    },
+                    Err(e) => {
+                        drop(waiter);
+                        Err(e)
+                    }
+                };
+                self.templates.write().await.abandon(hash);
+                result?
+            }
+        };
+
+        AgentPackage::from_bytes(&data)
+            .map_err(|e| crate::error::NodeError::DecodeError(format!("解析Agent模板失败: {}", e)))
+    }
+
+    /// 获取模板并导入为本节点的Agent，返回新Agent的ID
+    pub async fn import_template(
+        &self,
+        hash: &str,
+        agent_id: Option<String>,
+    ) -> NodeResult<String> {
+        let package = self.fetch_template(hash).await?;
+        self.import_agent_package(&package, agent_id).await
+    }
+
+    /// 使用本节点的Agent运行评测测试集
+    pub async fn run_eval(&self, suite: &EvalSuite, agents: &[String]) -> NodeResult<EvalReport> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .run_eval(&self.client_registry, suite, agents)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("评测失败: {}", e)))
+    }
+
+    /// 使用本节点的模型对长文档做分块摘要
+    pub async fn summarize_document(
+        &self,
+        document: &str,
+        options: &SummarizeOptions,
+    ) -> NodeResult<DocumentSummary> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .summarize_document(&self.client_registry, document, options)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("文档摘要失败: {}", e)))
+    }
+
+    /// 使用本节点的模型翻译文本
+    pub async fn translate(&self, request: &TranslateRequest) -> NodeResult<Translation> {
+        let agent_manager = self.agent_manager.read().await;
+        agent_manager
+            .translate(&self.client_registry, request)
+            .await
+            .map_err(|e| crate::error::NodeError::AgentError(format!("翻译失败: {}", e)))
+    }
+
+    /// 获取活跃话题列表
+    pub async fn get_active_topics(&self) -> Vec<TopicId> {
+        let topics = self.topics.read().await;
+        topics.keys().cloned().collect()
+    }
+
+    /// 获取话题统计
+    pub async fn get_topic_stats(&self, topic_id: &TopicId) -> Option<TopicStats> {
+        self.topic_stats
+            .read()
+            .await
+            .get(topic_id)
+            .map(|counters| counters.snapshot(topic_id))
+    }
+
+    /// 获取所有活跃话题的统计
+    pub async fn get_all_topic_stats(&self) -> Vec<TopicStats> {
+        self.topic_stats
+            .read()
+            .await
+            .iter()
+            .map(|(topic_id, counters)| counters.snapshot(topic_id))
+            .collect()
+    }
+
+    /// 获取房间活跃度：时间窗口内发过消息的成员，未指定窗口时使用配置的默认窗口
+    pub async fn get_room_liveness(
+        &self,
+        topic_id: &TopicId,
+        window: Option<Duration>,
+    ) -> NodeResult<RoomLiveness> {
+        if !self.topics.read().await.contains_key(topic_id) {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )));
+        }
+        let window = window.unwrap_or_else(|| self.config.presence.active_window());
+        let peers = self.peers.read().await;
+        let address_book = self.address_book.read().await;
+        let names: HashMap<String, String> = address_book
+            .list()
+            .into_iter()
+            .filter_map(|record| Some((record.node_id, record.name?)))
+            .chain(
+                peers
+                    .values()
+                    .filter_map(|peer| Some((peer.node_id.clone(), peer.name.clone()?))),
+            )
+            .collect();
+        Ok(self
+            .presence
+            .read()
+            .await
+            .liveness(topic_id, window, chrono::Utc::now(), |node_id| {
+                names.get(node_id).cloned()
+            }))
+    }
+
+    /// 本节点当前的Agent负载
+    pub async fn agent_load(&self) -> AgentLoad {
+        self.agent_load.read().await.snapshot()
+    }
+
+    /// 节点最近通告的Agent负载，未通告或已过期时为空
+    pub async fn peer_agent_load(&self, node_id: &str) -> NodeResult<Option<AgentLoad>> {
+        let peer: PublicKey = node_id
+            .parse()
+            .map_err(|e| crate::error::NodeError::ConfigError(format!("解析节点ID失败: {}", e)))?;
+        Ok(self.peer_loads.read().await.get(&peer))
+    }
+
+    /// 设置房间机器人，并按配置中的模型与人设创建或更新回复所用的Agent。
+    /// 机器人只在设置了它的节点上运行，房间中其他节点无需设置
+    pub async fn set_room_bot(&self, topic_id: &TopicId, config: RoomBotConfig) -> NodeResult<()> {
+        if !self.topics.read().await.contains_key(topic_id) {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )));
+        }
+        if config.name.trim().is_empty() && config.triggers.is_empty() {
+            return Err(crate::error::NodeError::ConfigError(
+                "房间机器人需要提及名称或触发词".to_string(),
+            ));
+        }
+
+        let agent_manager = self.agent_manager.read().await;
+        if !agent_manager.list_agents().await.contains(&config.agent_id) {
+            agent_manager
+                .create_agent(config.agent_id.clone(), None)
+                .await?;
+        }
+        let agent_config = agent_manager.get_agent_config(&config.agent_id).await?;
+        agent_manager
+            .update_agent_config(&config.agent_id, config.apply_to(agent_config))
+            .await?;
+        drop(agent_manager);
+
+        info!("话题 {} 的机器人使用Agent: {}", topic_id, config.agent_id);
+        self.room_bots.write().await.set(*topic_id, config);
+        Ok(())
+    }
+
+    /// 获取房间机器人配置
+    pub async fn get_room_bot(&self, topic_id: &TopicId) -> Option<RoomBotConfig> {
+        self.room_bots.read().await.get(topic_id)
+    }
+
+    /// 移除房间机器人，回复所用的Agent保留
+    pub async fn remove_room_bot(&self, topic_id: &TopicId) -> bool {
+        self.room_bots.write().await.remove(topic_id)
+    }
+
+    /// 设置成员是否不让房间机器人回复自己的消息
+    pub async fn set_room_bot_opt_out(
+        &self,
+        topic_id: &TopicId,
+        member: &str,
+        opted_out: bool,
+    ) -> NodeResult<()> {
+        if !self
+            .room_bots
+            .write()
+            .await
+            .set_opt_out(topic_id, member, opted_out)
+        {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题 {} 没有设置机器人",
+                topic_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// 补发话题中暂存在发件箱的消息，返回补发成功的消息数；未启用发件箱或话题未连上时为0
+    pub async fn flush_outbox(&self, topic_id: &TopicId) -> usize {
+        match self.outbox_flusher() {
+            Some(flusher) if *self.running.read().await => flusher.flush(topic_id).await,
+            _ => 0,
+        }
+    }
+
+    /// 获取话题中暂存在发件箱的消息，按暂存顺序
+    pub async fn outbox_pending(&self, topic_id: &TopicId) -> NodeResult<Vec<OutboxEntry>> {
+        let outbox = self
+            .outbox
+            .as_ref()
+            .ok_or_else(|| crate::error::NodeError::ConfigError("未启用发件箱".to_string()))?;
+        Ok(outbox.read().await.pending(topic_id))
+    }
+
+    /// 发件箱补发所需的节点状态，未启用发件箱时为空
+    fn outbox_flusher(&self) -> Option<OutboxFlusher> {
+        let outbox = self.outbox.clone()?;
+        Some(OutboxFlusher {
+            outbox,
+            topics: self.topics.clone(),
+            topic_stats: self.topic_stats.clone(),
+            secret_key: self.secret_key.clone(),
+            neighbors: self.neighbors.clone(),
+            chat_history: self.chat_history.clone(),
+            events: self.events.clone(),
+        })
+    }
+
+    /// 提及解析与记录所需的节点状态
+    fn mention_router(&self) -> MentionRouter {
+        MentionRouter {
+            node_id: self.node_id.clone(),
+            name: self.name.clone(),
+            presence: self.presence.clone(),
+            peers: self.peers.clone(),
+            address_book: self.address_book.clone(),
+            mentions: self.mentions.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// 已读状态统计与同步所需的节点状态
+    fn read_tracker(&self) -> ReadTracker {
+        ReadTracker {
+            node_id: self.node_id.clone(),
+            chat_history: self.chat_history.clone(),
+            mentions: self.mentions.clone(),
+            topic_labels: self.topic_labels.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// 房间机器人回复所需的节点状态
+    fn room_bot_responder(&self) -> RoomBotResponder {
+        RoomBotResponder {
+            agent_manager: self.agent_manager.clone(),
+            client_registry: self.client_registry.clone(),
+            usage: self.usage.clone(),
+            topics: self.topics.clone(),
+            topic_stats: self.topic_stats.clone(),
+            secret_key: self.secret_key.clone(),
+            chat_history: self.chat_history.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// 分页获取已加入的话题（房间），按加入时间排序
+    pub async fn list_topics_page(&self, request: &PageRequest) -> ListPage<TopicStats> {
+        let topics = self.get_all_topic_stats().await;
+        request.paginate(topics, |stats| {
+            stats
+                .joined_at
+                .unwrap_or(chrono::DateTime::<chrono::Utc>::MIN_UTC)
+        })
+    }
+
+    /// 获取用量汇总，包括各Agent的令牌数与费用以及各话题的流量
+    pub async fn get_usage_summary(&self) -> UsageSummary {
+        let agent_stats = self.agent_manager.read().await.get_all_agent_stats().await;
+        let topics = self.get_all_topic_stats().await;
+        self.usage.summary(&agent_stats, topics)
+    }
+
+    /// 获取最近 `limit` 个时间窗口的用量，按时间升序排列
+    pub fn get_usage_series(&self, window: StatsWindow, limit: Option<usize>) -> Vec<UsageBucket> {
+        self.usage.series(window, limit)
+    }
+
+    /// 设置话题名称（房间名称），传入 None 清除
+    pub async fn set_topic_label(
+        &self,
+        topic_id: &TopicId,
+        label: Option<String>,
+    ) -> NodeResult<()> {
+        if !self.topics.read().await.contains_key(topic_id) {
+            return Err(crate::error::NodeError::TopicError(format!(
+                "话题不存在: {}",
+                topic_id
+            )));
+        }
+
+        let mut labels = self.topic_labels.write().await;
+        match label {
+            Some(label) => labels.insert(*topic_id, label),
+            None => labels.remove(topic_id),
+        };
+        Ok(())
+    }
+
+    /// 获取话题名称
+    pub async fn get_topic_label(&self, topic_id: &TopicId) -> Option<String> {
+        self.topic_labels.read().await.get(topic_id).cloned()
+    }
+
+    /// 导出节点身份、已加入话题及话题元数据为加密配置包
+    pub async fn export_bundle(&self, passphrase: &str) -> NodeResult<String> {
+        let mut topics = Vec::new();
+        for topic_id in self.get_active_topics().await {
+            topics.push(BundleTopic {
+                topic_id: topic_id.to_string(),
+                ticket: self.generate_ticket(topic_id).await?,
+                label: self.get_topic_label(&topic_id).await,
+                joined_at: self
+                    .topic_stats
+                    .read()
+                    .await
+                    .get(&topic_id)
+                    .and_then(|counters| counters.joined_at),
+            });
+        }
+
+        let bundle = NodeBundle {
+            secret_key: encode_secret_key(&self.secret_key),
+            name: self.name.clone(),
+            relay: self.config.relay.as_ref().map(|url| url.to_string()),
+            no_relay: self.config.no_relay,
+            proxy: self.config.proxy.clone(),
+            privacy_mode: self.config.privacy_mode,
+            topics,
+            exported_at: chrono::Utc::now(),
+        };
+
+        info!("导出节点配置包，包含 {} 个话题", bundle.topics.len());
+        bundle.encrypt(passphrase)
+    }
+
+    /// 从配置包重新加入话题并恢复话题名称，单个话题失败不影响其他话题
+    pub async fn restore_topics(&self, bundle: &NodeBundle) -> Vec<TopicId> {
+        let mut restored = Vec::new();
+        for topic in &bundle.topics {
+            match self.join_topic(None, Some(&topic.ticket)).await {
+                Ok((topic_id, _)) => {
+                    if topic.label.is_some() {
+                        let _ = self.set_topic_label(&topic_id, topic.label.clone()).await;
+                    }
+                    restored.push(topic_id);
+                }
+                Err(e) => warn!("恢复话题 {} 失败: {}", topic.topic_id, e),
+            }
+        }
+
+        info!(
+            "已从配置包恢复 {}/{} 个话题",
+            restored.len(),
+            bundle.topics.len()
+        );
+        restored
+    }
+
+    /// 从加密配置包创建并启动节点，然后恢复话题
+    pub async fn from_bundle(encoded: &str, passphrase: &str, bind_port: u16) -> NodeResult<Self> {
+        let bundle = NodeBundle::decrypt(encoded, passphrase)?;
+
+        let mut node = Self::new(bundle.node_config(bind_port)?).await?;
+        if let Some(name) = &bundle.name {
+            node.set_name(name.clone());
+        }
+        node.start().await?;
+        node.restore_topics(&bundle).await;
+
+        Ok(node)
+    }
+
+    /// 检查节点是否正在运行
+    pub async fn is_running(&self) -> bool {
+        *self.running.read().await
+    }
+}
+
+/// 按各话题邻居重新统计对等节点数量，变化时发布事件
+/// 按保留策略删除Agent会话、聊天记录与审计日志中的过期数据
+async fn apply_retention(
+    agent_manager: &RwLock<AgentManager>,
+    chat_history: &RwLock<ChatHistory>,
+    policy: &RetentionPolicy,
+) -> NodeResult<PurgeReport> {
+    let mut report = agent_manager.read().await.apply_retention(policy).await?;
+    if let Some(cutoff) = policy.conversation_cutoff() {
+        report.messages += chat_history
+            .write()
+            .await
+            .retain(|entry| entry.timestamp >= cutoff);
+    }
+    if let Some(cutoff) = policy.audit_cutoff() {
+        report.log_entries += LogBuffer::global()
+            .retain(|entry| entry.target != AUDIT_LOG_TARGET || entry.timestamp >= cutoff);
+    }
+    Ok(report)
+}
+
+/// 把服务节点通告的处理状态转为事件，只关心本节点发出且仍在等待的请求，返回是否转发
+async fn forward_agent_status(
+    pending: &PendingAgentRequests,
+    events: &EventBus,
+    topic_id: &TopicId,
+    from: &PublicKey,
+    request_id: String,
+    agent_id: String,
+    status: AgentRequestStatus,
+) -> bool {
+    if !pending.read().await.contains_key(&request_id) {
+        return false;
+    }
+    debug!(
+        "{} 通告Agent请求 {} 的处理状态: {:?}",
+        from.fmt_short(),
+        request_id,
+        status
+    );
+    events.publish(NodeEvent::AgentRequestStatus {
+        topic_id: topic_id.to_string(),
+        request_id,
+        from: from.to_string(),
+        agent_id,
+        status,
+    });
+    true
+}
+
+async fn refresh_peer_count(
+    status: &RwLock<NodeStatus>,
+    neighbors: &TopicNeighbors,
+    events: &EventBus,
+) {
+    let current = {
+        let neighbors = neighbors.read().await;
+        neighbors.values().flatten().collect::<HashSet<_>>().len()
+    };
+
+    let previous = {
+        let mut status = status.write().await;
+        status.last_activity = chrono::Utc::now();
+        std::mem::replace(&mut status.connected_peers, current)
+    };
+
+    if previous != current {
+        debug!("对等节点数量变化: {} -> {}", previous, current);
+        events.publish(NodeEvent::PeerCountChanged { previous, current });
+    }
+}
+
+/// 签名并广播消息到话题，同时记录出站流量
+pub(crate) async fn broadcast_signed(
+    topics: &RwLock<TopicMap>,
+    topic_stats: &TopicCountersMap,
+    secret_key: &SecretKey,
+    topic_id: &TopicId,
+    message: &MessageType,
+) -> NodeResult<()> {
+    let encoded = SignedMessage::sign_and_encode(secret_key, message)?;
+    let bytes = encoded.len();
+
+    let topics = topics.read().await;
+    let (sender, _) = topics
+        .get(topic_id)
+        .ok_or_else(|| crate::error::NodeError::TopicError(format!("话题不存在: {}", topic_id)))?;
+    sender
+        .broadcast(encoded)
+        .await
+        .map_err(|e| crate::error::NodeError::IrohError(e.to_string()))?;
+    drop(topics);
+
+    if let Some(counters) = topic_stats.write().await.get_mut(topic_id) {
+        counters.record_out(bytes);
     }
+    Ok(())
+}
 
-    /// 获取客户端注册表
-    pub fn get_client_registry(&self) -> &ClientRegistry {
-        &self.client_registry
+/// 收集Agent请求的应答，直到满足寻址方式的要求或超时
+async fn collect_agent_answers(
+    topic_id: TopicId,
+    request_id: String,
+    mode: AgentRequestMode,
+    mut answers_rx: mpsc::Receiver<AgentAnswer>,
+    timeout: Duration,
+) -> AgentRequestOutcome {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut answers: Vec<AgentAnswer> = Vec::new();
+    let timed_out = loop {
+        if mode.is_satisfied(&answers) {
+            break false;
+        }
+        match tokio::time::timeout_at(deadline, answers_rx.recv()).await {
+            Ok(Some(answer)) => {
+                // 每个节点只计一次
+                if answers.iter().any(|existing| existing.from == answer.from) {
+                    continue;
+                }
+                answers.push(answer);
+            }
+            Ok(None) | Err(_) => break true,
+        }
+    };
+    if timed_out {
+        warn!(
+            "Agent请求 {} 超时，收到 {} 个应答",
+            request_id,
+            answers.len()
+        );
     }
-    
-    /// 获取活跃话题列表
-    pub async fn get_active_topics(&self) -> Vec<TopicId> {
-        let topics = self.topics.read().await;
-        topics.keys().cloned().collect()
+    AgentRequestOutcome::new(request_id, topic_id.to_string(), mode, answers, timed_out)
+}
+
+fn chat_log_disabled() -> crate::error::NodeError {
+    crate::error::NodeError::ConfigError("聊天记录存储未启用".to_string())
+}
+
+/// 分批通告同步文件夹的文件版本
+async fn announce_folder(
+    topics: &RwLock<TopicMap>,
+    topic_stats: &TopicCountersMap,
+    secret_key: &SecretKey,
+    topic_id: &TopicId,
+    folder: &str,
+    entries: Vec<FolderSyncEntry>,
+) -> NodeResult<()> {
+    for update in folder_sync::folder_updates(folder, &entries) {
+        broadcast_signed(topics, topic_stats, secret_key, topic_id, &update).await?;
     }
-    
-    /// 检查节点是否正在运行
-    pub async fn is_running(&self) -> bool {
-        *self.running.read().await
+    Ok(())
+}
+
+/// 本节点的节点信息消息
+fn node_info(name: Option<String>) -> MessageType {
+    MessageType::NodeInfo {
+        name,
+        version: PROTOCOL_VERSION,
+        capabilities: Capabilities::local(),
+    }
+}
+
+/// 计算文本的嵌入向量
+async fn embed_text(
+    client_registry: &ClientRegistry,
+    config: &SharedMemoryConfig,
+    text: &str,
+) -> NodeResult<Vec<f32>> {
+    let embedding = client_registry
+        .embed(&config.embedding_provider, &config.embedding_model, text)
+        .await
+        .map_err(|e| crate::error::NodeError::AgentError(format!("计算嵌入向量失败: {}", e)))?;
+    Ok(embedding.into_iter().map(|value| value as f32).collect())
+}
+
+/// 检索与提示词相关的共享记忆并附加到提示词前，失败时使用原提示词
+async fn recall_context(
+    memory: &RwLock<SharedMemory>,
+    config: &SharedMemoryConfig,
+    client_registry: &ClientRegistry,
+    topic_id: &str,
+    prompt: &str,
+) -> String {
+    let embedding = match embed_text(client_registry, config, prompt).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            warn!("检索共享记忆失败: {}", e);
+            return prompt.to_string();
+        }
+    };
+    let matches = memory.read().await.recall(
+        topic_id,
+        &config.embedding_model,
+        &embedding,
+        config.recall_limit,
+        config.min_score,
+    );
+    memory::with_context(prompt, &matches)
+}
+
+/// 话题消息处理器的共享状态
+///
+/// 每个话题构建一次，处理器重启、各条消息与处理Agent请求的任务共享同一个 `Arc`，
+/// 不再为每条消息逐个克隆节点状态
+struct TopicHandler {
+    topic_id: TopicId,
+    /// 是否为模板发现话题
+    is_template_topic: bool,
+    secret_key: SecretKey,
+    name: Option<String>,
+    agent_manager: Arc<RwLock<AgentManager>>,
+    client_registry: Arc<ClientRegistry>,
+    topics: Arc<RwLock<TopicMap>>,
+    topic_stats: TopicCountersMap,
+    events: EventBus,
+    chat_history: Arc<RwLock<ChatHistory>>,
+    peers: PeerInfoMap,
+    address_book: Arc<RwLock<AddressBook>>,
+    trust_policy: TrustPolicy,
+    agent_peers: TopicNeighbors,
+    agent_claims: Arc<RwLock<ClaimTracker>>,
+    pending_agent_requests: PendingAgentRequests,
+    shared_memory: Option<Arc<RwLock<SharedMemory>>>,
+    memory_config: Option<SharedMemoryConfig>,
+    usage: Arc<UsageLedger>,
+    templates: Arc<RwLock<TemplateExchange>>,
+    room_bots: Arc<RwLock<RoomBots>>,
+    bot_responder: RoomBotResponder,
+    agent_load: Arc<RwLock<LoadTracker>>,
+    peer_loads: Arc<RwLock<PeerLoads>>,
+    folder_syncs: FolderSyncMap,
+    mention_router: MentionRouter,
+    read_tracker: ReadTracker,
+    own_devices: Vec<String>,
+}
+
+impl TopicHandler {
+    /// 签名并广播消息到本话题
+    async fn broadcast(&self, message: &MessageType) -> NodeResult<()> {
+        broadcast_signed(
+            &self.topics,
+            &self.topic_stats,
+            &self.secret_key,
+            &self.topic_id,
+            message,
+        )
+        .await
+    }
+
+    /// 依次广播功能模块处理消息后返回的回复
+    async fn broadcast_replies(
+        &self,
+        replies: impl IntoIterator<Item = MessageType>,
+        feature: &str,
+    ) {
+        for reply in replies {
+            if let Err(e) = self.broadcast(&reply).await {
+                warn!("发送{}消息失败: {}", feature, e);
+            }
+        }
+    }
+
+    /// 向请求方通告Agent请求的处理状态
+    async fn send_agent_status(
+        &self,
+        request_id: &str,
+        agent_id: &str,
+        status: AgentRequestStatus,
+    ) {
+        let message = MessageType::AgentStatus {
+            request_id: request_id.to_string(),
+            agent_id: agent_id.to_string(),
+            status,
+        };
+        if let Err(e) = self.broadcast(&message).await {
+            warn!("通告Agent请求 {} 的处理状态失败: {}", request_id, e);
+        }
+    }
+
+    /// 向话题通告本节点的Agent负载，话题中没有支持负载通告的Agent节点时不发送
+    async fn report_load(&self, load: AgentLoad) {
+        let supported = {
+            let peers = self.peers.read().await;
+            self.agent_peers
+                .read()
+                .await
+                .get(&self.topic_id)
+                .is_some_and(|candidates| {
+                    candidates.iter().any(|peer| {
+                        peers
+                            .get(peer)
+                            .is_some_and(|info| info.supports(Capabilities::AGENT_LOAD))
+                    })
+                })
+        };
+        if !supported {
+            return;
+        }
+        if let Err(e) = self.broadcast(&MessageType::LoadReport { load }).await {
+            warn!("通告Agent负载失败: {}", e);
+        }
+    }
+
+    /// 处理一条已验证的消息
+    async fn handle(self: &Arc<Self>, from: PublicKey, message: MessageType) {
+        // Agent请求的响应者范围，以及应答需要关联的请求ID
+        let (target, reply_to) = match &message {
+            MessageType::AgentFanOutRequest { .. } => (AgentTarget::All, None),
+            MessageType::AgentQuery {
+                request_id, target, ..
+            } => (target.clone(), Some(request_id.clone())),
+            _ => (AgentTarget::Elected, None),
+        };
+        // 回复与结构化内容按聊天消息处理，另外记录被回复的消息ID与内容
+        let (message, replied_to, content) = match message {
+            MessageType::ChatReply { id, text, reply_to } => {
+                (MessageType::Chat { id, text }, Some(reply_to), None)
+            }
+            MessageType::ChatContent {
+                id,
+                content,
+                reply_to,
+            } => {
+                let text = content.plain_text();
+                (
+                    MessageType::Chat { id, text },
+                    reply_to,
+                    Some(content).filter(|content| !content.is_text()),
+                )
+            }
+            message => (message, None, None),
+        };
+
+        match message {
+            MessageType::Chat { id, text } => {
+                debug!("收到聊天消息: {}", text);
+                let mut entry = ChatHistoryEntry {
+                    id: id.clone(),
+                    topic_id: self.topic_id.to_string(),
+                    from: from.to_string(),
+                    text,
+                    content,
+                    timestamp: chrono::Utc::now(),
+                    outgoing: false,
+                    delivery: None,
+                    read: false,
+                    locked: false,
+                    reply_to: replied_to,
+                    reactions: Reactions::new(),
+                    mentions: Vec::new(),
+                };
+                entry.mentions = self.mention_router.resolve(&self.topic_id, &entry).await;
+                if !self
+                    .chat_history
+                    .write()
+                    .await
+                    .push(self.topic_id, entry.clone())
+                {
+                    return;
+                }
+                self.mention_router.record(&entry).await;
+                let trust = self.address_book.read().await.trust_of(&entry.from);
+                let bot_trigger = self.room_bots.write().await.trigger(
+                    &self.topic_id,
+                    &entry.from,
+                    &entry.text,
+                    entry.timestamp,
+                );
+                self.events.publish(NodeEvent::ChatReceived {
+                    topic_id: self.topic_id.to_string(),
+                    entry,
+                    trust,
+                });
+
+                // 房间机器人被提及或命中触发词时回复
+                if let Some(trigger) = bot_trigger {
+                    self.bot_responder.spawn_reply(trigger);
+                }
+
+                // 对方不支持确认时不回复，避免其无法解析
+                let supports_ack = self
+                    .peers
+                    .read()
+                    .await
+                    .get(&from)
+                    .is_none_or(|peer| peer.supports(Capabilities::CHAT_ACK));
+                if !supports_ack {
+                    return;
+                }
+
+                // 回复送达确认
+                let ack = MessageType::Ack {
+                    message_id: id,
+                    kind: AckKind::Delivered,
+                };
+                if let Err(e) = self.broadcast(&ack).await {
+                    warn!("发送送达确认失败: {}", e);
+                }
+            }
+            // 已在上面转换为聊天消息
+            MessageType::ChatReply { .. } | MessageType::ChatContent { .. } => {}
+            MessageType::Reaction {
+                message_id,
+                emoji,
+                removed,
+            } => {
+                let reactions = self.chat_history.write().await.react(
+                    &self.topic_id,
+                    &message_id,
+                    &from.to_string(),
+                    &emoji,
+                    removed,
+                );
+                if let Some(reactions) = reactions {
+                    self.events.publish(NodeEvent::ReactionChanged {
+                        topic_id: self.topic_id.to_string(),
+                        message_id,
+                        from: from.to_string(),
+                        emoji,
+                        removed,
+                        reactions,
+                    });
+                }
+            }
+            MessageType::Ack { message_id, kind } => {
+                debug!(
+                    "收到 {} 对消息 {} 的确认: {:?}",
+                    from.fmt_short(),
+                    message_id,
+                    kind
+                );
+                // 本人其他设备读过的消息在本节点同样标记为已读，同步时不再发送确认，避免来回同步
+                if kind == AckKind::Read && self.own_devices.contains(&from.to_string()) {
+                    self.read_tracker
+                        .mark_until(&self.topic_id, &message_id, Some(from.to_string()))
+                        .await;
+                }
+                let status = self.chat_history.write().await.record_ack(
+                    &self.topic_id,
+                    &message_id,
+                    &from.to_string(),
+                    kind,
+                );
+                if let Some(status) = status {
+                    self.events.publish(NodeEvent::DeliveryStatusChanged {
+                        topic_id: self.topic_id.to_string(),
+                        message_id,
+                        status,
+                    });
+                }
+            }
+            MessageType::AgentRequest { prompt, agent_id }
+            | MessageType::AgentFanOutRequest { prompt, agent_id }
+            | MessageType::AgentQuery {
+                prompt, agent_id, ..
+            } => {
+                debug!(
+                    "收到Agent请求: {}, agent_id: {}, target: {:?}",
+                    prompt, agent_id, target
+                );
+
+                // 指定了其他节点的请求不处理
+                if let AgentTarget::Node(node_id) = &target {
+                    if *node_id != self.secret_key.public().to_string() {
+                        return;
+                    }
+                }
+
+                // 信任级别不足的节点不能使用本节点的Agent
+                let authorized = self
+                    .address_book
+                    .read()
+                    .await
+                    .authorize_agent(&from.to_string(), &self.trust_policy);
+                if let Err(trust) = authorized {
+                    warn!(
+                        "拒绝来自 {} 的Agent请求，信任级别: {:?}",
+                        from.fmt_short(),
+                        trust
+                    );
+                    self.events.publish(NodeEvent::AgentRequestRejected {
+                        topic_id: self.topic_id.to_string(),
+                        from: from.to_string(),
+                        agent_id: agent_id.clone(),
+                        trust,
+                    });
+                    let message = "未授权使用本节点的Agent".to_string();
+                    let error = match reply_to {
+                        Some(request_id) => MessageType::AgentReply {
+                            request_id,
+                            agent_id,
+                            content: None,
+                            error: Some(message),
+                        },
+                        None => MessageType::Error { message },
+                    };
+                    if let Err(e) = self.broadcast(&error).await {
+                        warn!("发送拒绝消息失败: {}", e);
+                    }
+                    return;
+                }
+
+                // 按负载与请求ID选出响应者，排名靠后的节点等待认领超时后再接手
+                let elected = target == AgentTarget::Elected;
+                let request_id = reply_to
+                    .clone()
+                    .unwrap_or_else(|| agent_request_id(&from, &agent_id, &prompt));
+                let rank = if elected {
+                    let candidates = self
+                        .agent_peers
+                        .read()
+                        .await
+                        .get(&self.topic_id)
+                        .cloned()
+                        .unwrap_or_default();
+                    let me = self.secret_key.public();
+                    let my_load = self.agent_load.read().await.snapshot();
+                    let loads = self.peer_loads.read().await;
+                    responder_rank(&me, &from, candidates, &request_id, |node| {
+                        if *node == me {
+                            Some(my_load)
+                        } else {
+                            loads.get(node)
+                        }
+                    })
+                } else {
+                    0
+                };
+
+                // 请求方支持时通告处理状态，只有带请求ID的请求才能关联
+                let report_status = reply_to.is_some()
+                    && self
+                        .peers
+                        .read()
+                        .await
+                        .get(&from)
+                        .is_some_and(|peer| peer.supports(Capabilities::AGENT_STATUS));
+                // 调用方为发起请求的节点，跟踪ID沿用请求ID，便于跨节点对照日志
+                let context = RequestContext::new(RequestOrigin::P2p)
+                    .with_caller_id(from.to_string())
+                    .with_trace_id(request_id.clone());
+
+                // 使用tokio::spawn处理异步请求，避免阻塞消息处理循环；任务与处理器共享同一份状态
+                let handler = self.clone();
+                tokio::spawn(async move {
+                    if elected {
+                        if !claim_after_backoff(&handler.agent_claims, &request_id, rank).await {
+                            debug!("Agent请求 {} 已被其他节点认领", request_id);
+                            return;
+                        }
+                        let claim = MessageType::AgentClaim {
+                            request_id: request_id.clone(),
+                        };
+                        if let Err(e) = handler.broadcast(&claim).await {
+                            warn!("发送Agent请求认领失败: {}", e);
+                        }
+                    }
+
+                    let accepted = Instant::now();
+                    let status_for = reply_to.as_deref().filter(|_| report_status);
+                    if let Some(request_id) = status_for {
+                        handler
+                            .send_agent_status(request_id, &agent_id, AgentRequestStatus::Accepted)
+                            .await;
+                    }
+
+                    // 附加房间共享记忆中的相关内容
+                    let prompt = match (&handler.shared_memory, &handler.memory_config) {
+                        (Some(memory), Some(config)) if config.recall_limit > 0 => {
+                            recall_context(
+                                memory,
+                                config,
+                                &handler.client_registry,
+                                &handler.topic_id.to_string(),
+                                &prompt,
+                            )
+                            .await
+                        }
+                        _ => prompt,
+                    };
+
+                    // 处理Agent请求，开始与结束时通告负载
+                    let started = Instant::now();
+                    let load = handler.agent_load.write().await.start();
+                    handler.report_load(load).await;
+                    let work = process_agent_request(
+                        &handler.agent_manager,
+                        &handler.client_registry,
+                        &handler.usage,
+                        &agent_id,
+                        &prompt,
+                        Some(context),
+                    );
+                    let result = match status_for {
+                        // 通告排队位置，处理期间定期通告已处理时长
+                        Some(request_id) => {
+                            if let Some(queued) = AgentRequestStatus::queued(load) {
+                                handler
+                                    .send_agent_status(request_id, &agent_id, queued)
+                                    .await;
+                            }
+                            tokio::pin!(work);
+                            let mut ticker = tokio::time::interval_at(
+                                tokio::time::Instant::now() + STATUS_INTERVAL,
+                                STATUS_INTERVAL,
+                            );
+                            loop {
+                                tokio::select! {
+                                    result = &mut work => break result,
+                                    _ = ticker.tick() => {
+                                        let status = AgentRequestStatus::processing(accepted.elapsed());
+                                        handler.send_agent_status(request_id, &agent_id, status).await;
+                                    }
+                                }
+                            }
+                        }
+                        None => work.await,
+                    };
+                    let load = handler.agent_load.write().await.finish(started.elapsed());
+                    handler.report_load(load).await;
+                    let response = match (result, reply_to) {
+                        (Ok(resp), Some(request_id)) => {
+                            debug!(
+                                "Agent请求 {} 处理成功，响应长度: {}",
+                                request_id,
+                                resp.content.len()
+                            );
+                            MessageType::AgentReply {
+                                request_id,
+                                agent_id,
+                                content: Some(resp.content),
+                                error: None,
+                            }
+                        }
+                        (Err(e), Some(request_id)) => {
+                            error!("处理Agent请求 {} 失败: {}", request_id, e);
+                            MessageType::AgentReply {
+                                request_id,
+                                agent_id,
+                                content: None,
+                                error: Some(format!("处理Agent请求失败: {}", e)),
+                            }
+                        }
+                        (Ok(resp), None) => {
+                            debug!("Agent请求处理成功，响应长度: {}", resp.content.len());
+                            MessageType::AgentResponse {
+                                content: resp.content,
+                                agent_id,
+                            }
+                        }
+                        (Err(e), None) => {
+                            error!("处理Agent请求失败: {}", e);
+                            MessageType::Error {
+                                message: format!("处理Agent请求失败: {}", e),
+                            }
+                        }
+                    };
+
+                    // 发送响应
+                    match handler.broadcast(&response).await {
+                        Ok(()) => debug!("成功发送Agent响应"),
+                        Err(e) => error!("发送Agent响应失败: {}", e),
+                    }
+                });
+            }
+            MessageType::NodeInfo {
+                name: peer_name,
+                version,
+                capabilities,
+            } => {
+                let capabilities = Capabilities::from_bits_truncate(capabilities.bits());
+                let peer = PeerInfo::negotiate(from.to_string(), peer_name, version, capabilities);
+                debug!(
+                    "节点 {} 的协议版本: {}，协商能力: [{}]",
+                    from.fmt_short(),
+                    peer.version,
+                    peer.negotiated
+                );
+                let previous = self
+                    .address_book
+                    .write()
+                    .await
+                    .observe(&peer.node_id, peer.name.as_deref());
+                if let (Some(previous_node_id), Some(peer_name)) = (previous, peer.name.clone()) {
+                    warn!(
+                        "已验证的节点名称 {} 出现在新的密钥 {} 上",
+                        peer_name,
+                        from.fmt_short()
+                    );
+                    self.events.publish(NodeEvent::VerifiedKeyChanged {
+                        topic_id: self.topic_id.to_string(),
+                        name: peer_name,
+                        previous_node_id,
+                        node_id: peer.node_id.clone(),
+                    });
+                }
+                {
+                    let mut agent_peers = self.agent_peers.write().await;
+                    let topic_agents = agent_peers.entry(self.topic_id).or_default();
+                    if peer.supports(Capabilities::AGENT_ELECTION) {
+                        topic_agents.insert(from);
+                    } else {
+                        topic_agents.remove(&from);
+                    }
+                }
+                let supports_memory = peer.supports(Capabilities::SHARED_MEMORY);
+                let supports_templates = peer.supports(Capabilities::TEMPLATE_EXCHANGE);
+                let supports_folder_sync = peer.supports(Capabilities::FOLDER_SYNC);
+                let is_new = self
+                    .peers
+                    .write()
+                    .await
+                    .insert(from, peer.clone())
+                    .is_none();
+                self.events.publish(NodeEvent::PeerInfoUpdated {
+                    topic_id: self.topic_id.to_string(),
+                    peer,
+                });
+
+                // 新节点加入时回复本节点信息，使其也能完成协商
+                if is_new {
+                    if let Err(e) = self.broadcast(&node_info(self.name.clone())).await {
+                        warn!("回复节点信息失败: {}", e);
+                    }
+
+                    // 与新节点交换共享记忆
+                    if let (Some(memory), true) = (&self.shared_memory, supports_memory) {
+                        let known = memory.read().await.versions(&self.topic_id.to_string());
+                        let request = MessageType::MemorySyncRequest { known };
+                        if let Err(e) = self.broadcast(&request).await {
+                            warn!("发送共享记忆同步请求失败: {}", e);
+                        }
+                    }
+
+                    // 向新节点请求同步文件夹的全部文件版本
+                    if supports_folder_sync {
+                        let requests =
+                            folder_sync::sync_requests(&self.folder_syncs, &self.topic_id).await;
+                        self.broadcast_replies(requests, "文件夹同步请求").await;
+                    }
+                }
+
+                // 有节点加入发现话题时重新通告本节点分享的模板
+                if self.is_template_topic && supports_templates {
+                    let announce = templates::announcement(&self.templates).await;
+                    self.broadcast_replies(announce, "Agent模板通告").await;
+                }
+            }
+            MessageType::AgentResponse { content, agent_id } => {
+                debug!(
+                    "收到Agent响应: agent_id={}, 内容长度={}",
+                    agent_id,
+                    content.len()
+                );
+                self.events.publish(NodeEvent::AgentResponseReceived {
+                    topic_id: self.topic_id.to_string(),
+                    from: from.to_string(),
+                    agent_id,
+                    content,
+                    request_id: None,
+                });
+            }
+            MessageType::AgentReply {
+                request_id,
+                agent_id,
+                content,
+                error,
+            } => {
+                debug!(
+                    "收到 {} 对Agent请求 {} 的应答",
+                    from.fmt_short(),
+                    request_id
+                );
+                match (&content, &error) {
+                    (Some(content), _) => self.events.publish(NodeEvent::AgentResponseReceived {
+                        topic_id: self.topic_id.to_string(),
+                        from: from.to_string(),
+                        agent_id: agent_id.clone(),
+                        content: content.clone(),
+                        request_id: Some(request_id.clone()),
+                    }),
+                    (None, Some(message)) => self.events.publish(NodeEvent::RemoteError {
+                        topic_id: self.topic_id.to_string(),
+                        from: from.to_string(),
+                        message: message.clone(),
+                    }),
+                    (None, None) => {}
+                }
+
+                // 转交给等待该请求的收集任务
+                if let Some(collector) = self.pending_agent_requests.read().await.get(&request_id) {
+                    let answer = AgentAnswer {
+                        from: from.to_string(),
+                        agent_id,
+                        content,
+                        error,
+                        received_at: chrono::Utc::now(),
+                    };
+                    if collector.try_send(answer).is_err() {
+                        warn!("Agent请求 {} 的应答队列已满，丢弃应答", request_id);
+                    }
+                }
+            }
+            MessageType::AgentStatus {
+                request_id,
+                agent_id,
+                status,
+            } => {
+                forward_agent_status(
+                    &self.pending_agent_requests,
+                    &self.events,
+                    &self.topic_id,
+                    &from,
+                    request_id,
+                    agent_id,
+                    status,
+                )
+                .await;
+            }
+            MessageType::Error { message } => {
+                error!("收到错误消息: {}", message);
+                self.events.publish(NodeEvent::RemoteError {
+                    topic_id: self.topic_id.to_string(),
+                    from: from.to_string(),
+                    message,
+                });
+            }
+            MessageType::System { content } => {
+                info!("收到系统消息: {}", content);
+                // 这里可以处理系统消息
+            }
+            MessageType::VerifyConfirm { peer_id, digest } => {
+                verification::handle_confirm(
+                    &self.secret_key.public(),
+                    &from,
+                    &self.topic_id,
+                    &peer_id,
+                    &digest,
+                    &self.address_book,
+                    &self.events,
+                )
+                .await;
+            }
+            MessageType::MemoryUpdate { entries } => {
+                let Some(memory) = &self.shared_memory else {
+                    return;
+                };
+                let topic = self.topic_id.to_string();
+                for entry in entries {
+                    if entry.topic_id != topic {
+                        warn!("丢弃来自 {} 的其他话题记忆: {}", from.fmt_short(), entry.id);
+                        continue;
+                    }
+                    if let Err(e) = entry.verify() {
+                        warn!("丢弃来自 {} 的记忆 {}: {}", from.fmt_short(), entry.id, e);
+                        continue;
+                    }
+                    let event = NodeEvent::MemoryChanged {
+                        topic_id: topic.clone(),
+                        id: entry.id.clone(),
+                        author: entry.author.clone(),
+                        deleted: entry.deleted,
+                    };
+                    if memory.write().await.merge(entry) {
+                        self.events.publish(event);
+                    }
+                }
+            }
+            MessageType::MemorySyncRequest { known } => {
+                let Some(memory) = &self.shared_memory else {
+                    return;
+                };
+                let missing = memory
+                    .read()
+                    .await
+                    .missing_for(&self.topic_id.to_string(), &known);
+                if missing.is_empty() {
+                    return;
+                }
+                debug!("向 {} 补发 {} 条共享记忆", from.fmt_short(), missing.len());
+                for entries in memory::chunk_entries(missing) {
+                    let update = MessageType::MemoryUpdate { entries };
+                    if let Err(e) = self.broadcast(&update).await {
+                        warn!("补发共享记忆失败: {}", e);
+                        break;
+                    }
+                }
+            }
+            MessageType::AgentClaim { request_id } => {
+                debug!("节点 {} 认领了Agent请求 {}", from.fmt_short(), request_id);
+                self.agent_claims.write().await.claim(&request_id);
+            }
+            MessageType::TemplateAnnounce { .. }
+            | MessageType::TemplateFetch { .. }
+            | MessageType::TemplateContent { .. }
+                if !self.is_template_topic =>
+            {
+                debug!("忽略发现话题之外的模板消息: {}", from.fmt_short());
+            }
+            message @ (MessageType::TemplateAnnounce { .. }
+            | MessageType::TemplateFetch { .. }
+            | MessageType::TemplateContent { .. }) => {
+                let reply =
+                    templates::handle_message(&self.templates, &self.events, &from, message).await;
+                self.broadcast_replies(reply, "Agent模板").await;
+            }
+            MessageType::Presence { interval_secs } => {
+                // 活跃时间已在接收时记录
+                debug!(
+                    "节点 {} 在线，保活间隔 {} 秒",
+                    from.fmt_short(),
+                    interval_secs
+                );
+            }
+            MessageType::LoadReport { load } => {
+                debug!("节点 {} 的Agent负载: {:?}", from.fmt_short(), load);
+                self.peer_loads.write().await.update(from, load);
+            }
+            message @ (MessageType::FolderUpdate { .. }
+            | MessageType::FolderSyncRequest { .. }
+            | MessageType::FolderChunkRequest { .. }
+            | MessageType::FolderChunk { .. }) => {
+                let replies = folder_sync::handle_message(
+                    &self.folder_syncs,
+                    &self.events,
+                    &self.topic_id,
+                    &from,
+                    message,
+                )
+                .await;
+                self.broadcast_replies(replies, "同步文件夹").await;
+            }
+        }
+    }
+}
+
+/// 提及解析与记录所需的节点状态
+#[derive(Clone)]
+struct MentionRouter {
+    node_id: String,
+    name: Option<String>,
+    presence: Arc<RwLock<PresenceTracker>>,
+    peers: PeerInfoMap,
+    address_book: Arc<RwLock<AddressBook>>,
+    mentions: Arc<RwLock<MentionIndex>>,
+    events: EventBus,
+}
+
+impl MentionRouter {
+    /// 房间成员名单：本节点与在话题中出现过的节点，名称优先取节点信息，其次取通讯录
+    async fn members(&self, topic_id: &TopicId) -> Vec<RoomMember> {
+        let node_ids = self.presence.read().await.members(topic_id);
+        let peers = self.peers.read().await;
+        let address_book = self.address_book.read().await;
+        let mut members = vec![RoomMember {
+            node_id: self.node_id.clone(),
+            name: self.name.clone(),
+        }];
+        for node_id in node_ids {
+            if node_id == self.node_id {
+                continue;
+            }
+            let name = peers
+                .values()
+                .find(|peer| peer.node_id == node_id)
+                .and_then(|peer| peer.name.clone())
+                .or_else(|| {
+                    address_book
+                        .get(&node_id)
+                        .and_then(|record| record.name.clone())
+                });
+            members.push(RoomMember { node_id, name });
+        }
+        members
+    }
+
+    /// 解析消息中提及的成员，代码块中的 `@` 不算提及
+    async fn resolve(&self, topic_id: &TopicId, entry: &ChatHistoryEntry) -> Vec<String> {
+        if matches!(entry.content, Some(MessageContent::Code { .. })) || !entry.text.contains('@') {
+            return Vec::new();
+        }
+        parse_mentions(&entry.text, &self.members(topic_id).await)
+    }
+
+    /// 记录消息中的提及，别人提及本节点时发布事件
+    async fn record(&self, entry: &ChatHistoryEntry) {
+        if entry.mentions.is_empty() {
+            return;
+        }
+        let mut mentions = self.mentions.write().await;
+        for mentioned in &entry.mentions {
+            let record = MentionRecord {
+                topic_id: entry.topic_id.clone(),
+                message_id: entry.id.clone(),
+                from: entry.from.clone(),
+                mentioned: mentioned.clone(),
+                snippet: snippet(&entry.text),
+                timestamp: entry.timestamp,
+                // 自己发的消息不算未读
+                read: entry.outgoing,
+            };
+            if mentions.record(record.clone()) && !entry.outgoing && *mentioned == self.node_id {
+                self.events.publish(NodeEvent::Mentioned {
+                    topic_id: entry.topic_id.clone(),
+                    mention: record,
+                });
+            }
+        }
+    }
+}
+
+/// 已读状态统计与同步所需的节点状态
+#[derive(Clone)]
+struct ReadTracker {
+    node_id: String,
+    chat_history: Arc<RwLock<ChatHistory>>,
+    mentions: Arc<RwLock<MentionIndex>>,
+    topic_labels: Arc<RwLock<HashMap<TopicId, String>>>,
+    events: EventBus,
+}
+
+impl ReadTracker {
+    /// 房间的未读状态，包括房间名称与未读提及数
+    async fn room(&self, topic_id: &TopicId) -> RoomUnread {
+        let mut room = self.chat_history.read().await.unread(topic_id);
+        room.label = self.topic_labels.read().await.get(topic_id).cloned();
+        room.mentions = self
+            .mentions
+            .read()
+            .await
+            .summary(&self.node_id)
+            .topics
+            .get(&room.topic_id)
+            .copied()
+            .unwrap_or(0);
+        room
+    }
+
+    /// 标记到指定消息为止的消息已读，返回新标记的条数，消息不存在时返回空
+    async fn mark_until(
+        &self,
+        topic_id: &TopicId,
+        message_id: &str,
+        device: Option<String>,
+    ) -> Option<usize> {
+        let marked = self
+            .chat_history
+            .write()
+            .await
+            .mark_read_until(topic_id, message_id)?;
+        if !marked.is_empty() {
+            self.marked(topic_id, &marked, device).await;
+        }
+        Some(marked.len())
+    }
+
+    /// 消息已标记为已读：其中的提及不再算未读，并发布房间新的未读状态
+    async fn marked(&self, topic_id: &TopicId, message_ids: &[String], device: Option<String>) {
+        let topic = topic_id.to_string();
+        {
+            let mut mentions = self.mentions.write().await;
+            for message_id in message_ids {
+                mentions.mark_read(&self.node_id, Some(&topic), Some(message_id));
+            }
+        }
+        self.events.publish(NodeEvent::ReadStateChanged {
+            topic_id: topic,
+            unread: self.room(topic_id).await,
+            device,
+        });
+    }
+}
+
+/// 发件箱补发所需的节点状态
+#[derive(Clone)]
+struct OutboxFlusher {
+    outbox: Arc<RwLock<Outbox>>,
+    topics: Arc<RwLock<TopicMap>>,
+    topic_stats: TopicCountersMap,
+    secret_key: SecretKey,
+    neighbors: TopicNeighbors,
+    chat_history: Arc<RwLock<ChatHistory>>,
+    events: EventBus,
+}
+
+impl OutboxFlusher {
+    /// 话题已加入且至少有一个邻居
+    async fn is_connected(&self, topic_id: &TopicId) -> bool {
+        self.topics.read().await.contains_key(topic_id)
+            && self
+                .neighbors
+                .read()
+                .await
+                .get(topic_id)
+                .is_some_and(|peers| !peers.is_empty())
+    }
+
+    /// 按暂存顺序补发话题中的消息，过期的消息丢弃，发送失败时其余消息放回发件箱。
+    /// 返回补发成功的消息数
+    async fn flush(&self, topic_id: &TopicId) -> usize {
+        if !self.is_connected(topic_id).await {
+            return 0;
+        }
+        let (ready, expired) = self.outbox.write().await.take(topic_id, chrono::Utc::now());
+        if ready.is_empty() && expired.is_empty() {
+            return 0;
+        }
+        for entry in &expired {
+            self.update_chat(topic_id, entry, DeliveryState::Expired)
+                .await;
+        }
+
+        let mut sent = 0;
+        let mut error = None;
+        let mut ready = ready.into_iter();
+        while let Some(entry) = ready.next() {
+            match broadcast_signed(
+                &self.topics,
+                &self.topic_stats,
+                &self.secret_key,
+                topic_id,
+                &entry.message,
+            )
+            .await
+            {
+                Ok(()) => {
+                    sent += 1;
+                    self.update_chat(topic_id, &entry, DeliveryState::Sent)
+                        .await;
+                }
+                Err(e) => {
+                    warn!("补发话题 {} 的消息失败: {}", topic_id, e);
+                    error = Some(e.to_string());
+                    self.outbox
+                        .write()
+                        .await
+                        .restore(std::iter::once(entry).chain(ready).collect());
+                    break;
+                }
+            }
+        }
+
+        let remaining = self.outbox.read().await.pending(topic_id).len();
+        info!(
+            "话题 {} 的发件箱补发 {} 条，过期 {} 条，剩余 {} 条",
+            topic_id,
+            sent,
+            expired.len(),
+            remaining
+        );
+        self.events.publish(NodeEvent::OutboxFlushed {
+            topic_id: topic_id.to_string(),
+            sent,
+            expired: expired.len(),
+            remaining,
+            error,
+        });
+        sent
+    }
+
+    /// 更新暂存的聊天消息的投递状态
+    async fn update_chat(&self, topic_id: &TopicId, entry: &OutboxEntry, state: DeliveryState) {
+        if !matches!(
+            entry.message,
+            MessageType::Chat { .. }
+                | MessageType::ChatReply { .. }
+                | MessageType::ChatContent { .. }
+        ) {
+            return;
+        }
+        let status = self
+            .chat_history
+            .write()
+            .await
+            .update_queued(topic_id, &entry.id, state);
+        if let Some(status) = status {
+            self.events.publish(NodeEvent::DeliveryStatusChanged {
+                topic_id: topic_id.to_string(),
+                message_id: entry.id.clone(),
+                status,
+            });
+        }
     }
 }
 
-/// 处理Agent请求
-async fn process_agent_request(
+/// 处理Agent请求，`context` 为空时沿用调用方作用域中的请求上下文
+pub(crate) async fn process_agent_request(
     agent_manager: &Arc<RwLock<AgentManager>>,
     client_registry: &ClientRegistry,
+    usage: &UsageLedger,
     agent_id: &str,
     prompt: &str,
+    context: Option<RequestContext>,
 ) -> NodeResult<AgentResponse> {
     // 检查Agent是否存在，如果不存在则创建
     {
         let manager = agent_manager.read().await;
         let agents = manager.list_agents().await;
-        
+
         if !agents.contains(&agent_id.to_string()) {
             drop(manager); // 释放读锁
-            
-            let mut manager = agent_manager.write().await;
+
+            let manager = agent_manager.write().await;
             manager.create_agent(agent_id.to_string(), None).await?;
         }
     }
-    
+
     // 重新获取读锁并处理请求
     let manager = agent_manager.read().await;
+    let options = ChatOptions {
+        context,
+        ..Default::default()
+    };
     let response = manager
-        .chat(client_registry, agent_id, prompt)
+        .chat_with_options(client_registry, agent_id, prompt, options)
         .await
-        .map_err(|e| crate::error::NodeError::AgentError(format!("Agent请求失败: {}", e)))?;
-    
+        .map_err(|e| crate::error::NodeError::AgentError(format!("Agent请求失败: {}", e)));
+    usage.record_agent(agent_id, prompt, response.as_ref().ok());
+    let response = response?;
+
     Ok(response)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_counters() {
+        let topic_id = TopicId::from_bytes([1; 32]);
+        let peer = |i: u8| SecretKey::from_bytes(&[i; 32]).public();
+        let mut counters = TopicCounters::default();
+        let stats = counters.snapshot(&topic_id);
+        assert_eq!(stats.topic_id, topic_id.to_string());
+        assert_eq!(stats.messages_in + stats.messages_out, 0);
+        assert!(stats.last_activity.is_none());
+
+        counters.record_in(peer(1), 100);
+        counters.record_in(peer(1), 50);
+        counters.record_in(peer(2), 10);
+        assert!(counters.last_sent.is_none());
+        counters.record_out(200);
+
+        let stats = counters.snapshot(&topic_id);
+        assert_eq!(stats.messages_in, 3);
+        assert_eq!(stats.bytes_in, 160);
+        assert_eq!(stats.messages_out, 1);
+        assert_eq!(stats.bytes_out, 200);
+        // 同一节点的多条消息只计一个发送者
+        assert_eq!(stats.unique_senders, 2);
+        assert_eq!(stats.last_activity, counters.last_sent);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_peer_count() {
+        let status = RwLock::new(NodeStatus {
+            node_id: String::new(),
+            node_addr: None,
+            connected_peers: 0,
+            active_topics: 0,
+            started_at: chrono::Utc::now(),
+            last_activity: chrono::Utc::now(),
+            relay_mode: String::new(),
+            home_relay: None,
+            tasks: Vec::new(),
+        });
+        let neighbors: TopicNeighbors = Arc::default();
+        let events = EventBus::new();
+        let mut rx = events.subscribe();
+        let peer = |i: u8| SecretKey::from_bytes(&[i; 32]).public();
+
+        // 多个话题中的同一节点只计一次
+        neighbors.write().await.extend([
+            (
+                TopicId::from_bytes([1; 32]),
+                HashSet::from([peer(1), peer(2)]),
+            ),
+            (TopicId::from_bytes([2; 32]), HashSet::from([peer(2)])),
+        ]);
+        refresh_peer_count(&status, &neighbors, &events).await;
+        assert_eq!(status.read().await.connected_peers, 2);
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            NodeEvent::PeerCountChanged {
+                previous: 0,
+                current: 2
+            }
+        ));
+
+        // 数量不变时不发布事件
+        refresh_peer_count(&status, &neighbors, &events).await;
+        assert!(rx.try_recv().is_err());
+
+        neighbors
+            .write()
+            .await
+            .remove(&TopicId::from_bytes([1; 32]));
+        refresh_peer_count(&status, &neighbors, &events).await;
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            NodeEvent::PeerCountChanged {
+                previous: 2,
+                current: 1
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_forward_agent_status() {
+        let topic_id = TopicId::from_bytes([1; 32]);
+        let from = SecretKey::from_bytes(&[2; 32]).public();
+        let events = EventBus::new();
+        let mut rx = events.subscribe();
+        let pending: PendingAgentRequests = Arc::default();
+        let (tx, _answers) = mpsc::channel(ANSWER_QUEUE_CAPACITY);
+        pending.write().await.insert("r1".to_string(), tx);
+
+        let status = AgentRequestStatus::QueuePosition { position: 2 };
+        assert!(
+            forward_agent_status(
+                &pending,
+                &events,
+                &topic_id,
+                &from,
+                "r1".to_string(),
+                "default".to_string(),
+                status,
+            )
+            .await
+        );
+        match rx.try_recv().unwrap() {
+            NodeEvent::AgentRequestStatus {
+                topic_id: topic,
+                request_id,
+                from: sender,
+                agent_id,
+                status: received,
+            } => {
+                assert_eq!(topic, topic_id.to_string());
+                assert_eq!(request_id, "r1");
+                assert_eq!(sender, from.to_string());
+                assert_eq!(agent_id, "default");
+                assert_eq!(received, status);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        // 其他节点的请求或已结束的请求不产生事件
+        assert!(
+            !forward_agent_status(
+                &pending,
+                &events,
+                &topic_id,
+                &from,
+                "r2".to_string(),
+                "default".to_string(),
+                AgentRequestStatus::Accepted,
+            )
+            .await
+        );
+        pending.write().await.remove("r1");
+        assert!(
+            !forward_agent_status(
+                &pending,
+                &events,
+                &topic_id,
+                &from,
+                "r1".to_string(),
+                "default".to_string(),
+                AgentRequestStatus::processing(Duration::from_secs(5)),
+            )
+            .await
+        );
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! 在线状态与话题保活
+//!
+//! 话题长时间没有消息时，gossip 会断开空闲的邻居连接。节点在每个话题中按自适应间隔广播
+//! 轻量的在线消息：成员稳定时逐步拉长间隔，有邻居上下线时恢复到最短间隔，最近已发过
+//! 其他消息时跳过。收到任何消息都会刷新发送者的最近活跃时间，用于统计房间的活跃成员
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+
+/// 在线状态配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    /// 是否广播在线消息
+    pub enabled: bool,
+    /// 最短保活间隔（秒）
+    pub min_interval_secs: u64,
+    /// 最长保活间隔（秒）
+    pub max_interval_secs: u64,
+    /// 统计活跃成员的默认时间窗口（秒）
+    pub active_window_secs: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_interval_secs: 20,
+            max_interval_secs: 160,
+            active_window_secs: 300,
+        }
+    }
+}
+
+impl PresenceConfig {
+    /// 设置是否广播在线消息
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// 设置保活间隔范围（秒）
+    pub fn with_intervals(mut self, min_interval_secs: u64, max_interval_secs: u64) -> Self {
+        self.min_interval_secs = min_interval_secs;
+        self.max_interval_secs = max_interval_secs;
+        self
+    }
+
+    /// 设置统计活跃成员的默认时间窗口（秒）
+    pub fn with_active_window_secs(mut self, active_window_secs: u64) -> Self {
+        self.active_window_secs = active_window_secs;
+        self
+    }
+
+    /// 默认的活跃时间窗口
+    pub fn active_window(&self) -> Duration {
+        Duration::from_secs(self.active_window_secs)
+    }
+}
+
+/// 自适应的保活间隔：成员稳定时每次翻倍直到上限，成员变化时回到下限
+#[derive(Debug, Clone)]
+pub(crate) struct KeepaliveSchedule {
+    current: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl KeepaliveSchedule {
+    pub(crate) fn new(config: &PresenceConfig) -> Self {
+        let min = Duration::from_secs(config.min_interval_secs.max(1));
+        let max = Duration::from_secs(config.max_interval_secs).max(min);
+        Self {
+            current: min,
+            min,
+            max,
+        }
+    }
+
+    /// 当前间隔
+    pub(crate) fn current(&self) -> Duration {
+        self.current
+    }
+
+    /// 成员变化，回到最短间隔
+    pub(crate) fn reset(&mut self) {
+        self.current = self.min;
+    }
+
+    /// 成员稳定，拉长间隔
+    pub(crate) fn backoff(&mut self) {
+        self.current = (self.current * 2).min(self.max);
+    }
+}
+
+/// 房间成员的在线状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberPresence {
+    /// 节点ID
+    pub node_id: String,
+    /// 节点名称
+    pub name: Option<String>,
+    /// 最近一次收到其消息的时间
+    pub last_seen: DateTime<Utc>,
+}
+
+/// 房间活跃度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomLiveness {
+    /// 话题ID
+    pub topic_id: String,
+    /// 统计的时间窗口（秒）
+    pub window_secs: u64,
+    /// 时间窗口内活跃的成员数量（不含本节点）
+    pub active_count: usize,
+    /// 时间窗口内活跃的成员，最近活跃的在前
+    pub members: Vec<MemberPresence>,
+    /// 房间中最近一次收到消息的时间
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// 各话题中成员的最近活跃时间
+#[derive(Debug, Default)]
+pub(crate) struct PresenceTracker {
+    topics: HashMap<TopicId, HashMap<String, DateTime<Utc>>>,
+}
+
+impl PresenceTracker {
+    /// 记录成员活跃
+    pub(crate) fn observe(&mut self, topic_id: TopicId, node_id: String, at: DateTime<Utc>) {
+        let last_seen = self
+            .topics
+            .entry(topic_id)
+            .or_default()
+            .entry(node_id)
+            .or_insert(at);
+        *last_seen = (*last_seen).max(at);
+    }
+
+    /// 离开话题时清除记录
+    pub(crate) fn remove_topic(&mut self, topic_id: &TopicId) {
+        self.topics.remove(topic_id);
+    }
+
+    /// 在话题中出现过的全部成员
+    pub(crate) fn members(&self, topic_id: &TopicId) -> Vec<String> {
+        self.topics
+            .get(topic_id)
+            .into_iter()
+            .flatten()
+            .map(|(node_id, _)| node_id.clone())
+            .collect()
+    }
+
+    /// 统计时间窗口内的活跃成员，`name_of` 用于查找节点名称
+    pub(crate) fn liveness<F>(
+        &self,
+        topic_id: &TopicId,
+        window: Duration,
+        now: DateTime<Utc>,
+        name_of: F,
+    ) -> RoomLiveness
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let members = self.topics.get(topic_id);
+        let since = chrono::Duration::from_std(window)
+            .ok()
+            .and_then(|window| now.checked_sub_signed(window))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+
+        let mut active: Vec<MemberPresence> = members
+            .into_iter()
+            .flatten()
+            .filter(|(_, last_seen)| **last_seen >= since)
+            .map(|(node_id, last_seen)| MemberPresence {
+                node_id: node_id.clone(),
+                name: name_of(node_id),
+                last_seen: *last_seen,
+            })
+            .collect();
+        active.sort_by(|a, b| {
+            b.last_seen
+                .cmp(&a.last_seen)
+                .then_with(|| a.node_id.cmp(&b.node_id))
+        });
+
+        RoomLiveness {
+            topic_id: topic_id.to_string(),
+            window_secs: window.as_secs(),
+            active_count: active.len(),
+            members: active,
+            last_seen: members.and_then(|members| members.values().max().copied()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_schedule_and_liveness() {
+        let mut schedule =
+            KeepaliveSchedule::new(&PresenceConfig::default().with_intervals(10, 35));
+        assert_eq!(schedule.current(), Duration::from_secs(10));
+        schedule.backoff();
+        schedule.backoff();
+        assert_eq!(schedule.current(), Duration::from_secs(35));
+        schedule.reset();
+        assert_eq!(schedule.current(), Duration::from_secs(10));
+
+        let topic = TopicId::from_bytes([7; 32]);
+        let now = Utc::now();
+        let mut tracker = PresenceTracker::default();
+        tracker.observe(
+            topic,
+            "alice".to_string(),
+            now - chrono::Duration::minutes(10),
+        );
+        tracker.observe(topic, "bob".to_string(), now - chrono::Duration::minutes(2));
+        tracker.observe(
+            topic,
+            "carol".to_string(),
+            now - chrono::Duration::minutes(1),
+        );
+        // 较早的时间不会覆盖较新的记录
+        tracker.observe(
+            topic,
+            "carol".to_string(),
+            now - chrono::Duration::minutes(30),
+        );
+
+        let liveness = tracker.liveness(&topic, Duration::from_secs(300), now, |node_id| {
+            (node_id == "bob").then(|| "Bob".to_string())
+        });
+        assert_eq!(liveness.active_count, 2);
+        assert_eq!(liveness.members[0].node_id, "carol");
+        assert_eq!(liveness.members[1].name.as_deref(), Some("Bob"));
+        assert_eq!(liveness.last_seen, Some(now - chrono::Duration::minutes(1)));
+        // 成员名单包括窗口外的成员
+        let mut members = tracker.members(&topic);
+        members.sort();
+        assert_eq!(members, ["alice", "bob", "carol"]);
+
+        tracker.remove_topic(&topic);
+        let liveness = tracker.liveness(&topic, Duration::from_secs(300), now, |_| None);
+        assert_eq!(liveness.active_count, 0);
+        assert_eq!(liveness.last_seen, None);
+    }
+}
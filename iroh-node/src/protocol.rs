@@ -0,0 +1,212 @@
+//! 协议版本与能力协商
+//!
+//! 每条签名消息都带有协议版本号，节点加入话题或发现新节点时通过 `NodeInfo`
+//! 交换版本与能力位集。双方只使用共同支持的可选功能，版本不兼容时给出提示而不是误解析消息
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 当前消息协议版本
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// 可以解析的最低协议版本
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// 是否可以解析指定版本的消息
+pub fn is_supported_version(version: u16) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+/// 节点能力位集
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// 聊天消息送达与已读确认
+    pub const CHAT_ACK: Self = Self(1 << 0);
+    /// 处理Agent请求
+    pub const AGENT: Self = Self(1 << 1);
+    /// 端到端加密
+    pub const ENCRYPTION: Self = Self(1 << 2);
+    /// 大消息分片
+    pub const FRAGMENTATION: Self = Self(1 << 3);
+    /// 请求/响应式RPC
+    pub const RPC: Self = Self(1 << 4);
+    /// 身份验证确认
+    pub const VERIFICATION: Self = Self(1 << 5);
+    /// Agent响应者选举与请求认领
+    pub const AGENT_ELECTION: Self = Self(1 << 6);
+    /// 带请求ID的Agent请求与应答
+    pub const AGENT_ROUTING: Self = Self(1 << 7);
+    /// 共享记忆同步
+    pub const SHARED_MEMORY: Self = Self(1 << 8);
+    /// Agent模板通告与交换
+    pub const TEMPLATE_EXCHANGE: Self = Self(1 << 9);
+    /// 在线消息与话题保活
+    pub const PRESENCE: Self = Self(1 << 10);
+    /// Agent负载通告与按负载选举响应者
+    pub const AGENT_LOAD: Self = Self(1 << 11);
+    /// 双向文件夹同步
+    pub const FOLDER_SYNC: Self = Self(1 << 12);
+    /// 消息回复（消息串）与表情回应
+    pub const CHAT_THREADS: Self = Self(1 << 13);
+    /// 结构化消息内容（Markdown、代码块、系统提示）
+    pub const RICH_CONTENT: Self = Self(1 << 14);
+    /// Agent请求处理状态（已接受、排队位置、处理进度）
+    pub const AGENT_STATUS: Self = Self(1 << 15);
+
+    /// 所有已定义的能力及其名称
+    const NAMED: [(Self, &'static str); 16] = [
+        (Self::CHAT_ACK, "chat_ack"),
+        (Self::AGENT, "agent"),
+        (Self::ENCRYPTION, "encryption"),
+        (Self::FRAGMENTATION, "fragmentation"),
+        (Self::RPC, "rpc"),
+        (Self::VERIFICATION, "verification"),
+        (Self::AGENT_ELECTION, "agent_election"),
+        (Self::AGENT_ROUTING, "agent_routing"),
+        (Self::SHARED_MEMORY, "shared_memory"),
+        (Self::TEMPLATE_EXCHANGE, "template_exchange"),
+        (Self::PRESENCE, "presence"),
+        (Self::AGENT_LOAD, "agent_load"),
+        (Self::FOLDER_SYNC, "folder_sync"),
+        (Self::CHAT_THREADS, "chat_threads"),
+        (Self::RICH_CONTENT, "rich_content"),
+        (Self::AGENT_STATUS, "agent_status"),
+    ];
+
+    /// 空能力集
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// 未收到 `NodeInfo` 的节点按当前协议版本的基础能力对待
+    pub const fn baseline() -> Self {
+        Self(Self::CHAT_ACK.0 | Self::AGENT.0)
+    }
+
+    /// 本节点支持的能力
+    pub const fn local() -> Self {
+        Self(
+            Self::baseline().0
+                | Self::VERIFICATION.0
+                | Self::AGENT_ELECTION.0
+                | Self::AGENT_ROUTING.0
+                | Self::SHARED_MEMORY.0
+                | Self::TEMPLATE_EXCHANGE.0
+                | Self::PRESENCE.0
+                | Self::AGENT_LOAD.0
+                | Self::FOLDER_SYNC.0
+                | Self::CHAT_THREADS.0
+                | Self::RICH_CONTENT.0
+                | Self::AGENT_STATUS.0,
+        )
+    }
+
+    /// 从原始位构造，忽略未知的位
+    pub fn from_bits_truncate(bits: u32) -> Self {
+        let known = Self::NAMED.iter().fold(0, |acc, (cap, _)| acc | cap.0);
+        Self(bits & known)
+    }
+
+    /// 原始位
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// 是否包含全部指定能力
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 双方共同支持的能力
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// 合并能力
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// 能力名称列表，未知的位被忽略
+    pub fn names(self) -> Vec<&'static str> {
+        Self::NAMED
+            .iter()
+            .filter(|(cap, _)| self.contains(*cap))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.names().join(","))
+    }
+}
+
+/// 对等节点的协议信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfo {
+    /// 节点ID
+    pub node_id: String,
+    /// 节点名称
+    pub name: Option<String>,
+    /// 协议版本
+    pub version: u16,
+    /// 对方声明的能力
+    pub capabilities: Capabilities,
+    /// 协商后双方可用的能力
+    pub negotiated: Capabilities,
+    /// 最后一次收到 `NodeInfo` 的时间
+    pub last_seen: DateTime<Utc>,
+}
+
+impl PeerInfo {
+    /// 根据对方声明的信息与本节点能力协商
+    pub fn negotiate(
+        node_id: String,
+        name: Option<String>,
+        version: u16,
+        capabilities: Capabilities,
+    ) -> Self {
+        Self {
+            node_id,
+            name,
+            version,
+            capabilities,
+            negotiated: Capabilities::local().intersection(capabilities),
+            last_seen: Utc::now(),
+        }
+    }
+
+    /// 是否可以对该节点使用指定能力
+    pub fn supports(&self, capability: Capabilities) -> bool {
+        self.negotiated.contains(capability)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_capabilities() {
+        let remote = Capabilities::CHAT_ACK
+            .union(Capabilities::ENCRYPTION)
+            .union(Capabilities::from_bits_truncate(1 << 31));
+        assert_eq!(remote.names(), vec!["chat_ack", "encryption"]);
+
+        let peer = PeerInfo::negotiate("peer".to_string(), None, PROTOCOL_VERSION, remote);
+        assert!(peer.supports(Capabilities::CHAT_ACK));
+        // 本节点尚不支持加密，协商后回退为不加密
+        assert!(!peer.supports(Capabilities::ENCRYPTION));
+        assert!(!peer.supports(Capabilities::AGENT));
+
+        assert!(is_supported_version(PROTOCOL_VERSION));
+        assert!(!is_supported_version(PROTOCOL_VERSION + 1));
+    }
+}
@@ -0,0 +1,143 @@
+//! 房间已读状态
+//!
+//! 按聊天记录中收到的消息的已读标记统计各房间的未读数与最后读到的消息，
+//! 用于Tauri角标与网页端的未读提示。配置了本人其他设备时，
+//! 这些设备发出的已读确认会同步本节点的已读状态
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::chat::ChatHistoryEntry;
+
+/// 最后读到的消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadMarker {
+    /// 消息ID
+    pub message_id: String,
+    /// 消息时间
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 单个房间的未读状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomUnread {
+    /// 话题ID
+    pub topic_id: String,
+    /// 房间名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// 未读消息数
+    pub unread: usize,
+    /// 未读提及数
+    pub mentions: usize,
+    /// 最后读到的消息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read: Option<ReadMarker>,
+    /// 最新消息的时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<DateTime<Utc>>,
+}
+
+/// 全部房间的未读汇总
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnreadSummary {
+    /// 未读消息总数
+    pub unread: usize,
+    /// 未读提及总数
+    pub mentions: usize,
+    /// 各房间的未读状态，有新消息的房间在前
+    pub rooms: Vec<RoomUnread>,
+}
+
+impl UnreadSummary {
+    /// 汇总各房间的未读状态
+    pub(crate) fn new(mut rooms: Vec<RoomUnread>) -> Self {
+        rooms.sort_by(|a, b| {
+            b.latest
+                .cmp(&a.latest)
+                .then_with(|| a.topic_id.cmp(&b.topic_id))
+        });
+        Self {
+            unread: rooms.iter().map(|room| room.unread).sum(),
+            mentions: rooms.iter().map(|room| room.mentions).sum(),
+            rooms,
+        }
+    }
+}
+
+/// 统计房间的未读状态，只计收到的消息；房间名称与提及数由调用方填写
+pub(crate) fn room_unread<'a>(
+    topic_id: &str,
+    entries: impl IntoIterator<Item = &'a ChatHistoryEntry>,
+) -> RoomUnread {
+    let mut room = RoomUnread {
+        topic_id: topic_id.to_string(),
+        label: None,
+        unread: 0,
+        mentions: 0,
+        last_read: None,
+        latest: None,
+    };
+    for entry in entries {
+        room.latest = room.latest.max(Some(entry.timestamp));
+        if entry.outgoing {
+            continue;
+        }
+        if entry.read {
+            room.last_read = Some(ReadMarker {
+                message_id: entry.id.clone(),
+                timestamp: entry.timestamp,
+            });
+        } else {
+            room.unread += 1;
+        }
+    }
+    room
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_unread() {
+        let now = Utc::now();
+        let entry = |id: &str, minutes: i64, outgoing: bool, read: bool| ChatHistoryEntry {
+            id: id.to_string(),
+            topic_id: "room".to_string(),
+            from: "peer".to_string(),
+            text: "你好".to_string(),
+            content: None,
+            timestamp: now - chrono::Duration::minutes(minutes),
+            outgoing,
+            delivery: None,
+            read,
+            locked: false,
+            reply_to: None,
+            reactions: Default::default(),
+            mentions: Vec::new(),
+        };
+        let entries = [
+            entry("a", 5, false, true),
+            entry("b", 4, false, true),
+            entry("c", 3, true, true),
+            entry("d", 2, false, false),
+            entry("e", 1, false, false),
+        ];
+        let room = room_unread("room", &entries);
+        assert_eq!(room.unread, 2);
+        assert_eq!(room.last_read.as_ref().unwrap().message_id, "b");
+        assert_eq!(room.latest, Some(now - chrono::Duration::minutes(1)));
+
+        let empty = room_unread("quiet", []);
+        assert_eq!(empty.unread, 0);
+        assert!(empty.last_read.is_none());
+
+        let mut busy = room.clone();
+        busy.mentions = 1;
+        let summary = UnreadSummary::new(vec![empty, busy]);
+        assert_eq!(summary.unread, 2);
+        assert_eq!(summary.mentions, 1);
+        assert_eq!(summary.rooms[0].topic_id, "room");
+    }
+}
@@ -0,0 +1,411 @@
+//! 房间机器人
+//!
+//! 房间可以指定一个Agent（模型与人设）作为机器人：有人@提及机器人或消息命中触发词时，
+//! 由设置了机器人的节点调用该Agent，并以带署名的聊天消息回复。每个房间单独限流，
+//! 房间可以暂停机器人，成员也可以选择不让机器人回复自己的消息
+//!
+//! 收到的聊天消息经 [`RoomBots::trigger`] 判断是否触发机器人，触发后由 [`RoomBotResponder`]
+//! 在后台调用Agent并把回复发到房间
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use iroh::SecretKey;
+use rig_agent::{
+    core::{AgentConfig, ClientRegistry},
+    AgentManager, RequestContext, RequestOrigin,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::{
+    chat::{new_message_id, ChatHistory, ChatHistoryEntry, DeliveryStatus, Reactions},
+    events::{EventBus, NodeEvent},
+    p2p::{broadcast_signed, process_agent_request, TopicCountersMap, TopicMap},
+    usage::UsageLedger,
+    MessageType,
+};
+
+/// 默认的机器人Agent ID
+const DEFAULT_AGENT_ID: &str = "room-bot";
+/// 默认的提及名称
+const DEFAULT_NAME: &str = "bot";
+/// 默认每分钟最多回复的次数
+const DEFAULT_MAX_REPLIES_PER_MINUTE: u32 = 6;
+
+/// 房间机器人配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoomBotConfig {
+    /// 回复所用的Agent ID，不存在时自动创建
+    pub agent_id: String,
+    /// 提及名称，消息中出现 `@名称` 时触发，也用作回复的署名
+    pub name: String,
+    /// 模型提供商，与 `model` 同时设置时覆盖Agent的模型
+    pub provider: Option<String>,
+    /// 模型名称
+    pub model: Option<String>,
+    /// 人设（系统提示），设置时覆盖Agent的人设
+    pub persona: Option<String>,
+    /// 触发词，消息包含任意一个（不区分大小写）时触发
+    pub triggers: Vec<String>,
+    /// 每分钟最多回复的次数，为0时不限制
+    pub max_replies_per_minute: u32,
+    /// 是否启用，关闭后保留配置但不再回复
+    pub enabled: bool,
+    /// 选择不让机器人回复的成员节点ID
+    pub opted_out: BTreeSet<String>,
+}
+
+impl Default for RoomBotConfig {
+    fn default() -> Self {
+        Self {
+            agent_id: DEFAULT_AGENT_ID.to_string(),
+            name: DEFAULT_NAME.to_string(),
+            provider: None,
+            model: None,
+            persona: None,
+            triggers: Vec::new(),
+            max_replies_per_minute: DEFAULT_MAX_REPLIES_PER_MINUTE,
+            enabled: true,
+            opted_out: BTreeSet::new(),
+        }
+    }
+}
+
+impl RoomBotConfig {
+    /// 使用指定的Agent创建配置
+    pub fn new(agent_id: impl Into<String>) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            ..Default::default()
+        }
+    }
+
+    /// 设置提及名称
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// 设置模型
+    pub fn with_model(mut self, provider: impl Into<String>, model: impl Into<String>) -> Self {
+        self.provider = Some(provider.into());
+        self.model = Some(model.into());
+        self
+    }
+
+    /// 设置人设
+    pub fn with_persona(mut self, persona: impl Into<String>) -> Self {
+        self.persona = Some(persona.into());
+        self
+    }
+
+    /// 添加触发词
+    pub fn with_trigger(mut self, trigger: impl Into<String>) -> Self {
+        self.triggers.push(trigger.into());
+        self
+    }
+
+    /// 设置每分钟最多回复的次数
+    pub fn with_max_replies_per_minute(mut self, max_replies_per_minute: u32) -> Self {
+        self.max_replies_per_minute = max_replies_per_minute;
+        self
+    }
+
+    /// 设置是否启用
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// 把模型与人设应用到Agent配置
+    pub fn apply_to(&self, mut config: AgentConfig) -> AgentConfig {
+        if let (Some(provider), Some(model)) = (&self.provider, &self.model) {
+            config.provider = provider.clone();
+            config.model = model.clone();
+        }
+        if let Some(persona) = &self.persona {
+            config.preamble = Some(persona.clone());
+        }
+        config
+    }
+
+    /// 判断消息是否需要机器人回复，需要时返回发给Agent的提示词（去掉了@提及）
+    pub fn prompt_for(&self, from: &str, text: &str) -> Option<String> {
+        if !self.enabled || self.opted_out.contains(from) {
+            return None;
+        }
+        // 机器人自己的回复带有署名，不再触发
+        if text.starts_with(&format!("{}: ", self.name)) {
+            return None;
+        }
+
+        let mention = format!("@{}", self.name);
+        let mentioned = text.char_indices().map(|(i, _)| i).find(|&i| {
+            text.get(i..i + mention.len())
+                .is_some_and(|candidate| candidate.eq_ignore_ascii_case(&mention))
+        });
+        if let (false, Some(start)) = (self.name.is_empty(), mentioned) {
+            let prompt = format!("{}{}", &text[..start], &text[start + mention.len()..]);
+            let prompt = prompt.trim();
+            return Some(if prompt.is_empty() { text } else { prompt }.to_string());
+        }
+
+        let lower = text.to_lowercase();
+        self.triggers
+            .iter()
+            .filter(|trigger| !trigger.is_empty())
+            .any(|trigger| lower.contains(&trigger.to_lowercase()))
+            .then(|| text.to_string())
+    }
+
+    /// 机器人回复的聊天文本
+    pub fn reply_text(&self, content: &str) -> String {
+        format!("{}: {}", self.name, content)
+    }
+}
+
+/// 一次需要机器人回复的消息
+#[derive(Debug, Clone)]
+pub(crate) struct BotTrigger {
+    /// 话题ID
+    pub(crate) topic_id: TopicId,
+    /// 发送者节点ID
+    pub(crate) from: String,
+    /// 发给Agent的提示词
+    pub(crate) prompt: String,
+    /// 机器人配置
+    pub(crate) config: RoomBotConfig,
+}
+
+/// 单个房间的机器人与最近的回复时间
+#[derive(Debug)]
+struct RoomBot {
+    config: RoomBotConfig,
+    replies: VecDeque<DateTime<Utc>>,
+}
+
+/// 各房间的机器人
+#[derive(Debug, Default)]
+pub(crate) struct RoomBots {
+    rooms: HashMap<TopicId, RoomBot>,
+}
+
+impl RoomBots {
+    /// 设置房间的机器人，替换原有配置
+    pub(crate) fn set(&mut self, topic_id: TopicId, config: RoomBotConfig) {
+        self.rooms.insert(
+            topic_id,
+            RoomBot {
+                config,
+                replies: VecDeque::new(),
+            },
+        );
+    }
+
+    /// 获取房间的机器人配置
+    pub(crate) fn get(&self, topic_id: &TopicId) -> Option<RoomBotConfig> {
+        self.rooms.get(topic_id).map(|bot| bot.config.clone())
+    }
+
+    /// 移除房间的机器人
+    pub(crate) fn remove(&mut self, topic_id: &TopicId) -> bool {
+        self.rooms.remove(topic_id).is_some()
+    }
+
+    /// 设置成员是否不让机器人回复，房间没有机器人时返回 false
+    pub(crate) fn set_opt_out(
+        &mut self,
+        topic_id: &TopicId,
+        member: &str,
+        opted_out: bool,
+    ) -> bool {
+        let Some(bot) = self.rooms.get_mut(topic_id) else {
+            return false;
+        };
+        if opted_out {
+            bot.config.opted_out.insert(member.to_string());
+        } else {
+            bot.config.opted_out.remove(member);
+        }
+        true
+    }
+
+    /// 检查消息是否触发机器人，触发且未超过限流时记录一次回复
+    pub(crate) fn trigger(
+        &mut self,
+        topic_id: &TopicId,
+        from: &str,
+        text: &str,
+        now: DateTime<Utc>,
+    ) -> Option<BotTrigger> {
+        let bot = self.rooms.get_mut(topic_id)?;
+        let prompt = bot.config.prompt_for(from, text)?;
+
+        let limit = bot.config.max_replies_per_minute as usize;
+        if limit > 0 {
+            let since = now - chrono::Duration::minutes(1);
+            while bot.replies.front().is_some_and(|at| *at <= since) {
+                bot.replies.pop_front();
+            }
+            if bot.replies.len() >= limit {
+                tracing::debug!("话题 {} 的机器人回复过于频繁，忽略本条消息", topic_id);
+                return None;
+            }
+            bot.replies.push_back(now);
+        }
+
+        Some(BotTrigger {
+            topic_id: *topic_id,
+            from: from.to_string(),
+            prompt,
+            config: bot.config.clone(),
+        })
+    }
+}
+
+/// 房间机器人回复所需的节点状态
+#[derive(Clone)]
+pub(crate) struct RoomBotResponder {
+    pub(crate) agent_manager: Arc<RwLock<AgentManager>>,
+    pub(crate) client_registry: Arc<ClientRegistry>,
+    pub(crate) usage: Arc<UsageLedger>,
+    pub(crate) topics: Arc<RwLock<TopicMap>>,
+    pub(crate) topic_stats: TopicCountersMap,
+    pub(crate) secret_key: SecretKey,
+    pub(crate) chat_history: Arc<RwLock<ChatHistory>>,
+    pub(crate) events: EventBus,
+}
+
+impl RoomBotResponder {
+    /// 在后台回复触发的机器人，不阻塞消息处理
+    pub(crate) fn spawn_reply(&self, trigger: BotTrigger) {
+        let responder = self.clone();
+        tokio::spawn(async move {
+            responder.reply(trigger).await;
+        });
+    }
+
+    /// 调用机器人的Agent，把回复作为带署名的聊天消息发到房间
+    async fn reply(&self, trigger: BotTrigger) {
+        let context = RequestContext::new(RequestOrigin::P2p).with_caller_id(trigger.from.clone());
+        let response = match process_agent_request(
+            &self.agent_manager,
+            &self.client_registry,
+            &self.usage,
+            &trigger.config.agent_id,
+            &trigger.prompt,
+            Some(context),
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("话题 {} 的机器人回复失败: {}", trigger.topic_id, e);
+                return;
+            }
+        };
+
+        let id = new_message_id();
+        let text = trigger.config.reply_text(&response.content);
+        let message = MessageType::Chat {
+            id: id.clone(),
+            text: text.clone(),
+        };
+        if let Err(e) = broadcast_signed(
+            &self.topics,
+            &self.topic_stats,
+            &self.secret_key,
+            &trigger.topic_id,
+            &message,
+        )
+        .await
+        {
+            warn!("发送机器人回复失败: {}", e);
+            return;
+        }
+
+        let entry = ChatHistoryEntry {
+            id,
+            topic_id: trigger.topic_id.to_string(),
+            from: self.secret_key.public().to_string(),
+            text,
+            content: None,
+            timestamp: Utc::now(),
+            outgoing: true,
+            delivery: Some(DeliveryStatus::default()),
+            read: true,
+            locked: false,
+            reply_to: None,
+            reactions: Reactions::new(),
+            mentions: Vec::new(),
+        };
+        self.chat_history
+            .write()
+            .await
+            .push(trigger.topic_id, entry.clone());
+        self.events.publish(NodeEvent::ChatSent {
+            topic_id: trigger.topic_id.to_string(),
+            entry,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_room_bot_triggers() {
+        let topic = TopicId::from_bytes([7; 32]);
+        let config = RoomBotConfig::new("helper")
+            .with_name("Helper")
+            .with_trigger("求助")
+            .with_max_replies_per_minute(2);
+        assert_eq!(
+            config.prompt_for("a", "@helper 今天天气如何").as_deref(),
+            Some("今天天气如何")
+        );
+        assert_eq!(
+            config.prompt_for("a", "我需要求助").as_deref(),
+            Some("我需要求助")
+        );
+        assert!(config.prompt_for("a", "随便聊聊").is_none());
+        assert!(config
+            .prompt_for("a", &config.reply_text("@helper 你好"))
+            .is_none());
+        assert!(config
+            .clone()
+            .with_enabled(false)
+            .prompt_for("a", "@helper")
+            .is_none());
+
+        let mut bots = RoomBots::default();
+        assert!(bots
+            .trigger(&topic, "a", "@helper hi", Utc::now())
+            .is_none());
+        bots.set(topic, config);
+
+        // 成员选择不让机器人回复
+        assert!(bots.set_opt_out(&topic, "b", true));
+        assert!(bots
+            .trigger(&topic, "b", "@helper hi", Utc::now())
+            .is_none());
+
+        // 每分钟最多回复两次
+        let now = Utc::now();
+        assert!(bots.trigger(&topic, "a", "@helper 1", now).is_some());
+        assert!(bots.trigger(&topic, "a", "@helper 2", now).is_some());
+        assert!(bots.trigger(&topic, "a", "@helper 3", now).is_none());
+        let later = now + chrono::Duration::seconds(61);
+        let trigger = bots.trigger(&topic, "a", "@helper 4", later).unwrap();
+        assert_eq!(trigger.prompt, "4");
+        assert_eq!(trigger.config.agent_id, "helper");
+    }
+}
@@ -0,0 +1,666 @@
+//! 接收文件的安全扫描
+//!
+//! 文件接收完成后依次交给配置的扫描器检查：ClamAV 守护进程（`INSTREAM` 协议）、
+//! 自定义命令（退出码 0 表示安全，1 表示发现威胁），以及按大小与扩展名的策略检查。
+//! 全部通过后文件才算安全；未通过（默认也包括扫描出错）的文件移入隔离目录。
+//! 每次扫描的结果通过 [`FileScanHook::subscribe`] 推送
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{
+    chat::new_message_id,
+    error::{NodeError, NodeResult},
+};
+
+/// 发送给 ClamAV 的每块字节数
+const CLAMAV_CHUNK_BYTES: usize = 64 * 1024;
+/// 等待扫描命令退出时的轮询间隔
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// 扫描结果事件的缓冲数量
+const SCAN_EVENT_CAPACITY: usize = 64;
+
+/// 扫描结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanVerdict {
+    /// 未发现问题
+    Clean,
+    /// 发现威胁或违反策略
+    Infected,
+    /// 扫描器无法完成检查
+    Error,
+}
+
+/// 单个扫描器的检查结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanOutcome {
+    /// 结论
+    pub verdict: ScanVerdict,
+    /// 威胁名称或错误原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ScanOutcome {
+    /// 未发现问题
+    pub fn clean() -> Self {
+        Self {
+            verdict: ScanVerdict::Clean,
+            detail: None,
+        }
+    }
+
+    /// 发现威胁
+    pub fn infected(detail: impl Into<String>) -> Self {
+        Self {
+            verdict: ScanVerdict::Infected,
+            detail: Some(detail.into()),
+        }
+    }
+
+    /// 扫描出错
+    pub fn error(detail: impl Into<String>) -> Self {
+        Self {
+            verdict: ScanVerdict::Error,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// 文件扫描器，在阻塞线程中调用
+pub trait FileScanner: Send + Sync {
+    /// 扫描器名称，显示在扫描结果中
+    fn name(&self) -> &str;
+
+    /// 检查文件
+    fn scan(&self, path: &Path) -> ScanOutcome;
+}
+
+/// 按大小与扩展名的策略检查
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanPolicy {
+    /// 允许的最大字节数
+    pub max_bytes: Option<u64>,
+    /// 只允许这些扩展名（小写，不含 `.`），为空时不限制
+    pub allowed_extensions: Vec<String>,
+    /// 拒绝这些扩展名（小写，不含 `.`）
+    pub blocked_extensions: Vec<String>,
+}
+
+impl ScanPolicy {
+    /// 设置允许的最大字节数
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// 添加允许的扩展名
+    pub fn with_allowed_extension(mut self, extension: impl Into<String>) -> Self {
+        self.allowed_extensions
+            .push(extension.into().to_ascii_lowercase());
+        self
+    }
+
+    /// 添加拒绝的扩展名
+    pub fn with_blocked_extension(mut self, extension: impl Into<String>) -> Self {
+        self.blocked_extensions
+            .push(extension.into().to_ascii_lowercase());
+        self
+    }
+}
+
+impl FileScanner for ScanPolicy {
+    fn name(&self) -> &str {
+        "policy"
+    }
+
+    fn scan(&self, path: &Path) -> ScanOutcome {
+        let size = match std::fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => return ScanOutcome::error(format!("读取文件信息失败: {}", e)),
+        };
+        if let Some(max_bytes) = self.max_bytes.filter(|max_bytes| size > *max_bytes) {
+            return ScanOutcome::infected(format!("文件大小 {} 超过上限 {}", size, max_bytes));
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        if self.blocked_extensions.contains(&extension) {
+            return ScanOutcome::infected(format!("不允许的文件类型: .{}", extension));
+        }
+        if !self.allowed_extensions.is_empty() && !self.allowed_extensions.contains(&extension) {
+            return ScanOutcome::infected(format!("不在允许列表中的文件类型: .{}", extension));
+        }
+        ScanOutcome::clean()
+    }
+}
+
+/// ClamAV 守护进程扫描器
+#[derive(Debug, Clone)]
+pub struct ClamAvScanner {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAvScanner {
+    /// `address` 为 `host:port`，或以 `unix:` 开头的本地套接字路径
+    pub fn new(address: impl Into<String>, timeout: Duration) -> Self {
+        Self {
+            address: address.into(),
+            timeout,
+        }
+    }
+
+    fn scan_stream(&self, path: &Path) -> std::io::Result<String> {
+        let mut file = std::fs::File::open(path)?;
+        match self.address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(socket) => {
+                let mut stream = std::os::unix::net::UnixStream::connect(socket)?;
+                stream.set_read_timeout(Some(self.timeout))?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                clamav_instream(&mut stream, &mut file)
+            }
+            #[cfg(not(unix))]
+            Some(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "当前平台不支持本地套接字",
+            )),
+            None => {
+                let mut stream = std::net::TcpStream::connect(&self.address)?;
+                stream.set_read_timeout(Some(self.timeout))?;
+                stream.set_write_timeout(Some(self.timeout))?;
+                clamav_instream(&mut stream, &mut file)
+            }
+        }
+    }
+}
+
+/// 以 `INSTREAM` 命令发送文件内容，返回守护进程的应答
+fn clamav_instream(
+    stream: &mut (impl Read + Write),
+    file: &mut impl Read,
+) -> std::io::Result<String> {
+    stream.write_all(b"zINSTREAM\0")?;
+    let mut buffer = vec![0; CLAMAV_CHUNK_BYTES];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        stream.write_all(&(read as u32).to_be_bytes())?;
+        stream.write_all(&buffer[..read])?;
+    }
+    stream.write_all(&[0; 4])?;
+    stream.flush()?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    let reply = String::from_utf8_lossy(&reply);
+    Ok(reply.trim_end_matches(['\0', '\n']).to_string())
+}
+
+/// 解析 ClamAV 的应答，例如 `stream: OK`、`stream: Eicar-Signature FOUND`
+fn parse_clamav_reply(reply: &str) -> ScanOutcome {
+    let result = reply.split_once(": ").map_or(reply, |(_, result)| result);
+    if result == "OK" {
+        ScanOutcome::clean()
+    } else if let Some(threat) = result.strip_suffix(" FOUND") {
+        ScanOutcome::infected(threat)
+    } else {
+        ScanOutcome::error(format!("ClamAV: {}", reply))
+    }
+}
+
+impl FileScanner for ClamAvScanner {
+    fn name(&self) -> &str {
+        "clamav"
+    }
+
+    fn scan(&self, path: &Path) -> ScanOutcome {
+        match self.scan_stream(path) {
+            Ok(reply) => parse_clamav_reply(&reply),
+            Err(e) => ScanOutcome::error(format!("连接 ClamAV {} 失败: {}", self.address, e)),
+        }
+    }
+}
+
+/// 自定义命令扫描器，文件路径作为最后一个参数传入
+#[derive(Debug, Clone)]
+pub struct CommandScanner {
+    program: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandScanner {
+    /// 创建命令扫描器
+    pub fn new(program: impl Into<PathBuf>, args: Vec<String>, timeout: Duration) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            timeout,
+        }
+    }
+}
+
+impl FileScanner for CommandScanner {
+    fn name(&self) -> &str {
+        self.program
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("command")
+    }
+
+    fn scan(&self, path: &Path) -> ScanOutcome {
+        let mut child = match Command::new(&self.program)
+            .args(&self.args)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                return ScanOutcome::error(format!(
+                    "启动扫描命令 {} 失败: {}",
+                    self.program.display(),
+                    e
+                ))
+            }
+        };
+        // 输出较多时命令会阻塞在写管道上，需要边运行边读取
+        let mut stdout = child.stdout.take();
+        let output = std::thread::spawn(move || {
+            let mut output = String::new();
+            if let Some(stdout) = stdout.as_mut() {
+                let _ = stdout.read_to_string(&mut output);
+            }
+            output
+        });
+
+        let started = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if started.elapsed() < self.timeout => {
+                    std::thread::sleep(COMMAND_POLL_INTERVAL)
+                }
+                Ok(None) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return ScanOutcome::error(format!(
+                        "扫描命令超时（{} 秒）",
+                        self.timeout.as_secs()
+                    ));
+                }
+                Err(e) => return ScanOutcome::error(format!("等待扫描命令失败: {}", e)),
+            }
+        };
+        let output = output.join().unwrap_or_default();
+        let detail = output.lines().next().unwrap_or_default().trim().to_string();
+        match status.code() {
+            Some(0) => ScanOutcome::clean(),
+            Some(1) if detail.is_empty() => ScanOutcome::infected("扫描命令报告发现威胁"),
+            Some(1) => ScanOutcome::infected(detail),
+            _ => ScanOutcome::error(format!("扫描命令异常退出: {}", status)),
+        }
+    }
+}
+
+/// 扫描器配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScannerConfig {
+    /// ClamAV 守护进程，`address` 为 `host:port` 或 `unix:/path/to/clamd.sock`
+    Clamav { address: String },
+    /// 自定义命令
+    Command {
+        program: PathBuf,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// 大小与扩展名策略
+    Policy(ScanPolicy),
+}
+
+/// 安全扫描配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    /// 按顺序执行的扫描器
+    pub scanners: Vec<ScannerConfig>,
+    /// 未通过扫描的文件移入的目录
+    pub quarantine_dir: PathBuf,
+    /// 单个扫描器的超时时间（秒）
+    pub timeout_secs: u64,
+    /// 扫描出错时按未通过处理；关闭后出错的文件仍标记为安全
+    pub quarantine_on_error: bool,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            scanners: Vec::new(),
+            quarantine_dir: std::env::temp_dir().join("iroh-node-quarantine"),
+            timeout_secs: 60,
+            quarantine_on_error: true,
+        }
+    }
+}
+
+impl ScanConfig {
+    /// 添加扫描器
+    pub fn with_scanner(mut self, scanner: ScannerConfig) -> Self {
+        self.scanners.push(scanner);
+        self
+    }
+
+    /// 设置隔离目录
+    pub fn with_quarantine_dir(mut self, quarantine_dir: impl Into<PathBuf>) -> Self {
+        self.quarantine_dir = quarantine_dir.into();
+        self
+    }
+
+    /// 设置单个扫描器的超时时间（秒）
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// 设置扫描出错时是否隔离
+    pub fn with_quarantine_on_error(mut self, quarantine_on_error: bool) -> Self {
+        self.quarantine_on_error = quarantine_on_error;
+        self
+    }
+}
+
+/// 单个扫描器的记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScannerResult {
+    /// 扫描器名称
+    pub scanner: String,
+    /// 检查结果
+    #[serde(flatten)]
+    pub outcome: ScanOutcome,
+}
+
+/// 一个文件的扫描结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanResult {
+    /// 文件名
+    pub file_name: String,
+    /// 扫描后文件所在的路径，隔离时为隔离目录中的路径
+    pub path: PathBuf,
+    /// 文件是否安全
+    pub safe: bool,
+    /// 是否已隔离
+    pub quarantined: bool,
+    /// 各扫描器的结果，发现威胁后不再执行后续扫描器
+    pub results: Vec<ScannerResult>,
+    /// 扫描时间
+    pub scanned_at: DateTime<Utc>,
+}
+
+impl ScanResult {
+    /// 第一个未通过的扫描器的说明
+    pub fn reason(&self) -> Option<String> {
+        self.results
+            .iter()
+            .find(|result| result.outcome.verdict != ScanVerdict::Clean)
+            .map(|result| {
+                format!(
+                    "{}: {}",
+                    result.scanner,
+                    result.outcome.detail.as_deref().unwrap_or("未通过")
+                )
+            })
+    }
+}
+
+/// 接收文件的扫描钩子
+pub struct FileScanHook {
+    config: ScanConfig,
+    scanners: Vec<Arc<dyn FileScanner>>,
+    events: broadcast::Sender<ScanResult>,
+}
+
+impl std::fmt::Debug for FileScanHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileScanHook")
+            .field("config", &self.config)
+            .field(
+                "scanners",
+                &self.scanners.iter().map(|s| s.name()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl FileScanHook {
+    /// 按配置创建扫描器
+    pub fn new(config: ScanConfig) -> Self {
+        let timeout = Duration::from_secs(config.timeout_secs.max(1));
+        let scanners = config
+            .scanners
+            .iter()
+            .map(|scanner| -> Arc<dyn FileScanner> {
+                match scanner {
+                    ScannerConfig::Clamav { address } => {
+                        Arc::new(ClamAvScanner::new(address.clone(), timeout))
+                    }
+                    ScannerConfig::Command { program, args } => {
+                        Arc::new(CommandScanner::new(program.clone(), args.clone(), timeout))
+                    }
+                    ScannerConfig::Policy(policy) => Arc::new(policy.clone()),
+                }
+            })
+            .collect();
+        let (events, _) = broadcast::channel(SCAN_EVENT_CAPACITY);
+        Self {
+            config,
+            scanners,
+            events,
+        }
+    }
+
+    /// 追加自定义扫描器
+    pub fn with_scanner(mut self, scanner: impl FileScanner + 'static) -> Self {
+        self.scanners.push(Arc::new(scanner));
+        self
+    }
+
+    /// 配置
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// 订阅扫描结果
+    pub fn subscribe(&self) -> broadcast::Receiver<ScanResult> {
+        self.events.subscribe()
+    }
+
+    /// 扫描文件，未通过时移入隔离目录
+    pub async fn scan(&self, path: &Path) -> NodeResult<ScanResult> {
+        let scanners = self.scanners.clone();
+        let target = path.to_path_buf();
+        let results = tokio::task::spawn_blocking(move || run_scanners(&scanners, &target))
+            .await
+            .map_err(|e| NodeError::IoError(format!("扫描任务失败: {}", e)))?;
+
+        let mut result = self.judge(path, results);
+        if !result.safe {
+            let quarantine_dir = self.config.quarantine_dir.clone();
+            let source = path.to_path_buf();
+            let file_name = result.file_name.clone();
+            result.path = tokio::task::spawn_blocking(move || {
+                quarantine(&quarantine_dir, &source, &file_name)
+            })
+            .await
+            .map_err(|e| NodeError::IoError(format!("隔离文件失败: {}", e)))??;
+            result.quarantined = true;
+        }
+        self.report(&result);
+        Ok(result)
+    }
+
+    /// 在当前线程中扫描文件，供已经运行在阻塞线程中的调用方（例如同步文件夹）使用
+    pub fn scan_blocking(&self, path: &Path) -> NodeResult<ScanResult> {
+        let results = run_scanners(&self.scanners, path);
+        let mut result = self.judge(path, results);
+        if !result.safe {
+            result.path = quarantine(&self.config.quarantine_dir, path, &result.file_name)?;
+            result.quarantined = true;
+        }
+        self.report(&result);
+        Ok(result)
+    }
+
+    /// 按各扫描器的结果判断文件是否安全
+    fn judge(&self, path: &Path, results: Vec<ScannerResult>) -> ScanResult {
+        ScanResult {
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: path.to_path_buf(),
+            safe: self.is_safe(&results),
+            quarantined: false,
+            results,
+            scanned_at: Utc::now(),
+        }
+    }
+
+    fn is_safe(&self, results: &[ScannerResult]) -> bool {
+        results.iter().all(|result| match result.outcome.verdict {
+            ScanVerdict::Clean => true,
+            ScanVerdict::Infected => false,
+            ScanVerdict::Error => !self.config.quarantine_on_error,
+        })
+    }
+
+    /// 记录并推送扫描结果
+    fn report(&self, result: &ScanResult) {
+        if result.quarantined {
+            warn!(
+                "文件未通过安全扫描，已隔离 {}: {}",
+                result.path.display(),
+                result.reason().unwrap_or_default()
+            );
+        } else {
+            info!("文件通过安全扫描: {}", result.path.display());
+        }
+        let _ = self.events.send(result.clone());
+    }
+}
+
+/// 移入隔离目录，同名文件已存在时加上随机前缀
+fn quarantine(quarantine_dir: &Path, path: &Path, file_name: &str) -> NodeResult<PathBuf> {
+    std::fs::create_dir_all(quarantine_dir)?;
+    let mut target = quarantine_dir.join(file_name);
+    if target.exists() {
+        target = quarantine_dir.join(format!("{}-{}", new_message_id(), file_name));
+    }
+    if std::fs::rename(path, &target).is_err() {
+        // 跨文件系统时改为复制后删除
+        std::fs::copy(path, &target)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(target)
+}
+
+/// 依次执行扫描器，发现威胁后停止
+fn run_scanners(scanners: &[Arc<dyn FileScanner>], path: &Path) -> Vec<ScannerResult> {
+    let mut results = Vec::new();
+    for scanner in scanners {
+        let outcome = scanner.scan(path);
+        let infected = outcome.verdict == ScanVerdict::Infected;
+        results.push(ScannerResult {
+            scanner: scanner.name().to_string(),
+            outcome,
+        });
+        if infected {
+            break;
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scan_and_quarantine() {
+        assert_eq!(parse_clamav_reply("stream: OK"), ScanOutcome::clean());
+        assert_eq!(
+            parse_clamav_reply("stream: Eicar-Signature FOUND"),
+            ScanOutcome::infected("Eicar-Signature")
+        );
+        assert_eq!(
+            parse_clamav_reply("INSTREAM size limit exceeded. ERROR").verdict,
+            ScanVerdict::Error
+        );
+
+        let dir = std::env::temp_dir().join(format!("iroh-node-scan-{}", new_message_id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let hook = FileScanHook::new(
+            ScanConfig::default()
+                .with_quarantine_dir(dir.join("quarantine"))
+                .with_scanner(ScannerConfig::Policy(
+                    ScanPolicy::default()
+                        .with_max_bytes(16)
+                        .with_blocked_extension("EXE"),
+                )),
+        );
+        let mut events = hook.subscribe();
+
+        let notes = dir.join("notes.txt");
+        std::fs::write(&notes, "hello").unwrap();
+        let result = hook.scan(&notes).await.unwrap();
+        assert!(result.safe && !result.quarantined);
+        assert!(notes.exists());
+        assert_eq!(events.recv().await.unwrap(), result);
+
+        let setup = dir.join("setup.exe");
+        std::fs::write(&setup, "MZ").unwrap();
+        let result = hook.scan(&setup).await.unwrap();
+        assert!(!result.safe && result.quarantined);
+        assert!(!setup.exists());
+        assert_eq!(result.path, dir.join("quarantine").join("setup.exe"));
+        assert_eq!(
+            result.reason().as_deref(),
+            Some("policy: 不允许的文件类型: .exe")
+        );
+
+        // 扫描出错默认按未通过处理
+        let missing = hook
+            .with_scanner(ClamAvScanner::new(
+                "unix:/nonexistent/clamd.sock",
+                Duration::from_secs(1),
+            ))
+            .scan(&notes)
+            .await
+            .unwrap();
+        assert!(missing.quarantined);
+        assert_eq!(missing.results[1].outcome.verdict, ScanVerdict::Error);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,247 @@
+//! 后台任务监督
+//!
+//! P2P节点的后台循环都交给监督器运行：每个话题一个 `JoinSet`，离开话题时取消该话题的全部任务，
+//! 节点停止时取消所有任务。任务 panic 后按指数退避重新启动，连续崩溃次数超过上限时放弃，
+//! 各任务的运行状况汇总到节点状态中
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use futures_lite::FutureExt;
+use iroh_gossip::proto::TopicId;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// 第一次重启前的等待时间
+const MIN_RESTART_BACKOFF: Duration = Duration::from_millis(500);
+/// 重启前的最长等待时间
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+/// 连续崩溃的最大次数，超过后不再重启
+const MAX_CONSECUTIVE_CRASHES: u32 = 5;
+/// 任务运行超过这个时间后再崩溃，不计入连续崩溃
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// 后台任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// 运行中
+    Running,
+    /// 崩溃后等待重启
+    Restarting,
+    /// 正常结束
+    Finished,
+    /// 连续崩溃次数过多，已放弃
+    Failed,
+}
+
+/// 后台任务的运行状况
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskHealth {
+    /// 任务名称
+    pub name: String,
+    /// 所属话题，节点级任务为空
+    pub topic_id: Option<String>,
+    /// 状态
+    pub state: TaskState,
+    /// 累计重启次数
+    pub restarts: u32,
+    /// 最近一次崩溃的panic信息
+    pub last_panic: Option<String>,
+    /// 最近一次崩溃的时间
+    pub last_panic_at: Option<DateTime<Utc>>,
+}
+
+type TaskKey = (Option<TopicId>, &'static str);
+type HealthMap = Arc<Mutex<HashMap<TaskKey, TaskHealth>>>;
+
+/// 后台任务监督器
+#[derive(Default)]
+pub(crate) struct TaskSupervisor {
+    tasks: Mutex<HashMap<Option<TopicId>, JoinSet<()>>>,
+    health: HealthMap,
+}
+
+impl TaskSupervisor {
+    /// 启动受监督的任务，`factory` 每次（重新）启动时创建新的任务循环；
+    /// `topic_id` 为空时是节点级任务，只在节点停止时取消
+    pub(crate) fn spawn<F, Fut>(&self, topic_id: Option<TopicId>, name: &'static str, factory: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let key = (topic_id, name);
+        self.health.lock().unwrap().insert(
+            key,
+            TaskHealth {
+                name: name.to_string(),
+                topic_id: topic_id.map(|topic_id| topic_id.to_string()),
+                state: TaskState::Running,
+                restarts: 0,
+                last_panic: None,
+                last_panic_at: None,
+            },
+        );
+
+        let task = supervise(key, factory, self.health.clone());
+        let mut tasks = self.tasks.lock().unwrap();
+        let set = tasks.entry(topic_id).or_default();
+        // 回收已经结束的任务
+        while set.try_join_next().is_some() {}
+        set.spawn(task);
+    }
+
+    /// 取消话题的全部任务
+    pub(crate) fn cancel_topic(&self, topic_id: &TopicId) {
+        let set = self.tasks.lock().unwrap().remove(&Some(*topic_id));
+        // 丢弃 JoinSet 时中止其中的所有任务
+        drop(set);
+        self.health
+            .lock()
+            .unwrap()
+            .retain(|(topic, _), _| topic.as_ref() != Some(topic_id));
+    }
+
+    /// 取消所有任务
+    pub(crate) fn shutdown(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        drop(tasks);
+        self.health.lock().unwrap().clear();
+    }
+
+    /// 各任务的运行状况，节点级任务在前
+    pub(crate) fn health(&self) -> Vec<TaskHealth> {
+        let mut health: Vec<TaskHealth> = self.health.lock().unwrap().values().cloned().collect();
+        health.sort_by(|a, b| (&a.topic_id, &a.name).cmp(&(&b.topic_id, &b.name)));
+        health
+    }
+}
+
+/// 运行任务循环，panic 后按退避时间重启
+async fn supervise<F, Fut>(key: TaskKey, mut factory: F, health: HealthMap)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let update = |f: &dyn Fn(&mut TaskHealth)| {
+        if let Some(entry) = health.lock().unwrap().get_mut(&key) {
+            f(entry);
+        }
+    };
+    let mut crashes = 0;
+    let mut backoff = MIN_RESTART_BACKOFF;
+
+    loop {
+        let started = Instant::now();
+        let panic = match AssertUnwindSafe(factory()).catch_unwind().await {
+            Ok(()) => {
+                update(&|entry| entry.state = TaskState::Finished);
+                return;
+            }
+            Err(panic) => panic_message(panic.as_ref()),
+        };
+
+        if started.elapsed() >= STABLE_RUN {
+            crashes = 0;
+            backoff = MIN_RESTART_BACKOFF;
+        }
+        crashes += 1;
+        let gave_up = crashes > MAX_CONSECUTIVE_CRASHES;
+        error!(
+            "后台任务 {} 崩溃（连续第 {} 次）: {}",
+            key.1, crashes, panic
+        );
+        update(&|entry| {
+            entry.state = if gave_up {
+                TaskState::Failed
+            } else {
+                TaskState::Restarting
+            };
+            entry.last_panic = Some(panic.clone());
+            entry.last_panic_at = Some(Utc::now());
+        });
+        if gave_up {
+            error!("后台任务 {} 连续崩溃次数过多，不再重启", key.1);
+            return;
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+        info!("重启后台任务 {}", key.1);
+        update(&|entry| {
+            entry.state = TaskState::Running;
+            entry.restarts += 1;
+        });
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_supervisor_restarts_and_cancels() {
+        let supervisor = TaskSupervisor::default();
+        let topic = TopicId::from_bytes([3; 32]);
+
+        // 前两次启动时崩溃，之后正常运行直到被取消
+        let runs = Arc::new(AtomicU32::new(0));
+        let counter = runs.clone();
+        supervisor.spawn(Some(topic), "flaky", move || {
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    panic!("boom");
+                }
+                std::future::pending::<()>().await;
+            }
+        });
+        supervisor.spawn(None, "done", || async {});
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        let health = supervisor.health();
+        assert_eq!(health.len(), 2);
+        assert_eq!(health[0].name, "done");
+        assert_eq!(health[0].state, TaskState::Finished);
+        assert_eq!(health[1].state, TaskState::Running);
+        assert_eq!(health[1].restarts, 2);
+        assert_eq!(health[1].last_panic.as_deref(), Some("boom"));
+
+        // 一直崩溃的任务最终被放弃
+        supervisor.spawn(None, "broken", || async { panic!("always") });
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        let broken = supervisor
+            .health()
+            .into_iter()
+            .find(|health| health.name == "broken")
+            .unwrap();
+        assert_eq!(broken.state, TaskState::Failed);
+        assert_eq!(broken.restarts, MAX_CONSECUTIVE_CRASHES);
+
+        supervisor.cancel_topic(&topic);
+        assert!(supervisor
+            .health()
+            .iter()
+            .all(|health| health.topic_id.is_none()));
+        supervisor.shutdown();
+        assert!(supervisor.health().is_empty());
+    }
+}
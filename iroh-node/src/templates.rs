@@ -0,0 +1,329 @@
+//! Agent模板交换
+//!
+//! 节点在固定的发现话题中通告可分享的Agent包（名称、描述、哈希与大小），
+//! 其他节点汇总通告形成模板列表，按哈希请求内容后导入。
+//! 模板内容以SHA-256哈希寻址：提供者收到请求后在话题中发送内容，
+//! 请求方校验哈希与包格式后才交给等待者，任何持有相同内容的节点都可以应答
+//!
+//! 发现话题中的模板消息由 [`handle_message`] 处理，节点只负责签名广播它返回的回复
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use iroh_gossip::proto::TopicId;
+use iroh::PublicKey;
+use rig_agent::AgentPackage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{oneshot, RwLock};
+use tracing::{debug, warn};
+
+use crate::{
+    error::{NodeError, NodeResult},
+    events::{EventBus, NodeEvent},
+    MessageType,
+};
+
+/// 发现话题的种子，话题ID为其SHA-256哈希
+const TEMPLATE_TOPIC_SEED: &[u8] = b"iroh-node/template-exchange/1";
+
+/// 单个模板内容的最大字节数，需能放入一条话题消息
+pub(crate) const MAX_TEMPLATE_BYTES: usize = 384 * 1024;
+
+/// 模板描述的最大字节数
+pub(crate) const MAX_TEMPLATE_DESCRIPTION: usize = 1024;
+
+/// 模板交换的发现话题
+pub fn template_topic() -> TopicId {
+    TopicId::from_bytes(Sha256::digest(TEMPLATE_TOPIC_SEED).into())
+}
+
+/// 计算模板内容的哈希
+pub fn template_hash(bytes: &[u8]) -> String {
+    data_encoding::HEXLOWER.encode(&Sha256::digest(bytes))
+}
+
+/// 模板通告
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateAnnouncement {
+    /// 内容哈希（SHA-256，十六进制）
+    pub hash: String,
+    /// 模板名称
+    pub name: String,
+    /// 描述
+    pub description: Option<String>,
+    /// 内容字节数
+    pub size: u64,
+}
+
+/// 其他节点通告的模板
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteTemplate {
+    /// 通告内容
+    #[serde(flatten)]
+    pub announcement: TemplateAnnouncement,
+    /// 通告该模板的节点ID
+    pub providers: Vec<String>,
+    /// 最近一次收到通告的时间
+    pub last_seen: DateTime<Utc>,
+}
+
+/// 模板交换状态：本节点分享的模板、收到的通告与等待中的请求
+#[derive(Debug, Default)]
+pub struct TemplateExchange {
+    /// 本节点分享的模板及其内容
+    shared: BTreeMap<String, (TemplateAnnouncement, Vec<u8>)>,
+    /// 其他节点通告的模板
+    remote: BTreeMap<String, RemoteTemplate>,
+    /// 等待模板内容的请求
+    pending: HashMap<String, Vec<oneshot::Sender<Vec<u8>>>>,
+}
+
+impl TemplateExchange {
+    /// 分享Agent包，返回其通告
+    pub fn share(&mut self, package: &AgentPackage) -> NodeResult<TemplateAnnouncement> {
+        let bytes = package
+            .to_bytes()
+            .map_err(|e| NodeError::EncodeError(format!("编码Agent包失败: {}", e)))?;
+        if bytes.len() > MAX_TEMPLATE_BYTES {
+            return Err(NodeError::ConfigError(format!(
+                "Agent包过大: {} 字节，上限 {} 字节",
+                bytes.len(),
+                MAX_TEMPLATE_BYTES
+            )));
+        }
+
+        let mut description = package.description.clone();
+        if let Some(text) = &mut description {
+            truncate_utf8(text, MAX_TEMPLATE_DESCRIPTION);
+        }
+        let announcement = TemplateAnnouncement {
+            hash: template_hash(&bytes),
+            name: package.name.clone(),
+            description,
+            size: bytes.len() as u64,
+        };
+        self.shared
+            .insert(announcement.hash.clone(), (announcement.clone(), bytes));
+        Ok(announcement)
+    }
+
+    /// 停止分享模板
+    pub fn unshare(&mut self, hash: &str) -> bool {
+        self.shared.remove(hash).is_some()
+    }
+
+    /// 本节点分享的模板
+    pub fn shared(&self) -> Vec<TemplateAnnouncement> {
+        self.shared
+            .values()
+            .map(|(announcement, _)| announcement.clone())
+            .collect()
+    }
+
+    /// 本节点持有的模板内容
+    pub fn content(&self, hash: &str) -> Option<&[u8]> {
+        self.shared.get(hash).map(|(_, bytes)| bytes.as_slice())
+    }
+
+    /// 记录其他节点的通告，返回新出现的模板数量
+    pub fn record_announcements(
+        &mut self,
+        from: &str,
+        templates: Vec<TemplateAnnouncement>,
+    ) -> usize {
+        let now = Utc::now();
+        let mut added = 0;
+        for announcement in templates {
+            let entry = self
+                .remote
+                .entry(announcement.hash.clone())
+                .or_insert_with(|| {
+                    added += 1;
+                    RemoteTemplate {
+                        announcement: announcement.clone(),
+                        providers: Vec::new(),
+                        last_seen: now,
+                    }
+                });
+            if !entry.providers.iter().any(|provider| provider == from) {
+                entry.providers.push(from.to_string());
+            }
+            entry.last_seen = now;
+        }
+        added
+    }
+
+    /// 模板列表（含本节点分享的模板），按名称排序
+    pub fn list(&self, local_node_id: &str) -> Vec<RemoteTemplate> {
+        let mut templates: BTreeMap<String, RemoteTemplate> = self.remote.clone();
+        for (hash, (announcement, _)) in &self.shared {
+            let template = templates
+                .entry(hash.clone())
+                .or_insert_with(|| RemoteTemplate {
+                    announcement: announcement.clone(),
+                    providers: Vec::new(),
+                    last_seen: Utc::now(),
+                });
+            if !template
+                .providers
+                .iter()
+                .any(|provider| provider == local_node_id)
+            {
+                template.providers.push(local_node_id.to_string());
+            }
+        }
+
+        let mut templates: Vec<RemoteTemplate> = templates.into_values().collect();
+        templates.sort_by(|a, b| a.announcement.name.cmp(&b.announcement.name));
+        templates
+    }
+
+    /// 登记等待模板内容的请求
+    pub fn wait_for(&mut self, hash: &str) -> oneshot::Receiver<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.entry(hash.to_string()).or_default().push(tx);
+        rx
+    }
+
+    /// 是否有等待该模板内容的请求
+    pub fn is_pending(&self, hash: &str) -> bool {
+        self.pending.contains_key(hash)
+    }
+
+    /// 清理已放弃等待（超时）的请求
+    pub fn abandon(&mut self, hash: &str) {
+        if let Some(waiters) = self.pending.get_mut(hash) {
+            waiters.retain(|waiter| !waiter.is_closed());
+            if waiters.is_empty() {
+                self.pending.remove(hash);
+            }
+        }
+    }
+
+    /// 交付收到的模板内容，哈希不符或不是合法的Agent包时丢弃
+    pub fn deliver(&mut self, hash: &str, data: Vec<u8>) -> NodeResult<bool> {
+        if !self.pending.contains_key(hash) {
+            return Ok(false);
+        }
+        if template_hash(&data) != hash {
+            return Err(NodeError::InvalidMessage(format!(
+                "模板内容与哈希不符: {}",
+                hash
+            )));
+        }
+        AgentPackage::from_bytes(&data)
+            .map_err(|e| NodeError::InvalidMessage(format!("模板不是合法的Agent包: {}", e)))?;
+
+        for waiter in self.pending.remove(hash).unwrap_or_default() {
+            let _ = waiter.send(data.clone());
+        }
+        Ok(true)
+    }
+}
+
+/// 按UTF-8字符边界截断
+fn truncate_utf8(text: &mut String, max: usize) {
+    if text.len() <= max {
+        return;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+}
+
+/// 本节点分享的模板通告，没有分享模板时为空
+pub(crate) async fn announcement(templates: &RwLock<TemplateExchange>) -> Option<MessageType> {
+    let shared = templates.read().await.shared();
+    (!shared.is_empty()).then_some(MessageType::TemplateAnnounce { templates: shared })
+}
+
+/// 处理发现话题中的模板消息，返回需要广播的回复
+pub(crate) async fn handle_message(
+    templates: &RwLock<TemplateExchange>,
+    events: &EventBus,
+    from: &PublicKey,
+    message: MessageType,
+) -> Option<MessageType> {
+    match message {
+        MessageType::TemplateAnnounce {
+            templates: announced,
+        } => {
+            let count = announced.len();
+            let added = templates
+                .write()
+                .await
+                .record_announcements(&from.to_string(), announced);
+            debug!(
+                "节点 {} 通告了 {} 个Agent模板，新增 {} 个",
+                from.fmt_short(),
+                count,
+                added
+            );
+            if added > 0 {
+                events.publish(NodeEvent::TemplatesAnnounced {
+                    from: from.to_string(),
+                    added,
+                });
+            }
+            None
+        }
+        MessageType::TemplateFetch { hash } => {
+            let data = templates.read().await.content(&hash).map(<[u8]>::to_vec)?;
+            debug!("向 {} 发送Agent模板 {}", from.fmt_short(), hash);
+            Some(MessageType::TemplateContent { hash, data })
+        }
+        MessageType::TemplateContent { hash, data } => {
+            match templates.write().await.deliver(&hash, data) {
+                Ok(true) => debug!("收到来自 {} 的Agent模板 {}", from.fmt_short(), hash),
+                Ok(false) => {}
+                Err(e) => warn!("丢弃来自 {} 的Agent模板: {}", from.fmt_short(), e),
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rig_agent::AgentConfig;
+
+    #[tokio::test]
+    async fn test_template_exchange() {
+        let package = AgentPackage::from_config("translator", &AgentConfig::default())
+            .with_description("中英互译");
+        let mut provider = TemplateExchange::default();
+        let announcement = provider.share(&package).unwrap();
+        assert_eq!(announcement.name, "translator");
+        assert_eq!(announcement.hash.len(), 64);
+        let content = provider.content(&announcement.hash).unwrap().to_vec();
+        assert_eq!(announcement.size, content.len() as u64);
+
+        let mut peer = TemplateExchange::default();
+        assert_eq!(
+            peer.record_announcements("node-a", vec![announcement.clone()]),
+            1
+        );
+        assert_eq!(
+            peer.record_announcements("node-b", vec![announcement.clone()]),
+            0
+        );
+        let listed = peer.list("node-c");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].providers, vec!["node-a", "node-b"]);
+
+        // 未请求的内容、哈希不符的内容都不会交付
+        assert!(!peer.deliver(&announcement.hash, content.clone()).unwrap());
+        let waiter = peer.wait_for(&announcement.hash);
+        assert!(peer.deliver(&announcement.hash, b"{}".to_vec()).is_err());
+        assert!(peer.deliver(&announcement.hash, content.clone()).unwrap());
+        assert_eq!(waiter.await.unwrap(), content);
+
+        assert!(!peer.is_pending(&announcement.hash));
+        assert_eq!(provider.list("node-a")[0].providers, vec!["node-a"]);
+    }
+}
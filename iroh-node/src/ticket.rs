@@ -0,0 +1,200 @@
+//! 票据压缩
+//!
+//! 票据中包含多个 `NodeAddr` 时，base32 编码会很长，不便于复制粘贴或生成二维码。
+//! 紧凑格式可以只保留中继地址、用 zstd 压缩 postcard 负载，并以带校验和的 base58 编码。
+//! 紧凑票据以 `1` 开头，该字符不在 base32 字母表中，解析时据此区分新旧格式
+
+use iroh::NodeAddr;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{NodeError, NodeResult},
+    Ticket,
+};
+
+/// 紧凑票据前缀
+pub(crate) const COMPACT_PREFIX: char = '1';
+
+/// 紧凑票据格式版本
+const COMPACT_VERSION: u8 = 1;
+
+/// 标志位：负载经过 zstd 压缩
+const FLAG_ZSTD: u8 = 1 << 0;
+
+/// zstd 压缩级别
+const ZSTD_LEVEL: i32 = 19;
+
+/// 解压后负载的最大字节数，防止恶意票据占用大量内存
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// 票据生成选项
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TicketOptions {
+    /// 有中继地址的节点只保留中继地址，去掉直连地址
+    pub relay_only: bool,
+    /// 使用紧凑格式（zstd 压缩 + base58check），关闭时生成旧版 base32 票据
+    pub compact: bool,
+}
+
+impl Default for TicketOptions {
+    fn default() -> Self {
+        Self {
+            relay_only: false,
+            compact: true,
+        }
+    }
+}
+
+impl TicketOptions {
+    /// 旧版 base32 票据，兼容尚未支持紧凑格式的节点
+    pub fn legacy() -> Self {
+        Self {
+            relay_only: false,
+            compact: false,
+        }
+    }
+
+    /// 最短票据：只保留中继地址并使用紧凑格式
+    pub fn shortest() -> Self {
+        Self {
+            relay_only: true,
+            compact: true,
+        }
+    }
+}
+
+impl Ticket {
+    /// 只保留中继地址；没有中继地址的节点保留直连地址，否则无法连接
+    pub fn relay_only(&self) -> Self {
+        let peers = self
+            .peers
+            .iter()
+            .map(|peer| match peer.relay_url() {
+                Some(relay_url) => NodeAddr::new(peer.node_id).with_relay_url(relay_url.clone()),
+                None => peer.clone(),
+            })
+            .collect();
+        Self {
+            topic: self.topic,
+            peers,
+        }
+    }
+
+    /// 按选项编码为字符串
+    pub fn encode(&self, options: &TicketOptions) -> NodeResult<String> {
+        let ticket = if options.relay_only {
+            self.relay_only()
+        } else {
+            self.clone()
+        };
+
+        if options.compact {
+            ticket.to_compact_string()
+        } else {
+            Ok(ticket.to_string())
+        }
+    }
+
+    /// 编码为紧凑格式
+    pub fn to_compact_string(&self) -> NodeResult<String> {
+        let payload = self.to_bytes();
+        let compressed = zstd::bulk::compress(&payload, ZSTD_LEVEL)
+            .map_err(|e| NodeError::EncodeError(format!("压缩票据失败: {}", e)))?;
+
+        // 负载很小时压缩反而更长，此时保留原始负载
+        let mut data = vec![COMPACT_VERSION];
+        if compressed.len() < payload.len() {
+            data.push(FLAG_ZSTD);
+            data.extend_from_slice(&compressed);
+        } else {
+            data.push(0);
+            data.extend_from_slice(&payload);
+        }
+
+        let encoded = bs58::encode(data).with_check().into_string();
+        Ok(format!("{}{}", COMPACT_PREFIX, encoded))
+    }
+
+    /// 从紧凑格式解码（不含前缀）
+    pub(crate) fn from_compact_str(s: &str) -> NodeResult<Self> {
+        let data = bs58::decode(s)
+            .with_check(None)
+            .into_vec()
+            .map_err(|e| NodeError::DecodeError(format!("解码票据失败: {}", e)))?;
+
+        let (version, flags, payload) = match data.as_slice() {
+            [version, flags, payload @ ..] => (*version, *flags, payload),
+            _ => return Err(NodeError::DecodeError("票据过短".to_string())),
+        };
+        if version != COMPACT_VERSION {
+            return Err(NodeError::DecodeError(format!(
+                "不支持的票据格式版本: {}",
+                version
+            )));
+        }
+
+        if flags & FLAG_ZSTD != 0 {
+            let payload = zstd::bulk::decompress(payload, MAX_PAYLOAD_BYTES)
+                .map_err(|e| NodeError::DecodeError(format!("解压票据失败: {}", e)))?;
+            Self::from_bytes(&payload)
+        } else {
+            Self::from_bytes(payload)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use iroh_gossip::proto::TopicId;
+    use iroh::SecretKey;
+
+    use super::*;
+
+    fn ticket_with_peers(count: u8) -> Ticket {
+        let relay_url: iroh::RelayUrl = "https://relay.example.com".parse().unwrap();
+        let peers = (0..count)
+            .map(|i| {
+                let node_id = SecretKey::from_bytes(&[i + 1; 32]).public();
+                let addrs =
+                    (0..4).map(|port| format!("192.168.1.{}:{}", i, 4000 + port).parse().unwrap());
+                NodeAddr::from_parts(node_id, Some(relay_url.clone()), addrs)
+            })
+            .collect();
+        Ticket {
+            topic: TopicId::from_bytes([9; 32]),
+            peers,
+        }
+    }
+
+    #[test]
+    fn test_compact_ticket_round_trip() {
+        let ticket = ticket_with_peers(3);
+        let legacy = ticket.encode(&TicketOptions::legacy()).unwrap();
+        let compact = ticket.encode(&TicketOptions::default()).unwrap();
+        let shortest = ticket.encode(&TicketOptions::shortest()).unwrap();
+        assert!(compact.starts_with(COMPACT_PREFIX));
+        assert!(shortest.len() < compact.len());
+        assert!(compact.len() < legacy.len());
+
+        // 新旧格式都可以解析
+        for text in [&legacy, &compact] {
+            let parsed = Ticket::from_str(text).unwrap();
+            assert_eq!(parsed.topic, ticket.topic);
+            assert_eq!(parsed.peers, ticket.peers);
+        }
+        let parsed = Ticket::from_str(&shortest).unwrap();
+        assert!(parsed
+            .peers
+            .iter()
+            .all(|peer| peer.direct_addresses().next().is_none() && peer.relay_url().is_some()));
+
+        // 校验和可以发现复制错误
+        let mut corrupted = compact.clone().into_bytes();
+        let last = corrupted.len() - 1;
+        corrupted[last] = if corrupted[last] == b'2' { b'3' } else { b'2' };
+        assert!(Ticket::from_str(std::str::from_utf8(&corrupted).unwrap()).is_err());
+    }
+}
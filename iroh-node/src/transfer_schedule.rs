@@ -0,0 +1,312 @@
+//! 传输调度策略
+//!
+//! 大文件传输只在配置的时间段内进行，系统报告按流量计费的网络（移动数据、热点）时自动暂停，
+//! 离开时间段或网络恢复后再继续。[`TransferGate`] 保存策略与当前网络状态，
+//! 状态变化时通知订阅者，由各传输（例如分块上传）据此暂停与恢复
+
+use std::time::Duration;
+
+use chrono::{Local, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+/// 一天的秒数
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+/// 因按流量计费暂停时建议的重试间隔
+const METERED_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// 允许进行大文件传输的时间段（本地时间），`end` 早于 `start` 时跨越午夜
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferWindow {
+    /// 开始时间
+    pub start: NaiveTime,
+    /// 结束时间（不含）
+    pub end: NaiveTime,
+}
+
+impl TransferWindow {
+    /// 创建时间段
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    /// 时间是否在时间段内；开始与结束相同时表示全天
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start == self.end || (self.start <= time && time < self.end)
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// 距离下一次开始的时长
+    fn until_start(&self, time: NaiveTime) -> Duration {
+        let start = self.start.num_seconds_from_midnight();
+        let now = time.num_seconds_from_midnight();
+        Duration::from_secs(u64::from((start + SECONDS_PER_DAY - now) % SECONDS_PER_DAY))
+    }
+}
+
+/// 传输暂停的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    /// 当前网络按流量计费
+    Metered,
+    /// 不在允许的时间段内
+    OutsideWindow,
+}
+
+/// 传输调度策略
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferSchedule {
+    /// 达到该字节数的传输受调度限制，更小的传输随时进行
+    pub large_transfer_bytes: u64,
+    /// 允许进行大文件传输的时间段，为空时不限制时间
+    pub windows: Vec<TransferWindow>,
+    /// 网络按流量计费时暂停大文件传输
+    pub pause_on_metered: bool,
+}
+
+impl Default for TransferSchedule {
+    fn default() -> Self {
+        Self {
+            large_transfer_bytes: 100 * 1024 * 1024,
+            windows: Vec::new(),
+            pause_on_metered: true,
+        }
+    }
+}
+
+impl TransferSchedule {
+    /// 设置受调度限制的传输大小
+    pub fn with_large_transfer_bytes(mut self, large_transfer_bytes: u64) -> Self {
+        self.large_transfer_bytes = large_transfer_bytes;
+        self
+    }
+
+    /// 添加允许传输的时间段
+    pub fn with_window(mut self, start: NaiveTime, end: NaiveTime) -> Self {
+        self.windows.push(TransferWindow::new(start, end));
+        self
+    }
+
+    /// 设置网络按流量计费时是否暂停
+    pub fn with_pause_on_metered(mut self, pause_on_metered: bool) -> Self {
+        self.pause_on_metered = pause_on_metered;
+        self
+    }
+
+    /// 时间是否在允许的时间段内
+    pub fn in_window(&self, time: NaiveTime) -> bool {
+        self.windows.is_empty() || self.windows.iter().any(|window| window.contains(time))
+    }
+
+    /// 检查 `size` 字节的传输此时能否进行，不能时返回暂停原因
+    pub fn check(&self, size: u64, metered: bool, time: NaiveTime) -> Option<PauseReason> {
+        if size < self.large_transfer_bytes {
+            return None;
+        }
+        if metered && self.pause_on_metered {
+            return Some(PauseReason::Metered);
+        }
+        (!self.in_window(time)).then_some(PauseReason::OutsideWindow)
+    }
+
+    /// 距离下一个时间段开始的时长，已在时间段内或没有时间段时为空
+    pub fn next_window_in(&self, time: NaiveTime) -> Option<Duration> {
+        if self.in_window(time) {
+            return None;
+        }
+        self.windows
+            .iter()
+            .map(|window| window.until_start(time))
+            .min()
+    }
+
+    /// 暂停后建议的重试间隔
+    pub fn retry_after(&self, reason: PauseReason, time: NaiveTime) -> Duration {
+        match reason {
+            PauseReason::Metered => METERED_RETRY_AFTER,
+            PauseReason::OutsideWindow => self
+                .next_window_in(time)
+                .unwrap_or(METERED_RETRY_AFTER)
+                .max(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// 传输调度的当前状态，推送给界面
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferScheduleStatus {
+    /// 调度策略
+    pub schedule: TransferSchedule,
+    /// 当前网络是否按流量计费
+    pub metered: bool,
+    /// 此时是否在允许的时间段内
+    pub in_window: bool,
+    /// 大文件传输此时暂停的原因，可以进行时为空
+    pub paused: Option<PauseReason>,
+    /// 距离下一个时间段开始的秒数
+    pub next_window_in_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct GateState {
+    schedule: TransferSchedule,
+    metered: bool,
+}
+
+/// 传输调度闸门，保存调度策略与网络状态，多个传输子系统可以共用
+#[derive(Debug)]
+pub struct TransferGate {
+    state: watch::Sender<GateState>,
+}
+
+impl Default for TransferGate {
+    fn default() -> Self {
+        Self::new(TransferSchedule::default())
+    }
+}
+
+impl TransferGate {
+    /// 创建调度闸门
+    pub fn new(schedule: TransferSchedule) -> Self {
+        let (state, _) = watch::channel(GateState {
+            schedule,
+            metered: false,
+        });
+        Self { state }
+    }
+
+    /// 当前调度策略
+    pub fn schedule(&self) -> TransferSchedule {
+        self.state.borrow().schedule.clone()
+    }
+
+    /// 替换调度策略
+    pub fn set_schedule(&self, schedule: TransferSchedule) {
+        self.state.send_if_modified(|state| {
+            let changed = state.schedule != schedule;
+            state.schedule = schedule;
+            changed
+        });
+    }
+
+    /// 当前网络是否按流量计费
+    pub fn is_metered(&self) -> bool {
+        self.state.borrow().metered
+    }
+
+    /// 记录系统报告的网络计费状态，状态变化时返回 true
+    pub fn set_metered(&self, metered: bool) -> bool {
+        self.state.send_if_modified(|state| {
+            let changed = state.metered != metered;
+            state.metered = metered;
+            changed
+        })
+    }
+
+    /// 检查 `size` 字节的传输此时能否进行，不能时返回暂停原因
+    pub fn check(&self, size: u64) -> Option<PauseReason> {
+        let state = self.state.borrow();
+        state
+            .schedule
+            .check(size, state.metered, Local::now().time())
+    }
+
+    /// 暂停后建议的重试间隔
+    pub fn retry_after(&self, reason: PauseReason) -> Duration {
+        self.state
+            .borrow()
+            .schedule
+            .retry_after(reason, Local::now().time())
+    }
+
+    /// 当前状态
+    pub fn status(&self) -> TransferScheduleStatus {
+        let state = self.state.borrow();
+        let time = Local::now().time();
+        TransferScheduleStatus {
+            schedule: state.schedule.clone(),
+            metered: state.metered,
+            in_window: state.schedule.in_window(time),
+            paused: state
+                .schedule
+                .check(state.schedule.large_transfer_bytes, state.metered, time),
+            next_window_in_secs: state
+                .schedule
+                .next_window_in(time)
+                .map(|wait| wait.as_secs()),
+        }
+    }
+
+    /// 订阅策略与网络状态的变化
+    pub fn subscribe(&self) -> TransferGateSubscription {
+        TransferGateSubscription(self.state.subscribe())
+    }
+}
+
+/// 调度闸门的变化订阅
+#[derive(Debug)]
+pub struct TransferGateSubscription(watch::Receiver<GateState>);
+
+impl TransferGateSubscription {
+    /// 等待下一次变化，闸门已被释放时返回 false
+    pub async fn changed(&mut self) -> bool {
+        self.0.changed().await.is_ok()
+    }
+
+    /// 是否有尚未处理的变化
+    pub fn has_changed(&self) -> bool {
+        self.0.has_changed().unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_transfer_schedule() {
+        let schedule = TransferSchedule::default()
+            .with_large_transfer_bytes(1000)
+            .with_window(at(22, 0), at(6, 0));
+        assert!(schedule.in_window(at(23, 30)));
+        assert!(schedule.in_window(at(5, 59)));
+        assert!(!schedule.in_window(at(6, 0)));
+
+        // 小传输随时进行
+        assert_eq!(schedule.check(999, true, at(12, 0)), None);
+        assert_eq!(
+            schedule.check(1000, false, at(12, 0)),
+            Some(PauseReason::OutsideWindow)
+        );
+        assert_eq!(
+            schedule.check(1000, true, at(23, 0)),
+            Some(PauseReason::Metered)
+        );
+        assert_eq!(schedule.check(1000, false, at(23, 0)), None);
+        assert_eq!(
+            schedule.retry_after(PauseReason::OutsideWindow, at(21, 30)),
+            Duration::from_secs(30 * 60)
+        );
+        assert!(TransferSchedule::default()
+            .with_pause_on_metered(false)
+            .check(u64::MAX, true, at(12, 0))
+            .is_none());
+
+        let gate = TransferGate::new(schedule);
+        let receiver = gate.subscribe();
+        assert!(gate.set_metered(true));
+        assert!(!gate.set_metered(true));
+        assert!(receiver.has_changed());
+        assert_eq!(gate.check(1000), Some(PauseReason::Metered));
+        assert!(gate.status().metered);
+    }
+}
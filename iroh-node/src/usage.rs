@@ -0,0 +1,485 @@
+//! 用量统计
+//!
+//! 按小时汇总P2P消息数、流量、Agent请求数、令牌数与费用，供仪表盘按小时或按天查询。
+//! 模型未返回令牌用量时按字符数估算；费用依据配置中的模型单价计算，未配置单价的模型记为0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use rig_agent::{core::AgentStats, AgentResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::TopicStats;
+
+/// 小时桶的保留时长（小时）
+const RETENTION_HOURS: i64 = 31 * 24;
+
+/// 每个令牌大约对应的字符数，用于估算
+const CHARS_PER_TOKEN: usize = 4;
+
+/// 模型单价（每百万令牌）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// 提示令牌单价
+    pub prompt: f64,
+    /// 完成令牌单价
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt + completion_tokens as f64 * self.completion)
+            / 1_000_000.0
+    }
+}
+
+/// 统计时间窗口
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsWindow {
+    /// 按小时
+    #[default]
+    Hour,
+    /// 按天（UTC）
+    Day,
+}
+
+impl StatsWindow {
+    /// 窗口长度（秒）
+    fn seconds(self) -> i64 {
+        match self {
+            StatsWindow::Hour => 3600,
+            StatsWindow::Day => 86400,
+        }
+    }
+
+    /// 未指定数量时返回的窗口数
+    fn default_limit(self) -> usize {
+        match self {
+            StatsWindow::Hour => 24,
+            StatsWindow::Day => 30,
+        }
+    }
+
+    /// 保留期内最多可返回的窗口数
+    fn max_limit(self) -> usize {
+        (RETENTION_HOURS * 3600 / self.seconds()) as usize
+    }
+
+    /// 时间戳所在窗口的起始秒数
+    fn floor(self, timestamp: i64) -> i64 {
+        timestamp.div_euclid(self.seconds()) * self.seconds()
+    }
+}
+
+/// 一个时间窗口内的用量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageBucket {
+    /// 窗口起始时间
+    pub start: DateTime<Utc>,
+    /// 收到的消息数量
+    pub messages_in: u64,
+    /// 发出的消息数量
+    pub messages_out: u64,
+    /// 收到的字节数
+    pub bytes_in: u64,
+    /// 发出的字节数
+    pub bytes_out: u64,
+    /// 本地处理的Agent请求数
+    pub agent_requests: u64,
+    /// 处理失败的Agent请求数
+    pub agent_errors: u64,
+    /// 提示令牌数
+    pub prompt_tokens: u64,
+    /// 完成令牌数
+    pub completion_tokens: u64,
+    /// 费用
+    pub cost: f64,
+}
+
+impl UsageBucket {
+    fn empty(start: i64) -> Self {
+        Self {
+            start: Utc.timestamp_opt(start, 0).single().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, other: &UsageBucket) {
+        self.messages_in += other.messages_in;
+        self.messages_out += other.messages_out;
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.agent_requests += other.agent_requests;
+        self.agent_errors += other.agent_errors;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.cost += other.cost;
+    }
+}
+
+/// 单个Agent的用量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentUsage {
+    /// Agent ID
+    pub agent_id: String,
+    /// 提供商，Agent已被回收时为空
+    pub provider: Option<String>,
+    /// 模型，Agent已被回收时为最后一次请求使用的模型
+    pub model: Option<String>,
+    /// 对话历史中的消息数量
+    pub total_messages: usize,
+    /// 本地处理的请求数
+    pub requests: u64,
+    /// 处理失败的请求数
+    pub errors: u64,
+    /// 提示令牌数
+    pub prompt_tokens: u64,
+    /// 完成令牌数
+    pub completion_tokens: u64,
+    /// 费用
+    pub cost: f64,
+    /// 对话历史中被点赞的回复数
+    pub positive_feedback: usize,
+    /// 对话历史中被点踩的回复数
+    pub negative_feedback: usize,
+    /// 最后活动时间
+    pub last_activity: Option<DateTime<Utc>>,
+}
+
+/// 用量汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSummary {
+    /// 开始统计的时间
+    pub started_at: DateTime<Utc>,
+    /// 生成时间
+    pub generated_at: DateTime<Utc>,
+    /// 节点启动以来的总用量
+    pub totals: UsageBucket,
+    /// 各Agent的用量
+    pub agents: Vec<AgentUsage>,
+    /// 各活跃话题的流量
+    pub topics: Vec<TopicStats>,
+}
+
+#[derive(Default)]
+struct LedgerState {
+    totals: UsageBucket,
+    hours: BTreeMap<i64, UsageBucket>,
+    agents: HashMap<String, AgentUsage>,
+}
+
+impl LedgerState {
+    /// 当前小时的桶，同时清理超出保留期的旧桶
+    fn current_hour(&mut self) -> &mut UsageBucket {
+        let hour = StatsWindow::Hour.floor(Utc::now().timestamp());
+        let cutoff = hour - RETENTION_HOURS * 3600;
+        while let Some(entry) = self.hours.first_entry() {
+            if *entry.key() > cutoff {
+                break;
+            }
+            entry.remove();
+        }
+        self.hours
+            .entry(hour)
+            .or_insert_with(|| UsageBucket::empty(hour))
+    }
+
+    fn record(&mut self, update: impl Fn(&mut UsageBucket)) {
+        update(&mut self.totals);
+        update(self.current_hour());
+    }
+}
+
+/// 用量账本
+pub struct UsageLedger {
+    prices: HashMap<String, ModelPrice>,
+    state: Mutex<LedgerState>,
+}
+
+impl UsageLedger {
+    /// 使用模型单价创建账本
+    pub fn new(prices: HashMap<String, ModelPrice>) -> Self {
+        let state = LedgerState {
+            totals: UsageBucket {
+                start: Utc::now(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        Self {
+            prices,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut LedgerState) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// 记录收到的消息
+    pub(crate) fn record_in(&self, bytes: usize) {
+        self.with_state(|state| {
+            state.record(|bucket| {
+                bucket.messages_in += 1;
+                bucket.bytes_in += bytes as u64;
+            })
+        });
+    }
+
+    /// 记录发出的消息
+    pub(crate) fn record_out(&self, bytes: usize) {
+        self.with_state(|state| {
+            state.record(|bucket| {
+                bucket.messages_out += 1;
+                bucket.bytes_out += bytes as u64;
+            })
+        });
+    }
+
+    /// 记录一次Agent请求的结果，`response` 为空表示请求失败
+    pub(crate) fn record_agent(
+        &self,
+        agent_id: &str,
+        prompt: &str,
+        response: Option<&AgentResponse>,
+    ) {
+        let (model, prompt_tokens, completion_tokens) = match response {
+            Some(response) => {
+                let (prompt_tokens, completion_tokens) = match &response.usage {
+                    Some(usage) => (usage.prompt_tokens as u64, usage.completion_tokens as u64),
+                    None => (estimate_tokens(prompt), estimate_tokens(&response.content)),
+                };
+                (
+                    Some(response.model.clone()),
+                    prompt_tokens,
+                    completion_tokens,
+                )
+            }
+            None => (None, 0, 0),
+        };
+        let cost = model
+            .as_ref()
+            .and_then(|model| self.prices.get(model))
+            .map_or(0.0, |price| price.cost(prompt_tokens, completion_tokens));
+        let failed = response.is_none() as u64;
+
+        self.with_state(|state| {
+            state.record(|bucket| {
+                bucket.agent_requests += 1;
+                bucket.agent_errors += failed;
+                bucket.prompt_tokens += prompt_tokens;
+                bucket.completion_tokens += completion_tokens;
+                bucket.cost += cost;
+            });
+
+            let usage = state
+                .agents
+                .entry(agent_id.to_string())
+                .or_insert_with(|| AgentUsage {
+                    agent_id: agent_id.to_string(),
+                    ..Default::default()
+                });
+            usage.requests += 1;
+            usage.errors += failed;
+            usage.prompt_tokens += prompt_tokens;
+            usage.completion_tokens += completion_tokens;
+            usage.cost += cost;
+            usage.last_activity = Some(Utc::now());
+            if model.is_some() {
+                usage.model = model;
+            }
+        });
+    }
+
+    /// 最近 `limit` 个窗口的用量，按时间升序排列，没有活动的窗口以0填充
+    pub fn series(&self, window: StatsWindow, limit: Option<usize>) -> Vec<UsageBucket> {
+        let limit = limit
+            .unwrap_or_else(|| window.default_limit())
+            .clamp(1, window.max_limit());
+        let end = window.floor(Utc::now().timestamp());
+        let first = end - (limit as i64 - 1) * window.seconds();
+
+        let mut buckets: Vec<UsageBucket> = (0..limit as i64)
+            .map(|index| UsageBucket::empty(first + index * window.seconds()))
+            .collect();
+        self.with_state(|state| {
+            for (hour, bucket) in state.hours.range(first..) {
+                let index = ((window.floor(*hour) - first) / window.seconds()) as usize;
+                if let Some(target) = buckets.get_mut(index) {
+                    target.add(bucket);
+                }
+            }
+        });
+        buckets
+    }
+
+    /// 合并Agent管理器的统计与账本中的用量
+    pub fn summary(&self, agent_stats: &[AgentStats], topics: Vec<TopicStats>) -> UsageSummary {
+        let (totals, mut agents) =
+            self.with_state(|state| (state.totals.clone(), state.agents.clone()));
+
+        for stats in agent_stats {
+            let usage = agents
+                .entry(stats.agent_id.clone())
+                .or_insert_with(|| AgentUsage {
+                    agent_id: stats.agent_id.clone(),
+                    ..Default::default()
+                });
+            usage.provider = Some(stats.provider.clone());
+            usage.model = Some(stats.model.clone());
+            usage.total_messages = stats.total_messages;
+            usage.positive_feedback = stats.positive_feedback;
+            usage.negative_feedback = stats.negative_feedback;
+            usage.last_activity = usage.last_activity.max(Some(stats.last_activity));
+        }
+        let mut agents: Vec<AgentUsage> = agents.into_values().collect();
+        agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+
+        UsageSummary {
+            started_at: totals.start,
+            generated_at: Utc::now(),
+            totals,
+            agents,
+            topics,
+        }
+    }
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        Self::new(HashMap::new())
+    }
+}
+
+/// 按字符数估算令牌数
+fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+/// 转义CSV字段
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 将时间窗口用量导出为CSV
+pub fn series_to_csv(buckets: &[UsageBucket]) -> String {
+    let mut csv = String::from(
+        "start,messages_in,messages_out,bytes_in,bytes_out,agent_requests,agent_errors,prompt_tokens,completion_tokens,cost\n",
+    );
+    for bucket in buckets {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{},{:.6}",
+            bucket.start.to_rfc3339(),
+            bucket.messages_in,
+            bucket.messages_out,
+            bucket.bytes_in,
+            bucket.bytes_out,
+            bucket.agent_requests,
+            bucket.agent_errors,
+            bucket.prompt_tokens,
+            bucket.completion_tokens,
+            bucket.cost
+        );
+    }
+    csv
+}
+
+/// 将各Agent用量导出为CSV
+pub fn agents_to_csv(agents: &[AgentUsage]) -> String {
+    let mut csv = String::from(
+        "agent_id,provider,model,total_messages,requests,errors,prompt_tokens,completion_tokens,cost,positive_feedback,negative_feedback,last_activity\n",
+    );
+    for agent in agents {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{},{:.6},{},{},{}",
+            csv_field(&agent.agent_id),
+            csv_field(agent.provider.as_deref().unwrap_or_default()),
+            csv_field(agent.model.as_deref().unwrap_or_default()),
+            agent.total_messages,
+            agent.requests,
+            agent.errors,
+            agent.prompt_tokens,
+            agent.completion_tokens,
+            agent.cost,
+            agent.positive_feedback,
+            agent.negative_feedback,
+            agent
+                .last_activity
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default()
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use rig_agent::core::TokenUsage;
+
+    use super::*;
+
+    #[test]
+    fn test_usage_rollup_and_export() {
+        let prices = HashMap::from([(
+            "gpt-4o-mini".to_string(),
+            ModelPrice {
+                prompt: 1.0,
+                completion: 2.0,
+            },
+        )]);
+        let ledger = UsageLedger::new(prices);
+        ledger.record_in(100);
+        ledger.record_out(40);
+
+        let response = AgentResponse {
+            id: "r1".to_string(),
+            agent_id: "a,b".to_string(),
+            content: "你好".to_string(),
+            timestamp: Utc::now(),
+            model: "gpt-4o-mini".to_string(),
+            usage: Some(TokenUsage {
+                prompt_tokens: 1000,
+                completion_tokens: 500,
+                total_tokens: 1500,
+            }),
+            tool_calls: None,
+            citations: Vec::new(),
+            compression: None,
+            creativity: None,
+            fingerprint: None,
+            finish_reason: None,
+            warnings: Vec::new(),
+            variant: None,
+        };
+        ledger.record_agent("a,b", "hi", Some(&response));
+        ledger.record_agent("a,b", "hi", None);
+
+        let hours = ledger.series(StatsWindow::Hour, Some(3));
+        assert_eq!(hours.len(), 3);
+        let current = hours.last().unwrap();
+        assert_eq!((current.messages_in, current.bytes_out), (1, 40));
+        assert_eq!((current.agent_requests, current.agent_errors), (2, 1));
+        assert!((current.cost - 0.002).abs() < 1e-9);
+
+        let days = ledger.series(StatsWindow::Day, Some(1000));
+        assert_eq!(days.len(), StatsWindow::Day.max_limit());
+        assert_eq!(days.last().unwrap().prompt_tokens, 1000);
+
+        let summary = ledger.summary(&[], Vec::new());
+        assert_eq!(summary.totals.completion_tokens, 500);
+        assert_eq!(summary.agents[0].requests, 2);
+        assert!(agents_to_csv(&summary.agents).contains("\n\"a,b\",,gpt-4o-mini,0,2,1,"));
+        assert_eq!(series_to_csv(&hours).lines().count(), 4);
+    }
+}
@@ -0,0 +1,394 @@
+//! 入站消息校验
+//!
+//! 来自网络的消息在交给聊天或Agent处理器之前，按消息类型检查大小与字段格式，
+//! 拒绝超长或畸形的消息
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::MAX_REACTION_BYTES,
+    content::{is_valid_language, MessageContent, MAX_CODE_BYTES},
+    coordination::AgentTarget,
+    error::{NodeError, NodeResult},
+    folder_sync::{is_syncable_name, MAX_FOLDER_CHUNK_BYTES, MAX_FOLDER_ENTRIES},
+    templates::{MAX_TEMPLATE_BYTES, MAX_TEMPLATE_DESCRIPTION},
+    MessageType,
+};
+
+/// 消息大小限制（字节）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageLimits {
+    /// 编码后整条签名消息的最大字节数，超过时不做签名校验直接丢弃
+    pub max_message_bytes: usize,
+    /// 聊天消息文本
+    pub max_chat_text: usize,
+    /// Agent请求的提示词
+    pub max_prompt: usize,
+    /// Agent响应内容
+    pub max_agent_response: usize,
+    /// 错误消息与系统消息
+    pub max_notice: usize,
+    /// 消息ID与Agent ID
+    pub max_id: usize,
+    /// 节点名称
+    pub max_name: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: 512 * 1024,
+            max_chat_text: 16 * 1024,
+            max_prompt: 32 * 1024,
+            max_agent_response: 256 * 1024,
+            max_notice: 4 * 1024,
+            max_id: 64,
+            max_name: 64,
+        }
+    }
+}
+
+impl MessageLimits {
+    /// 校验消息内容
+    pub fn validate(&self, message: &MessageType) -> NodeResult<()> {
+        match message {
+            MessageType::Chat { id, text } => {
+                check_id("消息ID", id, self.max_id)?;
+                check_text("聊天消息", text, self.max_chat_text)
+            }
+            MessageType::Ack { message_id, .. } => check_id("消息ID", message_id, self.max_id),
+            MessageType::NodeInfo { name, .. } => match name {
+                Some(name) => {
+                    check_len("节点名称", name, self.max_name)?;
+                    if name.chars().any(char::is_control) {
+                        return Err(invalid("节点名称包含控制字符"));
+                    }
+                    Ok(())
+                }
+                None => Ok(()),
+            },
+            MessageType::AgentRequest { prompt, agent_id }
+            | MessageType::AgentFanOutRequest { prompt, agent_id } => {
+                self.check_agent_request(prompt, agent_id)
+            }
+            MessageType::AgentQuery {
+                request_id,
+                prompt,
+                agent_id,
+                target,
+            } => {
+                check_id("请求ID", request_id, self.max_id)?;
+                if let AgentTarget::Node(node_id) = target {
+                    check_id("目标节点ID", node_id, self.max_id)?;
+                }
+                self.check_agent_request(prompt, agent_id)
+            }
+            MessageType::AgentResponse { content, agent_id } => {
+                check_id("Agent ID", agent_id, self.max_id)?;
+                check_text("Agent响应", content, self.max_agent_response)
+            }
+            MessageType::Error { message } => check_text("错误消息", message, self.max_notice),
+            MessageType::System { content } => check_text("系统消息", content, self.max_notice),
+            MessageType::VerifyConfirm { peer_id, digest } => {
+                check_id("节点ID", peer_id, self.max_id)?;
+                check_id("安全码摘要", digest, self.max_id)
+            }
+            MessageType::AgentClaim { request_id } => check_id("请求ID", request_id, self.max_id),
+            MessageType::MemoryUpdate { entries } => entries
+                .iter()
+                .try_for_each(|entry| entry.validate(self.max_id)),
+            MessageType::MemorySyncRequest { known } => known
+                .iter()
+                .try_for_each(|version| check_id("记忆ID", &version.id, self.max_id)),
+            MessageType::TemplateAnnounce { templates } => {
+                templates.iter().try_for_each(|template| {
+                    check_id("模板哈希", &template.hash, self.max_id)?;
+                    check_text("模板名称", &template.name, self.max_name)?;
+                    match &template.description {
+                        Some(description) => {
+                            check_len("模板描述", description, MAX_TEMPLATE_DESCRIPTION)
+                        }
+                        None => Ok(()),
+                    }
+                })
+            }
+            MessageType::TemplateFetch { hash } => check_id("模板哈希", hash, self.max_id),
+            MessageType::TemplateContent { hash, data } => {
+                check_id("模板哈希", hash, self.max_id)?;
+                if data.len() > MAX_TEMPLATE_BYTES {
+                    return Err(invalid(format!(
+                        "模板内容过长: {} 字节，上限 {} 字节",
+                        data.len(),
+                        MAX_TEMPLATE_BYTES
+                    )));
+                }
+                Ok(())
+            }
+            MessageType::AgentReply {
+                request_id,
+                agent_id,
+                content,
+                error,
+            } => {
+                check_id("请求ID", request_id, self.max_id)?;
+                check_id("Agent ID", agent_id, self.max_id)?;
+                if let Some(content) = content {
+                    check_text("Agent响应", content, self.max_agent_response)?;
+                }
+                match error {
+                    Some(error) => check_text("错误消息", error, self.max_notice),
+                    None => Ok(()),
+                }
+            }
+            MessageType::Presence { .. } | MessageType::LoadReport { .. } => Ok(()),
+            MessageType::AgentStatus {
+                request_id,
+                agent_id,
+                ..
+            } => {
+                check_id("请求ID", request_id, self.max_id)?;
+                check_id("Agent ID", agent_id, self.max_id)
+            }
+            MessageType::FolderUpdate { folder, entries } => {
+                check_id("同步文件夹名称", folder, self.max_id)?;
+                if entries.len() > MAX_FOLDER_ENTRIES {
+                    return Err(invalid(format!(
+                        "同步文件变更过多: {} 条，上限 {} 条",
+                        entries.len(),
+                        MAX_FOLDER_ENTRIES
+                    )));
+                }
+                entries.iter().try_for_each(|entry| {
+                    if !is_syncable_name(&entry.name) {
+                        return Err(invalid("同步文件名不合法"));
+                    }
+                    for hash in entry.hash.iter().chain(entry.base.iter()) {
+                        check_id("内容哈希", hash, self.max_id)?;
+                    }
+                    Ok(())
+                })
+            }
+            MessageType::FolderSyncRequest { folder } => {
+                check_id("同步文件夹名称", folder, self.max_id)
+            }
+            MessageType::FolderChunkRequest { folder, hash, .. } => {
+                check_id("同步文件夹名称", folder, self.max_id)?;
+                check_id("内容哈希", hash, self.max_id)
+            }
+            MessageType::FolderChunk {
+                folder, hash, data, ..
+            } => {
+                check_id("同步文件夹名称", folder, self.max_id)?;
+                check_id("内容哈希", hash, self.max_id)?;
+                if data.len() > MAX_FOLDER_CHUNK_BYTES {
+                    return Err(invalid(format!(
+                        "文件分块过长: {} 字节，上限 {} 字节",
+                        data.len(),
+                        MAX_FOLDER_CHUNK_BYTES
+                    )));
+                }
+                Ok(())
+            }
+            MessageType::ChatReply { id, text, reply_to } => {
+                check_id("消息ID", id, self.max_id)?;
+                check_id("被回复的消息ID", reply_to, self.max_id)?;
+                check_text("聊天消息", text, self.max_chat_text)
+            }
+            MessageType::Reaction {
+                message_id, emoji, ..
+            } => {
+                check_id("消息ID", message_id, self.max_id)?;
+                check_emoji(emoji)
+            }
+            MessageType::ChatContent {
+                id,
+                content,
+                reply_to,
+            } => {
+                check_id("消息ID", id, self.max_id)?;
+                if let Some(reply_to) = reply_to {
+                    check_id("被回复的消息ID", reply_to, self.max_id)?;
+                }
+                self.check_content(content)
+            }
+        }
+    }
+
+    /// 按内容类型校验长度与格式
+    fn check_content(&self, content: &MessageContent) -> NodeResult<()> {
+        match content {
+            MessageContent::Text { text } | MessageContent::Markdown { text } => {
+                check_text("聊天消息", text, self.max_chat_text)
+            }
+            MessageContent::Code { language, code } => {
+                if let Some(language) = language {
+                    if !is_valid_language(language) {
+                        return Err(invalid(format!("代码语言标注无效: {}", language)));
+                    }
+                }
+                check_text("代码块", code, MAX_CODE_BYTES)
+            }
+            MessageContent::Notice { text } => check_text("系统提示", text, self.max_notice),
+        }
+    }
+
+    /// 校验Agent请求的提示词与Agent ID
+    fn check_agent_request(&self, prompt: &str, agent_id: &str) -> NodeResult<()> {
+        check_id("Agent ID", agent_id, self.max_id)?;
+        if prompt.trim().is_empty() {
+            return Err(invalid("提示词为空"));
+        }
+        check_text("提示词", prompt, self.max_prompt)
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> NodeError {
+    NodeError::InvalidMessage(reason.into())
+}
+
+fn check_len(field: &str, value: &str, max: usize) -> NodeResult<()> {
+    if value.len() > max {
+        return Err(invalid(format!(
+            "{}过长: {} 字节，上限 {} 字节",
+            field,
+            value.len(),
+            max
+        )));
+    }
+    Ok(())
+}
+
+/// ID只允许ASCII字母、数字、`-`、`_`、`.`
+fn check_id(field: &str, value: &str, max: usize) -> NodeResult<()> {
+    if value.is_empty() {
+        return Err(invalid(format!("{}为空", field)));
+    }
+    check_len(field, value, max)?;
+    if !value
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+    {
+        return Err(invalid(format!("{}包含非法字符", field)));
+    }
+    Ok(())
+}
+
+/// 表情回应不能为空，不能包含空白与控制字符
+pub(crate) fn check_emoji(emoji: &str) -> NodeResult<()> {
+    if emoji.is_empty() {
+        return Err(invalid("表情为空"));
+    }
+    check_len("表情", emoji, MAX_REACTION_BYTES)?;
+    if emoji.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return Err(invalid("表情包含空白或控制字符"));
+    }
+    Ok(())
+}
+
+/// 文本允许换行与制表符，拒绝其他控制字符
+fn check_text(field: &str, value: &str, max: usize) -> NodeResult<()> {
+    check_len(field, value, max)?;
+    if value
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    {
+        return Err(invalid(format!("{}包含控制字符", field)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_messages() {
+        let limits = MessageLimits {
+            max_chat_text: 8,
+            ..MessageLimits::default()
+        };
+
+        assert!(limits
+            .validate(&MessageType::Chat {
+                id: "abc123".to_string(),
+                text: "你好".to_string(),
+            })
+            .is_ok());
+        assert!(limits
+            .validate(&MessageType::Chat {
+                id: "abc123".to_string(),
+                text: "这条消息太长了".to_string(),
+            })
+            .is_err());
+        assert!(limits
+            .validate(&MessageType::Chat {
+                id: "../etc".to_string(),
+                text: "hi".to_string(),
+            })
+            .is_err());
+        assert!(limits
+            .validate(&MessageType::AgentRequest {
+                prompt: "  ".to_string(),
+                agent_id: "default".to_string(),
+            })
+            .is_err());
+        assert!(matches!(
+            limits.validate(&MessageType::System {
+                content: "a\u{0}b".to_string(),
+            }),
+            Err(NodeError::InvalidMessage(_))
+        ));
+        assert!(limits
+            .validate(&MessageType::ChatReply {
+                id: "abc124".to_string(),
+                text: "好".to_string(),
+                reply_to: "../etc".to_string(),
+            })
+            .is_err());
+        assert!(limits
+            .validate(&MessageType::Reaction {
+                message_id: "abc123".to_string(),
+                emoji: "👍🏽".to_string(),
+                removed: false,
+            })
+            .is_ok());
+        assert!(limits
+            .validate(&MessageType::Reaction {
+                message_id: "abc123".to_string(),
+                emoji: "a b".to_string(),
+                removed: true,
+            })
+            .is_err());
+        // 代码块不受聊天消息长度限制，但语言标注须合法
+        assert!(limits
+            .validate(&MessageType::ChatContent {
+                id: "abc125".to_string(),
+                content: MessageContent::Code {
+                    language: Some("rust".to_string()),
+                    code: "fn main() {}".to_string(),
+                },
+                reply_to: None,
+            })
+            .is_ok());
+        assert!(limits
+            .validate(&MessageType::ChatContent {
+                id: "abc125".to_string(),
+                content: MessageContent::Code {
+                    language: Some("<b>".to_string()),
+                    code: "x".to_string(),
+                },
+                reply_to: None,
+            })
+            .is_err());
+        assert!(limits
+            .validate(&MessageType::ChatContent {
+                id: "abc125".to_string(),
+                content: MessageContent::Markdown {
+                    text: "**这段文本太长了**".to_string(),
+                },
+                reply_to: None,
+            })
+            .is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! 身份验证
+//!
+//! 由双方公钥派生安全码（60位数字），用户可以当面比对数字或扫描对方的二维码确认没有中间人。
+//! 确认后在地址簿中标记为已验证，并通过 `VerifyConfirm` 消息告知对方，
+//! 对方收到后由 [`handle_confirm`] 比对安全码
+
+use std::str::FromStr;
+
+use iroh_gossip::proto::TopicId;
+use iroh::PublicKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::{
+    address_book::AddressBook,
+    error::{NodeError, NodeResult},
+    events::{EventBus, NodeEvent},
+    invite::render_qr_png,
+    INVITE_SCHEME,
+};
+
+/// 派生指纹时使用的域分隔前缀
+const FINGERPRINT_DOMAIN: &[u8] = b"iroh-agent safety number v1";
+
+/// 每个公钥贡献的数字组数，每组5位
+const GROUPS_PER_KEY: usize = 6;
+
+/// 双方公钥派生的安全码
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetyNumber {
+    /// 本节点ID
+    pub node_id: String,
+    /// 对方节点ID
+    pub peer_id: String,
+    /// 12组5位数字，以空格分隔
+    pub number: String,
+}
+
+impl SafetyNumber {
+    /// 计算两个节点之间的安全码，交换参数顺序结果相同
+    pub fn new(me: &PublicKey, peer: &PublicKey) -> Self {
+        let mut parts = [fingerprint(me), fingerprint(peer)];
+        parts.sort();
+        Self {
+            node_id: me.to_string(),
+            peer_id: peer.to_string(),
+            number: parts.concat().join(" "),
+        }
+    }
+
+    /// 供对方扫描的二维码内容：`iroh-agent://verify/<本节点ID>?sn=<安全码数字>`
+    pub fn qr_payload(&self) -> String {
+        format!(
+            "{}://verify/{}?sn={}",
+            INVITE_SCHEME,
+            self.node_id,
+            self.number.replace(' ', "")
+        )
+    }
+
+    /// 生成PNG二维码
+    pub fn qr_png(&self) -> NodeResult<Vec<u8>> {
+        render_qr_png(&self.qr_payload())
+    }
+
+    /// 检查扫描到的二维码是否与本地计算的安全码一致
+    pub fn matches_scanned(&self, payload: &str) -> NodeResult<bool> {
+        let scanned = ScannedCode::from_str(payload)?;
+        if scanned.node_id != self.peer_id {
+            return Err(NodeError::VerifyError(
+                "二维码不属于正在验证的节点".to_string(),
+            ));
+        }
+        Ok(scanned.digits == self.number.replace(' ', ""))
+    }
+
+    /// 安全码摘要，在确认消息中发送，避免明文传输完整安全码
+    pub fn digest(&self) -> String {
+        let digest = Sha256::digest(self.number.as_bytes());
+        data_encoding::HEXLOWER.encode(&digest[..8])
+    }
+}
+
+/// 扫描到的验证二维码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedCode {
+    /// 对方节点ID
+    pub node_id: String,
+    /// 不含空格的安全码数字
+    pub digits: String,
+}
+
+impl FromStr for ScannedCode {
+    type Err = NodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NodeError::DecodeError(format!("无效的验证二维码: {}", s));
+        let rest = s
+            .trim()
+            .strip_prefix(INVITE_SCHEME)
+            .and_then(|rest| rest.strip_prefix("://verify/"))
+            .ok_or_else(invalid)?;
+        let (node_id, digits) = rest.split_once("?sn=").ok_or_else(invalid)?;
+        node_id.parse::<PublicKey>().map_err(|_| invalid())?;
+        if digits.len() != GROUPS_PER_KEY * 2 * 5 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            node_id: node_id.to_string(),
+            digits: digits.to_string(),
+        })
+    }
+}
+
+/// 单个公钥的指纹：6组5位数字
+fn fingerprint(key: &PublicKey) -> Vec<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(FINGERPRINT_DOMAIN);
+    hasher.update(key.as_bytes());
+    let digest = hasher.finalize();
+
+    digest
+        .chunks(5)
+        .take(GROUPS_PER_KEY)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+            format!("{:05}", value % 100_000)
+        })
+        .collect()
+}
+
+/// 处理对方发来的 `VerifyConfirm`：只处理验证本节点的确认，
+/// 比对双方的安全码后记录到地址簿并发布事件，不一致时可能存在中间人
+pub(crate) async fn handle_confirm(
+    me: &PublicKey,
+    from: &PublicKey,
+    topic_id: &TopicId,
+    peer_id: &str,
+    digest: &str,
+    address_book: &RwLock<AddressBook>,
+    events: &EventBus,
+) {
+    if peer_id != me.to_string() {
+        return;
+    }
+    let matched = SafetyNumber::new(me, from).digest() == digest;
+    if !matched {
+        warn!(
+            "节点 {} 的安全码与本地不一致，可能存在中间人",
+            from.fmt_short()
+        );
+    }
+    address_book
+        .write()
+        .await
+        .record_peer_confirmation(&from.to_string(), matched);
+    events.publish(NodeEvent::VerificationConfirmed {
+        topic_id: topic_id.to_string(),
+        from: from.to_string(),
+        matched,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use iroh::SecretKey;
+
+    use super::*;
+
+    #[test]
+    fn test_safety_number_is_symmetric() {
+        let alice = SecretKey::from_bytes(&[1; 32]).public();
+        let bob = SecretKey::from_bytes(&[2; 32]).public();
+        let mallory = SecretKey::from_bytes(&[3; 32]).public();
+
+        let from_alice = SafetyNumber::new(&alice, &bob);
+        let from_bob = SafetyNumber::new(&bob, &alice);
+        assert_eq!(from_alice.number, from_bob.number);
+        assert_eq!(from_alice.number.split(' ').count(), 12);
+        assert_eq!(from_alice.digest(), from_bob.digest());
+        assert_ne!(
+            from_alice.number,
+            SafetyNumber::new(&alice, &mallory).number
+        );
+
+        // Alice 扫描 Bob 的二维码
+        assert!(from_alice.matches_scanned(&from_bob.qr_payload()).unwrap());
+        let forged = SafetyNumber::new(&bob, &mallory).qr_payload();
+        assert!(!from_alice.matches_scanned(&forged).unwrap());
+        assert!(from_alice
+            .matches_scanned(&SafetyNumber::new(&mallory, &alice).qr_payload())
+            .is_err());
+    }
+}
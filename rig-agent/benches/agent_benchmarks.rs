@@ -1,44 +1,57 @@
 //! Agent 模块性能基准测试
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion};
 use rig_agent::{
-    core::{agent::AgentManager, types::AgentConfig},
-    tools::{BuiltinTools, ToolCall},
+    core::{
+        agent::{AgentManager, ClientRegistry},
+        types::{AgentConfig, ClientConfig, ToolCall},
+    },
+    tools::BuiltinTools,
 };
-use std::env;
+use std::{hint::black_box, time::Duration};
 use tokio::runtime::Runtime;
 
-/// 设置测试环境
-fn setup_bench_env() {
-    if env::var("OPENAI_API_KEY").is_err() {
-        env::set_var("OPENAI_API_KEY", "test-key-for-benchmarking");
-    }
+/// 模拟提供商
+#[path = "../tests/support/mock_provider.rs"]
+mod mock_provider;
+
+/// 设置测试环境：启动模拟提供商，返回指向它的客户端注册表
+fn setup_bench_env(rt: &Runtime) -> ClientRegistry {
+    let addr = rt.block_on(mock_provider::spawn(mock_provider::MockConfig {
+        latency: Duration::ZERO,
+        chunks: 1,
+        streaming: true,
+        echo: false,
+    }));
+    let mut registry = ClientRegistry::empty();
+    registry
+        .register_openai(
+            ClientConfig::new("openai", "gpt-3.5-turbo")
+                .with_api_key("test-key-for-benchmarking")
+                .with_base_url(format!("http://{}/v1", addr)),
+        )
+        .unwrap();
+    registry
 }
 
 /// 创建基准测试用的配置
 fn create_bench_config() -> AgentConfig {
-    AgentConfig {
-        model: "gpt-3.5-turbo".to_string(),
-        provider: Some("openai".to_string()),
-        system_prompt: Some("你是一个基准测试助手。".to_string()),
-        temperature: Some(0.1),
-        max_tokens: Some(50),
-        enable_tools: false,
-        history_limit: Some(20),
-        extra_params: std::collections::HashMap::new(),
-    }
+    AgentConfig::new("openai", "gpt-3.5-turbo")
+        .with_preamble("你是一个基准测试助手。")
+        .with_temperature(0.1)
+        .with_max_tokens(50)
+        .with_history_limit(20)
 }
 
 /// 基准测试：Agent 创建性能
 fn bench_agent_creation(c: &mut Criterion) {
-    setup_bench_env();
     let rt = Runtime::new().unwrap();
     
     c.bench_function("agent_creation", |b| {
         b.iter(|| {
             rt.block_on(async {
                 let config = create_bench_config();
-                let mut manager = AgentManager::new(config);
+                let manager = AgentManager::new(config);
                 
                 let agent_id = format!("bench_agent_{}", uuid::Uuid::new_v4());
                 let _ = manager.create_agent(black_box(agent_id), None).await;
@@ -70,20 +83,20 @@ fn bench_tool_execution(c: &mut Criterion) {
 
 /// 基准测试：对话历史管理性能
 fn bench_conversation_history(c: &mut Criterion) {
-    setup_bench_env();
     let rt = Runtime::new().unwrap();
+    let registry = setup_bench_env(&rt);
     
     c.bench_function("conversation_history_management", |b| {
         b.iter(|| {
             rt.block_on(async {
                 let config = create_bench_config();
-                let mut manager = AgentManager::new(config);
+                let manager = AgentManager::new(config);
                 let agent_id = "bench_history_agent";
                 
                 if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
                     // 添加多条消息到历史
                     for i in 0..10 {
-                        let _ = manager.chat(agent_id, &format!("测试消息 {}", i)).await;
+                        let _ = manager.chat(&registry, agent_id, &format!("测试消息 {}", i)).await;
                     }
                     
                     // 获取历史
@@ -99,7 +112,6 @@ fn bench_conversation_history(c: &mut Criterion) {
 
 /// 基准测试：并发 Agent 操作
 fn bench_concurrent_operations(c: &mut Criterion) {
-    setup_bench_env();
     let rt = Runtime::new().unwrap();
     
     c.bench_function("concurrent_agent_list", |b| {
@@ -108,8 +120,7 @@ fn bench_concurrent_operations(c: &mut Criterion) {
                 let config = create_bench_config();
                 let manager = AgentManager::new(config);
                 
-                // 并发获取 Agent 列表
-                let mut handles = vec![];
+                // 连续获取 Agent 列表
                 for _ in 0..10 {
                     let agents = manager.list_agents().await;
                     black_box(agents);
@@ -121,14 +132,13 @@ fn bench_concurrent_operations(c: &mut Criterion) {
 
 /// 基准测试：配置管理性能
 fn bench_config_management(c: &mut Criterion) {
-    setup_bench_env();
     let rt = Runtime::new().unwrap();
     
     c.bench_function("config_management", |b| {
         b.iter(|| {
             rt.block_on(async {
                 let config = create_bench_config();
-                let mut manager = AgentManager::new(config.clone());
+                let manager = AgentManager::new(config.clone());
                 let agent_id = "bench_config_agent";
                 
                 if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
@@ -147,8 +157,8 @@ fn bench_config_management(c: &mut Criterion) {
 
 /// 基准测试：内存使用情况
 fn bench_memory_usage(c: &mut Criterion) {
-    setup_bench_env();
     let rt = Runtime::new().unwrap();
+    let registry = setup_bench_env(&rt);
     
     c.bench_function("memory_usage_large_history", |b| {
         b.iter(|| {
@@ -156,18 +166,18 @@ fn bench_memory_usage(c: &mut Criterion) {
                 let mut config = create_bench_config();
                 config.history_limit = Some(1000); // 大历史限制
                 
-                let mut manager = AgentManager::new(config);
+                let manager = AgentManager::new(config);
                 let agent_id = "bench_memory_agent";
                 
                 if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
                     // 创建大量历史记录
                     for i in 0..100 {
                         let message = format!("这是一条很长的测试消息，用于测试内存使用情况。消息编号：{}。这条消息包含了足够的文本来模拟真实的对话场景。", i);
-                        let _ = manager.chat(agent_id, &message).await;
+                        let _ = manager.chat(&registry, agent_id, &message).await;
                     }
                     
                     let history = manager.get_conversation_history(black_box(agent_id)).await;
-                    black_box(history);
+                    let _ = black_box(history);
                 }
             });
         });
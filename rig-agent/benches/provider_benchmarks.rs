@@ -0,0 +1,220 @@
+//! 提供商调用链路基准测试
+//!
+//! 在本地启动一个兼容 OpenAI Chat Completions 协议的模拟提供商，测量：
+//! 端到端聊天延迟、流式首字延迟（TTFT）、工具调用循环开销以及并发聊天吞吐。
+//! 模拟提供商的响应是确定的，测得的时间主要来自 AgentManager、调度与锁等本地开销，
+//! 可用 criterion 基线在不同提交之间对比：
+//!
+//! ```bash
+//! cargo bench --bench provider_benchmarks -- --save-baseline main
+//! cargo bench --bench provider_benchmarks -- --baseline main
+//! ```
+//!
+//! 通过 `MOCK_PROVIDER_LATENCY_MS` 可为每次请求增加固定的模拟网络延迟（默认 0）。
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rig_agent::{AgentConfig, AgentManager, ChatOptions, ToolSelection, core::ClientRegistry};
+use std::{
+    hint::black_box,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Runtime;
+
+/// 模拟提供商
+#[path = "../tests/support/mock_provider.rs"]
+mod mock_provider;
+
+/// 基准测试共享环境
+struct BenchEnv {
+    runtime: Runtime,
+    registry: ClientRegistry,
+}
+
+impl BenchEnv {
+    /// 启动模拟提供商并让 OpenAI 客户端指向它
+    fn start() -> Self {
+        let runtime = Runtime::new().unwrap();
+        let latency = std::env::var("MOCK_PROVIDER_LATENCY_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or_default();
+        let addr = runtime.block_on(mock_provider::spawn(mock_provider::MockConfig {
+            latency,
+            chunks: 16,
+            streaming: true,
+            echo: false,
+        }));
+
+        // SAFETY: 在创建客户端注册表之前设置，此时还没有其他线程读取这些环境变量
+        unsafe {
+            std::env::set_var("OPENAI_API_KEY", "mock-key");
+            std::env::set_var("OPENAI_BASE_URL", format!("http://{}/v1", addr));
+        }
+
+        Self {
+            runtime,
+            registry: ClientRegistry::new(),
+        }
+    }
+}
+
+/// 基准测试用的 Agent 配置，限制历史长度使每次迭代的请求大小保持稳定
+fn bench_config() -> AgentConfig {
+    AgentConfig {
+        history_limit: Some(10),
+        ..AgentConfig::new("openai", "mock")
+    }
+}
+
+/// 基准测试：端到端聊天延迟
+fn bench_chat_latency(c: &mut Criterion, env: &BenchEnv) {
+    let manager = AgentManager::new(bench_config());
+    env.runtime
+        .block_on(manager.create_agent("latency".to_string(), None))
+        .unwrap();
+
+    c.bench_function("provider/chat_latency", |b| {
+        b.iter(|| {
+            env.runtime.block_on(async {
+                let response = manager
+                    .chat(&env.registry, "latency", black_box("你好"))
+                    .await
+                    .unwrap();
+                black_box(response);
+            })
+        });
+    });
+}
+
+/// 基准测试：流式首字延迟与完整流式响应时间
+fn bench_streaming(c: &mut Criterion, env: &BenchEnv) {
+    let manager = AgentManager::new(bench_config());
+    env.runtime
+        .block_on(manager.create_agent("streaming".to_string(), None))
+        .unwrap();
+
+    let mut group = c.benchmark_group("provider/streaming");
+    group.bench_function("time_to_first_token", |b| {
+        b.iter_custom(|iters| {
+            env.runtime.block_on(async {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    let mut first_token = None;
+                    manager
+                        .chat_stream(
+                            &env.registry,
+                            "streaming",
+                            "你好",
+                            ChatOptions::default(),
+                            |_| {
+                                first_token.get_or_insert_with(|| start.elapsed());
+                            },
+                        )
+                        .await
+                        .unwrap();
+                    total += first_token.unwrap_or_else(|| start.elapsed());
+                }
+                total
+            })
+        });
+    });
+    group.bench_function("full_response", |b| {
+        b.iter(|| {
+            env.runtime.block_on(async {
+                let response = manager
+                    .chat_stream(
+                        &env.registry,
+                        "streaming",
+                        "你好",
+                        ChatOptions::default(),
+                        |chunk| {
+                            black_box(chunk);
+                        },
+                    )
+                    .await
+                    .unwrap();
+                black_box(response);
+            })
+        });
+    });
+    group.finish();
+}
+
+/// 基准测试：工具调用循环开销（两次模型往返加一次工具执行），与 chat_latency 对比
+fn bench_tool_loop(c: &mut Criterion, env: &BenchEnv) {
+    let mut config = bench_config();
+    config.enable_tools = true;
+    config.tool_selection = ToolSelection::only(["math"]);
+    let manager = AgentManager::new(config);
+    env.runtime
+        .block_on(manager.create_agent("tools".to_string(), None))
+        .unwrap();
+
+    c.bench_function("provider/tool_loop", |b| {
+        b.iter(|| {
+            env.runtime.block_on(async {
+                let response = manager
+                    .chat(&env.registry, "tools", black_box("计算 123+456"))
+                    .await
+                    .unwrap();
+                black_box(response);
+            })
+        });
+    });
+}
+
+/// 基准测试：多个 Agent 并发聊天的吞吐，用于发现管理器锁与调度层的串行化
+fn bench_concurrent_chat(c: &mut Criterion, env: &BenchEnv) {
+    let mut group = c.benchmark_group("provider/concurrent_chat");
+
+    for agents in [1usize, 4, 16] {
+        let manager = Arc::new(AgentManager::new(bench_config()));
+        env.runtime.block_on(async {
+            for i in 0..agents {
+                manager
+                    .create_agent(format!("concurrent_{}", i), None)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        group.throughput(Throughput::Elements(agents as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(agents),
+            &agents,
+            |b, &agents| {
+                b.iter(|| {
+                    env.runtime.block_on(async {
+                        let chats = (0..agents).map(|i| {
+                            let manager = manager.clone();
+                            async move {
+                                manager
+                                    .chat(&env.registry, &format!("concurrent_{}", i), "你好")
+                                    .await
+                                    .unwrap()
+                            }
+                        });
+                        black_box(futures::future::join_all(chats).await);
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+/// 所有基准测试共享同一个模拟提供商
+fn provider_benchmarks(c: &mut Criterion) {
+    let env = BenchEnv::start();
+    bench_chat_latency(c, &env);
+    bench_streaming(c, &env);
+    bench_tool_loop(c, &env);
+    bench_concurrent_chat(c, &env);
+}
+
+criterion_group!(benches, provider_benchmarks);
+
+criterion_main!(benches);
@@ -4,15 +4,14 @@
 
 use rig_agent::{
     core::{
-        agent::AgentManager,
+        agent::{AgentManager, ClientRegistry},
         types::{AgentConfig, AgentRole},
     },
-    tools::{ToolManager, CustomTool},
+    tools::CustomTool,
     error::AgentResult,
 };
 use std::env;
 use tracing::{info, Level};
-use tracing_subscriber;
 
 /// 自定义工具示例：文本长度计算器
 struct TextLengthTool;
@@ -68,19 +67,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // 1. 创建 Agent 配置
-    let config = AgentConfig {
-        model: "gpt-3.5-turbo".to_string(),
-        provider: Some("openai".to_string()),
-        system_prompt: Some("你是一个友好的AI助手，擅长回答问题和使用工具。".to_string()),
-        temperature: Some(0.7),
-        max_tokens: Some(500),
-        enable_tools: true,
-        history_limit: Some(20),
-        extra_params: std::collections::HashMap::new(),
-    };
+    let config = AgentConfig::new("openai", "gpt-3.5-turbo")
+        .with_preamble("你是一个友好的AI助手，擅长回答问题和使用工具。")
+        .with_temperature(0.7)
+        .with_max_tokens(500)
+        .with_tools(true)
+        .with_history_limit(20);
 
     // 2. 创建 Agent 管理器
     let mut manager = AgentManager::new(config);
+    let registry = ClientRegistry::new();
 
     // 3. 添加自定义工具
     let text_length_tool = Box::new(TextLengthTool);
@@ -109,7 +105,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     for message in messages {
         println!("用户: {}", message);
         
-        match manager.chat(agent_id, message).await {
+        match manager.chat(&registry, agent_id, message).await {
             Ok(response) => {
                 println!("助手: {}\n", response.content);
                 
@@ -139,7 +135,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     for message in tool_messages {
         println!("用户: {}", message);
         
-        match manager.chat(agent_id, message).await {
+        match manager.chat(&registry, agent_id, message).await {
             Ok(response) => {
                 println!("助手: {}", response.content);
                 
@@ -246,26 +242,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // 创建多个不同配置的 Agent
     let agents_config = vec![
-        ("creative_agent", AgentConfig {
-            model: "gpt-3.5-turbo".to_string(),
-            provider: Some("openai".to_string()),
-            system_prompt: Some("你是一个富有创意的助手，善于创作和想象。".to_string()),
-            temperature: Some(0.9),
-            max_tokens: Some(300),
-            enable_tools: false,
-            history_limit: Some(10),
-            extra_params: std::collections::HashMap::new(),
-        }),
-        ("analytical_agent", AgentConfig {
-            model: "gpt-3.5-turbo".to_string(),
-            provider: Some("openai".to_string()),
-            system_prompt: Some("你是一个分析型助手，善于逻辑推理和数据分析。".to_string()),
-            temperature: Some(0.2),
-            max_tokens: Some(400),
-            enable_tools: true,
-            history_limit: Some(15),
-            extra_params: std::collections::HashMap::new(),
-        }),
+        ("creative_agent", AgentConfig::new("openai", "gpt-3.5-turbo")
+            .with_preamble("你是一个富有创意的助手，善于创作和想象。")
+            .with_temperature(0.9)
+            .with_max_tokens(300)
+            .with_history_limit(10)),
+        ("analytical_agent", AgentConfig::new("openai", "gpt-3.5-turbo")
+            .with_preamble("你是一个分析型助手，善于逻辑推理和数据分析。")
+            .with_temperature(0.2)
+            .with_max_tokens(400)
+            .with_tools(true)
+            .with_history_limit(15)),
     ];
 
     for (agent_name, agent_config) in agents_config {
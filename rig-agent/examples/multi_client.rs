@@ -3,7 +3,7 @@
 //! 这个示例展示了如何使用重构后的 rig-agent 库同时使用多个 AI 提供商
 //! 确保设置了相应的环境变量：OPENAI_API_KEY, ANTHROPIC_API_KEY, GEMINI_API_KEY
 
-use rig_agent::{AgentConfig, AgentManager, ClientConfig};
+use rig_agent::{core::ClientRegistry, AgentConfig, AgentManager, ClientConfig};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -15,27 +15,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_preamble("你是一个有用的AI助手。")
         .with_temperature(0.7);
 
-    // 创建 AgentManager
-    let mut manager = AgentManager::new(config);
+    // 创建 AgentManager 与客户端注册表
+    let manager = AgentManager::new(config);
+    let mut registry = ClientRegistry::empty();
 
     // 注册多个提供商
     if std::env::var("OPENAI_API_KEY").is_ok() {
         println!("注册 OpenAI 客户端");
-        manager.register_openai(ClientConfig::new("openai", "gpt-4o"))?;
+        registry.register_openai(ClientConfig::new("openai", "gpt-4o"))?;
     }
 
     if std::env::var("ANTHROPIC_API_KEY").is_ok() {
         println!("注册 Anthropic 客户端");
-        manager.register_anthropic(ClientConfig::new("anthropic", "claude-3-sonnet-20240229"))?;
+        registry.register_anthropic(ClientConfig::new("anthropic", "claude-3-sonnet-20240229"))?;
     }
 
     if std::env::var("GEMINI_API_KEY").is_ok() {
         println!("注册 Gemini 客户端");
-        manager.register_gemini(ClientConfig::new("gemini", "gemini-pro"))?;
+        registry.register_gemini(ClientConfig::new("gemini", "gemini-pro"))?;
     }
 
     // 获取已注册的客户端列表
-    let clients = manager.get_registered_clients();
+    let clients = registry.get_registered_clients();
     println!("已注册的客户端: {:?}", clients);
 
     if clients.is_empty() {
@@ -59,26 +60,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // 创建 Agent
         manager
-            .create_agent(agent_id.clone(), Some(AgentConfig::new(&provider, model)))
+            .create_agent(agent_id.clone(), Some(AgentConfig::new(provider.as_str(), model)))
             .await?;
 
         // 发送相同的提示到不同的 Agent
         println!("向 {} 发送提示: {}", agent_id, prompt);
-        let response = manager.chat(&agent_id, prompt).await?;
+        let response = manager.chat(&registry, &agent_id, prompt).await?;
         println!("{} 的响应: {}\n", agent_id, response.content);
     }
 
     // 使用临时 Agent 进行快速提问
-    if manager.get_client_registry().has_client("openai") {
+    if registry.has_client("openai") {
         println!("使用临时 OpenAI Agent 进行快速提问");
         let response = manager
-            .prompt_with("openai", "gpt-3.5-turbo", "用一句话描述人工智能的未来")
+            .prompt_with(&registry, "openai", "gpt-3.5-turbo", "用一句话描述人工智能的未来")
             .await?;
         println!("临时 Agent 响应: {}\n", response);
     }
 
     // 切换 Agent 的提供商
-    if manager.get_client_registry().has_client("openai") && manager.get_client_registry().has_client("anthropic") {
+    if registry.has_client("openai") && registry.has_client("anthropic") {
         let agent_id = "openai_agent";
         println!("将 {} 从 OpenAI 切换到 Anthropic", agent_id);
         
@@ -86,7 +87,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .switch_provider(agent_id, "anthropic", "claude-3-sonnet-20240229")
             .await?;
         
-        let response = manager.chat(agent_id, "你现在是哪个模型?").await?;
+        let response = manager.chat(&registry, agent_id, "你现在是哪个模型?").await?;
         println!("切换后的响应: {}\n", response.content);
     }
 
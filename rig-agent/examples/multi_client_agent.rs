@@ -3,8 +3,8 @@
 //! 这个示例展示了如何使用重新设计的 Agent 系统来支持多个 AI 提供商客户端。
 //! 参考了 rig-core 的 dyn_client.rs 示例模式。
 
-use rig_agent::core::agent::{AgentManager, ClientConfig};
-use rig_agent::core::types::AgentConfig;
+use rig_agent::core::agent::{AgentManager, ClientRegistry};
+use rig_agent::core::types::{AgentConfig, ClientConfig};
 use rig_agent::error::AgentResult;
 
 #[tokio::main]
@@ -22,26 +22,22 @@ async fn main() -> AgentResult<()> {
 
     // 创建默认配置
     let default_config = AgentConfig::default();
-    let mut manager = AgentManager::new(default_config);
+    let manager = AgentManager::new(default_config);
+    let mut registry = ClientRegistry::new();
 
     // 显示已注册的客户端
-    let registered_clients = manager.get_registered_clients();
+    let registered_clients = registry.get_registered_clients();
     println!("已注册的客户端: {:?}", registered_clients);
 
     // 示例 1: 使用 OpenAI 客户端
     if registered_clients.contains(&"openai".to_string()) {
         println!("\n--- 示例 1: OpenAI 客户端 ---");
         
-        let openai_config = AgentConfig {
-            model: "gpt-3.5-turbo".to_string(),
-            provider: Some("openai".to_string()),
-            preamble: Some("你是一个有用的AI助手，请用中文回答。".to_string()),
-            temperature: Some(0.7),
-            max_tokens: Some(500),
-            enable_tools: false,
-            history_limit: Some(10),
-            extra_params: std::collections::HashMap::new(),
-        };
+        let openai_config = AgentConfig::new("openai", "gpt-3.5-turbo")
+            .with_preamble("你是一个有用的AI助手，请用中文回答。")
+            .with_temperature(0.7)
+            .with_max_tokens(500)
+            .with_history_limit(10);
 
         // 创建 OpenAI Agent
         manager
@@ -50,7 +46,7 @@ async fn main() -> AgentResult<()> {
 
         // 发送消息
         let response = manager
-            .chat("openai_agent", "你好，请介绍一下你自己。")
+            .chat(&registry, "openai_agent", "你好，请介绍一下你自己。")
             .await?;
 
         println!("OpenAI 响应: {}", response.content);
@@ -60,16 +56,11 @@ async fn main() -> AgentResult<()> {
     if registered_clients.contains(&"anthropic".to_string()) {
         println!("\n--- 示例 2: Anthropic 客户端 ---");
         
-        let anthropic_config = AgentConfig {
-            model: "claude-3-sonnet-20240229".to_string(),
-            provider: Some("anthropic".to_string()),
-            preamble: Some("你是一个专业的AI助手，请用中文回答。".to_string()),
-            temperature: Some(0.5),
-            max_tokens: Some(800),
-            enable_tools: false,
-            history_limit: Some(15),
-            extra_params: std::collections::HashMap::new(),
-        };
+        let anthropic_config = AgentConfig::new("anthropic", "claude-3-sonnet-20240229")
+            .with_preamble("你是一个专业的AI助手，请用中文回答。")
+            .with_temperature(0.5)
+            .with_max_tokens(800)
+            .with_history_limit(15);
 
         // 创建 Anthropic Agent
         manager
@@ -78,7 +69,7 @@ async fn main() -> AgentResult<()> {
 
         // 发送消息
         let response = manager
-            .chat("anthropic_agent", "请解释一下什么是人工智能。")
+            .chat(&registry, "anthropic_agent", "请解释一下什么是人工智能。")
             .await?;
 
         println!("Anthropic 响应: {}", response.content);
@@ -88,32 +79,24 @@ async fn main() -> AgentResult<()> {
     println!("\n--- 示例 3: 动态注册客户端 ---");
     
     // 注册一个自定义客户端配置
-    let custom_config = ClientConfig {
-        provider: "custom_provider".to_string(),
-        default_model: "custom-model".to_string(),
-        api_key: Some("your-api-key".to_string()),
-        base_url: Some("https://api.custom-provider.com".to_string()),
-    };
-
-    manager.register_client("custom_provider".to_string(), custom_config)?;
+    let custom_config = ClientConfig::new("openai", "custom-model")
+        .with_api_key("your-api-key")
+        .with_base_url("https://api.custom-provider.com");
+
+    registry.register_client("custom_provider", custom_config)?;
     
-    let updated_clients = manager.get_registered_clients();
+    let updated_clients = registry.get_registered_clients();
     println!("更新后的客户端列表: {:?}", updated_clients);
 
     // 示例 4: 多轮对话
     if registered_clients.contains(&"openai".to_string()) {
         println!("\n--- 示例 4: 多轮对话 ---");
         
-        let chat_config = AgentConfig {
-            model: "gpt-3.5-turbo".to_string(),
-            provider: Some("openai".to_string()),
-            preamble: Some("你是一个友好的聊天机器人，请保持对话的连贯性。".to_string()),
-            temperature: Some(0.8),
-            max_tokens: Some(300),
-            enable_tools: false,
-            history_limit: Some(20),
-            extra_params: std::collections::HashMap::new(),
-        };
+        let chat_config = AgentConfig::new("openai", "gpt-3.5-turbo")
+            .with_preamble("你是一个友好的聊天机器人，请保持对话的连贯性。")
+            .with_temperature(0.8)
+            .with_max_tokens(300)
+            .with_history_limit(20);
 
         // 创建聊天 Agent
         manager
@@ -121,7 +104,7 @@ async fn main() -> AgentResult<()> {
             .await?;
 
         // 多轮对话
-        let messages = vec![
+        let messages = [
             "你好！",
             "今天天气怎么样？",
             "你能帮我写一首诗吗？",
@@ -131,7 +114,7 @@ async fn main() -> AgentResult<()> {
         for (i, message) in messages.iter().enumerate() {
             println!("用户 (第{}轮): {}", i + 1, message);
             
-            let response = manager.chat("chat_agent", message).await?;
+            let response = manager.chat(&registry, "chat_agent", message).await?;
             println!("AI (第{}轮): {}", i + 1, response.content);
             println!();
         }
@@ -150,7 +133,7 @@ async fn main() -> AgentResult<()> {
         println!("\n--- 示例 5: 简单 Prompt ---");
         
         let response = manager
-            .prompt("openai_agent", "请用一句话总结今天的天气。")
+            .prompt(&registry, "openai_agent", "请用一句话总结今天的天气。")
             .await?;
 
         println!("简单 Prompt 响应: {}", response);
@@ -46,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // 手动设置 provider
         let mut config = anthropic_config;
-        config.provider = Some("anthropic".to_string());
+        config.provider = "anthropic".to_string();
 
         let adapter = StandaloneAgentAdapter::new(config);
         
@@ -75,7 +75,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // 手动设置 provider
         let mut config = cohere_config;
-        config.provider = Some("cohere".to_string());
+        config.provider = "cohere".to_string();
 
         let adapter = StandaloneAgentAdapter::new(config);
         
@@ -104,7 +104,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         // 手动设置 provider
         let mut config = gemini_config;
-        config.provider = Some("gemini".to_string());
+        config.provider = "gemini".to_string();
 
         let adapter = StandaloneAgentAdapter::new(config);
         
@@ -143,7 +143,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // 手动设置 provider
             let mut config = config;
-            config.provider = Some(provider.to_string());
+            config.provider = provider.to_string();
 
             let adapter = StandaloneAgentAdapter::new(config);
             let agent_id = format!("{}_comparison", provider);
@@ -1,8 +1,9 @@
 //! 基于 rig-core 官方示例的实现
 
-use rig_core::{
+use rig::{
+    client::CompletionClient,
     completion::Prompt,
-    providers::openai::{Client, GPT_3_5_TURBO},
+    providers::openai::{Client, GPT_35_TURBO},
 };
 
 #[tokio::main]
@@ -13,8 +14,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 创建 OpenAI 客户端
     let openai_client = Client::new(&openai_api_key);
     
-    // 创建完成模型
-    let gpt3_5 = openai_client.completion_model(GPT_3_5_TURBO);
+    // 创建 Agent
+    let gpt3_5 = openai_client.agent(GPT_35_TURBO).build();
     
     // 发送提示并获取响应
     let response = gpt3_5
@@ -24,4 +25,4 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("AI 回复: {}", response);
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -16,16 +16,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // 创建默认配置
-    let config = AgentConfig {
-        model: "gpt-3.5-turbo".to_string(),
-        provider: Some("openai".to_string()),
-        preamble: Some("你是一个友好的AI助手，请用中文回答问题。".to_string()),
-        temperature: Some(0.7),
-        max_tokens: Some(1000),
-        enable_tools: false,
-        history_limit: Some(10),
-        extra_params: std::collections::HashMap::new(),
-    };
+    let mut config = AgentConfig::new("openai", "gpt-3.5-turbo");
+    config.preamble = Some("你是一个友好的AI助手，请用中文回答问题。".to_string());
+    config.temperature = Some(0.7);
+    config.max_tokens = Some(1000);
+    config.history_limit = Some(10);
 
     // 创建适配器
     let adapter = StandaloneAgentAdapter::new(config.clone());
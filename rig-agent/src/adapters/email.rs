@@ -0,0 +1,811 @@
+//! 邮件适配器
+//!
+//! 收取邮件（轮询 IMAP 收件箱，或由 webhook 推送解析好的 [`InboundEmail`]），
+//! 每个邮件线程对应一个 Agent 与会话，用配置的 Agent 生成回复并通过 SMTP 发出。
+//! 只处理白名单发件人的邮件；默认为草稿模式，回复需要人工确认后才会发送
+
+use crate::{
+    AgentManager,
+    core::{AgentConfig, ChatOptions, ClientRegistry, RequestContext, RequestOrigin},
+    error::{AgentError, AgentResult},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{RwLock, watch};
+use tracing::{info, warn};
+
+/// 默认轮询间隔（秒）
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 60;
+/// 默认交给 Agent 的正文最大字符数
+const DEFAULT_MAX_BODY_CHARS: usize = 16 * 1024;
+/// 默认每个线程最多自动回复的次数
+const DEFAULT_MAX_REPLIES_PER_THREAD: usize = 10;
+
+/// 收到的邮件，也是 webhook 推送的载荷格式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InboundEmail {
+    /// Message-ID
+    pub message_id: String,
+    /// In-Reply-To
+    #[serde(default)]
+    pub in_reply_to: Option<String>,
+    /// References，按从早到晚排列
+    #[serde(default)]
+    pub references: Vec<String>,
+    /// 发件人地址
+    pub from: String,
+    /// 主题
+    #[serde(default)]
+    pub subject: String,
+    /// 纯文本正文
+    #[serde(default)]
+    pub body: String,
+    /// 接收时间
+    #[serde(default = "Utc::now")]
+    pub received_at: DateTime<Utc>,
+    /// 是否为自动发送的邮件（`Auto-Submitted` 头不为 `no`），如退信、休假自动回复
+    #[serde(default)]
+    pub auto_submitted: bool,
+}
+
+impl InboundEmail {
+    /// 邮件所属线程的 ID：线程中第一封邮件的 Message-ID
+    pub fn thread_id(&self) -> String {
+        let root = self
+            .references
+            .first()
+            .or(self.in_reply_to.as_ref())
+            .unwrap_or(&self.message_id);
+        normalize_message_id(root)
+    }
+}
+
+/// 去掉 Message-ID 两侧的尖括号与空白并转为小写
+fn normalize_message_id(id: &str) -> String {
+    id.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_lowercase()
+}
+
+/// 为 Message-ID 加上尖括号
+fn bracket_message_id(id: &str) -> String {
+    format!(
+        "<{}>",
+        id.trim().trim_start_matches('<').trim_end_matches('>')
+    )
+}
+
+/// 要发出的邮件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutgoingEmail {
+    /// 发件人地址
+    pub from: String,
+    /// 收件人地址
+    pub to: String,
+    /// 主题
+    pub subject: String,
+    /// 纯文本正文
+    pub body: String,
+    /// In-Reply-To
+    pub in_reply_to: Option<String>,
+    /// References
+    pub references: Vec<String>,
+}
+
+/// 等待人工确认的回复草稿
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailDraft {
+    /// 草稿 ID
+    pub id: String,
+    /// 线程 ID
+    pub thread_id: String,
+    /// 生成回复的 Agent ID
+    pub agent_id: String,
+    /// 回复内容
+    pub email: OutgoingEmail,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+}
+
+/// 一封邮件的处理结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmailDisposition {
+    /// 未处理
+    Ignored {
+        /// 原因
+        reason: String,
+    },
+    /// 已生成草稿，等待确认
+    Drafted {
+        /// 草稿 ID
+        draft_id: String,
+    },
+    /// 已发送回复
+    Sent {
+        /// 线程 ID
+        thread_id: String,
+    },
+}
+
+impl EmailDisposition {
+    fn ignored<T: Into<String>>(reason: T) -> Self {
+        Self::Ignored {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// 邮件适配器配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// 回复使用的发件地址，也用于忽略自己发出的邮件
+    pub address: String,
+    /// 允许的发件人：完整地址或 `@域名`，不区分大小写。为空时不处理任何邮件
+    pub allowed_senders: Vec<String>,
+    /// 草稿模式：回复先保存为草稿，确认后才发送
+    pub draft_mode: bool,
+    /// 新线程的 Agent 配置，为空时使用管理器的默认配置
+    pub agent_config: Option<AgentConfig>,
+    /// 轮询收件箱的间隔（秒）
+    pub poll_interval_secs: u64,
+    /// 交给 Agent 的正文最大字符数，超出部分被截断
+    pub max_body_chars: usize,
+    /// 每个线程最多自动回复的次数，防止与其他自动程序互相回复
+    pub max_replies_per_thread: usize,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            allowed_senders: Vec::new(),
+            draft_mode: true,
+            agent_config: None,
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            max_body_chars: DEFAULT_MAX_BODY_CHARS,
+            max_replies_per_thread: DEFAULT_MAX_REPLIES_PER_THREAD,
+        }
+    }
+}
+
+impl EmailConfig {
+    /// 创建配置
+    pub fn new<S: Into<String>>(address: S) -> Self {
+        Self {
+            address: address.into(),
+            ..Default::default()
+        }
+    }
+
+    /// 添加允许的发件人（完整地址或 `@域名`）
+    pub fn allow_sender<S: Into<String>>(mut self, sender: S) -> Self {
+        self.allowed_senders.push(sender.into());
+        self
+    }
+
+    /// 设置是否为草稿模式
+    pub fn with_draft_mode(mut self, draft_mode: bool) -> Self {
+        self.draft_mode = draft_mode;
+        self
+    }
+
+    /// 设置新线程的 Agent 配置
+    pub fn with_agent_config(mut self, config: AgentConfig) -> Self {
+        self.agent_config = Some(config);
+        self
+    }
+
+    /// 设置轮询间隔（秒）
+    pub fn with_poll_interval_secs(mut self, poll_interval_secs: u64) -> Self {
+        self.poll_interval_secs = poll_interval_secs;
+        self
+    }
+
+    /// 设置每个线程最多自动回复的次数
+    pub fn with_max_replies_per_thread(mut self, max_replies_per_thread: usize) -> Self {
+        self.max_replies_per_thread = max_replies_per_thread;
+        self
+    }
+
+    /// 发件人是否在白名单中
+    pub fn is_allowed(&self, sender: &str) -> bool {
+        let sender = sender.trim().to_lowercase();
+        let domain = sender.rsplit_once('@').map(|(_, domain)| domain);
+        self.allowed_senders.iter().any(|allowed| {
+            let allowed = allowed.trim().to_lowercase();
+            match allowed.strip_prefix('@') {
+                Some(allowed_domain) => domain == Some(allowed_domain),
+                None => allowed == sender,
+            }
+        })
+    }
+}
+
+/// 邮件来源，如 IMAP 收件箱
+#[async_trait]
+pub trait MailSource: Send {
+    /// 取出尚未处理的邮件
+    async fn fetch(&mut self) -> AgentResult<Vec<InboundEmail>>;
+}
+
+/// 邮件发送方式，如 SMTP
+#[async_trait]
+pub trait MailSender: Send + Sync {
+    /// 发送邮件
+    async fn send(&self, email: &OutgoingEmail) -> AgentResult<()>;
+}
+
+/// 邮件线程对应的 Agent 与会话
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailThread {
+    /// 线程 ID
+    pub thread_id: String,
+    /// Agent ID
+    pub agent_id: String,
+    /// 会话 ID，未配置会话存储时为空
+    pub session_id: Option<String>,
+    /// 原始主题
+    pub subject: String,
+    /// 已回复的次数
+    pub replies: usize,
+    /// 最近一封邮件的时间
+    pub last_message_at: DateTime<Utc>,
+}
+
+/// 邮件 Agent：把邮件线程映射到 Agent，生成并发送（或保存为草稿）回复
+pub struct EmailAgent {
+    manager: Arc<RwLock<AgentManager>>,
+    registry: Arc<ClientRegistry>,
+    sender: Arc<dyn MailSender>,
+    config: EmailConfig,
+    threads: RwLock<HashMap<String, EmailThread>>,
+    drafts: Mutex<Vec<EmailDraft>>,
+}
+
+impl EmailAgent {
+    /// 创建邮件 Agent
+    pub fn new(
+        manager: Arc<RwLock<AgentManager>>,
+        registry: Arc<ClientRegistry>,
+        sender: Arc<dyn MailSender>,
+        config: EmailConfig,
+    ) -> Self {
+        Self {
+            manager,
+            registry,
+            sender,
+            config,
+            threads: RwLock::new(HashMap::new()),
+            drafts: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 配置
+    pub fn config(&self) -> &EmailConfig {
+        &self.config
+    }
+
+    /// 已知的邮件线程
+    pub async fn threads(&self) -> Vec<EmailThread> {
+        let mut threads: Vec<EmailThread> = self.threads.read().await.values().cloned().collect();
+        threads.sort_by_key(|thread| thread.last_message_at);
+        threads
+    }
+
+    /// 处理一封收到的邮件
+    pub async fn handle_inbound(&self, email: InboundEmail) -> AgentResult<EmailDisposition> {
+        if let Some(reason) = self.screen(&email) {
+            info!("忽略来自 {} 的邮件: {}", email.from, reason);
+            return Ok(EmailDisposition::ignored(reason));
+        }
+
+        let thread_id = email.thread_id();
+        let thread = self.thread_for(&thread_id, &email).await?;
+        if thread.replies >= self.config.max_replies_per_thread {
+            return Ok(EmailDisposition::ignored(format!(
+                "线程已自动回复 {} 次",
+                thread.replies
+            )));
+        }
+
+        let prompt = self.prompt_for(&email);
+        let context = RequestContext::new(RequestOrigin::Email).with_caller_id(email.from.clone());
+        let response = {
+            let manager = self.manager.read().await;
+            manager
+                .chat_with_options(
+                    &self.registry,
+                    &thread.agent_id,
+                    &prompt,
+                    ChatOptions::default().with_context(context),
+                )
+                .await?
+        };
+
+        let reply = self.reply_to(&email, response.content);
+        if let Some(thread) = self.threads.write().await.get_mut(&thread_id) {
+            thread.replies += 1;
+            thread.last_message_at = email.received_at;
+        }
+
+        if self.config.draft_mode {
+            let draft = EmailDraft {
+                id: uuid::Uuid::new_v4().to_string(),
+                thread_id,
+                agent_id: thread.agent_id,
+                email: reply,
+                created_at: Utc::now(),
+            };
+            let draft_id = draft.id.clone();
+            self.drafts.lock().unwrap().push(draft);
+            info!("已为来自 {} 的邮件生成回复草稿: {}", email.from, draft_id);
+            return Ok(EmailDisposition::Drafted { draft_id });
+        }
+
+        self.sender.send(&reply).await?;
+        info!("已回复 {} 的邮件", email.from);
+        Ok(EmailDisposition::Sent { thread_id })
+    }
+
+    /// 待确认的草稿
+    pub fn drafts(&self) -> Vec<EmailDraft> {
+        self.drafts.lock().unwrap().clone()
+    }
+
+    /// 确认并发送草稿
+    pub async fn approve_draft(&self, draft_id: &str) -> AgentResult<OutgoingEmail> {
+        let draft = self.take_draft(draft_id)?;
+        if let Err(e) = self.sender.send(&draft.email).await {
+            // 发送失败时放回草稿，便于重试
+            self.drafts.lock().unwrap().push(draft);
+            return Err(e);
+        }
+        Ok(draft.email)
+    }
+
+    /// 丢弃草稿
+    pub fn discard_draft(&self, draft_id: &str) -> AgentResult<EmailDraft> {
+        self.take_draft(draft_id)
+    }
+
+    fn take_draft(&self, draft_id: &str) -> AgentResult<EmailDraft> {
+        let mut drafts = self.drafts.lock().unwrap();
+        let index = drafts
+            .iter()
+            .position(|draft| draft.id == draft_id)
+            .ok_or_else(|| AgentError::other(format!("草稿不存在: {}", draft_id)))?;
+        Ok(drafts.remove(index))
+    }
+
+    /// 从邮件来源取一次邮件并逐封处理，返回各封的处理结果
+    pub async fn poll_once(
+        &self,
+        source: &mut dyn MailSource,
+    ) -> AgentResult<Vec<EmailDisposition>> {
+        let mut dispositions = Vec::new();
+        for email in source.fetch().await? {
+            let from = email.from.clone();
+            match self.handle_inbound(email).await {
+                Ok(disposition) => dispositions.push(disposition),
+                Err(e) => {
+                    warn!("处理来自 {} 的邮件失败: {}", from, e);
+                    dispositions.push(EmailDisposition::ignored(e.to_string()));
+                }
+            }
+        }
+        Ok(dispositions)
+    }
+
+    /// 按配置的间隔轮询邮件来源，直到 `shutdown` 变为 true
+    pub async fn run(&self, mut source: Box<dyn MailSource>, mut shutdown: watch::Receiver<bool>) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.poll_interval_secs.max(1)));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.changed() => break,
+            }
+            if *shutdown.borrow() {
+                break;
+            }
+            if let Err(e) = self.poll_once(source.as_mut()).await {
+                warn!("收取邮件失败: {}", e);
+            }
+        }
+    }
+
+    /// 检查邮件是否应当处理，不处理时返回原因
+    fn screen(&self, email: &InboundEmail) -> Option<String> {
+        if email.auto_submitted {
+            return Some("自动发送的邮件".to_string());
+        }
+        if email
+            .from
+            .trim()
+            .eq_ignore_ascii_case(self.config.address.trim())
+        {
+            return Some("本地址发出的邮件".to_string());
+        }
+        if !self.config.is_allowed(&email.from) {
+            return Some("发件人不在白名单中".to_string());
+        }
+        if email.body.trim().is_empty() {
+            return Some("正文为空".to_string());
+        }
+        None
+    }
+
+    /// 获取线程，新线程时创建对应的 Agent 与会话
+    async fn thread_for(&self, thread_id: &str, email: &InboundEmail) -> AgentResult<EmailThread> {
+        if let Some(thread) = self.threads.read().await.get(thread_id) {
+            return Ok(thread.clone());
+        }
+
+        let agent_id = format!("email-{}", uuid::Uuid::new_v4().simple());
+        let session_id = {
+            let manager = self.manager.read().await;
+            manager
+                .create_agent(agent_id.clone(), self.config.agent_config.clone())
+                .await?;
+            if manager.session_store().is_ok() {
+                Some(manager.start_session(&agent_id, &email.subject).await?.id)
+            } else {
+                None
+            }
+        };
+
+        let thread = EmailThread {
+            thread_id: thread_id.to_string(),
+            agent_id,
+            session_id,
+            subject: email.subject.clone(),
+            replies: 0,
+            last_message_at: email.received_at,
+        };
+        info!("新邮件线程 {} 对应 Agent {}", thread_id, thread.agent_id);
+        self.threads
+            .write()
+            .await
+            .insert(thread_id.to_string(), thread.clone());
+        Ok(thread)
+    }
+
+    /// 把邮件转换为给 Agent 的消息
+    fn prompt_for(&self, email: &InboundEmail) -> String {
+        let mut body: String = email
+            .body
+            .chars()
+            .take(self.config.max_body_chars)
+            .collect();
+        if body.len() < email.body.len() {
+            body.push_str("\n…[正文过长，已截断]");
+        }
+        format!(
+            "发件人: {}\n主题: {}\n\n{}",
+            email.from, email.subject, body
+        )
+    }
+
+    /// 构造回复邮件
+    fn reply_to(&self, email: &InboundEmail, body: String) -> OutgoingEmail {
+        let subject = if email.subject.to_lowercase().starts_with("re:") {
+            email.subject.clone()
+        } else {
+            format!("Re: {}", email.subject)
+        };
+        let mut references: Vec<String> = email
+            .references
+            .iter()
+            .map(|id| bracket_message_id(id))
+            .collect();
+        references.push(bracket_message_id(&email.message_id));
+
+        OutgoingEmail {
+            from: self.config.address.clone(),
+            to: email.from.clone(),
+            subject,
+            body,
+            in_reply_to: Some(bracket_message_id(&email.message_id)),
+            references,
+        }
+    }
+}
+
+#[cfg(feature = "email")]
+pub use self::transport::{ImapConfig, ImapSource, SmtpConfig, SmtpSender};
+
+/// IMAP 收件与 SMTP 发件
+#[cfg(feature = "email")]
+mod transport {
+    use super::{InboundEmail, MailSender, MailSource, OutgoingEmail};
+    use crate::error::{AgentError, AgentResult};
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use futures::TryStreamExt;
+    use lettre::{
+        AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+        message::{Mailbox, header::ContentType},
+        transport::smtp::authentication::Credentials,
+    };
+    use mail_parser::{HeaderValue, MessageParser};
+    use serde::{Deserialize, Serialize};
+    use tokio::net::TcpStream;
+
+    /// IMAP 收件箱配置
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ImapConfig {
+        /// 服务器主机名
+        pub host: String,
+        /// 端口（TLS）
+        pub port: u16,
+        /// 用户名
+        pub username: String,
+        /// 密码
+        pub password: String,
+        /// 邮箱文件夹
+        pub mailbox: String,
+    }
+
+    impl ImapConfig {
+        /// 创建配置，使用 993 端口与 INBOX 文件夹
+        pub fn new<S: Into<String>>(host: S, username: S, password: S) -> Self {
+            Self {
+                host: host.into(),
+                port: 993,
+                username: username.into(),
+                password: password.into(),
+                mailbox: "INBOX".to_string(),
+            }
+        }
+    }
+
+    /// 轮询 IMAP 收件箱中的未读邮件，取出后标记为已读
+    pub struct ImapSource {
+        config: ImapConfig,
+    }
+
+    impl ImapSource {
+        /// 创建 IMAP 邮件来源
+        pub fn new(config: ImapConfig) -> Self {
+            Self { config }
+        }
+    }
+
+    #[async_trait]
+    impl MailSource for ImapSource {
+        async fn fetch(&mut self) -> AgentResult<Vec<InboundEmail>> {
+            let config = &self.config;
+            let tcp = TcpStream::connect((config.host.as_str(), config.port)).await?;
+            let tls = async_native_tls::TlsConnector::new()
+                .connect(&config.host, tcp)
+                .await
+                .map_err(|e| AgentError::network(format!("IMAP TLS 连接失败: {}", e)))?;
+            let mut session = async_imap::Client::new(tls)
+                .login(&config.username, &config.password)
+                .await
+                .map_err(|(e, _)| AgentError::permission(format!("IMAP 登录失败: {}", e)))?;
+            session.select(&config.mailbox).await.map_err(imap_error)?;
+
+            let uids = session.uid_search("UNSEEN").await.map_err(imap_error)?;
+            let mut emails = Vec::new();
+            if !uids.is_empty() {
+                let uid_set = uids
+                    .iter()
+                    .map(|uid| uid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let fetches: Vec<_> = session
+                    .uid_fetch(&uid_set, "RFC822")
+                    .await
+                    .map_err(imap_error)?
+                    .try_collect()
+                    .await
+                    .map_err(imap_error)?;
+                emails.extend(
+                    fetches
+                        .iter()
+                        .filter_map(|fetch| fetch.body())
+                        .filter_map(parse_email),
+                );
+                let _: Vec<_> = session
+                    .uid_store(&uid_set, "+FLAGS (\\Seen)")
+                    .await
+                    .map_err(imap_error)?
+                    .try_collect()
+                    .await
+                    .map_err(imap_error)?;
+            }
+            session.logout().await.map_err(imap_error)?;
+            Ok(emails)
+        }
+    }
+
+    fn imap_error(e: async_imap::error::Error) -> AgentError {
+        AgentError::network(format!("IMAP 错误: {}", e))
+    }
+
+    /// 解析原始邮件，缺少 Message-ID 或发件人时返回 `None`
+    fn parse_email(raw: &[u8]) -> Option<InboundEmail> {
+        let message = MessageParser::default().parse(raw)?;
+        let from = message.from()?.first()?.address()?.to_string();
+        let references = match message.references() {
+            HeaderValue::Text(id) => vec![id.to_string()],
+            HeaderValue::TextList(ids) => ids.iter().map(|id| id.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        let auto_submitted = message
+            .header_raw("Auto-Submitted")
+            .is_some_and(|value| !value.trim().eq_ignore_ascii_case("no"));
+
+        Some(InboundEmail {
+            message_id: message.message_id()?.to_string(),
+            in_reply_to: message.in_reply_to().as_text().map(str::to_string),
+            references,
+            from,
+            subject: message.subject().unwrap_or_default().to_string(),
+            body: message
+                .body_text(0)
+                .map(|body| body.into_owned())
+                .unwrap_or_default(),
+            received_at: message
+                .date()
+                .and_then(|date| DateTime::<Utc>::from_timestamp(date.to_timestamp(), 0))
+                .unwrap_or_else(Utc::now),
+            auto_submitted,
+        })
+    }
+
+    /// SMTP 发件配置
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SmtpConfig {
+        /// 服务器主机名
+        pub host: String,
+        /// 端口，为空时使用 TLS 默认端口
+        pub port: Option<u16>,
+        /// 用户名
+        pub username: String,
+        /// 密码
+        pub password: String,
+    }
+
+    /// 通过 SMTP 发送邮件
+    pub struct SmtpSender {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+    }
+
+    impl SmtpSender {
+        /// 创建 SMTP 发件器
+        pub fn new(config: SmtpConfig) -> AgentResult<Self> {
+            let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| AgentError::config(format!("SMTP 配置错误: {}", e)))?
+                .credentials(Credentials::new(config.username, config.password));
+            if let Some(port) = config.port {
+                builder = builder.port(port);
+            }
+            Ok(Self {
+                transport: builder.build(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl MailSender for SmtpSender {
+        async fn send(&self, email: &OutgoingEmail) -> AgentResult<()> {
+            let mailbox = |address: &str| {
+                address
+                    .parse::<Mailbox>()
+                    .map_err(|e| AgentError::config(format!("邮件地址无效 {}: {}", address, e)))
+            };
+            let mut builder = Message::builder()
+                .from(mailbox(&email.from)?)
+                .to(mailbox(&email.to)?)
+                .subject(email.subject.clone())
+                .header(ContentType::TEXT_PLAIN);
+            if let Some(in_reply_to) = &email.in_reply_to {
+                builder = builder.in_reply_to(in_reply_to.clone());
+            }
+            if !email.references.is_empty() {
+                builder = builder.references(email.references.join(" "));
+            }
+            let message = builder
+                .body(email.body.clone())
+                .map_err(|e| AgentError::other(format!("构造邮件失败: {}", e)))?;
+
+            self.transport
+                .send(message)
+                .await
+                .map_err(|e| AgentError::network(format!("SMTP 发送失败: {}", e)))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RecordingSender(Mutex<Vec<OutgoingEmail>>);
+
+    #[async_trait]
+    impl MailSender for RecordingSender {
+        async fn send(&self, email: &OutgoingEmail) -> AgentResult<()> {
+            self.0.lock().unwrap().push(email.clone());
+            Ok(())
+        }
+    }
+
+    fn email(message_id: &str, from: &str) -> InboundEmail {
+        InboundEmail {
+            message_id: message_id.to_string(),
+            in_reply_to: None,
+            references: Vec::new(),
+            from: from.to_string(),
+            subject: "退款问题".to_string(),
+            body: "我的订单还没有退款".to_string(),
+            received_at: Utc::now(),
+            auto_submitted: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_screening_and_threads() {
+        let config = EmailConfig::new("support@example.com")
+            .allow_sender("alice@example.com")
+            .allow_sender("@partner.org");
+        assert!(config.is_allowed("Alice@Example.com"));
+        assert!(config.is_allowed("bob@partner.org"));
+        assert!(!config.is_allowed("eve@evil.com"));
+        assert!(!EmailConfig::default().is_allowed("alice@example.com"));
+
+        let mut reply = email("<Second@example.com>", "alice@example.com");
+        reply.in_reply_to = Some("<first@example.com>".to_string());
+        reply.references = vec![
+            "<Root@example.com>".to_string(),
+            "<first@example.com>".to_string(),
+        ];
+        assert_eq!(reply.thread_id(), "root@example.com");
+        assert_eq!(email("<a@b>", "x@y").thread_id(), "a@b");
+
+        let sender = Arc::new(RecordingSender(Mutex::new(Vec::new())));
+        let agent = EmailAgent::new(
+            Arc::new(RwLock::new(AgentManager::new(AgentConfig::default()))),
+            Arc::new(ClientRegistry::new()),
+            sender.clone(),
+            config,
+        );
+
+        let mut auto_reply = email("<c@d>", "alice@example.com");
+        auto_reply.auto_submitted = true;
+        for ignored in [
+            email("<c@d>", "eve@evil.com"),
+            email("<c@d>", "support@example.com"),
+            auto_reply,
+        ] {
+            assert!(matches!(
+                agent.handle_inbound(ignored).await.unwrap(),
+                EmailDisposition::Ignored { .. }
+            ));
+        }
+        assert!(agent.threads().await.is_empty());
+
+        let outgoing = agent.reply_to(&reply, "已为您处理".to_string());
+        assert_eq!(outgoing.subject, "Re: 退款问题");
+        assert_eq!(outgoing.to, "alice@example.com");
+        assert_eq!(
+            outgoing.in_reply_to.as_deref(),
+            Some("<Second@example.com>")
+        );
+        assert_eq!(outgoing.references.len(), 3);
+        assert!(sender.0.lock().unwrap().is_empty());
+        assert!(agent.approve_draft("missing").await.is_err());
+    }
+}
@@ -2,14 +2,24 @@
 
 #[cfg(feature = "tauri-support")]
 pub mod tauri_adapter;
+pub mod email;
+pub mod repl;
 pub mod standalone;
 
 #[cfg(feature = "tauri-support")]
 pub use tauri_adapter::TauriAgentAdapter;
+pub use email::{
+    EmailAgent, EmailConfig, EmailDisposition, EmailDraft, EmailThread, InboundEmail, MailSender,
+    MailSource, OutgoingEmail,
+};
+#[cfg(feature = "email")]
+pub use email::{ImapConfig, ImapSource, SmtpConfig, SmtpSender};
+pub use repl::{Repl, ReplCommand};
 pub use standalone::StandaloneAgentAdapter;
 
 /// 通用适配器特征
-pub trait AgentAdapter {
+#[async_trait::async_trait]
+pub trait AgentAdapter: Send + Sync {
     /// 发送聊天消息
     async fn chat(&self, agent_id: &str, message: &str) -> crate::error::AgentResult<crate::core::AgentResponse>;
 
@@ -0,0 +1,326 @@
+//! 交互式终端对话（REPL）
+//!
+//! 普通输入作为聊天消息发送，以 `/` 开头的输入作为命令处理，
+//! 可在对话中切换模型、提供商、工具开关，查看或保存对话历史。
+
+use crate::{
+    AgentManager,
+    core::{ChatOptions, ClientRegistry},
+    error::AgentResult,
+};
+use std::{
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+/// 帮助信息
+const HELP: &str = "\
+可用命令:
+  /model <名称>              切换模型
+  /provider <名称> [模型]    切换提供商（未指定模型时使用该提供商的默认模型）
+  /tools on|off              启用或禁用工具
+  /stream on|off             启用或禁用流式输出
+  /history [条数]            查看对话历史
+  /save [目录]               保存对话历史为 JSON 文件
+  /clear                     清空对话历史
+  /help                      显示帮助
+  /exit                      退出";
+
+/// REPL 输入解析结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplCommand {
+    /// 发送聊天消息
+    Chat(String),
+    /// 切换模型
+    Model(String),
+    /// 切换提供商及可选的模型
+    Provider(String, Option<String>),
+    /// 启用或禁用工具
+    Tools(bool),
+    /// 启用或禁用流式输出
+    Stream(bool),
+    /// 查看最近的对话历史
+    History(Option<usize>),
+    /// 保存对话历史到目录
+    Save(Option<PathBuf>),
+    /// 清空对话历史
+    Clear,
+    /// 显示帮助
+    Help,
+    /// 退出
+    Exit,
+}
+
+impl ReplCommand {
+    /// 解析一行输入，空行返回 `Ok(None)`
+    pub fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
+        }
+        let Some(command) = line.strip_prefix('/') else {
+            return Ok(Some(Self::Chat(line.to_string())));
+        };
+
+        let mut parts = command.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts.collect();
+
+        let command = match (name, args.as_slice()) {
+            ("model", [model]) => Self::Model(model.to_string()),
+            ("provider", [provider]) => Self::Provider(provider.to_string(), None),
+            ("provider", [provider, model]) => {
+                Self::Provider(provider.to_string(), Some(model.to_string()))
+            }
+            ("tools", [flag]) => Self::Tools(parse_switch(flag)?),
+            ("stream", [flag]) => Self::Stream(parse_switch(flag)?),
+            ("history", []) => Self::History(None),
+            ("history", [limit]) => Self::History(Some(
+                limit
+                    .parse()
+                    .map_err(|_| format!("无效的条数: {}", limit))?,
+            )),
+            ("save", []) => Self::Save(None),
+            ("save", [dir]) => Self::Save(Some(PathBuf::from(dir))),
+            ("clear", []) => Self::Clear,
+            ("help", []) => Self::Help,
+            ("exit" | "quit", []) => Self::Exit,
+            _ => return Err(format!("无法识别的命令: /{}，输入 /help 查看帮助", command)),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn parse_switch(flag: &str) -> Result<bool, String> {
+    match flag {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("应为 on 或 off: {}", flag)),
+    }
+}
+
+/// 交互式终端对话
+pub struct Repl<'a> {
+    manager: &'a AgentManager,
+    registry: &'a ClientRegistry,
+    agent_id: String,
+    streaming: bool,
+    save_dir: PathBuf,
+}
+
+impl<'a> Repl<'a> {
+    /// 创建 REPL，默认启用流式输出，历史保存到当前目录
+    pub fn new(manager: &'a AgentManager, registry: &'a ClientRegistry, agent_id: &str) -> Self {
+        Self {
+            manager,
+            registry,
+            agent_id: agent_id.to_string(),
+            streaming: true,
+            save_dir: PathBuf::from("."),
+        }
+    }
+
+    /// 设置是否流式输出
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// 设置 `/save` 未指定目录时的保存目录
+    pub fn with_save_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.save_dir = dir.into();
+        self
+    }
+
+    /// 从输入逐行读取并执行，直到 `/exit` 或输入结束
+    pub async fn run<R: BufRead, W: Write + Send>(
+        &mut self,
+        mut input: R,
+        mut output: W,
+    ) -> AgentResult<()> {
+        let config = self.manager.get_agent_config(&self.agent_id).await?;
+        writeln!(
+            output,
+            "与 {} 对话 ({}/{})，输入 /help 查看命令",
+            self.agent_id, config.provider, config.model
+        )?;
+
+        let mut line = String::new();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            match ReplCommand::parse(&line) {
+                Ok(Some(ReplCommand::Exit)) => break,
+                Ok(Some(command)) => {
+                    if let Err(e) = self.execute(command, &mut output).await {
+                        writeln!(output, "错误: {}", e)?;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => writeln!(output, "{}", e)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 执行一条命令
+    async fn execute<W: Write + Send>(
+        &mut self,
+        command: ReplCommand,
+        output: &mut W,
+    ) -> AgentResult<()> {
+        let agent_id = self.agent_id.as_str();
+        match command {
+            ReplCommand::Chat(message) => {
+                if self.streaming {
+                    // 回调中无法返回错误，写入失败时在结束后统一报告
+                    let mut write_result = Ok(());
+                    self.manager
+                        .chat_stream(
+                            self.registry,
+                            agent_id,
+                            &message,
+                            ChatOptions::default(),
+                            |chunk| {
+                                if write_result.is_ok() {
+                                    write_result =
+                                        write!(output, "{}", chunk).and_then(|_| output.flush());
+                                }
+                            },
+                        )
+                        .await?;
+                    write_result?;
+                    writeln!(output, "\n")?;
+                } else {
+                    let response = self.manager.chat(self.registry, agent_id, &message).await?;
+                    writeln!(output, "{}\n", response.content)?;
+                }
+            }
+            ReplCommand::Model(model) => {
+                let mut config = self.manager.get_agent_config(agent_id).await?;
+                config.model = model;
+                self.manager
+                    .update_agent_config(agent_id, config.clone())
+                    .await?;
+                writeln!(output, "已切换模型: {}", config.model)?;
+            }
+            ReplCommand::Provider(provider, model) => {
+                let model = match model {
+                    Some(model) => model,
+                    None => match self.registry.get_client_config(&provider) {
+                        Some(client) => client.default_model.clone(),
+                        None => self.manager.get_agent_config(agent_id).await?.model,
+                    },
+                };
+                self.manager
+                    .switch_provider(agent_id, &provider, &model)
+                    .await?;
+                writeln!(output, "已切换到 {}/{}", provider, model)?;
+            }
+            ReplCommand::Tools(enabled) => {
+                let mut config = self.manager.get_agent_config(agent_id).await?;
+                config.enable_tools = enabled;
+                self.manager.update_agent_config(agent_id, config).await?;
+                writeln!(output, "工具已{}", if enabled { "启用" } else { "禁用" })?;
+            }
+            ReplCommand::Stream(enabled) => {
+                self.streaming = enabled;
+                writeln!(
+                    output,
+                    "流式输出已{}",
+                    if enabled { "启用" } else { "禁用" }
+                )?;
+            }
+            ReplCommand::History(limit) => {
+                let history = self.manager.get_conversation_history(agent_id).await?;
+                let skip = limit.map_or(0, |limit| history.messages.len().saturating_sub(limit));
+                for message in history.messages.iter().skip(skip) {
+                    writeln!(
+                        output,
+                        "[{}] {:?}: {}",
+                        message.timestamp.format("%H:%M:%S"),
+                        message.role,
+                        message.content
+                    )?;
+                }
+            }
+            ReplCommand::Save(dir) => {
+                let dir = dir.unwrap_or_else(|| self.save_dir.clone());
+                let path = self.manager.save_history(agent_id, &dir).await?;
+                writeln!(output, "已保存到 {}", path.display())?;
+            }
+            ReplCommand::Clear => {
+                self.manager.clear_conversation_history(agent_id).await?;
+                writeln!(output, "对话历史已清空")?;
+            }
+            ReplCommand::Help => writeln!(output, "{}", HELP)?,
+            ReplCommand::Exit => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(ReplCommand::parse("   ").unwrap(), None);
+        assert_eq!(
+            ReplCommand::parse("你好").unwrap(),
+            Some(ReplCommand::Chat("你好".to_string()))
+        );
+        assert_eq!(
+            ReplCommand::parse("/provider anthropic").unwrap(),
+            Some(ReplCommand::Provider("anthropic".to_string(), None))
+        );
+        assert_eq!(
+            ReplCommand::parse("/tools off").unwrap(),
+            Some(ReplCommand::Tools(false))
+        );
+        assert_eq!(
+            ReplCommand::parse("/history 5").unwrap(),
+            Some(ReplCommand::History(Some(5)))
+        );
+        assert_eq!(
+            ReplCommand::parse("/quit").unwrap(),
+            Some(ReplCommand::Exit)
+        );
+        assert!(ReplCommand::parse("/tools maybe").is_err());
+        assert!(ReplCommand::parse("/unknown").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repl_runs_commands() {
+        let manager = AgentManager::new(crate::core::AgentConfig::default());
+        manager
+            .create_agent("repl_agent".to_string(), None)
+            .await
+            .unwrap();
+        let registry = ClientRegistry::new();
+
+        let input = "/model gpt-4o\n/tools on\n/history\n/exit\n/model ignored\n";
+        let mut output = Vec::new();
+        Repl::new(&manager, &registry, "repl_agent")
+            .run(input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+
+        let config = manager.get_agent_config("repl_agent").await.unwrap();
+        assert_eq!(config.model, "gpt-4o");
+        assert!(config.enable_tools);
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("已切换模型: gpt-4o")
+        );
+    }
+}
@@ -1,9 +1,14 @@
 //! 独立适配器实现
 
 use crate::{
-    adapters::AgentAdapter,
-    core::{AgentConfig, AgentResponse, ClientRegistry, ConversationHistory},
+    adapters::{AgentAdapter, Repl},
+    core::{
+        spawn_agent_gc, AgentConfig, AgentGcConfig, AgentMessage, AgentResponse, CacheStats,
+        ChatOptions, ChatSession, ClientRegistry, MessageMetadata, ConversationHistory, ProviderQueueMetrics, ReplayOptions, ReplayReport, RequestPriority, ToolSelection,
+    },
     error::AgentResult,
+    storage::{Page, SessionQuery, SessionStore},
+    tools::{spawn_tools_watcher, ToolsDirectoryConfig},
     AgentManager,
 };
 use std::sync::Arc;
@@ -26,6 +31,13 @@ impl StandaloneAgentAdapter {
         Self { manager, registry }
     }
 
+    /// 使用指定的客户端注册表创建适配器，API 密钥由注册表中的客户端配置提供
+    pub fn with_registry(default_config: AgentConfig, registry: ClientRegistry) -> Self {
+        let manager = Arc::new(RwLock::new(AgentManager::new(default_config)));
+
+        Self { manager, registry }
+    }
+
     /// 获取 Agent 管理器
     pub async fn get_manager(&self) -> tokio::sync::RwLockReadGuard<'_, AgentManager> {
         self.manager.read().await
@@ -36,6 +48,25 @@ impl StandaloneAgentAdapter {
         self.manager.write().await
     }
 
+    /// 启动空闲 Agent 回收任务，通过 `get_manager().await.subscribe_events()` 接收回收事件
+    pub fn start_gc(&self, config: AgentGcConfig) -> tokio::task::JoinHandle<()> {
+        spawn_agent_gc(self.manager.clone(), config)
+    }
+
+    /// 启动工具目录监视任务，通过 `get_manager().await.get_tool_manager().subscribe_events()` 接收工具变化
+    pub fn start_tools_watcher(&self, config: ToolsDirectoryConfig) -> tokio::task::JoinHandle<()> {
+        spawn_tools_watcher(self.manager.clone(), config)
+    }
+
+    /// 在当前终端中启动与指定 Agent 的交互式对话，直到输入 `/exit` 或标准输入结束
+    pub async fn run_repl(&self, agent_id: &str) -> AgentResult<()> {
+        let manager = self.manager.read().await;
+        let stdin = std::io::stdin();
+        Repl::new(&manager, &self.registry, agent_id)
+            .run(stdin.lock(), std::io::stdout())
+            .await
+    }
+
     /// 获取对话历史
     pub async fn get_conversation_history(
         &self,
@@ -67,7 +98,7 @@ impl StandaloneAgentAdapter {
         manager.update_agent_config(agent_id, config).await
     }
 
-    /// 批量聊天
+    /// 批量聊天（以批处理优先级排队，不抢占交互式请求）
     pub async fn batch_chat(
         &self,
         requests: Vec<(String, String)>,
@@ -76,7 +107,9 @@ impl StandaloneAgentAdapter {
         let mut results = Vec::new();
 
         for (agent_id, message) in requests {
-            let result = manager.chat(&self.registry, &agent_id, &message).await;
+            let result = manager
+                .chat_with_priority(&self.registry, &agent_id, &message, RequestPriority::Batch)
+                .await;
             results.push((agent_id, result));
         }
 
@@ -94,6 +127,138 @@ impl StandaloneAgentAdapter {
         self.batch_chat(requests).await
     }
 
+    /// 按指定优先级发送聊天消息
+    pub async fn chat_with_priority(
+        &self,
+        agent_id: &str,
+        message: &str,
+        priority: RequestPriority,
+    ) -> AgentResult<AgentResponse> {
+        let manager = self.manager.read().await;
+        manager
+            .chat_with_priority(&self.registry, agent_id, message, priority)
+            .await
+    }
+
+    /// 按请求选项（优先级、是否跳过缓存）发送聊天消息
+    pub async fn chat_with_options(
+        &self,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+    ) -> AgentResult<AgentResponse> {
+        let manager = self.manager.read().await;
+        manager
+            .chat_with_options(&self.registry, agent_id, message, options)
+            .await
+    }
+
+    /// 用新的提供商/模型回放 Agent 的当前对话并对比回复
+    pub async fn replay_conversation(
+        &self,
+        agent_id: &str,
+        options: &ReplayOptions,
+    ) -> AgentResult<ReplayReport> {
+        let manager = self.manager.read().await;
+        manager
+            .replay_conversation(&self.registry, agent_id, options)
+            .await
+    }
+
+    /// 用新的提供商/模型回放已保存的会话并对比回复
+    pub async fn replay_session(
+        &self,
+        session_id: &str,
+        options: &ReplayOptions,
+    ) -> AgentResult<ReplayReport> {
+        let manager = self.manager.read().await;
+        manager
+            .replay_session(&self.registry, session_id, options)
+            .await
+    }
+
+    /// 获取响应缓存统计
+    pub async fn get_cache_stats(&self) -> CacheStats {
+        self.manager.read().await.get_cache_stats()
+    }
+
+    /// 获取各提供商的请求排队指标
+    pub async fn get_scheduler_metrics(&self) -> Vec<ProviderQueueMetrics> {
+        self.manager.read().await.get_scheduler_metrics()
+    }
+
+    /// 更新消息注解，返回合并后的元数据
+    pub async fn annotate_message(
+        &self,
+        agent_id: &str,
+        message_id: &str,
+        annotations: MessageMetadata,
+    ) -> AgentResult<MessageMetadata> {
+        let manager = self.manager.read().await;
+        manager
+            .annotate_message(agent_id, message_id, annotations)
+            .await
+    }
+
+    /// 设置会话存储
+    pub async fn set_session_store(&self, store: Arc<SessionStore>) {
+        self.manager.write().await.set_session_store(Some(store));
+    }
+
+    /// 获取会话存储
+    async fn session_store(&self) -> AgentResult<Arc<SessionStore>> {
+        self.manager.read().await.session_store().cloned()
+    }
+
+    /// 为 Agent 创建并关联新会话
+    pub async fn start_session(&self, agent_id: &str, title: &str) -> AgentResult<ChatSession> {
+        let manager = self.manager.read().await;
+        manager.start_session(agent_id, title).await
+    }
+
+    /// 分页列出会话
+    pub async fn list_sessions(&self, query: SessionQuery) -> AgentResult<Page<ChatSession>> {
+        self.session_store().await?.list_sessions(query).await
+    }
+
+    /// 获取会话
+    pub async fn get_session(&self, session_id: &str) -> AgentResult<Option<ChatSession>> {
+        self.session_store().await?.get_session(session_id).await
+    }
+
+    /// 分页获取会话消息
+    pub async fn get_session_messages(
+        &self,
+        session_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> AgentResult<Page<AgentMessage>> {
+        self.session_store()
+            .await?
+            .get_messages(session_id, offset, limit)
+            .await
+    }
+
+    /// 更新会话
+    pub async fn update_session(&self, session: &ChatSession) -> AgentResult<()> {
+        self.session_store().await?.update_session(session).await
+    }
+
+    /// 删除会话（可恢复）
+    pub async fn delete_session(&self, session_id: &str) -> AgentResult<()> {
+        self.session_store().await?.delete_session(session_id).await
+    }
+
+    /// 恢复已删除的会话
+    pub async fn restore_session(&self, session_id: &str) -> AgentResult<()> {
+        self.session_store().await?.restore_session(session_id).await
+    }
+
+    /// 永久删除会话
+    pub async fn purge_session(&self, session_id: &str) -> AgentResult<()> {
+        self.session_store().await?.purge_session(session_id).await
+    }
+
     /// 获取统计信息
     pub async fn get_statistics(&self) -> AgentResult<AgentStatistics> {
         let manager = self.manager.read().await;
@@ -117,6 +282,7 @@ impl StandaloneAgentAdapter {
     }
 }
 
+#[async_trait::async_trait]
 impl super::AgentAdapter for StandaloneAgentAdapter {
     async fn chat(&self, agent_id: &str, message: &str) -> AgentResult<AgentResponse> {
         let manager = self.manager.read().await;
@@ -259,6 +425,12 @@ impl StandaloneConfigBuilder {
         self
     }
 
+    /// 设置工具选择规则
+    pub fn tool_selection(mut self, selection: ToolSelection) -> Self {
+        self.config.tool_selection = selection;
+        self
+    }
+
     /// 设置历史消息限制
     pub fn history_limit(mut self, limit: usize) -> Self {
         self.config.history_limit = Some(limit);
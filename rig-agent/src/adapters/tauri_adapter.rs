@@ -1,11 +1,23 @@
 //! Tauri 适配器实现
 
 use crate::{
-    core::{AgentConfig, AgentResponse},
+    core::{
+        spawn_agent_gc, AgentConfig, AgentEvent, AgentGcConfig, AgentMessage, AgentPackage,
+        AgentResponse, ChatSession, ClientRegistry, DocumentSummary, FeedbackRating,
+        FeedbackRecord, MessageMetadata, RaceStats, RequestContext, RequestOrigin,
+        ResponseFeedback, SummarizeOptions, TranslateRequest, Translation, VariantMetrics,
+    },
     error::{AgentError, AgentResult},
+    storage::{Page, SessionQuery, SessionStore},
+    tools::{
+        spawn_tools_watcher, McpServerHealth, PermissionDecision, PermissionGrant, ToolEvent,
+        ToolStats, ToolsDirectoryConfig,
+    },
     AgentManager,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -44,17 +56,380 @@ impl<E: TauriEventEmitter> TauriAgentAdapter<E> {
         self.manager.write().await
     }
 
+    /// 将工具事件（连续失败告警、恢复、工具目录变化、MCP 服务器放弃重启）转发到前端
+    pub async fn forward_tool_events(&self) -> tokio::task::JoinHandle<()>
+    where
+        E: 'static,
+    {
+        let mut receiver = self.manager.read().await.get_tool_manager().subscribe_events();
+        let event_emitter = self.event_emitter.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let event_name = match &event {
+                            ToolEvent::RepeatedFailures { .. } => "agent-tool-failure",
+                            ToolEvent::Recovered { .. } => "agent-tool-recovered",
+                            ToolEvent::ToolRegistered { .. } | ToolEvent::ToolUnregistered { .. } => {
+                                "agent-tools-changed"
+                            }
+                            ToolEvent::ToolLoadFailed { .. } => "agent-tool-load-failed",
+                            ToolEvent::McpServerFailed { .. } => "agent-mcp-server-failed",
+                        };
+                        let payload = serde_json::to_value(&event).unwrap_or_default();
+                        event_emitter.emit_event(event_name, payload);
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// 启动空闲 Agent 回收任务，并将回收事件转发到前端
+    pub async fn start_gc(&self, config: AgentGcConfig) -> tokio::task::JoinHandle<()>
+    where
+        E: 'static,
+    {
+        let mut receiver = self.manager.read().await.subscribe_events();
+        let event_emitter = self.event_emitter.clone();
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let event_name = match &event {
+                            AgentEvent::Reaped { .. } => "agent-reaped",
+                        };
+                        let payload = serde_json::to_value(&event).unwrap_or_default();
+                        event_emitter.emit_event(event_name, payload);
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        spawn_agent_gc(self.manager.clone(), config)
+    }
+
+    /// 启动工具目录监视任务，工具注册与注销事件经 [`Self::forward_tool_events`] 转发到前端
+    pub fn start_tools_watcher(&self, config: ToolsDirectoryConfig) -> tokio::task::JoinHandle<()> {
+        spawn_tools_watcher(self.manager.clone(), config)
+    }
+
+    /// 更新消息注解并发射事件
+    pub async fn annotate_message_with_events(
+        &self,
+        agent_id: &str,
+        message_id: &str,
+        annotations: MessageMetadata,
+    ) -> AgentResult<MessageMetadata> {
+        let metadata = self
+            .manager
+            .read()
+            .await
+            .annotate_message(agent_id, message_id, annotations)
+            .await?;
+        self.event_emitter.emit_event("agent-message-annotated", serde_json::json!({
+            "agent_id": agent_id,
+            "message_id": message_id,
+            "metadata": metadata,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(metadata)
+    }
+
+    /// 提交对回复的反馈并发射事件
+    pub async fn submit_feedback_with_events(
+        &self,
+        response_id: &str,
+        feedback: ResponseFeedback,
+    ) -> AgentResult<ResponseFeedback> {
+        let feedback = self
+            .manager
+            .read()
+            .await
+            .submit_feedback(response_id, feedback)
+            .await?;
+        self.event_emitter.emit_event("agent-feedback-submitted", serde_json::json!({
+            "response_id": response_id,
+            "feedback": feedback,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(feedback)
+    }
+
+    /// 导出 Agent 带反馈的问答
+    pub async fn export_feedback(&self, agent_id: &str) -> AgentResult<Vec<FeedbackRecord>> {
+        self.manager.read().await.export_feedback(agent_id).await
+    }
+
+    /// 把 Agent 导出为包文件，可通过 P2P 文件传输分享
+    pub async fn export_agent_package(&self, agent_id: &str, path: &Path) -> AgentResult<()> {
+        let package = self
+            .manager
+            .read()
+            .await
+            .export_agent_package(agent_id)
+            .await?;
+        package.save(path)
+    }
+
+    /// 从包文件导入 Agent 并发射事件，返回新 Agent 的 ID
+    pub async fn import_agent_package_with_events(
+        &self,
+        path: &Path,
+        agent_id: Option<String>,
+    ) -> AgentResult<String> {
+        let package = AgentPackage::load(path)?;
+        let agent_id = self
+            .manager
+            .read()
+            .await
+            .import_agent_package(&package, agent_id)
+            .await?;
+        self.event_emitter.emit_event("agent-package-imported", serde_json::json!({
+            "agent_id": agent_id,
+            "package": package.name,
+            "knowledge_files": package.knowledge.len(),
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(agent_id)
+    }
+
+    /// 读取文件并做分块摘要，未指定标题时使用文件名，完成后发射事件
+    pub async fn summarize_file_with_events(
+        &self,
+        registry: &ClientRegistry,
+        path: &Path,
+        mut options: SummarizeOptions,
+    ) -> AgentResult<DocumentSummary> {
+        let document = tokio::fs::read_to_string(path).await.map_err(|e| {
+            AgentError::other(format!("读取文件 {} 失败: {}", path.display(), e))
+        })?;
+        if options.title.is_none() {
+            options.title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+        let summary = self
+            .manager
+            .read()
+            .await
+            .summarize_document(registry, &document, &options)
+            .await?;
+        self.event_emitter.emit_event("agent-document-summarized", serde_json::json!({
+            "path": path,
+            "sections": summary.sections.len(),
+            "chunks": summary.chunks,
+            "duration_ms": summary.duration_ms,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(summary)
+    }
+
+    /// 翻译文本
+    pub async fn translate(
+        &self,
+        registry: &ClientRegistry,
+        request: &TranslateRequest,
+    ) -> AgentResult<Translation> {
+        self.manager.read().await.translate(registry, request).await
+    }
+
+    /// 获取 Agent 各 A/B 变体的汇总指标
+    pub async fn get_variant_metrics(&self, agent_id: &str) -> Vec<VariantMetrics> {
+        self.manager.read().await.get_variant_metrics(agent_id)
+    }
+
+    /// 获取 Agent 的提供商竞速指标
+    pub async fn get_race_stats(&self, agent_id: &str) -> RaceStats {
+        self.manager.read().await.get_race_stats(agent_id)
+    }
+
+    /// 设置会话存储
+    pub async fn set_session_store(&self, store: Arc<SessionStore>) {
+        self.manager.write().await.set_session_store(Some(store));
+    }
+
+    /// 获取会话存储
+    async fn session_store(&self) -> AgentResult<Arc<SessionStore>> {
+        self.manager.read().await.session_store().cloned()
+    }
+
+    /// 为 Agent 创建会话并发射事件
+    pub async fn start_session_with_events(
+        &self,
+        agent_id: &str,
+        title: &str,
+    ) -> AgentResult<ChatSession> {
+        let session = self
+            .manager
+            .read()
+            .await
+            .start_session(agent_id, title)
+            .await?;
+        self.event_emitter.emit_event(
+            "agent-session-created",
+            serde_json::to_value(&session).unwrap_or_default(),
+        );
+        Ok(session)
+    }
+
+    /// 分页列出会话
+    pub async fn list_sessions(&self, query: SessionQuery) -> AgentResult<Page<ChatSession>> {
+        self.session_store().await?.list_sessions(query).await
+    }
+
+    /// 分页获取会话消息
+    pub async fn get_session_messages(
+        &self,
+        session_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> AgentResult<Page<AgentMessage>> {
+        self.session_store()
+            .await?
+            .get_messages(session_id, offset, limit)
+            .await
+    }
+
+    /// 更新会话
+    pub async fn update_session(&self, session: &ChatSession) -> AgentResult<()> {
+        self.session_store().await?.update_session(session).await
+    }
+
+    /// 获取会话变量
+    pub async fn get_session_variables(
+        &self,
+        session_id: &str,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        self.manager.read().await.get_session_variables(session_id).await
+    }
+
+    /// 设置（值为空时移除）会话变量并发射事件
+    pub async fn set_session_variable_with_events(
+        &self,
+        session_id: &str,
+        key: &str,
+        value: Option<&str>,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        let variables = {
+            let manager = self.manager.read().await;
+            match value {
+                Some(value) => manager.set_session_variable(session_id, key, value).await?,
+                None => manager.remove_session_variable(session_id, key).await?,
+            }
+        };
+        self.event_emitter.emit_event("agent-session-variables-updated", serde_json::json!({
+            "session_id": session_id,
+            "variables": variables,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(variables)
+    }
+
+    /// 删除会话（可恢复）并发射事件
+    pub async fn delete_session_with_events(&self, session_id: &str) -> AgentResult<()> {
+        self.session_store().await?.delete_session(session_id).await?;
+        self.event_emitter.emit_event("agent-session-deleted", serde_json::json!({
+            "session_id": session_id,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(())
+    }
+
+    /// 恢复已删除的会话并发射事件
+    pub async fn restore_session_with_events(&self, session_id: &str) -> AgentResult<()> {
+        self.session_store().await?.restore_session(session_id).await?;
+        self.event_emitter.emit_event("agent-session-restored", serde_json::json!({
+            "session_id": session_id,
+            "timestamp": chrono::Utc::now()
+        }));
+        Ok(())
+    }
+
+    /// 获取工具调用统计
+    pub async fn get_tool_stats(&self) -> Vec<ToolStats> {
+        self.manager.read().await.get_tool_manager().get_tool_stats()
+    }
+
+    /// 获取各 MCP 服务器的运行状况
+    pub async fn get_mcp_server_health(&self) -> Vec<McpServerHealth> {
+        self.manager
+            .read()
+            .await
+            .get_tool_manager()
+            .get_mcp_server_health()
+    }
+
+    /// 列出工具授权
+    pub async fn list_tool_permissions(&self, agent_id: Option<&str>) -> Vec<PermissionGrant> {
+        self.manager.read().await.list_tool_permissions(agent_id)
+    }
+
+    /// 设置工具授权并发射事件
+    pub async fn grant_tool_permission_with_events(
+        &self,
+        agent_id: Option<&str>,
+        tool_name: &str,
+        decision: PermissionDecision,
+    ) -> AgentResult<PermissionGrant> {
+        let grant = self
+            .manager
+            .read()
+            .await
+            .grant_tool_permission(agent_id, tool_name, decision)?;
+        self.event_emitter.emit_event("agent-tool-permission-granted", serde_json::to_value(&grant)?);
+        Ok(grant)
+    }
+
+    /// 撤销工具授权并发射事件
+    pub async fn revoke_tool_permission_with_events(
+        &self,
+        agent_id: Option<&str>,
+        tool_name: &str,
+    ) -> AgentResult<bool> {
+        let revoked = self
+            .manager
+            .read()
+            .await
+            .revoke_tool_permission(agent_id, tool_name)?;
+        if revoked {
+            self.event_emitter.emit_event("agent-tool-permission-revoked", serde_json::json!({
+                "agent_id": agent_id,
+                "tool_name": tool_name,
+                "timestamp": chrono::Utc::now()
+            }));
+        }
+        Ok(revoked)
+    }
+
     /// 发送聊天消息并发射事件
     pub async fn chat_with_events(&self, agent_id: &str, message: &str) -> AgentResult<AgentResponse> {
+        self.chat_with_context(agent_id, message, RequestContext::new(RequestOrigin::Tauri))
+            .await
+    }
+
+    /// 在请求上下文中发送聊天消息并发射事件
+    pub async fn chat_with_context(
+        &self,
+        agent_id: &str,
+        message: &str,
+        context: RequestContext,
+    ) -> AgentResult<AgentResponse> {
         // 发射开始聊天事件
         self.event_emitter.emit_event("agent-chat-start", serde_json::json!({
             "agent_id": agent_id,
             "message": message,
+            "trace_id": context.trace_id,
             "timestamp": chrono::Utc::now()
         }));
 
         let manager = self.manager.read().await;
-        let result = manager.chat(agent_id, message).await;
+        let result = context.scope(manager.chat(agent_id, message)).await;
 
         match &result {
             Ok(response) => {
@@ -175,6 +550,7 @@ impl<E: TauriEventEmitter> TauriAgentAdapter<E> {
     }
 }
 
+#[async_trait::async_trait]
 impl<E: TauriEventEmitter> super::AgentAdapter for TauriAgentAdapter<E> {
     async fn chat(&self, agent_id: &str, message: &str) -> AgentResult<AgentResponse> {
         self.chat_with_events(agent_id, message).await
@@ -194,11 +570,80 @@ impl<E: TauriEventEmitter> super::AgentAdapter for TauriAgentAdapter<E> {
     }
 }
 
+/// 消息注解请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnnotateMessageRequest {
+    pub agent_id: String,
+    pub message_id: String,
+    pub annotations: MessageMetadata,
+}
+
+/// 回复反馈请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeedbackRequest {
+    pub response_id: String,
+    #[serde(default)]
+    pub rating: Option<FeedbackRating>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// 导出 Agent 包请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportPackageRequest {
+    pub agent_id: String,
+    pub path: PathBuf,
+}
+
+/// 导入 Agent 包请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPackageRequest {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+}
+
+/// 文件摘要请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SummarizeFileRequest {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub options: SummarizeOptions,
+}
+
+/// 会话 ID 请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionIdRequest {
+    pub session_id: String,
+}
+
+/// 会话变量请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionVariableRequest {
+    pub session_id: String,
+    pub key: String,
+    /// 为空时移除该变量
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// 会话消息分页请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionMessagesRequest {
+    pub session_id: String,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: usize,
+}
+
 /// Tauri 命令请求类型
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatRequest {
     pub agent_id: String,
     pub message: String,
+    /// 前端的语言区域，如 `zh-CN`
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -212,6 +657,32 @@ pub struct AgentIdRequest {
     pub agent_id: String,
 }
 
+/// 工具授权列表请求
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ToolPermissionQuery {
+    /// 为空时列出所有授权
+    #[serde(default)]
+    pub agent_id: Option<String>,
+}
+
+/// 工具授权请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ToolPermissionRequest {
+    /// 为空时对所有 Agent 生效
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    pub tool_name: String,
+    pub decision: PermissionDecision,
+}
+
+/// 撤销工具授权请求
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokeToolPermissionRequest {
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    pub tool_name: String,
+}
+
 /// Tauri 命令响应类型
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TauriResponse<T> {
@@ -256,7 +727,11 @@ pub mod commands {
         adapter: tauri::State<'_, TauriAgentAdapter<E>>,
         request: ChatRequest,
     ) -> Result<TauriResponse<AgentResponse>, String> {
-        let result = adapter.chat_with_events(&request.agent_id, &request.message).await;
+        let mut context = RequestContext::new(RequestOrigin::Tauri);
+        context.locale = request.locale;
+        let result = adapter
+            .chat_with_context(&request.agent_id, &request.message, context)
+            .await;
         Ok(TauriResponse::from(result))
     }
 
@@ -295,6 +770,217 @@ pub mod commands {
         Ok(TauriResponse::from(result))
     }
 
+    /// 获取工具调用统计命令
+    pub async fn get_tool_stats<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+    ) -> Result<TauriResponse<Vec<ToolStats>>, String> {
+        Ok(TauriResponse::success(adapter.get_tool_stats().await))
+    }
+
+    /// 获取 MCP 服务器运行状况命令
+    pub async fn get_mcp_server_health<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+    ) -> Result<TauriResponse<Vec<McpServerHealth>>, String> {
+        Ok(TauriResponse::success(adapter.get_mcp_server_health().await))
+    }
+
+    /// 列出工具授权命令
+    pub async fn list_tool_permissions<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        query: ToolPermissionQuery,
+    ) -> Result<TauriResponse<Vec<PermissionGrant>>, String> {
+        Ok(TauriResponse::success(
+            adapter.list_tool_permissions(query.agent_id.as_deref()).await,
+        ))
+    }
+
+    /// 设置工具授权命令
+    pub async fn grant_tool_permission<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: ToolPermissionRequest,
+    ) -> Result<TauriResponse<PermissionGrant>, String> {
+        let result = adapter
+            .grant_tool_permission_with_events(
+                request.agent_id.as_deref(),
+                &request.tool_name,
+                request.decision,
+            )
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 撤销工具授权命令
+    pub async fn revoke_tool_permission<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: RevokeToolPermissionRequest,
+    ) -> Result<TauriResponse<bool>, String> {
+        let result = adapter
+            .revoke_tool_permission_with_events(request.agent_id.as_deref(), &request.tool_name)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 更新消息注解命令
+    pub async fn annotate_message<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: AnnotateMessageRequest,
+    ) -> Result<TauriResponse<MessageMetadata>, String> {
+        let result = adapter
+            .annotate_message_with_events(&request.agent_id, &request.message_id, request.annotations)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 提交回复反馈命令
+    pub async fn submit_feedback<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: FeedbackRequest,
+    ) -> Result<TauriResponse<ResponseFeedback>, String> {
+        let feedback = ResponseFeedback {
+            rating: request.rating,
+            comment: request.comment,
+            created_at: chrono::Utc::now(),
+        };
+        let result = adapter
+            .submit_feedback_with_events(&request.response_id, feedback)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 导出反馈数据集命令
+    pub async fn export_feedback<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: AgentIdRequest,
+    ) -> Result<TauriResponse<Vec<FeedbackRecord>>, String> {
+        let result = adapter.export_feedback(&request.agent_id).await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 导出 Agent 包命令
+    pub async fn export_agent_package<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: ExportPackageRequest,
+    ) -> Result<TauriResponse<()>, String> {
+        let result = adapter
+            .export_agent_package(&request.agent_id, &request.path)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 导入 Agent 包命令
+    pub async fn import_agent_package<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: ImportPackageRequest,
+    ) -> Result<TauriResponse<String>, String> {
+        let result = adapter
+            .import_agent_package_with_events(&request.path, request.agent_id)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 文件摘要命令，客户端注册表需由应用通过 `manage` 注入
+    pub async fn summarize_document<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        registry: tauri::State<'_, ClientRegistry>,
+        request: SummarizeFileRequest,
+    ) -> Result<TauriResponse<DocumentSummary>, String> {
+        let result = adapter
+            .summarize_file_with_events(&registry, &request.path, request.options)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 翻译命令，客户端注册表需由应用通过 `manage` 注入
+    pub async fn translate<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        registry: tauri::State<'_, ClientRegistry>,
+        request: TranslateRequest,
+    ) -> Result<TauriResponse<Translation>, String> {
+        let result = adapter.translate(&registry, &request).await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 获取 A/B 变体指标命令
+    pub async fn get_variant_metrics<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: AgentIdRequest,
+    ) -> Result<TauriResponse<Vec<VariantMetrics>>, String> {
+        Ok(TauriResponse::success(
+            adapter.get_variant_metrics(&request.agent_id).await,
+        ))
+    }
+
+    /// 获取提供商竞速指标命令
+    pub async fn get_race_stats<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: AgentIdRequest,
+    ) -> Result<TauriResponse<RaceStats>, String> {
+        Ok(TauriResponse::success(
+            adapter.get_race_stats(&request.agent_id).await,
+        ))
+    }
+
+    /// 分页列出会话命令
+    pub async fn list_sessions<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        query: SessionQuery,
+    ) -> Result<TauriResponse<Page<ChatSession>>, String> {
+        Ok(TauriResponse::from(adapter.list_sessions(query).await))
+    }
+
+    /// 分页获取会话消息命令
+    pub async fn get_session_messages<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: SessionMessagesRequest,
+    ) -> Result<TauriResponse<Page<AgentMessage>>, String> {
+        let result = adapter
+            .get_session_messages(&request.session_id, request.offset, request.limit)
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 获取会话变量命令
+    pub async fn get_session_variables<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: SessionIdRequest,
+    ) -> Result<TauriResponse<BTreeMap<String, String>>, String> {
+        let result = adapter.get_session_variables(&request.session_id).await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 设置会话变量命令
+    pub async fn set_session_variable<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: SessionVariableRequest,
+    ) -> Result<TauriResponse<BTreeMap<String, String>>, String> {
+        let result = adapter
+            .set_session_variable_with_events(
+                &request.session_id,
+                &request.key,
+                request.value.as_deref(),
+            )
+            .await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 删除会话命令
+    pub async fn delete_session<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: SessionIdRequest,
+    ) -> Result<TauriResponse<()>, String> {
+        let result = adapter.delete_session_with_events(&request.session_id).await;
+        Ok(TauriResponse::from(result))
+    }
+
+    /// 恢复会话命令
+    pub async fn restore_session<E: TauriEventEmitter>(
+        adapter: tauri::State<'_, TauriAgentAdapter<E>>,
+        request: SessionIdRequest,
+    ) -> Result<TauriResponse<()>, String> {
+        let result = adapter.restore_session_with_events(&request.session_id).await;
+        Ok(TauriResponse::from(result))
+    }
+
     /// 清除对话历史命令
     pub async fn clear_conversation_history<E: TauriEventEmitter>(
         adapter: tauri::State<'_, TauriAgentAdapter<E>>,
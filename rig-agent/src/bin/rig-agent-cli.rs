@@ -0,0 +1,723 @@
+//! rig-agent 命令行工具
+//!
+//! 直接内嵌 AgentManager，用于脚本化调用与调试：创建 Agent、发送一次性提示、
+//! 在终端中流式对话（支持斜杠命令）、导出对话历史、批量执行提示文件、运行评测测试集、
+//! 导出微调数据集，以及把 Agent 导出为可分享的包文件或从包文件导入。
+//!
+//! Agent 配置保存在状态目录的 `agents.json` 中，对话记录写入同目录的 `sessions.db`。
+
+use clap::{Parser, Subcommand, ValueEnum};
+use rig_agent::{
+    AgentConfig, AgentError, AgentManager, AgentMessage, AgentPackage, AgentResult, AgentRole,
+    DatasetExporter, DatasetFilter, DatasetFormat, EvalSuite, PACKAGE_EXTENSION, PatternScrubber,
+    Repl, RequestPriority, SessionStore, core::ClientRegistry,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+/// Agent 配置文件名
+const AGENTS_FILE: &str = "agents.json";
+
+/// 会话数据库文件名
+const SESSIONS_DB: &str = "sessions.db";
+
+/// rig-agent 命令行工具
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// 状态目录（保存 Agent 配置与会话记录）
+    #[arg(long, default_value = ".rig-agent", env = "RIG_AGENT_STATE_DIR")]
+    state_dir: PathBuf,
+
+    /// 输出详细日志
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// 子命令
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// 子命令
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 创建 Agent
+    Create {
+        /// Agent ID
+        agent_id: String,
+
+        /// 提供商
+        #[arg(long, default_value = "openai")]
+        provider: String,
+
+        /// 模型
+        #[arg(long)]
+        model: Option<String>,
+
+        /// 系统提示
+        #[arg(long)]
+        preamble: Option<String>,
+
+        /// 温度参数
+        #[arg(long)]
+        temperature: Option<f32>,
+
+        /// 最大令牌数
+        #[arg(long)]
+        max_tokens: Option<u32>,
+
+        /// 启用工具
+        #[arg(long)]
+        tools: bool,
+    },
+
+    /// 列出 Agent
+    List,
+
+    /// 删除 Agent（保留其会话记录）
+    Remove {
+        /// Agent ID
+        agent_id: String,
+    },
+
+    /// 发送一次性提示（不保存历史）
+    Prompt {
+        /// Agent ID
+        agent_id: String,
+
+        /// 提示内容
+        message: String,
+    },
+
+    /// 在终端中与 Agent 对话，支持 `/model`、`/tools` 等命令，输入 `/exit` 退出
+    Chat {
+        /// Agent ID
+        agent_id: String,
+
+        /// 关闭流式输出
+        #[arg(long)]
+        no_stream: bool,
+    },
+
+    /// 导出 Agent 的对话历史
+    History {
+        /// Agent ID
+        agent_id: String,
+
+        /// 输出格式
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// 只输出最近的条数
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+
+    /// 批量执行提示文件（每行一条提示，`#` 开头的行被忽略），结果按 JSON Lines 输出
+    Batch {
+        /// Agent ID
+        agent_id: String,
+
+        /// 提示文件路径，`-` 表示标准输入
+        file: PathBuf,
+
+        /// 按对话方式执行（保留上下文并写入会话记录）
+        #[arg(long)]
+        with_history: bool,
+    },
+
+    /// 运行评测测试集（JSON 文件），有用例失败时以非零状态退出
+    Eval {
+        /// 测试集文件路径
+        suite: PathBuf,
+
+        /// 被测 Agent，可重复指定；未指定时使用测试集中的默认 Agent
+        #[arg(long = "agent")]
+        agents: Vec<String>,
+
+        /// 输出格式
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// 将筛选出的会话导出为 JSONL 微调数据集
+    Dataset {
+        /// 仅导出该 Agent 的会话，未指定系统提示时使用其系统提示
+        #[arg(long)]
+        agent: Option<String>,
+
+        /// 仅导出包含该标签的会话，可重复指定
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// 最低反馈得分（-1.0 - 1.0）
+        #[arg(long, allow_hyphen_values = true)]
+        min_score: Option<f64>,
+
+        /// 数据集格式
+        #[arg(long, value_enum, default_value_t = DatasetFormatArg::Openai)]
+        format: DatasetFormatArg,
+
+        /// 写入每条样本开头的系统提示
+        #[arg(long)]
+        system_prompt: Option<String>,
+
+        /// 清洗邮箱、电话等个人信息
+        #[arg(long)]
+        scrub_pii: bool,
+
+        /// 输出文件，默认写到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// 将 Agent 导出为包文件（配置、人设、提示词模板、工具允许列表与知识文件）
+    Export {
+        /// Agent ID
+        agent_id: String,
+
+        /// 包描述
+        #[arg(long)]
+        description: Option<String>,
+
+        /// 附带的知识文件，可重复指定
+        #[arg(long = "knowledge")]
+        knowledge: Vec<PathBuf>,
+
+        /// 输出文件，默认为当前目录下的 `<agent_id>.agentpkg`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// 从包文件导入 Agent
+    Import {
+        /// 包文件路径
+        file: PathBuf,
+
+        /// 新 Agent 的 ID，默认使用包名称
+        #[arg(long = "id")]
+        agent_id: Option<String>,
+
+        /// 知识文件的解压目录，默认为状态目录下的 `knowledge/<agent_id>`
+        #[arg(long)]
+        knowledge_dir: Option<PathBuf>,
+    },
+}
+
+/// 历史输出格式
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    /// 纯文本
+    Text,
+    /// JSON
+    Json,
+}
+
+/// 数据集格式
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DatasetFormatArg {
+    /// OpenAI chat 微调格式
+    Openai,
+    /// 通用 messages 格式（附带反馈与会话信息）
+    Messages,
+}
+
+impl From<DatasetFormatArg> for DatasetFormat {
+    fn from(format: DatasetFormatArg) -> Self {
+        match format {
+            DatasetFormatArg::Openai => DatasetFormat::OpenaiChat,
+            DatasetFormatArg::Messages => DatasetFormat::Messages,
+        }
+    }
+}
+
+/// 批量执行结果
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    /// 行号
+    line: usize,
+    /// 提示内容
+    prompt: String,
+    /// 响应内容
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<String>,
+    /// 错误信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// 命令行运行环境
+struct Cli {
+    state_dir: PathBuf,
+    agents: BTreeMap<String, AgentConfig>,
+    manager: AgentManager,
+    registry: ClientRegistry,
+}
+
+impl Cli {
+    /// 加载状态目录中的 Agent 配置并打开会话数据库
+    async fn load(state_dir: PathBuf) -> AgentResult<Self> {
+        fs::create_dir_all(&state_dir)?;
+
+        let agents_path = state_dir.join(AGENTS_FILE);
+        let agents: BTreeMap<String, AgentConfig> = if agents_path.exists() {
+            serde_json::from_slice(&fs::read(&agents_path)?)?
+        } else {
+            BTreeMap::new()
+        };
+
+        let store = Arc::new(SessionStore::open(state_dir.join(SESSIONS_DB))?);
+        let manager = AgentManager::new(AgentConfig::default()).with_session_store(store);
+        for (agent_id, config) in &agents {
+            manager
+                .create_agent(agent_id.clone(), Some(config.clone()))
+                .await?;
+        }
+
+        Ok(Self {
+            state_dir,
+            agents,
+            manager,
+            registry: ClientRegistry::new(),
+        })
+    }
+
+    /// 保存 Agent 配置
+    fn save_agents(&self) -> AgentResult<()> {
+        let data = serde_json::to_vec_pretty(&self.agents)?;
+        fs::write(self.state_dir.join(AGENTS_FILE), data)?;
+        Ok(())
+    }
+
+    fn ensure_agent(&self, agent_id: &str) -> AgentResult<()> {
+        if self.agents.contains_key(agent_id) {
+            Ok(())
+        } else {
+            Err(AgentError::AgentNotFound(agent_id.to_string()))
+        }
+    }
+
+    async fn run(mut self, command: Command) -> AgentResult<()> {
+        match command {
+            Command::Create {
+                agent_id,
+                provider,
+                model,
+                preamble,
+                temperature,
+                max_tokens,
+                tools,
+            } => {
+                if self.agents.contains_key(&agent_id) {
+                    return Err(AgentError::other(format!("Agent 已存在: {}", agent_id)));
+                }
+
+                let mut config = AgentConfig::default();
+                if let Some(model) = model {
+                    config.model = model;
+                }
+                config.provider = provider;
+                config.preamble = preamble.or(config.preamble);
+                config.temperature = temperature.or(config.temperature);
+                config.max_tokens = max_tokens.or(config.max_tokens);
+                config.enable_tools = tools;
+
+                self.manager
+                    .create_agent(agent_id.clone(), Some(config.clone()))
+                    .await?;
+                self.manager
+                    .start_session(&agent_id, &format!("CLI: {}", agent_id))
+                    .await?;
+                self.agents.insert(agent_id.clone(), config);
+                self.save_agents()?;
+                println!("已创建 Agent: {}", agent_id);
+            }
+            Command::List => {
+                for (agent_id, config) in &self.agents {
+                    println!("{}\t{}\t{}", agent_id, config.provider, config.model);
+                }
+            }
+            Command::Remove { agent_id } => {
+                self.ensure_agent(&agent_id)?;
+                self.agents.remove(&agent_id);
+                self.save_agents()?;
+                println!("已删除 Agent: {}", agent_id);
+            }
+            Command::Prompt { agent_id, message } => {
+                self.ensure_agent(&agent_id)?;
+                let response = self
+                    .manager
+                    .prompt(&self.registry, &agent_id, &message)
+                    .await?;
+                println!("{}", response);
+            }
+            Command::Chat {
+                agent_id,
+                no_stream,
+            } => {
+                self.ensure_agent(&agent_id)?;
+                let stdin = io::stdin();
+                Repl::new(&self.manager, &self.registry, &agent_id)
+                    .with_streaming(!no_stream)
+                    .with_save_dir(self.state_dir.join("history"))
+                    .run(stdin.lock(), io::stdout())
+                    .await?;
+
+                // 保留对话中通过 /model、/provider、/tools 所做的修改
+                let config = self.manager.get_agent_config(&agent_id).await?;
+                self.agents.insert(agent_id, config);
+                self.save_agents()?;
+            }
+            Command::History {
+                agent_id,
+                format,
+                limit,
+            } => {
+                let messages = self.history(&agent_id, limit).await?;
+                match format {
+                    OutputFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&messages)?);
+                    }
+                    OutputFormat::Text => {
+                        for message in &messages {
+                            println!(
+                                "[{}] {}: {}",
+                                message.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                                role_label(&message.role),
+                                message.content
+                            );
+                        }
+                    }
+                }
+            }
+            Command::Batch {
+                agent_id,
+                file,
+                with_history,
+            } => {
+                self.ensure_agent(&agent_id)?;
+                self.batch(&agent_id, &file, with_history).await?;
+            }
+            Command::Eval {
+                suite,
+                agents,
+                format,
+            } => {
+                let suite = EvalSuite::load(&suite)?;
+                for agent_id in agents.iter().chain(&suite.agents).chain(&suite.judge_agent) {
+                    self.ensure_agent(agent_id)?;
+                }
+
+                let report = self
+                    .manager
+                    .run_eval(&self.registry, &suite, &agents)
+                    .await?;
+                match format {
+                    OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                    OutputFormat::Text => println!("{}", report.summary()),
+                }
+                if !report.all_passed() {
+                    return Err(AgentError::other(format!(
+                        "评测未通过：{} 个用例失败",
+                        report.failed
+                    )));
+                }
+            }
+            Command::Dataset {
+                agent,
+                tags,
+                min_score,
+                format,
+                system_prompt,
+                scrub_pii,
+                output,
+            } => {
+                let system_prompt = match (&system_prompt, &agent) {
+                    (Some(_), _) => system_prompt,
+                    (None, Some(agent_id)) => {
+                        self.ensure_agent(agent_id)?;
+                        self.agents[agent_id].preamble.clone()
+                    }
+                    (None, None) => None,
+                };
+
+                let mut exporter = DatasetExporter::new(format.into()).with_filter(DatasetFilter {
+                    agent_id: agent,
+                    tags,
+                    min_feedback_score: min_score,
+                    ..DatasetFilter::default()
+                });
+                if let Some(system_prompt) = system_prompt {
+                    exporter = exporter.with_system_prompt(system_prompt);
+                }
+                if scrub_pii {
+                    exporter = exporter.with_scrubber(PatternScrubber::pii());
+                }
+
+                let export = exporter.export(self.manager.session_store()?).await?;
+                match output {
+                    Some(path) => fs::write(path, &export.jsonl)?,
+                    None => io::stdout().write_all(export.jsonl.as_bytes())?,
+                }
+                info!(
+                    "已导出 {} 个会话，跳过 {} 个",
+                    export.conversations, export.skipped
+                );
+            }
+            Command::Export {
+                agent_id,
+                description,
+                knowledge,
+                output,
+            } => {
+                self.ensure_agent(&agent_id)?;
+                let mut package = AgentPackage::from_config(&agent_id, &self.agents[&agent_id]);
+                if let Some(description) = description {
+                    package = package.with_description(description);
+                }
+                for path in &knowledge {
+                    package.add_knowledge_from(path)?;
+                }
+
+                let output = output.unwrap_or_else(|| {
+                    PathBuf::from(format!("{}.{}", agent_id, PACKAGE_EXTENSION))
+                });
+                package.save(&output)?;
+                println!("已导出 Agent {} 到 {}", agent_id, output.display());
+            }
+            Command::Import {
+                file,
+                agent_id,
+                knowledge_dir,
+            } => {
+                let package = AgentPackage::load(&file)?;
+                let agent_id = agent_id.unwrap_or_else(|| package.name.clone());
+                if self.agents.contains_key(&agent_id) {
+                    return Err(AgentError::other(format!("Agent 已存在: {}", agent_id)));
+                }
+
+                self.manager
+                    .import_agent_package(&package, Some(agent_id.clone()))
+                    .await?;
+                self.manager
+                    .start_session(&agent_id, &format!("CLI: {}", agent_id))
+                    .await?;
+                if !package.knowledge.is_empty() {
+                    let dir = knowledge_dir
+                        .unwrap_or_else(|| self.state_dir.join("knowledge").join(&agent_id));
+                    let written = package.extract_knowledge(&dir)?;
+                    info!("已解压 {} 个知识文件到 {}", written.len(), dir.display());
+                }
+                self.agents.insert(agent_id.clone(), package.to_config());
+                self.save_agents()?;
+                println!("已从 {} 导入 Agent: {}", file.display(), agent_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 从会话数据库读取历史
+    async fn history(
+        &self,
+        agent_id: &str,
+        limit: Option<usize>,
+    ) -> AgentResult<Vec<AgentMessage>> {
+        let store = self.manager.session_store()?;
+        let Some(session) = store.find_session_by_agent(agent_id).await? else {
+            return Ok(Vec::new());
+        };
+
+        let total = store.get_messages(&session.id, 0, 0).await?.total;
+        let offset = limit.map_or(0, |limit| total.saturating_sub(limit));
+        Ok(store
+            .get_messages(&session.id, offset, total - offset)
+            .await?
+            .items)
+    }
+
+    /// 批量执行提示文件
+    async fn batch(&self, agent_id: &str, file: &Path, with_history: bool) -> AgentResult<()> {
+        let content = if file == Path::new("-") {
+            io::read_to_string(io::stdin())?
+        } else {
+            fs::read_to_string(file)?
+        };
+
+        let mut failed = 0;
+        let mut stdout = io::stdout();
+        for (index, line) in content.lines().enumerate() {
+            let prompt = line.trim();
+            if prompt.is_empty() || prompt.starts_with('#') {
+                continue;
+            }
+
+            let result = if with_history {
+                self.manager
+                    .chat_with_priority(&self.registry, agent_id, prompt, RequestPriority::Batch)
+                    .await
+                    .map(|response| response.content)
+            } else {
+                self.manager
+                    .prompt_with_priority(&self.registry, agent_id, prompt, RequestPriority::Batch)
+                    .await
+            };
+
+            let (response, error) = match result {
+                Ok(response) => (Some(response), None),
+                Err(e) => {
+                    failed += 1;
+                    warn!("第 {} 行执行失败: {}", index + 1, e);
+                    (None, Some(e.to_string()))
+                }
+            };
+            let result = BatchResult {
+                line: index + 1,
+                prompt: prompt.to_string(),
+                response,
+                error,
+            };
+            writeln!(stdout, "{}", serde_json::to_string(&result)?)?;
+        }
+
+        info!("批量执行完成，失败 {} 条", failed);
+        Ok(())
+    }
+}
+
+fn role_label(role: &AgentRole) -> &'static str {
+    match role {
+        AgentRole::System => "system",
+        AgentRole::User => "user",
+        AgentRole::Assistant => "assistant",
+        AgentRole::Tool => "tool",
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_max_level(if args.verbose {
+            tracing::Level::DEBUG
+        } else {
+            tracing::Level::WARN
+        })
+        .with_writer(io::stderr)
+        .init();
+
+    let result = match Cli::load(args.state_dir).await {
+        Ok(cli) => cli.run(args.command).await,
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        eprintln!("错误: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+
+        let args = Args::try_parse_from([
+            "rig-agent-cli",
+            "--state-dir",
+            "/tmp/state",
+            "create",
+            "helper",
+            "--provider",
+            "anthropic",
+            "--temperature",
+            "0.2",
+            "--tools",
+        ])
+        .unwrap();
+        assert_eq!(args.state_dir, PathBuf::from("/tmp/state"));
+        match args.command {
+            Command::Create {
+                agent_id,
+                provider,
+                model,
+                temperature,
+                tools,
+                ..
+            } => {
+                assert_eq!(agent_id, "helper");
+                assert_eq!(provider, "anthropic");
+                assert_eq!(model, None);
+                assert_eq!(temperature, Some(0.2));
+                assert!(tools);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        let args = Args::try_parse_from(["rig-agent-cli", "history", "helper", "--format", "json"])
+            .unwrap();
+        assert!(matches!(
+            args.command,
+            Command::History {
+                format: OutputFormat::Json,
+                limit: None,
+                ..
+            }
+        ));
+        assert!(
+            Args::try_parse_from(["rig-agent-cli", "history", "helper", "--format", "xml"])
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agents_persist_in_state_dir() {
+        let state_dir =
+            std::env::temp_dir().join(format!("rig-agent-cli-{}", uuid::Uuid::new_v4()));
+        let create = Command::Create {
+            agent_id: "helper".to_string(),
+            provider: "openai".to_string(),
+            model: Some("gpt-4o-mini".to_string()),
+            preamble: None,
+            temperature: None,
+            max_tokens: None,
+            tools: false,
+        };
+        Cli::load(state_dir.clone())
+            .await
+            .unwrap()
+            .run(create)
+            .await
+            .unwrap();
+
+        // 重新加载时恢复 Agent 配置，新建的 Agent 还没有对话记录
+        let cli = Cli::load(state_dir.clone()).await.unwrap();
+        assert_eq!(cli.agents["helper"].model, "gpt-4o-mini");
+        assert!(cli.history("helper", None).await.unwrap().is_empty());
+        assert!(matches!(
+            cli.ensure_agent("missing"),
+            Err(AgentError::AgentNotFound(_))
+        ));
+        cli.run(Command::Remove {
+            agent_id: "helper".to_string(),
+        })
+        .await
+        .unwrap();
+
+        let cli = Cli::load(state_dir.clone()).await.unwrap();
+        assert!(cli.agents.is_empty());
+        fs::remove_dir_all(&state_dir).ok();
+    }
+}
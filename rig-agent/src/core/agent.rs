@@ -1,37 +1,86 @@
 //! 核心 Agent 实现 - 基于 rig-core
 
+use crate::core::cache::{CacheStats, ResponseCache, ResponseCacheConfig};
+use crate::core::citation::{ToolOutput, ToolProvenance, cite};
+use crate::core::compress::{CompressionReport, compress};
+use crate::core::context::{CALLER_ID_KEY, RequestContext};
+use crate::core::creativity::{CREATIVITY_KEY, CreativityProfile};
+use crate::core::determinism::{RequestFingerprint, request_params};
+use crate::core::feedback::{self, FeedbackRecord, ResponseFeedback};
+use crate::core::finish::{FinishReason, ResponseWarning};
+use crate::core::gc::{AgentEvent, AgentGcConfig};
+use crate::core::keys::{ApiKeyStats, KeyLease, KeyOutcome, KeyPool, KeyPoolConfig};
+use crate::core::language::{DETECTED_LANGUAGE_KEY, detect_language, reply_language_instruction};
+use crate::core::package::AgentPackage;
+use crate::core::paging::{ListPage, PageRequest};
+use crate::core::race::{RACE_WINNER_KEY, RaceConfig, RaceMetrics, RaceOutcome, RaceStats, race};
+use crate::core::replay::{self, ReplayOptions, ReplayReport, ReplayTurn};
+use crate::core::routing::{ModelVariant, RoutingMetrics, VARIANT_KEY, VariantMetrics};
+use crate::core::scheduler::{
+    ProviderQueueMetrics, RequestPermit, RequestPriority, RequestScheduler, SchedulerConfig,
+};
+use crate::core::summarize::{self, DocumentSummary, SectionSummary, SummarizeOptions};
+use crate::core::template::render_template;
+use crate::core::translate::{self, TranslateRequest, Translation};
 use crate::core::types::{
-    AgentConfig, AgentMessage, AgentResponse, ClientConfig, ConversationHistory,
+    AgentConfig, AgentMessage, AgentResponse, AgentRole, ChatOptions, ChatSession, ClientConfig,
+    ConversationHistory, MessageMetadata, StreamingMode, merge_metadata, new_message_id,
 };
 use crate::error::{AgentError, AgentResult};
-use crate::tools::ToolManager;
+use crate::eval::{self, CaseResult, CheckResult, EvalCase, EvalReport, EvalSuite, Expectation};
+use crate::storage::{PurgeReport, RetentionPolicy, SessionStore};
+use crate::tools::{
+    ManagedTool, PermissionDecision, PermissionGrant, PermissionStore, SessionMemory,
+    ToolDefinition, ToolManager,
+};
+use futures::StreamExt;
 use rig::{
-    client::builder::DynClientBuilder,
+    client::{builder::DynClientBuilder, completion::CompletionModelHandle},
     completion::{Chat, Prompt},
     message::Message,
+    streaming::{StreamedAssistantContent, StreamingChat},
 };
-use std::collections::HashMap;
-use tokio::sync::RwLock;
-use tracing::{debug, error, info, instrument};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{RwLock, broadcast};
+use tracing::{debug, error, info, instrument, warn};
+
+/// 启用工具时允许的最大工具调用轮数
+const MAX_TOOL_TURNS: usize = 5;
 
 /// 客户端注册表，管理多个 AI 提供商客户端
+///
+/// rig 的 `DynClientBuilder` 不是 `Send`/`Sync`，因此不保存在注册表中，而是在创建
+/// 客户端时临时构造，使注册表可以通过 `Arc` 在任务间共享
 pub struct ClientRegistry {
-    builder: DynClientBuilder,
     /// 已注册的客户端配置
     clients: HashMap<String, ClientConfig>,
+    /// 各提供商的 API 密钥池，未配置时使用环境变量中的密钥
+    key_pools: HashMap<String, Mutex<KeyPool>>,
+    /// 自动检测到的各提供商是否支持流式响应
+    stream_support: Mutex<HashMap<String, bool>>,
 }
 
 impl ClientRegistry {
-    /// 创建新的客户端注册表
+    /// 创建新的客户端注册表，按环境变量中的 API 密钥注册默认客户端
     pub fn new() -> Self {
-        let mut registry = Self {
-            builder: DynClientBuilder::new(),
-            clients: HashMap::new(),
-        };
+        let mut registry = Self::empty();
         registry.register_default_clients();
         registry
     }
 
+    /// 创建不含任何客户端的注册表，不读取环境变量
+    pub fn empty() -> Self {
+        Self {
+            clients: HashMap::new(),
+            key_pools: HashMap::new(),
+            stream_support: Mutex::new(HashMap::new()),
+        }
+    }
+
     /// 注册默认客户端
     fn register_default_clients(&mut self) {
         // 注册 OpenAI 客户端
@@ -40,8 +89,10 @@ impl ClientRegistry {
                 provider: "openai".to_string(),
                 default_model: "gpt-3.5-turbo".to_string(),
                 api_key: None,
-                base_url: None,
+                // 自定义端点按 OpenAI 兼容协议连接
+                base_url: std::env::var("OPENAI_BASE_URL").ok(),
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             };
             let _ = self.register_openai(config);
         }
@@ -54,6 +105,7 @@ impl ClientRegistry {
                 api_key: None,
                 base_url: None,
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             };
             let _ = self.register_anthropic(config);
         }
@@ -66,6 +118,7 @@ impl ClientRegistry {
                 api_key: None,
                 base_url: None,
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             };
             let _ = self.register_gemini(config);
         }
@@ -74,10 +127,41 @@ impl ClientRegistry {
     /// 注册客户端
     pub fn register_client(&mut self, provider: &str, config: ClientConfig) -> AgentResult<()> {
         info!("注册 {} 客户端: {}", provider, config.default_model);
+        // 配置可能已变化，重新检测流式支持
+        self.stream_support.get_mut().unwrap().remove(provider);
         self.clients.insert(provider.to_string(), config);
         Ok(())
     }
 
+    /// 提供商配置的流式响应模式，未注册时为自动
+    pub fn streaming_mode(&self, provider: &str) -> StreamingMode {
+        self.clients
+            .get(provider)
+            .map(|config| config.streaming)
+            .unwrap_or_default()
+    }
+
+    /// 自动检测到的流式支持情况，尚未检测时为空
+    pub fn streaming_support(&self, provider: &str) -> Option<bool> {
+        self.stream_support.lock().unwrap().get(provider).copied()
+    }
+
+    /// 本次调用是否应该流式进行
+    fn should_stream(&self, provider: &str) -> bool {
+        match self.streaming_mode(provider) {
+            StreamingMode::Auto => self.streaming_support(provider) != Some(false),
+            StreamingMode::Always => true,
+            StreamingMode::Never => false,
+        }
+    }
+
+    fn record_streaming_support(&self, provider: &str, supported: bool) {
+        self.stream_support
+            .lock()
+            .unwrap()
+            .insert(provider.to_string(), supported);
+    }
+
     /// 注册 OpenAI 客户端
     pub fn register_openai(&mut self, config: ClientConfig) -> AgentResult<()> {
         self.register_client("openai", config)
@@ -98,11 +182,131 @@ impl ClientRegistry {
         self.register_client("cohere", config)
     }
 
+    /// 为提供商配置 API 密钥池（替换已有的池），返回各密钥的 ID
+    pub fn register_api_keys<I, S>(
+        &mut self,
+        provider: &str,
+        keys: I,
+        config: KeyPoolConfig,
+    ) -> AgentResult<Vec<String>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if !self.clients.contains_key(provider) {
+            return Err(AgentError::config(format!(
+                "提供商 {} 未注册，请先注册客户端",
+                provider
+            )));
+        }
+
+        let mut pool = KeyPool::new(config);
+        let ids = keys.into_iter().map(|key| pool.add(key)).collect();
+        info!("为 {} 配置 {} 个 API 密钥", provider, pool.len());
+        self.key_pools
+            .insert(provider.to_string(), Mutex::new(pool));
+        Ok(ids)
+    }
+
+    /// 向提供商的密钥池添加密钥，没有池时按默认配置创建
+    pub fn add_api_key<S: Into<String>>(&mut self, provider: &str, key: S) -> AgentResult<String> {
+        if !self.clients.contains_key(provider) {
+            return Err(AgentError::config(format!(
+                "提供商 {} 未注册，请先注册客户端",
+                provider
+            )));
+        }
+
+        let pool = self
+            .key_pools
+            .entry(provider.to_string())
+            .or_insert_with(|| Mutex::new(KeyPool::new(KeyPoolConfig::default())));
+        Ok(pool.get_mut().unwrap().add(key))
+    }
+
+    /// 从提供商的密钥池移除密钥
+    pub fn remove_api_key(&mut self, provider: &str, key_id: &str) -> bool {
+        self.key_pools
+            .get_mut(provider)
+            .is_some_and(|pool| pool.get_mut().unwrap().remove(key_id))
+    }
+
+    /// 提供商各密钥的用量统计
+    pub fn api_key_stats(&self, provider: &str) -> Vec<ApiKeyStats> {
+        self.key_pools
+            .get(provider)
+            .map(|pool| pool.lock().unwrap().stats())
+            .unwrap_or_default()
+    }
+
+    /// 从密钥池分配密钥；没有配置密钥池时返回空，全部密钥都在冷却中时返回限流错误
+    pub fn acquire_api_key(&self, provider: &str) -> AgentResult<Option<(KeyLease, String)>> {
+        let Some(pool) = self.key_pools.get(provider) else {
+            return Ok(None);
+        };
+        let mut pool = pool.lock().unwrap();
+        if pool.is_empty() {
+            return Ok(None);
+        }
+
+        let (key_id, key) = pool.acquire(chrono::Utc::now()).ok_or_else(|| {
+            warn!("{} 的 API 密钥全部处于冷却中", provider);
+            AgentError::RateLimit
+        })?;
+        let lease = KeyLease {
+            provider: provider.to_string(),
+            key_id,
+        };
+        Ok(Some((lease, key)))
+    }
+
+    /// 反馈模型调用结果，被限流的密钥进入冷却
+    pub fn report_api_key<T>(&self, lease: Option<&KeyLease>, result: &AgentResult<T>) {
+        let Some(lease) = lease else {
+            return;
+        };
+        let outcome = KeyOutcome::of(result);
+        if outcome == KeyOutcome::RateLimited {
+            warn!(
+                "{} 的密钥 {} 被限流，进入冷却",
+                lease.provider, lease.key_id
+            );
+        }
+        if let Some(pool) = self.key_pools.get(&lease.provider) {
+            pool.lock()
+                .unwrap()
+                .record(&lease.key_id, outcome, chrono::Utc::now());
+        }
+    }
+
     /// 创建 Agent 实例
     pub fn create_agent<'a>(
         &'a self,
         config: &'a AgentConfig,
     ) -> AgentResult<rig::agent::Agent<rig::client::completion::CompletionModelHandle<'a>>> {
+        self.create_agent_with_tools(config, Vec::new())
+    }
+
+    /// 创建挂载指定工具的 Agent 实例
+    pub fn create_agent_with_tools<'a>(
+        &'a self,
+        config: &'a AgentConfig,
+        tools: Vec<ManagedTool>,
+    ) -> AgentResult<rig::agent::Agent<rig::client::completion::CompletionModelHandle<'a>>> {
+        self.create_agent_with_lease(config, tools)
+            .map(|(agent, _)| agent)
+    }
+
+    /// 创建挂载指定工具的 Agent 实例，配置了密钥池时同时返回所用的密钥，
+    /// 调用结束后应通过 [`ClientRegistry::report_api_key`] 反馈结果
+    pub fn create_agent_with_lease<'a>(
+        &'a self,
+        config: &'a AgentConfig,
+        tools: Vec<ManagedTool>,
+    ) -> AgentResult<(
+        rig::agent::Agent<rig::client::completion::CompletionModelHandle<'a>>,
+        Option<KeyLease>,
+    )> {
         let provider = &config.provider;
 
         info!("创建 Agent 实例: {} - {}", provider, config.model);
@@ -115,29 +319,61 @@ impl ClientRegistry {
             )));
         }
 
-        // 使用构建器
-        let mut agent_builder = self
-            .builder
-            .agent(provider, &config.model)
-            .map_err(|e| AgentError::config(format!("创建 {} 客户端失败: {}", provider, e)))?;
+        // 使用构建器：优先使用密钥池分配的密钥，其次是客户端配置中的密钥，都没有时读取环境变量；
+        // 配置了基础 URL 的客户端直接连接该端点
+        let lease = self.acquire_api_key(provider)?;
+        let client = &self.clients[provider];
+        let key = match &lease {
+            Some((_, key)) => Some(key.clone()),
+            None => client.api_key.clone(),
+        };
+        let mut agent_builder = match (&client.base_url, key) {
+            (Some(base_url), key) => {
+                // 没有配置密钥时与内置客户端一样读取环境变量
+                let key = key
+                    .or_else(|| {
+                        std::env::var(format!("{}_API_KEY", client.provider.to_uppercase())).ok()
+                    })
+                    .unwrap_or_default();
+                endpoint_agent(client, base_url, &key, &config.model)
+            }
+            (None, Some(key)) => DynClientBuilder::new()
+                .agent_with_api_key_val(provider, &config.model, key)
+                .map_err(|e| e.to_string()),
+            (None, None) => DynClientBuilder::new()
+                .agent(provider, &config.model)
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| AgentError::config(format!("创建 {} 客户端失败: {}", provider, e)))?;
 
         // 应用配置参数
         if let Some(preamble) = &config.preamble {
             agent_builder = agent_builder.preamble(preamble);
         }
 
-        if let Some(temperature) = config.temperature {
+        // 创造性配置的温度覆盖配置中的温度，其余采样参数与种子只发给支持它们的提供商；
+        // 确定性模式下温度为 0
+        let params = request_params(config);
+        if let Some(temperature) = params.temperature {
             agent_builder = agent_builder.temperature(temperature as f64);
         }
+        if let Some(additional) = params.additional {
+            agent_builder = agent_builder.additional_params(additional);
+        }
 
         if let Some(max_tokens) = config.max_tokens {
             agent_builder = agent_builder.max_tokens(max_tokens as u64);
         }
 
+        for tool in tools {
+            debug!("挂载工具: {}", tool.definition().qualified_name());
+            agent_builder = agent_builder.tool(tool);
+        }
+
         let agent = agent_builder.build();
         info!("Agent 实例创建成功: {} - {}", provider, config.model);
 
-        Ok(agent)
+        Ok((agent, lease.map(|(lease, _)| lease)))
     }
 
     /// 获取已注册的客户端列表
@@ -154,6 +390,25 @@ impl ClientRegistry {
     pub fn get_client_config(&self, provider: &str) -> Option<&ClientConfig> {
         self.clients.get(provider)
     }
+
+    /// 使用指定提供商的嵌入模型计算文本向量
+    pub async fn embed(&self, provider: &str, model: &str, text: &str) -> AgentResult<Vec<f64>> {
+        if !self.clients.contains_key(provider) {
+            return Err(AgentError::config(format!(
+                "提供商 {} 未注册，请先注册客户端",
+                provider
+            )));
+        }
+
+        let embedding_model = DynClientBuilder::new()
+            .embeddings(provider, model)
+            .map_err(|e| AgentError::config(format!("创建 {} 嵌入模型失败: {}", provider, e)))?;
+        let embedding = embedding_model
+            .embed_text(text)
+            .await
+            .map_err(|e| AgentError::model(format!("计算嵌入向量失败: {}", e)))?;
+        Ok(embedding.vec)
+    }
 }
 
 impl Default for ClientRegistry {
@@ -162,32 +417,617 @@ impl Default for ClientRegistry {
     }
 }
 
+/// 新消息的 ID、时间与元数据
+struct MessageMeta {
+    id: String,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    metadata: MessageMetadata,
+}
+
+impl MessageMeta {
+    fn new(id: String, metadata: MessageMetadata) -> Self {
+        Self {
+            id,
+            timestamp: chrono::Utc::now(),
+            metadata,
+        }
+    }
+}
+
+/// 为配置了基础 URL 的客户端构建 Agent：`anthropic` 使用 Anthropic 协议，
+/// 其余提供商按 OpenAI 兼容的 Chat Completions 协议连接
+fn endpoint_agent<'a>(
+    client: &ClientConfig,
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+) -> Result<rig::agent::AgentBuilder<CompletionModelHandle<'a>>, String> {
+    use rig::providers::{anthropic, openai};
+
+    let model: Arc<dyn rig::completion::CompletionModelDyn> = match client.provider.as_str() {
+        "anthropic" => {
+            let endpoint = anthropic::Client::builder(api_key)
+                .base_url(base_url)
+                .build()
+                .map_err(|e| e.to_string())?;
+            Arc::new(anthropic::completion::CompletionModel::new(endpoint, model))
+        }
+        _ => {
+            let endpoint = openai::Client::builder(api_key)
+                .base_url(base_url)
+                .build()
+                .map_err(|e| e.to_string())?;
+            Arc::new(openai::completion::CompletionModel::new(endpoint, model))
+        }
+    };
+    Ok(rig::agent::AgentBuilder::new(CompletionModelHandle {
+        inner: model,
+    }))
+}
+
+/// 将 rig Message 转换为 AgentMessage，只保留文本内容
+fn convert_message(message: &Message) -> AgentMessage {
+    match message {
+        Message::User { content, .. } => {
+            // 提取文本内容
+            let text = content
+                .iter()
+                .filter_map(|c| match c {
+                    rig::message::UserContent::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            AgentMessage::user(text)
+        }
+        Message::Assistant { content, .. } => {
+            // 提取文本内容
+            let text = content
+                .iter()
+                .filter_map(|c| match c {
+                    rig::message::AssistantContent::Text(text) => Some(text.text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            AgentMessage::assistant(text)
+        }
+    }
+}
+
 /// Agent 信息结构体
+///
+/// 对话历史与转换好的消息都是写时复制的：读取时只复制 `Arc`，
+/// 只有在快照仍被持有时追加或修改才会复制整个列表。克隆得到的就是这样的快照
+#[derive(Clone)]
 pub struct Agent {
     id: String,
     config: AgentConfig,
-    conversation_history: Vec<Message>,
+    /// 同一 Agent 的对话轮次依次进行，调用模型期间不占用 Agent 表的锁
+    turn: Arc<tokio::sync::Mutex<()>>,
+    /// 发给模型的对话历史
+    conversation_history: Arc<Vec<Message>>,
+    /// 与 `conversation_history` 一一对应、追加时即转换好的消息，带有 ID、时间与元数据
+    messages: Arc<Vec<AgentMessage>>,
     created_at: chrono::DateTime<chrono::Utc>,
     last_activity: chrono::DateTime<chrono::Utc>,
 }
 
+impl Agent {
+    /// 追加消息及其元数据
+    fn push_message(&mut self, message: Message, meta: MessageMeta) {
+        let mut converted = convert_message(&message);
+        converted.id = meta.id;
+        converted.timestamp = meta.timestamp;
+        converted.metadata = meta.metadata;
+        Arc::make_mut(&mut self.conversation_history).push(message);
+        Arc::make_mut(&mut self.messages).push(converted);
+    }
+
+    /// 只保留最近 `limit` 条消息，返回丢弃的消息数量
+    fn trim_history(&mut self, limit: usize) -> usize {
+        if self.conversation_history.len() <= limit {
+            return 0;
+        }
+        let excess = self.conversation_history.len() - limit;
+        Arc::make_mut(&mut self.conversation_history).drain(0..excess);
+        Arc::make_mut(&mut self.messages).drain(0..excess);
+        excess
+    }
+
+    /// 发给模型的对话历史。配置了提示词压缩且发往的模型在压缩范围内时压缩过长的历史，
+    /// 同时返回压缩报告；保存的对话历史不变
+    fn history_for(
+        &self,
+        config: &AgentConfig,
+        query: &str,
+    ) -> (Vec<Message>, Option<CompressionReport>) {
+        let Some(compression) = config
+            .compression
+            .as_ref()
+            .filter(|compression| compression.applies_to(config))
+            .and_then(|compression| compress(&self.messages, query, compression))
+        else {
+            return (self.conversation_history.to_vec(), None);
+        };
+        let history = compression
+            .kept
+            .into_iter()
+            .map(|(index, text)| match text {
+                Some(text) if self.messages[index].role == AgentRole::User => Message::user(text),
+                Some(text) => Message::assistant(text),
+                None => self.conversation_history[index].clone(),
+            })
+            .collect();
+        (history, Some(compression.report))
+    }
+
+    /// 清空对话历史
+    fn clear_history(&mut self) {
+        self.conversation_history = Arc::default();
+        self.messages = Arc::default();
+    }
+
+    /// 丢弃早于 `cutoff` 的消息，返回丢弃的消息数量
+    fn forget_before(&mut self, cutoff: chrono::DateTime<chrono::Utc>) -> usize {
+        let expired = self
+            .messages
+            .iter()
+            .take_while(|message| message.timestamp < cutoff)
+            .count();
+        if expired > 0 {
+            Arc::make_mut(&mut self.conversation_history).drain(0..expired);
+            Arc::make_mut(&mut self.messages).drain(0..expired);
+        }
+        expired
+    }
+
+    /// 对话历史中是否有该调用方发出的消息
+    fn has_caller(&self, caller_id: &str) -> bool {
+        self.messages.iter().any(|message| {
+            message
+                .metadata
+                .get(CALLER_ID_KEY)
+                .and_then(|id| id.as_str())
+                == Some(caller_id)
+        })
+    }
+
+    /// 按 ID 修改消息的元数据，返回修改后的元数据
+    fn update_metadata(
+        &mut self,
+        message_id: &str,
+        update: impl FnOnce(&mut MessageMetadata),
+    ) -> Option<MessageMetadata> {
+        // 先查找，找不到时不复制被快照共享的列表
+        let index = self
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)?;
+        let message = &mut Arc::make_mut(&mut self.messages)[index];
+        update(&mut message.metadata);
+        Some(message.metadata.clone())
+    }
+
+    /// 对话历史中点赞与点踩的回复数
+    fn feedback_counts(&self) -> (usize, usize) {
+        self.messages
+            .iter()
+            .filter_map(|message| ResponseFeedback::from_metadata(&message.metadata)?.rating)
+            .fold((0, 0), |(positive, negative), rating| {
+                if rating.is_positive() {
+                    (positive + 1, negative)
+                } else {
+                    (positive, negative + 1)
+                }
+            })
+    }
+
+    fn summary(&self) -> AgentSummary {
+        AgentSummary {
+            id: self.id.clone(),
+            provider: self.config.provider.clone(),
+            model: self.config.model.clone(),
+            message_count: self.conversation_history.len(),
+            created_at: self.created_at,
+            last_activity: self.last_activity,
+        }
+    }
+
+    /// 转换好的消息的快照，与 Agent 共享内存，之后的修改不影响快照
+    fn history_snapshot(&self) -> Arc<Vec<AgentMessage>> {
+        self.messages.clone()
+    }
+
+    /// 转换为对话历史
+    fn to_history(&self) -> ConversationHistory {
+        let messages = self.messages.as_ref().clone();
+        let total_tokens = messages.iter().map(|msg| msg.content.len() as u64).sum();
+
+        ConversationHistory {
+            agent_id: self.id.clone(),
+            messages,
+            total_messages: self.conversation_history.len(),
+            total_tokens: Some(total_tokens),
+            created_at: self.created_at,
+            last_activity: self.last_activity,
+        }
+    }
+}
+
 /// Agent 管理器，负责创建和管理 Agent 实例
 pub struct AgentManager {
     agents: RwLock<HashMap<String, Agent>>,
     default_config: AgentConfig,
     tool_manager: ToolManager,
+    events: broadcast::Sender<AgentEvent>,
+    scheduler: RequestScheduler,
+    cache: ResponseCache,
+    routing: RoutingMetrics,
+    races: RaceMetrics,
+    session_store: Option<Arc<SessionStore>>,
 }
 
 impl AgentManager {
     /// 创建新的 Agent 管理器
     pub fn new(default_config: AgentConfig) -> Self {
         let tool_manager = ToolManager::new();
+        let (events, _) = broadcast::channel(64);
 
         Self {
             default_config,
             agents: RwLock::new(HashMap::new()),
             tool_manager,
+            events,
+            scheduler: RequestScheduler::default(),
+            cache: ResponseCache::default(),
+            routing: RoutingMetrics::default(),
+            races: RaceMetrics::default(),
+            session_store: None,
+        }
+    }
+
+    /// 设置会话存储，设置后 Agent 的聊天记录会写入其关联的会话
+    pub fn with_session_store(mut self, store: Arc<SessionStore>) -> Self {
+        self.session_store = Some(store);
+        self
+    }
+
+    /// 设置工具授权存储，例如 `PermissionStore::open` 加载的持久化授权
+    pub fn with_permission_store(mut self, store: PermissionStore) -> Self {
+        self.tool_manager.set_permission_store(store);
+        self
+    }
+
+    /// 替换会话存储
+    pub fn set_session_store(&mut self, store: Option<Arc<SessionStore>>) {
+        self.session_store = store;
+    }
+
+    /// 获取会话存储，未配置时返回配置错误
+    pub fn session_store(&self) -> AgentResult<&Arc<SessionStore>> {
+        self.session_store
+            .as_ref()
+            .ok_or_else(|| AgentError::config("未配置会话存储"))
+    }
+
+    /// 为 Agent 创建并关联新会话
+    pub async fn start_session(&self, agent_id: &str, title: &str) -> AgentResult<ChatSession> {
+        let store = self.session_store()?;
+        let config = self.get_agent_config(agent_id).await?;
+        // 开始新会话即结束之前的会话
+        self.end_tool_states(agent_id).await;
+        let session = ChatSession::new(title.to_string(), config.model).with_agent_id(agent_id);
+        store.create_session(session).await
+    }
+
+    /// 获取 Agent 关联的会话，未配置会话存储或查询失败时返回 `None`
+    async fn agent_session(&self, agent_id: &str) -> Option<ChatSession> {
+        let store = self.session_store.as_ref()?;
+        match store.find_session_by_agent(agent_id).await {
+            Ok(session) => session,
+            Err(e) => {
+                warn!("查询 Agent {} 的关联会话失败: {}", agent_id, e);
+                None
+            }
+        }
+    }
+
+    /// 获取会话变量
+    pub async fn get_session_variables(
+        &self,
+        session_id: &str,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        self.session_store()?.get_variables(session_id).await
+    }
+
+    /// 设置会话变量，返回更新后的全部变量
+    pub async fn set_session_variable(
+        &self,
+        session_id: &str,
+        key: &str,
+        value: &str,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        self.session_store()?
+            .set_variable(session_id, key, Some(value.to_string()))
+            .await
+    }
+
+    /// 移除会话变量，返回更新后的全部变量
+    pub async fn remove_session_variable(
+        &self,
+        session_id: &str,
+        key: &str,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        self.session_store()?
+            .set_variable(session_id, key, None)
+            .await
+    }
+
+    /// 将一轮对话写入 Agent 关联的会话，没有关联会话时忽略
+    async fn record_session_turn(&self, agent_id: &str, messages: Vec<AgentMessage>) {
+        let Some(store) = &self.session_store else {
+            return;
+        };
+
+        let result = match store.find_session_by_agent(agent_id).await {
+            Ok(Some(session)) => store.append_messages(&session.id, messages).await,
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            warn!("写入 Agent {} 的会话记录失败: {}", agent_id, e);
+        }
+    }
+
+    /// 按保留策略删除过期的会话与内存中的对话历史
+    pub async fn apply_retention(&self, policy: &RetentionPolicy) -> AgentResult<PurgeReport> {
+        let mut report = PurgeReport::default();
+        let Some(cutoff) = policy.conversation_cutoff() else {
+            return Ok(report);
+        };
+
+        if let Some(store) = &self.session_store {
+            report.sessions = store.purge_before(cutoff).await?;
+        }
+        for agent in self.agents.write().await.values_mut() {
+            report.messages += agent.forget_before(cutoff);
+        }
+        if report.total() > 0 {
+            info!(
+                "按保留策略删除 {} 个会话、{} 条消息",
+                report.sessions, report.messages
+            );
+        }
+        Ok(report)
+    }
+
+    /// 删除调用方的全部数据：包含其消息的会话与对话历史，并清空响应缓存
+    ///
+    /// 调用方 ID 取自消息元数据（见 [`RequestContext`]），没有记录调用方的消息无法定位
+    pub async fn purge_user_data(&self, caller_id: &str) -> AgentResult<PurgeReport> {
+        let mut report = PurgeReport::default();
+        if let Some(store) = &self.session_store {
+            report.sessions = store.purge_caller(caller_id).await?;
+        }
+        for agent in self.agents.write().await.values_mut() {
+            if agent.has_caller(caller_id) {
+                report.messages += agent.messages.len();
+                agent.clear_history();
+            }
         }
+        // 缓存的回复无法对应到调用方，全部清空
+        self.cache.clear();
+        info!(
+            "删除调用方 {} 的数据：{} 个会话、{} 条消息",
+            caller_id, report.sessions, report.messages
+        );
+        Ok(report)
+    }
+
+    /// 设置响应缓存配置
+    pub fn with_cache_config(mut self, config: ResponseCacheConfig) -> Self {
+        self.cache = ResponseCache::new(config);
+        self
+    }
+
+    /// 获取响应缓存统计
+    pub fn get_cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// 清空响应缓存
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// 查找缓存的响应，返回缓存键（不可缓存时为空）与命中的响应。
+    /// 挂载工具的 Agent 结果依赖外部数据（天气、搜索等），不参与缓存
+    async fn lookup_cache(
+        &self,
+        agent_id: &str,
+        message: &str,
+        with_history: bool,
+        options: &ChatOptions,
+        context: &RequestContext,
+    ) -> AgentResult<(Option<u64>, Option<String>)> {
+        if !self.cache.is_enabled() {
+            return Ok((None, None));
+        }
+
+        let session = self.agent_session(agent_id).await;
+        let agents = self.agents.read().await;
+        let agent_data = agents
+            .get(agent_id)
+            .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
+        // 挂载工具或启用 A/B 路由时不使用缓存，避免混淆各变体的指标；
+        // 确定性模式用于比较模型输出，总是调用模型
+        if agent_data.config.deterministic
+            || agent_data.config.ab_route.is_some()
+            || !self
+                .rig_tools_for(agent_id, &agent_data.config, session.as_ref())
+                .is_empty()
+        {
+            return Ok((None, None));
+        }
+        if options.bypass_cache {
+            self.cache.record_bypass();
+            return Ok((None, None));
+        }
+
+        let history: &[Message] = if with_history {
+            &agent_data.conversation_history
+        } else {
+            &[]
+        };
+        // 与实际请求使用相同的回复语言，避免缓存键与请求的配置不一致
+        let reply_language = reply_language(message, context);
+        let config = effective_config(
+            &agent_data.config,
+            session.as_ref(),
+            reply_language.as_deref(),
+            options.creativity,
+        );
+        let key = ResponseCache::turn_key(&config, history, &Message::user(message));
+        Ok((Some(key), self.cache.get(key)))
+    }
+
+    /// 设置提供商请求调度配置
+    pub fn with_scheduler_config(mut self, config: SchedulerConfig) -> Self {
+        self.scheduler = RequestScheduler::new(config);
+        self
+    }
+
+    /// 获取各提供商的请求排队指标
+    pub fn get_scheduler_metrics(&self) -> Vec<ProviderQueueMetrics> {
+        self.scheduler.get_metrics()
+    }
+
+    /// 按 Agent 所用提供商获取执行名额
+    async fn acquire_for_agent(
+        &self,
+        agent_id: &str,
+        priority: RequestPriority,
+    ) -> AgentResult<RequestPermit> {
+        let provider = {
+            let agents = self.agents.read().await;
+            agents
+                .get(agent_id)
+                .map(|agent| agent.config.provider.clone())
+                .ok_or_else(|| {
+                    error!("Agent 不存在: {}", agent_id);
+                    AgentError::AgentNotFound(agent_id.to_string())
+                })?
+        };
+        Ok(self.scheduler.acquire(&provider, priority).await)
+    }
+
+    /// 按 Agent 的 A/B 路由策略选择本次请求使用的变体，未设置策略时返回 `None`；
+    /// 确定性模式下总是使用对照变体
+    async fn route_variant(&self, agent_id: &str) -> Option<ModelVariant> {
+        let agents = self.agents.read().await;
+        let config = &agents.get(agent_id)?.config;
+        let route = config.ab_route.as_ref()?;
+        let variant = if config.deterministic {
+            route.control.clone()
+        } else {
+            route.pick().clone()
+        };
+        debug!("Agent {} 本次请求路由到变体 {}", agent_id, variant.name);
+        Some(variant)
+    }
+
+    /// 按 Agent 的竞速策略生成本次请求挑战者的配置。挂载工具时（工具调用有副作用，不能执行两次）、
+    /// 非交互式请求或提示词超过预算时不竞速；挑战者与主模型相同时没有竞速的意义，
+    /// 确定性模式下也不竞速
+    fn challenger_config<'a>(
+        &self,
+        agent_id: &str,
+        race: Option<&'a RaceConfig>,
+        config: &AgentConfig,
+        message: &str,
+        tool_count: usize,
+        priority: RequestPriority,
+    ) -> Option<(&'a RaceConfig, AgentConfig)> {
+        let race = race?;
+        if config.deterministic {
+            return None;
+        }
+        if tool_count > 0
+            || priority != RequestPriority::Interactive
+            || message.chars().count() > race.max_prompt_chars
+        {
+            self.races.record_skip(agent_id);
+            return None;
+        }
+        let mut challenger = config.clone();
+        race.challenger.apply(&mut challenger);
+        (challenger.provider != config.provider || challenger.model != config.model)
+            .then_some((race, challenger))
+    }
+
+    /// 占用执行名额；路由到变体时按变体的提供商排队
+    async fn acquire_for_request(
+        &self,
+        agent_id: &str,
+        variant: Option<&ModelVariant>,
+        priority: RequestPriority,
+    ) -> AgentResult<RequestPermit> {
+        match variant {
+            Some(variant) => Ok(self.scheduler.acquire(&variant.provider, priority).await),
+            None => self.acquire_for_agent(agent_id, priority).await,
+        }
+    }
+
+    /// 订阅 Agent 管理器事件
+    pub fn subscribe_events(&self) -> broadcast::Receiver<AgentEvent> {
+        self.events.subscribe()
+    }
+
+    /// 回收空闲超过 `idle_ttl` 的 Agent，返回被回收的 Agent ID
+    pub async fn reap_idle_agents(&self, config: &AgentGcConfig) -> Vec<String> {
+        let now = chrono::Utc::now();
+        let ttl = chrono::Duration::from_std(config.idle_ttl).unwrap_or(chrono::Duration::MAX);
+
+        let reaped: Vec<Agent> = {
+            let mut agents = self.agents.write().await;
+            let idle_ids: Vec<String> = agents
+                .iter()
+                .filter(|(id, agent)| {
+                    !config.exempt.contains(id) && now - agent.last_activity >= ttl
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            idle_ids.iter().filter_map(|id| agents.remove(id)).collect()
+        };
+
+        let mut reaped_ids = Vec::with_capacity(reaped.len());
+        for agent in reaped {
+            let history_path = match &config.persist_dir {
+                Some(dir) => match persist_history(dir, &agent.to_history()).await {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        warn!("保存 Agent {} 的对话历史失败: {}", agent.id, e);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            self.end_tool_states(&agent.id).await;
+            let idle_seconds = (now - agent.last_activity).num_seconds();
+            info!("回收空闲 Agent: {}，空闲 {} 秒", agent.id, idle_seconds);
+            let _ = self.events.send(AgentEvent::Reaped {
+                agent_id: agent.id.clone(),
+                idle_seconds,
+                message_count: agent.conversation_history.len(),
+                history_path,
+            });
+            reaped_ids.push(agent.id);
+        }
+
+        reaped_ids
     }
 
     /// 创建新的 Agent
@@ -209,7 +1049,9 @@ impl AgentManager {
             Agent {
                 id: agent_id.clone(),
                 config: agent_config,
-                conversation_history: Vec::new(),
+                turn: Arc::default(),
+                conversation_history: Arc::default(),
+                messages: Arc::default(),
                 created_at: chrono::Utc::now(),
                 last_activity: chrono::Utc::now(),
             },
@@ -221,8 +1063,11 @@ impl AgentManager {
 
     /// 删除 Agent
     pub async fn remove_agent(&self, agent_id: &str) -> bool {
-        let mut agents = self.agents.write().await;
-        agents.remove(agent_id).is_some()
+        let removed = self.agents.write().await.remove(agent_id).is_some();
+        if removed {
+            self.end_tool_states(agent_id).await;
+        }
+        removed
     }
 
     /// 获取 Agent 列表
@@ -240,77 +1085,470 @@ impl AgentManager {
             .collect()
     }
 
-    /// 发送聊天消息
-    #[instrument(skip(self, registry, message), fields(agent_id = %agent_id, message_len = message.len()))]
+    /// 分页获取 Agent 摘要，时间范围按排序字段的时间过滤（按 ID 排序时使用创建时间）
+    pub async fn list_agents_page(
+        &self,
+        request: &PageRequest,
+        sort_by: AgentSortKey,
+    ) -> ListPage<AgentSummary> {
+        let timestamp = |summary: &AgentSummary| match sort_by {
+            AgentSortKey::LastActivity => summary.last_activity,
+            AgentSortKey::Id | AgentSortKey::CreatedAt => summary.created_at,
+        };
+
+        let agents = self.agents.read().await;
+        let mut summaries: Vec<AgentSummary> = agents
+            .values()
+            .map(Agent::summary)
+            .filter(|summary| request.contains(timestamp(summary)))
+            .collect();
+        match sort_by {
+            AgentSortKey::Id => summaries.sort_by(|a, b| a.id.cmp(&b.id)),
+            AgentSortKey::CreatedAt | AgentSortKey::LastActivity => summaries.sort_by(|a, b| {
+                timestamp(a)
+                    .cmp(&timestamp(b))
+                    .then_with(|| a.id.cmp(&b.id))
+            }),
+        }
+        request.page(summaries)
+    }
+
+    /// 发送聊天消息（交互式优先级）
     pub async fn chat(
         &self,
         registry: &ClientRegistry,
         agent_id: &str,
         message: &str,
     ) -> AgentResult<AgentResponse> {
+        self.chat_with_options(registry, agent_id, message, ChatOptions::default())
+            .await
+    }
+
+    /// 按指定优先级发送聊天消息
+    pub async fn chat_with_priority(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        priority: RequestPriority,
+    ) -> AgentResult<AgentResponse> {
+        let options = ChatOptions::default().with_priority(priority);
+        self.chat_with_options(registry, agent_id, message, options)
+            .await
+    }
+
+    /// 按请求选项（优先级、是否跳过缓存）发送聊天消息
+    #[instrument(skip(self, registry, message), fields(agent_id = %agent_id, message_len = message.len()))]
+    pub async fn chat_with_options(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+    ) -> AgentResult<AgentResponse> {
+        self.chat_inner(registry, agent_id, message, options, None)
+            .await
+    }
+
+    /// 以流式方式发送聊天消息，每收到一段文本调用一次 `on_chunk`，结束后返回完整响应。
+    /// 挂载工具或命中缓存时无法逐段输出，完整响应会作为一段回调
+    #[instrument(skip(self, registry, message, on_chunk), fields(agent_id = %agent_id, message_len = message.len()))]
+    pub async fn chat_stream<F>(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+        mut on_chunk: F,
+    ) -> AgentResult<AgentResponse>
+    where
+        F: FnMut(&str) + Send,
+    {
+        self.chat_inner(registry, agent_id, message, options, Some(&mut on_chunk))
+            .await
+    }
+
+    async fn chat_inner(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        mut options: ChatOptions,
+        on_chunk: Option<&mut (dyn FnMut(&str) + Send)>,
+    ) -> AgentResult<AgentResponse> {
+        let context = RequestContext::resolve(options.context.take());
         let start_time = std::time::Instant::now();
+        let result = context
+            .clone()
+            .scope(self.chat_in_context(registry, agent_id, message, options, &context, on_chunk))
+            .await;
+        audit_result(&context, "chat", agent_id, start_time, &result);
+        result
+    }
+
+    async fn chat_in_context(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+        context: &RequestContext,
+        mut on_chunk: Option<&mut (dyn FnMut(&str) + Send)>,
+    ) -> AgentResult<AgentResponse> {
+        let start_time = std::time::Instant::now();
+        let variant = self.route_variant(agent_id).await;
+        let (cache_key, cached) = self
+            .lookup_cache(agent_id, message, true, &options, context)
+            .await?;
+        // 命中缓存时无需占用提供商的执行名额
+        let _permit = match cached {
+            Some(_) => None,
+            None => Some(
+                self.acquire_for_request(agent_id, variant.as_ref(), options.priority)
+                    .await?,
+            ),
+        };
         info!(
             "开始处理聊天消息，Agent: {}, 消息长度: {}",
             agent_id,
             message.len()
         );
 
-        let mut agents = self.agents.write().await;
-        let agent_data = agents.get_mut(agent_id).ok_or_else(|| {
+        let session = self.agent_session(agent_id).await;
+        let not_found = || {
             error!("Agent 不存在: {}", agent_id);
             AgentError::AgentNotFound(agent_id.to_string())
-        })?;
-
-        // 动态创建 agent
-        let agent = registry.create_agent(&agent_data.config)?;
-
-        // 更新最后活动时间
-        agent_data.last_activity = chrono::Utc::now();
-        debug!("更新 Agent {} 最后活动时间", agent_id);
+        };
+        // 同一 Agent 的对话轮次依次进行，保持历史中问答的顺序
+        let turn = self
+            .agents
+            .read()
+            .await
+            .get(agent_id)
+            .map(|agent| agent.turn.clone())
+            .ok_or_else(not_found)?;
+        let _turn = turn.lock().await;
 
         // 创建用户消息
         let user_message = Message::user(message);
-        agent_data.conversation_history.push(user_message.clone());
-        debug!(
-            "添加用户消息到对话历史，当前历史长度: {}",
-            agent_data.conversation_history.len()
-        );
-
-        // 调用 rig-core AI 模型
-        debug!(
-            "准备调用 AI 模型 ({}/{})",
-            agent_data.config.provider, agent_data.config.model
-        );
-        let ai_start_time = std::time::Instant::now();
-
-        // 使用对话历史进行聊天
-        let response = agent
-            .chat(user_message, agent_data.conversation_history.clone())
-            .await
-            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))?;
+        let mut user_message_meta = MessageMeta::new(new_message_id(), options.metadata.clone());
+        user_message_meta.metadata.extend(context.metadata());
+        if let Some(language) = detect_language(message) {
+            user_message_meta
+                .metadata
+                .insert(DETECTED_LANGUAGE_KEY.to_string(), language.into());
+        }
+        let user_message_id = user_message_meta.id.clone();
+        let user_metadata = user_message_meta.metadata.clone();
+
+        // 在锁内追加用户消息并取 Agent 的快照，调用模型期间不占用 Agent 表的锁，
+        // 其他 Agent 的请求可以同时处理
+        let agent_data = {
+            let mut agents = self.agents.write().await;
+            let agent_data = agents.get_mut(agent_id).ok_or_else(not_found)?;
+            agent_data.last_activity = chrono::Utc::now();
+            agent_data.push_message(user_message.clone(), user_message_meta);
+            debug!(
+                "添加用户消息到对话历史，当前历史长度: {}",
+                agent_data.conversation_history.len()
+            );
+            agent_data.clone()
+        };
 
-        let ai_duration = ai_start_time.elapsed();
-        info!(
-            "AI 模型调用完成，Agent: {}, 提供商: {}, 模型: {}, 耗时: {:?}",
-            agent_id, agent_data.config.provider, agent_data.config.model, ai_duration
-        );
+        let mut warnings = Vec::new();
+        // 本次请求的工具调用记录，由挂载的全部工具共享
+        let provenance = ToolProvenance::default();
+        // 竞速时胜出的挑战者
+        let mut race_winner = None;
+        // 发给模型前对话历史的压缩报告
+        let mut compression = None;
+        // 确定性模式下的请求指纹
+        let mut fingerprint = None;
+        let (response, finish_reason) = match cached {
+            Some(response) => {
+                info!("使用缓存响应，Agent: {}", agent_id);
+                if let Some(on_chunk) = on_chunk.as_mut() {
+                    on_chunk(&response);
+                }
+                // 只缓存完整的响应
+                (response, FinishReason::Stop)
+            }
+            None => {
+                // 动态创建 agent，并挂载该 Agent 被允许使用的工具
+                let tools: Vec<ManagedTool> = self
+                    .rig_tools_for(agent_id, &agent_data.config, session.as_ref())
+                    .into_iter()
+                    .map(|tool| tool.with_provenance(provenance.clone()))
+                    .collect();
+                let tool_count = tools.len();
+                let tool_names: Vec<String> = tools
+                    .iter()
+                    .map(|tool| tool.definition().qualified_name())
+                    .collect();
+                let reply_language = reply_language(message, context);
+                let mut config = effective_config(
+                    &agent_data.config,
+                    session.as_ref(),
+                    reply_language.as_deref(),
+                    options.creativity,
+                );
+                if let Some(variant) = &variant {
+                    variant.apply(config.to_mut());
+                }
+                let challenger = self.challenger_config(
+                    agent_id,
+                    agent_data.config.race.as_ref(),
+                    &config,
+                    message,
+                    tool_count,
+                    options.priority,
+                );
+                let (agent, lease) = registry.create_agent_with_lease(&config, tools)?;
+                // 挑战者创建失败（例如缺少密钥）时只调用主模型
+                let contest = challenger.as_ref().and_then(|(race, challenger)| {
+                    match registry.create_agent_with_lease(challenger, Vec::new()) {
+                        Ok((agent, lease)) => Some((*race, challenger, agent, lease)),
+                        Err(e) => {
+                            warn!(
+                                "无法创建竞速模型 {}，本次不竞速: {}",
+                                race.challenger.name, e
+                            );
+                            None
+                        }
+                    }
+                });
+
+                // 调用 rig-core AI 模型
+                debug!("准备调用 AI 模型 ({}/{})", config.provider, config.model);
+                let ai_start_time = std::time::Instant::now();
+
+                // 使用对话历史进行聊天，挂载工具时允许多轮工具调用
+                let timeout_ms = context.cap_timeout(options.timeout_ms.or(config.timeout_ms));
+                // rig 的接口需要独占的历史，这里是每次请求唯一的一次复制
+                let (history, report) = agent_data.history_for(&config, message);
+                if let Some(report) = &report {
+                    info!(
+                        "压缩 Agent {} 的对话历史: {} -> {} 令牌，移除 {} 处内容",
+                        agent_id,
+                        report.original_tokens,
+                        report.compressed_tokens,
+                        report.removed.len()
+                    );
+                }
+                compression = report;
+                if config.deterministic {
+                    fingerprint = Some(RequestFingerprint::new(
+                        &config, &history, message, tool_names,
+                    ));
+                }
+                let mut race_outcome = None;
+                let result = if tool_count > 0 {
+                    let mut history = history;
+                    with_timeout(timeout_ms, async {
+                        agent
+                            .prompt(user_message)
+                            .with_history(&mut history)
+                            .multi_turn(MAX_TOOL_TURNS)
+                            .await
+                            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))
+                    })
+                    .await
+                    .map(|response| {
+                        if let Some(on_chunk) = on_chunk.as_mut() {
+                            on_chunk(&response);
+                        }
+                        (response, FinishReason::Stop)
+                    })
+                } else if let Some((race_config, challenger, challenger_agent, _)) = &contest {
+                    // 两个提供商都使用流式调用时按首个片段竞速，否则按完整响应竞速
+                    let streaming = registry.should_stream(&config.provider)
+                        && registry.should_stream(&challenger.provider);
+                    let admit = || self.races.admit(agent_id, race_config);
+                    with_timeout(timeout_ms, async {
+                        match on_chunk.as_mut() {
+                            Some(on_chunk) if streaming => {
+                                let (stream, outcome) = race(
+                                    open_stream_first(
+                                        &agent,
+                                        user_message.clone(),
+                                        history.clone(),
+                                    ),
+                                    open_stream_first(challenger_agent, user_message, history),
+                                    race_config.hedge_delay(),
+                                    admit,
+                                )
+                                .await;
+                                race_outcome = Some(outcome);
+                                read_stream(stream?, *on_chunk, &mut warnings).await
+                            }
+                            on_chunk => {
+                                let (response, outcome) = race(
+                                    agent.chat(user_message.clone(), history.clone()),
+                                    challenger_agent.chat(user_message, history),
+                                    race_config.hedge_delay(),
+                                    admit,
+                                )
+                                .await;
+                                race_outcome = Some(outcome);
+                                let response = response.map_err(|e| {
+                                    AgentError::other(format!("AI 模型调用失败: {}", e))
+                                })?;
+                                if let Some(on_chunk) = on_chunk {
+                                    on_chunk(&response);
+                                }
+                                Ok((response, FinishReason::Stop))
+                            }
+                        }
+                    })
+                    .await
+                } else if let Some(on_chunk) = on_chunk.as_mut() {
+                    with_timeout(
+                        timeout_ms,
+                        stream_with_fallback(
+                            registry,
+                            &config.provider,
+                            &agent,
+                            user_message,
+                            history,
+                            *on_chunk,
+                            &mut warnings,
+                        ),
+                    )
+                    .await
+                } else {
+                    with_timeout(timeout_ms, async {
+                        agent
+                            .chat(user_message, history)
+                            .await
+                            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))
+                    })
+                    .await
+                    .map(|response| (response, FinishReason::Stop))
+                };
+
+                let ai_duration = ai_start_time.elapsed();
+                // 竞速时向胜出的一方反馈密钥的使用结果，双方都失败时都反馈
+                let challenger_won =
+                    matches!(race_outcome, Some(RaceOutcome::ChallengerWon { .. }));
+                match (&contest, race_outcome) {
+                    (Some((.., challenger_lease)), Some(RaceOutcome::ChallengerWon { .. })) => {
+                        registry.report_api_key(challenger_lease.as_ref(), &result);
+                    }
+                    (Some((.., challenger_lease)), Some(RaceOutcome::BothFailed)) => {
+                        registry.report_api_key(lease.as_ref(), &result);
+                        registry.report_api_key(challenger_lease.as_ref(), &result);
+                    }
+                    _ => registry.report_api_key(lease.as_ref(), &result),
+                }
+                if let Some(outcome) = race_outcome {
+                    self.races
+                        .record(agent_id, outcome, ai_duration.as_millis() as u64);
+                }
+                let (response, mut finish_reason) = match result {
+                    Ok(output) => output,
+                    Err(e) => {
+                        if let Some(variant) = &variant {
+                            self.routing.record_failure(agent_id, variant);
+                        }
+                        return Err(e);
+                    }
+                };
+                // rig-core 不返回提供商的完成原因，按输出估算是否达到长度上限
+                if let Some(max_tokens) = config.max_tokens
+                    && finish_reason.is_complete()
+                    && estimated_tokens(&response) >= max_tokens
+                {
+                    finish_reason = FinishReason::Length;
+                    warnings.push(ResponseWarning::MaxTokensReached { max_tokens });
+                }
+                // 挑战者胜出时回答不是来自路由到的变体，不计入变体指标
+                if let Some(variant) = &variant
+                    && !challenger_won
+                {
+                    self.routing.record_success(
+                        agent_id,
+                        variant,
+                        ai_duration.as_millis() as u64,
+                        message,
+                        &response,
+                    );
+                }
+                let answered: &AgentConfig = match &contest {
+                    Some((race_config, challenger, ..)) if challenger_won => {
+                        race_winner = Some(race_config.challenger.clone());
+                        challenger
+                    }
+                    _ => &config,
+                };
+                info!(
+                    "AI 模型调用完成，Agent: {}, 提供商: {}, 模型: {}, 耗时: {:?}",
+                    agent_id, answered.provider, answered.model, ai_duration
+                );
+
+                if let Some(key) = cache_key
+                    && finish_reason.is_complete()
+                {
+                    self.cache.insert(key, response.clone());
+                }
+                (response, finish_reason)
+            }
+        };
 
         debug!("AI 响应内容长度: {}", response.len());
 
-        // 创建助手消息并添加到历史
-        let assistant_message = Message::assistant(&response);
-        agent_data.conversation_history.push(assistant_message);
+        // 本次回答经过的工具调用，以及回答对工具输出的引用
+        let tool_outputs = provenance.take();
+        let citations = cite(&response, &tool_outputs);
+        let tool_calls = (!tool_outputs.is_empty())
+            .then(|| tool_outputs.iter().map(ToolOutput::to_call).collect());
 
-        // 应用历史限制
-        if let Some(limit) = agent_data.config.history_limit {
-            if agent_data.conversation_history.len() > limit {
-                let excess = agent_data.conversation_history.len() - limit;
-                agent_data.conversation_history.drain(0..excess);
+        // 创建助手消息并添加到历史，消息 ID 与响应 ID 相同，便于之后添加注解
+        let response_id = new_message_id();
+        let assistant_message = Message::assistant(&response);
+        let mut assistant_metadata = MessageMetadata::new();
+        if let Some(variant) = &variant {
+            assistant_metadata.insert(VARIANT_KEY.to_string(), variant.name.clone().into());
+        }
+        if let Some(winner) = &race_winner {
+            assistant_metadata.insert(RACE_WINNER_KEY.to_string(), winner.name.clone().into());
+        }
+        let creativity = options.creativity.or(agent_data.config.creativity);
+        if let Some(creativity) = creativity {
+            assistant_metadata.insert(CREATIVITY_KEY.to_string(), creativity.as_str().into());
+        }
+        {
+            // 调用模型期间 Agent 可能已被删除
+            let mut agents = self.agents.write().await;
+            let current = agents.get_mut(agent_id).ok_or_else(not_found)?;
+            current.last_activity = chrono::Utc::now();
+            current.push_message(
+                assistant_message,
+                MessageMeta::new(response_id.clone(), assistant_metadata.clone()),
+            );
+
+            // 应用历史限制
+            if let Some(limit) = current.config.history_limit {
+                let dropped = current.trim_history(limit);
+                if dropped > 0 {
+                    warnings.push(ResponseWarning::HistoryTruncated { dropped });
+                }
             }
         }
 
+        self.record_session_turn(
+            agent_id,
+            vec![
+                AgentMessage::user(message.to_string())
+                    .with_id(user_message_id)
+                    .with_metadata(user_metadata),
+                AgentMessage::assistant(response.clone())
+                    .with_id(response_id.clone())
+                    .with_metadata(assistant_metadata),
+            ],
+        )
+        .await;
+
         let total_duration = start_time.elapsed();
-        let response_id = uuid::Uuid::new_v4().to_string();
 
         info!(
             "聊天消息处理完成，Agent: {}, 响应ID: {}, 总耗时: {:?}, 响应长度: {}",
@@ -325,45 +1563,144 @@ impl AgentManager {
             agent_id: agent_id.to_string(),
             content: response,
             timestamp: chrono::Utc::now(),
-            model: agent_data.config.model.clone(),
-            usage: None,      // TODO: 从 rig-core 获取使用统计
-            tool_calls: None, // TODO: 处理工具调用
-            finish_reason: Some("stop".to_string()),
+            model: race_winner
+                .or_else(|| variant.clone())
+                .map_or_else(|| agent_data.config.model.clone(), |v| v.model),
+            usage: None, // TODO: 从 rig-core 获取使用统计
+            tool_calls,
+            citations,
+            compression,
+            creativity,
+            fingerprint,
+            finish_reason: Some(finish_reason),
+            warnings,
+            variant: variant.map(|v| v.name),
         })
     }
 
-    /// 简单的 prompt 方法（不保存历史）
-    #[instrument(skip(self, registry, message), fields(agent_id = %agent_id, message_len = message.len()))]
+    /// 简单的 prompt 方法（不保存历史，交互式优先级）
     pub async fn prompt(
         &self,
         registry: &ClientRegistry,
         agent_id: &str,
         message: &str,
     ) -> AgentResult<String> {
+        self.prompt_with_options(registry, agent_id, message, ChatOptions::default())
+            .await
+    }
+
+    /// 按指定优先级执行 prompt（不保存历史）
+    pub async fn prompt_with_priority(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        priority: RequestPriority,
+    ) -> AgentResult<String> {
+        let options = ChatOptions::default().with_priority(priority);
+        self.prompt_with_options(registry, agent_id, message, options)
+            .await
+    }
+
+    /// 按请求选项执行 prompt（不保存历史）
+    #[instrument(skip(self, registry, message), fields(agent_id = %agent_id, message_len = message.len()))]
+    pub async fn prompt_with_options(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        mut options: ChatOptions,
+    ) -> AgentResult<String> {
+        let context = RequestContext::resolve(options.context.take());
+        let start_time = std::time::Instant::now();
+        let result = context
+            .clone()
+            .scope(self.prompt_in_context(registry, agent_id, message, options, &context))
+            .await;
+        audit_result(&context, "prompt", agent_id, start_time, &result);
+        result
+    }
+
+    async fn prompt_in_context(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        message: &str,
+        options: ChatOptions,
+        context: &RequestContext,
+    ) -> AgentResult<String> {
+        let variant = self.route_variant(agent_id).await;
+        let (cache_key, cached) = self
+            .lookup_cache(agent_id, message, false, &options, context)
+            .await?;
+        if let Some(response) = cached {
+            info!("使用缓存响应，Agent: {}", agent_id);
+            return Ok(response);
+        }
+
+        let _permit = self
+            .acquire_for_request(agent_id, variant.as_ref(), options.priority)
+            .await?;
+        let session = self.agent_session(agent_id).await;
         let agents = self.agents.read().await;
         let agent_data = agents.get(agent_id).ok_or_else(|| {
             error!("Agent 不存在: {}", agent_id);
             AgentError::AgentNotFound(agent_id.to_string())
         })?;
 
-        // 动态创建 agent
-        let agent = registry.create_agent(&agent_data.config)?;
+        // 动态创建 agent，并挂载该 Agent 被允许使用的工具
+        let tools = self.rig_tools_for(agent_id, &agent_data.config, session.as_ref());
+        let tool_count = tools.len();
+        let reply_language = reply_language(message, context);
+        let mut config = effective_config(
+            &agent_data.config,
+            session.as_ref(),
+            reply_language.as_deref(),
+            options.creativity,
+        );
+        if let Some(variant) = &variant {
+            variant.apply(config.to_mut());
+        }
+        let (agent, lease) = registry.create_agent_with_lease(&config, tools)?;
 
         debug!("准备调用 AI 模型进行简单 prompt");
         let ai_start_time = std::time::Instant::now();
 
         // 使用 prompt 方法，不保存历史
-        let response = agent
-            .prompt(message)
-            .await
-            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))?;
+        let timeout_ms = context.cap_timeout(options.timeout_ms.or(config.timeout_ms));
+        let result = with_timeout(timeout_ms, async {
+            if tool_count > 0 {
+                agent.prompt(message).multi_turn(MAX_TOOL_TURNS).await
+            } else {
+                agent.prompt(message).await
+            }
+            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))
+        })
+        .await;
 
         let ai_duration = ai_start_time.elapsed();
+        registry.report_api_key(lease.as_ref(), &result);
+        if let Some(variant) = &variant {
+            match &result {
+                Ok(response) => self.routing.record_success(
+                    agent_id,
+                    variant,
+                    ai_duration.as_millis() as u64,
+                    message,
+                    response,
+                ),
+                Err(_) => self.routing.record_failure(agent_id, variant),
+            }
+        }
+        let response = result?;
         info!(
             "简单 prompt 完成，Agent: {}, 提供商: {}, 模型: {}, 耗时: {:?}",
-            agent_id, agent_data.config.provider, agent_data.config.model, ai_duration
+            agent_id, config.provider, config.model, ai_duration
         );
 
+        if let Some(key) = cache_key {
+            self.cache.insert(key, response.clone());
+        }
         Ok(response)
     }
 
@@ -375,6 +1712,20 @@ impl AgentManager {
         model: &str,
         message: &str,
     ) -> AgentResult<String> {
+        let config = AgentConfig::new(provider, model);
+        self.prompt_ephemeral(registry, &config, message, RequestPriority::Interactive)
+            .await
+    }
+
+    /// 按配置创建临时 Agent 并以指定优先级执行 prompt
+    async fn prompt_ephemeral(
+        &self,
+        registry: &ClientRegistry,
+        config: &AgentConfig,
+        message: &str,
+        priority: RequestPriority,
+    ) -> AgentResult<String> {
+        let (provider, model) = (config.provider.as_str(), config.model.as_str());
         // 检查提供商是否已注册
         if !registry.has_client(provider) {
             return Err(AgentError::config(format!(
@@ -383,28 +1734,481 @@ impl AgentManager {
             )));
         }
 
-        // 创建临时配置
-        let config = AgentConfig::new(provider, model);
-
         // 创建临时 Agent
-        let agent = registry.create_agent(&config)?;
+        let (agent, lease) = registry.create_agent_with_lease(config, Vec::new())?;
+        let _permit = self.scheduler.acquire(provider, priority).await;
 
         debug!("准备使用临时 Agent 调用 AI 模型进行 prompt");
         let ai_start_time = std::time::Instant::now();
 
-        // 使用 prompt 方法
-        let response = agent
-            .prompt(message)
+        // 使用 prompt 方法
+        let result = with_timeout(config.timeout_ms, async {
+            agent
+                .prompt(message)
+                .await
+                .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))
+        })
+        .await;
+        registry.report_api_key(lease.as_ref(), &result);
+        let response = result?;
+
+        let ai_duration = ai_start_time.elapsed();
+        info!(
+            "临时 Agent prompt 完成，提供商: {}, 模型: {}, 耗时: {:?}",
+            provider, model, ai_duration
+        );
+
+        Ok(response)
+    }
+
+    /// 翻译文本：由带翻译提示词的临时 Agent 处理，不写入对话历史
+    pub async fn translate(
+        &self,
+        registry: &ClientRegistry,
+        request: &TranslateRequest,
+    ) -> AgentResult<Translation> {
+        if request.text.trim().is_empty() {
+            return Err(AgentError::other("待翻译的文本为空"));
+        }
+        if request.target.trim().is_empty() {
+            return Err(AgentError::config("未指定目标语言"));
+        }
+        let config = request.config(&self.default_config);
+        let source = request.source_language();
+        let prompt = translate::translate_prompt(
+            &request.text,
+            source.as_deref(),
+            &request.target,
+            request.register,
+        );
+
+        let start_time = std::time::Instant::now();
+        let reply = self
+            .prompt_ephemeral(registry, &config, &prompt, RequestPriority::Interactive)
+            .await?;
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+        debug!(
+            "翻译完成，{:?} -> {}，耗时 {} 毫秒",
+            source, request.target, duration_ms
+        );
+        Ok(Translation {
+            text: translate::clean_translation(&reply),
+            source,
+            target: request.target.clone(),
+            register: request.register,
+            provider: config.provider,
+            model: config.model,
+            duration_ms,
+        })
+    }
+
+    /// 对长文档做 map-reduce 摘要：逐块摘要，合并为章节摘要，再生成全文摘要与要点。
+    /// 请求不写入对话历史，以批处理优先级排队
+    pub async fn summarize_document(
+        &self,
+        registry: &ClientRegistry,
+        document: &str,
+        options: &SummarizeOptions,
+    ) -> AgentResult<DocumentSummary> {
+        let chunk_chars = options.effective_chunk_chars();
+        let sections = summarize::split_sections(document, chunk_chars);
+        if sections.is_empty() {
+            return Err(AgentError::other("文档内容为空"));
+        }
+        let config = options.config(&self.default_config);
+        let title = options.title.as_deref();
+        let chunks: usize = sections.iter().map(|section| section.chunks.len()).sum();
+        let start_time = std::time::Instant::now();
+        info!(
+            "开始摘要文档，{} 个章节，{} 个块，提供商: {}, 模型: {}",
+            sections.len(),
+            chunks,
+            config.provider,
+            config.model
+        );
+
+        // map：逐块摘要
+        let prompts = sections.iter().flat_map(|section| {
+            section.chunks.iter().enumerate().map(move |(i, chunk)| {
+                summarize::chunk_prompt(title, &section.title, i, section.chunks.len(), chunk)
+            })
+        });
+        let config_ref = &config;
+        let mut chunk_summaries = futures::future::try_join_all(prompts.map(|prompt| async move {
+            self.prompt_ephemeral(registry, config_ref, &prompt, RequestPriority::Batch)
+                .await
+        }))
+        .await?
+        .into_iter();
+
+        // reduce：合并为章节摘要
+        let mut section_summaries = Vec::with_capacity(sections.len());
+        for section in &sections {
+            let summaries: Vec<String> = chunk_summaries
+                .by_ref()
+                .take(section.chunks.len())
+                .collect();
+            let summary = self
+                .reduce_summaries(registry, &config, summaries, chunk_chars, |batch| {
+                    summarize::section_prompt(title, &section.title, batch)
+                })
+                .await?;
+            section_summaries.push(SectionSummary {
+                title: section.title.clone(),
+                summary,
+                chunks: section.chunks.len(),
+                chars: section
+                    .chunks
+                    .iter()
+                    .map(|chunk| chunk.chars().count())
+                    .sum(),
+            });
+        }
+
+        // 章节摘要过长时先分批合并，再生成全文摘要
+        let mut overview: Vec<String> = section_summaries
+            .iter()
+            .map(|section| format!("{}：{}", section.title, section.summary))
+            .collect();
+        while overview.len() > 1
+            && overview.iter().map(|s| s.chars().count()).sum::<usize>() > chunk_chars
+        {
+            overview = self
+                .reduce_batches(registry, &config, &overview, chunk_chars, |batch| {
+                    summarize::section_prompt(title, "多个部分", batch)
+                })
+                .await?;
+        }
+        let reply = self
+            .prompt_ephemeral(
+                registry,
+                &config,
+                &summarize::document_prompt(title, &overview),
+                RequestPriority::Batch,
+            )
+            .await?;
+        let (summary, key_points) = summarize::parse_document_summary(&reply);
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+        info!("文档摘要完成，{} 个块，耗时 {} 毫秒", chunks, duration_ms);
+        Ok(DocumentSummary {
+            title: options.title.clone(),
+            summary,
+            key_points,
+            sections: section_summaries,
+            chunks,
+            chars: document.chars().count(),
+            provider: config.provider,
+            model: config.model,
+            duration_ms,
+        })
+    }
+
+    /// 把若干段摘要逐层合并为一段
+    async fn reduce_summaries<F>(
+        &self,
+        registry: &ClientRegistry,
+        config: &AgentConfig,
+        mut summaries: Vec<String>,
+        max_chars: usize,
+        prompt: F,
+    ) -> AgentResult<String>
+    where
+        F: Fn(&[String]) -> String,
+    {
+        while summaries.len() > 1 {
+            summaries = self
+                .reduce_batches(registry, config, &summaries, max_chars, &prompt)
+                .await?;
+        }
+        Ok(summaries.pop().unwrap_or_default())
+    }
+
+    /// 分批合并摘要，每批合并为一段；只有一条的批次原样保留
+    async fn reduce_batches<F>(
+        &self,
+        registry: &ClientRegistry,
+        config: &AgentConfig,
+        summaries: &[String],
+        max_chars: usize,
+        prompt: F,
+    ) -> AgentResult<Vec<String>>
+    where
+        F: Fn(&[String]) -> String,
+    {
+        let batches = summarize::batches(summaries, max_chars);
+        futures::future::try_join_all(batches.into_iter().map(|mut batch| {
+            let request = (batch.len() > 1).then(|| prompt(&batch));
+            async move {
+                match request {
+                    Some(request) => {
+                        self.prompt_ephemeral(registry, config, &request, RequestPriority::Batch)
+                            .await
+                    }
+                    None => Ok(batch.pop().unwrap_or_default()),
+                }
+            }
+        }))
+        .await
+    }
+
+    /// 用新的提供商/模型或系统提示词回放 Agent 的当前对话
+    pub async fn replay_conversation(
+        &self,
+        registry: &ClientRegistry,
+        agent_id: &str,
+        options: &ReplayOptions,
+    ) -> AgentResult<ReplayReport> {
+        let (config, history) = {
+            let agents = self.agents.read().await;
+            let agent = agents
+                .get(agent_id)
+                .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
+            (agent.config.clone(), agent.history_snapshot())
+        };
+        self.replay_messages(registry, agent_id, &config, &history, options)
+            .await
+    }
+
+    /// 回放已保存的会话；会话关联的 Agent 仍存在时以其配置为基础，否则使用默认配置
+    pub async fn replay_session(
+        &self,
+        registry: &ClientRegistry,
+        session_id: &str,
+        options: &ReplayOptions,
+    ) -> AgentResult<ReplayReport> {
+        let store = self.session_store()?;
+        let session = store
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| AgentError::database("会话不存在"))?;
+        let messages = store
+            .get_messages(session_id, 0, session.message_count)
+            .await?
+            .items;
+
+        let mut config = match &session.agent_id {
+            Some(agent_id) => self.get_agent_config(agent_id).await.ok(),
+            None => None,
+        }
+        .unwrap_or_else(|| self.default_config.clone());
+        config.model = session.model.clone();
+
+        self.replay_messages(registry, session_id, &config, &messages, options)
+            .await
+    }
+
+    /// 逐轮回放对话；每轮使用原对话作为上下文，单轮失败不影响其他轮次。
+    /// 回放不挂载工具、不读写缓存，也不修改原对话
+    async fn replay_messages(
+        &self,
+        registry: &ClientRegistry,
+        source: &str,
+        base: &AgentConfig,
+        messages: &[AgentMessage],
+        options: &ReplayOptions,
+    ) -> AgentResult<ReplayReport> {
+        let started_at = chrono::Utc::now();
+        let config = options.apply(base);
+        let agent = registry.create_agent(&config)?;
+        let turns = replay::pending_turns(messages);
+        info!(
+            "开始回放 {}，共 {} 轮，{}/{} -> {}/{}",
+            source,
+            turns.len(),
+            base.provider,
+            base.model,
+            config.provider,
+            config.model
+        );
+
+        let mut results = Vec::with_capacity(turns.len());
+        for (index, turn) in turns.into_iter().enumerate() {
+            let _permit = self
+                .scheduler
+                .acquire(&config.provider, RequestPriority::Batch)
+                .await;
+            let start_time = std::time::Instant::now();
+            let result = with_timeout(config.timeout_ms, async {
+                agent
+                    .chat(Message::user(turn.user_message.as_str()), turn.history)
+                    .await
+                    .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))
+            })
+            .await
+            .map_err(|e| e.to_string());
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+
+            let (replayed, error) = match result {
+                Ok(response) => (Some(response), None),
+                Err(e) => {
+                    warn!("回放 {} 第 {} 轮失败: {}", source, index, e);
+                    (None, Some(e))
+                }
+            };
+            let diff = match &replayed {
+                Some(replayed) => {
+                    replay::diff_lines(turn.original.as_deref().unwrap_or_default(), replayed)
+                }
+                None => Vec::new(),
+            };
+            let similarity = if replayed.is_some() {
+                replay::similarity(&diff)
+            } else {
+                0.0
+            };
+
+            results.push(ReplayTurn {
+                index,
+                user_message: turn.user_message,
+                original: turn.original,
+                replayed,
+                error,
+                diff,
+                similarity,
+                duration_ms,
+            });
+        }
+
+        Ok(ReplayReport::new(
+            source.to_string(),
+            base,
+            &config,
+            results,
+            started_at,
+        ))
+    }
+
+    /// 运行评测测试集；`agents` 为空时使用测试集中的默认被测 Agent。
+    /// 提示词不写入对话历史、不使用缓存，单个用例失败不影响其他用例
+    pub async fn run_eval(
+        &self,
+        registry: &ClientRegistry,
+        suite: &EvalSuite,
+        agents: &[String],
+    ) -> AgentResult<EvalReport> {
+        suite.validate()?;
+        let agents = if agents.is_empty() {
+            suite.agents.clone()
+        } else {
+            agents.to_vec()
+        };
+        if agents.is_empty() {
+            return Err(AgentError::config("未指定被测 Agent"));
+        }
+        for agent_id in &agents {
+            if !self.agents.read().await.contains_key(agent_id) {
+                return Err(AgentError::AgentNotFound(agent_id.clone()));
+            }
+        }
+
+        let started_at = chrono::Utc::now();
+        info!(
+            "开始评测 {}，{} 个用例，Agent: {:?}",
+            suite.name,
+            suite.cases.len(),
+            agents
+        );
+        let options = ChatOptions::default()
+            .with_priority(RequestPriority::Batch)
+            .with_bypass_cache(true);
+
+        let mut results = Vec::with_capacity(agents.len() * suite.cases.len());
+        for agent_id in &agents {
+            for case in &suite.cases {
+                let start_time = std::time::Instant::now();
+                let response = self
+                    .prompt_with_options(registry, agent_id, &case.prompt, options.clone())
+                    .await;
+                let duration_ms = start_time.elapsed().as_millis() as u64;
+
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => {
+                        warn!("评测用例 {} 在 {} 上调用失败: {}", case.id, agent_id, e);
+                        results.push(CaseResult {
+                            case_id: case.id.clone(),
+                            agent_id: agent_id.clone(),
+                            response: None,
+                            error: Some(e.to_string()),
+                            checks: Vec::new(),
+                            passed: false,
+                            duration_ms,
+                        });
+                        continue;
+                    }
+                };
+
+                let mut checks = Vec::with_capacity(case.expect.len());
+                for expectation in &case.expect {
+                    let check = match eval::check_local(expectation, &response) {
+                        Some(check) => check,
+                        None => {
+                            self.judge(registry, suite, case, expectation, &response, &options)
+                                .await
+                        }
+                    };
+                    checks.push(check);
+                }
+
+                results.push(CaseResult {
+                    case_id: case.id.clone(),
+                    agent_id: agent_id.clone(),
+                    passed: checks.iter().all(|check| check.passed),
+                    response: Some(response),
+                    error: None,
+                    checks,
+                    duration_ms,
+                });
+            }
+        }
+
+        let report = EvalReport::new(suite, agents, results, started_at);
+        info!(
+            "评测 {} 完成，通过 {}，失败 {}",
+            report.suite, report.passed, report.failed
+        );
+        Ok(report)
+    }
+
+    /// 请评审 Agent 按评分细则为回复打分
+    async fn judge(
+        &self,
+        registry: &ClientRegistry,
+        suite: &EvalSuite,
+        case: &EvalCase,
+        expectation: &Expectation,
+        response: &str,
+        options: &ChatOptions,
+    ) -> CheckResult {
+        let Expectation::Rubric {
+            rubric,
+            threshold,
+            judge_agent,
+        } = expectation
+        else {
+            return CheckResult::new(expectation, false, Some("不是评分细则".to_string()));
+        };
+        let Some(judge_agent) = judge_agent.as_ref().or(suite.judge_agent.as_ref()) else {
+            return CheckResult::new(expectation, false, Some("没有评审 Agent".to_string()));
+        };
+
+        let prompt = eval::judge_prompt(&case.prompt, response, rubric);
+        let judgement = match self
+            .prompt_with_options(registry, judge_agent, &prompt, options.clone())
             .await
-            .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))?;
-
-        let ai_duration = ai_start_time.elapsed();
-        info!(
-            "临时 Agent prompt 完成，提供商: {}, 模型: {}, 耗时: {:?}",
-            provider, model, ai_duration
-        );
+        {
+            Ok(reply) => eval::parse_judgement(&reply),
+            Err(e) => Err(e),
+        };
 
-        Ok(response)
+        match judgement {
+            Ok((score, reason)) => CheckResult {
+                score: Some(score),
+                ..CheckResult::new(expectation, score >= *threshold, reason)
+            },
+            Err(e) => CheckResult::new(expectation, false, Some(format!("评审失败: {}", e))),
+        }
     }
 
     /// 获取对话历史
@@ -417,48 +2221,32 @@ impl AgentManager {
             .get(agent_id)
             .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
 
-        // 将 rig Message 转换为我们的 AgentMessage
-        let messages: Vec<AgentMessage> = agent
-            .conversation_history
-            .iter()
-            .map(|msg| match msg {
-                Message::User { content, .. } => {
-                    // 提取文本内容
-                    let text = content
-                        .iter()
-                        .filter_map(|c| match c {
-                            rig::message::UserContent::Text(text) => Some(text.text.clone()),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    AgentMessage::user(text)
-                }
-                Message::Assistant { content, .. } => {
-                    // 提取文本内容
-                    let text = content
-                        .iter()
-                        .filter_map(|c| match c {
-                            rig::message::AssistantContent::Text(text) => Some(text.text.clone()),
-                            _ => None,
-                        })
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    AgentMessage::assistant(text)
-                }
-            })
-            .collect();
+        Ok(agent.to_history())
+    }
 
-        let total_tokens = messages.iter().map(|msg| msg.content.len() as u64).sum();
+    /// 获取对话历史的快照。快照与 Agent 共享内存，不复制消息，之后的对话不影响快照
+    pub async fn get_history_snapshot(
+        &self,
+        agent_id: &str,
+    ) -> AgentResult<Arc<Vec<AgentMessage>>> {
+        let agents = self.agents.read().await;
+        let agent = agents
+            .get(agent_id)
+            .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
 
-        Ok(ConversationHistory {
-            agent_id: agent_id.to_string(),
-            messages,
-            total_messages: agent.conversation_history.len(),
-            total_tokens: Some(total_tokens),
-            created_at: agent.created_at,
-            last_activity: agent.last_activity,
-        })
+        Ok(agent.history_snapshot())
+    }
+
+    /// 分页获取对话历史，只复制本页的消息
+    pub async fn get_conversation_page(
+        &self,
+        agent_id: &str,
+        request: &PageRequest,
+    ) -> AgentResult<ListPage<AgentMessage>> {
+        let snapshot = self.get_history_snapshot(agent_id).await?;
+        Ok(request
+            .paginate(snapshot.iter().collect(), |message| message.timestamp)
+            .map(AgentMessage::clone))
     }
 
     /// 获取 Agent 的提供商信息
@@ -478,11 +2266,140 @@ impl AgentManager {
             .get_mut(agent_id)
             .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
 
-        agent.conversation_history.clear();
+        agent.clear_history();
         agent.last_activity = chrono::Utc::now();
+        drop(agents);
+        self.end_tool_states(agent_id).await;
         Ok(())
     }
 
+    /// 将 Agent 的对话历史保存为目录中的 JSON 文件，返回文件路径
+    pub async fn save_history(
+        &self,
+        agent_id: &str,
+        dir: &std::path::Path,
+    ) -> AgentResult<PathBuf> {
+        let history = self.get_conversation_history(agent_id).await?;
+        persist_history(dir, &history).await
+    }
+
+    /// 更新消息注解：合并到消息元数据，值为 null 的键会被移除。
+    /// 同时更新内存中的对话历史与会话存储中的副本，返回合并后的元数据
+    pub async fn annotate_message(
+        &self,
+        agent_id: &str,
+        message_id: &str,
+        annotations: MessageMetadata,
+    ) -> AgentResult<MessageMetadata> {
+        if !self.agents.read().await.contains_key(agent_id) {
+            return Err(AgentError::AgentNotFound(agent_id.to_string()));
+        }
+        self.annotate(Some(agent_id), message_id, annotations)
+            .await
+            .map(|(_, metadata)| metadata)
+    }
+
+    /// 更新消息注解；未指定 Agent 时在所有 Agent 的对话中查找该消息。
+    /// 返回消息所属的 Agent（仅在内存中找到时）与合并后的元数据
+    async fn annotate(
+        &self,
+        agent_id: Option<&str>,
+        message_id: &str,
+        annotations: MessageMetadata,
+    ) -> AgentResult<(Option<String>, MessageMetadata)> {
+        let in_memory = {
+            let mut agents = self.agents.write().await;
+            agents
+                .iter_mut()
+                .filter(|(id, _)| agent_id.is_none_or(|agent_id| agent_id == id.as_str()))
+                .find_map(|(id, agent)| {
+                    let metadata = agent.update_metadata(message_id, |metadata| {
+                        merge_metadata(metadata, annotations.clone())
+                    })?;
+                    Some((id.clone(), metadata))
+                })
+        };
+
+        // 超出历史限制的消息可能只保存在会话存储中
+        let stored = match &self.session_store {
+            Some(store) => store.annotate_message(message_id, annotations).await?,
+            None => None,
+        };
+
+        match (in_memory, stored) {
+            (Some((agent_id, metadata)), _) => Ok((Some(agent_id), metadata)),
+            (None, Some(metadata)) => Ok((None, metadata)),
+            (None, None) => Err(AgentError::other(format!("消息不存在: {}", message_id))),
+        }
+    }
+
+    /// 提交对助手回复的反馈，随对话一起保存在消息元数据中。
+    /// 未提供的评价或意见沿用之前的反馈；改变评价时同步更新该回复所用 A/B 变体的指标
+    pub async fn submit_feedback(
+        &self,
+        response_id: &str,
+        feedback: ResponseFeedback,
+    ) -> AgentResult<ResponseFeedback> {
+        if feedback.is_empty() {
+            return Err(AgentError::config("反馈不能为空"));
+        }
+
+        let (_, current) = self
+            .annotate(None, response_id, MessageMetadata::new())
+            .await?;
+        let previous = ResponseFeedback::from_metadata(&current);
+        let feedback = feedback.merged_with(previous.as_ref());
+
+        let (agent_id, metadata) = self
+            .annotate(None, response_id, feedback.to_annotations())
+            .await?;
+        let variant = metadata.get(VARIANT_KEY).and_then(|value| value.as_str());
+        if let (Some(agent_id), Some(variant), Some(rating)) = (agent_id, variant, feedback.rating)
+        {
+            let previous = previous
+                .and_then(|previous| previous.rating)
+                .map(|rating| rating.is_positive());
+            self.routing
+                .record_feedback(&agent_id, variant, previous, rating.is_positive());
+        }
+        Ok(feedback)
+    }
+
+    /// 导出 Agent 带反馈的问答；设置了会话存储时读取完整会话记录，否则使用内存中的对话历史
+    pub async fn export_feedback(&self, agent_id: &str) -> AgentResult<Vec<FeedbackRecord>> {
+        if let Some(store) = &self.session_store
+            && let Some(session) = store.find_session_by_agent(agent_id).await?
+        {
+            let messages = store
+                .get_messages(&session.id, 0, session.message_count)
+                .await?
+                .items;
+            return Ok(feedback::feedback_records(agent_id, &messages));
+        }
+        let history = self.get_history_snapshot(agent_id).await?;
+        Ok(feedback::feedback_records(agent_id, &history))
+    }
+
+    /// 获取 Agent 各 A/B 变体的汇总指标
+    pub fn get_variant_metrics(&self, agent_id: &str) -> Vec<VariantMetrics> {
+        self.routing.get(agent_id)
+    }
+
+    /// 清空 Agent 的 A/B 变体指标
+    pub fn reset_variant_metrics(&self, agent_id: &str) {
+        self.routing.reset(agent_id);
+    }
+
+    /// 获取 Agent 的提供商竞速指标
+    pub fn get_race_stats(&self, agent_id: &str) -> RaceStats {
+        self.races.get(agent_id)
+    }
+
+    /// 清空 Agent 的提供商竞速指标
+    pub fn reset_race_stats(&self, agent_id: &str) {
+        self.races.reset(agent_id);
+    }
+
     /// 获取 Agent 配置
     pub async fn get_agent_config(&self, agent_id: &str) -> AgentResult<AgentConfig> {
         let agents = self.agents.read().await;
@@ -510,6 +2427,26 @@ impl AgentManager {
         Ok(())
     }
 
+    /// 把 Agent 导出为包，包名称为 Agent ID
+    pub async fn export_agent_package(&self, agent_id: &str) -> AgentResult<AgentPackage> {
+        let config = self.get_agent_config(agent_id).await?;
+        Ok(AgentPackage::from_config(agent_id, &config))
+    }
+
+    /// 从包重建 Agent，未指定 ID 时使用包名称，返回新 Agent 的 ID
+    pub async fn import_agent_package(
+        &self,
+        package: &AgentPackage,
+        agent_id: Option<String>,
+    ) -> AgentResult<String> {
+        package.validate()?;
+        let agent_id = agent_id.unwrap_or_else(|| package.name.clone());
+        self.create_agent(agent_id.clone(), Some(package.to_config()))
+            .await?;
+        info!("已从包 {} 导入 Agent: {}", package.name, agent_id);
+        Ok(agent_id)
+    }
+
     /// 切换 Agent 的提供商和模型
     pub async fn switch_provider(
         &self,
@@ -535,6 +2472,97 @@ impl AgentManager {
         Ok(())
     }
 
+    /// 获取 Agent 可用的工具定义（即发送给模型的工具 schema）
+    pub async fn get_agent_tools(&self, agent_id: &str) -> AgentResult<Vec<ToolDefinition>> {
+        let agents = self.agents.read().await;
+        let agent = agents
+            .get(agent_id)
+            .ok_or_else(|| AgentError::AgentNotFound(agent_id.to_string()))?;
+
+        if !agent.config.enable_tools {
+            return Ok(Vec::new());
+        }
+        let permissions = self.tool_manager.permissions();
+        Ok(self
+            .tool_manager
+            .get_tool_definitions(&agent.config.tool_selection)
+            .into_iter()
+            .filter(|tool| !permissions.is_denied(agent_id, &tool.name))
+            .collect())
+    }
+
+    /// 按 Agent 配置筛选要挂载的工具
+    /// 有关联会话时同时挂载该会话的记忆工具
+    fn rig_tools_for(
+        &self,
+        agent_id: &str,
+        config: &AgentConfig,
+        session: Option<&ChatSession>,
+    ) -> Vec<ManagedTool> {
+        if !config.enable_tools {
+            return Vec::new();
+        }
+        // 自定义工具的状态按会话保存，没有关联会话时按 Agent 保存
+        let conversation_id = session.map_or(agent_id, |session| session.id.as_str());
+        let states = self.tool_manager.tool_states();
+        let mut tools: Vec<ManagedTool> = self
+            .tool_manager
+            .get_agent_rig_tools(agent_id, &config.tool_selection)
+            .into_iter()
+            .map(|tool| tool.with_state(states.clone(), conversation_id))
+            .collect();
+        if let (Some(store), Some(session)) = (&self.session_store, session) {
+            tools.extend(self.tool_manager.get_memory_rig_tools(
+                agent_id,
+                &config.tool_selection,
+                SessionMemory::new(store.clone(), session.id.clone()),
+            ));
+        }
+        tools
+    }
+
+    /// 结束 Agent 当前会话中的工具状态，返回释放的状态数
+    async fn end_tool_states(&self, agent_id: &str) -> usize {
+        let states = self.tool_manager.tool_states();
+        let mut disposed = states.dispose(agent_id).await;
+        if let Some(session) = self.agent_session(agent_id).await {
+            disposed += states.dispose(&session.id).await;
+        }
+        if disposed > 0 {
+            debug!("释放 Agent {} 的 {} 个工具状态", agent_id, disposed);
+        }
+        disposed
+    }
+
+    /// 设置 Agent 的工具授权（agent_id 为空时对所有 Agent 生效）
+    pub fn grant_tool_permission(
+        &self,
+        agent_id: Option<&str>,
+        tool_name: &str,
+        decision: PermissionDecision,
+    ) -> AgentResult<PermissionGrant> {
+        if !self.tool_manager.has_tool(tool_name) {
+            return Err(AgentError::tool(format!("未找到工具: {}", tool_name)));
+        }
+        self.tool_manager
+            .permissions()
+            .grant(agent_id, tool_name, decision)
+    }
+
+    /// 撤销工具授权，返回是否存在该授权
+    pub fn revoke_tool_permission(
+        &self,
+        agent_id: Option<&str>,
+        tool_name: &str,
+    ) -> AgentResult<bool> {
+        self.tool_manager.permissions().revoke(agent_id, tool_name)
+    }
+
+    /// 列出工具授权；指定 Agent 时包含对所有 Agent 生效的授权
+    pub fn list_tool_permissions(&self, agent_id: Option<&str>) -> Vec<PermissionGrant> {
+        self.tool_manager.permissions().list(agent_id)
+    }
+
     /// 获取工具管理器
     pub fn get_tool_manager(&self) -> &ToolManager {
         &self.tool_manager
@@ -563,6 +2591,7 @@ impl AgentManager {
             .iter()
             .filter(|msg| matches!(msg, Message::Assistant { .. }))
             .count();
+        let (positive_feedback, negative_feedback) = agent.feedback_counts();
 
         Ok(AgentStats {
             agent_id: agent_id.to_string(),
@@ -571,6 +2600,8 @@ impl AgentManager {
             total_messages,
             user_messages,
             assistant_messages,
+            positive_feedback,
+            negative_feedback,
             created_at: agent.created_at,
             last_activity: agent.last_activity,
             uptime: chrono::Utc::now().signed_duration_since(agent.created_at),
@@ -594,6 +2625,7 @@ impl AgentManager {
                 .iter()
                 .filter(|msg| matches!(msg, Message::Assistant { .. }))
                 .count();
+            let (positive_feedback, negative_feedback) = agent.feedback_counts();
 
             stats.push(AgentStats {
                 agent_id: agent_id.clone(),
@@ -602,6 +2634,8 @@ impl AgentManager {
                 total_messages,
                 user_messages,
                 assistant_messages,
+                positive_feedback,
+                negative_feedback,
                 created_at: agent.created_at,
                 last_activity: agent.last_activity,
                 uptime: chrono::Utc::now().signed_duration_since(agent.created_at),
@@ -612,6 +2646,277 @@ impl AgentManager {
     }
 }
 
+/// 按提供商的流式模式调用模型。自动模式下流式调用报告不支持流式，或首次检测时流式响应为空，
+/// 视为提供商不支持流式：记住检测结果并改为普通调用，完整响应作为最后一段回调
+async fn stream_with_fallback(
+    registry: &ClientRegistry,
+    provider: &str,
+    agent: &rig::agent::Agent<rig::client::completion::CompletionModelHandle<'_>>,
+    message: Message,
+    history: Vec<Message>,
+    on_chunk: &mut (dyn FnMut(&str) + Send),
+    warnings: &mut Vec<ResponseWarning>,
+) -> AgentResult<(String, FinishReason)> {
+    if registry.should_stream(provider) {
+        let auto = registry.streaming_mode(provider) == StreamingMode::Auto;
+        let undetected = registry.streaming_support(provider).is_none();
+        let mut stream_warnings = Vec::new();
+        match stream_chat_response(
+            agent,
+            message.clone(),
+            history.clone(),
+            on_chunk,
+            &mut stream_warnings,
+        )
+        .await
+        {
+            Err(e) if auto && is_streaming_unsupported(&e.to_string()) => {
+                warn!("{} 不支持流式响应，改为普通调用: {}", provider, e);
+                registry.record_streaming_support(provider, false);
+            }
+            Ok((response, _))
+                if auto && undetected && response.is_empty() && stream_warnings.is_empty() =>
+            {
+                warn!(
+                    "{} 的流式响应为空，按不支持流式处理，改为普通调用",
+                    provider
+                );
+                registry.record_streaming_support(provider, false);
+            }
+            result => {
+                if let Ok((response, _)) = &result
+                    && !response.is_empty()
+                {
+                    registry.record_streaming_support(provider, true);
+                }
+                warnings.extend(stream_warnings);
+                return result;
+            }
+        }
+    }
+
+    let response = agent
+        .chat(message, history)
+        .await
+        .map_err(|e| AgentError::other(format!("AI 模型调用失败: {}", e)))?;
+    on_chunk(&response);
+    Ok((response, FinishReason::Stop))
+}
+
+/// 错误信息是否表明提供商不支持流式响应
+fn is_streaming_unsupported(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    lower.contains("stream")
+        && [
+            "not supported",
+            "unsupported",
+            "not implemented",
+            "not available",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+}
+
+/// 以流式方式调用模型，逐段回调文本并返回完整响应与完成原因。
+/// 已经输出部分内容后失败时返回已收到的部分，并记录警告
+async fn stream_chat_response(
+    agent: &rig::agent::Agent<rig::client::completion::CompletionModelHandle<'_>>,
+    message: Message,
+    history: Vec<Message>,
+    on_chunk: &mut (dyn FnMut(&str) + Send),
+    warnings: &mut Vec<ResponseWarning>,
+) -> AgentResult<(String, FinishReason)> {
+    let stream = open_stream(agent, message, history).await?;
+    read_stream(stream, on_chunk, warnings).await
+}
+
+/// 流式响应中的一段内容
+enum StreamEvent {
+    Text(String),
+    ToolCall(String),
+    Other,
+}
+
+/// 发起流式调用，把提供商返回的片段转换为 [`StreamEvent`]
+async fn open_stream(
+    agent: &rig::agent::Agent<rig::client::completion::CompletionModelHandle<'_>>,
+    message: Message,
+    history: Vec<Message>,
+) -> AgentResult<impl futures::Stream<Item = Result<StreamEvent, String>> + Unpin> {
+    let stream = agent
+        .stream_chat(message, history)
+        .await
+        .map_err(|e| AgentError::other(format!("AI 模型流式调用失败: {}", e)))?;
+    Ok(stream.map(|chunk| match chunk {
+        Ok(StreamedAssistantContent::Text(text)) => Ok(StreamEvent::Text(text.text)),
+        Ok(StreamedAssistantContent::ToolCall(tool_call)) => {
+            Ok(StreamEvent::ToolCall(tool_call.function.name))
+        }
+        Ok(_) => Ok(StreamEvent::Other),
+        Err(e) => Err(e.to_string()),
+    }))
+}
+
+/// 发起流式调用并等到首个片段，用于按首个片段竞速；首个片段就是错误时视为调用失败
+async fn open_stream_first(
+    agent: &rig::agent::Agent<rig::client::completion::CompletionModelHandle<'_>>,
+    message: Message,
+    history: Vec<Message>,
+) -> AgentResult<impl futures::Stream<Item = Result<StreamEvent, String>> + Unpin> {
+    let mut stream = open_stream(agent, message, history).await?;
+    let first = match stream.next().await {
+        Some(Err(e)) => return Err(AgentError::other(format!("AI 模型流式响应失败: {}", e))),
+        first => first,
+    };
+    Ok(futures::stream::iter(first).chain(stream))
+}
+
+/// 读完流式响应，逐段回调文本
+async fn read_stream(
+    mut stream: impl futures::Stream<Item = Result<StreamEvent, String>> + Unpin,
+    on_chunk: &mut (dyn FnMut(&str) + Send),
+    warnings: &mut Vec<ResponseWarning>,
+) -> AgentResult<(String, FinishReason)> {
+    let mut response = String::new();
+    let mut finish_reason = FinishReason::Stop;
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(StreamEvent::Text(text)) => {
+                on_chunk(&text);
+                response.push_str(&text);
+            }
+            Ok(StreamEvent::ToolCall(tool)) => {
+                finish_reason = FinishReason::ToolCalls;
+                warnings.push(ResponseWarning::ToolCallIgnored { tool });
+            }
+            Ok(StreamEvent::Other) => {}
+            Err(e) if response.is_empty() => {
+                return Err(AgentError::other(format!("AI 模型流式响应失败: {}", e)));
+            }
+            Err(error) => {
+                warn!("AI 模型流式响应中途失败，返回已收到的部分: {}", error);
+                finish_reason =
+                    FinishReason::from_error_message(&error).unwrap_or(FinishReason::Error);
+                warnings.push(ResponseWarning::StreamInterrupted { error });
+                break;
+            }
+        }
+    }
+    Ok((response, finish_reason))
+}
+
+/// 按字符数估算令牌数，约 4 个字符一个令牌
+fn estimated_tokens(text: &str) -> u32 {
+    text.len().div_ceil(4) as u32
+}
+
+/// 为请求记录审计日志
+fn audit_result<T>(
+    context: &RequestContext,
+    action: &str,
+    agent_id: &str,
+    start_time: std::time::Instant,
+    result: &AgentResult<T>,
+) {
+    let error = result.as_ref().err().map(ToString::to_string);
+    context.audit(
+        action,
+        agent_id,
+        start_time.elapsed().as_millis() as u64,
+        error.as_deref(),
+    );
+}
+
+/// 为模型调用加上超时，超时后放弃调用并释放其持有的锁，`timeout_ms` 为空时不限制
+async fn with_timeout<T>(
+    timeout_ms: Option<u64>,
+    call: impl Future<Output = AgentResult<T>>,
+) -> AgentResult<T> {
+    let Some(timeout_ms) = timeout_ms else {
+        return call.await;
+    };
+    tokio::time::timeout(std::time::Duration::from_millis(timeout_ms), call)
+        .await
+        .unwrap_or_else(|_| {
+            warn!("模型调用超过 {} 毫秒，已放弃", timeout_ms);
+            Err(AgentError::Timeout(timeout_ms))
+        })
+}
+
+/// 将对话历史保存为 JSON 文件
+async fn persist_history(
+    dir: &std::path::Path,
+    history: &ConversationHistory,
+) -> AgentResult<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    // Agent ID 可能来自远程节点，只保留安全字符作为文件名
+    let safe_id: String = history
+        .agent_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!(
+        "{}-{}.json",
+        safe_id,
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+
+    let data = serde_json::to_vec_pretty(history)?;
+    tokio::fs::write(&path, data).await?;
+    Ok(path)
+}
+
+/// 本次请求的回复语言：优先使用消息中检测到的语言，检测不出时按调用方的语言区域回复
+fn reply_language(message: &str, context: &RequestContext) -> Option<String> {
+    detect_language(message)
+        .map(str::to_string)
+        .or_else(|| context.language())
+}
+
+/// 生成本次请求实际使用的配置：用会话变量渲染系统提示词，追加回复语言指令，
+/// 并应用按消息切换的创造性配置。无需改动时直接借用原配置
+fn effective_config<'a>(
+    config: &'a AgentConfig,
+    session: Option<&ChatSession>,
+    detected_language: Option<&str>,
+    creativity: Option<CreativityProfile>,
+) -> Cow<'a, AgentConfig> {
+    let preamble = match (session, &config.preamble) {
+        (Some(session), Some(preamble)) if !session.variables.is_empty() => {
+            Some(render_template(preamble, &session.variables))
+        }
+        _ => None,
+    };
+    let language = config.reply_language.target(detected_language);
+    let creativity = creativity.filter(|creativity| config.creativity != Some(*creativity));
+    if preamble.is_none() && language.is_none() && creativity.is_none() {
+        return Cow::Borrowed(config);
+    }
+
+    let mut config = config.clone();
+    if creativity.is_some() {
+        config.creativity = creativity;
+    }
+    if let Some(preamble) = preamble {
+        config.preamble = Some(preamble);
+    }
+    if let Some(language) = language {
+        let instruction = reply_language_instruction(&language);
+        config.preamble = Some(match config.preamble.take() {
+            Some(preamble) => format!("{}\n\n{}", preamble, instruction),
+            None => instruction,
+        });
+    }
+    Cow::Owned(config)
+}
+
 /// Agent 统计信息
 #[derive(Debug, Clone)]
 pub struct AgentStats {
@@ -621,11 +2926,43 @@ pub struct AgentStats {
     pub total_messages: usize,
     pub user_messages: usize,
     pub assistant_messages: usize,
+    pub positive_feedback: usize,
+    pub negative_feedback: usize,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_activity: chrono::DateTime<chrono::Utc>,
     pub uptime: chrono::Duration,
 }
 
+/// Agent 列表的排序字段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentSortKey {
+    /// Agent ID
+    Id,
+    /// 创建时间
+    #[default]
+    CreatedAt,
+    /// 最后活动时间
+    LastActivity,
+}
+
+/// Agent 摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSummary {
+    /// Agent ID
+    pub id: String,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 消息数量
+    pub message_count: usize,
+    /// 创建时间
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// 最后活动时间
+    pub last_activity: chrono::DateTime<chrono::Utc>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -639,6 +2976,247 @@ mod tests {
         assert_eq!(agents.len(), 0);
     }
 
+    #[tokio::test]
+    async fn test_agent_tool_selection() {
+        let manager = AgentManager::new(AgentConfig::default());
+
+        manager
+            .create_agent(
+                "math_agent".to_string(),
+                Some(AgentConfig::new("openai", "gpt-4o-mini").allow_tools(["math"])),
+            )
+            .await
+            .unwrap();
+        manager
+            .create_agent("plain_agent".to_string(), None)
+            .await
+            .unwrap();
+
+        let tools = manager.get_agent_tools("math_agent").await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].qualified_name(), "math.calculator");
+
+        let tools = manager.get_agent_tools("plain_agent").await.unwrap();
+        assert!(tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reap_idle_agents() {
+        let manager = AgentManager::new(AgentConfig::default());
+        let mut events = manager.subscribe_events();
+        manager
+            .create_agent("idle".to_string(), None)
+            .await
+            .unwrap();
+        manager
+            .create_agent("pinned".to_string(), None)
+            .await
+            .unwrap();
+
+        let persist_dir =
+            std::env::temp_dir().join(format!("rig-agent-gc-{}", uuid::Uuid::new_v4()));
+        let config = AgentGcConfig::default()
+            .with_idle_ttl(std::time::Duration::ZERO)
+            .with_persist_dir(&persist_dir)
+            .with_exempt("pinned");
+
+        let reaped = manager.reap_idle_agents(&config).await;
+        assert_eq!(reaped, vec!["idle".to_string()]);
+        assert_eq!(manager.list_agents().await, vec!["pinned".to_string()]);
+
+        match events.recv().await.unwrap() {
+            AgentEvent::Reaped {
+                agent_id,
+                history_path,
+                ..
+            } => {
+                assert_eq!(agent_id, "idle");
+                assert!(history_path.unwrap().exists());
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(persist_dir);
+    }
+
+    #[tokio::test]
+    async fn test_list_agents_page() {
+        let manager = AgentManager::new(AgentConfig::default());
+        for id in ["b", "a", "c"] {
+            manager.create_agent(id.to_string(), None).await.unwrap();
+        }
+
+        let page = manager
+            .list_agents_page(&PageRequest::default().with_range(0, 2), AgentSortKey::Id)
+            .await;
+        let ids: Vec<&str> = page.items.iter().map(|agent| agent.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.next_offset, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_chat_uses_response_cache() {
+        let manager = AgentManager::new(AgentConfig::default())
+            .with_cache_config(ResponseCacheConfig::default().with_enabled(true));
+        manager
+            .create_agent("cached".to_string(), None)
+            .await
+            .unwrap();
+
+        let key = ResponseCache::key(&AgentConfig::default(), &[Message::user("你好")]);
+        manager.cache.insert(key, "缓存的回复".to_string());
+
+        // 未注册任何提供商，命中缓存时不会创建模型客户端
+        let registry = ClientRegistry::new();
+        let response = manager.chat(&registry, "cached", "你好").await.unwrap();
+        assert_eq!(response.content, "缓存的回复");
+        assert_eq!(
+            manager
+                .get_conversation_history("cached")
+                .await
+                .unwrap()
+                .total_messages,
+            2
+        );
+
+        // 跳过缓存时会真正调用提供商
+        let options = ChatOptions::default().with_bypass_cache(true);
+        assert!(
+            manager
+                .prompt_with_options(&registry, "cached", "你好", options)
+                .await
+                .is_err()
+        );
+
+        let stats = manager.get_cache_stats();
+        assert_eq!((stats.hits, stats.bypassed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_chat_records_session() {
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let manager = AgentManager::new(AgentConfig::default())
+            .with_cache_config(ResponseCacheConfig::default().with_enabled(true))
+            .with_session_store(store.clone());
+        manager
+            .create_agent("writer".to_string(), None)
+            .await
+            .unwrap();
+        let session = manager.start_session("writer", "写作").await.unwrap();
+
+        let key = ResponseCache::key(&AgentConfig::default(), &[Message::user("你好")]);
+        manager.cache.insert(key, "你好！".to_string());
+        manager
+            .chat(&ClientRegistry::new(), "writer", "你好")
+            .await
+            .unwrap();
+
+        let messages = store.get_messages(&session.id, 0, 10).await.unwrap();
+        assert_eq!(messages.total, 2);
+        assert_eq!(messages.items[1].content, "你好！");
+    }
+
+    #[tokio::test]
+    async fn test_annotate_message() {
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let manager = AgentManager::new(AgentConfig::default())
+            .with_cache_config(ResponseCacheConfig::default().with_enabled(true))
+            .with_session_store(store.clone());
+        manager
+            .create_agent("annotated".to_string(), None)
+            .await
+            .unwrap();
+        let session = manager.start_session("annotated", "注解").await.unwrap();
+
+        let key = ResponseCache::key(&AgentConfig::default(), &[Message::user("你好")]);
+        manager.cache.insert(key, "你好！".to_string());
+        let options =
+            ChatOptions::default().with_metadata("client_message_id", serde_json::json!("c-42"));
+        let response = manager
+            .chat_with_options(&ClientRegistry::new(), "annotated", "你好", options)
+            .await
+            .unwrap();
+
+        let history = manager.get_conversation_history("annotated").await.unwrap();
+        assert_eq!(
+            history.messages[0].metadata["client_message_id"],
+            serde_json::json!("c-42")
+        );
+        assert_eq!(history.messages[1].id, response.id);
+
+        let snapshot = manager.get_history_snapshot("annotated").await.unwrap();
+        let reactions =
+            MessageMetadata::from([("reactions".to_string(), serde_json::json!(["👍"]))]);
+        manager
+            .annotate_message("annotated", &response.id, reactions)
+            .await
+            .unwrap();
+
+        let history = manager.get_conversation_history("annotated").await.unwrap();
+        assert_eq!(
+            history.messages[1].metadata["reactions"],
+            serde_json::json!(["👍"])
+        );
+        // 之前取得的快照不受修改影响
+        assert_eq!(snapshot.len(), 2);
+        assert!(!snapshot[1].metadata.contains_key("reactions"));
+        let stored = store.get_messages(&session.id, 0, 10).await.unwrap();
+        assert_eq!(
+            stored.items[1].metadata["reactions"],
+            serde_json::json!(["👍"])
+        );
+        assert_eq!(
+            stored.items[0].metadata["client_message_id"],
+            serde_json::json!("c-42")
+        );
+
+        assert!(
+            manager
+                .annotate_message("annotated", "missing", MessageMetadata::new())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_purge_user_data() {
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let manager = AgentManager::new(AgentConfig::default())
+            .with_cache_config(ResponseCacheConfig::default().with_enabled(true))
+            .with_session_store(store.clone());
+        manager
+            .create_agent("forgetful".to_string(), None)
+            .await
+            .unwrap();
+        let session = manager.start_session("forgetful", "被遗忘").await.unwrap();
+
+        let key = ResponseCache::key(&AgentConfig::default(), &[Message::user("你好")]);
+        manager.cache.insert(key, "你好！".to_string());
+        let context =
+            RequestContext::new(crate::core::context::RequestOrigin::Http).with_caller_id("alice");
+        manager
+            .chat_with_options(
+                &ClientRegistry::new(),
+                "forgetful",
+                "你好",
+                ChatOptions::default().with_context(context),
+            )
+            .await
+            .unwrap();
+
+        // 保留期限内的数据不受影响
+        let policy = RetentionPolicy::default().with_conversation_days(30);
+        assert_eq!(manager.apply_retention(&policy).await.unwrap().total(), 0);
+
+        let report = manager.purge_user_data("alice").await.unwrap();
+        assert_eq!(report.sessions, 1);
+        assert_eq!(report.messages, 2);
+        assert!(store.get_session(&session.id).await.unwrap().is_none());
+        let history = manager.get_conversation_history("forgetful").await.unwrap();
+        assert!(history.messages.is_empty());
+        assert_eq!(manager.get_cache_stats().entries, 0);
+    }
+
     #[tokio::test]
     async fn test_client_registry() {
         let mut registry = ClientRegistry::new();
@@ -651,6 +3229,7 @@ mod tests {
                 api_key: None,
                 base_url: None,
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             })
             .unwrap();
 
@@ -670,6 +3249,7 @@ mod tests {
                 api_key: None,
                 base_url: None,
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             })
             .unwrap();
 
@@ -680,6 +3260,7 @@ mod tests {
                 api_key: None,
                 base_url: None,
                 extra_params: std::collections::HashMap::new(),
+                streaming: StreamingMode::Auto,
             })
             .unwrap();
 
@@ -817,6 +3398,7 @@ mod tests {
                     api_key: None,
                     base_url: None,
                     extra_params: std::collections::HashMap::new(),
+                    streaming: StreamingMode::Auto,
                 })
                 .unwrap();
 
@@ -827,10 +3409,7 @@ mod tests {
                 .unwrap();
 
             // 获取初始配置
-            let initial_config = manager
-                .get_agent_config("switch_test_agent")
-                .await
-                .unwrap();
+            let initial_config = manager.get_agent_config("switch_test_agent").await.unwrap();
             assert_eq!(initial_config.provider, "openai");
 
             // 切换到 Anthropic
@@ -840,12 +3419,74 @@ mod tests {
                 .unwrap();
 
             // 验证切换成功
-            let new_config = manager
-                .get_agent_config("switch_test_agent")
-                .await
-                .unwrap();
+            let new_config = manager.get_agent_config("switch_test_agent").await.unwrap();
             assert_eq!(new_config.provider, "anthropic");
             assert_eq!(new_config.model, "claude-3-sonnet-20240229");
         }
     }
+
+    #[test]
+    fn test_history_snapshot_is_copy_on_write() {
+        let mut agent = Agent {
+            id: "snapshot".to_string(),
+            config: AgentConfig::default(),
+            turn: Arc::default(),
+            conversation_history: Arc::default(),
+            messages: Arc::default(),
+            created_at: chrono::Utc::now(),
+            last_activity: chrono::Utc::now(),
+        };
+        let mut metadata = MessageMetadata::new();
+        metadata.insert("source".to_string(), serde_json::json!("test"));
+        agent.push_message(
+            Message::user("你好"),
+            MessageMeta::new("m1".to_string(), metadata),
+        );
+        agent.push_message(
+            Message::assistant("你好！"),
+            MessageMeta::new("m2".to_string(), MessageMetadata::new()),
+        );
+
+        // 追加时即转换好，ID 与元数据随消息保存，和对话历史一一对应
+        let snapshot = agent.history_snapshot();
+        assert!(Arc::ptr_eq(&snapshot, &agent.messages));
+        assert_eq!(snapshot.len(), agent.conversation_history.len());
+        assert_eq!(snapshot[0].id, "m1");
+        assert_eq!(snapshot[0].role, AgentRole::User);
+        assert_eq!(snapshot[0].content, "你好");
+        assert_eq!(snapshot[0].metadata["source"], "test");
+        assert_eq!(snapshot[1].id, "m2");
+        assert_eq!(snapshot[1].role, AgentRole::Assistant);
+
+        // 快照被持有时追加会复制列表，快照保持不变
+        agent.push_message(
+            Message::user("再见"),
+            MessageMeta::new("m3".to_string(), MessageMetadata::new()),
+        );
+        assert!(!Arc::ptr_eq(&snapshot, &agent.messages));
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(agent.messages.len(), 3);
+        assert_eq!(agent.conversation_history.len(), 3);
+
+        // 裁剪同时作用于对话历史和转换好的消息
+        assert_eq!(agent.trim_history(1), 2);
+        assert_eq!(agent.messages.len(), 1);
+        assert_eq!(agent.conversation_history.len(), 1);
+        assert_eq!(agent.messages[0].id, "m3");
+        assert_eq!(snapshot.len(), 2);
+    }
+
+    #[test]
+    fn test_reply_language_falls_back_to_locale() {
+        use crate::core::context::RequestOrigin;
+
+        let anonymous = RequestContext::new(RequestOrigin::Local);
+        let context = RequestContext::new(RequestOrigin::Http).with_locale("en-US");
+        assert_eq!(
+            reply_language("今天北京的天气怎么样？", &context).as_deref(),
+            Some("zh")
+        );
+        assert_eq!(reply_language("1 + 1", &context).as_deref(), Some("en"));
+        assert_eq!(reply_language("1 + 1", &anonymous), None);
+    }
 }
@@ -0,0 +1,299 @@
+//! 响应缓存
+//!
+//! 按提供商、模型、Agent 配置与完整消息序列做精确匹配缓存，
+//! 避免 UI 重试或 P2P 重复请求对同一提示重复调用 API。
+
+use crate::core::types::AgentConfig;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// 响应缓存配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// 是否启用缓存
+    pub enabled: bool,
+    /// 缓存有效期
+    pub ttl: Duration,
+    /// 最大缓存条目数
+    pub max_entries: usize,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl: Duration::from_secs(5 * 60),
+            max_entries: 256,
+        }
+    }
+}
+
+impl ResponseCacheConfig {
+    /// 启用或禁用缓存
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// 设置缓存有效期
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// 设置最大缓存条目数
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+}
+
+/// 响应缓存统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// 命中次数
+    pub hits: u64,
+    /// 未命中次数
+    pub misses: u64,
+    /// 被跳过（bypass）的次数
+    pub bypassed: u64,
+    /// 命中率
+    pub hit_rate: f64,
+    /// 当前条目数
+    pub entries: usize,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: String,
+    inserted_at: Instant,
+}
+
+/// 响应缓存
+#[derive(Debug)]
+pub struct ResponseCache {
+    config: ResponseCacheConfig,
+    entries: Mutex<HashMap<u64, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+impl ResponseCache {
+    /// 创建响应缓存
+    pub fn new(config: ResponseCacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            bypassed: AtomicU64::new(0),
+        }
+    }
+
+    /// 是否启用
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// 计算缓存键：提供商、模型、影响输出的配置以及完整消息序列
+    pub fn key(config: &AgentConfig, messages: &[Message]) -> u64 {
+        Self::hash_messages(config, messages.iter())
+    }
+
+    /// 计算一轮对话的缓存键，等同于对 `history` 追加 `message` 后调用 [`ResponseCache::key`]，
+    /// 但不需要复制历史
+    pub fn turn_key(config: &AgentConfig, history: &[Message], message: &Message) -> u64 {
+        Self::hash_messages(config, history.iter().chain(std::iter::once(message)))
+    }
+
+    fn hash_messages<'a>(config: &AgentConfig, messages: impl Iterator<Item = &'a Message>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        config.provider.hash(&mut hasher);
+        config.model.hash(&mut hasher);
+        config.preamble.hash(&mut hasher);
+        config.temperature.map(f32::to_bits).hash(&mut hasher);
+        config.creativity.hash(&mut hasher);
+        config.seed.hash(&mut hasher);
+        config.max_tokens.hash(&mut hasher);
+        // 逐条序列化进哈希，不构造整段 JSON
+        let mut count = 0usize;
+        for message in messages {
+            let _ = serde_json::to_writer(HashWriter(&mut hasher), message);
+            count += 1;
+        }
+        count.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// 查找未过期的缓存响应
+    pub fn get(&self, key: u64) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let hit = match entries.get(&key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.config.ttl => {
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        if hit.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            debug!("响应缓存命中: {:016x}", key);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    /// 写入缓存，超过容量时先清理过期条目，再淘汰最早写入的条目
+    pub fn insert(&self, key: u64, response: String) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.config.max_entries && !entries.contains_key(&key) {
+            let ttl = self.config.ttl;
+            entries.retain(|_, entry| entry.inserted_at.elapsed() < ttl);
+            if entries.len() >= self.config.max_entries
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(key, _)| *key)
+            {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            CacheEntry {
+                response,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// 记录一次跳过缓存的请求
+    pub fn record_bypass(&self) {
+        self.bypassed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 清空缓存（保留统计）
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// 获取缓存统计
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+
+        CacheStats {
+            hits,
+            misses,
+            bypassed: self.bypassed.load(Ordering::Relaxed),
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+/// 把写入的字节直接喂给哈希器
+struct HashWriter<'a>(&'a mut DefaultHasher);
+
+impl std::io::Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(ResponseCacheConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_and_ttl() {
+        let config = AgentConfig::default();
+        let messages = vec![Message::user("你好")];
+        let key = ResponseCache::key(&config, &messages);
+        assert_eq!(key, ResponseCache::key(&config, &messages));
+        let other_model = AgentConfig {
+            model: "other".to_string(),
+            ..config.clone()
+        };
+        assert_ne!(key, ResponseCache::key(&other_model, &messages));
+        assert_ne!(key, ResponseCache::key(&config, &[Message::user("你好！")]));
+        assert_eq!(key, ResponseCache::turn_key(&config, &[], &messages[0]));
+        let history = vec![Message::user("你好"), Message::assistant("世界")];
+        let mut full = history.clone();
+        full.push(Message::user("再见"));
+        assert_eq!(
+            ResponseCache::key(&config, &full),
+            ResponseCache::turn_key(&config, &history, &Message::user("再见"))
+        );
+
+        let cache = ResponseCache::new(ResponseCacheConfig::default().with_enabled(true));
+        assert!(cache.get(key).is_none());
+        cache.insert(key, "世界".to_string());
+        assert_eq!(cache.get(key).as_deref(), Some("世界"));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        let expired = ResponseCache::new(
+            ResponseCacheConfig::default()
+                .with_enabled(true)
+                .with_ttl(Duration::ZERO),
+        );
+        expired.insert(key, "世界".to_string());
+        assert!(expired.get(key).is_none());
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest() {
+        let cache = ResponseCache::new(
+            ResponseCacheConfig::default()
+                .with_enabled(true)
+                .with_max_entries(2),
+        );
+        cache.insert(1, "a".to_string());
+        std::thread::sleep(Duration::from_millis(1));
+        cache.insert(2, "b".to_string());
+        cache.insert(3, "c".to_string());
+
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.get(3).as_deref(), Some("c"));
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
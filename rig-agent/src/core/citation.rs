@@ -0,0 +1,202 @@
+//! 工具输出引用
+//!
+//! 挂载工具的对话中，托管工具把每次调用的工具名、调用 ID、参数与输出记录到本次请求的
+//! [`ToolProvenance`]。拿到最终回答后，按工具输出中的片段是否出现在回答里生成引用，
+//! 界面据此渲染“来源”。没有片段出现在回答中的成功调用仍会被引用，此时引用整个输出。
+
+use crate::core::types::ToolCall;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// 参与匹配的输出片段的最少字符数，过短的片段（数字、单词）容易误匹配
+const MIN_EXCERPT_CHARS: usize = 8;
+
+/// 引用片段的最大字符数
+const MAX_EXCERPT_CHARS: usize = 200;
+
+/// 每次工具调用最多引用的片段数
+const MAX_SPANS_PER_CALL: usize = 3;
+
+/// 切分工具输出的分隔符，JSON 的引号与括号也作为分隔符，使字符串值成为单独的片段
+const SEGMENT_DELIMITERS: &[char] = &[
+    '\n', '"', '{', '}', '[', ']', '。', '！', '？', '；', '!', '?', ';',
+];
+
+/// 被引用片段在工具输出中的字节范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitationSpan {
+    /// 起始字节（含）
+    pub start: usize,
+    /// 结束字节（不含）
+    pub end: usize,
+}
+
+/// 回答对工具输出的引用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Citation {
+    /// 工具名称
+    pub tool: String,
+    /// 工具调用 ID，与响应的 `tool_calls` 对应
+    pub call_id: String,
+    /// 被引用的片段在工具输出中的位置，为空时引用整个输出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<CitationSpan>,
+    /// 被引用的内容，过长时截断
+    pub excerpt: String,
+}
+
+/// 一次工具调用及其输出
+#[derive(Debug, Clone)]
+pub(crate) struct ToolOutput {
+    pub call_id: String,
+    pub tool: String,
+    pub arguments: String,
+    pub output: Result<String, String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ToolOutput {
+    /// 转换为响应中的工具调用记录
+    pub fn to_call(&self) -> ToolCall {
+        ToolCall {
+            id: self.call_id.clone(),
+            name: self.tool.clone(),
+            arguments: self.arguments.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// 一次请求中的工具调用记录，由该请求挂载的全部托管工具共享
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ToolProvenance(Arc<Mutex<Vec<ToolOutput>>>);
+
+impl ToolProvenance {
+    /// 记录一次工具调用
+    pub fn record(&self, output: ToolOutput) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(output);
+    }
+
+    /// 取出全部调用记录，按调用顺序排列
+    pub fn take(&self) -> Vec<ToolOutput> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// 按回答内容生成对各工具输出的引用，失败的调用不引用
+pub(crate) fn cite(response: &str, outputs: &[ToolOutput]) -> Vec<Citation> {
+    let response = response.to_lowercase();
+    let mut citations = Vec::new();
+    for call in outputs {
+        let Ok(output) = &call.output else {
+            continue;
+        };
+        let spans: Vec<CitationSpan> = segments(output)
+            .filter(|span| response.contains(&output[span.start..span.end].to_lowercase()))
+            .take(MAX_SPANS_PER_CALL)
+            .collect();
+        if spans.is_empty() {
+            citations.push(Citation {
+                tool: call.tool.clone(),
+                call_id: call.call_id.clone(),
+                span: None,
+                excerpt: excerpt(output.trim()),
+            });
+            continue;
+        }
+        citations.extend(spans.into_iter().map(|span| Citation {
+            tool: call.tool.clone(),
+            call_id: call.call_id.clone(),
+            span: Some(span),
+            excerpt: excerpt(&output[span.start..span.end]),
+        }));
+    }
+    citations
+}
+
+/// 切分输出为可引用的片段，去掉首尾的空白与标点，过短的片段不参与匹配
+fn segments(output: &str) -> impl Iterator<Item = CitationSpan> + '_ {
+    let mut start = 0;
+    output
+        .match_indices(SEGMENT_DELIMITERS)
+        .map(|(index, delimiter)| (index, index + delimiter.len()))
+        .chain(std::iter::once((output.len(), output.len())))
+        .filter_map(move |(end, next)| {
+            let segment = &output[start..end];
+            let offset = start;
+            start = next;
+            let trimmed =
+                segment.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ':'));
+            let leading = segment.len() - trimmed.len();
+            let trimmed =
+                trimmed.trim_end_matches(|c: char| c.is_whitespace() || matches!(c, ',' | ':'));
+            (trimmed.chars().count() >= MIN_EXCERPT_CHARS).then(|| CitationSpan {
+                start: offset + leading,
+                end: offset + leading + trimmed.len(),
+            })
+        })
+}
+
+/// 截断过长的引用内容
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(MAX_EXCERPT_CHARS) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(call_id: &str, tool: &str, output: Result<&str, &str>) -> ToolOutput {
+        ToolOutput {
+            call_id: call_id.to_string(),
+            tool: tool.to_string(),
+            arguments: "{}".to_string(),
+            output: output.map(str::to_string).map_err(str::to_string),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_cite_tool_outputs() {
+        let search = r#"{"results":[{"title":"Rust 1.80 发布","snippet":"Rust 1.80 stabilizes LazyCell and LazyLock"},{"title":"无关结果","snippet":"nothing to see here"}]}"#;
+        let provenance = ToolProvenance::default();
+        provenance.record(output("c1", "web_search", Ok(search)));
+        provenance.record(output("c2", "get_weather", Ok(r#"{"temp":23}"#)));
+        provenance.record(output("c3", "get_weather", Err("超时")));
+        let outputs = provenance.take();
+        assert_eq!(outputs.len(), 3);
+        assert!(provenance.take().is_empty());
+        assert_eq!(outputs[0].to_call().id, "c1");
+
+        let citations = cite(
+            "根据搜索结果，rust 1.80 stabilizes lazycell and lazylock。今天 23 度。",
+            &outputs,
+        );
+        assert_eq!(citations.len(), 2);
+        let span = citations[0].span.unwrap();
+        assert_eq!(citations[0].call_id, "c1");
+        assert_eq!(
+            &search[span.start..span.end],
+            "Rust 1.80 stabilizes LazyCell and LazyLock"
+        );
+        assert_eq!(
+            citations[0].excerpt,
+            "Rust 1.80 stabilizes LazyCell and LazyLock"
+        );
+        // 没有片段出现在回答中时引用整个输出，失败的调用不引用
+        assert_eq!(citations[1].call_id, "c2");
+        assert_eq!(citations[1].span, None);
+        assert_eq!(citations[1].excerpt, r#"{"temp":23}"#);
+
+        let long = "很长的输出".repeat(100);
+        assert!(excerpt(&long).ends_with('…'));
+        assert_eq!(excerpt(&long).chars().count(), MAX_EXCERPT_CHARS + 1);
+    }
+}
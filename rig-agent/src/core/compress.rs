@@ -0,0 +1,480 @@
+//! 提示词压缩
+//!
+//! 发给昂贵模型前按启发式压缩过长的对话历史（参考 LLMLingua 的思路，但不依赖小模型打分）：
+//!
+//! 1. 去掉客套话、免责声明等样板行，合并多余的空行；
+//! 2. 同一段较长的内容（反复粘贴的日志、代码）只保留最近的一次，较早的替换为省略标记；
+//! 3. 仍超出目标令牌数时，按与当前问题及最近消息的关键词重合度，从关联最低的轮次开始整轮丢弃。
+//!
+//! 最近的若干条消息始终原样保留。压缩只改变发给模型的历史，Agent 保存的对话历史不变，
+//! 被移除的内容记录在响应的压缩报告中
+
+use crate::core::types::{AgentConfig, AgentMessage, AgentRole};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 按字符数估算令牌数，约 4 个字符一个令牌
+const CHARS_PER_TOKEN: usize = 4;
+
+/// 参与去重的段落的最少字符数，过短的段落（“好的”“继续”）重复是正常的
+const MIN_DUPLICATE_CHARS: usize = 40;
+
+/// 移除记录中内容摘要的最大字符数
+const EXCERPT_CHARS: usize = 80;
+
+/// 替换重复段落的省略标记
+const DUPLICATE_MARKER: &str = "[与后文重复的内容已省略]";
+
+/// 整行出现时视为样板的短语（比较前转为小写并去掉首尾标点）
+const BOILERPLATE_LINES: &[&str] = &[
+    "好的",
+    "好",
+    "嗯",
+    "谢谢",
+    "多谢",
+    "谢谢你",
+    "收到",
+    "明白了",
+    "没问题",
+    "不客气",
+    "当然",
+    "当然可以",
+    "ok",
+    "okay",
+    "sure",
+    "thanks",
+    "thank you",
+    "got it",
+    "you're welcome",
+    "certainly",
+    "of course",
+];
+
+/// 以这些内容开头的行视为样板（比较前转为小写）
+const BOILERPLATE_PREFIXES: &[&str] = &[
+    "希望对你有帮助",
+    "希望这对你有帮助",
+    "希望以上内容对你有帮助",
+    "如果你还有其他问题",
+    "如果还有其他问题",
+    "如有其他问题",
+    "作为一个ai",
+    "作为一个人工智能",
+    "作为ai语言模型",
+    "i hope this helps",
+    "hope this helps",
+    "let me know if you have any",
+    "feel free to ask",
+    "as an ai",
+    "great question",
+];
+
+/// 不参与关联度计算的常见英文词
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "your", "with", "this", "that", "have",
+    "from", "was", "what", "how", "can", "will", "please",
+];
+
+fn default_keep_recent() -> usize {
+    6
+}
+
+/// 提示词压缩配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// 目标令牌数（按字符数估算），对话历史不超过该值时不压缩
+    pub target_tokens: u32,
+    /// 只压缩发往这些模型的请求，取值为模型名或 `提供商/模型`，为空时对所有模型生效
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 始终原样保留的最近消息数
+    #[serde(default = "default_keep_recent")]
+    pub keep_recent: usize,
+}
+
+impl CompressionConfig {
+    /// 创建压缩配置
+    pub fn new(target_tokens: u32) -> Self {
+        Self {
+            target_tokens,
+            models: Vec::new(),
+            keep_recent: default_keep_recent(),
+        }
+    }
+
+    /// 只压缩发往指定模型的请求
+    pub fn with_models<I, S>(mut self, models: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.models = models.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 设置始终保留的最近消息数
+    pub fn with_keep_recent(mut self, keep_recent: usize) -> Self {
+        self.keep_recent = keep_recent;
+        self
+    }
+
+    /// 是否压缩发往该配置所用模型的请求
+    pub fn applies_to(&self, config: &AgentConfig) -> bool {
+        self.models.is_empty()
+            || self.models.iter().any(|model| {
+                *model == config.model || *model == format!("{}/{}", config.provider, config.model)
+            })
+    }
+}
+
+/// 内容被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionReason {
+    /// 与后文重复
+    Duplicate,
+    /// 客套话等样板内容
+    Boilerplate,
+    /// 与当前问题关联度低的轮次
+    LowSalience,
+}
+
+/// 一条被移除的内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Removal {
+    /// 所在消息的 ID
+    pub message_id: String,
+    /// 移除原因
+    pub reason: CompressionReason,
+    /// 节省的令牌数（估算）
+    pub tokens: u32,
+    /// 被移除的内容，过长时截断
+    pub excerpt: String,
+}
+
+/// 压缩报告
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionReport {
+    /// 压缩前对话历史的令牌数（估算）
+    pub original_tokens: u32,
+    /// 压缩后对话历史的令牌数（估算）
+    pub compressed_tokens: u32,
+    /// 目标令牌数
+    pub target_tokens: u32,
+    /// 被移除的内容，按消息顺序排列
+    pub removed: Vec<Removal>,
+}
+
+/// 压缩结果：保留的消息下标，内容被改写时附带新内容
+#[derive(Debug)]
+pub(crate) struct Compression {
+    pub kept: Vec<(usize, Option<String>)>,
+    pub report: CompressionReport,
+}
+
+/// 估算文本的令牌数
+fn tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u32
+}
+
+/// 压缩发给模型的对话历史，`query` 为当前的用户消息；历史不超过目标令牌数时返回 `None`
+pub(crate) fn compress(
+    messages: &[AgentMessage],
+    query: &str,
+    config: &CompressionConfig,
+) -> Option<Compression> {
+    let original_tokens: u32 = messages.iter().map(|m| tokens(&m.content)).sum();
+    if original_tokens <= config.target_tokens {
+        return None;
+    }
+
+    // 保留区从一轮对话的开头算起，避免丢弃轮次时留下没有提问的回答
+    let mut protected_from = messages.len().saturating_sub(config.keep_recent);
+    while protected_from > 0 && messages[protected_from].role != AgentRole::User {
+        protected_from -= 1;
+    }
+
+    let mut texts: Vec<Option<String>> = messages.iter().map(|m| Some(m.content.clone())).collect();
+    let mut removed = Vec::new();
+
+    // 1. 样板内容
+    for (index, message) in messages[..protected_from].iter().enumerate() {
+        let (stripped, dropped) = strip_boilerplate(&message.content);
+        if stripped.trim().is_empty() || stripped == message.content {
+            continue;
+        }
+        if !dropped.is_empty() {
+            removed.push(removal(
+                message,
+                CompressionReason::Boilerplate,
+                tokens(&message.content).saturating_sub(tokens(&stripped)),
+                &dropped.join(" "),
+            ));
+        }
+        texts[index] = Some(stripped);
+    }
+
+    // 2. 重复段落，只保留最近的一次
+    let mut seen = HashSet::new();
+    for index in (0..messages.len()).rev() {
+        let Some(text) = texts[index].as_deref() else {
+            continue;
+        };
+        if index >= protected_from {
+            seen.extend(paragraphs(text).map(normalize).filter(|p| is_dedupable(p)));
+            continue;
+        }
+        let mut duplicates = Vec::new();
+        let mut kept = Vec::new();
+        for paragraph in paragraphs(text) {
+            let normalized = normalize(paragraph);
+            if is_dedupable(&normalized) && !seen.insert(normalized) {
+                duplicates.push(paragraph);
+                if kept.last() != Some(&DUPLICATE_MARKER) {
+                    kept.push(DUPLICATE_MARKER);
+                }
+            } else {
+                kept.push(paragraph);
+            }
+        }
+        if duplicates.is_empty() {
+            continue;
+        }
+        let deduped = kept.join("\n\n");
+        removed.push(removal(
+            &messages[index],
+            CompressionReason::Duplicate,
+            tokens(text).saturating_sub(tokens(&deduped)),
+            &duplicates.join(" "),
+        ));
+        texts[index] = Some(deduped);
+    }
+
+    // 3. 关联度低的轮次
+    let mut total: u32 = texts.iter().flatten().map(|text| tokens(text)).sum();
+    if total > config.target_tokens {
+        let mut context = keywords(query);
+        for text in texts[protected_from..].iter().flatten() {
+            context.extend(keywords(text));
+        }
+        let mut turns = turns(&messages[..protected_from])
+            .into_iter()
+            .map(|turn| {
+                let turn_keywords: HashSet<String> = turn
+                    .clone()
+                    .filter_map(|index| texts[index].as_deref())
+                    .flat_map(keywords)
+                    .collect();
+                let overlap = turn_keywords.intersection(&context).count() as f64;
+                let score = overlap / (turn_keywords.len() as f64).sqrt().max(1.0);
+                (score, turn)
+            })
+            .collect::<Vec<_>>();
+        // 关联度相同时先丢弃较早的轮次
+        turns.sort_by(|(a, a_turn), (b, b_turn)| {
+            a.total_cmp(b).then_with(|| a_turn.start.cmp(&b_turn.start))
+        });
+        for (_, turn) in turns {
+            if total <= config.target_tokens {
+                break;
+            }
+            for index in turn {
+                let Some(text) = texts[index].take() else {
+                    continue;
+                };
+                total = total.saturating_sub(tokens(&text));
+                removed.push(removal(
+                    &messages[index],
+                    CompressionReason::LowSalience,
+                    tokens(&text),
+                    &text,
+                ));
+            }
+        }
+    }
+
+    let position = |id: &str| messages.iter().position(|m| m.id == id);
+    removed.sort_by_key(|removal: &Removal| position(&removal.message_id));
+    let kept = texts
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, text)| {
+            let text = text?;
+            Some((index, (text != messages[index].content).then_some(text)))
+        })
+        .collect::<Vec<_>>();
+    let compressed_tokens = kept
+        .iter()
+        .map(|(index, text)| tokens(text.as_deref().unwrap_or(&messages[*index].content)))
+        .sum();
+    Some(Compression {
+        kept,
+        report: CompressionReport {
+            original_tokens,
+            compressed_tokens,
+            target_tokens: config.target_tokens,
+            removed,
+        },
+    })
+}
+
+fn removal(message: &AgentMessage, reason: CompressionReason, tokens: u32, text: &str) -> Removal {
+    Removal {
+        message_id: message.id.clone(),
+        reason,
+        tokens,
+        excerpt: excerpt(text.trim()),
+    }
+}
+
+fn excerpt(text: &str) -> String {
+    match text.char_indices().nth(EXCERPT_CHARS) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+/// 去掉样板行并合并多余的空行，返回处理后的内容与被去掉的行
+fn strip_boilerplate(text: &str) -> (String, Vec<&str>) {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut dropped = Vec::new();
+    for line in text.lines() {
+        if is_boilerplate(line) {
+            dropped.push(line.trim());
+            continue;
+        }
+        let blank = line.trim().is_empty();
+        if blank && lines.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line.trim_end());
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    (lines.join("\n"), dropped)
+}
+
+fn is_boilerplate(line: &str) -> bool {
+    let line = line
+        .trim()
+        .trim_matches(|c: char| c.is_ascii_punctuation() || "！。，～、…".contains(c))
+        .to_lowercase();
+    !line.is_empty()
+        && (BOILERPLATE_LINES.contains(&line.as_str())
+            || BOILERPLATE_PREFIXES
+                .iter()
+                .any(|prefix| line.starts_with(prefix)))
+}
+
+/// 按空行切分段落
+fn paragraphs(text: &str) -> impl Iterator<Item = &str> {
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+}
+
+/// 去重时比较的形式：合并空白
+fn normalize(paragraph: &str) -> String {
+    paragraph.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn is_dedupable(paragraph: &str) -> bool {
+    paragraph != DUPLICATE_MARKER && paragraph.chars().count() >= MIN_DUPLICATE_CHARS
+}
+
+/// 按用户消息切分轮次，每轮包括一条用户消息与其后的回复
+fn turns(messages: &[AgentMessage]) -> Vec<std::ops::Range<usize>> {
+    let mut turns: Vec<std::ops::Range<usize>> = Vec::new();
+    for (index, message) in messages.iter().enumerate() {
+        match turns.last_mut() {
+            Some(turn) if message.role != AgentRole::User => turn.end = index + 1,
+            _ => turns.push(index..index + 1),
+        }
+    }
+    turns
+}
+
+/// 关联度计算用的关键词：长度不小于 3 的英文单词与数字，以及相邻汉字组成的二元组
+fn keywords(text: &str) -> HashSet<String> {
+    let mut keywords = HashSet::new();
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+        let word = word.to_ascii_lowercase();
+        if word.len() >= 3 && !STOP_WORDS.contains(&word.as_str()) {
+            keywords.insert(word);
+        }
+    }
+    let chars: Vec<char> = text.chars().collect();
+    for pair in chars.windows(2) {
+        if pair.iter().all(|c| is_cjk(*c)) {
+            keywords.insert(pair.iter().collect());
+        }
+    }
+    keywords
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_history() {
+        let log = "error[E0502]: cannot borrow `agents` as mutable because it is also borrowed as immutable";
+        let messages = vec![
+            AgentMessage::user("今天天气怎么样？适合去公园散步吗".to_string()),
+            AgentMessage::assistant(
+                "今天晴，气温 23 度，很适合去公园散步。\n\n希望对你有帮助！".to_string(),
+            ),
+            AgentMessage::user(format!("编译报错了：\n\n{}", log)),
+            AgentMessage::assistant(
+                "好的\n\n\n\n这是借用冲突，先结束不可变借用再修改 agents。".to_string(),
+            ),
+            AgentMessage::user(format!(
+                "改完还是报错：\n\n{}\n\n怎么修复借用 agents 的问题",
+                log
+            )),
+            AgentMessage::assistant("把读取放进单独的作用域。".to_string()),
+        ];
+        let total: u32 = messages.iter().map(|m| tokens(&m.content)).sum();
+        let config = CompressionConfig::new(total).with_keep_recent(2);
+        assert!(compress(&messages, "怎么修复", &config).is_none());
+
+        let config = CompressionConfig::new(total - 30).with_keep_recent(2);
+        let compression = compress(&messages, "借用 agents 还是报错", &config).unwrap();
+        let report = &compression.report;
+        assert!(report.compressed_tokens <= report.target_tokens);
+        assert!(report.compressed_tokens < report.original_tokens);
+
+        let reasons: Vec<(&str, CompressionReason)> = report
+            .removed
+            .iter()
+            .map(|r| (r.message_id.as_str(), r.reason))
+            .collect();
+        // 与当前问题无关的天气轮次被整轮丢弃，样板行去掉，较早粘贴的日志只保留最近一次
+        assert!(reasons.contains(&(messages[0].id.as_str(), CompressionReason::LowSalience)));
+        assert!(reasons.contains(&(messages[1].id.as_str(), CompressionReason::LowSalience)));
+        assert!(reasons.contains(&(messages[2].id.as_str(), CompressionReason::Duplicate)));
+        assert!(reasons.contains(&(messages[3].id.as_str(), CompressionReason::Boilerplate)));
+        let kept: Vec<usize> = compression.kept.iter().map(|(index, _)| *index).collect();
+        assert_eq!(kept, [2, 3, 4, 5]);
+        assert_eq!(
+            compression.kept[0].1.as_deref(),
+            Some(format!("编译报错了：\n\n{}", DUPLICATE_MARKER).as_str())
+        );
+        assert_eq!(
+            compression.kept[1].1.as_deref(),
+            Some("这是借用冲突，先结束不可变借用再修改 agents。")
+        );
+        // 保留区的消息原样发送
+        assert_eq!(compression.kept[2].1, None);
+
+        let config = CompressionConfig::new(100).with_models(["openai/gpt-4o"]);
+        assert!(config.applies_to(&AgentConfig::new("openai", "gpt-4o")));
+        assert!(!config.applies_to(&AgentConfig::new("ollama", "gpt-4o")));
+        assert!(CompressionConfig::new(100).applies_to(&AgentConfig::default()));
+    }
+}
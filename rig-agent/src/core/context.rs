@@ -0,0 +1,255 @@
+//! 请求上下文
+//!
+//! 记录一次请求的调用方身份、来源适配器、跟踪 ID、语言区域与截止时间。适配器在入口处创建上下文：
+//! 通过 [`ChatOptions::with_context`](crate::core::ChatOptions::with_context) 传给 AgentManager，
+//! 或用 [`RequestContext::scope`] 包住整个调用。AgentManager 在该上下文中执行请求：
+//!
+//! - 日志带上 `trace_id` 等字段，工具可以通过 [`RequestContext::current`] 取得调用方信息
+//! - 截止时间收紧模型调用的超时时间
+//! - 无法从消息中检测出语言时，按语言区域决定回复语言
+//! - 调用方信息写入用户消息的元数据，每次对话与工具调用记录一条审计日志（目标为 [`AUDIT_LOG_TARGET`]）
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{Instrument, info, info_span, warn};
+
+/// 审计日志的 tracing 目标，可以单独过滤或输出到独立的文件
+pub const AUDIT_LOG_TARGET: &str = "rig_agent::audit";
+
+/// 消息元数据中的跟踪 ID
+pub const TRACE_ID_KEY: &str = "trace_id";
+/// 消息元数据中的调用方 ID
+pub const CALLER_ID_KEY: &str = "caller_id";
+/// 消息元数据中的请求来源
+pub const ORIGIN_KEY: &str = "origin";
+
+tokio::task_local! {
+    static CURRENT: RequestContext;
+}
+
+/// 请求来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestOrigin {
+    /// 进程内直接调用
+    #[default]
+    Local,
+    /// HTTP 接口
+    Http,
+    /// Tauri 命令
+    Tauri,
+    /// P2P 网络中的其他节点
+    P2p,
+    /// 电子邮件
+    Email,
+}
+
+impl RequestOrigin {
+    /// 来源名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Http => "http",
+            Self::Tauri => "tauri",
+            Self::P2p => "p2p",
+            Self::Email => "email",
+        }
+    }
+}
+
+/// 请求上下文
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestContext {
+    /// 调用方 ID（用户、节点 ID、邮件地址等），匿名调用为空
+    pub caller_id: Option<String>,
+    /// 请求来源
+    pub origin: RequestOrigin,
+    /// 跟踪 ID
+    pub trace_id: String,
+    /// 语言区域，如 `zh-CN`
+    pub locale: Option<String>,
+    /// 截止时间，超过后模型调用以超时失败
+    pub deadline: Option<DateTime<Utc>>,
+}
+
+impl RequestContext {
+    /// 创建指定来源的上下文，生成新的跟踪 ID
+    pub fn new(origin: RequestOrigin) -> Self {
+        Self {
+            caller_id: None,
+            origin,
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            locale: None,
+            deadline: None,
+        }
+    }
+
+    /// 设置调用方 ID
+    pub fn with_caller_id<S: Into<String>>(mut self, caller_id: S) -> Self {
+        self.caller_id = Some(caller_id.into());
+        self
+    }
+
+    /// 沿用上游的跟踪 ID
+    pub fn with_trace_id<S: Into<String>>(mut self, trace_id: S) -> Self {
+        self.trace_id = trace_id.into();
+        self
+    }
+
+    /// 设置语言区域
+    pub fn with_locale<S: Into<String>>(mut self, locale: S) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// 设置截止时间
+    pub fn with_deadline(mut self, deadline: DateTime<Utc>) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// 设置从现在起的剩余时间，超出时间范围时不设截止时间
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = chrono::Duration::from_std(timeout)
+            .ok()
+            .and_then(|timeout| Utc::now().checked_add_signed(timeout));
+        self
+    }
+
+    /// 距离截止时间的剩余毫秒数，没有截止时间时为空
+    pub fn remaining_ms(&self) -> Option<u64> {
+        self.deadline
+            .map(|deadline| (deadline - Utc::now()).num_milliseconds().max(0) as u64)
+    }
+
+    /// 用截止时间收紧超时时间
+    pub fn cap_timeout(&self, timeout_ms: Option<u64>) -> Option<u64> {
+        match (timeout_ms, self.remaining_ms()) {
+            (Some(timeout_ms), Some(remaining)) => Some(timeout_ms.min(remaining)),
+            (timeout_ms, remaining) => timeout_ms.or(remaining),
+        }
+    }
+
+    /// 语言区域的主语言代码，如 `zh-CN` 为 `zh`
+    pub fn language(&self) -> Option<String> {
+        self.locale
+            .as_deref()
+            .and_then(|locale| locale.split(['-', '_']).next())
+            .filter(|language| !language.is_empty())
+            .map(str::to_lowercase)
+    }
+
+    /// 当前任务作用域中的上下文
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// 在该上下文中执行 `future`，其中的日志带上上下文字段，工具调用可以取得上下文
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        let span = info_span!(
+            "request",
+            trace_id = %self.trace_id,
+            caller_id = self.caller_id.as_deref().unwrap_or("-"),
+            origin = self.origin.as_str(),
+        );
+        CURRENT.scope(self, future.instrument(span)).await
+    }
+
+    /// 本次请求使用的上下文：优先使用显式传入的上下文，其次是外层作用域的上下文，都没有时创建本地上下文
+    pub(crate) fn resolve(context: Option<Self>) -> Self {
+        context
+            .or_else(Self::current)
+            .unwrap_or_else(|| Self::new(RequestOrigin::Local))
+    }
+
+    /// 写入用户消息元数据的调用方信息
+    pub(crate) fn metadata(&self) -> impl Iterator<Item = (String, serde_json::Value)> {
+        [
+            Some((TRACE_ID_KEY, self.trace_id.clone())),
+            self.caller_id
+                .clone()
+                .map(|caller_id| (CALLER_ID_KEY, caller_id)),
+            Some((ORIGIN_KEY, self.origin.as_str().to_string())),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| (key.to_string(), value.into()))
+    }
+
+    /// 记录一条审计日志
+    pub(crate) fn audit(
+        &self,
+        action: &str,
+        resource: &str,
+        duration_ms: u64,
+        error: Option<&str>,
+    ) {
+        let caller_id = self.caller_id.as_deref().unwrap_or("-");
+        match error {
+            None => info!(
+                target: AUDIT_LOG_TARGET,
+                trace_id = %self.trace_id,
+                caller_id,
+                origin = self.origin.as_str(),
+                action,
+                resource,
+                duration_ms,
+                "请求成功"
+            ),
+            Some(error) => warn!(
+                target: AUDIT_LOG_TARGET,
+                trace_id = %self.trace_id,
+                caller_id,
+                origin = self.origin.as_str(),
+                action,
+                resource,
+                duration_ms,
+                error,
+                "请求失败"
+            ),
+        }
+    }
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self::new(RequestOrigin::Local)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_context_scope() {
+        let context = RequestContext::new(RequestOrigin::Http)
+            .with_caller_id("alice")
+            .with_locale("zh-CN")
+            .with_timeout(Duration::from_secs(10));
+        assert_eq!(context.trace_id.len(), 32);
+        assert_eq!(context.language().as_deref(), Some("zh"));
+        assert!(context.cap_timeout(Some(60_000)).unwrap() <= 10_000);
+        assert_eq!(context.cap_timeout(Some(1_000)), Some(1_000));
+        assert_eq!(RequestContext::default().cap_timeout(None), None);
+
+        // 作用域内可以取得上下文，显式传入的上下文优先
+        assert!(RequestContext::current().is_none());
+        let inner = context
+            .clone()
+            .scope(async {
+                let current = RequestContext::current().unwrap();
+                assert_eq!(current.caller_id.as_deref(), Some("alice"));
+                RequestContext::resolve(Some(RequestContext::new(RequestOrigin::Tauri)))
+            })
+            .await;
+        assert_eq!(inner.origin, RequestOrigin::Tauri);
+        assert_eq!(RequestContext::resolve(None).origin, RequestOrigin::Local);
+
+        let metadata: Vec<_> = context.metadata().collect();
+        assert_eq!(metadata.len(), 3);
+        assert_eq!(metadata[1], (CALLER_ID_KEY.to_string(), "alice".into()));
+    }
+}
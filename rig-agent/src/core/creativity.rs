@@ -0,0 +1,157 @@
+//! 创造性配置 - 把“精确 / 均衡 / 创意”三档映射为一组采样参数。
+//!
+//! 温度对所有提供商生效；`top_p` 与存在、频率惩罚只发给支持它们的提供商，
+//! 其余提供商忽略这些参数。配置可以设在 Agent 上，也可以按消息切换，
+//! 所用的配置记录在响应与助手消息的元数据中，便于复现
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// 助手消息元数据中记录所用创造性配置的键
+pub const CREATIVITY_KEY: &str = "creativity";
+
+/// 支持 `top_p` 与存在、频率惩罚的 OpenAI 兼容提供商
+const OPENAI_COMPATIBLE: &[&str] = &[
+    "openai",
+    "azure",
+    "deepseek",
+    "groq",
+    "mistral",
+    "openrouter",
+    "perplexity",
+    "together",
+    "xai",
+];
+
+/// 只支持 `top_p` 的提供商
+const TOP_P_ONLY: &[&str] = &["anthropic"];
+
+/// 创造性配置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreativityProfile {
+    /// 精确：低温度，适合代码、事实问答与数据提取
+    Precise,
+    /// 均衡：默认的对话参数
+    #[default]
+    Balanced,
+    /// 创意：高温度并惩罚重复，适合写作与头脑风暴
+    Creative,
+}
+
+/// 一组采样参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SamplingParams {
+    /// 温度
+    pub temperature: f32,
+    /// 核采样概率
+    pub top_p: f32,
+    /// 存在惩罚
+    pub presence_penalty: f32,
+    /// 频率惩罚
+    pub frequency_penalty: f32,
+}
+
+impl CreativityProfile {
+    /// 全部配置
+    pub const ALL: [CreativityProfile; 3] = [Self::Precise, Self::Balanced, Self::Creative];
+
+    /// 配置名称
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Precise => "precise",
+            Self::Balanced => "balanced",
+            Self::Creative => "creative",
+        }
+    }
+
+    /// 该配置对应的采样参数
+    pub fn params(self) -> SamplingParams {
+        match self {
+            Self::Precise => SamplingParams {
+                temperature: 0.1,
+                top_p: 0.5,
+                presence_penalty: 0.0,
+                frequency_penalty: 0.0,
+            },
+            Self::Balanced => SamplingParams {
+                temperature: 0.7,
+                top_p: 0.9,
+                presence_penalty: 0.0,
+                frequency_penalty: 0.0,
+            },
+            Self::Creative => SamplingParams {
+                temperature: 1.1,
+                top_p: 0.95,
+                presence_penalty: 0.6,
+                frequency_penalty: 0.3,
+            },
+        }
+    }
+}
+
+impl fmt::Display for CreativityProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CreativityProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|profile| profile.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("未知的创造性配置: {}", s))
+    }
+}
+
+impl SamplingParams {
+    /// 温度之外、提供商支持的采样参数，作为请求的附加参数发送；都不支持时返回 `None`
+    pub fn additional_params(&self, provider: &str) -> Option<serde_json::Value> {
+        if OPENAI_COMPATIBLE.contains(&provider) {
+            Some(serde_json::json!({
+                "top_p": self.top_p,
+                "presence_penalty": self.presence_penalty,
+                "frequency_penalty": self.frequency_penalty,
+            }))
+        } else if TOP_P_ONLY.contains(&provider) {
+            Some(serde_json::json!({ "top_p": self.top_p }))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_creativity_profiles() {
+        let temperatures: Vec<f32> = CreativityProfile::ALL
+            .iter()
+            .map(|profile| profile.params().temperature)
+            .collect();
+        assert!(temperatures.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(CreativityProfile::default(), CreativityProfile::Balanced);
+        assert_eq!(
+            " Creative".parse::<CreativityProfile>(),
+            Ok(CreativityProfile::Creative)
+        );
+        assert!("wild".parse::<CreativityProfile>().is_err());
+        assert_eq!(
+            serde_json::to_value(CreativityProfile::Precise).unwrap(),
+            serde_json::json!("precise")
+        );
+
+        let creative = CreativityProfile::Creative.params();
+        let openai = creative.additional_params("openai").unwrap();
+        assert_eq!(openai["presence_penalty"], serde_json::json!(0.6f32));
+        let anthropic = creative.additional_params("anthropic").unwrap();
+        assert!(anthropic.get("presence_penalty").is_none());
+        assert!(creative.additional_params("ollama").is_none());
+    }
+}
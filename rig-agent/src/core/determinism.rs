@@ -0,0 +1,236 @@
+//! 确定性模式与请求指纹
+//!
+//! 支持种子的提供商随请求发送 `seed`。确定性模式用于回归测试：固定种子（未设置时使用
+//! [`DEFAULT_SEED`]），温度设为 0 并忽略创造性配置，A/B 路由固定使用对照变体，不竞速也不使用
+//! 响应缓存，并在响应中记录完整的请求指纹。指纹相同的两次请求发给模型的内容与参数完全相同，
+//! 输出仍不同时说明差异来自提供商一侧。
+//!
+//! 摘要使用 FNV-1a，不随 Rust 版本变化，可以保存在测试快照中
+
+use crate::core::creativity::CreativityProfile;
+use crate::core::types::AgentConfig;
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 确定性模式未设置种子时使用的种子
+pub const DEFAULT_SEED: u64 = 42;
+
+/// 以 `seed` 参数接收种子的提供商
+const SEED_PROVIDERS: &[&str] = &[
+    "openai",
+    "azure",
+    "groq",
+    "openrouter",
+    "together",
+    "xai",
+    "cohere",
+];
+
+/// 以 `random_seed` 参数接收种子的提供商
+const RANDOM_SEED_PROVIDERS: &[&str] = &["mistral"];
+
+/// 提供商接收种子的参数名，不支持种子时返回 `None`
+fn seed_param(provider: &str) -> Option<&'static str> {
+    if SEED_PROVIDERS.contains(&provider) {
+        Some("seed")
+    } else if RANDOM_SEED_PROVIDERS.contains(&provider) {
+        Some("random_seed")
+    } else {
+        None
+    }
+}
+
+/// 实际随请求发送的采样参数
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RequestParams {
+    pub temperature: Option<f32>,
+    /// 温度之外的附加参数
+    pub additional: Option<Value>,
+}
+
+/// 按配置生成随请求发送的采样参数：确定性模式下温度为 0 并忽略创造性配置，
+/// 否则创造性配置的温度覆盖配置中的温度；提供商支持时附带种子
+pub(crate) fn request_params(config: &AgentConfig) -> RequestParams {
+    let provider = config.provider.as_str();
+    let mut additional = serde_json::Map::new();
+    let temperature = if config.deterministic {
+        Some(0.0)
+    } else {
+        let sampling = config.creativity.map(CreativityProfile::params);
+        if let Some(Value::Object(params)) =
+            sampling.and_then(|params| params.additional_params(provider))
+        {
+            additional.extend(params);
+        }
+        sampling
+            .map(|params| params.temperature)
+            .or(config.temperature)
+    };
+    if let (Some(seed), Some(param)) = (config.effective_seed(), seed_param(provider)) {
+        additional.insert(param.to_string(), seed.into());
+    }
+    RequestParams {
+        temperature,
+        additional: (!additional.is_empty()).then_some(Value::Object(additional)),
+    }
+}
+
+/// 请求指纹：发给模型的全部内容与参数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestFingerprint {
+    /// 整个请求的摘要，内容与参数都相同时相同
+    pub digest: String,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 温度
+    pub temperature: Option<f32>,
+    /// 最大令牌数
+    pub max_tokens: Option<u32>,
+    /// 种子
+    pub seed: Option<u64>,
+    /// 提供商是否支持种子，不支持时相同的请求仍可能得到不同的输出
+    pub seed_supported: bool,
+    /// 温度之外随请求发送的参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    /// 系统提示词的摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preamble_digest: Option<String>,
+    /// 发给模型的历史消息数
+    pub history_len: usize,
+    /// 发给模型的历史的摘要
+    pub history_digest: String,
+    /// 用户消息的摘要
+    pub message_digest: String,
+    /// 挂载的工具，按名称排序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+}
+
+impl RequestFingerprint {
+    /// 按本次请求实际使用的配置、历史、用户消息与工具生成指纹
+    pub(crate) fn new(
+        config: &AgentConfig,
+        history: &[Message],
+        message: &str,
+        mut tools: Vec<String>,
+    ) -> Self {
+        tools.sort();
+        let params = request_params(config);
+        let mut history_digest = Fnv::default();
+        for message in history {
+            let _ = serde_json::to_writer(&mut history_digest, message);
+        }
+        let mut fingerprint = Self {
+            digest: String::new(),
+            provider: config.provider.clone(),
+            model: config.model.clone(),
+            temperature: params.temperature,
+            max_tokens: config.max_tokens,
+            seed: config.effective_seed(),
+            seed_supported: seed_param(&config.provider).is_some(),
+            params: params.additional,
+            preamble_digest: config.preamble.as_deref().map(digest),
+            history_len: history.len(),
+            history_digest: history_digest.hex(),
+            message_digest: digest(message),
+            tools,
+        };
+        let mut whole = Fnv::default();
+        let _ = serde_json::to_writer(&mut whole, &fingerprint);
+        fingerprint.digest = whole.hex();
+        fingerprint
+    }
+}
+
+/// 文本的 FNV-1a 摘要
+fn digest(text: &str) -> String {
+    let mut hasher = Fnv::default();
+    hasher.update(text.as_bytes());
+    hasher.hex()
+}
+
+/// 64 位 FNV-1a
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+impl std::io::Write for Fnv {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deterministic_request() {
+        // FNV-1a 的标准测试向量
+        assert_eq!(digest(""), "cbf29ce484222325");
+        assert_eq!(digest("a"), "af63dc4c8601ec8c");
+
+        let creative =
+            AgentConfig::new("openai", "gpt-4o-mini").with_creativity(CreativityProfile::Creative);
+        let params = request_params(&creative);
+        assert_eq!(params.temperature, Some(1.1));
+        assert!(params.additional.unwrap().get("seed").is_none());
+
+        let deterministic = creative.clone().with_deterministic(true);
+        let params = request_params(&deterministic);
+        assert_eq!(params.temperature, Some(0.0));
+        assert_eq!(params.additional, Some(json!({ "seed": DEFAULT_SEED })));
+        let seeded = AgentConfig::new("mistral", "mistral-small").with_seed(7);
+        assert_eq!(
+            request_params(&seeded).additional,
+            Some(json!({ "random_seed": 7 }))
+        );
+
+        let history = vec![Message::user("你好"), Message::assistant("你好！")];
+        let tools = vec!["web.search".to_string(), "builtin.calculator".to_string()];
+        let fingerprint = RequestFingerprint::new(&deterministic, &history, "1+1", tools.clone());
+        assert_eq!(
+            fingerprint,
+            RequestFingerprint::new(&deterministic, &history, "1+1", tools.clone())
+        );
+        assert_eq!(fingerprint.tools, ["builtin.calculator", "web.search"]);
+        assert_eq!(fingerprint.seed, Some(DEFAULT_SEED));
+        assert!(fingerprint.seed_supported);
+        assert_ne!(
+            fingerprint.digest,
+            RequestFingerprint::new(&deterministic, &history[..1], "1+1", tools).digest
+        );
+
+        let anthropic = AgentConfig::new("anthropic", "claude-3-5-haiku").with_deterministic(true);
+        let fingerprint = RequestFingerprint::new(&anthropic, &[], "1+1", Vec::new());
+        assert!(!fingerprint.seed_supported);
+        assert_eq!(fingerprint.params, None);
+    }
+}
@@ -0,0 +1,195 @@
+//! 回复反馈 - 用户对助手回复的点赞/点踩与文字意见
+//!
+//! 反馈保存在助手消息的元数据中，随对话一起持久化；
+//! 带反馈的问答对可以导出为 JSON Lines，用于之后整理微调数据集。
+
+use crate::core::types::{AgentMessage, AgentRole, MessageMetadata};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 消息元数据中保存反馈的键
+pub const FEEDBACK_KEY: &str = "feedback";
+
+/// 反馈评价
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackRating {
+    /// 点赞
+    Positive,
+    /// 点踩
+    Negative,
+}
+
+impl FeedbackRating {
+    /// 是否为正面评价
+    pub fn is_positive(self) -> bool {
+        self == Self::Positive
+    }
+}
+
+/// 对一条回复的反馈
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseFeedback {
+    /// 评价
+    #[serde(default)]
+    pub rating: Option<FeedbackRating>,
+    /// 文字意见
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// 提交时间
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl ResponseFeedback {
+    /// 点赞或点踩
+    pub fn rating(rating: FeedbackRating) -> Self {
+        Self {
+            rating: Some(rating),
+            comment: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// 只有文字意见
+    pub fn comment<S: Into<String>>(comment: S) -> Self {
+        Self {
+            rating: None,
+            comment: Some(comment.into()),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// 设置文字意见
+    pub fn with_comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// 是否为空反馈
+    pub fn is_empty(&self) -> bool {
+        self.rating.is_none() && self.comment.as_deref().is_none_or(|c| c.trim().is_empty())
+    }
+
+    /// 在之前的反馈上合并：未提供的评价或意见沿用之前的值
+    pub fn merged_with(mut self, previous: Option<&ResponseFeedback>) -> Self {
+        if let Some(previous) = previous {
+            self.rating = self.rating.or(previous.rating);
+            if self.comment.is_none() {
+                self.comment = previous.comment.clone();
+            }
+        }
+        self
+    }
+
+    /// 从消息元数据中读取反馈
+    pub fn from_metadata(metadata: &MessageMetadata) -> Option<Self> {
+        serde_json::from_value(metadata.get(FEEDBACK_KEY)?.clone()).ok()
+    }
+
+    /// 写入消息元数据的注解
+    pub fn to_annotations(&self) -> MessageMetadata {
+        MessageMetadata::from([(
+            FEEDBACK_KEY.to_string(),
+            serde_json::to_value(self).unwrap_or_default(),
+        )])
+    }
+}
+
+/// 带反馈的一组问答，用于导出数据集
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    /// Agent ID
+    pub agent_id: String,
+    /// 回复（助手消息）ID
+    pub response_id: String,
+    /// 用户提示
+    pub prompt: String,
+    /// 助手回复
+    pub response: String,
+    /// 反馈
+    pub feedback: ResponseFeedback,
+}
+
+/// 从对话记录中取出带反馈的问答，提示为回复前最近的一条用户消息
+pub fn feedback_records(agent_id: &str, messages: &[AgentMessage]) -> Vec<FeedbackRecord> {
+    let mut records = Vec::new();
+    let mut prompt: Option<&str> = None;
+
+    for message in messages {
+        match message.role {
+            AgentRole::User => prompt = Some(&message.content),
+            AgentRole::Assistant => {
+                let feedback = ResponseFeedback::from_metadata(&message.metadata);
+                if let (Some(prompt), Some(feedback)) = (prompt, feedback) {
+                    records.push(FeedbackRecord {
+                        agent_id: agent_id.to_string(),
+                        response_id: message.id.clone(),
+                        prompt: prompt.to_string(),
+                        response: message.content.clone(),
+                        feedback,
+                    });
+                }
+            }
+            AgentRole::System | AgentRole::Tool => {}
+        }
+    }
+
+    records
+}
+
+/// 导出为 JSON Lines，每行一组对话消息与反馈，可直接整理为微调数据
+pub fn feedback_to_jsonl(records: &[FeedbackRecord]) -> String {
+    records
+        .iter()
+        .map(|record| {
+            serde_json::json!({
+                "messages": [
+                    { "role": "user", "content": record.prompt },
+                    { "role": "assistant", "content": record.response },
+                ],
+                "agent_id": record.agent_id,
+                "response_id": record.response_id,
+                "rating": record.feedback.rating,
+                "comment": record.feedback.comment,
+                "created_at": record.feedback.created_at,
+            })
+            .to_string()
+                + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_records() {
+        let feedback = ResponseFeedback::rating(FeedbackRating::Negative);
+        let mut answer = AgentMessage::assistant("等于 3".to_string());
+        answer.metadata = feedback.to_annotations();
+        let messages = vec![
+            AgentMessage::user("你好".to_string()),
+            AgentMessage::assistant("你好！".to_string()),
+            AgentMessage::user("1+1=?".to_string()),
+            answer.clone(),
+        ];
+
+        let records = feedback_records("math", &messages);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].prompt, "1+1=?");
+        assert_eq!(records[0].response_id, answer.id);
+        assert_eq!(records[0].feedback.rating, Some(FeedbackRating::Negative));
+
+        let merged = ResponseFeedback::comment("答案错了").merged_with(Some(&feedback));
+        assert_eq!(merged.rating, Some(FeedbackRating::Negative));
+        assert_eq!(merged.comment.as_deref(), Some("答案错了"));
+        assert!(ResponseFeedback::comment("  ").is_empty());
+
+        let jsonl = feedback_to_jsonl(&records);
+        let line: serde_json::Value = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(line["rating"], "negative");
+        assert_eq!(line["messages"][1]["content"], "等于 3");
+    }
+}
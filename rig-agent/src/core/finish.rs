@@ -0,0 +1,153 @@
+//! 完成原因与响应警告
+//!
+//! rig-core 的对话接口只返回文本，不透传提供商原始的完成原因，因此完成原因有两个来源：
+//! 拿到提供商原始值（流式事件、错误信息）时按下表映射；拿不到时根据调用过程推断，
+//! 例如流式响应中途失败、模型请求了工具调用、输出估算达到 `max_tokens` 上限。
+//!
+//! | 提供商 | `stop` | `length` | `tool_calls` | `content_filter` |
+//! |---|---|---|---|---|
+//! | openai | `stop` | `length` | `tool_calls`、`function_call` | `content_filter` |
+//! | anthropic | `end_turn`、`stop_sequence`、`pause_turn` | `max_tokens` | `tool_use` | `refusal` |
+//! | gemini | `STOP` | `MAX_TOKENS` | 工具调用同样以 `STOP` 结束 | `SAFETY`、`RECITATION`、`BLOCKLIST`、`PROHIBITED_CONTENT`、`SPII` |
+//! | cohere | `COMPLETE`、`STOP_SEQUENCE` | `MAX_TOKENS` | `TOOL_CALL` | `ERROR_TOXIC` |
+//!
+//! 其余取值（cohere 的 `ERROR`、gemini 的 `OTHER`、`MALFORMED_FUNCTION_CALL` 等）映射为 `error`。
+//! 模型调用直接失败时返回错误而不是响应，`error` 只出现在已经输出部分内容之后失败的流式响应中。
+
+use serde::{Deserialize, Serialize};
+
+/// 响应的完成原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// 正常结束
+    Stop,
+    /// 达到输出长度上限，内容被截断
+    Length,
+    /// 模型请求调用工具
+    ToolCalls,
+    /// 被提供商的内容过滤拦截
+    ContentFilter,
+    /// 生成中途出错，内容不完整
+    Error,
+}
+
+impl FinishReason {
+    /// 按上表把提供商原始的完成原因映射为统一取值
+    pub fn from_provider(provider: &str, raw: &str) -> Self {
+        match (provider, raw) {
+            (_, "stop" | "end_turn" | "stop_sequence" | "pause_turn" | "STOP" | "COMPLETE")
+            | ("cohere", "STOP_SEQUENCE") => Self::Stop,
+            (_, "length" | "max_tokens" | "MAX_TOKENS") => Self::Length,
+            (_, "tool_calls" | "function_call" | "tool_use" | "TOOL_CALL") => Self::ToolCalls,
+            (
+                _,
+                "content_filter" | "refusal" | "SAFETY" | "RECITATION" | "BLOCKLIST"
+                | "PROHIBITED_CONTENT" | "SPII" | "ERROR_TOXIC",
+            ) => Self::ContentFilter,
+            _ => Self::Error,
+        }
+    }
+
+    /// 从提供商的错误信息中识别完成原因，只识别内容过滤与长度上限，其余返回 `None`
+    pub fn from_error_message(message: &str) -> Option<Self> {
+        let lower = message.to_lowercase();
+        if [
+            "content_filter",
+            "content management policy",
+            "refusal",
+            "safety",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+        {
+            Some(Self::ContentFilter)
+        } else if [
+            "max_tokens",
+            "maximum context length",
+            "finish_reason: length",
+        ]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+        {
+            Some(Self::Length)
+        } else {
+            None
+        }
+    }
+
+    /// 内容是否完整
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Stop)
+    }
+}
+
+/// 响应警告，响应可用但客户端可能需要提示用户或调整请求
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResponseWarning {
+    /// 超过历史限制，最早的消息已从对话历史中丢弃
+    HistoryTruncated {
+        /// 丢弃的消息数量
+        dropped: usize,
+    },
+    /// 流式响应中途失败，只返回了已收到的部分
+    StreamInterrupted {
+        /// 错误信息
+        error: String,
+    },
+    /// 输出的估算令牌数达到 `max_tokens` 上限，内容可能被截断
+    MaxTokensReached {
+        /// 配置的上限
+        max_tokens: u32,
+    },
+    /// 模型请求调用工具，但本次请求未挂载工具，调用没有执行
+    ToolCallIgnored {
+        /// 工具名称
+        tool: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_reason_mapping() {
+        assert_eq!(
+            FinishReason::from_provider("openai", "stop"),
+            FinishReason::Stop
+        );
+        assert_eq!(
+            FinishReason::from_provider("anthropic", "max_tokens"),
+            FinishReason::Length
+        );
+        assert_eq!(
+            FinishReason::from_provider("anthropic", "tool_use"),
+            FinishReason::ToolCalls
+        );
+        assert_eq!(
+            FinishReason::from_provider("gemini", "SAFETY"),
+            FinishReason::ContentFilter
+        );
+        assert_eq!(
+            FinishReason::from_provider("cohere", "ERROR"),
+            FinishReason::Error
+        );
+
+        assert_eq!(
+            FinishReason::from_error_message("The response was filtered due to content_filter"),
+            Some(FinishReason::ContentFilter)
+        );
+        assert_eq!(FinishReason::from_error_message("connection reset"), None);
+
+        assert_eq!(
+            serde_json::to_value(FinishReason::ToolCalls).unwrap(),
+            serde_json::json!("tool_calls")
+        );
+        assert_eq!(
+            serde_json::to_value(ResponseWarning::HistoryTruncated { dropped: 2 }).unwrap(),
+            serde_json::json!({"kind": "history_truncated", "dropped": 2})
+        );
+    }
+}
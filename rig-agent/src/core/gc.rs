@@ -0,0 +1,97 @@
+//! 空闲 Agent 回收
+//!
+//! 远程 P2P 节点创建的 Agent 或被遗忘的会话会一直留在 AgentManager 中，
+//! 这里按 last_activity 的空闲时间定期回收，并可选地持久化被回收的对话历史。
+
+use super::agent::AgentManager;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{sync::RwLock, task::JoinHandle};
+use tracing::{debug, info};
+
+/// Agent 回收配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentGcConfig {
+    /// 空闲多久后回收
+    pub idle_ttl: Duration,
+    /// 检查间隔
+    pub interval: Duration,
+    /// 被回收 Agent 的对话历史保存目录，为空时不保存
+    pub persist_dir: Option<PathBuf>,
+    /// 永不回收的 Agent
+    pub exempt: Vec<String>,
+}
+
+impl Default for AgentGcConfig {
+    fn default() -> Self {
+        Self {
+            idle_ttl: Duration::from_secs(30 * 60),
+            interval: Duration::from_secs(60),
+            persist_dir: None,
+            exempt: Vec::new(),
+        }
+    }
+}
+
+impl AgentGcConfig {
+    /// 设置空闲回收时间
+    pub fn with_idle_ttl(mut self, idle_ttl: Duration) -> Self {
+        self.idle_ttl = idle_ttl;
+        self
+    }
+
+    /// 设置检查间隔
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 设置对话历史保存目录
+    pub fn with_persist_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.persist_dir = Some(dir.into());
+        self
+    }
+
+    /// 添加永不回收的 Agent
+    pub fn with_exempt<S: Into<String>>(mut self, agent_id: S) -> Self {
+        self.exempt.push(agent_id.into());
+        self
+    }
+}
+
+/// Agent 管理器事件，线上格式带版本号，见 [`crate::core::wire`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self", tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// Agent 因空闲被回收
+    Reaped {
+        /// Agent ID
+        agent_id: String,
+        /// 空闲时长（秒）
+        idle_seconds: i64,
+        /// 回收时的消息数量
+        message_count: usize,
+        /// 对话历史保存路径
+        history_path: Option<PathBuf>,
+    },
+}
+
+/// 启动后台回收任务，按配置的间隔回收空闲 Agent
+pub fn spawn_agent_gc(manager: Arc<RwLock<AgentManager>>, config: AgentGcConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!(
+            "启动 Agent 回收任务，空闲时间: {:?}, 检查间隔: {:?}",
+            config.idle_ttl, config.interval
+        );
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+            let reaped = manager.read().await.reap_idle_agents(&config).await;
+            if !reaped.is_empty() {
+                debug!("本轮回收 {} 个 Agent", reaped.len());
+            }
+        }
+    })
+}
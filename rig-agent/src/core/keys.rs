@@ -0,0 +1,319 @@
+//! API 密钥池 - 同一提供商注册多个密钥，按轮询或最久未限流的策略选择，
+//! 密钥遇到 429 限流时自动冷却一段时间，并按密钥统计用量
+
+use crate::error::AgentError;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 默认冷却时间（秒）
+const DEFAULT_COOLDOWN_SECS: u64 = 60;
+
+/// 密钥选择策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySelection {
+    /// 轮询
+    #[default]
+    RoundRobin,
+    /// 优先使用最久没有被限流的密钥
+    LeastRecentlyLimited,
+}
+
+/// 密钥池配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyPoolConfig {
+    /// 选择策略
+    pub selection: KeySelection,
+    /// 被限流后的冷却时间（秒）
+    pub cooldown_secs: u64,
+}
+
+impl Default for KeyPoolConfig {
+    fn default() -> Self {
+        Self {
+            selection: KeySelection::default(),
+            cooldown_secs: DEFAULT_COOLDOWN_SECS,
+        }
+    }
+}
+
+impl KeyPoolConfig {
+    /// 设置选择策略
+    pub fn with_selection(mut self, selection: KeySelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// 设置冷却时间（秒）
+    pub fn with_cooldown_secs(mut self, cooldown_secs: u64) -> Self {
+        self.cooldown_secs = cooldown_secs;
+        self
+    }
+}
+
+/// 单个密钥的用量统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKeyStats {
+    /// 密钥 ID
+    pub id: String,
+    /// 脱敏后的密钥，只保留首尾几个字符
+    pub label: String,
+    /// 分配出去的请求数
+    pub requests: u64,
+    /// 成功次数
+    pub successes: u64,
+    /// 被限流次数
+    pub rate_limited: u64,
+    /// 其他失败次数
+    pub failures: u64,
+    /// 最近一次使用时间
+    pub last_used: Option<DateTime<Utc>>,
+    /// 最近一次被限流时间
+    pub last_limited: Option<DateTime<Utc>>,
+    /// 冷却结束时间
+    pub cooldown_until: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyStats {
+    /// 在给定时间是否处于冷却中
+    pub fn is_cooling_down(&self, now: DateTime<Utc>) -> bool {
+        self.cooldown_until.is_some_and(|until| until > now)
+    }
+}
+
+/// 一次模型调用的结果，用于更新密钥统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOutcome {
+    /// 成功
+    Success,
+    /// 被限流（HTTP 429）
+    RateLimited,
+    /// 其他失败
+    Failed,
+}
+
+impl KeyOutcome {
+    /// 根据调用结果判断
+    pub fn of<T>(result: &Result<T, AgentError>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(AgentError::RateLimit) => Self::RateLimited,
+            Err(e) if is_rate_limit_message(&e.to_string()) => Self::RateLimited,
+            Err(_) => Self::Failed,
+        }
+    }
+}
+
+/// 提供商返回的错误信息是否表示限流
+pub fn is_rate_limit_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("429")
+        || message.contains("rate limit")
+        || message.contains("rate_limit")
+        || message.contains("too many requests")
+}
+
+/// 分配出去的密钥，调用结束后凭此反馈结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyLease {
+    /// 提供商
+    pub provider: String,
+    /// 密钥 ID
+    pub key_id: String,
+}
+
+/// 池中的密钥
+#[derive(Debug)]
+struct PooledKey {
+    key: String,
+    stats: ApiKeyStats,
+}
+
+/// 单个提供商的密钥池
+#[derive(Debug)]
+pub struct KeyPool {
+    config: KeyPoolConfig,
+    keys: Vec<PooledKey>,
+    /// 轮询的下一个位置
+    cursor: usize,
+    /// 下一个密钥 ID 的序号
+    next_id: usize,
+}
+
+impl KeyPool {
+    /// 创建空的密钥池
+    pub fn new(config: KeyPoolConfig) -> Self {
+        Self {
+            config,
+            keys: Vec::new(),
+            cursor: 0,
+            next_id: 1,
+        }
+    }
+
+    /// 池配置
+    pub fn config(&self) -> &KeyPoolConfig {
+        &self.config
+    }
+
+    /// 添加密钥，返回密钥 ID；已存在的密钥返回原 ID
+    pub fn add<S: Into<String>>(&mut self, key: S) -> String {
+        let key = key.into();
+        if let Some(existing) = self.keys.iter().find(|pooled| pooled.key == key) {
+            return existing.stats.id.clone();
+        }
+
+        let id = format!("key-{}", self.next_id);
+        self.next_id += 1;
+        self.keys.push(PooledKey {
+            stats: ApiKeyStats {
+                id: id.clone(),
+                label: mask_key(&key),
+                requests: 0,
+                successes: 0,
+                rate_limited: 0,
+                failures: 0,
+                last_used: None,
+                last_limited: None,
+                cooldown_until: None,
+            },
+            key,
+        });
+        id
+    }
+
+    /// 移除密钥
+    pub fn remove(&mut self, key_id: &str) -> bool {
+        let before = self.keys.len();
+        self.keys.retain(|pooled| pooled.stats.id != key_id);
+        self.keys.len() != before
+    }
+
+    /// 密钥数量
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// 是否没有密钥
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// 选择一个不在冷却中的密钥，返回密钥 ID 与密钥；全部冷却时返回空
+    pub fn acquire(&mut self, now: DateTime<Utc>) -> Option<(String, String)> {
+        let available = |pooled: &PooledKey| !pooled.stats.is_cooling_down(now);
+        let count = self.keys.len();
+        let index = match self.config.selection {
+            KeySelection::RoundRobin => (0..count)
+                .map(|offset| (self.cursor + offset) % count)
+                .find(|&index| available(&self.keys[index]))?,
+            KeySelection::LeastRecentlyLimited => self
+                .keys
+                .iter()
+                .enumerate()
+                .filter(|(_, pooled)| available(pooled))
+                .min_by_key(|(_, pooled)| (pooled.stats.last_limited, pooled.stats.last_used))
+                .map(|(index, _)| index)?,
+        };
+        self.cursor = (index + 1) % count;
+
+        let pooled = &mut self.keys[index];
+        pooled.stats.requests += 1;
+        pooled.stats.last_used = Some(now);
+        Some((pooled.stats.id.clone(), pooled.key.clone()))
+    }
+
+    /// 记录调用结果，被限流的密钥进入冷却
+    pub fn record(&mut self, key_id: &str, outcome: KeyOutcome, now: DateTime<Utc>) {
+        let Some(pooled) = self
+            .keys
+            .iter_mut()
+            .find(|pooled| pooled.stats.id == key_id)
+        else {
+            return;
+        };
+        match outcome {
+            KeyOutcome::Success => pooled.stats.successes += 1,
+            KeyOutcome::RateLimited => {
+                pooled.stats.rate_limited += 1;
+                pooled.stats.last_limited = Some(now);
+                pooled.stats.cooldown_until =
+                    Some(now + Duration::seconds(self.config.cooldown_secs as i64));
+            }
+            KeyOutcome::Failed => pooled.stats.failures += 1,
+        }
+    }
+
+    /// 各密钥的统计
+    pub fn stats(&self) -> Vec<ApiKeyStats> {
+        self.keys
+            .iter()
+            .map(|pooled| pooled.stats.clone())
+            .collect()
+    }
+}
+
+/// 脱敏密钥：保留前 3 个与后 4 个字符
+fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_pool_rotation_and_cooldown() {
+        let now = Utc::now();
+        let mut pool = KeyPool::new(KeyPoolConfig::default().with_cooldown_secs(30));
+        let first = pool.add("sk-aaaaaaaa1111");
+        let second = pool.add("sk-bbbbbbbb2222");
+        assert_eq!(pool.add("sk-aaaaaaaa1111"), first);
+        assert_eq!(pool.len(), 2);
+
+        assert_eq!(pool.acquire(now).unwrap().0, first);
+        assert_eq!(pool.acquire(now).unwrap().0, second);
+        assert_eq!(pool.acquire(now).unwrap().0, first);
+
+        // 被限流的密钥在冷却期内不再被选中
+        pool.record(&first, KeyOutcome::RateLimited, now);
+        assert_eq!(pool.acquire(now).unwrap().0, second);
+        assert_eq!(pool.acquire(now).unwrap().0, second);
+        pool.record(&second, KeyOutcome::RateLimited, now);
+        assert!(pool.acquire(now).is_none());
+        let later = now + Duration::seconds(31);
+        assert!(pool.acquire(later).is_some());
+
+        let stats = pool.stats();
+        assert_eq!(stats[0].label, "sk-...1111");
+        assert_eq!(stats[0].rate_limited, 1);
+        assert_eq!(stats[1].requests, 3);
+
+        let mut pool = KeyPool::new(
+            KeyPoolConfig::default().with_selection(KeySelection::LeastRecentlyLimited),
+        );
+        let first = pool.add("key-one-aaaa");
+        let second = pool.add("key-two-bbbb");
+        pool.record(&second, KeyOutcome::RateLimited, now - Duration::hours(1));
+        pool.record(&first, KeyOutcome::RateLimited, now - Duration::minutes(10));
+        assert_eq!(pool.acquire(now).unwrap().0, second);
+        assert!(pool.remove(&second));
+        assert_eq!(pool.acquire(now).unwrap().0, first);
+
+        assert_eq!(
+            KeyOutcome::of::<()>(&Err(AgentError::other("HTTP 429 Too Many Requests"))),
+            KeyOutcome::RateLimited
+        );
+        assert_eq!(
+            KeyOutcome::of::<()>(&Err(AgentError::other("连接超时"))),
+            KeyOutcome::Failed
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! 语言检测与回复语言控制
+//!
+//! 按文字系统判断语言，拉丁字母文本再按常见虚词区分具体语言；
+//! 检测结果用于生成回复语言指令，并记录在用户消息的元数据中。
+
+use serde::{Deserialize, Serialize};
+
+/// 用户消息元数据中记录检测到的语言的键
+pub const DETECTED_LANGUAGE_KEY: &str = "detected_language";
+
+/// 回复语言控制
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ReplyLanguage {
+    /// 不干预，由模型自行决定
+    #[default]
+    Off,
+    /// 使用与用户输入相同的语言回复
+    Auto,
+    /// 始终使用指定语言回复（语言代码如 `en`，或语言名称）
+    Fixed {
+        /// 语言
+        language: String,
+    },
+}
+
+impl ReplyLanguage {
+    /// 根据用户输入确定回复语言，无需干预或无法判断时返回 `None`
+    pub fn target(&self, detected: Option<&str>) -> Option<String> {
+        match self {
+            Self::Off => None,
+            Self::Auto => detected.map(str::to_string),
+            Self::Fixed { language } => Some(language.clone()),
+        }
+    }
+}
+
+/// 拉丁字母语言的常见虚词
+const LATIN_STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "you", "what", "how", "to", "of", "in", "it", "this",
+            "that", "with", "for", "can", "please", "i",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "que", "es", "por", "para", "cómo", "qué", "una", "con", "hola",
+            "y", "está",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "des", "est", "et", "pour", "vous", "je", "une", "avec", "bonjour",
+            "comment", "pas", "c'est",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "ich", "nicht", "sie", "ein", "eine", "mit", "wie",
+            "was", "für", "bitte",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "não", "um", "uma", "com", "você", "olá", "como", "é", "obrigado", "para",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "che", "di", "è", "non", "per", "come", "ciao", "sono", "grazie",
+        ],
+    ),
+];
+
+#[derive(Default)]
+struct ScriptCounts {
+    han: usize,
+    kana: usize,
+    hangul: usize,
+    cyrillic: usize,
+    arabic: usize,
+    hebrew: usize,
+    greek: usize,
+    thai: usize,
+    devanagari: usize,
+    latin: usize,
+}
+
+/// 检测文本的主要语言，返回 ISO 639-1 代码，无法判断时返回 `None`
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut counts = ScriptCounts::default();
+    for c in text.chars() {
+        match c as u32 {
+            0x3040..=0x30FF | 0x31F0..=0x31FF => counts.kana += 1,
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0xF900..=0xFAFF => counts.han += 1,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => counts.hangul += 1,
+            0x0400..=0x04FF => counts.cyrillic += 1,
+            0x0600..=0x06FF | 0x0750..=0x077F => counts.arabic += 1,
+            0x0590..=0x05FF => counts.hebrew += 1,
+            0x0370..=0x03FF => counts.greek += 1,
+            0x0E00..=0x0E7F => counts.thai += 1,
+            0x0900..=0x097F => counts.devanagari += 1,
+            _ if c.is_alphabetic() => counts.latin += 1,
+            _ => {}
+        }
+    }
+
+    // 日文混用汉字与假名，出现假名即视为日文
+    let cjk = counts.han + counts.kana;
+    let cjk_language = if counts.kana > 0 { "ja" } else { "zh" };
+    let scripts = [
+        (cjk, cjk_language),
+        (counts.hangul, "ko"),
+        (counts.cyrillic, "ru"),
+        (counts.arabic, "ar"),
+        (counts.hebrew, "he"),
+        (counts.greek, "el"),
+        (counts.thai, "th"),
+        (counts.devanagari, "hi"),
+    ];
+    let (count, language) = scripts
+        .into_iter()
+        .max_by_key(|(count, _)| *count)
+        .unwrap_or((0, "zh"));
+
+    // 拉丁字母约 4 个字母算一个词，非拉丁文字不明显少于拉丁单词时以其为准
+    if count > 0 && count * 2 >= counts.latin / 4 {
+        return Some(language);
+    }
+    if counts.latin == 0 {
+        return None;
+    }
+    detect_latin_language(text)
+}
+
+/// 按常见虚词判断拉丁字母语言
+fn detect_latin_language(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !(c.is_alphabetic() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    LATIN_STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (hits, *language)
+        })
+        .filter(|(hits, _)| *hits > 0)
+        // 命中数相同时取列表中靠前的语言
+        .fold(None, |best: Option<(usize, &str)>, candidate| match best {
+            Some(best) if best.0 >= candidate.0 => Some(best),
+            _ => Some(candidate),
+        })
+        .map(|(_, language)| language)
+}
+
+/// 语言代码对应的中文名称，未知代码原样返回
+pub fn language_name(language: &str) -> &str {
+    match language.to_ascii_lowercase().as_str() {
+        "zh" => "中文",
+        "en" => "英语",
+        "ja" => "日语",
+        "ko" => "韩语",
+        "ru" => "俄语",
+        "ar" => "阿拉伯语",
+        "he" => "希伯来语",
+        "el" => "希腊语",
+        "th" => "泰语",
+        "hi" => "印地语",
+        "es" => "西班牙语",
+        "fr" => "法语",
+        "de" => "德语",
+        "pt" => "葡萄牙语",
+        "it" => "意大利语",
+        _ => language,
+    }
+}
+
+/// 追加到系统提示词的回复语言指令
+pub fn reply_language_instruction(language: &str) -> String {
+    format!(
+        "请始终使用{}回复用户，除非用户明确要求使用其他语言。",
+        language_name(language)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language("今天北京的天气怎么样？"), Some("zh"));
+        assert_eq!(detect_language("今日の天気はどうですか"), Some("ja"));
+        assert_eq!(detect_language("오늘 날씨 어때요?"), Some("ko"));
+        assert_eq!(detect_language("Какая сегодня погода?"), Some("ru"));
+        assert_eq!(
+            detect_language("What is the weather like in Paris?"),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("Bonjour, comment est la météo pour demain ?"),
+            Some("fr")
+        );
+        assert_eq!(detect_language("Wie ist das Wetter in Berlin?"), Some("de"));
+        assert_eq!(detect_language("请把 README.md 翻译一下"), Some("zh"));
+        assert_eq!(detect_language("12345 !!!"), None);
+
+        assert_eq!(ReplyLanguage::Off.target(Some("en")), None);
+        assert_eq!(
+            ReplyLanguage::Auto.target(Some("en")).as_deref(),
+            Some("en")
+        );
+        let fixed = ReplyLanguage::Fixed {
+            language: "zh".to_string(),
+        };
+        assert_eq!(fixed.target(Some("en")).as_deref(), Some("zh"));
+        assert!(reply_language_instruction("zh").contains("中文"));
+    }
+}
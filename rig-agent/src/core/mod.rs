@@ -1,8 +1,50 @@
 //! 核心模块
 
 pub mod agent;
+pub mod cache;
+pub mod citation;
+pub mod compress;
+pub mod context;
+pub mod creativity;
+pub mod determinism;
+pub mod feedback;
+pub mod finish;
+pub mod gc;
+pub mod keys;
+pub mod language;
+pub mod package;
+pub mod paging;
+pub mod race;
+pub mod replay;
+pub mod routing;
+pub mod scheduler;
+pub mod summarize;
+pub mod template;
+pub mod translate;
 pub mod types;
+pub mod wire;
 
 pub use agent::*;
+pub use cache::*;
+pub use citation::*;
+pub use compress::*;
+pub use context::*;
+pub use creativity::*;
+pub use determinism::*;
+pub use feedback::*;
+pub use finish::*;
+pub use gc::*;
+pub use keys::*;
+pub use language::*;
+pub use package::*;
+pub use paging::*;
+pub use race::*;
+pub use replay::*;
+pub use routing::*;
+pub use scheduler::*;
+pub use summarize::*;
+pub use template::*;
+pub use translate::*;
 pub use types::*;
+pub use wire::*;
 
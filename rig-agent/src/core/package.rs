@@ -0,0 +1,322 @@
+//! Agent 包 - 把 Agent 的配置、人设、提示词模板、工具允许列表和知识文件打包为单个文件
+//!
+//! 包文件是带格式标识的 JSON，可以通过 P2P 文件传输发送给其他节点，
+//! 对方导入后即可重建同样的 Agent。包文件也可以用口令加密（见 [`AgentPackage::save_encrypted`]）。
+
+use crate::core::types::{AgentConfig, ToolSelection};
+use crate::error::{AgentError, AgentResult};
+use crate::storage::crypto::{is_passphrase_sealed, open_with_passphrase, seal_with_passphrase};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// 包格式标识
+pub const PACKAGE_FORMAT: &str = "rig-agent-package";
+/// 当前包格式版本
+pub const PACKAGE_VERSION: u32 = 1;
+/// 包文件扩展名
+pub const PACKAGE_EXTENSION: &str = "agentpkg";
+/// Agent 配置 `extra_params` 中保存提示词模板的键
+pub const PROMPT_TEMPLATES_KEY: &str = "prompt_templates";
+
+/// 包内的知识文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnowledgeFile {
+    /// 相对路径
+    pub path: String,
+    /// 文件内容
+    pub content: String,
+}
+
+/// Agent 包
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentPackage {
+    /// 格式标识
+    pub format: String,
+    /// 格式版本
+    pub version: u32,
+    /// 包名称，导入时默认作为 Agent ID
+    pub name: String,
+    /// 描述
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 模型与生成参数（人设、工具与模板单独保存）
+    pub config: AgentConfig,
+    /// 人设（系统提示）
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// 提示词模板，名称到模板内容
+    #[serde(default)]
+    pub prompt_templates: BTreeMap<String, String>,
+    /// 工具允许列表，为空表示不启用工具
+    #[serde(default)]
+    pub tool_allowlist: Option<ToolSelection>,
+    /// 知识文件
+    #[serde(default)]
+    pub knowledge: Vec<KnowledgeFile>,
+    /// 导出时间
+    pub exported_at: DateTime<Utc>,
+}
+
+impl AgentPackage {
+    /// 从 Agent 配置创建包
+    pub fn from_config<S: Into<String>>(name: S, config: &AgentConfig) -> Self {
+        let mut config = config.clone();
+        let persona = config.preamble.take();
+        let prompt_templates = prompt_templates(&config);
+        config.extra_params.remove(PROMPT_TEMPLATES_KEY);
+        let tool_allowlist = config
+            .enable_tools
+            .then(|| std::mem::take(&mut config.tool_selection));
+        config.enable_tools = false;
+
+        Self {
+            format: PACKAGE_FORMAT.to_string(),
+            version: PACKAGE_VERSION,
+            name: name.into(),
+            description: None,
+            config,
+            persona,
+            prompt_templates,
+            tool_allowlist,
+            knowledge: Vec::new(),
+            exported_at: Utc::now(),
+        }
+    }
+
+    /// 设置描述
+    pub fn with_description<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// 添加提示词模板
+    pub fn with_prompt_template<S: Into<String>>(mut self, name: S, template: S) -> Self {
+        self.prompt_templates.insert(name.into(), template.into());
+        self
+    }
+
+    /// 添加知识文件
+    pub fn with_knowledge_file<S: Into<String>>(
+        mut self,
+        path: S,
+        content: S,
+    ) -> AgentResult<Self> {
+        let path = path.into();
+        relative_path(&path)?;
+        self.knowledge.push(KnowledgeFile {
+            path,
+            content: content.into(),
+        });
+        Ok(self)
+    }
+
+    /// 从磁盘读取知识文件，包内路径为文件名
+    pub fn add_knowledge_from(&mut self, path: &Path) -> AgentResult<()> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| AgentError::config(format!("无效的知识文件路径: {}", path.display())))?;
+        let content = std::fs::read_to_string(path)?;
+        self.knowledge.push(KnowledgeFile {
+            path: name.to_string(),
+            content,
+        });
+        Ok(())
+    }
+
+    /// 检查格式、版本与知识文件路径
+    pub fn validate(&self) -> AgentResult<()> {
+        if self.format != PACKAGE_FORMAT {
+            return Err(AgentError::config(format!(
+                "不是 Agent 包: {}",
+                self.format
+            )));
+        }
+        if self.version > PACKAGE_VERSION {
+            return Err(AgentError::config(format!(
+                "不支持的包版本: {}（最高支持 {}）",
+                self.version, PACKAGE_VERSION
+            )));
+        }
+        if self.name.trim().is_empty() {
+            return Err(AgentError::config("包名称不能为空"));
+        }
+        for file in &self.knowledge {
+            relative_path(&file.path)?;
+        }
+        Ok(())
+    }
+
+    /// 还原为 Agent 配置
+    pub fn to_config(&self) -> AgentConfig {
+        let mut config = self.config.clone();
+        config.preamble = self.persona.clone();
+        config.enable_tools = self.tool_allowlist.is_some();
+        config.tool_selection = self.tool_allowlist.clone().unwrap_or_default();
+        if !self.prompt_templates.is_empty() {
+            config.extra_params.insert(
+                PROMPT_TEMPLATES_KEY.to_string(),
+                serde_json::to_value(&self.prompt_templates).unwrap_or_default(),
+            );
+        }
+        config
+    }
+
+    /// 编码为包文件内容
+    pub fn to_bytes(&self) -> AgentResult<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// 从包文件内容解码并检查
+    pub fn from_bytes(bytes: &[u8]) -> AgentResult<Self> {
+        if is_passphrase_sealed(bytes) {
+            return Err(AgentError::permission("包文件已加密，需要提供口令"));
+        }
+        let package: Self = serde_json::from_slice(bytes)?;
+        package.validate()?;
+        Ok(package)
+    }
+
+    /// 保存为单个包文件
+    pub fn save(&self, path: &Path) -> AgentResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    /// 读取包文件
+    pub fn load(path: &Path) -> AgentResult<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// 编码并用口令加密
+    pub fn to_encrypted_bytes(&self, passphrase: &str) -> AgentResult<Vec<u8>> {
+        seal_with_passphrase(&self.to_bytes()?, passphrase)
+    }
+
+    /// 解码包文件内容，加密的内容用口令解密，未加密的直接解码
+    pub fn from_encrypted_bytes(bytes: &[u8], passphrase: &str) -> AgentResult<Self> {
+        if !is_passphrase_sealed(bytes) {
+            return Self::from_bytes(bytes);
+        }
+        Self::from_bytes(&open_with_passphrase(bytes, passphrase)?)
+    }
+
+    /// 用口令加密后保存为单个包文件
+    pub fn save_encrypted(&self, path: &Path, passphrase: &str) -> AgentResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_encrypted_bytes(passphrase)?)?;
+        Ok(())
+    }
+
+    /// 读取（可能加密的）包文件
+    pub fn load_encrypted(path: &Path, passphrase: &str) -> AgentResult<Self> {
+        Self::from_encrypted_bytes(&std::fs::read(path)?, passphrase)
+    }
+
+    /// 把知识文件写入目录，返回写入的文件路径
+    pub fn extract_knowledge(&self, dir: &Path) -> AgentResult<Vec<PathBuf>> {
+        let mut written = Vec::with_capacity(self.knowledge.len());
+        for file in &self.knowledge {
+            let target = dir.join(relative_path(&file.path)?);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target, &file.content)?;
+            written.push(target);
+        }
+        Ok(written)
+    }
+}
+
+/// 读取 Agent 配置中的提示词模板
+pub fn prompt_templates(config: &AgentConfig) -> BTreeMap<String, String> {
+    config
+        .extra_params
+        .get(PROMPT_TEMPLATES_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default()
+}
+
+/// 知识文件只能使用相对路径，且不能跳出目标目录
+fn relative_path(path: &str) -> AgentResult<PathBuf> {
+    let path = Path::new(path);
+    let valid = !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !valid {
+        return Err(AgentError::permission(format!(
+            "知识文件路径不合法: {}",
+            path.display()
+        )));
+    }
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_round_trip() {
+        let config = AgentConfig::new("openai", "gpt-4o")
+            .with_preamble("你是翻译助手")
+            .allow_tools(["web_*"]);
+        let package = AgentPackage::from_config("translator", &config)
+            .with_description("中英互译")
+            .with_prompt_template("formal", "请用正式语气翻译：{{text}}")
+            .with_knowledge_file("glossary/terms.md", "agent = 智能体")
+            .unwrap();
+        assert_eq!(package.persona.as_deref(), Some("你是翻译助手"));
+        assert!(package.config.preamble.is_none());
+
+        let decoded = AgentPackage::from_bytes(&package.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.name, "translator");
+        assert_eq!(decoded.knowledge, package.knowledge);
+
+        let restored = decoded.to_config();
+        assert_eq!(restored.preamble, config.preamble);
+        assert!(restored.enable_tools);
+        assert_eq!(restored.tool_selection, config.tool_selection);
+        assert_eq!(
+            prompt_templates(&restored)
+                .get("formal")
+                .map(String::as_str),
+            Some("请用正式语气翻译：{{text}}")
+        );
+
+        let dir = std::env::temp_dir().join(format!("rig-agent-package-{}", uuid::Uuid::new_v4()));
+        let written = decoded.extract_knowledge(&dir).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&written[0]).unwrap(),
+            "agent = 智能体"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            AgentPackage::from_config("x", &config)
+                .with_knowledge_file("../etc/passwd", "")
+                .is_err()
+        );
+        let mut foreign = package.clone();
+        foreign.version = PACKAGE_VERSION + 1;
+        assert!(foreign.validate().is_err());
+
+        // 加密的包需要口令
+        let sealed = package.to_encrypted_bytes("correct horse").unwrap();
+        assert!(matches!(
+            AgentPackage::from_bytes(&sealed),
+            Err(AgentError::Permission(_))
+        ));
+        assert!(AgentPackage::from_encrypted_bytes(&sealed, "battery staple").is_err());
+        let opened = AgentPackage::from_encrypted_bytes(&sealed, "correct horse").unwrap();
+        assert_eq!(opened.knowledge, package.knowledge);
+    }
+}
@@ -0,0 +1,221 @@
+//! 分页 - 列表接口通用的偏移/条数分页、时间范围过滤与排序参数
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 未指定条数时每页返回的条数
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+/// 每页最多返回的条数
+pub const MAX_PAGE_LIMIT: usize = 500;
+
+/// 排序方向
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// 升序（由旧到新）
+    #[default]
+    Asc,
+    /// 降序（由新到旧）
+    Desc,
+}
+
+/// 分页请求
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    /// 跳过的条数
+    pub offset: usize,
+    /// 返回的条数，不指定时为 [`DEFAULT_PAGE_LIMIT`]，最多 [`MAX_PAGE_LIMIT`]
+    pub limit: Option<usize>,
+    /// 只包含该时间及之后的条目
+    pub since: Option<DateTime<Utc>>,
+    /// 只包含该时间之前的条目
+    pub until: Option<DateTime<Utc>>,
+    /// 排序方向
+    pub order: SortOrder,
+}
+
+impl PageRequest {
+    /// 设置偏移与条数
+    pub fn with_range(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = Some(limit);
+        self
+    }
+
+    /// 设置时间范围
+    pub fn with_time_range(
+        mut self,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    /// 设置排序方向
+    pub fn with_order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// 实际生效的条数
+    pub fn effective_limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT)
+    }
+
+    /// 时间是否在过滤范围内
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp < until)
+    }
+
+    /// 按时间过滤、排序并取出一页
+    pub fn paginate<T, F>(&self, items: Vec<T>, timestamp: F) -> ListPage<T>
+    where
+        F: Fn(&T) -> DateTime<Utc>,
+    {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| self.contains(timestamp(item)))
+            .collect();
+        items.sort_by_key(&timestamp);
+        self.page(items)
+    }
+
+    /// 对已按升序排好的条目取出一页，降序时从末尾开始
+    pub fn page<T>(&self, mut items: Vec<T>) -> ListPage<T> {
+        if self.order == SortOrder::Desc {
+            items.reverse();
+        }
+        let total = items.len();
+        let items: Vec<T> = items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.effective_limit())
+            .collect();
+        let end = self.offset.saturating_add(items.len());
+
+        ListPage {
+            items,
+            total,
+            offset: self.offset,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+}
+
+/// 一页结果
+///
+/// 与会话存储的 [`crate::storage::Page`] 不同，这里记录下一页的偏移而不是每页条数
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListPage<T> {
+    /// 本页条目
+    pub items: Vec<T>,
+    /// 过滤后的总条数
+    pub total: usize,
+    /// 本页的偏移
+    pub offset: usize,
+    /// 下一页的偏移，没有更多时为空
+    pub next_offset: Option<usize>,
+}
+
+impl<T> ListPage<T> {
+    /// 转换条目类型
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> ListPage<U> {
+        ListPage {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            offset: self.offset,
+            next_offset: self.next_offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_paginate_with_filters_and_order() {
+        let start = Utc::now();
+        let items: Vec<(u32, DateTime<Utc>)> = (0..10)
+            .map(|i| (i, start + Duration::minutes(i as i64)))
+            .rev()
+            .collect();
+
+        let page = PageRequest::default()
+            .with_range(2, 3)
+            .paginate(items.clone(), |item| item.1);
+        assert_eq!(
+            page.items.iter().map(|item| item.0).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(page.total, 10);
+        assert_eq!(page.next_offset, Some(5));
+
+        let page = PageRequest::default()
+            .with_time_range(
+                Some(start + Duration::minutes(3)),
+                Some(start + Duration::minutes(7)),
+            )
+            .with_order(SortOrder::Desc)
+            .paginate(items, |item| item.1);
+        assert_eq!(
+            page.items.iter().map(|item| item.0).collect::<Vec<_>>(),
+            vec![6, 5, 4, 3]
+        );
+        assert_eq!(page.next_offset, None);
+
+        let request = PageRequest {
+            limit: Some(10_000),
+            ..Default::default()
+        };
+        assert_eq!(request.effective_limit(), MAX_PAGE_LIMIT);
+    }
+
+    #[test]
+    fn test_paginate_edge_cases() {
+        let start = Utc::now();
+        let items: Vec<(u32, DateTime<Utc>)> = (0..10)
+            .map(|i| (i, start + Duration::minutes(i as i64)))
+            .collect();
+
+        // 偏移超出末尾：没有条目，也没有下一页，总数不变
+        let page = PageRequest::default()
+            .with_range(25, 5)
+            .paginate(items.clone(), |item| item.1);
+        assert!(page.items.is_empty());
+        assert_eq!((page.total, page.offset, page.next_offset), (10, 25, None));
+
+        // 空的时间范围
+        let page = PageRequest::default()
+            .with_time_range(
+                Some(start + Duration::minutes(5)),
+                Some(start + Duration::minutes(5)),
+            )
+            .paginate(items.clone(), |item| item.1);
+        assert!(page.items.is_empty());
+        assert_eq!((page.total, page.next_offset), (0, None));
+
+        // 过滤、降序与分页组合：总数按过滤后的条目计算
+        let request = PageRequest::default()
+            .with_time_range(Some(start + Duration::minutes(2)), None)
+            .with_order(SortOrder::Desc)
+            .with_range(3, 2);
+        let page = request.paginate(items.clone(), |item| item.1);
+        assert_eq!(
+            page.items.iter().map(|item| item.0).collect::<Vec<_>>(),
+            vec![6, 5]
+        );
+        assert_eq!((page.total, page.next_offset), (8, Some(5)));
+        let last = request
+            .with_range(6, 2)
+            .paginate(items, |item| item.1)
+            .map(|item| item.0);
+        assert_eq!(last.items, vec![3, 2]);
+        assert_eq!(last.next_offset, None);
+    }
+}
@@ -0,0 +1,370 @@
+//! 提供商竞速 - 把同一提示词发给两个提供商，先完成（流式调用时先返回首个片段）的一方胜出，
+//! 另一方的调用被取消，用于降低桌面端交互请求的长尾延迟。
+//!
+//! 竞速会让调用成本翻倍，因此只用于交互式请求：主模型在对冲延迟内完成时不启动挑战者，
+//! 并按提示词长度与每分钟竞速次数限制预算
+
+use crate::core::routing::ModelVariant;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 助手消息元数据中记录竞速胜出的挑战者的键
+pub const RACE_WINNER_KEY: &str = "race_winner";
+
+/// 统计竞速次数的时间窗口
+const RACE_WINDOW: Duration = Duration::from_secs(60);
+
+fn default_max_prompt_chars() -> usize {
+    4000
+}
+
+fn default_max_races_per_minute() -> u32 {
+    10
+}
+
+/// 竞速策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RaceConfig {
+    /// 与主模型竞速的挑战者
+    pub challenger: ModelVariant,
+    /// 主模型调用多久仍未完成时才启动挑战者（毫秒），为 0 时同时发出
+    #[serde(default)]
+    pub hedge_delay_ms: u64,
+    /// 提示词超过该字符数时不竞速
+    #[serde(default = "default_max_prompt_chars")]
+    pub max_prompt_chars: usize,
+    /// 每个 Agent 每分钟最多启动挑战者的次数
+    #[serde(default = "default_max_races_per_minute")]
+    pub max_races_per_minute: u32,
+}
+
+impl RaceConfig {
+    /// 创建竞速策略，默认同时发出两个请求
+    pub fn new(challenger: ModelVariant) -> Self {
+        Self {
+            challenger,
+            hedge_delay_ms: 0,
+            max_prompt_chars: default_max_prompt_chars(),
+            max_races_per_minute: default_max_races_per_minute(),
+        }
+    }
+
+    /// 设置对冲延迟（毫秒）
+    pub fn with_hedge_delay_ms(mut self, hedge_delay_ms: u64) -> Self {
+        self.hedge_delay_ms = hedge_delay_ms;
+        self
+    }
+
+    /// 设置参与竞速的提示词最大字符数
+    pub fn with_max_prompt_chars(mut self, max_prompt_chars: usize) -> Self {
+        self.max_prompt_chars = max_prompt_chars;
+        self
+    }
+
+    /// 设置每分钟最多竞速的次数
+    pub fn with_max_races_per_minute(mut self, max_races_per_minute: u32) -> Self {
+        self.max_races_per_minute = max_races_per_minute;
+        self
+    }
+
+    /// 对冲延迟
+    pub fn hedge_delay(&self) -> Duration {
+        Duration::from_millis(self.hedge_delay_ms)
+    }
+}
+
+/// 一次竞速的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RaceOutcome {
+    /// 主模型在对冲延迟内完成，没有启动挑战者
+    Solo,
+    /// 竞速预算不足，没有启动挑战者
+    OverBudget,
+    /// 主模型胜出，`cancelled` 表示挑战者的调用被取消（而不是已经失败）
+    PrimaryWon { cancelled: bool },
+    /// 挑战者胜出，`cancelled` 表示主模型的调用被取消（而不是已经失败）
+    ChallengerWon { cancelled: bool },
+    /// 双方都失败
+    BothFailed,
+}
+
+/// 单个 Agent 的竞速指标
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RaceStats {
+    /// 启动了挑战者的请求数
+    pub races: u64,
+    /// 主模型在对冲延迟内完成、没有启动挑战者的请求数
+    pub solo: u64,
+    /// 因预算限制或挂载了工具而没有竞速的请求数
+    pub skipped: u64,
+    /// 主模型胜出次数
+    pub primary_wins: u64,
+    /// 挑战者胜出次数
+    pub challenger_wins: u64,
+    /// 双方都失败的次数
+    pub failures: u64,
+    /// 被取消的落后调用数
+    pub cancelled: u64,
+    /// 竞速请求的总耗时（毫秒）
+    pub total_latency_ms: u64,
+}
+
+impl RaceStats {
+    /// 竞速请求的平均耗时（毫秒）
+    pub fn average_latency_ms(&self) -> f64 {
+        if self.races == 0 {
+            0.0
+        } else {
+            self.total_latency_ms as f64 / self.races as f64
+        }
+    }
+
+    /// 挑战者胜出的比例，没有竞速时为空
+    pub fn challenger_win_rate(&self) -> Option<f64> {
+        let decided = self.primary_wins + self.challenger_wins;
+        (decided > 0).then(|| self.challenger_wins as f64 / decided as f64)
+    }
+}
+
+#[derive(Debug, Default)]
+struct AgentRaces {
+    stats: RaceStats,
+    /// 最近一个时间窗口内启动挑战者的时间
+    started: VecDeque<Instant>,
+}
+
+/// 按 Agent 汇总的竞速指标与预算
+#[derive(Debug, Default)]
+pub struct RaceMetrics {
+    agents: Mutex<HashMap<String, AgentRaces>>,
+}
+
+impl RaceMetrics {
+    fn update<T>(&self, agent_id: &str, f: impl FnOnce(&mut AgentRaces) -> T) -> T {
+        let mut agents = self.agents.lock().unwrap();
+        f(agents.entry(agent_id.to_string()).or_default())
+    }
+
+    /// 即将启动挑战者时检查并占用每分钟的竞速预算
+    pub(crate) fn admit(&self, agent_id: &str, config: &RaceConfig) -> bool {
+        let now = Instant::now();
+        self.update(agent_id, |races| {
+            while races
+                .started
+                .front()
+                .is_some_and(|started| now.duration_since(*started) >= RACE_WINDOW)
+            {
+                races.started.pop_front();
+            }
+            if races.started.len() >= config.max_races_per_minute as usize {
+                return false;
+            }
+            races.started.push_back(now);
+            true
+        })
+    }
+
+    /// 记录一次没有竞速的请求
+    pub(crate) fn record_skip(&self, agent_id: &str) {
+        self.update(agent_id, |races| races.stats.skipped += 1);
+    }
+
+    /// 记录竞速结果
+    pub(crate) fn record(&self, agent_id: &str, outcome: RaceOutcome, latency_ms: u64) {
+        self.update(agent_id, |races| {
+            let stats = &mut races.stats;
+            let cancelled = match outcome {
+                RaceOutcome::Solo => {
+                    stats.solo += 1;
+                    return;
+                }
+                RaceOutcome::OverBudget => {
+                    stats.skipped += 1;
+                    return;
+                }
+                RaceOutcome::PrimaryWon { cancelled } => {
+                    stats.primary_wins += 1;
+                    cancelled
+                }
+                RaceOutcome::ChallengerWon { cancelled } => {
+                    stats.challenger_wins += 1;
+                    cancelled
+                }
+                RaceOutcome::BothFailed => {
+                    stats.failures += 1;
+                    false
+                }
+            };
+            stats.races += 1;
+            stats.cancelled += cancelled as u64;
+            stats.total_latency_ms += latency_ms;
+        });
+    }
+
+    /// 获取 Agent 的竞速指标
+    pub fn get(&self, agent_id: &str) -> RaceStats {
+        self.agents
+            .lock()
+            .unwrap()
+            .get(agent_id)
+            .map(|races| races.stats.clone())
+            .unwrap_or_default()
+    }
+
+    /// 清空 Agent 的竞速指标，不影响预算窗口
+    pub fn reset(&self, agent_id: &str) {
+        if let Some(races) = self.agents.lock().unwrap().get_mut(agent_id) {
+            races.stats = RaceStats::default();
+        }
+    }
+}
+
+/// 让主模型与挑战者竞速，返回先成功的结果，落后的一方随之被丢弃（取消）。
+///
+/// 主模型在 `hedge_delay` 内完成时不启动挑战者；主模型在此之前失败时立即启动挑战者作为后备。
+/// 启动挑战者前调用 `admit` 检查预算，预算不足时只等待主模型。一方失败时等待另一方，
+/// 双方都失败时返回主模型的错误
+pub(crate) async fn race<T, E>(
+    primary: impl Future<Output = Result<T, E>>,
+    challenger: impl Future<Output = Result<T, E>>,
+    hedge_delay: Duration,
+    admit: impl FnOnce() -> bool,
+) -> (Result<T, E>, RaceOutcome) {
+    tokio::pin!(primary);
+    let early = tokio::select! {
+        biased;
+        result = &mut primary => Some(result),
+        _ = tokio::time::sleep(hedge_delay) => None,
+    };
+    let early_error = match early {
+        Some(Ok(value)) => return (Ok(value), RaceOutcome::Solo),
+        Some(Err(e)) => Some(e),
+        None => None,
+    };
+    if !admit() {
+        return match early_error {
+            Some(e) => (Err(e), RaceOutcome::OverBudget),
+            None => (primary.await, RaceOutcome::OverBudget),
+        };
+    }
+
+    tokio::pin!(challenger);
+    if let Some(e) = early_error {
+        return match challenger.await {
+            Ok(value) => (Ok(value), RaceOutcome::ChallengerWon { cancelled: false }),
+            Err(_) => (Err(e), RaceOutcome::BothFailed),
+        };
+    }
+    tokio::select! {
+        result = &mut primary => match result {
+            Ok(value) => (Ok(value), RaceOutcome::PrimaryWon { cancelled: true }),
+            Err(e) => match challenger.await {
+                Ok(value) => (Ok(value), RaceOutcome::ChallengerWon { cancelled: false }),
+                Err(_) => (Err(e), RaceOutcome::BothFailed),
+            },
+        },
+        result = &mut challenger => match result {
+            Ok(value) => (Ok(value), RaceOutcome::ChallengerWon { cancelled: true }),
+            Err(_) => match primary.await {
+                Ok(value) => (Ok(value), RaceOutcome::PrimaryWon { cancelled: false }),
+                Err(e) => (Err(e), RaceOutcome::BothFailed),
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn reply(
+        delay_ms: u64,
+        result: Result<&'static str, &'static str>,
+    ) -> Result<&'static str, &'static str> {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        result
+    }
+
+    #[tokio::test]
+    async fn test_race() {
+        let hedge = Duration::from_millis(20);
+        // 主模型在对冲延迟内完成，不启动挑战者
+        let (result, outcome) = race(
+            reply(1, Ok("主")),
+            reply(1, Ok("挑战者")),
+            hedge,
+            || panic!("不应检查预算"),
+        )
+        .await;
+        assert_eq!((result, outcome), (Ok("主"), RaceOutcome::Solo));
+
+        let (result, outcome) =
+            race(reply(200, Ok("主")), reply(1, Ok("挑战者")), hedge, || true).await;
+        assert_eq!(
+            (result, outcome),
+            (Ok("挑战者"), RaceOutcome::ChallengerWon { cancelled: true })
+        );
+
+        // 挑战者失败时等待主模型；主模型提前失败时立即启动挑战者
+        let (result, outcome) = race(
+            reply(60, Ok("主")),
+            reply(1, Err("挑战者失败")),
+            hedge,
+            || true,
+        )
+        .await;
+        assert_eq!(
+            (result, outcome),
+            (Ok("主"), RaceOutcome::PrimaryWon { cancelled: false })
+        );
+        let (result, outcome) = race(
+            reply(1, Err("主失败")),
+            reply(1, Ok("挑战者")),
+            hedge,
+            || true,
+        )
+        .await;
+        assert_eq!(
+            (result, outcome),
+            (
+                Ok("挑战者"),
+                RaceOutcome::ChallengerWon { cancelled: false }
+            )
+        );
+        let (result, outcome) = race(
+            reply(60, Err("主失败")),
+            reply(1, Err("挑战者失败")),
+            hedge,
+            || true,
+        )
+        .await;
+        assert_eq!((result, outcome), (Err("主失败"), RaceOutcome::BothFailed));
+
+        // 预算不足时只等待主模型
+        let (result, outcome) =
+            race(reply(60, Ok("主")), reply(1, Ok("挑战者")), hedge, || false).await;
+        assert_eq!((result, outcome), (Ok("主"), RaceOutcome::OverBudget));
+
+        let config = RaceConfig::new(ModelVariant::new("local", "ollama", "qwen2.5:7b"))
+            .with_max_races_per_minute(1);
+        let metrics = RaceMetrics::default();
+        assert!(metrics.admit("agent", &config));
+        metrics.record("agent", RaceOutcome::ChallengerWon { cancelled: true }, 120);
+        assert!(!metrics.admit("agent", &config));
+        metrics.record("agent", RaceOutcome::OverBudget, 300);
+        metrics.record("agent", RaceOutcome::Solo, 80);
+        metrics.record_skip("agent");
+        let stats = metrics.get("agent");
+        assert_eq!((stats.races, stats.solo, stats.skipped), (1, 1, 2));
+        assert_eq!(stats.cancelled, 1);
+        assert_eq!(stats.challenger_win_rate(), Some(1.0));
+        assert_eq!(stats.average_latency_ms(), 120.0);
+
+        metrics.reset("agent");
+        assert_eq!(metrics.get("agent"), RaceStats::default());
+        assert!(!metrics.admit("agent", &config));
+    }
+}
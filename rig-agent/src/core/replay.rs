@@ -0,0 +1,283 @@
+//! 对话回放 - 用新的提供商/模型或系统提示词重跑已有对话的用户轮次，
+//! 逐轮对比原回复与新回复，便于在切换默认模型前评估效果
+
+use crate::core::types::{AgentConfig, AgentMessage, AgentRole};
+use chrono::{DateTime, Utc};
+use rig::message::Message;
+use serde::{Deserialize, Serialize};
+
+/// 回放选项，未设置的字段沿用原 Agent 配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayOptions {
+    /// 提供商
+    pub provider: Option<String>,
+    /// 模型
+    pub model: Option<String>,
+    /// 系统提示词
+    pub preamble: Option<String>,
+    /// 温度
+    pub temperature: Option<f32>,
+}
+
+impl ReplayOptions {
+    /// 回放到指定提供商和模型
+    pub fn with_model<S: Into<String>>(provider: S, model: S) -> Self {
+        Self {
+            provider: Some(provider.into()),
+            model: Some(model.into()),
+            ..Self::default()
+        }
+    }
+
+    /// 设置系统提示词
+    pub fn with_preamble<S: Into<String>>(mut self, preamble: S) -> Self {
+        self.preamble = Some(preamble.into());
+        self
+    }
+
+    /// 在原配置上应用回放选项
+    pub fn apply(&self, base: &AgentConfig) -> AgentConfig {
+        let mut config = base.clone();
+        if let Some(provider) = &self.provider {
+            config.provider = provider.clone();
+        }
+        if let Some(model) = &self.model {
+            config.model = model.clone();
+        }
+        if let Some(preamble) = &self.preamble {
+            config.preamble = Some(preamble.clone());
+        }
+        if let Some(temperature) = self.temperature {
+            config.temperature = Some(temperature);
+        }
+        config
+    }
+}
+
+/// 差异行类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    /// 两边相同
+    Equal,
+    /// 仅原回复中有
+    Removed,
+    /// 仅新回复中有
+    Added,
+}
+
+/// 一行差异
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffLine {
+    /// 差异类型
+    pub op: DiffOp,
+    /// 行内容
+    pub text: String,
+}
+
+/// 单轮回放结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayTurn {
+    /// 轮次序号（从 0 开始）
+    pub index: usize,
+    /// 用户消息
+    pub user_message: String,
+    /// 原回复，原对话中该轮没有回复时为空
+    pub original: Option<String>,
+    /// 新回复，调用失败时为空
+    pub replayed: Option<String>,
+    /// 调用失败的错误信息
+    pub error: Option<String>,
+    /// 按行对比的差异
+    pub diff: Vec<DiffLine>,
+    /// 按行计算的相似度（0.0 - 1.0）
+    pub similarity: f64,
+    /// 新回复耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// 回放报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayReport {
+    /// 回放来源（Agent ID 或会话 ID）
+    pub source: String,
+    /// 原提供商
+    pub original_provider: String,
+    /// 原模型
+    pub original_model: String,
+    /// 回放使用的提供商
+    pub provider: String,
+    /// 回放使用的模型
+    pub model: String,
+    /// 各轮结果
+    pub turns: Vec<ReplayTurn>,
+    /// 回复有变化的轮数
+    pub changed_turns: usize,
+    /// 调用失败的轮数
+    pub failed_turns: usize,
+    /// 平均相似度
+    pub average_similarity: f64,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 完成时间
+    pub completed_at: DateTime<Utc>,
+}
+
+impl ReplayReport {
+    /// 汇总各轮结果
+    pub(crate) fn new(
+        source: String,
+        original: &AgentConfig,
+        replay: &AgentConfig,
+        turns: Vec<ReplayTurn>,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        let changed_turns = turns
+            .iter()
+            .filter(|turn| turn.replayed.is_some() && turn.replayed != turn.original)
+            .count();
+        let failed_turns = turns.iter().filter(|turn| turn.error.is_some()).count();
+        let average_similarity = if turns.is_empty() {
+            1.0
+        } else {
+            turns.iter().map(|turn| turn.similarity).sum::<f64>() / turns.len() as f64
+        };
+
+        Self {
+            source,
+            original_provider: original.provider.clone(),
+            original_model: original.model.clone(),
+            provider: replay.provider.clone(),
+            model: replay.model.clone(),
+            turns,
+            changed_turns,
+            failed_turns,
+            average_similarity,
+            started_at,
+            completed_at: Utc::now(),
+        }
+    }
+}
+
+/// 待回放的一轮：此前的对话、用户消息与原回复
+pub(crate) struct PendingTurn {
+    pub history: Vec<Message>,
+    pub user_message: String,
+    pub original: Option<String>,
+}
+
+/// 从对话记录中拆出用户轮次；每轮的上下文使用原对话，保证各轮互不影响
+pub(crate) fn pending_turns(messages: &[AgentMessage]) -> Vec<PendingTurn> {
+    let mut turns = Vec::new();
+    let mut history = Vec::new();
+    let mut iter = messages.iter().peekable();
+
+    while let Some(message) = iter.next() {
+        match message.role {
+            AgentRole::User => {
+                let original = match iter.peek() {
+                    Some(next) if next.role == AgentRole::Assistant => Some(next.content.clone()),
+                    _ => None,
+                };
+                turns.push(PendingTurn {
+                    history: history.clone(),
+                    user_message: message.content.clone(),
+                    original,
+                });
+                history.push(Message::user(message.content.as_str()));
+            }
+            AgentRole::Assistant => history.push(Message::assistant(message.content.as_str())),
+            AgentRole::System | AgentRole::Tool => {}
+        }
+    }
+
+    turns
+}
+
+/// 按行对比两段文本（最长公共子序列）
+pub fn diff_lines(original: &str, replayed: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = original.lines().collect();
+    let b: Vec<&str> = replayed.lines().collect();
+
+    // lcs[i][j] 为 a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_string(),
+    };
+    let mut diff = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            diff.push(line(DiffOp::Equal, a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(line(DiffOp::Removed, a[i]));
+            i += 1;
+        } else {
+            diff.push(line(DiffOp::Added, b[j]));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|text| line(DiffOp::Removed, text)));
+    diff.extend(b[j..].iter().map(|text| line(DiffOp::Added, text)));
+    diff
+}
+
+/// 根据差异计算相似度：相同行数的两倍除以两边总行数
+pub fn similarity(diff: &[DiffLine]) -> f64 {
+    if diff.is_empty() {
+        return 1.0;
+    }
+    let equal = diff.iter().filter(|line| line.op == DiffOp::Equal).count();
+    let total = diff.len() + equal;
+    (2 * equal) as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_turns_and_diff() {
+        let messages = vec![
+            AgentMessage::user("你好".to_string()),
+            AgentMessage::assistant("你好！".to_string()),
+            AgentMessage::user("1+1=?".to_string()),
+            AgentMessage::assistant("等于 2".to_string()),
+            AgentMessage::user("谢谢".to_string()),
+        ];
+        let turns = pending_turns(&messages);
+        assert_eq!(turns.len(), 3);
+        assert!(turns[0].history.is_empty());
+        assert_eq!(turns[1].history.len(), 2);
+        assert_eq!(turns[1].original.as_deref(), Some("等于 2"));
+        assert_eq!(turns[2].original, None);
+
+        let diff = diff_lines("第一行\n第二行\n第三行", "第一行\n新的第二行\n第三行");
+        let ops: Vec<DiffOp> = diff.iter().map(|line| line.op).collect();
+        assert_eq!(
+            ops,
+            vec![DiffOp::Equal, DiffOp::Removed, DiffOp::Added, DiffOp::Equal]
+        );
+        assert!((similarity(&diff) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(similarity(&diff_lines("相同", "相同")), 1.0);
+
+        let config = AgentConfig::default();
+        let replay = ReplayOptions::with_model("anthropic", "claude-3-5-sonnet").apply(&config);
+        assert_eq!(replay.provider, "anthropic");
+        assert_eq!(replay.preamble, config.preamble);
+    }
+}
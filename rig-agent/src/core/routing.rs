@@ -0,0 +1,356 @@
+//! A/B 路由 - 按比例把 Agent 的请求分配到两套模型配置，并按变体统计延迟、成本与反馈，
+//! 便于把流量逐步迁移到新模型
+
+use crate::core::types::AgentConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// 助手消息元数据中记录所用变体的键
+pub const VARIANT_KEY: &str = "variant";
+
+/// 没有令牌统计时按字符数估算，约 4 个字符一个令牌
+const CHARS_PER_TOKEN: usize = 4;
+
+/// 模型变体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelVariant {
+    /// 变体名称，用于标记响应与汇总指标
+    pub name: String,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 每千令牌成本（美元），未设置时不计成本
+    #[serde(default)]
+    pub cost_per_1k_tokens: Option<f64>,
+}
+
+impl ModelVariant {
+    /// 创建模型变体
+    pub fn new<S: Into<String>>(name: S, provider: S, model: S) -> Self {
+        Self {
+            name: name.into(),
+            provider: provider.into(),
+            model: model.into(),
+            cost_per_1k_tokens: None,
+        }
+    }
+
+    /// 设置每千令牌成本
+    pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+        self.cost_per_1k_tokens = Some(cost);
+        self
+    }
+
+    /// 在 Agent 配置上应用该变体的提供商与模型
+    pub fn apply(&self, config: &mut AgentConfig) {
+        config.provider = self.provider.clone();
+        config.model = self.model.clone();
+    }
+}
+
+/// A/B 路由策略：`treatment_percent`% 的请求使用实验变体，其余使用对照变体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbRoute {
+    /// 对照变体（通常是当前模型）
+    pub control: ModelVariant,
+    /// 实验变体（准备迁移到的模型）
+    pub treatment: ModelVariant,
+    /// 分配给实验变体的流量百分比（0 - 100）
+    pub treatment_percent: u8,
+}
+
+impl AbRoute {
+    /// 创建路由策略，百分比超过 100 时按 100 处理
+    pub fn new(control: ModelVariant, treatment: ModelVariant, treatment_percent: u8) -> Self {
+        Self {
+            control,
+            treatment,
+            treatment_percent: treatment_percent.min(100),
+        }
+    }
+
+    /// 按 0 - 99 的随机数选择变体
+    pub fn choose(&self, roll: u8) -> &ModelVariant {
+        if roll < self.treatment_percent {
+            &self.treatment
+        } else {
+            &self.control
+        }
+    }
+
+    /// 随机选择变体
+    pub fn pick(&self) -> &ModelVariant {
+        self.choose((uuid::Uuid::new_v4().as_u128() % 100) as u8)
+    }
+}
+
+/// 单个变体的汇总指标
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantMetrics {
+    /// 变体名称
+    pub variant: String,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 成功请求数
+    pub requests: u64,
+    /// 失败请求数
+    pub failures: u64,
+    /// 成功请求的总耗时（毫秒）
+    pub total_latency_ms: u64,
+    /// 估算的令牌总数
+    pub estimated_tokens: u64,
+    /// 估算的总成本（美元）
+    pub estimated_cost: f64,
+    /// 正面反馈数
+    pub positive_feedback: u64,
+    /// 负面反馈数
+    pub negative_feedback: u64,
+}
+
+impl VariantMetrics {
+    /// 平均耗时（毫秒）
+    pub fn average_latency_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_latency_ms as f64 / self.requests as f64
+        }
+    }
+
+    /// 正面反馈占比，没有反馈时为空
+    pub fn positive_rate(&self) -> Option<f64> {
+        let total = self.positive_feedback + self.negative_feedback;
+        (total > 0).then(|| self.positive_feedback as f64 / total as f64)
+    }
+}
+
+/// 按 Agent 与变体汇总的路由指标
+#[derive(Debug, Default)]
+pub struct RoutingMetrics {
+    metrics: Mutex<HashMap<String, BTreeMap<String, VariantMetrics>>>,
+}
+
+impl RoutingMetrics {
+    fn update(&self, agent_id: &str, variant: &ModelVariant, f: impl FnOnce(&mut VariantMetrics)) {
+        let mut metrics = self.metrics.lock().unwrap();
+        let entry = metrics
+            .entry(agent_id.to_string())
+            .or_default()
+            .entry(variant.name.clone())
+            .or_insert_with(|| VariantMetrics {
+                variant: variant.name.clone(),
+                ..VariantMetrics::default()
+            });
+        // 变体的模型可能被修改过，以最近一次为准
+        entry.provider = variant.provider.clone();
+        entry.model = variant.model.clone();
+        f(entry);
+    }
+
+    /// 记录一次成功请求；没有令牌统计时按提示词与回复的字符数估算
+    pub fn record_success(
+        &self,
+        agent_id: &str,
+        variant: &ModelVariant,
+        latency_ms: u64,
+        prompt: &str,
+        response: &str,
+    ) {
+        let tokens = (prompt.chars().count() + response.chars().count()).div_ceil(CHARS_PER_TOKEN);
+        self.update(agent_id, variant, |metrics| {
+            metrics.requests += 1;
+            metrics.total_latency_ms += latency_ms;
+            metrics.estimated_tokens += tokens as u64;
+            if let Some(cost) = variant.cost_per_1k_tokens {
+                metrics.estimated_cost += tokens as f64 / 1000.0 * cost;
+            }
+        });
+    }
+
+    /// 记录一次失败请求
+    pub fn record_failure(&self, agent_id: &str, variant: &ModelVariant) {
+        self.update(agent_id, variant, |metrics| metrics.failures += 1);
+    }
+
+    /// 记录反馈；`previous` 为该消息之前的反馈，改变反馈时撤销之前的计数
+    pub fn record_feedback(
+        &self,
+        agent_id: &str,
+        variant: &str,
+        previous: Option<bool>,
+        positive: bool,
+    ) {
+        if previous == Some(positive) {
+            return;
+        }
+        let mut metrics = self.metrics.lock().unwrap();
+        let Some(entry) = metrics
+            .get_mut(agent_id)
+            .and_then(|variants| variants.get_mut(variant))
+        else {
+            return;
+        };
+        match previous {
+            Some(true) => entry.positive_feedback = entry.positive_feedback.saturating_sub(1),
+            Some(false) => entry.negative_feedback = entry.negative_feedback.saturating_sub(1),
+            None => {}
+        }
+        if positive {
+            entry.positive_feedback += 1;
+        } else {
+            entry.negative_feedback += 1;
+        }
+    }
+
+    /// 获取 Agent 各变体的指标
+    pub fn get(&self, agent_id: &str) -> Vec<VariantMetrics> {
+        self.metrics
+            .lock()
+            .unwrap()
+            .get(agent_id)
+            .map(|variants| variants.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// 清空 Agent 的指标
+    pub fn reset(&self, agent_id: &str) {
+        self.metrics.lock().unwrap().remove(agent_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ab_route_and_metrics() {
+        let control =
+            ModelVariant::new("gpt-3.5", "openai", "gpt-3.5-turbo").with_cost_per_1k_tokens(0.002);
+        let treatment = ModelVariant::new("local", "ollama", "qwen2.5:7b");
+        let route = AbRoute::new(control, treatment, 20);
+        assert_eq!(route.choose(0).name, "local");
+        assert_eq!(route.choose(19).name, "local");
+        assert_eq!(route.choose(20).name, "gpt-3.5");
+        assert_eq!(
+            AbRoute::new(route.control.clone(), route.treatment.clone(), 200).treatment_percent,
+            100
+        );
+
+        let metrics = RoutingMetrics::default();
+        metrics.record_success(
+            "agent",
+            &route.control,
+            300,
+            "你好",
+            "你好，有什么可以帮你？",
+        );
+        metrics.record_success("agent", &route.control, 100, "1+1", "2");
+        metrics.record_failure("agent", &route.treatment);
+        metrics.record_feedback("agent", "gpt-3.5", None, true);
+        metrics.record_feedback("agent", "gpt-3.5", Some(true), false);
+        metrics.record_feedback("agent", "gpt-3.5", Some(false), false);
+
+        let snapshot = metrics.get("agent");
+        assert_eq!(snapshot.len(), 2);
+        let control = snapshot.iter().find(|m| m.variant == "gpt-3.5").unwrap();
+        assert_eq!(control.requests, 2);
+        assert_eq!(control.average_latency_ms(), 200.0);
+        assert_eq!(control.estimated_tokens, 5);
+        assert!(control.estimated_cost > 0.0);
+        assert_eq!(
+            (control.positive_feedback, control.negative_feedback),
+            (0, 1)
+        );
+        assert_eq!(control.positive_rate(), Some(0.0));
+        let local = snapshot.iter().find(|m| m.variant == "local").unwrap();
+        assert_eq!((local.requests, local.failures), (0, 1));
+
+        metrics.reset("agent");
+        assert!(metrics.get("agent").is_empty());
+    }
+
+    fn route(treatment_percent: u8) -> AbRoute {
+        AbRoute::new(
+            ModelVariant::new("control", "openai", "gpt-4o-mini"),
+            ModelVariant::new("treatment", "anthropic", "claude-3-5-haiku"),
+            treatment_percent,
+        )
+    }
+
+    #[test]
+    fn test_ab_route_split() {
+        // 遍历全部 100 个取值，分配比例与设置完全一致，且低位取值总是落到实验变体
+        for percent in [1, 25, 50, 99] {
+            let route = route(percent);
+            let treated: Vec<u8> = (0..100)
+                .filter(|roll| route.choose(*roll).name == "treatment")
+                .collect();
+            assert_eq!(treated, (0..percent).collect::<Vec<_>>());
+        }
+
+        // 0% 与 100% 时随机选择也只会命中一个变体
+        let off = route(0);
+        assert!((0..100).all(|roll| off.choose(roll).name == "control"));
+        assert!((0..200).all(|_| off.pick().name == "control"));
+        let full = route(100);
+        assert!((0..100).all(|roll| full.choose(roll).name == "treatment"));
+        assert!((0..200).all(|_| full.pick().name == "treatment"));
+
+        let mut config = AgentConfig::new("openai", "gpt-4o-mini").with_ab_route(full.clone());
+        full.pick().apply(&mut config);
+        assert_eq!(
+            (config.provider.as_str(), config.model.as_str()),
+            ("anthropic", "claude-3-5-haiku")
+        );
+    }
+
+    #[test]
+    fn test_variant_metrics_per_agent_and_variant() {
+        let route = route(50);
+        let metrics = RoutingMetrics::default();
+        for roll in 0..10 {
+            let variant = route.choose(roll * 10);
+            metrics.record_success("a", variant, 100, "1234", "5678");
+        }
+        metrics.record_failure("a", &route.treatment);
+        metrics.record_success("b", &route.control, 50, "", "");
+
+        let a = metrics.get("a");
+        let counts: Vec<(&str, u64, u64)> = a
+            .iter()
+            .map(|m| (m.variant.as_str(), m.requests, m.failures))
+            .collect();
+        assert_eq!(counts, [("control", 5, 0), ("treatment", 5, 1)]);
+        assert!(a.iter().all(|m| m.estimated_tokens == 10));
+        assert!(a.iter().all(|m| m.estimated_cost == 0.0));
+        assert_eq!(metrics.get("b").len(), 1);
+        assert!(metrics.get("unknown").is_empty());
+
+        // 没有请求的变体不会因反馈而新建指标，重复的反馈不重复计数
+        metrics.record_feedback("b", "treatment", None, true);
+        assert_eq!(metrics.get("b").len(), 1);
+        metrics.record_feedback("a", "treatment", None, true);
+        metrics.record_feedback("a", "treatment", Some(true), true);
+        let treatment = metrics
+            .get("a")
+            .into_iter()
+            .find(|m| m.variant == "treatment")
+            .unwrap();
+        assert_eq!(treatment.positive_rate(), Some(1.0));
+        assert_eq!(treatment.average_latency_ms(), 100.0);
+
+        // 变体换了模型后以最近一次为准
+        let upgraded = ModelVariant::new("control", "openai", "gpt-4o");
+        metrics.record_success("a", &upgraded, 100, "", "");
+        let control = metrics
+            .get("a")
+            .into_iter()
+            .find(|m| m.variant == "control")
+            .unwrap();
+        assert_eq!((control.model.as_str(), control.requests), ("gpt-4o", 6));
+    }
+}
@@ -0,0 +1,424 @@
+//! 提供商请求调度
+//!
+//! 多个聊天与后台任务同时调用同一提供商时，按优先级排队并限制每个提供商的并发数，
+//! 保证交互式请求优先获得执行机会。
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// 请求优先级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestPriority {
+    /// 交互式请求（用户正在等待）
+    #[default]
+    Interactive,
+    /// 后台请求
+    Background,
+    /// 批处理请求
+    Batch,
+}
+
+impl RequestPriority {
+    /// 按优先级从高到低排列
+    pub const ALL: [RequestPriority; 3] = [
+        RequestPriority::Interactive,
+        RequestPriority::Background,
+        RequestPriority::Batch,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            RequestPriority::Interactive => 0,
+            RequestPriority::Background => 1,
+            RequestPriority::Batch => 2,
+        }
+    }
+}
+
+/// 调度器配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerConfig {
+    /// 未单独配置的提供商的并发上限
+    pub default_concurrency: usize,
+    /// 各提供商的并发上限
+    pub provider_concurrency: HashMap<String, usize>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            default_concurrency: 4,
+            provider_concurrency: HashMap::new(),
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// 设置默认并发上限
+    pub fn with_default_concurrency(mut self, limit: usize) -> Self {
+        self.default_concurrency = limit;
+        self
+    }
+
+    /// 设置指定提供商的并发上限
+    pub fn with_provider_concurrency<S: Into<String>>(mut self, provider: S, limit: usize) -> Self {
+        self.provider_concurrency.insert(provider.into(), limit);
+        self
+    }
+
+    fn limit_for(&self, provider: &str) -> usize {
+        self.provider_concurrency
+            .get(provider)
+            .copied()
+            .unwrap_or(self.default_concurrency)
+            .max(1)
+    }
+}
+
+/// 单个优先级的排队指标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueMetrics {
+    /// 优先级
+    pub priority: RequestPriority,
+    /// 当前排队数量
+    pub queued: usize,
+    /// 已开始执行的请求数量
+    pub started: u64,
+    /// 平均排队时间（毫秒）
+    pub avg_wait_ms: f64,
+    /// 最长排队时间（毫秒）
+    pub max_wait_ms: u64,
+}
+
+/// 单个提供商的调度指标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderQueueMetrics {
+    /// 提供商名称
+    pub provider: String,
+    /// 并发上限
+    pub limit: usize,
+    /// 正在执行的请求数量
+    pub running: usize,
+    /// 各优先级的排队指标，按优先级从高到低排列
+    pub queues: Vec<QueueMetrics>,
+}
+
+#[derive(Debug, Default)]
+struct WaitCounters {
+    started: u64,
+    total_wait: Duration,
+    max_wait: Duration,
+}
+
+#[derive(Debug)]
+struct ProviderQueue {
+    limit: usize,
+    running: usize,
+    waiters: [VecDeque<oneshot::Sender<()>>; 3],
+    counters: [WaitCounters; 3],
+}
+
+impl ProviderQueue {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            running: 0,
+            waiters: Default::default(),
+            counters: Default::default(),
+        }
+    }
+
+    fn has_waiters(&self) -> bool {
+        self.waiters
+            .iter()
+            .any(|queue| queue.iter().any(|tx| !tx.is_closed()))
+    }
+
+    fn record_wait(&mut self, priority: RequestPriority, wait: Duration) {
+        let counters = &mut self.counters[priority.index()];
+        counters.started += 1;
+        counters.total_wait += wait;
+        counters.max_wait = counters.max_wait.max(wait);
+    }
+
+    /// 释放一个执行名额，优先交给最高优先级的等待者
+    fn release(&mut self) {
+        for queue in self.waiters.iter_mut() {
+            while let Some(tx) = queue.pop_front() {
+                // 等待者已取消时继续尝试下一个，名额直接转交，running 不变
+                if tx.send(()).is_ok() {
+                    return;
+                }
+            }
+        }
+        self.running = self.running.saturating_sub(1);
+    }
+
+    fn metrics(&self, provider: &str) -> ProviderQueueMetrics {
+        let queues = RequestPriority::ALL
+            .iter()
+            .map(|&priority| {
+                let counters = &self.counters[priority.index()];
+                QueueMetrics {
+                    priority,
+                    queued: self.waiters[priority.index()]
+                        .iter()
+                        .filter(|tx| !tx.is_closed())
+                        .count(),
+                    started: counters.started,
+                    avg_wait_ms: if counters.started == 0 {
+                        0.0
+                    } else {
+                        counters.total_wait.as_secs_f64() * 1000.0 / counters.started as f64
+                    },
+                    max_wait_ms: counters.max_wait.as_millis() as u64,
+                }
+            })
+            .collect();
+
+        ProviderQueueMetrics {
+            provider: provider.to_string(),
+            limit: self.limit,
+            running: self.running,
+            queues,
+        }
+    }
+}
+
+/// 提供商请求调度器
+#[derive(Debug, Clone)]
+pub struct RequestScheduler {
+    config: Arc<SchedulerConfig>,
+    queues: Arc<Mutex<HashMap<String, ProviderQueue>>>,
+}
+
+impl RequestScheduler {
+    /// 创建调度器
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            queues: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 获取调度器配置
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// 等待获得指定提供商的执行名额，名额在返回的许可被丢弃时释放
+    pub async fn acquire(&self, provider: &str, priority: RequestPriority) -> RequestPermit {
+        let enqueued_at = Instant::now();
+
+        let receiver = {
+            let mut queues = self.queues.lock().unwrap();
+            let queue = queues
+                .entry(provider.to_string())
+                .or_insert_with(|| ProviderQueue::new(self.config.limit_for(provider)));
+
+            if queue.running < queue.limit && !queue.has_waiters() {
+                queue.running += 1;
+                queue.record_wait(priority, Duration::ZERO);
+                None
+            } else {
+                let (tx, rx) = oneshot::channel();
+                queue.waiters[priority.index()].push_back(tx);
+                debug!(
+                    "提供商 {} 已达并发上限 {}，请求进入 {:?} 队列",
+                    provider, queue.limit, priority
+                );
+                Some(rx)
+            }
+        };
+
+        if let Some(receiver) = receiver {
+            // 等待期间被取消时由守卫归还已转交但未领取的名额
+            let mut pending = PendingSlot {
+                receiver: Some(receiver),
+                queues: self.queues.clone(),
+                provider: provider.to_string(),
+            };
+            if let Some(receiver) = pending.receiver.as_mut() {
+                // 发送端只会在转交名额时使用，调度器存活期间不会被提前丢弃
+                let _ = receiver.await;
+            }
+            pending.receiver = None;
+            let wait = enqueued_at.elapsed();
+            if let Some(queue) = self.queues.lock().unwrap().get_mut(provider) {
+                queue.record_wait(priority, wait);
+            }
+            debug!("提供商 {} 的 {:?} 请求排队 {:?}", provider, priority, wait);
+        }
+
+        RequestPermit {
+            queues: self.queues.clone(),
+            provider: provider.to_string(),
+        }
+    }
+
+    /// 获取所有提供商的调度指标
+    pub fn get_metrics(&self) -> Vec<ProviderQueueMetrics> {
+        let queues = self.queues.lock().unwrap();
+        let mut metrics: Vec<ProviderQueueMetrics> = queues
+            .iter()
+            .map(|(provider, queue)| queue.metrics(provider))
+            .collect();
+        metrics.sort_by(|a, b| a.provider.cmp(&b.provider));
+        metrics
+    }
+}
+
+impl Default for RequestScheduler {
+    fn default() -> Self {
+        Self::new(SchedulerConfig::default())
+    }
+}
+
+/// 提供商执行许可
+#[derive(Debug)]
+pub struct RequestPermit {
+    queues: Arc<Mutex<HashMap<String, ProviderQueue>>>,
+    provider: String,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        release_slot(&self.queues, &self.provider);
+    }
+}
+
+/// 排队中的请求；`acquire` 在领取名额前被取消时，归还已经转交给它的名额
+struct PendingSlot {
+    receiver: Option<oneshot::Receiver<()>>,
+    queues: Arc<Mutex<HashMap<String, ProviderQueue>>>,
+    provider: String,
+}
+
+impl Drop for PendingSlot {
+    fn drop(&mut self) {
+        let Some(mut receiver) = self.receiver.take() else {
+            return;
+        };
+        // 关闭后发送端无法再转交；关闭前已经转交的名额在这里取出并释放
+        receiver.close();
+        if receiver.try_recv().is_ok() {
+            release_slot(&self.queues, &self.provider);
+        }
+    }
+}
+
+fn release_slot(queues: &Mutex<HashMap<String, ProviderQueue>>, provider: &str) {
+    let Ok(mut queues) = queues.lock() else {
+        return;
+    };
+    if let Some(queue) = queues.get_mut(provider) {
+        queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_interactive_requests_run_first() {
+        let scheduler = RequestScheduler::new(
+            SchedulerConfig::default().with_provider_concurrency("openai", 1),
+        );
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let running = scheduler
+            .acquire("openai", RequestPriority::Interactive)
+            .await;
+
+        let mut handles = Vec::new();
+        for priority in [RequestPriority::Batch, RequestPriority::Interactive] {
+            let scheduler = scheduler.clone();
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire("openai", priority).await;
+                order.lock().unwrap().push(priority);
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let metrics = scheduler.get_metrics();
+        assert_eq!(metrics[0].running, 1);
+        assert_eq!(metrics[0].queues[0].queued, 1);
+        assert_eq!(metrics[0].queues[2].queued, 1);
+
+        drop(running);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![RequestPriority::Interactive, RequestPriority::Batch]
+        );
+
+        let metrics = scheduler.get_metrics();
+        assert_eq!(metrics[0].running, 0);
+        assert_eq!(metrics[0].queues[0].started, 2);
+        assert!(metrics[0].queues[2].max_wait_ms >= 10);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_releases_slot() {
+        let scheduler = RequestScheduler::new(
+            SchedulerConfig::default().with_provider_concurrency("openai", 1),
+        );
+        let running = scheduler
+            .acquire("openai", RequestPriority::Interactive)
+            .await;
+
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(10),
+            scheduler.acquire("openai", RequestPriority::Background),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        drop(running);
+        assert_eq!(scheduler.get_metrics()[0].running, 0);
+        let _permit = scheduler.acquire("openai", RequestPriority::Batch).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_after_handoff_recovers_capacity() {
+        let scheduler = RequestScheduler::new(
+            SchedulerConfig::default().with_provider_concurrency("openai", 1),
+        );
+        let running = scheduler
+            .acquire("openai", RequestPriority::Interactive)
+            .await;
+
+        // 轮询一次使请求进入队列，随后名额转交给它，但在领取许可前取消
+        let mut waiting = Box::pin(scheduler.acquire("openai", RequestPriority::Background));
+        assert!(futures::poll!(waiting.as_mut()).is_pending());
+        assert_eq!(scheduler.get_metrics()[0].queues[1].queued, 1);
+        drop(running);
+        assert_eq!(scheduler.get_metrics()[0].running, 1);
+        drop(waiting);
+
+        let metrics = scheduler.get_metrics();
+        assert_eq!(metrics[0].running, 0);
+        assert_eq!(metrics[0].queues[1].queued, 0);
+        let permit = tokio::time::timeout(
+            Duration::from_millis(100),
+            scheduler.acquire("openai", RequestPriority::Batch),
+        )
+        .await
+        .expect("名额应已归还");
+        assert_eq!(scheduler.get_metrics()[0].running, 1);
+        drop(permit);
+        assert_eq!(scheduler.get_metrics()[0].running, 0);
+    }
+}
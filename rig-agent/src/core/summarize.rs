@@ -0,0 +1,344 @@
+//! 长文档摘要 - 对超出上下文窗口的文档做 map-reduce 摘要
+//!
+//! 文档先按 Markdown 标题拆分为章节，章节再按段落切块（块大小以字符计）。
+//! map 阶段逐块摘要；reduce 阶段先把每个章节的块摘要合并为章节摘要，
+//! 再由章节摘要生成全文摘要与要点。摘要过多、一次放不下时分批逐层合并。
+//! 没有标题的文档每个块作为一个章节
+
+use crate::core::types::AgentConfig;
+use crate::eval::extract_json;
+use serde::{Deserialize, Serialize};
+
+/// 默认块大小（字符数）
+pub const DEFAULT_CHUNK_CHARS: usize = 6000;
+/// 最小块大小，避免把文档切得过碎
+const MIN_CHUNK_CHARS: usize = 500;
+
+fn default_chunk_chars() -> usize {
+    DEFAULT_CHUNK_CHARS
+}
+
+/// 摘要选项，未设置提供商或模型时沿用默认配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizeOptions {
+    /// 块大小（字符数）
+    #[serde(default = "default_chunk_chars")]
+    pub chunk_chars: usize,
+    /// 提供商
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// 模型
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 文档标题
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl Default for SummarizeOptions {
+    fn default() -> Self {
+        Self {
+            chunk_chars: DEFAULT_CHUNK_CHARS,
+            provider: None,
+            model: None,
+            title: None,
+        }
+    }
+}
+
+impl SummarizeOptions {
+    /// 设置块大小
+    pub fn with_chunk_chars(mut self, chunk_chars: usize) -> Self {
+        self.chunk_chars = chunk_chars;
+        self
+    }
+
+    /// 使用指定提供商和模型
+    pub fn with_model<S: Into<String>>(mut self, provider: S, model: S) -> Self {
+        self.provider = Some(provider.into());
+        self.model = Some(model.into());
+        self
+    }
+
+    /// 设置文档标题
+    pub fn with_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// 实际使用的块大小
+    pub fn effective_chunk_chars(&self) -> usize {
+        self.chunk_chars.max(MIN_CHUNK_CHARS)
+    }
+
+    /// 摘要请求使用的配置
+    pub fn config(&self, base: &AgentConfig) -> AgentConfig {
+        AgentConfig::new(
+            self.provider.as_deref().unwrap_or(&base.provider),
+            self.model.as_deref().unwrap_or(&base.model),
+        )
+    }
+}
+
+/// 文档章节
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentSection {
+    /// 标题
+    pub title: String,
+    /// 切分后的块
+    pub chunks: Vec<String>,
+}
+
+/// 章节摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionSummary {
+    /// 标题
+    pub title: String,
+    /// 摘要
+    pub summary: String,
+    /// 块数
+    pub chunks: usize,
+    /// 字符数
+    pub chars: usize,
+}
+
+/// 文档摘要
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentSummary {
+    /// 文档标题
+    pub title: Option<String>,
+    /// 全文摘要
+    pub summary: String,
+    /// 要点
+    pub key_points: Vec<String>,
+    /// 各章节摘要
+    pub sections: Vec<SectionSummary>,
+    /// 总块数
+    pub chunks: usize,
+    /// 文档字符数
+    pub chars: usize,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// Markdown 标题的文本，不是标题时返回 `None`
+fn heading(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim();
+    (!title.is_empty()).then_some(title)
+}
+
+/// 按 Markdown 标题拆分章节，章节内按段落切块
+pub fn split_sections(text: &str, chunk_chars: usize) -> Vec<DocumentSection> {
+    let mut parts: Vec<(Option<String>, String)> = vec![(None, String::new())];
+    for line in text.lines() {
+        if let Some(title) = heading(line) {
+            parts.push((Some(title.to_string()), String::new()));
+        } else {
+            let body = &mut parts.last_mut().expect("至少有一个章节").1;
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    parts.retain(|(_, body)| !body.trim().is_empty());
+
+    if let [(None, body)] = parts.as_slice() {
+        return chunk_text(body, chunk_chars)
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| DocumentSection {
+                title: format!("第 {} 部分", i + 1),
+                chunks: vec![chunk],
+            })
+            .collect();
+    }
+    parts
+        .into_iter()
+        .map(|(title, body)| DocumentSection {
+            title: title.unwrap_or_else(|| "开头".to_string()),
+            chunks: chunk_text(&body, chunk_chars),
+        })
+        .collect()
+}
+
+/// 按段落切块，每块不超过 `chunk_chars` 个字符；超长的段落按字符硬切
+pub fn chunk_text(text: &str, chunk_chars: usize) -> Vec<String> {
+    let chunk_chars = chunk_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let chars = paragraph.chars().count();
+        if current_chars > 0 && current_chars + 2 + chars > chunk_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if chars > chunk_chars {
+            let all: Vec<char> = paragraph.chars().collect();
+            for piece in all.chunks(chunk_chars) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        if current_chars > 0 {
+            current.push_str("\n\n");
+            current_chars += 2;
+        }
+        current.push_str(paragraph);
+        current_chars += chars;
+    }
+    if current_chars > 0 {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// 把摘要分批，每批合计不超过 `max_chars` 个字符，但至少两条，保证逐层合并时条数减少
+pub fn batches(summaries: &[String], max_chars: usize) -> Vec<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut current_chars = 0;
+    for summary in summaries {
+        let chars = summary.chars().count();
+        if current.len() >= 2 && current_chars + chars > max_chars {
+            batches.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        current.push(summary.clone());
+        current_chars += chars;
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+fn document_label(title: Option<&str>) -> String {
+    title
+        .map(|title| format!("文档《{}》", title))
+        .unwrap_or_else(|| "文档".to_string())
+}
+
+/// map 阶段：摘要一个块
+pub fn chunk_prompt(
+    title: Option<&str>,
+    section: &str,
+    index: usize,
+    total: usize,
+    chunk: &str,
+) -> String {
+    format!(
+        "以下是{}中“{}”部分的第 {}/{} 段。请概括这段内容的要点，保留关键事实、数字与结论，\
+         不要添加原文中没有的信息，只输出摘要。\n\n{}",
+        document_label(title),
+        section,
+        index + 1,
+        total,
+        chunk
+    )
+}
+
+/// reduce 阶段：把同一章节的若干段摘要合并为一段
+pub fn section_prompt(title: Option<&str>, section: &str, summaries: &[String]) -> String {
+    format!(
+        "以下是{}中“{}”部分按顺序排列的若干段摘要。请把它们合并为一段连贯的摘要，\
+         去掉重复内容，保留关键事实、数字与结论，只输出摘要。\n\n{}",
+        document_label(title),
+        section,
+        numbered(summaries)
+    )
+}
+
+/// 最终 reduce：由章节摘要生成全文摘要与要点
+pub fn document_prompt(title: Option<&str>, sections: &[String]) -> String {
+    format!(
+        "以下是{}各部分按顺序排列的摘要。请写出全文摘要，并列出 3 到 7 条要点。\n\n{}\n\n\
+         请只输出 JSON，格式为 {{\"summary\": \"全文摘要\", \"key_points\": [\"要点\"]}}。",
+        document_label(title),
+        numbered(sections)
+    )
+}
+
+fn numbered(items: &[String]) -> String {
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| format!("[{}] {}", i + 1, item.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 解析最终摘要的回复；模型没有按格式输出时整段回复作为摘要
+pub fn parse_document_summary(reply: &str) -> (String, Vec<String>) {
+    #[derive(Deserialize)]
+    struct Reply {
+        summary: String,
+        #[serde(default)]
+        key_points: Vec<String>,
+    }
+
+    match serde_json::from_str::<Reply>(extract_json(reply)) {
+        Ok(parsed) => (parsed.summary.trim().to_string(), parsed.key_points),
+        Err(_) => (reply.trim().to_string(), Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_reduce_document() {
+        let text = "前言一段。\n\n# 背景\n\n第一段。\n\n第二段。\n\n## 方法 ##\n\n步骤说明。\n\n#标签不是标题\n";
+        let sections = split_sections(text, 1000);
+        let titles: Vec<&str> = sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["开头", "背景", "方法"]);
+        assert_eq!(sections[1].chunks, ["第一段。\n\n第二段。"]);
+        assert_eq!(sections[2].chunks, ["步骤说明。\n\n#标签不是标题"]);
+
+        // 按段落切块，超长段落按字符硬切
+        assert_eq!(
+            chunk_text("甲甲\n\n乙乙\n\n丙丙", 6),
+            ["甲甲\n\n乙乙", "丙丙"]
+        );
+        assert_eq!(chunk_text("一二三四五", 2), ["一二", "三四", "五"]);
+
+        // 没有标题的文档每块一个章节
+        let plain = split_sections("甲甲\n\n乙乙", 2);
+        assert_eq!(plain.len(), 2);
+        assert_eq!(plain[1].title, "第 2 部分");
+
+        let summaries: Vec<String> = ["长长长长", "长长长长", "短", "短"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let grouped = batches(&summaries, 3);
+        assert_eq!(grouped.len(), 2);
+        assert!(grouped.iter().all(|batch| batch.len() == 2));
+
+        let (summary, points) = parse_document_summary(
+            "```json\n{\"summary\": \" 全文 \", \"key_points\": [\"一\", \"二\"]}\n```",
+        );
+        assert_eq!(summary, "全文");
+        assert_eq!(points, ["一", "二"]);
+        assert_eq!(parse_document_summary("只有文字").0, "只有文字");
+
+        let options = SummarizeOptions::default().with_chunk_chars(10);
+        assert_eq!(options.effective_chunk_chars(), MIN_CHUNK_CHARS);
+        let config = options.config(&AgentConfig::new("openai", "gpt-4o-mini"));
+        assert_eq!(config.model, "gpt-4o-mini");
+    }
+}
@@ -0,0 +1,51 @@
+//! 提示词模板 - 用会话变量替换 `{{key}}` 占位符
+
+use std::collections::BTreeMap;
+
+/// 渲染模板，`{{key}}`（允许两侧空白）替换为对应变量，未定义的占位符保持原样
+pub fn render_template(template: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+
+        match variables.get(after[..end].trim()) {
+            Some(value) => output.push_str(value),
+            None => output.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let mut variables = BTreeMap::new();
+        variables.insert("user_name".to_string(), "小明".to_string());
+        variables.insert("project_path".to_string(), "/work/app".to_string());
+
+        assert_eq!(
+            render_template(
+                "你好 {{user_name}}，当前项目：{{ project_path }}",
+                &variables
+            ),
+            "你好 小明，当前项目：/work/app"
+        );
+        assert_eq!(
+            render_template("{{unknown}} 与 {{user_name", &variables),
+            "{{unknown}} 与 {{user_name"
+        );
+    }
+}
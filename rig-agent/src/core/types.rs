@@ -1,5 +1,15 @@
 //! Agent 核心类型定义
 
+use crate::core::citation::Citation;
+use crate::core::compress::{CompressionConfig, CompressionReport};
+use crate::core::context::RequestContext;
+use crate::core::creativity::CreativityProfile;
+use crate::core::determinism::{DEFAULT_SEED, RequestFingerprint};
+use crate::core::finish::{FinishReason, ResponseWarning};
+use crate::core::language::ReplyLanguage;
+use crate::core::race::RaceConfig;
+use crate::core::routing::AbRoute;
+use crate::core::scheduler::RequestPriority;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +26,22 @@ pub struct ClientConfig {
     pub base_url: Option<String>,
     /// 其他配置参数
     pub extra_params: std::collections::HashMap<String, serde_json::Value>,
+    /// 流式响应模式
+    #[serde(default)]
+    pub streaming: StreamingMode,
+}
+
+/// 提供商的流式响应模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamingMode {
+    /// 先尝试流式，检测到提供商不支持时改为普通调用，并记住检测结果
+    #[default]
+    Auto,
+    /// 总是流式调用，失败时直接返回错误
+    Always,
+    /// 从不流式调用，完整响应作为一段回调
+    Never,
 }
 
 impl ClientConfig {
@@ -27,6 +53,7 @@ impl ClientConfig {
             api_key: None,
             base_url: None,
             extra_params: std::collections::HashMap::new(),
+            streaming: StreamingMode::Auto,
         }
     }
 
@@ -47,6 +74,19 @@ impl ClientConfig {
         self.extra_params.insert(key.into(), value.into());
         self
     }
+
+    /// 设置流式响应模式
+    pub fn with_streaming(mut self, streaming: StreamingMode) -> Self {
+        self.streaming = streaming;
+        self
+    }
+}
+
+/// 模型调用的默认超时时间（毫秒）
+pub const DEFAULT_TIMEOUT_MS: u64 = 120_000;
+
+fn default_timeout_ms() -> Option<u64> {
+    Some(DEFAULT_TIMEOUT_MS)
 }
 
 /// Agent 配置
@@ -60,12 +100,39 @@ pub struct AgentConfig {
     pub preamble: Option<String>,
     /// 温度参数 (0.0-2.0)
     pub temperature: Option<f32>,
+    /// 创造性配置，设置后按配置的采样参数调用模型，覆盖 `temperature`
+    #[serde(default)]
+    pub creativity: Option<CreativityProfile>,
     /// 最大令牌数
     pub max_tokens: Option<u32>,
+    /// 随机种子，只发给支持种子的提供商
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// 确定性模式：固定种子、温度设为 0，并在响应中记录请求指纹，用于回归测试
+    #[serde(default)]
+    pub deterministic: bool,
     /// 是否启用工具
     pub enable_tools: bool,
+    /// 工具选择（启用工具时生效）
+    #[serde(default)]
+    pub tool_selection: ToolSelection,
     /// 历史消息限制
     pub history_limit: Option<usize>,
+    /// 回复语言控制
+    #[serde(default)]
+    pub reply_language: ReplyLanguage,
+    /// A/B 路由策略，设置后按比例在两套模型配置间分配请求
+    #[serde(default)]
+    pub ab_route: Option<AbRoute>,
+    /// 提供商竞速策略，设置后交互式请求同时发给挑战者，先返回的一方胜出
+    #[serde(default)]
+    pub race: Option<RaceConfig>,
+    /// 提示词压缩，设置后发给模型前压缩过长的对话历史
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    /// 单次模型调用的超时时间（毫秒），为空时不限制
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: Option<u64>,
     /// 其他配置参数
     pub extra_params: std::collections::HashMap<String, serde_json::Value>,
 }
@@ -78,9 +145,18 @@ impl AgentConfig {
             provider: provider.into(),
             preamble: Some("你是一个有用的AI助手。".to_string()),
             temperature: Some(0.7),
+            creativity: None,
             max_tokens: Some(1000),
+            seed: None,
+            deterministic: false,
             enable_tools: false,
+            tool_selection: ToolSelection::default(),
             history_limit: Some(50),
+            reply_language: ReplyLanguage::default(),
+            ab_route: None,
+            race: None,
+            compression: None,
+            timeout_ms: default_timeout_ms(),
             extra_params: std::collections::HashMap::new(),
         }
     }
@@ -91,30 +167,100 @@ impl AgentConfig {
         self
     }
 
+    /// 设置单次模型调用的超时时间（毫秒）
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = Some(timeout_ms);
+        self
+    }
+
     /// 设置温度
     pub fn with_temperature(mut self, temperature: f32) -> Self {
         self.temperature = Some(temperature);
         self
     }
 
+    /// 设置创造性配置
+    pub fn with_creativity(mut self, creativity: CreativityProfile) -> Self {
+        self.creativity = Some(creativity);
+        self
+    }
+
     /// 设置最大令牌数
     pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
         self.max_tokens = Some(max_tokens);
         self
     }
 
+    /// 设置随机种子
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// 设置确定性模式
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// 实际使用的种子：确定性模式下未设置种子时使用 [`DEFAULT_SEED`]
+    pub fn effective_seed(&self) -> Option<u64> {
+        self.seed.or(self.deterministic.then_some(DEFAULT_SEED))
+    }
+
     /// 启用工具
     pub fn with_tools(mut self, enable: bool) -> Self {
         self.enable_tools = enable;
         self
     }
 
+    /// 设置工具选择
+    pub fn with_tool_selection(mut self, selection: ToolSelection) -> Self {
+        self.tool_selection = selection;
+        self
+    }
+
+    /// 仅允许指定的工具、命名空间或工具组，并启用工具
+    pub fn allow_tools<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.enable_tools = true;
+        self.tool_selection = ToolSelection::only(patterns);
+        self
+    }
+
     /// 设置历史限制
     pub fn with_history_limit(mut self, limit: usize) -> Self {
         self.history_limit = Some(limit);
         self
     }
 
+    /// 设置回复语言控制
+    pub fn with_reply_language(mut self, reply_language: ReplyLanguage) -> Self {
+        self.reply_language = reply_language;
+        self
+    }
+
+    /// 设置 A/B 路由策略
+    pub fn with_ab_route(mut self, ab_route: AbRoute) -> Self {
+        self.ab_route = Some(ab_route);
+        self
+    }
+
+    /// 设置提供商竞速策略
+    pub fn with_race(mut self, race: RaceConfig) -> Self {
+        self.race = Some(race);
+        self
+    }
+
+    /// 设置提示词压缩
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
     /// 添加额外参数
     pub fn with_param<S: Into<String>, V: Into<serde_json::Value>>(mut self, key: S, value: V) -> Self {
         self.extra_params.insert(key.into(), value.into());
@@ -122,14 +268,152 @@ impl AgentConfig {
     }
 }
 
+/// 工具选择规则
+///
+/// 规则可以是工具名（`weather`）、命名空间限定名（`net.weather`）、
+/// 命名空间/工具组（`net` 或 `net.*`）或通配符 `*`。
+/// `allow` 为空时允许全部工具，`deny` 优先于 `allow`。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolSelection {
+    /// 允许列表
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// 拒绝列表
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl ToolSelection {
+    /// 允许全部工具
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// 仅允许匹配的工具
+    pub fn only<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allow: patterns.into_iter().map(Into::into).collect(),
+            deny: Vec::new(),
+        }
+    }
+
+    /// 添加允许规则
+    pub fn with_allow<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.allow.push(pattern.into());
+        self
+    }
+
+    /// 添加拒绝规则
+    pub fn with_deny<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.deny.push(pattern.into());
+        self
+    }
+
+    /// 检查工具是否被选中
+    pub fn allows(&self, name: &str, namespace: &str) -> bool {
+        if self
+            .deny
+            .iter()
+            .any(|pattern| Self::pattern_matches(pattern, name, namespace))
+        {
+            return false;
+        }
+
+        self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|pattern| Self::pattern_matches(pattern, name, namespace))
+    }
+
+    fn pattern_matches(pattern: &str, name: &str, namespace: &str) -> bool {
+        let pattern = pattern.trim();
+        if pattern == "*" || pattern == name || pattern == namespace {
+            return true;
+        }
+
+        match pattern.split_once('.') {
+            Some((ns, "*")) => ns == namespace,
+            Some((ns, tool)) => ns == namespace && tool == name,
+            None => false,
+        }
+    }
+}
+
 impl Default for AgentConfig {
     fn default() -> Self {
         Self::new("openai", "gpt-3.5-turbo")
     }
 }
 
-/// Agent 响应
+/// 单次请求选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatOptions {
+    /// 请求优先级
+    #[serde(default)]
+    pub priority: RequestPriority,
+    /// 跳过响应缓存，强制调用模型
+    #[serde(default)]
+    pub bypass_cache: bool,
+    /// 附加到用户消息的元数据
+    #[serde(default)]
+    pub metadata: MessageMetadata,
+    /// 覆盖 Agent 配置中的模型调用超时时间（毫秒）
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// 本条消息使用的创造性配置，覆盖 Agent 配置
+    #[serde(default)]
+    pub creativity: Option<CreativityProfile>,
+    /// 请求上下文，由适配器根据可信的来源填写，不从请求体中反序列化
+    #[serde(skip)]
+    pub context: Option<RequestContext>,
+}
+
+impl ChatOptions {
+    /// 设置请求优先级
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 设置是否跳过响应缓存
+    pub fn with_bypass_cache(mut self, bypass_cache: bool) -> Self {
+        self.bypass_cache = bypass_cache;
+        self
+    }
+
+    /// 设置本次请求的模型调用超时时间（毫秒）
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    /// 设置本条消息使用的创造性配置
+    pub fn with_creativity(mut self, creativity: CreativityProfile) -> Self {
+        self.creativity = Some(creativity);
+        self
+    }
+
+    /// 添加用户消息元数据
+    pub fn with_metadata<S: Into<String>>(mut self, key: S, value: serde_json::Value) -> Self {
+        self.metadata.insert(key.into(), value);
+        self
+    }
+
+    /// 设置请求上下文
+    pub fn with_context(mut self, context: RequestContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+}
+
+/// Agent 响应，线上格式带版本号，见 [`crate::core::wire`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct AgentResponse {
     /// 响应 ID
     pub id: String,
@@ -145,8 +429,26 @@ pub struct AgentResponse {
     pub usage: Option<TokenUsage>,
     /// 工具调用
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// 回答引用的工具输出
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    /// 发给模型前对话历史的压缩报告，没有压缩时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionReport>,
+    /// 使用的创造性配置，没有设置时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creativity: Option<CreativityProfile>,
+    /// 确定性模式下的请求指纹
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<RequestFingerprint>,
     /// 完成原因
-    pub finish_reason: Option<String>,
+    pub finish_reason: Option<FinishReason>,
+    /// 响应警告，例如对话历史被截断
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ResponseWarning>,
+    /// A/B 路由选中的变体
+    #[serde(default)]
+    pub variant: Option<String>,
 }
 
 /// 令牌使用统计
@@ -237,9 +539,15 @@ pub struct ToolResult {
     pub duration_ms: u64,
 }
 
+/// 消息元数据（客户端消息 ID、UI 状态、表情回应等）
+pub type MessageMetadata = std::collections::HashMap<String, serde_json::Value>;
+
 /// Agent 消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentMessage {
+    /// 消息 ID
+    #[serde(default = "new_message_id")]
+    pub id: String,
     /// 消息角色
     pub role: AgentRole,
     /// 消息内容
@@ -252,54 +560,70 @@ pub struct AgentMessage {
     pub tool_calls: Vec<ToolCall>,
     /// 工具结果（如果有）
     pub tool_results: Vec<ToolResult>,
+    /// 自定义元数据
+    #[serde(default, skip_serializing_if = "MessageMetadata::is_empty")]
+    pub metadata: MessageMetadata,
+}
+
+/// 生成新的消息 ID
+pub(crate) fn new_message_id() -> String {
+    uuid::Uuid::new_v4().to_string()
 }
 
 impl AgentMessage {
     /// 创建用户消息
     pub fn user(content: String) -> Self {
         Self {
+            id: new_message_id(),
             role: AgentRole::User,
             content,
             message_type: MessageType::Text,
             timestamp: Utc::now(),
             tool_calls: Vec::new(),
             tool_results: Vec::new(),
+            metadata: MessageMetadata::new(),
         }
     }
 
     /// 创建助手消息
     pub fn assistant(content: String) -> Self {
         Self {
+            id: new_message_id(),
             role: AgentRole::Assistant,
             content,
             message_type: MessageType::Text,
             timestamp: Utc::now(),
             tool_calls: Vec::new(),
             tool_results: Vec::new(),
+            metadata: MessageMetadata::new(),
         }
     }
 
     /// 创建系统消息
     pub fn system(content: String) -> Self {
         Self {
+            id: new_message_id(),
             role: AgentRole::System,
             content,
             message_type: MessageType::System,
             timestamp: Utc::now(),
             tool_calls: Vec::new(),
             tool_results: Vec::new(),
+            metadata: MessageMetadata::new(),
         }
     }
 
     /// 创建工具调用消息
     pub fn tool_call(tool_calls: Vec<ToolCall>) -> Self {
         Self {
+            id: new_message_id(),
             role: AgentRole::Assistant,
             content: "正在调用工具...".to_string(),
             message_type: MessageType::ToolCall,
             timestamp: Utc::now(),
             tool_calls,
             tool_results: Vec::new(),
+            metadata: MessageMetadata::new(),
         }
     }
 
@@ -312,31 +636,52 @@ impl AgentMessage {
             .join("\n");
 
         Self {
+            id: new_message_id(),
             role: AgentRole::Tool,
             content,
             message_type: MessageType::ToolResult,
             timestamp: Utc::now(),
             tool_calls: Vec::new(),
             tool_results,
+            metadata: MessageMetadata::new(),
         }
     }
 
     /// 创建错误消息
     pub fn error(error: String) -> Self {
         Self {
+            id: new_message_id(),
             role: AgentRole::System,
             content: format!("错误: {}", error),
             message_type: MessageType::Error,
             timestamp: Utc::now(),
             tool_calls: Vec::new(),
             tool_results: Vec::new(),
+            metadata: MessageMetadata::new(),
         }
     }
 
+    /// 设置消息 ID
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// 设置元数据
+    pub fn with_metadata(mut self, metadata: MessageMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// 合并注解到元数据，值为 null 的键会被移除
+    pub fn annotate(&mut self, annotations: MessageMetadata) {
+        merge_metadata(&mut self.metadata, annotations);
+    }
+
     /// 获取消息的令牌估算数量
     pub fn estimated_tokens(&self) -> u32 {
         // 简单的令牌估算：大约 4 个字符 = 1 个令牌
-        (self.content.len() as u32 + 3) / 4
+        (self.content.len() as u32).div_ceil(4)
     }
 
     /// 检查消息是否包含工具调用
@@ -358,8 +703,9 @@ impl AgentMessage {
             AgentRole::Tool => "工具",
         };
 
+        // 截断位置退到字符边界，避免切在多字节字符中间
         let content_preview = if self.content.len() > 50 {
-            format!("{}...", &self.content[..50])
+            format!("{}...", &self.content[..self.content.floor_char_boundary(50)])
         } else {
             self.content.clone()
         };
@@ -368,6 +714,17 @@ impl AgentMessage {
     }
 }
 
+/// 合并注解到元数据，值为 null 的键会被移除
+pub fn merge_metadata(metadata: &mut MessageMetadata, annotations: MessageMetadata) {
+    for (key, value) in annotations {
+        if value.is_null() {
+            metadata.remove(&key);
+        } else {
+            metadata.insert(key, value);
+        }
+    }
+}
+
 /// 聊天会话信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatSession {
@@ -385,6 +742,15 @@ pub struct ChatSession {
     pub model: String,
     /// 会话标签
     pub tags: Vec<String>,
+    /// 关联的 Agent ID，Agent 的聊天记录会写入该会话
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// 删除时间（软删除，可恢复）
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// 会话变量（如 user_name、project_path），可在提示词模板中以 `{{key}}` 引用
+    #[serde(default)]
+    pub variables: std::collections::BTreeMap<String, String>,
 }
 
 impl ChatSession {
@@ -399,9 +765,23 @@ impl ChatSession {
             message_count: 0,
             model,
             tags: Vec::new(),
+            agent_id: None,
+            deleted_at: None,
+            variables: std::collections::BTreeMap::new(),
         }
     }
 
+    /// 关联 Agent
+    pub fn with_agent_id<S: Into<String>>(mut self, agent_id: S) -> Self {
+        self.agent_id = Some(agent_id.into());
+        self
+    }
+
+    /// 是否已被删除
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
     /// 更新会话
     pub fn update(&mut self, message_count: usize) {
         self.updated_at = Utc::now();
@@ -419,6 +799,16 @@ impl ChatSession {
     pub fn remove_tag(&mut self, tag: &str) {
         self.tags.retain(|t| t != tag);
     }
+
+    /// 设置会话变量
+    pub fn set_variable<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        self.variables.insert(key.into(), value.into());
+    }
+
+    /// 获取会话变量
+    pub fn get_variable(&self, key: &str) -> Option<&str> {
+        self.variables.get(key).map(String::as_str)
+    }
 }
 
 #[cfg(test)]
@@ -433,6 +823,33 @@ mod tests {
         assert_eq!(user_msg.message_type, MessageType::Text);
     }
 
+    #[test]
+    fn test_message_metadata_annotations() {
+        let mut msg =
+            AgentMessage::user("你好".to_string()).with_metadata(MessageMetadata::from([
+                ("client_id".to_string(), serde_json::json!("c-1")),
+                ("pinned".to_string(), serde_json::json!(true)),
+            ]));
+        msg.annotate(MessageMetadata::from([
+            ("reactions".to_string(), serde_json::json!(["👍"])),
+            ("pinned".to_string(), serde_json::Value::Null),
+        ]));
+        assert_eq!(msg.metadata.len(), 2);
+        assert!(!msg.metadata.contains_key("pinned"));
+
+        let json = serde_json::to_string(&msg).unwrap();
+        let restored: AgentMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.id, msg.id);
+        assert_eq!(restored.metadata["reactions"], serde_json::json!(["👍"]));
+
+        // 旧数据没有 id 与 metadata 字段时仍可解析
+        let mut legacy = serde_json::to_value(AgentMessage::user("旧消息".to_string())).unwrap();
+        legacy.as_object_mut().unwrap().remove("id");
+        let legacy: AgentMessage = serde_json::from_value(legacy).unwrap();
+        assert!(!legacy.id.is_empty());
+        assert!(legacy.metadata.is_empty());
+    }
+
     #[test]
     fn test_message_token_estimation() {
         let msg = AgentMessage::user("这是一个测试消息".to_string());
@@ -448,6 +865,26 @@ mod tests {
         assert_eq!(session.message_count, 0);
     }
 
+    #[test]
+    fn test_tool_selection_matching() {
+        let all = ToolSelection::all();
+        assert!(all.allows("weather", "net"));
+
+        let net_only = ToolSelection::only(["net"]);
+        assert!(net_only.allows("weather", "net"));
+        assert!(net_only.allows("web_search", "net"));
+        assert!(!net_only.allows("calculator", "math"));
+
+        let mixed = ToolSelection::only(["math.*", "net.weather"]).with_deny("calculator");
+        assert!(mixed.allows("weather", "net"));
+        assert!(!mixed.allows("web_search", "net"));
+        assert!(!mixed.allows("calculator", "math"));
+
+        let deny_group = ToolSelection::all().with_deny("fs");
+        assert!(!deny_group.allows("read_file", "fs"));
+        assert!(deny_group.allows("current_time", "time"));
+    }
+
     #[test]
     fn test_message_summary() {
         let msg = AgentMessage::user(
@@ -458,4 +895,3 @@ mod tests {
         assert!(summary.contains("..."));
     }
 }
-
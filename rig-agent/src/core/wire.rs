@@ -0,0 +1,253 @@
+//! 带版本号的线上格式
+//!
+//! 对外序列化的类型（保存到磁盘、发给前端或其他节点）在 JSON 中带有版本号 `"v"`，
+//! 字段的不兼容变更通过新版本号加迁移完成：
+//!
+//! - 类型本身用 `#[serde(remote = "Self")]` 生成当前版本的序列化函数，这里再包一层 `#[serde(tag = "v")]`
+//! - 旧版本保留单独的结构，解码时迁移为当前版本
+//! - 引入版本号之前的数据没有 `"v"`，按第一个版本解码
+//!
+//! 新增可选字段（带 `#[serde(default)]`）不需要升级版本。
+
+use crate::core::finish::FinishReason;
+use crate::core::gc::AgentEvent;
+use crate::core::types::{AgentResponse, TokenUsage, ToolCall};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// [`AgentResponse`] 的当前版本
+pub const AGENT_RESPONSE_VERSION: &str = "2";
+
+/// [`AgentEvent`] 的当前版本
+pub const AGENT_EVENT_VERSION: &str = "1";
+
+/// 第 1 版的 Agent 响应：完成原因为任意字符串，没有警告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentResponseV1 {
+    /// 响应 ID
+    pub id: String,
+    /// Agent ID
+    pub agent_id: String,
+    /// 响应内容
+    pub content: String,
+    /// 时间戳
+    pub timestamp: DateTime<Utc>,
+    /// 使用的模型
+    pub model: String,
+    /// 使用统计
+    pub usage: Option<TokenUsage>,
+    /// 工具调用
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// 完成原因
+    pub finish_reason: Option<String>,
+    /// A/B 路由选中的变体
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+impl From<AgentResponseV1> for AgentResponse {
+    fn from(v1: AgentResponseV1) -> Self {
+        Self {
+            id: v1.id,
+            agent_id: v1.agent_id,
+            content: v1.content,
+            timestamp: v1.timestamp,
+            model: v1.model,
+            usage: v1.usage,
+            tool_calls: v1.tool_calls,
+            citations: Vec::new(),
+            compression: None,
+            creativity: None,
+            fingerprint: None,
+            // 第 1 版的取值都是 OpenAI 风格的字符串
+            finish_reason: v1
+                .finish_reason
+                .map(|raw| FinishReason::from_provider("openai", &raw)),
+            warnings: Vec::new(),
+            variant: v1.variant,
+        }
+    }
+}
+
+/// 当前版本的 Agent 响应
+struct AgentResponseV2<T>(T);
+
+impl Serialize for AgentResponseV2<&AgentResponse> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AgentResponse::serialize(self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentResponseV2<AgentResponse> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AgentResponse::deserialize(deserializer).map(AgentResponseV2)
+    }
+}
+
+impl Serialize for AgentResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(tag = "v")]
+        enum Wire<'a> {
+            #[serde(rename = "2")]
+            V2(AgentResponseV2<&'a AgentResponse>),
+        }
+
+        Wire::V2(AgentResponseV2(self)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(tag = "v")]
+        enum Wire {
+            #[serde(rename = "1")]
+            V1(AgentResponseV1),
+            #[serde(rename = "2")]
+            V2(Box<AgentResponseV2<AgentResponse>>),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Versioned(Wire),
+            Unversioned(AgentResponseV1),
+        }
+
+        Ok(match Compat::deserialize(deserializer)? {
+            Compat::Versioned(Wire::V1(v1)) | Compat::Unversioned(v1) => v1.into(),
+            Compat::Versioned(Wire::V2(v2)) => v2.0,
+        })
+    }
+}
+
+/// 当前版本的 Agent 事件
+struct AgentEventV1<T>(T);
+
+impl Serialize for AgentEventV1<&AgentEvent> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AgentEvent::serialize(self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentEventV1<AgentEvent> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AgentEvent::deserialize(deserializer).map(AgentEventV1)
+    }
+}
+
+impl Serialize for AgentEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(tag = "v")]
+        enum Wire<'a> {
+            #[serde(rename = "1")]
+            V1(AgentEventV1<&'a AgentEvent>),
+        }
+
+        Wire::V1(AgentEventV1(self)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(tag = "v")]
+        enum Wire {
+            #[serde(rename = "1")]
+            V1(AgentEventV1<AgentEvent>),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Compat {
+            Versioned(Wire),
+            Unversioned(AgentEventV1<AgentEvent>),
+        }
+
+        Ok(match Compat::deserialize(deserializer)? {
+            Compat::Versioned(Wire::V1(AgentEventV1(event)))
+            | Compat::Unversioned(AgentEventV1(event)) => event,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::citation::{Citation, CitationSpan};
+    use crate::core::creativity::CreativityProfile;
+    use crate::core::finish::ResponseWarning;
+    use serde_json::json;
+
+    #[test]
+    fn test_wire_versions_round_trip() {
+        let response = AgentResponse {
+            id: "r1".to_string(),
+            agent_id: "a1".to_string(),
+            content: "你好".to_string(),
+            timestamp: "2024-01-01T00:00:00Z".parse().unwrap(),
+            model: "gpt-4o-mini".to_string(),
+            usage: None,
+            tool_calls: None,
+            citations: vec![Citation {
+                tool: "web_search".to_string(),
+                call_id: "c1".to_string(),
+                span: Some(CitationSpan { start: 2, end: 8 }),
+                excerpt: "搜索结果".to_string(),
+            }],
+            compression: None,
+            creativity: Some(CreativityProfile::Creative),
+            fingerprint: None,
+            finish_reason: Some(FinishReason::Length),
+            warnings: vec![ResponseWarning::HistoryTruncated { dropped: 2 }],
+            variant: None,
+        };
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value["v"], json!(AGENT_RESPONSE_VERSION));
+        assert_eq!(value["finish_reason"], json!("length"));
+        let decoded: AgentResponse = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.finish_reason, Some(FinishReason::Length));
+        assert_eq!(decoded.warnings, response.warnings);
+        assert_eq!(decoded.citations, response.citations);
+        assert_eq!(decoded.creativity, Some(CreativityProfile::Creative));
+
+        // 第 1 版与没有版本号的旧数据都迁移为当前版本
+        let v1 = json!({
+            "id": "r0",
+            "agent_id": "a1",
+            "content": "旧响应",
+            "timestamp": "2023-06-01T00:00:00Z",
+            "model": "gpt-3.5-turbo",
+            "usage": null,
+            "tool_calls": null,
+            "finish_reason": "stop"
+        });
+        let unversioned: AgentResponse = serde_json::from_value(v1.clone()).unwrap();
+        assert_eq!(unversioned.finish_reason, Some(FinishReason::Stop));
+        assert!(unversioned.warnings.is_empty());
+        assert!(unversioned.citations.is_empty());
+        let mut tagged = v1;
+        tagged["v"] = json!("1");
+        tagged["finish_reason"] = json!("content_filter");
+        let migrated: AgentResponse = serde_json::from_value(tagged).unwrap();
+        assert_eq!(migrated.finish_reason, Some(FinishReason::ContentFilter));
+        assert_eq!(migrated.content, "旧响应");
+
+        let event = AgentEvent::Reaped {
+            agent_id: "a1".to_string(),
+            idle_seconds: 60,
+            message_count: 3,
+            history_path: None,
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["v"], json!(AGENT_EVENT_VERSION));
+        assert_eq!(value["type"], json!("reaped"));
+        serde_json::from_value::<AgentEvent>(value).unwrap();
+        let mut unversioned = serde_json::to_value(&event).unwrap();
+        unversioned.as_object_mut().unwrap().remove("v");
+        let AgentEvent::Reaped { message_count, .. } = serde_json::from_value(unversioned).unwrap();
+        assert_eq!(message_count, 3);
+    }
+}
@@ -46,6 +46,10 @@ pub enum AgentError {
     #[error("请求过于频繁，请稍后再试")]
     RateLimit,
 
+    /// 模型调用超时，附带超时时间（毫秒）
+    #[error("模型调用超时（{0} 毫秒）")]
+    Timeout(u64),
+
     /// 令牌不足错误
     #[error("令牌不足")]
     InsufficientTokens,
@@ -95,7 +99,10 @@ impl AgentError {
     pub fn is_retryable(&self) -> bool {
         matches!(
             self,
-            AgentError::Network(_) | AgentError::RateLimit | AgentError::Other(_)
+            AgentError::Network(_)
+                | AgentError::RateLimit
+                | AgentError::Timeout(_)
+                | AgentError::Other(_)
         )
     }
 
@@ -112,6 +119,7 @@ impl AgentError {
             AgentError::Database(_) => "DATABASE_ERROR",
             AgentError::Permission(_) => "PERMISSION_ERROR",
             AgentError::RateLimit => "RATE_LIMIT",
+            AgentError::Timeout(_) => "TIMEOUT",
             AgentError::InsufficientTokens => "INSUFFICIENT_TOKENS",
             AgentError::Other(_) => "OTHER_ERROR",
         }
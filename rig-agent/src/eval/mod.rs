@@ -0,0 +1,396 @@
+//! 评测 - 用预先定义的测试集检查 Agent 的回复
+//!
+//! 测试集由若干提示词组成，每条提示词附带期望：正则匹配、JSON Schema 校验，
+//! 或交给评审 Agent 按评分细则打分。运行结果汇总为通过/失败报告，
+//! 可通过命令行工具或 HTTP 接口触发。
+
+pub mod schema;
+
+use crate::error::{AgentError, AgentResult};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 评审打分的满分
+pub const RUBRIC_MAX_SCORE: f64 = 10.0;
+
+fn default_rubric_threshold() -> f64 {
+    7.0
+}
+
+/// 测试集
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalSuite {
+    /// 名称
+    pub name: String,
+    /// 描述
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 默认被测 Agent，运行时未指定 Agent 时使用
+    #[serde(default)]
+    pub agents: Vec<String>,
+    /// 默认评审 Agent
+    #[serde(default)]
+    pub judge_agent: Option<String>,
+    /// 测试用例
+    pub cases: Vec<EvalCase>,
+}
+
+impl EvalSuite {
+    /// 从 JSON 文件加载测试集
+    pub fn load(path: impl AsRef<Path>) -> AgentResult<Self> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let suite: Self = serde_json::from_str(&content)?;
+        suite.validate()?;
+        Ok(suite)
+    }
+
+    /// 检查测试集是否有效（正则可编译、评分细则有评审 Agent）
+    pub fn validate(&self) -> AgentResult<()> {
+        if self.cases.is_empty() {
+            return Err(AgentError::config(format!("测试集 {} 没有用例", self.name)));
+        }
+        for case in &self.cases {
+            for expectation in &case.expect {
+                match expectation {
+                    Expectation::Regex { pattern, .. } => {
+                        Regex::new(pattern).map_err(|e| {
+                            AgentError::config(format!("用例 {} 的正则无效: {}", case.id, e))
+                        })?;
+                    }
+                    Expectation::Rubric { judge_agent, .. } => {
+                        if judge_agent.is_none() && self.judge_agent.is_none() {
+                            return Err(AgentError::config(format!(
+                                "用例 {} 使用评分细则，但没有指定评审 Agent",
+                                case.id
+                            )));
+                        }
+                    }
+                    Expectation::JsonSchema { .. } => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 测试用例
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCase {
+    /// 用例 ID
+    pub id: String,
+    /// 发送给 Agent 的提示词
+    pub prompt: String,
+    /// 对回复的期望，全部满足才算通过
+    #[serde(default)]
+    pub expect: Vec<Expectation>,
+}
+
+/// 对回复的期望
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Expectation {
+    /// 回复匹配正则表达式
+    Regex {
+        /// 正则表达式
+        pattern: String,
+        /// 取反：回复不应匹配
+        #[serde(default)]
+        negate: bool,
+    },
+    /// 回复是符合 JSON Schema 的 JSON（允许包在 ``` 代码块中）
+    JsonSchema {
+        /// JSON Schema
+        schema: serde_json::Value,
+    },
+    /// 由评审 Agent 按评分细则打分（0 - 10）
+    Rubric {
+        /// 评分细则
+        rubric: String,
+        /// 及格分
+        #[serde(default = "default_rubric_threshold")]
+        threshold: f64,
+        /// 评审 Agent，未指定时使用测试集的默认评审 Agent
+        #[serde(default)]
+        judge_agent: Option<String>,
+    },
+}
+
+impl Expectation {
+    /// 简短描述，用于报告
+    pub fn label(&self) -> String {
+        match self {
+            Self::Regex { pattern, negate } => {
+                if *negate {
+                    format!("不匹配 /{}/", pattern)
+                } else {
+                    format!("匹配 /{}/", pattern)
+                }
+            }
+            Self::JsonSchema { .. } => "符合 JSON Schema".to_string(),
+            Self::Rubric { threshold, .. } => {
+                format!("评分不低于 {}/{}", threshold, RUBRIC_MAX_SCORE)
+            }
+        }
+    }
+}
+
+/// 单项期望的检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    /// 期望描述
+    pub expectation: String,
+    /// 是否通过
+    pub passed: bool,
+    /// 评审得分（仅评分细则）
+    pub score: Option<f64>,
+    /// 说明（失败原因或评审理由）
+    pub detail: Option<String>,
+}
+
+impl CheckResult {
+    pub(crate) fn new(expectation: &Expectation, passed: bool, detail: Option<String>) -> Self {
+        Self {
+            expectation: expectation.label(),
+            passed,
+            score: None,
+            detail,
+        }
+    }
+}
+
+/// 单个用例在单个 Agent 上的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseResult {
+    /// 用例 ID
+    pub case_id: String,
+    /// 被测 Agent
+    pub agent_id: String,
+    /// 回复，调用失败时为空
+    pub response: Option<String>,
+    /// 调用失败的错误信息
+    pub error: Option<String>,
+    /// 各项期望的检查结果
+    pub checks: Vec<CheckResult>,
+    /// 是否通过
+    pub passed: bool,
+    /// 回复耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// 评测报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// 测试集名称
+    pub suite: String,
+    /// 被测 Agent
+    pub agents: Vec<String>,
+    /// 各用例结果
+    pub results: Vec<CaseResult>,
+    /// 通过数
+    pub passed: usize,
+    /// 失败数
+    pub failed: usize,
+    /// 开始时间
+    pub started_at: DateTime<Utc>,
+    /// 完成时间
+    pub completed_at: DateTime<Utc>,
+}
+
+impl EvalReport {
+    /// 汇总各用例结果
+    pub(crate) fn new(
+        suite: &EvalSuite,
+        agents: Vec<String>,
+        results: Vec<CaseResult>,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        let passed = results.iter().filter(|result| result.passed).count();
+        Self {
+            suite: suite.name.clone(),
+            agents,
+            failed: results.len() - passed,
+            passed,
+            results,
+            started_at,
+            completed_at: Utc::now(),
+        }
+    }
+
+    /// 是否全部通过
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+
+    /// 通过率（0.0 - 1.0）
+    pub fn pass_rate(&self) -> f64 {
+        if self.results.is_empty() {
+            1.0
+        } else {
+            self.passed as f64 / self.results.len() as f64
+        }
+    }
+
+    /// 纯文本摘要，每个用例一行，失败项附原因
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!(
+            "测试集 {}：通过 {}，失败 {}（{:.0}%）",
+            self.suite,
+            self.passed,
+            self.failed,
+            self.pass_rate() * 100.0
+        )];
+        for result in &self.results {
+            let status = if result.passed { "PASS" } else { "FAIL" };
+            lines.push(format!(
+                "[{}] {} @ {} ({} ms)",
+                status, result.case_id, result.agent_id, result.duration_ms
+            ));
+            if let Some(error) = &result.error {
+                lines.push(format!("    错误: {}", error));
+            }
+            for check in result.checks.iter().filter(|check| !check.passed) {
+                lines.push(format!(
+                    "    未满足: {}{}",
+                    check.expectation,
+                    check
+                        .detail
+                        .as_deref()
+                        .map(|detail| format!(" - {}", detail))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// 检查不依赖模型的期望（正则、JSON Schema）；评分细则返回 `None`，由调用方交给评审 Agent
+pub fn check_local(expectation: &Expectation, response: &str) -> Option<CheckResult> {
+    match expectation {
+        Expectation::Regex { pattern, negate } => Some(match Regex::new(pattern) {
+            Ok(regex) => {
+                let matched = regex.is_match(response);
+                let passed = matched != *negate;
+                let detail = (!passed).then(|| {
+                    if matched {
+                        "回复匹配了不应出现的内容".to_string()
+                    } else {
+                        "回复未匹配".to_string()
+                    }
+                });
+                CheckResult::new(expectation, passed, detail)
+            }
+            Err(e) => CheckResult::new(expectation, false, Some(format!("正则无效: {}", e))),
+        }),
+        Expectation::JsonSchema { schema } => {
+            Some(match serde_json::from_str(extract_json(response)) {
+                Ok(value) => {
+                    let errors = schema::validate(schema, &value);
+                    let detail = (!errors.is_empty()).then(|| errors.join("; "));
+                    CheckResult::new(expectation, errors.is_empty(), detail)
+                }
+                Err(e) => CheckResult::new(
+                    expectation,
+                    false,
+                    Some(format!("回复不是有效的 JSON: {}", e)),
+                ),
+            })
+        }
+        Expectation::Rubric { .. } => None,
+    }
+}
+
+/// 取出回复中的 JSON：去掉 ``` 代码块包裹，否则截取第一个 `{`/`[` 到最后一个 `}`/`]`
+pub fn extract_json(response: &str) -> &str {
+    let trimmed = response.trim();
+    if let Some(rest) = trimmed.strip_prefix("```") {
+        let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or(rest);
+        return body.trim_end().trim_end_matches("```").trim();
+    }
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &trimmed[start..=end],
+        _ => trimmed,
+    }
+}
+
+/// 发送给评审 Agent 的提示词
+pub fn judge_prompt(prompt: &str, response: &str, rubric: &str) -> String {
+    format!(
+        "你是一名严格的评审，请根据评分细则为 AI 助手的回复打分。\n\n\
+         【用户提示词】\n{}\n\n【助手回复】\n{}\n\n【评分细则】\n{}\n\n\
+         请只输出 JSON，格式为 {{\"score\": 0 到 {} 之间的数字, \"reason\": \"简短理由\"}}。",
+        prompt, response, rubric, RUBRIC_MAX_SCORE
+    )
+}
+
+/// 解析评审回复，返回得分（截断到 0 - 10）与理由
+pub fn parse_judgement(reply: &str) -> AgentResult<(f64, Option<String>)> {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(extract_json(reply))
+        && let Some(score) = value["score"].as_f64()
+    {
+        let reason = value["reason"].as_str().map(str::to_string);
+        return Ok((score.clamp(0.0, RUBRIC_MAX_SCORE), reason));
+    }
+
+    // 评审没有按格式输出时，退而取回复中的第一个数字
+    let number = Regex::new(r"\d+(?:\.\d+)?")
+        .expect("数字正则有效")
+        .find(reply)
+        .and_then(|m| m.as_str().parse::<f64>().ok())
+        .ok_or_else(|| AgentError::other(format!("无法解析评审得分: {}", reply)))?;
+    Ok((
+        number.clamp(0.0, RUBRIC_MAX_SCORE),
+        Some(reply.trim().to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_suite_and_local_checks() {
+        let suite: EvalSuite = serde_json::from_value(json!({
+            "name": "基础",
+            "judge_agent": "judge",
+            "cases": [{
+                "id": "weather",
+                "prompt": "用 JSON 返回北京的天气",
+                "expect": [
+                    { "type": "regex", "pattern": "北京" },
+                    { "type": "regex", "pattern": "(?i)error", "negate": true },
+                    { "type": "json_schema", "schema": {
+                        "type": "object", "required": ["city"]
+                    }},
+                    { "type": "rubric", "rubric": "回复应包含温度" }
+                ]
+            }]
+        }))
+        .unwrap();
+        suite.validate().unwrap();
+
+        let expect = &suite.cases[0].expect;
+        let response = "```json\n{\"city\": \"北京\", \"temperature\": 20}\n```";
+        assert!(check_local(&expect[0], response).unwrap().passed);
+        assert!(check_local(&expect[1], response).unwrap().passed);
+        assert!(check_local(&expect[2], response).unwrap().passed);
+        assert!(check_local(&expect[3], response).is_none());
+        assert!(!check_local(&expect[2], "天气晴").unwrap().passed);
+
+        assert_eq!(
+            parse_judgement(r#"{"score": 8, "reason": "包含温度"}"#).unwrap(),
+            (8.0, Some("包含温度".to_string()))
+        );
+        assert_eq!(parse_judgement("得分：12").unwrap().0, RUBRIC_MAX_SCORE);
+        assert!(parse_judgement("无法评分").is_err());
+
+        let mut without_judge = suite.clone();
+        without_judge.judge_agent = None;
+        assert!(without_judge.validate().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! JSON Schema 校验（常用子集）
+//!
+//! 支持 `type`、`enum`、`const`、`required`、`properties`、`additionalProperties`、
+//! `items`、`minItems`/`maxItems`、`minLength`/`maxLength`、`minimum`/`maximum`，
+//! 足以检查模型输出的结构，其余关键字会被忽略。
+
+use serde_json::Value;
+
+/// 校验 JSON 值，返回所有不符合之处（为空表示通过）
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "$", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` / 空 schema 接受任意值，`false` 拒绝任意值
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{}: 不允许出现", path));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(list) => list.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+            errors.push(format!(
+                "{}: 类型应为 {}，实际为 {}",
+                path,
+                types.join(" | "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum")
+        && !options.contains(value)
+    {
+        errors.push(format!("{}: 取值不在枚举范围内", path));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push(format!("{}: 取值应为 {}", path, expected));
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        errors.push(format!("{}: 缺少必需字段 {}", path, key));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, item) in object {
+                let item_path = format!("{}.{}", path, key);
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(item_schema) => validate_at(item_schema, item, &item_path, errors),
+                    None => {
+                        if let Some(additional) = schema.get("additionalProperties") {
+                            validate_at(additional, item, &item_path, errors);
+                        }
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bound(schema, "minItems", items.len(), path, "元素个数", errors);
+            check_bound(schema, "maxItems", items.len(), path, "元素个数", errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}[{}]", path, index), errors);
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count();
+            check_bound(schema, "minLength", length, path, "长度", errors);
+            check_bound(schema, "maxLength", length, path, "长度", errors);
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+                && number < minimum
+            {
+                errors.push(format!("{}: 不能小于 {}", path, minimum));
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+                && number > maximum
+            {
+                errors.push(format!("{}: 不能大于 {}", path, maximum));
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+fn check_bound(
+    schema: &serde_json::Map<String, Value>,
+    keyword: &str,
+    actual: usize,
+    path: &str,
+    label: &str,
+    errors: &mut Vec<String>,
+) {
+    let Some(bound) = schema.get(keyword).and_then(Value::as_u64) else {
+        return;
+    };
+    let bound = bound as usize;
+    let ok = if keyword.starts_with("min") {
+        actual >= bound
+    } else {
+        actual <= bound
+    };
+    if !ok {
+        errors.push(format!(
+            "{}: {} {} 不满足 {} {}",
+            path, label, actual, keyword, bound
+        ));
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    match expected {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_subset() {
+        let schema = json!({
+            "type": "object",
+            "required": ["city", "temperature"],
+            "properties": {
+                "city": { "type": "string", "minLength": 1 },
+                "temperature": { "type": "number", "minimum": -90, "maximum": 60 },
+                "tags": { "type": "array", "items": { "enum": ["sunny", "rain"] } }
+            },
+            "additionalProperties": false
+        });
+
+        let valid = json!({ "city": "北京", "temperature": 21.5, "tags": ["sunny"] });
+        assert!(validate(&schema, &valid).is_empty());
+
+        let invalid = json!({ "city": "", "temperature": 99, "tags": ["snow"], "extra": 1 });
+        let errors = validate(&schema, &invalid);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+
+        let errors = validate(&schema, &json!({ "city": 1 }));
+        assert!(errors.iter().any(|e| e.contains("temperature")));
+        assert!(errors.iter().any(|e| e.contains("$.city")));
+    }
+}
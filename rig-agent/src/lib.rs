@@ -5,22 +5,48 @@
 pub mod adapters;
 pub mod core;
 pub mod error;
+pub mod eval;
+pub mod storage;
 pub mod tools;
 
 // 重新导出核心类型和功能
 pub use core::{
-    AgentConfig, AgentManager, AgentMessage, AgentResponse, AgentRole, ClientConfig, 
-    ConversationHistory, MessageType, ToolCall, ToolResult,
+    AbRoute, AgentConfig, AgentEvent, AgentGcConfig, AgentManager, AgentMessage, AgentPackage, AgentResponse, AgentRole, AgentSortKey, AgentSummary, ApiKeyStats,
+    CacheStats, ChatOptions, ChatSession, Citation, CitationSpan, ClientConfig, CompressionConfig, CompressionReason, CompressionReport, CreativityProfile, ConversationHistory, DEFAULT_SEED, DocumentSummary, FeedbackRating, FinishReason,
+    FeedbackRecord, KeyPoolConfig, KeySelection, KnowledgeFile, ListPage, MessageMetadata,
+    MessageType, ModelVariant, PACKAGE_EXTENSION, PageRequest, ProviderQueueMetrics, QueueMetrics, RACE_WINNER_KEY, RaceConfig, RaceStats, Register, ReplyLanguage, RequestContext, RequestFingerprint, RequestOrigin, RequestPriority, ResponseCacheConfig, ResponseFeedback, ResponseWarning, SamplingParams, SchedulerConfig, SectionSummary, SortOrder, StreamingMode, SummarizeOptions, TranslateRequest, Translation,
+    ToolCall, ToolResult, ToolSelection, VariantMetrics, detect_language, feedback_to_jsonl, render_template,
 };
 
 // 重新导出错误类型
 pub use error::{AgentError, AgentResult, ErrorResponse};
 
+// 重新导出评测
+pub use eval::{CaseResult, CheckResult, EvalCase, EvalReport, EvalSuite, Expectation};
+
+// 重新导出存储
+pub use storage::{
+    Cipher, DatasetExport, DatasetExporter, DatasetFilter, DatasetFormat, KeySource, MasterKey,
+    Page, PatternScrubber, PurgeReport, RetentionPolicy, Scrubber, SecretStore, SessionQuery,
+    SessionStore, SessionVisibility,
+};
+
 // 重新导出工具
-pub use tools::{BuiltinTools, CustomTool, ToolDefinition, ToolManager};
+pub use tools::{
+    BuiltinTools, CustomTool, ManagedTool, McpServerConfig, McpServerHealth, McpServerState,
+    OpenMeteoProvider, PermissionDecision,
+    PermissionGrant, PermissionStore, SearchEngine, SearchResult, SessionMemory,
+    TemperatureUnit, ToolDefinition, ToolEvent, ToolManager, ToolStateStore, ToolStateValue,
+    ToolStats, ToolTelemetryConfig, ToolsDirectoryConfig, WeatherConfig, WeatherProvider,
+    WeatherReport, WebSearchConfig, WebhookTool, WebhookToolSpec, spawn_tools_watcher,
+    tool_state,
+};
 
 // 重新导出适配器
-pub use adapters::{AgentAdapter, StandaloneAgentAdapter};
+pub use adapters::{
+    AgentAdapter, EmailAgent, EmailConfig, EmailDisposition, InboundEmail, MailSender, MailSource,
+    OutgoingEmail, Repl, ReplCommand, StandaloneAgentAdapter,
+};
 
 #[cfg(feature = "tauri-support")]
 pub use adapters::TauriAgentAdapter;
@@ -57,21 +83,66 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn creativity(mut self, creativity: CreativityProfile) -> Self {
+        self.config.creativity = Some(creativity);
+        self
+    }
+
     pub fn max_tokens(mut self, max_tokens: u32) -> Self {
         self.config.max_tokens = Some(max_tokens);
         self
     }
 
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.config.deterministic = deterministic;
+        self
+    }
+
     pub fn enable_tools(mut self, enable: bool) -> Self {
         self.config.enable_tools = enable;
         self
     }
 
+    pub fn tool_selection(mut self, selection: ToolSelection) -> Self {
+        self.config.tool_selection = selection;
+        self
+    }
+
     pub fn history_limit(mut self, limit: usize) -> Self {
         self.config.history_limit = Some(limit);
         self
     }
 
+    pub fn reply_language(mut self, reply_language: ReplyLanguage) -> Self {
+        self.config.reply_language = reply_language;
+        self
+    }
+
+    pub fn ab_route(mut self, ab_route: AbRoute) -> Self {
+        self.config.ab_route = Some(ab_route);
+        self
+    }
+
+    pub fn race(mut self, race: RaceConfig) -> Self {
+        self.config.race = Some(race);
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionConfig) -> Self {
+        self.config.compression = Some(compression);
+        self
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.config.timeout_ms = Some(timeout_ms);
+        self
+    }
+
     pub fn build(self) -> AgentConfig {
         self.config
     }
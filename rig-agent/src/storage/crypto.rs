@@ -0,0 +1,290 @@
+//! 静态数据加密
+//!
+//! 对话存储、密钥存储与导出的 Agent 包写入磁盘前用 XChaCha20-Poly1305 加密。主密钥有三种来源：
+//! 直接提供的 32 字节密钥、由口令经 Argon2 派生（盐与数据保存在一起）、
+//! 保存在系统钥匙串中（需要启用 `keychain` 特性，首次使用时生成）。
+//!
+//! 加密后的文本字段以 [`SEALED_TEXT_PREFIX`] 开头，后接 base64url 编码的随机数与密文。
+//! 不带前缀的值按明文读取，因此为已有数据启用加密后旧数据仍然可读，之后写入的数据才会加密。
+
+use crate::error::{AgentError, AgentResult};
+use argon2::Argon2;
+use chacha20poly1305::{
+    AeadCore, Key, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, OsRng, rand_core::RngCore},
+};
+use std::fmt;
+use std::sync::Arc;
+
+/// 加密文本的前缀
+pub const SEALED_TEXT_PREFIX: &str = "enc1:";
+/// 口令加密数据块的格式标识
+const ENVELOPE_MAGIC: &[u8; 4] = b"RAE1";
+/// 密钥派生盐长度
+pub const SALT_LEN: usize = 16;
+/// XChaCha20 随机数长度
+const NONCE_LEN: usize = 24;
+/// 主密钥长度
+const KEY_LEN: usize = 32;
+
+/// 主密钥
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey([u8; KEY_LEN]);
+
+impl MasterKey {
+    /// 随机生成
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// 使用已有的密钥
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// 由口令和盐派生
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> AgentResult<Self> {
+        if passphrase.is_empty() {
+            return Err(AgentError::config("口令不能为空"));
+        }
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| AgentError::config(format!("派生密钥失败: {}", e)))?;
+        Ok(Self(key))
+    }
+
+    /// 从 base64url 文本解码
+    pub fn from_encoded(encoded: &str) -> AgentResult<Self> {
+        let bytes = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.trim().as_bytes())
+            .map_err(|e| AgentError::config(format!("解码密钥失败: {}", e)))?;
+        let key = bytes
+            .try_into()
+            .map_err(|_| AgentError::config(format!("密钥长度必须为 {} 字节", KEY_LEN)))?;
+        Ok(Self(key))
+    }
+
+    /// 编码为 base64url 文本
+    pub fn encode(&self) -> String {
+        data_encoding::BASE64URL_NOPAD.encode(&self.0)
+    }
+
+    /// 从系统钥匙串读取，不存在时生成并保存
+    #[cfg(feature = "keychain")]
+    pub fn from_keychain(service: &str, account: &str) -> AgentResult<Self> {
+        let entry = keyring::Entry::new(service, account)
+            .map_err(|e| AgentError::config(format!("打开系统钥匙串失败: {}", e)))?;
+        match entry.get_password() {
+            Ok(encoded) => Self::from_encoded(&encoded),
+            Err(keyring::Error::NoEntry) => {
+                let key = Self::generate();
+                entry.set_password(&key.encode()).map_err(|e| {
+                    AgentError::config(format!("保存主密钥到系统钥匙串失败: {}", e))
+                })?;
+                tracing::info!("已在系统钥匙串中生成主密钥: {}/{}", service, account);
+                Ok(key)
+            }
+            Err(e) => Err(AgentError::config(format!("读取系统钥匙串失败: {}", e))),
+        }
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+/// 主密钥来源
+#[derive(Clone)]
+pub enum KeySource {
+    /// 直接提供的密钥
+    Key(MasterKey),
+    /// 由口令派生，盐与加密的数据保存在一起
+    Passphrase(String),
+    /// 系统钥匙串中的密钥
+    #[cfg(feature = "keychain")]
+    Keychain {
+        /// 服务名称
+        service: String,
+        /// 账户名称
+        account: String,
+    },
+}
+
+impl KeySource {
+    /// 由口令派生
+    pub fn passphrase<S: Into<String>>(passphrase: S) -> Self {
+        Self::Passphrase(passphrase.into())
+    }
+
+    /// 取得主密钥，`salt` 只用于口令派生
+    pub fn master_key(&self, salt: &[u8]) -> AgentResult<MasterKey> {
+        match self {
+            Self::Key(key) => Ok(key.clone()),
+            Self::Passphrase(passphrase) => MasterKey::from_passphrase(passphrase, salt),
+            #[cfg(feature = "keychain")]
+            Self::Keychain { service, account } => MasterKey::from_keychain(service, account),
+        }
+    }
+
+    /// 取得加密器
+    pub fn cipher(&self, salt: &[u8]) -> AgentResult<Cipher> {
+        Ok(Cipher::new(&self.master_key(salt)?))
+    }
+}
+
+impl fmt::Debug for KeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(_) => f.write_str("KeySource::Key(..)"),
+            Self::Passphrase(_) => f.write_str("KeySource::Passphrase(..)"),
+            #[cfg(feature = "keychain")]
+            Self::Keychain { service, account } => f
+                .debug_struct("KeySource::Keychain")
+                .field("service", service)
+                .field("account", account)
+                .finish(),
+        }
+    }
+}
+
+/// 加密器
+#[derive(Clone)]
+pub struct Cipher {
+    aead: Arc<XChaCha20Poly1305>,
+}
+
+impl Cipher {
+    /// 使用主密钥创建
+    pub fn new(key: &MasterKey) -> Self {
+        Self {
+            aead: Arc::new(XChaCha20Poly1305::new(Key::from_slice(&key.0))),
+        }
+    }
+
+    /// 加密，结果为随机数与密文
+    pub fn seal(&self, plaintext: &[u8]) -> AgentResult<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| AgentError::other(format!("加密失败: {}", e)))?;
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// 解密 [`Cipher::seal`] 的结果
+    pub fn open(&self, sealed: &[u8]) -> AgentResult<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(AgentError::permission("加密数据已损坏"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.aead
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| AgentError::permission("密钥错误或加密数据已损坏"))
+    }
+
+    /// 加密文本
+    pub fn seal_text(&self, plaintext: &str) -> AgentResult<String> {
+        let sealed = self.seal(plaintext.as_bytes())?;
+        Ok(format!(
+            "{}{}",
+            SEALED_TEXT_PREFIX,
+            data_encoding::BASE64URL_NOPAD.encode(&sealed)
+        ))
+    }
+
+    /// 解密文本，不带加密前缀的值按明文原样返回
+    pub fn open_text(&self, value: &str) -> AgentResult<String> {
+        let Some(encoded) = value.strip_prefix(SEALED_TEXT_PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let sealed = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.as_bytes())
+            .map_err(|_| AgentError::permission("加密数据已损坏"))?;
+        String::from_utf8(self.open(&sealed)?).map_err(|_| AgentError::permission("加密数据已损坏"))
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cipher(..)")
+    }
+}
+
+/// 随机生成密钥派生盐
+pub fn random_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+/// 用口令加密为自描述的数据块：格式标识、盐、随机数与密文
+pub fn seal_with_passphrase(plaintext: &[u8], passphrase: &str) -> AgentResult<Vec<u8>> {
+    let salt = random_salt();
+    let sealed = Cipher::new(&MasterKey::from_passphrase(passphrase, &salt)?).seal(plaintext)?;
+    let mut envelope = Vec::with_capacity(ENVELOPE_MAGIC.len() + SALT_LEN + sealed.len());
+    envelope.extend_from_slice(ENVELOPE_MAGIC);
+    envelope.extend_from_slice(&salt);
+    envelope.extend_from_slice(&sealed);
+    Ok(envelope)
+}
+
+/// 解密 [`seal_with_passphrase`] 的结果
+pub fn open_with_passphrase(envelope: &[u8], passphrase: &str) -> AgentResult<Vec<u8>> {
+    if !is_passphrase_sealed(envelope) || envelope.len() < ENVELOPE_MAGIC.len() + SALT_LEN {
+        return Err(AgentError::config("不是口令加密的数据"));
+    }
+    let (salt, sealed) = envelope[ENVELOPE_MAGIC.len()..].split_at(SALT_LEN);
+    Cipher::new(&MasterKey::from_passphrase(passphrase, salt)?).open(sealed)
+}
+
+/// 是否为 [`seal_with_passphrase`] 加密的数据块
+pub fn is_passphrase_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENVELOPE_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher_round_trip() {
+        let cipher = Cipher::new(&MasterKey::generate());
+        let sealed = cipher.seal_text("你好，世界").unwrap();
+        assert!(sealed.starts_with(SEALED_TEXT_PREFIX));
+        assert!(!sealed.contains("你好"));
+        assert_eq!(cipher.open_text(&sealed).unwrap(), "你好，世界");
+        // 明文原样读取
+        assert_eq!(cipher.open_text("旧数据").unwrap(), "旧数据");
+        // 密钥错误
+        let other = Cipher::new(&MasterKey::generate());
+        assert!(matches!(
+            other.open_text(&sealed),
+            Err(AgentError::Permission(_))
+        ));
+
+        let key = MasterKey::generate();
+        assert_eq!(MasterKey::from_encoded(&key.encode()).unwrap(), key);
+        let salt = random_salt();
+        assert_eq!(
+            KeySource::passphrase("口令").master_key(&salt).unwrap(),
+            MasterKey::from_passphrase("口令", &salt).unwrap()
+        );
+        assert!(format!("{:?}", KeySource::passphrase("口令")).ends_with("(..)"));
+
+        let envelope = seal_with_passphrase(b"package", "correct horse").unwrap();
+        assert!(is_passphrase_sealed(&envelope));
+        assert_eq!(
+            open_with_passphrase(&envelope, "correct horse").unwrap(),
+            b"package"
+        );
+        assert!(open_with_passphrase(&envelope, "battery staple").is_err());
+    }
+}
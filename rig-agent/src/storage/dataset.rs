@@ -0,0 +1,390 @@
+//! 微调数据集导出
+//!
+//! 从会话存储中按 Agent、标签或反馈得分筛选会话，转换为 JSONL 微调格式
+//! （OpenAI chat 格式或通用 messages 格式）。导出前可通过清洗钩子去除个人信息。
+
+use crate::core::feedback::ResponseFeedback;
+use crate::core::types::{AgentMessage, AgentRole, ChatSession};
+use crate::error::{AgentError, AgentResult};
+use crate::storage::session_store::{SessionQuery, SessionStore};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 每次从会话存储读取的会话数
+const EXPORT_PAGE_SIZE: usize = 100;
+
+/// 数据集格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetFormat {
+    /// OpenAI 微调使用的 chat 格式：每行 `{"messages": [...]}`
+    #[default]
+    OpenaiChat,
+    /// 通用格式：消息附带反馈，并保留会话 ID、Agent 与标签
+    Messages,
+}
+
+/// 会话筛选条件，多个条件同时生效
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatasetFilter {
+    /// 仅导出这些会话
+    pub session_ids: Vec<String>,
+    /// 仅导出关联该 Agent 的会话
+    pub agent_id: Option<String>,
+    /// 仅导出包含任一标签的会话
+    pub tags: Vec<String>,
+    /// 最低反馈得分（-1.0 - 1.0），设置后没有评价的会话不会导出
+    pub min_feedback_score: Option<f64>,
+}
+
+impl DatasetFilter {
+    /// 会话本身的条件（不含反馈得分）是否满足
+    pub fn matches_session(&self, session: &ChatSession) -> bool {
+        (self.session_ids.is_empty() || self.session_ids.contains(&session.id))
+            && self
+                .agent_id
+                .as_ref()
+                .is_none_or(|agent_id| session.agent_id.as_ref() == Some(agent_id))
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| session.tags.contains(tag)))
+    }
+}
+
+/// 会话的反馈得分：(点赞数 - 点踩数) / 评价数，没有评价时为空
+pub fn feedback_score(messages: &[AgentMessage]) -> Option<f64> {
+    let (positive, negative) = messages
+        .iter()
+        .filter_map(|message| ResponseFeedback::from_metadata(&message.metadata)?.rating)
+        .fold((0usize, 0usize), |(positive, negative), rating| {
+            if rating.is_positive() {
+                (positive + 1, negative)
+            } else {
+                (positive, negative + 1)
+            }
+        });
+    let rated = positive + negative;
+    (rated > 0).then(|| (positive as f64 - negative as f64) / rated as f64)
+}
+
+/// 个人信息清洗钩子
+pub trait Scrubber: Send + Sync {
+    /// 清洗文本
+    fn scrub(&self, text: &str) -> String;
+}
+
+impl<F> Scrubber for F
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn scrub(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
+/// 按正则把匹配内容替换为占位符的清洗器
+pub struct PatternScrubber {
+    patterns: Vec<(Regex, String)>,
+}
+
+impl PatternScrubber {
+    /// 创建空的清洗器
+    pub fn new() -> Self {
+        Self {
+            patterns: Vec::new(),
+        }
+    }
+
+    /// 添加规则，匹配内容替换为 `replacement`
+    pub fn with_pattern<S: Into<String>>(
+        mut self,
+        pattern: &str,
+        replacement: S,
+    ) -> AgentResult<Self> {
+        let regex = Regex::new(pattern)
+            .map_err(|e| AgentError::config(format!("清洗规则无效 {}: {}", pattern, e)))?;
+        self.patterns.push((regex, replacement.into()));
+        Ok(self)
+    }
+
+    /// 常见个人信息：邮箱、身份证号、银行卡号、手机号/电话号码、IPv4 地址
+    pub fn pii() -> Self {
+        // 顺序有意义：较长的数字串先于电话号码匹配
+        const RULES: &[(&str, &str)] = &[
+            (r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "[EMAIL]"),
+            (r"\b\d{17}[\dXx]\b", "[ID_NUMBER]"),
+            (r"\b(?:\d[ -]?){13,19}\b", "[CARD_NUMBER]"),
+            (
+                r"(?:\+?\d{1,3}[ -]?)?(?:\(\d{2,4}\)[ -]?)?\d{3,4}[ -]?\d{4}\b",
+                "[PHONE]",
+            ),
+            (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[IP_ADDRESS]"),
+        ];
+        let patterns = RULES
+            .iter()
+            .map(|(pattern, replacement)| {
+                (
+                    Regex::new(pattern).expect("内置清洗规则有效"),
+                    replacement.to_string(),
+                )
+            })
+            .collect();
+        Self { patterns }
+    }
+}
+
+impl Default for PatternScrubber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scrubber for PatternScrubber {
+    fn scrub(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, (regex, replacement)| {
+                regex.replace_all(&text, replacement.as_str()).into_owned()
+            })
+    }
+}
+
+/// 导出结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetExport {
+    /// JSONL 内容
+    pub jsonl: String,
+    /// 导出的会话数
+    pub conversations: usize,
+    /// 满足会话条件但因反馈得分或缺少完整问答而跳过的会话数
+    pub skipped: usize,
+}
+
+/// 微调数据集导出器
+#[derive(Default)]
+pub struct DatasetExporter {
+    filter: DatasetFilter,
+    format: DatasetFormat,
+    system_prompt: Option<String>,
+    scrubbers: Vec<Box<dyn Scrubber>>,
+}
+
+impl fmt::Debug for DatasetExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatasetExporter")
+            .field("filter", &self.filter)
+            .field("format", &self.format)
+            .field("system_prompt", &self.system_prompt)
+            .field("scrubbers", &self.scrubbers.len())
+            .finish()
+    }
+}
+
+impl DatasetExporter {
+    /// 创建导出器
+    pub fn new(format: DatasetFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// 设置筛选条件
+    pub fn with_filter(mut self, filter: DatasetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 设置写入每条样本开头的系统提示
+    pub fn with_system_prompt<S: Into<String>>(mut self, system_prompt: S) -> Self {
+        self.system_prompt = Some(system_prompt.into());
+        self
+    }
+
+    /// 添加清洗钩子，按添加顺序依次作用于每条消息
+    pub fn with_scrubber<S: Scrubber + 'static>(mut self, scrubber: S) -> Self {
+        self.scrubbers.push(Box::new(scrubber));
+        self
+    }
+
+    /// 筛选条件
+    pub fn filter(&self) -> &DatasetFilter {
+        &self.filter
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        self.scrubbers
+            .iter()
+            .fold(text.to_string(), |text, scrubber| scrubber.scrub(&text))
+    }
+
+    /// 把一个会话转换为一行 JSONL；不满足反馈得分或没有完整问答时返回 `None`
+    pub fn convert(&self, session: &ChatSession, messages: &[AgentMessage]) -> Option<String> {
+        let score = feedback_score(messages);
+        if let Some(min_score) = self.filter.min_feedback_score
+            && score.is_none_or(|score| score < min_score)
+        {
+            return None;
+        }
+
+        // 只保留用户与助手消息，并去掉末尾没有回复的用户消息
+        let mut turns: Vec<&AgentMessage> = messages
+            .iter()
+            .filter(|message| matches!(message.role, AgentRole::User | AgentRole::Assistant))
+            .collect();
+        while turns
+            .last()
+            .is_some_and(|message| message.role != AgentRole::Assistant)
+        {
+            turns.pop();
+        }
+        if turns.is_empty() {
+            return None;
+        }
+
+        let system = self
+            .system_prompt
+            .as_ref()
+            .map(|content| serde_json::json!({ "role": "system", "content": self.scrub(content) }));
+        let line = match self.format {
+            DatasetFormat::OpenaiChat => {
+                let messages: Vec<serde_json::Value> = system
+                    .into_iter()
+                    .chain(turns.iter().map(|message| {
+                        serde_json::json!({
+                            "role": role_name(&message.role),
+                            "content": self.scrub(&message.content),
+                        })
+                    }))
+                    .collect();
+                serde_json::json!({ "messages": messages })
+            }
+            DatasetFormat::Messages => {
+                let messages: Vec<serde_json::Value> = system
+                    .into_iter()
+                    .chain(turns.iter().map(|message| {
+                        let mut value = serde_json::json!({
+                            "id": message.id,
+                            "role": role_name(&message.role),
+                            "content": self.scrub(&message.content),
+                        });
+                        if let Some(mut feedback) =
+                            ResponseFeedback::from_metadata(&message.metadata)
+                        {
+                            feedback.comment = feedback.comment.map(|comment| self.scrub(&comment));
+                            value["feedback"] = serde_json::to_value(feedback).unwrap_or_default();
+                        }
+                        value
+                    }))
+                    .collect();
+                serde_json::json!({
+                    "session_id": session.id,
+                    "agent_id": session.agent_id,
+                    "model": session.model,
+                    "tags": session.tags,
+                    "feedback_score": score,
+                    "messages": messages,
+                })
+            }
+        };
+        Some(line.to_string())
+    }
+
+    /// 从会话存储导出（仅未删除的会话）
+    pub async fn export(&self, store: &SessionStore) -> AgentResult<DatasetExport> {
+        let mut export = DatasetExport::default();
+        let mut offset = 0;
+        loop {
+            let page = store
+                .list_sessions(SessionQuery::default().with_page(offset, EXPORT_PAGE_SIZE))
+                .await?;
+            for session in page.items.iter().filter(|s| self.filter.matches_session(s)) {
+                let messages = store
+                    .get_messages(&session.id, 0, session.message_count)
+                    .await?
+                    .items;
+                match self.convert(session, &messages) {
+                    Some(line) => {
+                        export.jsonl.push_str(&line);
+                        export.jsonl.push('\n');
+                        export.conversations += 1;
+                    }
+                    None => export.skipped += 1,
+                }
+            }
+            if !page.has_more() {
+                break;
+            }
+            offset += page.items.len();
+        }
+        Ok(export)
+    }
+}
+
+fn role_name(role: &AgentRole) -> &'static str {
+    match role {
+        AgentRole::System => "system",
+        AgentRole::User => "user",
+        AgentRole::Assistant => "assistant",
+        AgentRole::Tool => "tool",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::feedback::FeedbackRating;
+
+    #[tokio::test]
+    async fn test_export_dataset() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let mut session = ChatSession::new("客服".to_string(), "gpt-3.5-turbo".to_string());
+        session.agent_id = Some("support".to_string());
+        session.tags = vec!["curated".to_string()];
+        let session = store.create_session(session).await.unwrap();
+
+        let mut answer = AgentMessage::assistant("已发送到 alice@example.com".to_string());
+        answer.metadata = ResponseFeedback::rating(FeedbackRating::Positive).to_annotations();
+        store
+            .append_messages(
+                &session.id,
+                vec![
+                    AgentMessage::user("我的电话是 138 0013 8000，请发邮件".to_string()),
+                    answer,
+                    AgentMessage::user("谢谢".to_string()),
+                ],
+            )
+            .await
+            .unwrap();
+        store
+            .create_session(ChatSession::new("其他".to_string(), "gpt-4".to_string()))
+            .await
+            .unwrap();
+
+        let exporter = DatasetExporter::new(DatasetFormat::OpenaiChat)
+            .with_filter(DatasetFilter {
+                tags: vec!["curated".to_string()],
+                min_feedback_score: Some(0.5),
+                ..DatasetFilter::default()
+            })
+            .with_system_prompt("你是客服助手")
+            .with_scrubber(PatternScrubber::pii());
+        let export = exporter.export(&store).await.unwrap();
+        assert_eq!((export.conversations, export.skipped), (1, 0));
+
+        let line: serde_json::Value = serde_json::from_str(export.jsonl.trim()).unwrap();
+        let messages = line["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"], "我的电话是 [PHONE]，请发邮件");
+        assert_eq!(messages[2]["content"], "已发送到 [EMAIL]");
+
+        let strict = DatasetExporter::new(DatasetFormat::Messages).with_filter(DatasetFilter {
+            agent_id: Some("support".to_string()),
+            min_feedback_score: Some(1.5),
+            ..DatasetFilter::default()
+        });
+        let export = strict.export(&store).await.unwrap();
+        assert_eq!((export.conversations, export.skipped), (0, 1));
+    }
+}
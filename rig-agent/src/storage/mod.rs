@@ -0,0 +1,15 @@
+//! 持久化存储模块
+
+pub mod crypto;
+pub mod dataset;
+pub mod retention;
+pub mod secret_store;
+pub mod session_store;
+
+pub use crypto::{Cipher, KeySource, MasterKey};
+pub use dataset::{
+    DatasetExport, DatasetExporter, DatasetFilter, DatasetFormat, PatternScrubber, Scrubber,
+};
+pub use retention::{PurgeReport, RetentionPolicy};
+pub use secret_store::SecretStore;
+pub use session_store::{Page, SessionQuery, SessionStore, SessionVisibility};
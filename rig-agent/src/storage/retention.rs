@@ -0,0 +1,135 @@
+//! 数据保留策略
+//!
+//! 按天数自动删除过期的会话记录、审计日志与传输记录，并提供按调用方删除全部数据（被遗忘权）的统计结果。
+//! 各存储自行实现清理，这里只定义策略与结果。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 一天的秒数
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// 数据保留策略，未设置的天数表示永久保留
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// 会话记录（包括对话历史与聊天记录）保留天数，按最后更新时间计算
+    pub conversation_days: Option<u32>,
+    /// 审计日志保留天数
+    pub audit_days: Option<u32>,
+    /// 传输记录（上传完成的文件）保留天数
+    pub transfer_days: Option<u32>,
+}
+
+impl RetentionPolicy {
+    /// 设置会话记录保留天数
+    pub fn with_conversation_days(mut self, days: u32) -> Self {
+        self.conversation_days = Some(days);
+        self
+    }
+
+    /// 设置审计日志保留天数
+    pub fn with_audit_days(mut self, days: u32) -> Self {
+        self.audit_days = Some(days);
+        self
+    }
+
+    /// 设置传输记录保留天数
+    pub fn with_transfer_days(mut self, days: u32) -> Self {
+        self.transfer_days = Some(days);
+        self
+    }
+
+    /// 是否设置了任何保留期限
+    pub fn is_enabled(&self) -> bool {
+        self.conversation_days.is_some()
+            || self.audit_days.is_some()
+            || self.transfer_days.is_some()
+    }
+
+    /// 早于该时间的会话记录应被删除
+    pub fn conversation_cutoff(&self) -> Option<DateTime<Utc>> {
+        cutoff(self.conversation_days)
+    }
+
+    /// 早于该时间的审计日志应被删除
+    pub fn audit_cutoff(&self) -> Option<DateTime<Utc>> {
+        cutoff(self.audit_days)
+    }
+
+    /// 早于该时间的传输记录应被删除
+    pub fn transfer_cutoff(&self) -> Option<DateTime<Utc>> {
+        cutoff(self.transfer_days)
+    }
+
+    /// 传输记录的保留时长
+    pub fn transfer_ttl(&self) -> Option<Duration> {
+        self.transfer_days
+            .map(|days| Duration::from_secs(u64::from(days) * SECONDS_PER_DAY))
+    }
+}
+
+/// 距今 `days` 天的时间
+fn cutoff(days: Option<u32>) -> Option<DateTime<Utc>> {
+    days.map(|days| Utc::now() - chrono::Duration::days(i64::from(days)))
+}
+
+/// 一次清理删除的数据量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    /// 删除的会话数
+    pub sessions: usize,
+    /// 从内存中的对话历史与聊天记录删除的消息数
+    pub messages: usize,
+    /// 删除的审计日志与其他日志条数
+    pub log_entries: usize,
+    /// 删除的传输记录数
+    pub transfers: usize,
+}
+
+impl PurgeReport {
+    /// 累加另一次清理的结果
+    pub fn merge(&mut self, other: PurgeReport) {
+        self.sessions += other.sessions;
+        self.messages += other.messages;
+        self.log_entries += other.log_entries;
+        self.transfers += other.transfers;
+    }
+
+    /// 删除的数据总量
+    pub fn total(&self) -> usize {
+        self.sessions + self.messages + self.log_entries + self.transfers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_policy() {
+        let policy = RetentionPolicy::default();
+        assert!(!policy.is_enabled());
+        assert!(policy.conversation_cutoff().is_none());
+
+        let policy: RetentionPolicy =
+            serde_json::from_str(r#"{"conversation_days": 30, "transfer_days": 1}"#).unwrap();
+        assert!(policy.is_enabled());
+        let cutoff = policy.conversation_cutoff().unwrap();
+        assert!((Utc::now() - cutoff).num_days() == 30);
+        assert!(policy.audit_cutoff().is_none());
+        assert_eq!(policy.transfer_ttl(), Some(Duration::from_secs(86_400)));
+
+        let mut report = PurgeReport {
+            sessions: 2,
+            ..Default::default()
+        };
+        report.merge(PurgeReport {
+            messages: 3,
+            log_entries: 1,
+            ..Default::default()
+        });
+        assert_eq!(report.total(), 6);
+    }
+}
@@ -0,0 +1,198 @@
+//! 加密的密钥存储 - 保存 API 密钥、节点密钥等机密
+//!
+//! 每条机密单独加密后写入 JSON 文件，文件中还保存口令派生用的盐，以及用于在打开时校验主密钥的校验值。
+//! 密钥名称不加密，便于列出已保存的机密。
+
+use crate::error::{AgentError, AgentResult};
+use crate::storage::crypto::{Cipher, KeySource, SALT_LEN, random_salt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::info;
+
+/// 文件格式版本
+const SECRET_FILE_VERSION: u32 = 1;
+/// 校验值的明文
+const KEY_CHECK: &str = "rig-agent-secret-store";
+
+/// 密钥存储文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SecretFile {
+    version: u32,
+    /// 口令派生用的盐（base64url）
+    salt: String,
+    /// 加密的校验值
+    check: String,
+    /// 名称 -> 加密的机密
+    secrets: BTreeMap<String, String>,
+}
+
+/// 加密的密钥存储，设置了文件路径时每次修改后写回文件
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    path: Option<PathBuf>,
+    cipher: Cipher,
+    file: Arc<Mutex<SecretFile>>,
+}
+
+impl SecretStore {
+    /// 打开（或创建）密钥存储文件，主密钥错误时返回权限错误
+    pub fn open<P: AsRef<Path>>(path: P, key: &KeySource) -> AgentResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let store = if path.exists() {
+            let file: SecretFile = serde_json::from_slice(&fs::read(&path)?)?;
+            if file.version > SECRET_FILE_VERSION {
+                return Err(AgentError::config(format!(
+                    "不支持的密钥存储版本: {}",
+                    file.version
+                )));
+            }
+            let salt = data_encoding::BASE64URL_NOPAD
+                .decode(file.salt.as_bytes())
+                .map_err(|e| AgentError::config(format!("密钥存储已损坏: {}", e)))?;
+            let cipher = key.cipher(&salt)?;
+            if cipher.open_text(&file.check)? != KEY_CHECK {
+                return Err(AgentError::permission("密钥错误"));
+            }
+            Self::with_file(Some(path), cipher, file)?
+        } else {
+            let store = Self::create(Some(path), key)?;
+            store.save(&store.lock())?;
+            store
+        };
+        info!("打开密钥存储，共 {} 条机密", store.lock().secrets.len());
+        Ok(store)
+    }
+
+    /// 创建仅保存在内存中的密钥存储
+    pub fn in_memory(key: &KeySource) -> AgentResult<Self> {
+        Self::create(None, key)
+    }
+
+    fn create(path: Option<PathBuf>, key: &KeySource) -> AgentResult<Self> {
+        let salt: [u8; SALT_LEN] = random_salt();
+        let cipher = key.cipher(&salt)?;
+        let file = SecretFile {
+            version: SECRET_FILE_VERSION,
+            salt: data_encoding::BASE64URL_NOPAD.encode(&salt),
+            check: cipher.seal_text(KEY_CHECK)?,
+            secrets: BTreeMap::new(),
+        };
+        Self::with_file(path, cipher, file)
+    }
+
+    fn with_file(path: Option<PathBuf>, cipher: Cipher, file: SecretFile) -> AgentResult<Self> {
+        Ok(Self {
+            path,
+            cipher,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// 读取机密
+    pub fn get(&self, name: &str) -> AgentResult<Option<String>> {
+        self.lock()
+            .secrets
+            .get(name)
+            .map(|sealed| self.cipher.open_text(sealed))
+            .transpose()
+    }
+
+    /// 保存机密，已存在时覆盖
+    pub fn set(&self, name: &str, value: &str) -> AgentResult<()> {
+        let sealed = self.cipher.seal_text(value)?;
+        let mut file = self.lock();
+        file.secrets.insert(name.to_string(), sealed);
+        self.save(&file)
+    }
+
+    /// 读取机密，不存在时用 `init` 生成并保存
+    pub fn get_or_insert_with<F: FnOnce() -> String>(
+        &self,
+        name: &str,
+        init: F,
+    ) -> AgentResult<String> {
+        if let Some(value) = self.get(name)? {
+            return Ok(value);
+        }
+        let value = init();
+        self.set(name, &value)?;
+        Ok(value)
+    }
+
+    /// 删除机密，返回是否存在
+    pub fn remove(&self, name: &str) -> AgentResult<bool> {
+        let mut file = self.lock();
+        let removed = file.secrets.remove(name).is_some();
+        if removed {
+            self.save(&file)?;
+        }
+        Ok(removed)
+    }
+
+    /// 已保存的机密名称
+    pub fn names(&self) -> Vec<String> {
+        self.lock().secrets.keys().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SecretFile> {
+        self.file.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 先写临时文件再替换，避免写到一半时丢失全部机密
+    fn save(&self, file: &SecretFile) -> AgentResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(file)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_store_persists_encrypted() {
+        let path = std::env::temp_dir()
+            .join(format!("rig-agent-secrets-{}", uuid::Uuid::new_v4()))
+            .join("secrets.json");
+        let key = KeySource::passphrase("correct horse");
+
+        let store = SecretStore::open(&path, &key).unwrap();
+        store.set("openai", "sk-test").unwrap();
+        let node_key = store
+            .get_or_insert_with("node", || "node-secret".to_string())
+            .unwrap();
+        assert_eq!(node_key, "node-secret");
+        assert_eq!(
+            store.get_or_insert_with("node", String::new).unwrap(),
+            "node-secret"
+        );
+
+        // 文件中没有明文
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("sk-test"));
+        assert!(!raw.contains("node-secret"));
+
+        let reopened = SecretStore::open(&path, &key).unwrap();
+        assert_eq!(reopened.get("openai").unwrap().as_deref(), Some("sk-test"));
+        assert_eq!(reopened.names(), vec!["node", "openai"]);
+        assert!(reopened.remove("openai").unwrap());
+        assert!(reopened.get("openai").unwrap().is_none());
+
+        assert!(matches!(
+            SecretStore::open(&path, &KeySource::passphrase("battery staple")),
+            Err(AgentError::Permission(_))
+        ));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
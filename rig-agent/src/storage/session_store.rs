@@ -0,0 +1,993 @@
+//! 基于 SQLite 的聊天会话存储
+//!
+//! 会话与消息保存在本地 SQLite 数据库中，数据库结构通过内嵌迁移（`PRAGMA user_version`）升级。
+//! 删除会话为软删除，可在回收站中恢复，`purge_session` 才会真正删除数据。
+//!
+//! 用 [`SessionStore::open_encrypted`] 打开时，消息的正文与完整数据加密后写入（见 [`crate::storage::crypto`]），
+//! 会话标题、标签等列表信息仍为明文。启用加密前写入的消息仍可读取；数据库中记录了密钥校验值，
+//! 之后不提供密钥或密钥错误时无法打开。
+
+use crate::core::context::CALLER_ID_KEY;
+use crate::core::types::{AgentMessage, ChatSession, MessageMetadata};
+use crate::error::{AgentError, AgentResult};
+use crate::storage::crypto::{Cipher, KeySource, random_salt};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, Row, params, types::Type};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, info};
+
+/// 内嵌迁移，按顺序执行，版本号为下标 + 1
+const MIGRATIONS: &[&str] = &[
+    // 1: 会话表
+    r#"
+    CREATE TABLE sessions (
+        id            TEXT PRIMARY KEY,
+        title         TEXT NOT NULL,
+        model         TEXT NOT NULL,
+        tags          TEXT NOT NULL DEFAULT '[]',
+        agent_id      TEXT,
+        message_count INTEGER NOT NULL DEFAULT 0,
+        created_at    TEXT NOT NULL,
+        updated_at    TEXT NOT NULL,
+        deleted_at    TEXT
+    );
+    CREATE INDEX idx_sessions_updated_at ON sessions (updated_at);
+    CREATE INDEX idx_sessions_agent_id ON sessions (agent_id);
+    "#,
+    // 2: 会话消息表
+    r#"
+    CREATE TABLE session_messages (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT,
+        session_id TEXT NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+        role       TEXT NOT NULL,
+        content    TEXT NOT NULL,
+        data       TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_session_messages_session ON session_messages (session_id, id);
+    "#,
+    // 3: 按消息 ID 更新注解
+    r#"
+    ALTER TABLE session_messages ADD COLUMN message_id TEXT;
+    UPDATE session_messages SET message_id = json_extract(data, '$.id');
+    CREATE INDEX idx_session_messages_message_id ON session_messages (message_id);
+    "#,
+    // 4: 会话变量
+    r#"
+    ALTER TABLE sessions ADD COLUMN variables TEXT NOT NULL DEFAULT '{}';
+    "#,
+    // 5: 存储元数据（加密盐与密钥校验值）
+    r#"
+    CREATE TABLE store_meta (
+        key   TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    "#,
+    // 6: 按调用方删除数据，调用方 ID 取自消息元数据（加密的消息无法回填）
+    r#"
+    ALTER TABLE session_messages ADD COLUMN caller_id TEXT;
+    UPDATE session_messages
+    SET caller_id = json_extract(data, '$.metadata.caller_id')
+    WHERE json_valid(data);
+    CREATE INDEX idx_session_messages_caller ON session_messages (caller_id);
+    "#,
+];
+
+/// 元数据中的密钥派生盐
+const META_SALT: &str = "encryption_salt";
+/// 元数据中的密钥校验值
+const META_KEY_CHECK: &str = "encryption_key_check";
+/// 校验值的明文
+const KEY_CHECK: &str = "rig-agent-session-store";
+
+/// 会话列表的删除状态过滤
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionVisibility {
+    /// 仅未删除的会话
+    #[default]
+    Active,
+    /// 仅已删除的会话（回收站）
+    Deleted,
+    /// 全部会话
+    All,
+}
+
+/// 会话列表查询
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionQuery {
+    /// 偏移量
+    #[serde(default)]
+    pub offset: usize,
+    /// 每页数量
+    #[serde(default = "default_page_limit")]
+    pub limit: usize,
+    /// 删除状态过滤
+    #[serde(default)]
+    pub visibility: SessionVisibility,
+}
+
+fn default_page_limit() -> usize {
+    20
+}
+
+impl Default for SessionQuery {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            limit: default_page_limit(),
+            visibility: SessionVisibility::default(),
+        }
+    }
+}
+
+impl SessionQuery {
+    /// 设置分页
+    pub fn with_page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = limit;
+        self
+    }
+
+    /// 设置删除状态过滤
+    pub fn with_visibility(mut self, visibility: SessionVisibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+}
+
+/// 分页结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// 当前页数据
+    pub items: Vec<T>,
+    /// 总数
+    pub total: usize,
+    /// 偏移量
+    pub offset: usize,
+    /// 每页数量
+    pub limit: usize,
+}
+
+impl<T> Page<T> {
+    /// 是否还有下一页
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+}
+
+/// SQLite 会话存储
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    conn: Arc<Mutex<Connection>>,
+    cipher: Option<Cipher>,
+}
+
+impl SessionStore {
+    /// 打开（或创建）数据库文件并执行迁移
+    pub fn open<P: AsRef<Path>>(path: P) -> AgentResult<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path.as_ref()).map_err(AgentError::database)?;
+        info!("打开会话数据库: {}", path.as_ref().display());
+        Self::from_connection(conn, None)
+    }
+
+    /// 打开（或创建）加密的数据库文件，之后写入的消息都会加密
+    pub fn open_encrypted<P: AsRef<Path>>(path: P, key: &KeySource) -> AgentResult<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path.as_ref()).map_err(AgentError::database)?;
+        info!("打开加密的会话数据库: {}", path.as_ref().display());
+        Self::from_connection(conn, Some(key))
+    }
+
+    /// 创建内存数据库（用于测试）
+    pub fn open_in_memory() -> AgentResult<Self> {
+        let conn = Connection::open_in_memory().map_err(AgentError::database)?;
+        Self::from_connection(conn, None)
+    }
+
+    fn from_connection(mut conn: Connection, key: Option<&KeySource>) -> AgentResult<Self> {
+        conn.pragma_update(None, "foreign_keys", true)
+            .map_err(AgentError::database)?;
+        migrate(&mut conn)?;
+        let cipher = setup_encryption(&mut conn, key)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            cipher,
+        })
+    }
+
+    /// 是否加密写入消息
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// 当前数据库结构版本
+    pub async fn schema_version(&self) -> AgentResult<usize> {
+        self.run(|conn| {
+            conn.pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0))
+                .map(|v| v as usize)
+        })
+        .await
+    }
+
+    /// 创建会话
+    pub async fn create_session(&self, session: ChatSession) -> AgentResult<ChatSession> {
+        let stored = session.clone();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO sessions
+                    (id, title, model, tags, agent_id, message_count, created_at, updated_at, deleted_at,
+                     variables)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    stored.id,
+                    stored.title,
+                    stored.model,
+                    tags_to_json(&stored.tags),
+                    stored.agent_id,
+                    stored.message_count as i64,
+                    format_time(stored.created_at),
+                    format_time(stored.updated_at),
+                    stored.deleted_at.map(format_time),
+                    variables_to_json(&stored.variables),
+                ],
+            )
+        })
+        .await?;
+        debug!("创建会话: {}", session.id);
+        Ok(session)
+    }
+
+    /// 获取会话（包括已删除的会话）
+    pub async fn get_session(&self, session_id: &str) -> AgentResult<Option<ChatSession>> {
+        let session_id = session_id.to_string();
+        self.run(move |conn| {
+            conn.query_row(
+                "SELECT * FROM sessions WHERE id = ?1",
+                params![session_id],
+                session_from_row,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    /// 查找 Agent 关联的最近一个未删除会话
+    pub async fn find_session_by_agent(&self, agent_id: &str) -> AgentResult<Option<ChatSession>> {
+        let agent_id = agent_id.to_string();
+        self.run(move |conn| {
+            conn.query_row(
+                "SELECT * FROM sessions
+                 WHERE agent_id = ?1 AND deleted_at IS NULL
+                 ORDER BY updated_at DESC LIMIT 1",
+                params![agent_id],
+                session_from_row,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    /// 分页列出会话，按最后更新时间倒序
+    pub async fn list_sessions(&self, query: SessionQuery) -> AgentResult<Page<ChatSession>> {
+        self.run(move |conn| {
+            let filter = match query.visibility {
+                SessionVisibility::Active => "WHERE deleted_at IS NULL",
+                SessionVisibility::Deleted => "WHERE deleted_at IS NOT NULL",
+                SessionVisibility::All => "",
+            };
+
+            let total: i64 = conn.query_row(
+                &format!("SELECT COUNT(*) FROM sessions {}", filter),
+                [],
+                |row| row.get(0),
+            )?;
+
+            let mut stmt = conn.prepare(&format!(
+                "SELECT * FROM sessions {} ORDER BY updated_at DESC LIMIT ?1 OFFSET ?2",
+                filter
+            ))?;
+            let items = stmt
+                .query_map(
+                    params![query.limit as i64, query.offset as i64],
+                    session_from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Page {
+                items,
+                total: total as usize,
+                offset: query.offset,
+                limit: query.limit,
+            })
+        })
+        .await
+    }
+
+    /// 更新会话的标题、标签与关联 Agent
+    pub async fn update_session(&self, session: &ChatSession) -> AgentResult<()> {
+        let session = session.clone();
+        let updated = self
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE sessions
+                     SET title = ?2, model = ?3, tags = ?4, agent_id = ?5, updated_at = ?6
+                     WHERE id = ?1",
+                    params![
+                        session.id,
+                        session.title,
+                        session.model,
+                        tags_to_json(&session.tags),
+                        session.agent_id,
+                        format_time(Utc::now()),
+                    ],
+                )
+            })
+            .await?;
+        ensure_found(updated, "会话不存在")
+    }
+
+    /// 获取会话变量
+    pub async fn get_variables(&self, session_id: &str) -> AgentResult<BTreeMap<String, String>> {
+        let session_id = session_id.to_string();
+        let variables: Option<String> = self
+            .run(move |conn| {
+                conn.query_row(
+                    "SELECT variables FROM sessions WHERE id = ?1",
+                    params![session_id],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await?;
+        let variables = variables.ok_or_else(|| AgentError::database("会话不存在"))?;
+        Ok(serde_json::from_str(&variables)?)
+    }
+
+    /// 设置（值为 `None` 时移除）会话变量，返回更新后的全部变量
+    pub async fn set_variable(
+        &self,
+        session_id: &str,
+        key: &str,
+        value: Option<String>,
+    ) -> AgentResult<BTreeMap<String, String>> {
+        let session_id = session_id.to_string();
+        let key = key.to_string();
+        let variables = self
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let data: Option<String> = tx
+                    .query_row(
+                        "SELECT variables FROM sessions WHERE id = ?1",
+                        params![session_id],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+                let Some(data) = data else {
+                    return Ok(None);
+                };
+
+                let mut variables: BTreeMap<String, String> =
+                    serde_json::from_str(&data).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into())
+                    })?;
+                match value {
+                    Some(value) => variables.insert(key, value),
+                    None => variables.remove(&key),
+                };
+
+                tx.execute(
+                    "UPDATE sessions SET variables = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![
+                        session_id,
+                        variables_to_json(&variables),
+                        format_time(Utc::now())
+                    ],
+                )?;
+                tx.commit()?;
+                Ok(Some(variables))
+            })
+            .await?;
+        variables.ok_or_else(|| AgentError::database("会话不存在"))
+    }
+
+    /// 软删除会话
+    pub async fn delete_session(&self, session_id: &str) -> AgentResult<()> {
+        let session_id = session_id.to_string();
+        let updated = self
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE sessions SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+                    params![session_id, format_time(Utc::now())],
+                )
+            })
+            .await?;
+        ensure_found(updated, "会话不存在或已删除")
+    }
+
+    /// 恢复已软删除的会话
+    pub async fn restore_session(&self, session_id: &str) -> AgentResult<()> {
+        let session_id = session_id.to_string();
+        let updated = self
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE sessions SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                    params![session_id],
+                )
+            })
+            .await?;
+        ensure_found(updated, "会话不存在或未被删除")
+    }
+
+    /// 永久删除会话及其消息
+    pub async fn purge_session(&self, session_id: &str) -> AgentResult<()> {
+        let session_id = session_id.to_string();
+        let deleted = self
+            .run(move |conn| {
+                conn.execute("DELETE FROM sessions WHERE id = ?1", params![session_id])
+            })
+            .await?;
+        ensure_found(deleted, "会话不存在")
+    }
+
+    /// 永久删除最后更新时间早于 `cutoff` 的会话（包括回收站中的会话）及其消息，返回删除的会话数
+    pub async fn purge_before(&self, cutoff: DateTime<Utc>) -> AgentResult<usize> {
+        let deleted = self
+            .run(move |conn| {
+                conn.execute(
+                    "DELETE FROM sessions WHERE updated_at < ?1",
+                    params![format_time(cutoff)],
+                )
+            })
+            .await?;
+        if deleted > 0 {
+            info!("删除 {} 个过期会话", deleted);
+        }
+        Ok(deleted)
+    }
+
+    /// 永久删除包含该调用方消息的全部会话，返回删除的会话数
+    ///
+    /// 回复与上下文同样可能包含调用方的个人数据，因此删除整个会话而不只是调用方发出的消息
+    pub async fn purge_caller(&self, caller_id: &str) -> AgentResult<usize> {
+        let caller_id = caller_id.to_string();
+        self.run(move |conn| {
+            conn.execute(
+                "DELETE FROM sessions WHERE id IN
+                    (SELECT session_id FROM session_messages WHERE caller_id = ?1)",
+                params![caller_id],
+            )
+        })
+        .await
+    }
+
+    /// 追加消息并更新会话的消息数量与更新时间
+    pub async fn append_messages(
+        &self,
+        session_id: &str,
+        messages: Vec<AgentMessage>,
+    ) -> AgentResult<()> {
+        let session_id = session_id.to_string();
+        let rows = messages
+            .iter()
+            .map(|msg| {
+                Ok((
+                    msg.id.clone(),
+                    serde_json::to_value(&msg.role)?
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    msg.metadata
+                        .get(CALLER_ID_KEY)
+                        .and_then(|caller_id| caller_id.as_str())
+                        .map(str::to_string),
+                    seal(&self.cipher, &msg.content)?,
+                    seal(&self.cipher, &serde_json::to_string(msg)?)?,
+                    format_time(msg.timestamp),
+                ))
+            })
+            .collect::<AgentResult<Vec<_>>>()?;
+
+        let updated = self
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let updated = tx.execute(
+                    "UPDATE sessions
+                     SET message_count = message_count + ?2, updated_at = ?3
+                     WHERE id = ?1",
+                    params![session_id, rows.len() as i64, format_time(Utc::now())],
+                )?;
+                if updated > 0 {
+                    let mut stmt = tx.prepare(
+                        "INSERT INTO session_messages
+                            (session_id, message_id, role, caller_id, content, data, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    )?;
+                    for (message_id, role, caller_id, content, data, created_at) in &rows {
+                        stmt.execute(params![
+                            session_id, message_id, role, caller_id, content, data, created_at
+                        ])?;
+                    }
+                    drop(stmt);
+                }
+                tx.commit()?;
+                Ok(updated)
+            })
+            .await?;
+        ensure_found(updated, "会话不存在")
+    }
+
+    /// 合并消息注解，值为 null 的键会被移除。消息不存在时返回 `None`
+    pub async fn annotate_message(
+        &self,
+        message_id: &str,
+        annotations: MessageMetadata,
+    ) -> AgentResult<Option<MessageMetadata>> {
+        let message_id = message_id.to_string();
+        let cipher = self.cipher.clone();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let data: Option<String> = tx
+                .query_row(
+                    "SELECT data FROM session_messages WHERE message_id = ?1",
+                    params![message_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(data) = data else {
+                return Ok(None);
+            };
+
+            let data = open(&cipher, &data)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into()))?;
+            let mut message: AgentMessage = serde_json::from_str(&data)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into()))?;
+            message.annotate(annotations);
+            let data = serde_json::to_string(&message)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+            let data = seal(&cipher, &data)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+
+            tx.execute(
+                "UPDATE session_messages SET data = ?2 WHERE message_id = ?1",
+                params![message_id, data],
+            )?;
+            tx.commit()?;
+            Ok(Some(message.metadata))
+        })
+        .await
+    }
+
+    /// 分页获取会话消息，按写入顺序排列
+    pub async fn get_messages(
+        &self,
+        session_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> AgentResult<Page<AgentMessage>> {
+        let session_id = session_id.to_string();
+        let (total, rows) = self
+            .run(move |conn| {
+                let total: i64 = conn.query_row(
+                    "SELECT COUNT(*) FROM session_messages WHERE session_id = ?1",
+                    params![session_id],
+                    |row| row.get(0),
+                )?;
+                let mut stmt = conn.prepare(
+                    "SELECT data FROM session_messages WHERE session_id = ?1
+                     ORDER BY id LIMIT ?2 OFFSET ?3",
+                )?;
+                let rows = stmt
+                    .query_map(params![session_id, limit as i64, offset as i64], |row| {
+                        row.get::<_, String>(0)
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((total, rows))
+            })
+            .await?;
+
+        let items = rows
+            .iter()
+            .map(|data| Ok(serde_json::from_str(&open(&self.cipher, data)?)?))
+            .collect::<AgentResult<Vec<AgentMessage>>>()?;
+
+        Ok(Page {
+            items,
+            total: total as usize,
+            offset,
+            limit,
+        })
+    }
+
+    /// 在阻塞线程池中执行数据库操作
+    async fn run<T, F>(&self, f: F) -> AgentResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn
+                .lock()
+                .map_err(|_| AgentError::database("数据库连接锁已损坏"))?;
+            f(&mut conn).map_err(AgentError::database)
+        })
+        .await
+        .map_err(|e| AgentError::database(format!("数据库任务失败: {}", e)))?
+    }
+}
+
+/// 执行尚未应用的迁移
+fn migrate(conn: &mut Connection) -> AgentResult<()> {
+    let current: usize = conn
+        .pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0))
+        .map_err(AgentError::database)? as usize;
+
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+        let version = index + 1;
+        let tx = conn.transaction().map_err(AgentError::database)?;
+        tx.execute_batch(sql).map_err(AgentError::database)?;
+        tx.pragma_update(None, "user_version", version as i64)
+            .map_err(AgentError::database)?;
+        tx.commit().map_err(AgentError::database)?;
+        info!("会话数据库迁移到版本 {}", version);
+    }
+
+    Ok(())
+}
+
+/// 读取或初始化加密元数据，返回加密器
+///
+/// 数据库首次加密时生成盐并写入密钥校验值，之后打开时校验密钥；已加密的数据库不能以明文方式打开。
+fn setup_encryption(conn: &mut Connection, key: Option<&KeySource>) -> AgentResult<Option<Cipher>> {
+    let meta = |conn: &Connection, key: &str| {
+        conn.query_row(
+            "SELECT value FROM store_meta WHERE key = ?1",
+            params![key],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(AgentError::database)
+    };
+    let check = meta(conn, META_KEY_CHECK)?;
+    let Some(key) = key else {
+        if check.is_some() {
+            return Err(AgentError::permission("会话数据库已加密，需要提供密钥"));
+        }
+        return Ok(None);
+    };
+
+    let salt = match meta(conn, META_SALT)? {
+        Some(salt) => data_encoding::BASE64URL_NOPAD
+            .decode(salt.as_bytes())
+            .map_err(|e| AgentError::database(format!("加密盐已损坏: {}", e)))?,
+        None => random_salt().to_vec(),
+    };
+    let cipher = key.cipher(&salt)?;
+    match check {
+        Some(check) => {
+            if cipher.open_text(&check)? != KEY_CHECK {
+                return Err(AgentError::permission("密钥错误"));
+            }
+        }
+        None => {
+            let tx = conn.transaction().map_err(AgentError::database)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO store_meta (key, value) VALUES (?1, ?2), (?3, ?4)",
+                params![
+                    META_SALT,
+                    data_encoding::BASE64URL_NOPAD.encode(&salt),
+                    META_KEY_CHECK,
+                    cipher.seal_text(KEY_CHECK)?,
+                ],
+            )
+            .map_err(AgentError::database)?;
+            tx.commit().map_err(AgentError::database)?;
+            info!("会话数据库已启用加密");
+        }
+    }
+    Ok(Some(cipher))
+}
+
+/// 有加密器时加密文本
+fn seal(cipher: &Option<Cipher>, text: &str) -> AgentResult<String> {
+    match cipher {
+        Some(cipher) => cipher.seal_text(text),
+        None => Ok(text.to_string()),
+    }
+}
+
+/// 有加密器时解密文本，明文原样返回
+fn open(cipher: &Option<Cipher>, text: &str) -> AgentResult<String> {
+    match cipher {
+        Some(cipher) => cipher.open_text(text),
+        None => Ok(text.to_string()),
+    }
+}
+
+fn session_from_row(row: &Row<'_>) -> rusqlite::Result<ChatSession> {
+    let tags: String = row.get("tags")?;
+    let deleted_at: Option<String> = row.get("deleted_at")?;
+    let variables: String = row.get("variables")?;
+
+    Ok(ChatSession {
+        id: row.get("id")?,
+        title: row.get("title")?,
+        model: row.get("model")?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        agent_id: row.get("agent_id")?,
+        message_count: row.get::<_, i64>("message_count")? as usize,
+        created_at: parse_time(row.get("created_at")?),
+        updated_at: parse_time(row.get("updated_at")?),
+        deleted_at: deleted_at.map(parse_time),
+        variables: serde_json::from_str(&variables).unwrap_or_default(),
+    })
+}
+
+/// 固定精度格式化时间，保证按文本排序与时间顺序一致
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_time(value: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&value)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_default()
+}
+
+fn tags_to_json(tags: &[String]) -> String {
+    serde_json::to_string(tags).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn variables_to_json(variables: &BTreeMap<String, String>) -> String {
+    serde_json::to_string(variables).unwrap_or_else(|_| "{}".to_string())
+}
+
+fn ensure_found(affected: usize, message: &str) -> AgentResult<()> {
+    if affected == 0 {
+        return Err(AgentError::database(message));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_crud_and_soft_delete() {
+        let store = SessionStore::open_in_memory().unwrap();
+        assert_eq!(store.schema_version().await.unwrap(), MIGRATIONS.len());
+
+        let mut session = store
+            .create_session(ChatSession::new(
+                "第一个会话".to_string(),
+                "gpt-4".to_string(),
+            ))
+            .await
+            .unwrap();
+        store
+            .create_session(ChatSession::new(
+                "第二个会话".to_string(),
+                "gpt-4".to_string(),
+            ))
+            .await
+            .unwrap();
+
+        session.title = "重命名".to_string();
+        session.add_tag("工作".to_string());
+        store.update_session(&session).await.unwrap();
+        let loaded = store.get_session(&session.id).await.unwrap().unwrap();
+        assert_eq!(loaded.title, "重命名");
+        assert_eq!(loaded.tags, vec!["工作".to_string()]);
+
+        let page = store
+            .list_sessions(SessionQuery::default().with_page(0, 1))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items.len(), 1);
+        assert!(page.has_more());
+
+        store.delete_session(&session.id).await.unwrap();
+        assert_eq!(
+            store
+                .list_sessions(SessionQuery::default())
+                .await
+                .unwrap()
+                .total,
+            1
+        );
+        let trash = store
+            .list_sessions(SessionQuery::default().with_visibility(SessionVisibility::Deleted))
+            .await
+            .unwrap();
+        assert_eq!(trash.items[0].id, session.id);
+        assert!(trash.items[0].is_deleted());
+
+        store.restore_session(&session.id).await.unwrap();
+        assert!(store.restore_session(&session.id).await.is_err());
+
+        store.purge_session(&session.id).await.unwrap();
+        assert!(store.get_session(&session.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_session_messages() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let session = store
+            .create_session(
+                ChatSession::new("会话".to_string(), "gpt-4".to_string()).with_agent_id("agent"),
+            )
+            .await
+            .unwrap();
+
+        store
+            .append_messages(
+                &session.id,
+                vec![
+                    AgentMessage::user("你好".to_string()),
+                    AgentMessage::assistant("你好！".to_string()),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let found = store.find_session_by_agent("agent").await.unwrap().unwrap();
+        assert_eq!(found.id, session.id);
+        assert_eq!(found.message_count, 2);
+
+        let messages = store.get_messages(&session.id, 1, 10).await.unwrap();
+        assert_eq!(messages.total, 2);
+        assert_eq!(messages.items[0].content, "你好！");
+
+        assert!(
+            store
+                .append_messages("missing", vec![AgentMessage::user("x".to_string())])
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_variables() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let mut session = ChatSession::new("会话".to_string(), "gpt-4".to_string());
+        session.set_variable("user_name", "小明");
+        let session = store.create_session(session).await.unwrap();
+
+        let variables = store
+            .set_variable(&session.id, "project_path", Some("/work/app".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(variables.len(), 2);
+
+        store
+            .set_variable(&session.id, "user_name", None)
+            .await
+            .unwrap();
+        let loaded = store.get_session(&session.id).await.unwrap().unwrap();
+        assert_eq!(loaded.get_variable("project_path"), Some("/work/app"));
+        assert_eq!(loaded.get_variable("user_name"), None);
+        assert_eq!(store.get_variables(&session.id).await.unwrap().len(), 1);
+
+        assert!(store.set_variable("missing", "k", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_sessions() {
+        let store = SessionStore::open_in_memory().unwrap();
+        let alice = store
+            .create_session(ChatSession::new("alice".to_string(), "gpt-4".to_string()))
+            .await
+            .unwrap();
+        let bob = store
+            .create_session(ChatSession::new("bob".to_string(), "gpt-4".to_string()))
+            .await
+            .unwrap();
+        let mut message = AgentMessage::user("我的电话是 123".to_string());
+        message
+            .metadata
+            .insert(CALLER_ID_KEY.to_string(), "alice".into());
+        store
+            .append_messages(
+                &alice.id,
+                vec![message, AgentMessage::assistant("记住了".to_string())],
+            )
+            .await
+            .unwrap();
+        store
+            .append_messages(&bob.id, vec![AgentMessage::user("你好".to_string())])
+            .await
+            .unwrap();
+
+        // 按调用方删除整个会话
+        assert_eq!(store.purge_caller("alice").await.unwrap(), 1);
+        assert!(store.get_session(&alice.id).await.unwrap().is_none());
+        assert_eq!(store.purge_caller("alice").await.unwrap(), 0);
+
+        // 按最后更新时间删除，回收站中的会话同样删除
+        store.delete_session(&bob.id).await.unwrap();
+        assert_eq!(
+            store
+                .purge_before(Utc::now() - chrono::Duration::days(1))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(store.purge_before(Utc::now()).await.unwrap(), 1);
+        assert!(store.get_session(&bob.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_messages() {
+        let dir = std::env::temp_dir().join(format!("rig-agent-sessions-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("sessions.db");
+        let key = KeySource::passphrase("correct horse");
+
+        // 启用加密前写入的明文消息仍然可读
+        let plain = SessionStore::open(&path).unwrap();
+        let session = plain
+            .create_session(ChatSession::new("会话".to_string(), "gpt-4".to_string()))
+            .await
+            .unwrap();
+        plain
+            .append_messages(&session.id, vec![AgentMessage::user("旧消息".to_string())])
+            .await
+            .unwrap();
+        drop(plain);
+
+        let store = SessionStore::open_encrypted(&path, &key).unwrap();
+        assert!(store.is_encrypted());
+        let secret = AgentMessage::assistant("机密回复".to_string());
+        let secret_id = secret.id.clone();
+        store
+            .append_messages(&session.id, vec![secret])
+            .await
+            .unwrap();
+        let raw: Vec<String> = store
+            .run(|conn| {
+                conn.prepare("SELECT content || data FROM session_messages")?
+                    .query_map([], |row| row.get(0))?
+                    .collect()
+            })
+            .await
+            .unwrap();
+        assert!(!raw[1].contains("机密回复"));
+
+        let mut annotations = MessageMetadata::new();
+        annotations.insert("rating".to_string(), 5.into());
+        store
+            .annotate_message(&secret_id, annotations)
+            .await
+            .unwrap()
+            .unwrap();
+        let messages = store.get_messages(&session.id, 0, 10).await.unwrap();
+        assert_eq!(messages.items[0].content, "旧消息");
+        assert_eq!(messages.items[1].content, "机密回复");
+        assert_eq!(messages.items[1].metadata["rating"], 5);
+        drop(store);
+
+        // 已加密的数据库需要正确的密钥
+        assert!(matches!(
+            SessionStore::open(&path),
+            Err(AgentError::Permission(_))
+        ));
+        assert!(matches!(
+            SessionStore::open_encrypted(&path, &KeySource::passphrase("battery staple")),
+            Err(AgentError::Permission(_))
+        ));
+        assert!(SessionStore::open_encrypted(&path, &key).is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
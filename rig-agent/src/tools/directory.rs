@@ -0,0 +1,444 @@
+//! 工具目录 - 运行时从目录加载工具，无需重启
+//!
+//! 目录中的每个 `.json` 文件定义一个 webhook 工具：调用时把参数发给配置的地址，返回响应内容。
+//! 后台任务定期扫描目录，新增或修改的文件注册（或替换）为自定义工具，删除的文件注销对应工具，
+//! 每次变化都发出工具事件，前端据此刷新工具列表。当前构建没有 WASM 运行时，
+//! `.wasm` 文件会报告加载失败而不是被静默忽略
+
+use super::{CustomTool, ToolEvent, ToolManager, groups};
+use crate::core::AgentManager;
+use crate::error::{AgentError, AgentResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// 工具目录配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolsDirectoryConfig {
+    /// 工具目录
+    pub dir: PathBuf,
+    /// 扫描间隔（秒）
+    #[serde(default = "default_scan_interval_secs")]
+    pub scan_interval_secs: u64,
+}
+
+fn default_scan_interval_secs() -> u64 {
+    5
+}
+
+impl ToolsDirectoryConfig {
+    /// 创建工具目录配置
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            scan_interval_secs: default_scan_interval_secs(),
+        }
+    }
+
+    /// 设置扫描间隔（秒）
+    pub fn with_scan_interval_secs(mut self, scan_interval_secs: u64) -> Self {
+        self.scan_interval_secs = scan_interval_secs;
+        self
+    }
+
+    /// 扫描间隔，至少 1 秒
+    pub fn scan_interval(&self) -> Duration {
+        Duration::from_secs(self.scan_interval_secs.max(1))
+    }
+}
+
+/// JSON 文件定义的 webhook 工具
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookToolSpec {
+    /// 工具名称
+    pub name: String,
+    /// 工具命名空间（工具组）
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 工具描述
+    pub description: String,
+    /// 参数定义（JSON Schema）
+    #[serde(default = "default_parameters")]
+    pub parameters: serde_json::Value,
+    /// 调用地址
+    pub url: String,
+    /// HTTP 方法，`GET` 时参数作为查询字符串，其余方法作为 JSON 请求体
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// 请求头
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// 超时时间（秒）
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_namespace() -> String {
+    groups::CUSTOM.to_string()
+}
+
+fn default_parameters() -> serde_json::Value {
+    serde_json::json!({ "type": "object" })
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+/// webhook 工具
+pub struct WebhookTool {
+    spec: WebhookToolSpec,
+    method: reqwest::Method,
+    client: reqwest::Client,
+}
+
+impl WebhookTool {
+    /// 按定义创建工具，名称为空、地址或方法无效时返回错误
+    pub fn new(spec: WebhookToolSpec) -> AgentResult<Self> {
+        if spec.name.trim().is_empty() {
+            return Err(AgentError::tool("工具名称不能为空"));
+        }
+        reqwest::Url::parse(&spec.url)
+            .map_err(|e| AgentError::tool(format!("无效的 webhook 地址 {}: {}", spec.url, e)))?;
+        let method = reqwest::Method::from_bytes(spec.method.to_uppercase().as_bytes())
+            .map_err(|_| AgentError::tool(format!("无效的 HTTP 方法: {}", spec.method)))?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(spec.timeout_secs.max(1)))
+            .build()
+            .map_err(|e| AgentError::config(format!("创建 HTTP 客户端失败: {}", e)))?;
+        Ok(Self {
+            spec,
+            method,
+            client,
+        })
+    }
+
+    /// 从 JSON 文件加载工具
+    pub fn load(path: &Path) -> AgentResult<Self> {
+        if path.extension().is_some_and(|ext| ext == "wasm") {
+            return Err(AgentError::tool(
+                "不支持 WASM 工具：当前构建没有 WASM 运行时",
+            ));
+        }
+        let content = std::fs::read_to_string(path)?;
+        Self::new(serde_json::from_str(&content)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl CustomTool for WebhookTool {
+    fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    fn namespace(&self) -> &str {
+        &self.spec.namespace
+    }
+
+    fn description(&self) -> &str {
+        &self.spec.description
+    }
+
+    fn parameters(&self) -> serde_json::Value {
+        self.spec.parameters.clone()
+    }
+
+    async fn execute(&self, arguments: &str) -> AgentResult<String> {
+        let args: serde_json::Value = serde_json::from_str(arguments)?;
+        let mut request = self.client.request(self.method.clone(), &self.spec.url);
+        for (name, value) in &self.spec.headers {
+            request = request.header(name, value);
+        }
+        request = if self.method == reqwest::Method::GET {
+            let query: Vec<(String, String)> = args
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key.clone(), value.clone()),
+                    value => (key.clone(), value.to_string()),
+                })
+                .collect();
+            request.query(&query)
+        } else {
+            request.json(&args)
+        };
+
+        let response = request.send().await.map_err(|e| {
+            AgentError::tool(format!("调用 webhook 工具 {} 失败: {}", self.spec.name, e))
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| {
+            AgentError::tool(format!(
+                "读取 webhook 工具 {} 的响应失败: {}",
+                self.spec.name, e
+            ))
+        })?;
+        if !status.is_success() {
+            return Err(AgentError::tool(format!(
+                "webhook 工具 {} 返回 {}: {}",
+                self.spec.name, status, body
+            )));
+        }
+        Ok(body)
+    }
+}
+
+/// 文件的修改时间与大小，任一变化即视为文件被修改
+type FileStamp = (SystemTime, u64);
+
+/// 目录中的文件变化
+#[derive(Debug)]
+pub enum ToolFileChange {
+    /// 新增或修改的文件
+    Updated { path: PathBuf, stamp: FileStamp },
+    /// 删除的文件
+    Removed { path: PathBuf },
+}
+
+/// 已加载的文件
+struct LoadedFile {
+    stamp: FileStamp,
+    /// 从该文件注册的工具，加载失败时为空
+    tool: Option<String>,
+}
+
+/// 工具目录
+pub struct ToolsDirectory {
+    config: ToolsDirectoryConfig,
+    files: HashMap<PathBuf, LoadedFile>,
+}
+
+impl ToolsDirectory {
+    /// 创建工具目录，首次扫描时加载其中的全部工具
+    pub fn new(config: ToolsDirectoryConfig) -> Self {
+        Self {
+            config,
+            files: HashMap::new(),
+        }
+    }
+
+    /// 扫描目录，返回自上次应用以来的文件变化；目录不存在时视为空目录
+    pub fn scan(&self) -> Vec<ToolFileChange> {
+        let mut present = HashMap::new();
+        match std::fs::read_dir(&self.config.dir) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let is_tool = path
+                        .extension()
+                        .is_some_and(|ext| ext == "json" || ext == "wasm");
+                    let Ok(metadata) = entry.metadata() else {
+                        continue;
+                    };
+                    if is_tool && metadata.is_file() {
+                        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                        present.insert(path, (modified, metadata.len()));
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                warn!("扫描工具目录 {:?} 失败: {}", self.config.dir, e);
+                return Vec::new();
+            }
+        }
+
+        let mut changes: Vec<ToolFileChange> = self
+            .files
+            .keys()
+            .filter(|path| !present.contains_key(*path))
+            .map(|path| ToolFileChange::Removed { path: path.clone() })
+            .collect();
+        changes.extend(
+            present
+                .into_iter()
+                .filter(|(path, stamp)| {
+                    self.files
+                        .get(path)
+                        .is_none_or(|loaded| loaded.stamp != *stamp)
+                })
+                .map(|(path, stamp)| ToolFileChange::Updated { path, stamp }),
+        );
+        changes
+    }
+
+    /// 把文件变化应用到工具管理器，返回并发出相应的工具事件。
+    /// 与已有工具重名的定义不会覆盖已有工具
+    pub fn apply(
+        &mut self,
+        changes: Vec<ToolFileChange>,
+        tools: &mut ToolManager,
+    ) -> Vec<ToolEvent> {
+        let mut events = Vec::new();
+        for change in changes {
+            let (path, stamp) = match change {
+                ToolFileChange::Removed { path } => {
+                    if let Some(tool_name) = self.files.remove(&path).and_then(|file| file.tool) {
+                        tools.remove_custom_tool(&tool_name);
+                        events.push(ToolEvent::ToolUnregistered {
+                            tool_name,
+                            source: path.display().to_string(),
+                        });
+                    }
+                    continue;
+                }
+                ToolFileChange::Updated { path, stamp } => (path, stamp),
+            };
+
+            let source = path.display().to_string();
+            let previous = self.files.remove(&path).and_then(|file| file.tool);
+            if let Some(tool_name) = &previous {
+                tools.remove_custom_tool(tool_name);
+            }
+            let loaded = WebhookTool::load(&path).and_then(|tool| {
+                if tools.has_tool(tool.name()) {
+                    Err(AgentError::tool(format!("工具 {} 已存在", tool.name())))
+                } else {
+                    Ok(tool)
+                }
+            });
+            let tool = match loaded {
+                Ok(tool) => {
+                    let tool_name = tool.name().to_string();
+                    tools.add_custom_tool(Box::new(tool));
+                    Some(tool_name)
+                }
+                Err(e) => {
+                    events.push(ToolEvent::ToolLoadFailed {
+                        source: source.clone(),
+                        error: e.to_string(),
+                    });
+                    None
+                }
+            };
+            if let Some(old) = previous.filter(|old| tool.as_ref() != Some(old)) {
+                events.push(ToolEvent::ToolUnregistered {
+                    tool_name: old,
+                    source: source.clone(),
+                });
+            }
+            if let Some(tool_name) = &tool {
+                events.push(ToolEvent::ToolRegistered {
+                    tool_name: tool_name.clone(),
+                    source,
+                });
+            }
+            self.files.insert(path, LoadedFile { stamp, tool });
+        }
+
+        for event in &events {
+            match event {
+                ToolEvent::ToolLoadFailed { source, error } => {
+                    warn!("加载工具文件 {} 失败: {}", source, error)
+                }
+                event => info!("工具目录变化: {:?}", event),
+            }
+            tools.telemetry.emit(event.clone());
+        }
+        events
+    }
+}
+
+/// 启动后台任务，定期扫描工具目录并注册或注销其中的工具
+pub fn spawn_tools_watcher(
+    manager: Arc<RwLock<AgentManager>>,
+    config: ToolsDirectoryConfig,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!(
+            "监视工具目录: {:?}, 扫描间隔: {:?}",
+            config.dir,
+            config.scan_interval()
+        );
+        let mut interval = tokio::time::interval(config.scan_interval());
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut directory = ToolsDirectory::new(config);
+
+        loop {
+            interval.tick().await;
+            // 只在目录有变化时获取写锁
+            let changes = directory.scan();
+            if !changes.is_empty() {
+                let mut manager = manager.write().await;
+                directory.apply(changes, manager.get_tool_manager_mut());
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tools_directory_reload() {
+        let dir = std::env::temp_dir().join(format!("rig-agent-tools-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut tools = ToolManager::new();
+        let mut events = tools.subscribe_events();
+        let mut directory = ToolsDirectory::new(ToolsDirectoryConfig::new(&dir));
+
+        let spec = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "namespace": "crm",
+                "description": "查询客户信息",
+                "url": "http://127.0.0.1:9/lookup",
+                "method": "get",
+            })
+            .to_string()
+        };
+        let lookup = dir.join("lookup.json");
+        std::fs::write(&lookup, spec("crm_lookup")).unwrap();
+        std::fs::write(dir.join("calc.json"), spec("calculator")).unwrap();
+        std::fs::write(dir.join("plugin.wasm"), b"\0asm").unwrap();
+        std::fs::write(dir.join("notes.txt"), "忽略").unwrap();
+
+        let changed = directory.apply(directory.scan(), &mut tools);
+        assert_eq!(changed.len(), 3);
+        assert!(tools.has_tool("crm_lookup"));
+        assert_eq!(tools.get_tool_groups()["crm"], ["crm_lookup"]);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            ToolEvent::ToolRegistered { .. } | ToolEvent::ToolLoadFailed { .. }
+        ));
+        let failures = changed
+            .iter()
+            .filter(|event| matches!(event, ToolEvent::ToolLoadFailed { .. }))
+            .count();
+        // 与内置工具重名的定义与 WASM 文件都加载失败
+        assert_eq!(failures, 2);
+        assert!(directory.scan().is_empty());
+
+        // 修改文件时替换工具，名称变化时注销旧工具
+        std::fs::write(&lookup, spec("crm_lookup_v2")).unwrap();
+        let changed = directory.apply(directory.scan(), &mut tools);
+        assert!(matches!(
+            &changed[..],
+            [ToolEvent::ToolUnregistered { tool_name: old, .. }, ToolEvent::ToolRegistered { tool_name: new, .. }]
+                if old == "crm_lookup" && new == "crm_lookup_v2"
+        ));
+        assert!(!tools.has_tool("crm_lookup"));
+
+        std::fs::remove_file(&lookup).unwrap();
+        let changed = directory.apply(directory.scan(), &mut tools);
+        assert!(matches!(
+            &changed[..],
+            [ToolEvent::ToolUnregistered { tool_name, .. }] if tool_name == "crm_lookup_v2"
+        ));
+        assert!(!tools.has_tool("crm_lookup_v2"));
+        assert!(tools.has_tool("calculator"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
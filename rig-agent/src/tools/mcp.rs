@@ -0,0 +1,293 @@
+//! 子进程 MCP 服务器监督
+//!
+//! stdio 传输的 MCP 服务器作为子进程运行：启动时按配置拉起全部服务器，进程退出后按指数退避重启，
+//! 连续快速退出的次数超过上限时放弃并发出 [`ToolEvent::McpServerFailed`]。服务器的 stderr 逐行写入日志，
+//! 各服务器的运行状况汇总到工具统计中。子进程的 stdin/stdout 是与服务器通信的管道，
+//! 在进程运行期间保持打开（多数服务器读到 EOF 时会退出）
+
+use super::{ToolEvent, ToolTelemetry};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+/// 第一次重启前的等待时间
+const MIN_RESTART_BACKOFF: Duration = Duration::from_millis(500);
+/// 重启前的最长等待时间
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+/// 连续退出的最大次数，超过后不再重启
+const MAX_CONSECUTIVE_CRASHES: u32 = 5;
+/// 进程运行超过这个时间后再退出，不计入连续退出
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// stdio MCP 服务器配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// 服务器名称
+    pub name: String,
+    /// 启动命令
+    pub command: String,
+    /// 命令参数
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 额外的环境变量
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// 工作目录，未设置时使用当前目录
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+impl McpServerConfig {
+    /// 创建服务器配置
+    pub fn new<S: Into<String>>(name: S, command: S) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            args: Vec::new(),
+            env: HashMap::new(),
+            cwd: None,
+        }
+    }
+
+    /// 设置命令参数
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 添加环境变量
+    pub fn with_env<S: Into<String>>(mut self, key: S, value: S) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// 设置工作目录
+    pub fn with_cwd<P: Into<PathBuf>>(mut self, cwd: P) -> Self {
+        self.cwd = Some(cwd.into());
+        self
+    }
+}
+
+/// MCP 服务器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpServerState {
+    /// 正在启动
+    Starting,
+    /// 运行中
+    Running,
+    /// 退出后等待重启
+    Restarting,
+    /// 连续退出次数过多，已放弃
+    Failed,
+}
+
+/// MCP 服务器的运行状况
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct McpServerHealth {
+    /// 服务器名称
+    pub name: String,
+    /// 状态
+    pub state: McpServerState,
+    /// 当前进程 ID
+    pub pid: Option<u32>,
+    /// 累计重启次数
+    pub restarts: u32,
+    /// 当前进程的启动时间
+    pub started_at: Option<DateTime<Utc>>,
+    /// 最近一次退出的原因
+    pub last_exit: Option<String>,
+    /// 最近一次退出的时间
+    pub last_exit_at: Option<DateTime<Utc>>,
+    /// 最近一行 stderr 输出
+    pub last_stderr: Option<String>,
+}
+
+impl McpServerHealth {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: McpServerState::Starting,
+            pid: None,
+            restarts: 0,
+            started_at: None,
+            last_exit: None,
+            last_exit_at: None,
+            last_stderr: None,
+        }
+    }
+}
+
+/// MCP 服务器监督器
+#[derive(Default)]
+pub struct McpSupervisor {
+    tasks: Mutex<JoinSet<()>>,
+}
+
+impl McpSupervisor {
+    /// 启动服务器并监督其运行，运行状况记录到 `telemetry`；需要在 tokio 运行时中调用
+    pub fn start(&self, configs: Vec<McpServerConfig>, telemetry: Arc<ToolTelemetry>) {
+        let mut tasks = self.tasks.lock().unwrap();
+        for config in configs {
+            telemetry.update_server(&config.name, |health| {
+                *health = McpServerHealth::new(&config.name)
+            });
+            tasks.spawn(supervise(config, telemetry.clone()));
+        }
+    }
+
+    /// 停止全部服务器，丢弃任务时结束对应的子进程
+    pub fn shutdown(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        drop(tasks);
+    }
+}
+
+/// 运行服务器进程，退出后按退避时间重启
+async fn supervise(config: McpServerConfig, telemetry: Arc<ToolTelemetry>) {
+    let name = config.name.as_str();
+    let mut crashes = 0;
+    let mut backoff = MIN_RESTART_BACKOFF;
+
+    loop {
+        let started = Instant::now();
+        let exit = match run(&config, &telemetry).await {
+            Ok(status) => format!("进程退出: {}", status),
+            Err(e) => format!("无法运行: {}", e),
+        };
+
+        if started.elapsed() >= STABLE_RUN {
+            crashes = 0;
+            backoff = MIN_RESTART_BACKOFF;
+        }
+        crashes += 1;
+        let gave_up = crashes > MAX_CONSECUTIVE_CRASHES;
+        warn!(
+            "MCP 服务器 {} 退出（连续第 {} 次）: {}",
+            name, crashes, exit
+        );
+        telemetry.update_server(name, |health| {
+            health.state = if gave_up {
+                McpServerState::Failed
+            } else {
+                McpServerState::Restarting
+            };
+            health.pid = None;
+            health.last_exit = Some(exit.clone());
+            health.last_exit_at = Some(Utc::now());
+        });
+        if gave_up {
+            error!("MCP 服务器 {} 连续退出次数过多，不再重启", name);
+            telemetry.emit(ToolEvent::McpServerFailed {
+                server: name.to_string(),
+                last_exit: exit,
+            });
+            return;
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+        info!("重启 MCP 服务器 {}", name);
+        telemetry.update_server(name, |health| {
+            health.state = McpServerState::Starting;
+            health.restarts += 1;
+        });
+    }
+}
+
+/// 运行一次服务器进程直到退出，stderr 逐行写入日志
+async fn run(config: &McpServerConfig, telemetry: &ToolTelemetry) -> std::io::Result<ExitStatus> {
+    let name = config.name.as_str();
+    let mut command = Command::new(&config.command);
+    command
+        .args(&config.args)
+        .envs(&config.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(cwd) = &config.cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command.spawn()?;
+
+    let pid = child.id();
+    info!("MCP 服务器 {} 已启动，进程 {:?}", name, pid);
+    telemetry.update_server(name, |health| {
+        health.state = McpServerState::Running;
+        health.pid = pid;
+        health.started_at = Some(Utc::now());
+    });
+
+    let _stdin = child.stdin.take();
+    let _stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let log = async {
+        let Some(stderr) = stderr else { return };
+        let mut lines = BufReader::new(stderr).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            info!(target: "mcp", server = name, "{}", line);
+            telemetry.update_server(name, |health| health.last_stderr = Some(line));
+        }
+    };
+    let (status, ()) = tokio::join!(child.wait(), log);
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolTelemetryConfig;
+
+    #[cfg(unix)]
+    #[tokio::test(start_paused = true)]
+    async fn test_mcp_server_restarts_and_gives_up() {
+        let telemetry = Arc::new(ToolTelemetry::new(ToolTelemetryConfig::default()));
+        let mut events = telemetry.subscribe_events();
+        let supervisor = McpSupervisor::default();
+        let config =
+            McpServerConfig::new("flaky", "sh").with_args(["-c", "echo 缺少令牌 >&2; exit 3"]);
+        supervisor.start(vec![config], telemetry.clone());
+
+        // 等待放弃事件而不是轮询：时钟暂停时轮询会推进时间，使进程看起来运行得足够久
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ToolEvent::McpServerFailed { server, .. } if server == "flaky"
+        ));
+        let health = telemetry.get_server_health();
+        assert_eq!(health[0].state, McpServerState::Failed);
+        assert_eq!(health[0].restarts, MAX_CONSECUTIVE_CRASHES);
+        assert_eq!(health[0].pid, None);
+        assert_eq!(health[0].last_stderr.as_deref(), Some("缺少令牌"));
+        assert!(health[0].last_exit.as_deref().unwrap().contains('3'));
+
+        supervisor.shutdown();
+        let missing = McpServerConfig::new("missing", "/nonexistent/mcp-server");
+        supervisor.start(vec![missing], telemetry.clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let health = telemetry.get_server_health();
+        assert_eq!(health[0].name, "flaky");
+        assert_eq!(health[1].state, McpServerState::Restarting);
+        assert!(
+            health[1]
+                .last_exit
+                .as_deref()
+                .unwrap()
+                .starts_with("无法运行")
+        );
+        supervisor.shutdown();
+    }
+}
@@ -0,0 +1,167 @@
+//! 会话记忆工具 - 让模型读写当前会话的变量，提供简单的长期记忆
+
+use super::{ToolDefinition, groups};
+use crate::error::{AgentError, AgentResult};
+use crate::storage::SessionStore;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// 读取会话变量的工具名称
+pub const MEMORY_GET: &str = "memory_get";
+/// 写入会话变量的工具名称
+pub const MEMORY_SET: &str = "memory_set";
+
+/// 绑定到某个会话的变量读写句柄
+#[derive(Debug, Clone)]
+pub struct SessionMemory {
+    store: Arc<SessionStore>,
+    session_id: String,
+}
+
+impl SessionMemory {
+    /// 创建会话记忆句柄
+    pub fn new<S: Into<String>>(store: Arc<SessionStore>, session_id: S) -> Self {
+        Self {
+            store,
+            session_id: session_id.into(),
+        }
+    }
+
+    /// 会话 ID
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// 获取全部变量
+    pub async fn all(&self) -> AgentResult<BTreeMap<String, String>> {
+        self.store.get_variables(&self.session_id).await
+    }
+
+    /// 获取变量
+    pub async fn get(&self, key: &str) -> AgentResult<Option<String>> {
+        Ok(self.all().await?.remove(key))
+    }
+
+    /// 设置变量
+    pub async fn set(&self, key: &str, value: &str) -> AgentResult<()> {
+        self.store
+            .set_variable(&self.session_id, key, Some(value.to_string()))
+            .await
+            .map(|_| ())
+    }
+
+    /// 移除变量
+    pub async fn remove(&self, key: &str) -> AgentResult<()> {
+        self.store
+            .set_variable(&self.session_id, key, None)
+            .await
+            .map(|_| ())
+    }
+
+    /// 会话记忆工具的定义
+    pub fn tool_definitions() -> Vec<ToolDefinition> {
+        vec![
+            ToolDefinition {
+                name: MEMORY_GET.to_string(),
+                namespace: groups::MEMORY.to_string(),
+                description: "读取当前会话中记住的信息，不指定 key 时返回全部".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "key": {
+                            "type": "string",
+                            "description": "变量名，例如：user_name"
+                        }
+                    }
+                }),
+                required: false,
+            },
+            ToolDefinition {
+                name: MEMORY_SET.to_string(),
+                namespace: groups::MEMORY.to_string(),
+                description: "在当前会话中记住一条信息，省略 value 时忘记该信息".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "key": {
+                            "type": "string",
+                            "description": "变量名，例如：user_name"
+                        },
+                        "value": {
+                            "type": "string",
+                            "description": "要记住的值"
+                        }
+                    },
+                    "required": ["key"]
+                }),
+                required: false,
+            },
+        ]
+    }
+
+    /// 执行会话记忆工具
+    pub(crate) async fn execute(&self, tool_name: &str, arguments: &str) -> AgentResult<String> {
+        let args: serde_json::Value = serde_json::from_str(arguments)?;
+        let key = args["key"]
+            .as_str()
+            .map(str::trim)
+            .filter(|k| !k.is_empty());
+
+        match tool_name {
+            MEMORY_GET => match key {
+                Some(key) => Ok(match self.get(key).await? {
+                    Some(value) => format!("{} = {}", key, value),
+                    None => format!("没有记住 {}", key),
+                }),
+                None => Ok(serde_json::to_string(&self.all().await?)?),
+            },
+            MEMORY_SET => {
+                let key = key.ok_or_else(|| AgentError::tool("缺少 key 参数"))?;
+                match args["value"].as_str() {
+                    Some(value) => {
+                        self.set(key, value).await?;
+                        Ok(format!("已记住 {} = {}", key, value))
+                    }
+                    None => {
+                        self.remove(key).await?;
+                        Ok(format!("已忘记 {}", key))
+                    }
+                }
+            }
+            _ => Err(AgentError::tool(format!("未知工具: {}", tool_name))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::ChatSession;
+
+    #[tokio::test]
+    async fn test_memory_tools() {
+        let store = Arc::new(SessionStore::open_in_memory().unwrap());
+        let session = store
+            .create_session(ChatSession::new("会话".to_string(), "gpt-4".to_string()))
+            .await
+            .unwrap();
+        let memory = SessionMemory::new(store, session.id);
+
+        memory
+            .execute(MEMORY_SET, r#"{"key": "user_name", "value": "小明"}"#)
+            .await
+            .unwrap();
+        let result = memory
+            .execute(MEMORY_GET, r#"{"key": "user_name"}"#)
+            .await
+            .unwrap();
+        assert_eq!(result, "user_name = 小明");
+
+        memory
+            .execute(MEMORY_SET, r#"{"key": "user_name"}"#)
+            .await
+            .unwrap();
+        assert!(memory.all().await.unwrap().is_empty());
+        assert!(memory.execute(MEMORY_SET, "{}").await.is_err());
+    }
+}
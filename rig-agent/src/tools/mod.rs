@@ -0,0 +1,880 @@
+//! Agent 工具模块
+
+pub mod directory;
+pub mod mcp;
+pub mod memory;
+pub mod permissions;
+pub mod rig_bridge;
+pub mod state;
+pub mod stats;
+pub mod weather;
+pub mod web_search;
+
+use crate::core::types::{ToolCall, ToolResult, ToolSelection};
+use crate::error::{AgentError, AgentResult};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+pub use directory::{
+    ToolFileChange, ToolsDirectory, ToolsDirectoryConfig, WebhookTool, WebhookToolSpec,
+    spawn_tools_watcher,
+};
+pub use mcp::{McpServerConfig, McpServerHealth, McpServerState, McpSupervisor};
+pub use memory::SessionMemory;
+pub use permissions::{PermissionDecision, PermissionGrant, PermissionStore};
+pub use rig_bridge::ManagedTool;
+pub use state::{ToolStateStore, ToolStateValue, tool_state};
+pub use stats::{ToolErrorRecord, ToolEvent, ToolStats, ToolTelemetry, ToolTelemetryConfig};
+pub use weather::{
+    CachedWeatherProvider, OpenMeteoProvider, TemperatureUnit, WeatherConfig, WeatherProvider,
+    WeatherReport,
+};
+pub use web_search::{SearchEngine, SearchResult, WebSearchConfig};
+
+/// 内置工具组（命名空间）
+pub mod groups {
+    /// 数学计算
+    pub const MATH: &str = "math";
+    /// 时间日期
+    pub const TIME: &str = "time";
+    /// 网络访问
+    pub const NET: &str = "net";
+    /// 文件系统
+    pub const FS: &str = "fs";
+    /// 会话记忆
+    pub const MEMORY: &str = "memory";
+    /// 未指定命名空间的自定义工具
+    pub const CUSTOM: &str = "custom";
+}
+
+/// 工具定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    /// 工具名称
+    pub name: String,
+    /// 工具命名空间（工具组）
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// 工具描述
+    pub description: String,
+    /// 参数定义（JSON Schema）
+    pub parameters: serde_json::Value,
+    /// 是否必需
+    pub required: bool,
+}
+
+fn default_namespace() -> String {
+    groups::CUSTOM.to_string()
+}
+
+impl ToolDefinition {
+    /// 命名空间限定名，例如 `net.weather`
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    /// 检查工具是否被选择规则选中
+    pub fn is_selected(&self, selection: &ToolSelection) -> bool {
+        selection.allows(&self.name, &self.namespace)
+    }
+}
+
+/// 内置工具集合
+pub struct BuiltinTools {
+    tools: HashMap<String, ToolDefinition>,
+    /// 天气数据提供者
+    weather_provider: Arc<dyn WeatherProvider>,
+    /// 天气工具配置
+    weather_config: WeatherConfig,
+    /// 网络搜索引擎（未配置时不提供 web_search 工具）
+    search_engine: Option<Arc<dyn SearchEngine>>,
+    /// 网络搜索配置
+    search_config: Option<WebSearchConfig>,
+}
+
+impl BuiltinTools {
+    /// 创建内置工具集合（天气使用带缓存的 Open-Meteo）
+    ///
+    /// 如果环境变量中配置了搜索引擎，同时启用 web_search 工具
+    pub fn new() -> Self {
+        let tools = Self::with_weather_config(WeatherConfig::default());
+
+        if let Some(config) = WebSearchConfig::from_env() {
+            match config.build_engine() {
+                Ok(engine) => return tools.with_search_engine(engine, config),
+                Err(e) => warn!("网络搜索工具初始化失败: {}", e),
+            }
+        }
+
+        tools
+    }
+
+    /// 使用指定天气配置创建内置工具集合
+    pub fn with_weather_config(weather_config: WeatherConfig) -> Self {
+        let provider = Arc::new(CachedWeatherProvider::new(
+            Arc::new(OpenMeteoProvider::new()),
+            weather_config.cache_ttl,
+            weather_config.cache_capacity,
+        ));
+        Self::with_weather_provider(provider, weather_config)
+    }
+
+    /// 使用自定义天气提供者创建内置工具集合
+    ///
+    /// 提供者按原样使用，如需缓存请自行包装为 [`CachedWeatherProvider`]
+    pub fn with_weather_provider(
+        weather_provider: Arc<dyn WeatherProvider>,
+        weather_config: WeatherConfig,
+    ) -> Self {
+        let mut tools = HashMap::new();
+
+        // 添加计算器工具
+        tools.insert(
+            "calculator".to_string(),
+            ToolDefinition {
+                name: "calculator".to_string(),
+                namespace: groups::MATH.to_string(),
+                description: "执行基本的数学计算".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "expression": {
+                            "type": "string",
+                            "description": "要计算的数学表达式，例如：2+3*4"
+                        }
+                    },
+                    "required": ["expression"]
+                }),
+                required: false,
+            },
+        );
+
+        // 添加时间工具
+        tools.insert(
+            "current_time".to_string(),
+            ToolDefinition {
+                name: "current_time".to_string(),
+                namespace: groups::TIME.to_string(),
+                description: "获取当前时间".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "timezone": {
+                            "type": "string",
+                            "description": "时区，例如：Asia/Shanghai",
+                            "default": "UTC"
+                        }
+                    }
+                }),
+                required: false,
+            },
+        );
+
+        // 添加天气工具
+        tools.insert(
+            "weather".to_string(),
+            ToolDefinition {
+                name: "weather".to_string(),
+                namespace: groups::NET.to_string(),
+                description: "获取指定城市的实时天气信息（温度、体感温度、湿度、风速和天气状况）"
+                    .to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "city": {
+                            "type": "string",
+                            "description": "城市名称，例如：北京"
+                        },
+                        "unit": {
+                            "type": "string",
+                            "enum": ["celsius", "fahrenheit"],
+                            "description": "温度单位：celsius 或 fahrenheit",
+                            "default": weather_config.default_unit.to_string()
+                        }
+                    },
+                    "required": ["city"]
+                }),
+                required: false,
+            },
+        );
+
+        Self {
+            tools,
+            weather_provider,
+            weather_config,
+            search_engine: None,
+            search_config: None,
+        }
+    }
+
+    /// 根据配置启用 web_search 工具
+    pub fn with_web_search(self, config: WebSearchConfig) -> AgentResult<Self> {
+        let engine = config.build_engine()?;
+        Ok(self.with_search_engine(engine, config))
+    }
+
+    /// 使用自定义搜索引擎启用 web_search 工具
+    pub fn with_search_engine(
+        mut self,
+        engine: Arc<dyn SearchEngine>,
+        config: WebSearchConfig,
+    ) -> Self {
+        self.tools.insert(
+            "web_search".to_string(),
+            ToolDefinition {
+                name: "web_search".to_string(),
+                namespace: groups::NET.to_string(),
+                description: "搜索互联网获取最新信息，返回标题、链接和摘要".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "搜索关键词"
+                        },
+                        "count": {
+                            "type": "integer",
+                            "description": "返回结果数量",
+                            "minimum": 1,
+                            "maximum": config.max_limit.max(1),
+                            "default": config.default_limit
+                        }
+                    },
+                    "required": ["query"]
+                }),
+                required: false,
+            },
+        );
+        self.search_engine = Some(engine);
+        self.search_config = Some(config);
+        self
+    }
+
+    /// 获取天气提供者
+    pub fn weather_provider(&self) -> &Arc<dyn WeatherProvider> {
+        &self.weather_provider
+    }
+
+    /// 获取所有工具定义
+    pub fn get_all_tools(&self) -> Vec<ToolDefinition> {
+        self.tools.values().cloned().collect()
+    }
+
+    /// 获取指定工具定义
+    pub fn get_tool(&self, name: &str) -> Option<&ToolDefinition> {
+        self.tools.get(name)
+    }
+
+    /// 执行工具
+    pub async fn execute_tool(&self, tool_call: &ToolCall) -> AgentResult<ToolResult> {
+        let start_time = std::time::Instant::now();
+
+        let result = match tool_call.name.as_str() {
+            "calculator" => self.execute_calculator(tool_call).await,
+            "current_time" => self.execute_current_time(tool_call).await,
+            "weather" => self.execute_weather(tool_call).await,
+            "web_search" => self.execute_web_search(tool_call).await,
+            _ => Err(AgentError::tool(format!("未知工具: {}", tool_call.name))),
+        };
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(result_content) => Ok(ToolResult {
+                call_id: tool_call.id.clone(),
+                tool_name: tool_call.name.clone(),
+                result: result_content,
+                success: true,
+                error: None,
+                timestamp: Utc::now(),
+                duration_ms,
+            }),
+            Err(error) => Ok(ToolResult {
+                call_id: tool_call.id.clone(),
+                tool_name: tool_call.name.clone(),
+                result: "".to_string(),
+                success: false,
+                error: Some(error.to_string()),
+                timestamp: Utc::now(),
+                duration_ms,
+            }),
+        }
+    }
+
+    /// 执行计算器工具
+    async fn execute_calculator(&self, tool_call: &ToolCall) -> AgentResult<String> {
+        let args: serde_json::Value = serde_json::from_str(&tool_call.arguments)?;
+        let expression = args["expression"]
+            .as_str()
+            .ok_or_else(|| AgentError::tool("缺少 expression 参数"))?;
+
+        // 简单的数学表达式计算（实际应用中可以使用更强大的表达式解析器）
+        let result = self.evaluate_expression(expression)?;
+        Ok(format!("{} = {}", expression, result))
+    }
+
+    /// 执行当前时间工具
+    async fn execute_current_time(&self, tool_call: &ToolCall) -> AgentResult<String> {
+        let args: serde_json::Value =
+            serde_json::from_str(&tool_call.arguments).unwrap_or_else(|_| serde_json::json!({}));
+
+        let timezone = args["timezone"].as_str().unwrap_or("UTC");
+
+        let now = Utc::now();
+        let formatted_time = now.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+
+        Ok(format!("当前时间（{}）: {}", timezone, formatted_time))
+    }
+
+    /// 执行天气工具
+    async fn execute_weather(&self, tool_call: &ToolCall) -> AgentResult<String> {
+        let args: serde_json::Value = serde_json::from_str(&tool_call.arguments)?;
+        let city = args["city"]
+            .as_str()
+            .filter(|c| !c.trim().is_empty())
+            .ok_or_else(|| AgentError::tool("缺少 city 参数"))?;
+        let unit = match args["unit"].as_str() {
+            Some(unit) => unit.parse::<TemperatureUnit>()?,
+            None => self.weather_config.default_unit,
+        };
+
+        let report = self.weather_provider.current_weather(city, unit).await?;
+        Ok(report.to_text())
+    }
+
+    /// 执行网络搜索工具
+    async fn execute_web_search(&self, tool_call: &ToolCall) -> AgentResult<String> {
+        let (engine, config) = match (&self.search_engine, &self.search_config) {
+            (Some(engine), Some(config)) => (engine, config),
+            _ => return Err(AgentError::tool("未配置网络搜索引擎")),
+        };
+
+        let args: serde_json::Value = serde_json::from_str(&tool_call.arguments)?;
+        let query = args["query"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| AgentError::tool("缺少 query 参数"))?;
+        let limit = config.clamp_limit(args["count"].as_u64());
+
+        let results = engine.search(query, limit).await?;
+        Ok(web_search::format_results(query, &results))
+    }
+
+    /// 简单的数学表达式计算
+    fn evaluate_expression(&self, expression: &str) -> AgentResult<f64> {
+        // 这是一个非常简单的实现，实际应用中应该使用专门的表达式解析器
+        let cleaned = expression.replace(" ", "");
+
+        // 支持基本的四则运算
+        if let Some(pos) = cleaned.find('+') {
+            let (left, right) = cleaned.split_at(pos);
+            let right = &right[1..];
+            let left_val = self.evaluate_expression(left)?;
+            let right_val = self.evaluate_expression(right)?;
+            return Ok(left_val + right_val);
+        }
+
+        if let Some(pos) = cleaned.rfind('-')
+            && pos > 0
+        {
+            let (left, right) = cleaned.split_at(pos);
+            let right = &right[1..];
+            let left_val = self.evaluate_expression(left)?;
+            let right_val = self.evaluate_expression(right)?;
+            return Ok(left_val - right_val);
+        }
+
+        if let Some(pos) = cleaned.rfind('*') {
+            let (left, right) = cleaned.split_at(pos);
+            let right = &right[1..];
+            let left_val = self.evaluate_expression(left)?;
+            let right_val = self.evaluate_expression(right)?;
+            return Ok(left_val * right_val);
+        }
+
+        if let Some(pos) = cleaned.rfind('/') {
+            let (left, right) = cleaned.split_at(pos);
+            let right = &right[1..];
+            let left_val = self.evaluate_expression(left)?;
+            let right_val = self.evaluate_expression(right)?;
+            if right_val == 0.0 {
+                return Err(AgentError::tool("除零错误"));
+            }
+            return Ok(left_val / right_val);
+        }
+
+        // 解析数字
+        cleaned
+            .parse::<f64>()
+            .map_err(|_| AgentError::tool(format!("无法解析表达式: {}", expression)))
+    }
+}
+
+impl Default for BuiltinTools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 自定义工具特征
+#[async_trait::async_trait]
+pub trait CustomTool: Send + Sync {
+    /// 工具名称
+    fn name(&self) -> &str;
+
+    /// 工具命名空间（工具组），默认为 `custom`
+    fn namespace(&self) -> &str {
+        groups::CUSTOM
+    }
+
+    /// 工具描述
+    fn description(&self) -> &str;
+
+    /// 参数定义
+    fn parameters(&self) -> serde_json::Value;
+
+    /// 执行工具，在对话中调用时可以通过 [`RequestContext::current`](crate::core::RequestContext::current) 取得调用方信息，
+    /// 通过 [`tool_state`] 取得当前会话的状态
+    async fn execute(&self, arguments: &str) -> AgentResult<String>;
+
+    /// 工具在会话中首次被调用前初始化会话状态，无状态的工具返回 `None`（默认）
+    async fn init_state(&self, _conversation_id: &str) -> AgentResult<Option<ToolStateValue>> {
+        Ok(None)
+    }
+
+    /// 会话结束时释放会话状态，例如关闭浏览器会话或数据库游标
+    async fn dispose_state(&self, _conversation_id: &str, _state: ToolStateValue) {}
+}
+
+/// 工具管理器
+pub struct ToolManager {
+    builtin_tools: Arc<BuiltinTools>,
+    custom_tools: HashMap<String, Arc<dyn CustomTool>>,
+    telemetry: Arc<ToolTelemetry>,
+    permissions: Arc<PermissionStore>,
+    states: Arc<ToolStateStore>,
+    mcp: McpSupervisor,
+}
+
+impl ToolManager {
+    /// 创建工具管理器
+    pub fn new() -> Self {
+        Self::with_builtin_tools(BuiltinTools::new())
+    }
+
+    /// 使用自定义内置工具集合创建工具管理器
+    pub fn with_builtin_tools(builtin_tools: BuiltinTools) -> Self {
+        Self {
+            builtin_tools: Arc::new(builtin_tools),
+            custom_tools: HashMap::new(),
+            telemetry: Arc::new(ToolTelemetry::default()),
+            permissions: Arc::new(PermissionStore::in_memory()),
+            states: Arc::default(),
+            mcp: McpSupervisor::default(),
+        }
+    }
+
+    /// 设置工具授权存储（例如从文件加载的持久化授权）
+    pub fn with_permission_store(mut self, store: PermissionStore) -> Self {
+        self.permissions = Arc::new(store);
+        self
+    }
+
+    /// 替换工具授权存储
+    pub fn set_permission_store(&mut self, store: PermissionStore) {
+        self.permissions = Arc::new(store);
+    }
+
+    /// 获取工具授权存储
+    pub fn permissions(&self) -> &Arc<PermissionStore> {
+        &self.permissions
+    }
+
+    /// 获取会话级工具状态存储
+    pub fn tool_states(&self) -> &Arc<ToolStateStore> {
+        &self.states
+    }
+
+    /// 设置工具统计配置（会清空已有统计）
+    pub fn with_telemetry_config(mut self, config: ToolTelemetryConfig) -> Self {
+        self.telemetry = Arc::new(ToolTelemetry::new(config));
+        self
+    }
+
+    /// 获取所有工具的调用统计
+    pub fn get_tool_stats(&self) -> Vec<ToolStats> {
+        self.telemetry.get_all_stats()
+    }
+
+    /// 获取单个工具的调用统计
+    pub fn get_tool_stats_for(&self, name: &str) -> Option<ToolStats> {
+        self.telemetry.get_stats(name)
+    }
+
+    /// 清空工具调用统计
+    pub fn reset_tool_stats(&self) {
+        self.telemetry.reset();
+    }
+
+    /// 启动 stdio MCP 服务器并监督其运行，需要在 tokio 运行时中调用
+    pub fn start_mcp_servers(&self, configs: Vec<McpServerConfig>) {
+        self.mcp.start(configs, self.telemetry.clone());
+    }
+
+    /// 停止全部 MCP 服务器
+    pub fn stop_mcp_servers(&self) {
+        self.mcp.shutdown();
+        self.telemetry.clear_servers();
+    }
+
+    /// 获取各 MCP 服务器的运行状况
+    pub fn get_mcp_server_health(&self) -> Vec<McpServerHealth> {
+        self.telemetry.get_server_health()
+    }
+
+    /// 订阅工具事件（如连续失败告警）
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ToolEvent> {
+        self.telemetry.subscribe_events()
+    }
+
+    /// 添加自定义工具
+    pub fn add_custom_tool(&mut self, tool: Box<dyn CustomTool>) {
+        let name = tool.name().to_string();
+        self.custom_tools.insert(name, Arc::from(tool));
+    }
+
+    /// 移除自定义工具
+    pub fn remove_custom_tool(&mut self, name: &str) -> bool {
+        self.custom_tools.remove(name).is_some()
+    }
+
+    /// 获取所有工具定义
+    pub fn get_all_tool_definitions(&self) -> Vec<ToolDefinition> {
+        let mut tools = self.builtin_tools.get_all_tools();
+
+        for custom_tool in self.custom_tools.values() {
+            tools.push(ToolDefinition {
+                name: custom_tool.name().to_string(),
+                namespace: custom_tool.namespace().to_string(),
+                description: custom_tool.description().to_string(),
+                parameters: custom_tool.parameters(),
+                required: false,
+            });
+        }
+
+        tools
+    }
+
+    /// 获取被选择规则选中的工具定义
+    pub fn get_tool_definitions(&self, selection: &ToolSelection) -> Vec<ToolDefinition> {
+        self.get_all_tool_definitions()
+            .into_iter()
+            .filter(|tool| tool.is_selected(selection))
+            .collect()
+    }
+
+    /// 获取所有工具组（命名空间）及其工具名称
+    pub fn get_tool_groups(&self) -> HashMap<String, Vec<String>> {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for tool in self.get_all_tool_definitions() {
+            groups.entry(tool.namespace).or_default().push(tool.name);
+        }
+        for names in groups.values_mut() {
+            names.sort();
+        }
+        groups
+    }
+
+    /// 获取被选择规则选中、可直接挂载到 rig Agent 的工具
+    pub fn get_rig_tools(&self, selection: &ToolSelection) -> Vec<ManagedTool> {
+        let mut tools: Vec<ManagedTool> = self
+            .builtin_tools
+            .get_all_tools()
+            .into_iter()
+            .filter(|tool| tool.is_selected(selection))
+            .map(|tool| {
+                ManagedTool::builtin(tool, self.builtin_tools.clone(), self.telemetry.clone())
+            })
+            .collect();
+
+        for custom_tool in self.custom_tools.values() {
+            let definition = ToolDefinition {
+                name: custom_tool.name().to_string(),
+                namespace: custom_tool.namespace().to_string(),
+                description: custom_tool.description().to_string(),
+                parameters: custom_tool.parameters(),
+                required: false,
+            };
+            if definition.is_selected(selection) {
+                tools.push(ManagedTool::custom(
+                    definition,
+                    custom_tool.clone(),
+                    self.telemetry.clone(),
+                ));
+            }
+        }
+
+        tools
+    }
+
+    /// 获取指定 Agent 可挂载的工具，已被授权拒绝的工具不会暴露给模型
+    pub fn get_agent_rig_tools(
+        &self,
+        agent_id: &str,
+        selection: &ToolSelection,
+    ) -> Vec<ManagedTool> {
+        self.get_rig_tools(selection)
+            .into_iter()
+            .filter(|tool| {
+                !self
+                    .permissions
+                    .is_denied(agent_id, &tool.definition().name)
+            })
+            .collect()
+    }
+
+    /// 获取绑定到会话的记忆工具，同样受选择规则与授权约束
+    pub fn get_memory_rig_tools(
+        &self,
+        agent_id: &str,
+        selection: &ToolSelection,
+        memory: SessionMemory,
+    ) -> Vec<ManagedTool> {
+        SessionMemory::tool_definitions()
+            .into_iter()
+            .filter(|tool| tool.is_selected(selection))
+            .filter(|tool| !self.permissions.is_denied(agent_id, &tool.name))
+            .map(|tool| ManagedTool::memory(tool, memory.clone(), self.telemetry.clone()))
+            .collect()
+    }
+
+    /// 检查工具是否存在且被选择规则允许
+    pub fn is_tool_allowed(&self, name: &str, selection: &ToolSelection) -> bool {
+        self.get_all_tool_definitions()
+            .iter()
+            .any(|tool| tool.name == name && tool.is_selected(selection))
+    }
+
+    /// 在选择规则约束下执行工具
+    pub async fn execute_selected_tool(
+        &self,
+        tool_call: &ToolCall,
+        selection: &ToolSelection,
+    ) -> AgentResult<ToolResult> {
+        if !self.is_tool_allowed(&tool_call.name, selection) {
+            return Err(AgentError::permission(format!(
+                "工具 {} 未对当前 Agent 开放",
+                tool_call.name
+            )));
+        }
+        self.execute_tool(tool_call).await
+    }
+
+    /// 以指定 Agent 的身份执行工具，先检查选择规则，再检查已有授权
+    pub async fn execute_agent_tool(
+        &self,
+        agent_id: &str,
+        tool_call: &ToolCall,
+        selection: &ToolSelection,
+    ) -> AgentResult<ToolResult> {
+        if self.permissions.is_denied(agent_id, &tool_call.name) {
+            return Err(AgentError::permission(format!(
+                "工具 {} 已被禁止用于 Agent {}",
+                tool_call.name, agent_id
+            )));
+        }
+        self.execute_selected_tool(tool_call, selection).await
+    }
+
+    /// 获取可用工具名称列表
+    pub fn get_available_tools(&self) -> Vec<String> {
+        let mut tools = Vec::new();
+
+        // 添加内置工具名称
+        for tool_def in self.builtin_tools.get_all_tools() {
+            tools.push(tool_def.name.clone());
+        }
+
+        // 添加自定义工具名称
+        for custom_tool in self.custom_tools.values() {
+            tools.push(custom_tool.name().to_string());
+        }
+
+        tools
+    }
+
+    /// 执行工具
+    pub async fn execute_tool(&self, tool_call: &ToolCall) -> AgentResult<ToolResult> {
+        let result = self.execute_tool_inner(tool_call).await;
+        // 未知工具不计入统计
+        if let Ok(tool_result) = &result {
+            self.telemetry.record(
+                &tool_result.tool_name,
+                tool_result.duration_ms,
+                tool_result.error.as_deref(),
+            );
+        }
+        result
+    }
+
+    async fn execute_tool_inner(&self, tool_call: &ToolCall) -> AgentResult<ToolResult> {
+        // 先尝试内置工具
+        if self.builtin_tools.get_tool(&tool_call.name).is_some() {
+            return self.builtin_tools.execute_tool(tool_call).await;
+        }
+
+        // 再尝试自定义工具
+        if let Some(custom_tool) = self.custom_tools.get(&tool_call.name) {
+            let start_time = std::time::Instant::now();
+
+            let result = custom_tool.execute(&tool_call.arguments).await;
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+
+            return match result {
+                Ok(result_content) => Ok(ToolResult {
+                    call_id: tool_call.id.clone(),
+                    tool_name: tool_call.name.clone(),
+                    result: result_content,
+                    success: true,
+                    error: None,
+                    timestamp: Utc::now(),
+                    duration_ms,
+                }),
+                Err(error) => Ok(ToolResult {
+                    call_id: tool_call.id.clone(),
+                    tool_name: tool_call.name.clone(),
+                    result: "".to_string(),
+                    success: false,
+                    error: Some(error.to_string()),
+                    timestamp: Utc::now(),
+                    duration_ms,
+                }),
+            };
+        }
+
+        Err(AgentError::tool(format!("未找到工具: {}", tool_call.name)))
+    }
+
+    /// 检查工具是否存在
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.builtin_tools.get_tool(name).is_some() || self.custom_tools.contains_key(name)
+    }
+}
+
+impl Default for ToolManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_tools_creation() {
+        let tools = BuiltinTools::new();
+        let all_tools = tools.get_all_tools();
+        assert!(!all_tools.is_empty());
+        assert!(tools.get_tool("calculator").is_some());
+        assert!(tools.get_tool("current_time").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_calculator_tool() {
+        let tools = BuiltinTools::new();
+        let tool_call = ToolCall {
+            id: "test_call".to_string(),
+            name: "calculator".to_string(),
+            arguments: r#"{"expression": "2+3"}"#.to_string(),
+            timestamp: Utc::now(),
+        };
+
+        let result = tools.execute_tool(&tool_call).await.unwrap();
+        assert!(result.success);
+        assert!(result.result.contains("5"));
+    }
+
+    #[tokio::test]
+    async fn test_current_time_tool() {
+        let tools = BuiltinTools::new();
+        let tool_call = ToolCall {
+            id: "test_call".to_string(),
+            name: "current_time".to_string(),
+            arguments: "{}".to_string(),
+            timestamp: Utc::now(),
+        };
+
+        let result = tools.execute_tool(&tool_call).await.unwrap();
+        assert!(result.success);
+        assert!(result.result.contains("当前时间"));
+    }
+
+    #[test]
+    fn test_tool_selection_filters_definitions() {
+        let manager = ToolManager::new();
+
+        let math_only = ToolSelection::only(["math"]);
+        let names: Vec<String> = manager
+            .get_tool_definitions(&math_only)
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        assert_eq!(names, vec!["calculator".to_string()]);
+        assert!(manager.is_tool_allowed("calculator", &math_only));
+        assert!(!manager.is_tool_allowed("current_time", &math_only));
+
+        let no_net = ToolSelection::all().with_deny("net");
+        assert!(
+            manager
+                .get_tool_definitions(&no_net)
+                .iter()
+                .all(|tool| tool.namespace != groups::NET)
+        );
+        assert_eq!(
+            manager.get_rig_tools(&no_net).len(),
+            manager.get_tool_definitions(&no_net).len()
+        );
+
+        let tool_groups = manager.get_tool_groups();
+        assert_eq!(
+            tool_groups.get("time"),
+            Some(&vec!["current_time".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_stats_recorded() {
+        let manager = ToolManager::new();
+        for expression in ["1+1", "not a number"] {
+            let tool_call = ToolCall {
+                id: "stats_call".to_string(),
+                name: "calculator".to_string(),
+                arguments: format!(r#"{{"expression": "{}"}}"#, expression),
+                timestamp: Utc::now(),
+            };
+            manager.execute_tool(&tool_call).await.unwrap();
+        }
+
+        let stats = manager.get_tool_stats_for("calculator").unwrap();
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.successes, 1);
+        assert_eq!(stats.recent_errors.len(), 1);
+        assert_eq!(manager.get_tool_stats().len(), 1);
+    }
+
+    #[test]
+    fn test_expression_evaluation() {
+        let tools = BuiltinTools::new();
+        assert_eq!(tools.evaluate_expression("5").unwrap(), 5.0);
+        assert_eq!(tools.evaluate_expression("2+3").unwrap(), 5.0);
+        assert_eq!(tools.evaluate_expression("10-4").unwrap(), 6.0);
+        assert_eq!(tools.evaluate_expression("3*4").unwrap(), 12.0);
+        assert_eq!(tools.evaluate_expression("8/2").unwrap(), 4.0);
+    }
+}
@@ -0,0 +1,197 @@
+//! 工具权限授权 - 按 Agent / 工具持久化的“始终允许 / 始终拒绝”决定
+//!
+//! 调用工具前先查询已有授权：命中“拒绝”时直接拒绝，命中“允许”时直接执行，
+//! 没有授权时返回 `None`，由调用方决定是否向用户确认。
+//! 未指定 Agent 的授权对所有 Agent 生效，指定 Agent 的授权优先。
+
+use crate::error::AgentResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use tracing::info;
+
+/// 授权决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionDecision {
+    /// 始终允许
+    Allow,
+    /// 始终拒绝
+    Deny,
+}
+
+/// 一条工具授权
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionGrant {
+    /// Agent ID，为空时对所有 Agent 生效
+    pub agent_id: Option<String>,
+    /// 工具名称
+    pub tool_name: String,
+    /// 授权决定
+    pub decision: PermissionDecision,
+    /// 授权时间
+    pub granted_at: DateTime<Utc>,
+}
+
+type GrantKey = (Option<String>, String);
+
+/// 工具授权存储，设置了文件路径时每次修改后写回 JSON 文件
+#[derive(Debug, Default)]
+pub struct PermissionStore {
+    path: Option<PathBuf>,
+    grants: Mutex<BTreeMap<GrantKey, PermissionGrant>>,
+}
+
+impl PermissionStore {
+    /// 创建仅保存在内存中的授权存储
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// 从 JSON 文件加载授权，文件不存在时从空白开始
+    pub fn open<P: AsRef<Path>>(path: P) -> AgentResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let grants = if path.exists() {
+            let list: Vec<PermissionGrant> = serde_json::from_slice(&fs::read(&path)?)?;
+            list.into_iter()
+                .map(|grant| ((grant.agent_id.clone(), grant.tool_name.clone()), grant))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            grants: Mutex::new(grants),
+        })
+    }
+
+    /// 记录授权，同一 Agent / 工具已有授权时覆盖
+    pub fn grant(
+        &self,
+        agent_id: Option<&str>,
+        tool_name: &str,
+        decision: PermissionDecision,
+    ) -> AgentResult<PermissionGrant> {
+        let grant = PermissionGrant {
+            agent_id: agent_id.map(str::to_string),
+            tool_name: tool_name.to_string(),
+            decision,
+            granted_at: Utc::now(),
+        };
+
+        let mut grants = self.lock();
+        grants.insert(
+            (grant.agent_id.clone(), grant.tool_name.clone()),
+            grant.clone(),
+        );
+        self.save(&grants)?;
+        info!(
+            "工具授权: {} -> {} = {:?}",
+            agent_id.unwrap_or("*"),
+            tool_name,
+            decision
+        );
+        Ok(grant)
+    }
+
+    /// 撤销授权，返回是否存在该授权
+    pub fn revoke(&self, agent_id: Option<&str>, tool_name: &str) -> AgentResult<bool> {
+        let mut grants = self.lock();
+        let removed = grants
+            .remove(&(agent_id.map(str::to_string), tool_name.to_string()))
+            .is_some();
+        if removed {
+            self.save(&grants)?;
+        }
+        Ok(removed)
+    }
+
+    /// 列出授权；指定 Agent 时返回该 Agent 的授权以及全局授权
+    pub fn list(&self, agent_id: Option<&str>) -> Vec<PermissionGrant> {
+        self.lock()
+            .values()
+            .filter(|grant| {
+                agent_id.is_none_or(|id| grant.agent_id.as_deref().is_none_or(|g| g == id))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 查询 Agent 调用工具的授权决定，没有授权时返回 `None`
+    pub fn check(&self, agent_id: &str, tool_name: &str) -> Option<PermissionDecision> {
+        let grants = self.lock();
+        grants
+            .get(&(Some(agent_id.to_string()), tool_name.to_string()))
+            .or_else(|| grants.get(&(None, tool_name.to_string())))
+            .map(|grant| grant.decision)
+    }
+
+    /// 工具是否被明确拒绝
+    pub fn is_denied(&self, agent_id: &str, tool_name: &str) -> bool {
+        self.check(agent_id, tool_name) == Some(PermissionDecision::Deny)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<GrantKey, PermissionGrant>> {
+        self.grants.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn save(&self, grants: &BTreeMap<GrantKey, PermissionGrant>) -> AgentResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let list: Vec<&PermissionGrant> = grants.values().collect();
+        fs::write(path, serde_json::to_vec_pretty(&list)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grants_persist_and_agent_overrides_global() {
+        let path = std::env::temp_dir().join(format!(
+            "rig-agent-permissions-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        let store = PermissionStore::open(&path).unwrap();
+        store
+            .grant(None, "shell", PermissionDecision::Deny)
+            .unwrap();
+        store
+            .grant(Some("agent_x"), "calculator", PermissionDecision::Allow)
+            .unwrap();
+        store
+            .grant(Some("agent_x"), "shell", PermissionDecision::Allow)
+            .unwrap();
+
+        let reloaded = PermissionStore::open(&path).unwrap();
+        assert_eq!(
+            reloaded.check("agent_x", "calculator"),
+            Some(PermissionDecision::Allow)
+        );
+        assert_eq!(reloaded.check("agent_y", "calculator"), None);
+        assert!(reloaded.is_denied("agent_y", "shell"));
+        assert!(!reloaded.is_denied("agent_x", "shell"));
+        assert_eq!(reloaded.list(Some("agent_y")).len(), 1);
+        assert_eq!(reloaded.list(None).len(), 3);
+
+        assert!(reloaded.revoke(Some("agent_x"), "shell").unwrap());
+        assert!(!reloaded.revoke(Some("agent_x"), "shell").unwrap());
+        assert!(
+            PermissionStore::open(&path)
+                .unwrap()
+                .is_denied("agent_x", "shell")
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! 工具桥接 - 将 ToolManager 中被选中的工具暴露给 rig Agent
+//!
+//! rig 的 `Tool` 特征要求静态类型，这里用一个统一的包装类型承载
+//! 任意内置或自定义工具，并覆盖 `name()` 以返回真实的工具名称。
+//! 挂载了调用记录时，每次调用的参数与输出会被记录下来，用于生成回答对工具输出的引用。
+//! 绑定了会话时，自定义工具在该会话的状态作用域中执行。
+
+use super::{
+    BuiltinTools, CustomTool, SessionMemory, ToolDefinition, ToolStateStore, ToolTelemetry, state,
+};
+use crate::core::citation::{ToolOutput, ToolProvenance};
+use crate::core::context::RequestContext;
+use crate::core::types::ToolCall;
+use crate::error::{AgentError, AgentResult};
+use chrono::Utc;
+use rig::{completion::ToolDefinition as RigToolDefinition, tool::Tool};
+use std::sync::Arc;
+use sync_wrapper::SyncFuture;
+
+/// 工具执行者
+#[derive(Clone)]
+enum ToolExecutor {
+    Builtin(Arc<BuiltinTools>),
+    Custom(Arc<dyn CustomTool>),
+    Memory(SessionMemory),
+}
+
+/// 交给 rig Agent 的托管工具
+#[derive(Clone)]
+pub struct ManagedTool {
+    definition: ToolDefinition,
+    executor: ToolExecutor,
+    telemetry: Arc<ToolTelemetry>,
+    provenance: Option<ToolProvenance>,
+    /// 会话级状态存储与会话 ID
+    state: Option<(Arc<ToolStateStore>, String)>,
+}
+
+impl ManagedTool {
+    /// 包装内置工具
+    pub(crate) fn builtin(
+        definition: ToolDefinition,
+        tools: Arc<BuiltinTools>,
+        telemetry: Arc<ToolTelemetry>,
+    ) -> Self {
+        Self {
+            definition,
+            executor: ToolExecutor::Builtin(tools),
+            telemetry,
+            provenance: None,
+            state: None,
+        }
+    }
+
+    /// 包装自定义工具
+    pub(crate) fn custom(
+        definition: ToolDefinition,
+        tool: Arc<dyn CustomTool>,
+        telemetry: Arc<ToolTelemetry>,
+    ) -> Self {
+        Self {
+            definition,
+            executor: ToolExecutor::Custom(tool),
+            telemetry,
+            provenance: None,
+            state: None,
+        }
+    }
+
+    /// 包装会话记忆工具
+    pub(crate) fn memory(
+        definition: ToolDefinition,
+        memory: SessionMemory,
+        telemetry: Arc<ToolTelemetry>,
+    ) -> Self {
+        Self {
+            definition,
+            executor: ToolExecutor::Memory(memory),
+            telemetry,
+            provenance: None,
+            state: None,
+        }
+    }
+
+    /// 获取工具定义
+    pub fn definition(&self) -> &ToolDefinition {
+        &self.definition
+    }
+
+    /// 把调用记录到本次请求的调用记录中
+    pub(crate) fn with_provenance(mut self, provenance: ToolProvenance) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    /// 绑定到会话，自定义工具调用时使用该会话的状态
+    pub(crate) fn with_state<S: Into<String>>(
+        mut self,
+        store: Arc<ToolStateStore>,
+        conversation_id: S,
+    ) -> Self {
+        self.state = Some((store, conversation_id.into()));
+        self
+    }
+
+    /// 执行自定义工具，绑定了会话时首次调用前初始化工具在该会话中的状态
+    async fn execute_custom(
+        &self,
+        tool: &Arc<dyn CustomTool>,
+        arguments: &str,
+    ) -> AgentResult<String> {
+        let state = match &self.state {
+            Some((store, conversation_id)) => store.get_or_init(conversation_id, tool).await?,
+            None => None,
+        };
+        match state {
+            Some(state) => state::scope(state, tool.execute(arguments)).await,
+            None => tool.execute(arguments).await,
+        }
+    }
+
+    /// 执行工具并记录遥测、审计与调用记录
+    async fn execute(&self, args: serde_json::Value) -> AgentResult<String> {
+        let arguments = args.to_string();
+        let call_id = uuid::Uuid::new_v4().to_string();
+        let timestamp = Utc::now();
+        let start_time = std::time::Instant::now();
+
+        let result = match &self.executor {
+            ToolExecutor::Builtin(tools) => {
+                let tool_call = ToolCall {
+                    id: call_id.clone(),
+                    name: self.definition.name.clone(),
+                    arguments: arguments.clone(),
+                    timestamp,
+                };
+                let result = tools.execute_tool(&tool_call).await?;
+                if result.success {
+                    Ok(result.result)
+                } else {
+                    Err(AgentError::tool(
+                        result.error.unwrap_or_else(|| "工具执行失败".to_string()),
+                    ))
+                }
+            }
+            ToolExecutor::Custom(tool) => self.execute_custom(tool, &arguments).await,
+            ToolExecutor::Memory(memory) => memory.execute(&self.definition.name, &arguments).await,
+        };
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+        let error = result.as_ref().err().map(|e| e.to_string());
+        self.telemetry
+            .record(&self.definition.name, duration_ms, error.as_deref());
+        if let Some(context) = RequestContext::current() {
+            context.audit("tool", &self.definition.name, duration_ms, error.as_deref());
+        }
+        if let Some(provenance) = &self.provenance {
+            provenance.record(ToolOutput {
+                call_id,
+                tool: self.definition.name.clone(),
+                arguments,
+                output: result
+                    .as_ref()
+                    .map(String::clone)
+                    .map_err(ToString::to_string),
+                timestamp,
+            });
+        }
+
+        result
+    }
+}
+
+impl Tool for ManagedTool {
+    const NAME: &'static str = "managed_tool";
+
+    type Error = AgentError;
+    type Args = serde_json::Value;
+    type Output = String;
+
+    fn name(&self) -> String {
+        self.definition.name.clone()
+    }
+
+    async fn definition(&self, _prompt: String) -> RigToolDefinition {
+        RigToolDefinition {
+            name: self.definition.name.clone(),
+            description: self.definition.description.clone(),
+            parameters: self.definition.parameters.clone(),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // rig 要求工具调用的 future 同时满足 `Sync`，工具执行者返回的 future 只满足 `Send`
+        SyncFuture::new(self.execute(args)).await
+    }
+}
@@ -0,0 +1,196 @@
+//! 会话级工具状态 - 让自定义工具在同一会话的多次调用之间保存状态（浏览器会话、数据库游标等）
+//!
+//! 状态按（会话，工具）保存：工具在会话中首次被调用前执行 [`CustomTool::init_state`]，
+//! 会话结束（Agent 被删除或回收、清除对话历史、开始新会话）时执行 [`CustomTool::dispose_state`]。
+//! 工具在 [`CustomTool::execute`] 中通过 [`tool_state`] 取得当前会话的状态。
+//! 没有关联会话的 Agent 以 Agent ID 作为会话 ID
+
+use super::CustomTool;
+use crate::error::AgentResult;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+/// 工具在一个会话中的状态，需要修改时在内部使用锁
+pub type ToolStateValue = Arc<dyn Any + Send + Sync>;
+
+tokio::task_local! {
+    static CURRENT: ToolStateValue;
+}
+
+/// 当前工具调用所在会话的状态，没有状态或类型不符时返回 `None`
+pub fn tool_state<T: Any + Send + Sync>() -> Option<Arc<T>> {
+    CURRENT
+        .try_with(Clone::clone)
+        .ok()
+        .and_then(|state| state.downcast::<T>().ok())
+}
+
+/// 在带有状态的作用域中执行工具调用
+pub(crate) async fn scope<F: Future>(state: ToolStateValue, future: F) -> F::Output {
+    CURRENT.scope(state, future).await
+}
+
+/// 一个工具在一个会话中的状态；初始化完成前为空，无状态的工具初始化为 `None`
+struct Slot {
+    tool: Arc<dyn CustomTool>,
+    state: Arc<OnceCell<Option<ToolStateValue>>>,
+}
+
+/// 会话级工具状态存储
+#[derive(Default)]
+pub struct ToolStateStore {
+    slots: Mutex<HashMap<(String, String), Slot>>,
+}
+
+impl ToolStateStore {
+    /// 创建空的状态存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取得工具在会话中的状态，首次使用时初始化。并发的首次调用只初始化一次，
+    /// 初始化失败时不保存结果，下次调用重试
+    pub(crate) async fn get_or_init(
+        &self,
+        conversation_id: &str,
+        tool: &Arc<dyn CustomTool>,
+    ) -> AgentResult<Option<ToolStateValue>> {
+        let cell = {
+            let mut slots = self.slots.lock().unwrap();
+            slots
+                .entry((conversation_id.to_string(), tool.name().to_string()))
+                .or_insert_with(|| Slot {
+                    tool: tool.clone(),
+                    state: Arc::default(),
+                })
+                .state
+                .clone()
+        };
+        cell.get_or_try_init(|| async {
+            debug!(
+                "初始化工具 {} 在会话 {} 中的状态",
+                tool.name(),
+                conversation_id
+            );
+            tool.init_state(conversation_id).await
+        })
+        .await
+        .cloned()
+    }
+
+    /// 会话中已初始化状态的工具名称，按名称排序
+    pub fn tools_in(&self, conversation_id: &str) -> Vec<String> {
+        let slots = self.slots.lock().unwrap();
+        let mut tools: Vec<String> = slots
+            .iter()
+            .filter(|((id, _), slot)| id == conversation_id && slot.state.initialized())
+            .map(|((_, tool), _)| tool.clone())
+            .collect();
+        tools.sort();
+        tools
+    }
+
+    /// 结束会话，释放其中全部工具的状态，返回释放的状态数
+    pub async fn dispose(&self, conversation_id: &str) -> usize {
+        let ended: Vec<(String, Slot)> = {
+            let mut slots = self.slots.lock().unwrap();
+            let keys: Vec<(String, String)> = slots
+                .keys()
+                .filter(|(id, _)| id == conversation_id)
+                .cloned()
+                .collect();
+            keys.into_iter()
+                .filter_map(|key| slots.remove(&key).map(|slot| (key.1, slot)))
+                .collect()
+        };
+
+        let mut disposed = 0;
+        for (tool, slot) in ended {
+            if let Some(Some(state)) = slot.state.get() {
+                debug!("释放工具 {} 在会话 {} 中的状态", tool, conversation_id);
+                slot.tool
+                    .dispose_state(conversation_id, state.clone())
+                    .await;
+                disposed += 1;
+            }
+        }
+        disposed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 记录每个会话被调用次数的计数器工具
+    #[derive(Default)]
+    struct CounterTool {
+        inits: AtomicUsize,
+        disposed: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl CustomTool for CounterTool {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn description(&self) -> &str {
+            "计数"
+        }
+
+        fn parameters(&self) -> serde_json::Value {
+            serde_json::json!({ "type": "object" })
+        }
+
+        async fn execute(&self, _arguments: &str) -> AgentResult<String> {
+            let count = tool_state::<AtomicUsize>().expect("缺少会话状态");
+            Ok((count.fetch_add(1, Ordering::SeqCst) + 1).to_string())
+        }
+
+        async fn init_state(&self, _conversation_id: &str) -> AgentResult<Option<ToolStateValue>> {
+            self.inits.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(Arc::new(AtomicUsize::new(0))))
+        }
+
+        async fn dispose_state(&self, _conversation_id: &str, state: ToolStateValue) {
+            let count = state.downcast::<AtomicUsize>().unwrap();
+            self.disposed
+                .fetch_add(count.load(Ordering::SeqCst), Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_state_lifecycle() {
+        let counter = Arc::new(CounterTool::default());
+        let tool: Arc<dyn CustomTool> = counter.clone();
+        let store = ToolStateStore::new();
+
+        let call = |conversation_id: &'static str| {
+            let (store, tool) = (&store, tool.clone());
+            async move {
+                let state = store.get_or_init(conversation_id, &tool).await.unwrap();
+                scope(state.unwrap(), tool.execute("{}")).await.unwrap()
+            }
+        };
+        assert_eq!(call("s1").await, "1");
+        assert_eq!(call("s1").await, "2");
+        assert_eq!(call("s2").await, "1");
+        assert_eq!(counter.inits.load(Ordering::SeqCst), 2);
+        assert_eq!(store.tools_in("s1"), ["counter"]);
+        assert!(tool_state::<AtomicUsize>().is_none());
+
+        assert_eq!(store.dispose("s1").await, 1);
+        assert_eq!(counter.disposed.load(Ordering::SeqCst), 2);
+        assert!(store.tools_in("s1").is_empty());
+        assert_eq!(store.dispose("s1").await, 0);
+        // 会话结束后再次使用时重新初始化
+        assert_eq!(call("s1").await, "1");
+        assert_eq!(counter.inits.load(Ordering::SeqCst), 3);
+    }
+}
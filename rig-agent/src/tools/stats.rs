@@ -0,0 +1,361 @@
+//! 工具调用统计 - 记录调用次数、成功率、延迟分位数与最近错误，以及 MCP 服务器的运行状况
+
+use super::mcp::McpServerHealth;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// 每个工具保留的延迟样本数量
+const LATENCY_SAMPLES: usize = 512;
+
+/// 统计配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolTelemetryConfig {
+    /// 连续失败多少次后发出告警事件
+    pub failure_threshold: u32,
+    /// 每个工具保留的最近错误数量
+    pub recent_errors: usize,
+}
+
+impl Default for ToolTelemetryConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            recent_errors: 10,
+        }
+    }
+}
+
+impl ToolTelemetryConfig {
+    /// 设置连续失败告警阈值
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// 设置保留的最近错误数量
+    pub fn with_recent_errors(mut self, count: usize) -> Self {
+        self.recent_errors = count;
+        self
+    }
+}
+
+/// 工具错误记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolErrorRecord {
+    /// 错误信息
+    pub error: String,
+    /// 发生时间
+    pub timestamp: DateTime<Utc>,
+    /// 调用耗时（毫秒）
+    pub duration_ms: u64,
+}
+
+/// 单个工具的统计快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStats {
+    /// 工具名称
+    pub tool_name: String,
+    /// 调用次数
+    pub invocations: u64,
+    /// 成功次数
+    pub successes: u64,
+    /// 失败次数
+    pub failures: u64,
+    /// 成功率（0.0 - 1.0）
+    pub success_rate: f64,
+    /// 当前连续失败次数
+    pub consecutive_failures: u32,
+    /// 延迟 P50（毫秒）
+    pub latency_p50_ms: u64,
+    /// 延迟 P95（毫秒）
+    pub latency_p95_ms: u64,
+    /// 延迟 P99（毫秒）
+    pub latency_p99_ms: u64,
+    /// 最近错误（按时间先后）
+    pub recent_errors: Vec<ToolErrorRecord>,
+    /// 最后调用时间
+    pub last_invoked_at: Option<DateTime<Utc>>,
+}
+
+/// 工具事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolEvent {
+    /// 工具连续失败达到阈值
+    RepeatedFailures {
+        tool_name: String,
+        consecutive_failures: u32,
+        last_error: String,
+    },
+    /// 连续失败后恢复成功
+    Recovered {
+        tool_name: String,
+        after_failures: u32,
+    },
+    /// 从工具目录注册或更新了工具
+    ToolRegistered { tool_name: String, source: String },
+    /// 工具目录中的工具被移除
+    ToolUnregistered { tool_name: String, source: String },
+    /// 工具目录中的文件无法加载
+    ToolLoadFailed { source: String, error: String },
+    /// MCP 服务器连续退出次数过多，不再重启
+    McpServerFailed { server: String, last_exit: String },
+}
+
+#[derive(Default)]
+struct ToolCounters {
+    invocations: u64,
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    latencies: VecDeque<u64>,
+    recent_errors: VecDeque<ToolErrorRecord>,
+    last_invoked_at: Option<DateTime<Utc>>,
+}
+
+impl ToolCounters {
+    fn snapshot(&self, tool_name: &str) -> ToolStats {
+        let mut sorted: Vec<u64> = self.latencies.iter().copied().collect();
+        sorted.sort_unstable();
+
+        ToolStats {
+            tool_name: tool_name.to_string(),
+            invocations: self.invocations,
+            successes: self.successes,
+            failures: self.failures,
+            success_rate: if self.invocations == 0 {
+                0.0
+            } else {
+                self.successes as f64 / self.invocations as f64
+            },
+            consecutive_failures: self.consecutive_failures,
+            latency_p50_ms: percentile(&sorted, 50.0),
+            latency_p95_ms: percentile(&sorted, 95.0),
+            latency_p99_ms: percentile(&sorted, 99.0),
+            recent_errors: self.recent_errors.iter().cloned().collect(),
+            last_invoked_at: self.last_invoked_at,
+        }
+    }
+}
+
+/// 最近邻法计算分位数
+fn percentile(sorted: &[u64], pct: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// 工具调用统计收集器
+pub struct ToolTelemetry {
+    config: ToolTelemetryConfig,
+    counters: Mutex<HashMap<String, ToolCounters>>,
+    servers: Mutex<BTreeMap<String, McpServerHealth>>,
+    event_sender: broadcast::Sender<ToolEvent>,
+}
+
+impl ToolTelemetry {
+    /// 创建统计收集器
+    pub fn new(config: ToolTelemetryConfig) -> Self {
+        let (event_sender, _) = broadcast::channel(64);
+        Self {
+            config,
+            counters: Mutex::new(HashMap::new()),
+            servers: Mutex::new(BTreeMap::new()),
+            event_sender,
+        }
+    }
+
+    /// 订阅工具事件
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ToolEvent> {
+        self.event_sender.subscribe()
+    }
+
+    /// 记录一次工具调用
+    pub fn record(&self, tool_name: &str, duration_ms: u64, error: Option<&str>) {
+        let event = {
+            let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = counters.entry(tool_name.to_string()).or_default();
+
+            entry.invocations += 1;
+            entry.last_invoked_at = Some(Utc::now());
+            if entry.latencies.len() == LATENCY_SAMPLES {
+                entry.latencies.pop_front();
+            }
+            entry.latencies.push_back(duration_ms);
+
+            match error {
+                None => {
+                    entry.successes += 1;
+                    let after_failures = std::mem::take(&mut entry.consecutive_failures);
+                    (after_failures >= self.config.failure_threshold).then(|| {
+                        ToolEvent::Recovered {
+                            tool_name: tool_name.to_string(),
+                            after_failures,
+                        }
+                    })
+                }
+                Some(error) => {
+                    entry.failures += 1;
+                    entry.consecutive_failures += 1;
+                    if self.config.recent_errors > 0 {
+                        if entry.recent_errors.len() >= self.config.recent_errors {
+                            entry.recent_errors.pop_front();
+                        }
+                        entry.recent_errors.push_back(ToolErrorRecord {
+                            error: error.to_string(),
+                            timestamp: Utc::now(),
+                            duration_ms,
+                        });
+                    }
+                    // 达到阈值时告警一次，之后每再累计一个阈值周期告警一次
+                    entry.consecutive_failures.is_multiple_of(self.config.failure_threshold).then(|| {
+                        ToolEvent::RepeatedFailures {
+                            tool_name: tool_name.to_string(),
+                            consecutive_failures: entry.consecutive_failures,
+                            last_error: error.to_string(),
+                        }
+                    })
+                }
+            }
+        };
+
+        if let Some(event) = event {
+            if let ToolEvent::RepeatedFailures {
+                tool_name,
+                consecutive_failures,
+                last_error,
+            } = &event
+            {
+                warn!(
+                    "工具 {} 已连续失败 {} 次，最近错误: {}",
+                    tool_name, consecutive_failures, last_error
+                );
+            }
+            let _ = self.event_sender.send(event);
+        }
+    }
+
+    /// 获取单个工具的统计
+    pub fn get_stats(&self, tool_name: &str) -> Option<ToolStats> {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters
+            .get(tool_name)
+            .map(|entry| entry.snapshot(tool_name))
+    }
+
+    /// 获取所有工具的统计（按名称排序）
+    pub fn get_all_stats(&self) -> Vec<ToolStats> {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stats: Vec<ToolStats> = counters
+            .iter()
+            .map(|(name, entry)| entry.snapshot(name))
+            .collect();
+        stats.sort_by(|a, b| a.tool_name.cmp(&b.tool_name));
+        stats
+    }
+
+    /// 更新 MCP 服务器的运行状况
+    pub(crate) fn update_server(&self, name: &str, update: impl FnOnce(&mut McpServerHealth)) {
+        let mut servers = self.servers.lock().unwrap_or_else(|e| e.into_inner());
+        update(
+            servers
+                .entry(name.to_string())
+                .or_insert_with(|| McpServerHealth::new(name)),
+        );
+    }
+
+    /// 获取各 MCP 服务器的运行状况（按名称排序）
+    pub fn get_server_health(&self) -> Vec<McpServerHealth> {
+        let servers = self.servers.lock().unwrap_or_else(|e| e.into_inner());
+        servers.values().cloned().collect()
+    }
+
+    /// 移除 MCP 服务器的运行状况
+    pub(crate) fn clear_servers(&self) {
+        self.servers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// 发出工具事件
+    pub(crate) fn emit(&self, event: ToolEvent) {
+        let _ = self.event_sender.send(event);
+    }
+
+    /// 清空调用统计，MCP 服务器的运行状况不受影响
+    pub fn reset(&self) {
+        self.counters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl Default for ToolTelemetry {
+    fn default() -> Self {
+        Self::new(ToolTelemetryConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&samples, 50.0), 50);
+        assert_eq!(percentile(&samples, 95.0), 95);
+        assert_eq!(percentile(&samples, 99.0), 99);
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn test_record_stats() {
+        let telemetry = ToolTelemetry::default();
+        telemetry.record("calculator", 10, None);
+        telemetry.record("calculator", 30, Some("bad expression"));
+
+        let stats = telemetry.get_stats("calculator").unwrap();
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.success_rate, 0.5);
+        assert_eq!(stats.latency_p99_ms, 30);
+        assert_eq!(stats.recent_errors[0].error, "bad expression");
+        assert!(telemetry.get_stats("weather").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failure_events() {
+        let telemetry =
+            ToolTelemetry::new(ToolTelemetryConfig::default().with_failure_threshold(2));
+        let mut events = telemetry.subscribe_events();
+
+        telemetry.record("weather", 5, Some("timeout"));
+        assert!(events.try_recv().is_err());
+        telemetry.record("weather", 5, Some("timeout"));
+        match events.recv().await.unwrap() {
+            ToolEvent::RepeatedFailures {
+                consecutive_failures,
+                ..
+            } => assert_eq!(consecutive_failures, 2),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        telemetry.record("weather", 5, None);
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ToolEvent::Recovered {
+                after_failures: 2,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,584 @@
+//! 天气工具模块
+//!
+//! 通过可插拔的 [`WeatherProvider`] 获取真实天气数据，默认使用无需 API 密钥的 Open-Meteo
+
+use crate::error::{AgentError, AgentResult};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// 温度单位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    /// 摄氏度
+    #[default]
+    Celsius,
+    /// 华氏度
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    /// 单位符号
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+        }
+    }
+
+    /// 风速单位（与温度单位体系保持一致）
+    pub fn wind_speed_unit(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "km/h",
+            TemperatureUnit::Fahrenheit => "mph",
+        }
+    }
+}
+
+impl FromStr for TemperatureUnit {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "celsius" | "c" | "metric" | "摄氏度" => Ok(TemperatureUnit::Celsius),
+            "fahrenheit" | "f" | "imperial" | "华氏度" => Ok(TemperatureUnit::Fahrenheit),
+            other => Err(AgentError::tool(format!("不支持的温度单位: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemperatureUnit::Celsius => write!(f, "celsius"),
+            TemperatureUnit::Fahrenheit => write!(f, "fahrenheit"),
+        }
+    }
+}
+
+/// 天气报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeatherReport {
+    /// 解析后的地点名称
+    pub location: String,
+    /// 纬度
+    pub latitude: f64,
+    /// 经度
+    pub longitude: f64,
+    /// 当前温度
+    pub temperature: f64,
+    /// 体感温度
+    pub apparent_temperature: Option<f64>,
+    /// 相对湿度（%）
+    pub humidity: Option<f64>,
+    /// 风速
+    pub wind_speed: Option<f64>,
+    /// WMO 天气代码
+    pub weather_code: Option<u32>,
+    /// 天气描述
+    pub description: String,
+    /// 温度单位
+    pub unit: TemperatureUnit,
+    /// 观测时间（数据源本地时间）
+    pub observed_at: Option<String>,
+    /// 数据来源
+    pub source: String,
+    /// 获取时间
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl WeatherReport {
+    /// 生成供模型阅读的文本描述
+    pub fn to_text(&self) -> String {
+        let mut parts = vec![format!(
+            "{}的天气：{}，温度 {:.1}{}",
+            self.location,
+            self.description,
+            self.temperature,
+            self.unit.symbol()
+        )];
+
+        if let Some(apparent) = self.apparent_temperature {
+            parts.push(format!("体感 {:.1}{}", apparent, self.unit.symbol()));
+        }
+        if let Some(humidity) = self.humidity {
+            parts.push(format!("湿度 {:.0}%", humidity));
+        }
+        if let Some(wind) = self.wind_speed {
+            parts.push(format!("风速 {:.1} {}", wind, self.unit.wind_speed_unit()));
+        }
+
+        let mut text = parts.join("，");
+        if let Some(time) = &self.observed_at {
+            text.push_str(&format!("（观测时间 {}，数据来源 {}）", time, self.source));
+        } else {
+            text.push_str(&format!("（数据来源 {}）", self.source));
+        }
+        text
+    }
+}
+
+/// 将 WMO 天气代码转换为中文描述
+pub fn describe_weather_code(code: u32) -> &'static str {
+    match code {
+        0 => "晴朗",
+        1 => "大部晴朗",
+        2 => "局部多云",
+        3 => "阴天",
+        45 | 48 => "雾",
+        51 | 53 | 55 => "毛毛雨",
+        56 | 57 => "冻毛毛雨",
+        61 => "小雨",
+        63 => "中雨",
+        65 => "大雨",
+        66 | 67 => "冻雨",
+        71 => "小雪",
+        73 => "中雪",
+        75 => "大雪",
+        77 => "雪粒",
+        80..=82 => "阵雨",
+        85 | 86 => "阵雪",
+        95 => "雷暴",
+        96 | 99 => "雷暴伴有冰雹",
+        _ => "未知天气",
+    }
+}
+
+/// 天气数据提供者特征
+#[async_trait::async_trait]
+pub trait WeatherProvider: Send + Sync {
+    /// 提供者名称
+    fn name(&self) -> &str;
+
+    /// 获取指定城市的当前天气
+    async fn current_weather(
+        &self,
+        city: &str,
+        unit: TemperatureUnit,
+    ) -> AgentResult<WeatherReport>;
+}
+
+/// Open-Meteo 天气提供者（无需 API 密钥）
+pub struct OpenMeteoProvider {
+    client: reqwest::Client,
+    geocoding_url: String,
+    forecast_url: String,
+    language: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeocodingResponse {
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeocodingResult {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    country: Option<String>,
+    admin1: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastResponse {
+    current: ForecastCurrent,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastCurrent {
+    time: Option<String>,
+    temperature_2m: f64,
+    relative_humidity_2m: Option<f64>,
+    apparent_temperature: Option<f64>,
+    weather_code: Option<u32>,
+    wind_speed_10m: Option<f64>,
+}
+
+impl OpenMeteoProvider {
+    /// 默认地理编码端点
+    pub const DEFAULT_GEOCODING_URL: &'static str =
+        "https://geocoding-api.open-meteo.com/v1/search";
+    /// 默认天气预报端点
+    pub const DEFAULT_FORECAST_URL: &'static str = "https://api.open-meteo.com/v1/forecast";
+
+    /// 创建新的 Open-Meteo 提供者
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            geocoding_url: Self::DEFAULT_GEOCODING_URL.to_string(),
+            forecast_url: Self::DEFAULT_FORECAST_URL.to_string(),
+            language: "zh".to_string(),
+        }
+    }
+
+    /// 使用自定义 HTTP 客户端
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 设置自定义端点（用于自建实例或测试）
+    pub fn with_endpoints<S: Into<String>>(mut self, geocoding_url: S, forecast_url: S) -> Self {
+        self.geocoding_url = geocoding_url.into();
+        self.forecast_url = forecast_url.into();
+        self
+    }
+
+    /// 设置地名返回语言
+    pub fn with_language<S: Into<String>>(mut self, language: S) -> Self {
+        self.language = language.into();
+        self
+    }
+
+    /// 将城市名称解析为坐标
+    async fn geocode(&self, city: &str) -> AgentResult<GeocodingResult> {
+        let response = self
+            .client
+            .get(&self.geocoding_url)
+            .query(&[
+                ("name", city),
+                ("count", "1"),
+                ("language", self.language.as_str()),
+                ("format", "json"),
+            ])
+            .send()
+            .await
+            .map_err(|e| AgentError::network(format!("地理编码请求失败: {}", e)))?
+            .error_for_status()
+            .map_err(|e| AgentError::network(format!("地理编码服务返回错误: {}", e)))?;
+
+        let body: GeocodingResponse = response
+            .json()
+            .await
+            .map_err(|e| AgentError::tool(format!("解析地理编码响应失败: {}", e)))?;
+
+        body.results
+            .into_iter()
+            .next()
+            .ok_or_else(|| AgentError::tool(format!("未找到城市: {}", city)))
+    }
+}
+
+impl Default for OpenMeteoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl WeatherProvider for OpenMeteoProvider {
+    fn name(&self) -> &str {
+        "open-meteo"
+    }
+
+    async fn current_weather(
+        &self,
+        city: &str,
+        unit: TemperatureUnit,
+    ) -> AgentResult<WeatherReport> {
+        let place = self.geocode(city).await?;
+
+        let wind_unit = match unit {
+            TemperatureUnit::Celsius => "kmh",
+            TemperatureUnit::Fahrenheit => "mph",
+        };
+
+        let response = self
+            .client
+            .get(&self.forecast_url)
+            .query(&[
+                ("latitude", place.latitude.to_string()),
+                ("longitude", place.longitude.to_string()),
+                (
+                    "current",
+                    "temperature_2m,relative_humidity_2m,apparent_temperature,weather_code,wind_speed_10m"
+                        .to_string(),
+                ),
+                ("temperature_unit", unit.to_string()),
+                ("wind_speed_unit", wind_unit.to_string()),
+                ("timezone", "auto".to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| AgentError::network(format!("天气请求失败: {}", e)))?
+            .error_for_status()
+            .map_err(|e| AgentError::network(format!("天气服务返回错误: {}", e)))?;
+
+        let body: ForecastResponse = response
+            .json()
+            .await
+            .map_err(|e| AgentError::tool(format!("解析天气响应失败: {}", e)))?;
+
+        let location = [Some(place.name), place.admin1, place.country]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let current = body.current;
+        Ok(WeatherReport {
+            location,
+            latitude: place.latitude,
+            longitude: place.longitude,
+            temperature: current.temperature_2m,
+            apparent_temperature: current.apparent_temperature,
+            humidity: current.relative_humidity_2m,
+            wind_speed: current.wind_speed_10m,
+            weather_code: current.weather_code,
+            description: current
+                .weather_code
+                .map(describe_weather_code)
+                .unwrap_or("未知天气")
+                .to_string(),
+            unit,
+            observed_at: current.time,
+            source: self.name().to_string(),
+            fetched_at: Utc::now(),
+        })
+    }
+}
+
+/// 带缓存的天气提供者包装器
+pub struct CachedWeatherProvider {
+    inner: Arc<dyn WeatherProvider>,
+    ttl: Duration,
+    capacity: usize,
+    cache: Mutex<HashMap<(String, TemperatureUnit), (Instant, WeatherReport)>>,
+}
+
+impl CachedWeatherProvider {
+    /// 包装一个天气提供者并缓存其结果
+    pub fn new(inner: Arc<dyn WeatherProvider>, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            inner,
+            ttl,
+            capacity: capacity.max(1),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 清空缓存
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// 当前缓存条目数
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cache_key(city: &str, unit: TemperatureUnit) -> (String, TemperatureUnit) {
+        (city.trim().to_lowercase(), unit)
+    }
+}
+
+#[async_trait::async_trait]
+impl WeatherProvider for CachedWeatherProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn current_weather(
+        &self,
+        city: &str,
+        unit: TemperatureUnit,
+    ) -> AgentResult<WeatherReport> {
+        let key = Self::cache_key(city, unit);
+
+        {
+            let cache = self.cache.lock().unwrap();
+            if let Some((stored_at, report)) = cache.get(&key)
+                && stored_at.elapsed() < self.ttl
+            {
+                debug!("天气缓存命中: {} ({})", city, unit);
+                return Ok(report.clone());
+            }
+        }
+
+        let report = self.inner.current_weather(city, unit).await?;
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        if cache.len() >= self.capacity {
+            // 淘汰最早写入的条目
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(k, _)| k.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(key, (Instant::now(), report.clone()));
+
+        Ok(report)
+    }
+}
+
+/// 天气工具配置
+#[derive(Debug, Clone)]
+pub struct WeatherConfig {
+    /// 未指定单位时使用的默认温度单位
+    pub default_unit: TemperatureUnit,
+    /// 缓存有效期
+    pub cache_ttl: Duration,
+    /// 最大缓存条目数
+    pub cache_capacity: usize,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            default_unit: TemperatureUnit::Celsius,
+            cache_ttl: Duration::from_secs(600),
+            cache_capacity: 256,
+        }
+    }
+}
+
+impl WeatherConfig {
+    /// 设置默认温度单位
+    pub fn with_default_unit(mut self, unit: TemperatureUnit) -> Self {
+        self.default_unit = unit;
+        self
+    }
+
+    /// 设置缓存有效期
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// 设置最大缓存条目数
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl WeatherProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        async fn current_weather(
+            &self,
+            city: &str,
+            unit: TemperatureUnit,
+        ) -> AgentResult<WeatherReport> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(WeatherReport {
+                location: city.to_string(),
+                latitude: 0.0,
+                longitude: 0.0,
+                temperature: 20.0,
+                apparent_temperature: None,
+                humidity: None,
+                wind_speed: None,
+                weather_code: Some(0),
+                description: describe_weather_code(0).to_string(),
+                unit,
+                observed_at: None,
+                source: "counting".to_string(),
+                fetched_at: Utc::now(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_temperature_unit_parsing() {
+        assert_eq!(
+            "Fahrenheit".parse::<TemperatureUnit>().unwrap(),
+            TemperatureUnit::Fahrenheit
+        );
+        assert_eq!(
+            "c".parse::<TemperatureUnit>().unwrap(),
+            TemperatureUnit::Celsius
+        );
+        assert!("kelvin".parse::<TemperatureUnit>().is_err());
+    }
+
+    #[test]
+    fn test_weather_code_description() {
+        assert_eq!(describe_weather_code(0), "晴朗");
+        assert_eq!(describe_weather_code(95), "雷暴");
+        assert_eq!(describe_weather_code(1234), "未知天气");
+    }
+
+    #[tokio::test]
+    async fn test_cached_provider_reuses_results() {
+        let inner = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let cached = CachedWeatherProvider::new(inner.clone(), Duration::from_secs(60), 8);
+
+        cached
+            .current_weather("北京", TemperatureUnit::Celsius)
+            .await
+            .unwrap();
+        cached
+            .current_weather(" 北京 ", TemperatureUnit::Celsius)
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        // 不同单位分别缓存
+        cached
+            .current_weather("北京", TemperatureUnit::Fahrenheit)
+            .await
+            .unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cached.len(), 2);
+    }
+
+    #[test]
+    fn test_report_text() {
+        let report = WeatherReport {
+            location: "Beijing, China".to_string(),
+            latitude: 39.9,
+            longitude: 116.4,
+            temperature: 25.04,
+            apparent_temperature: Some(26.0),
+            humidity: Some(40.0),
+            wind_speed: Some(3.2),
+            weather_code: Some(0),
+            description: "晴朗".to_string(),
+            unit: TemperatureUnit::Celsius,
+            observed_at: None,
+            source: "open-meteo".to_string(),
+            fetched_at: Utc::now(),
+        };
+        let text = report.to_text();
+        assert!(text.contains("Beijing"));
+        assert!(text.contains("25.0°C"));
+        assert!(text.contains("湿度 40%"));
+    }
+}
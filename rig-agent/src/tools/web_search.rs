@@ -0,0 +1,436 @@
+//! 网络搜索工具模块
+//!
+//! 通过可插拔的 [`SearchEngine`] 执行网络搜索，支持 SearxNG、Brave Search 和 Serper
+
+use crate::error::{AgentError, AgentResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+/// 单条搜索结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// 标题
+    pub title: String,
+    /// 链接
+    pub url: String,
+    /// 摘要
+    pub snippet: String,
+}
+
+/// 格式化搜索结果，供模型阅读
+pub fn format_results(query: &str, results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return format!("未找到与 \"{}\" 相关的结果", query);
+    }
+
+    let mut text = format!("\"{}\" 的搜索结果：\n", query);
+    for (index, result) in results.iter().enumerate() {
+        text.push_str(&format!(
+            "{}. {}\n   {}\n   {}\n",
+            index + 1,
+            result.title,
+            result.url,
+            result.snippet
+        ));
+    }
+    text.trim_end().to_string()
+}
+
+/// 搜索引擎特征
+#[async_trait::async_trait]
+pub trait SearchEngine: Send + Sync {
+    /// 引擎名称
+    fn name(&self) -> &str;
+
+    /// 执行搜索，最多返回 `limit` 条结果
+    async fn search(&self, query: &str, limit: usize) -> AgentResult<Vec<SearchResult>>;
+}
+
+/// 搜索引擎配置（与 `ClientConfig` 保持一致的密钥管理方式）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchConfig {
+    /// 引擎名称 (searxng, brave, serper)
+    pub engine: String,
+    /// API 密钥（Brave/Serper 需要）
+    pub api_key: Option<String>,
+    /// 基础 URL（SearxNG 实例地址，或自定义端点）
+    pub base_url: Option<String>,
+    /// 默认返回结果数
+    pub default_limit: usize,
+    /// 允许的最大结果数
+    pub max_limit: usize,
+    /// 其他配置参数（例如 SearxNG 的 categories、language）
+    pub extra_params: HashMap<String, serde_json::Value>,
+}
+
+impl WebSearchConfig {
+    /// 创建新的搜索配置
+    pub fn new<S: Into<String>>(engine: S) -> Self {
+        Self {
+            engine: engine.into(),
+            api_key: None,
+            base_url: None,
+            default_limit: 5,
+            max_limit: 20,
+            extra_params: HashMap::new(),
+        }
+    }
+
+    /// 设置 API 密钥
+    pub fn with_api_key<S: Into<String>>(mut self, api_key: S) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// 设置基础 URL
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// 设置默认返回结果数
+    pub fn with_default_limit(mut self, limit: usize) -> Self {
+        self.default_limit = limit;
+        self
+    }
+
+    /// 设置最大结果数
+    pub fn with_max_limit(mut self, limit: usize) -> Self {
+        self.max_limit = limit;
+        self
+    }
+
+    /// 添加额外参数
+    pub fn with_param<S: Into<String>, V: Into<serde_json::Value>>(
+        mut self,
+        key: S,
+        value: V,
+    ) -> Self {
+        self.extra_params.insert(key.into(), value.into());
+        self
+    }
+
+    /// 从环境变量推断搜索配置
+    ///
+    /// 依次检查 `SEARXNG_URL`、`BRAVE_API_KEY`、`SERPER_API_KEY`
+    pub fn from_env() -> Option<Self> {
+        if let Ok(url) = std::env::var("SEARXNG_URL") {
+            return Some(Self::new("searxng").with_base_url(url));
+        }
+        if let Ok(key) = std::env::var("BRAVE_API_KEY") {
+            return Some(Self::new("brave").with_api_key(key));
+        }
+        if let Ok(key) = std::env::var("SERPER_API_KEY") {
+            return Some(Self::new("serper").with_api_key(key));
+        }
+        None
+    }
+
+    /// 将请求的结果数限制在允许范围内
+    pub fn clamp_limit(&self, requested: Option<u64>) -> usize {
+        let max = self.max_limit.max(1);
+        requested
+            .map(|n| n as usize)
+            .unwrap_or(self.default_limit)
+            .clamp(1, max)
+    }
+
+    /// 根据配置构建搜索引擎
+    pub fn build_engine(&self) -> AgentResult<Arc<dyn SearchEngine>> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(15))
+            .build()
+            .map_err(|e| AgentError::config(format!("创建 HTTP 客户端失败: {}", e)))?;
+
+        let engine: Arc<dyn SearchEngine> = match self.engine.as_str() {
+            "searxng" => {
+                let base_url = self
+                    .base_url
+                    .clone()
+                    .ok_or_else(|| AgentError::config("SearxNG 需要设置实例地址 base_url"))?;
+                let categories = self
+                    .extra_params
+                    .get("categories")
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string);
+                Arc::new(SearxngEngine {
+                    client,
+                    base_url,
+                    categories,
+                })
+            }
+            "brave" => Arc::new(BraveSearchEngine {
+                client,
+                api_key: self.require_api_key()?,
+                endpoint: self
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| BraveSearchEngine::DEFAULT_ENDPOINT.to_string()),
+            }),
+            "serper" => Arc::new(SerperEngine {
+                client,
+                api_key: self.require_api_key()?,
+                endpoint: self
+                    .base_url
+                    .clone()
+                    .unwrap_or_else(|| SerperEngine::DEFAULT_ENDPOINT.to_string()),
+            }),
+            other => {
+                return Err(AgentError::config(format!("不支持的搜索引擎: {}", other)));
+            }
+        };
+
+        info!("网络搜索引擎已配置: {}", self.engine);
+        Ok(engine)
+    }
+
+    fn require_api_key(&self) -> AgentResult<String> {
+        self.api_key
+            .clone()
+            .ok_or_else(|| AgentError::config(format!("搜索引擎 {} 需要 API 密钥", self.engine)))
+    }
+}
+
+/// SearxNG 搜索引擎（自建实例，需开启 JSON 输出格式）
+pub struct SearxngEngine {
+    client: reqwest::Client,
+    base_url: String,
+    categories: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl SearchEngine for SearxngEngine {
+    fn name(&self) -> &str {
+        "searxng"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> AgentResult<Vec<SearchResult>> {
+        let url = format!("{}/search", self.base_url.trim_end_matches('/'));
+        let mut params = vec![("q", query.to_string()), ("format", "json".to_string())];
+        if let Some(categories) = &self.categories {
+            params.push(("categories", categories.clone()));
+        }
+
+        let body: serde_json::Value = self
+            .client
+            .get(url)
+            .query(&params)
+            .send()
+            .await
+            .map_err(|e| AgentError::network(format!("SearxNG 请求失败: {}", e)))?
+            .error_for_status()
+            .map_err(|e| AgentError::network(format!("SearxNG 返回错误: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AgentError::tool(format!("解析 SearxNG 响应失败: {}", e)))?;
+
+        Ok(parse_searxng(&body, limit))
+    }
+}
+
+/// Brave Search 搜索引擎
+pub struct BraveSearchEngine {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+}
+
+impl BraveSearchEngine {
+    /// 默认端点
+    pub const DEFAULT_ENDPOINT: &'static str = "https://api.search.brave.com/res/v1/web/search";
+}
+
+#[async_trait::async_trait]
+impl SearchEngine for BraveSearchEngine {
+    fn name(&self) -> &str {
+        "brave"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> AgentResult<Vec<SearchResult>> {
+        let response = self
+            .client
+            .get(&self.endpoint)
+            .header("X-Subscription-Token", &self.api_key)
+            .header("Accept", "application/json")
+            .query(&[("q", query.to_string()), ("count", limit.to_string())])
+            .send()
+            .await
+            .map_err(|e| AgentError::network(format!("Brave 搜索请求失败: {}", e)))?;
+
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(AgentError::RateLimit);
+        }
+
+        let body: serde_json::Value = response
+            .error_for_status()
+            .map_err(|e| AgentError::network(format!("Brave 搜索返回错误: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AgentError::tool(format!("解析 Brave 响应失败: {}", e)))?;
+
+        Ok(parse_brave(&body, limit))
+    }
+}
+
+/// Serper (Google 搜索 API) 搜索引擎
+pub struct SerperEngine {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+}
+
+impl SerperEngine {
+    /// 默认端点
+    pub const DEFAULT_ENDPOINT: &'static str = "https://google.serper.dev/search";
+}
+
+#[async_trait::async_trait]
+impl SearchEngine for SerperEngine {
+    fn name(&self) -> &str {
+        "serper"
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> AgentResult<Vec<SearchResult>> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header("X-API-KEY", &self.api_key)
+            .json(&serde_json::json!({ "q": query, "num": limit }))
+            .send()
+            .await
+            .map_err(|e| AgentError::network(format!("Serper 搜索请求失败: {}", e)))?;
+
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(AgentError::RateLimit);
+        }
+
+        let body: serde_json::Value = response
+            .error_for_status()
+            .map_err(|e| AgentError::network(format!("Serper 搜索返回错误: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AgentError::tool(format!("解析 Serper 响应失败: {}", e)))?;
+
+        Ok(parse_serper(&body, limit))
+    }
+}
+
+/// 从 JSON 数组中按字段名提取结果
+fn collect_results(
+    items: Option<&Vec<serde_json::Value>>,
+    title_key: &str,
+    url_key: &str,
+    snippet_key: &str,
+    limit: usize,
+) -> Vec<SearchResult> {
+    items
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    let url = item[url_key].as_str()?.to_string();
+                    Some(SearchResult {
+                        title: item[title_key].as_str().unwrap_or(&url).to_string(),
+                        snippet: item[snippet_key].as_str().unwrap_or_default().to_string(),
+                        url,
+                    })
+                })
+                .take(limit)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_searxng(body: &serde_json::Value, limit: usize) -> Vec<SearchResult> {
+    collect_results(body["results"].as_array(), "title", "url", "content", limit)
+}
+
+fn parse_brave(body: &serde_json::Value, limit: usize) -> Vec<SearchResult> {
+    collect_results(
+        body["web"]["results"].as_array(),
+        "title",
+        "url",
+        "description",
+        limit,
+    )
+}
+
+fn parse_serper(body: &serde_json::Value, limit: usize) -> Vec<SearchResult> {
+    collect_results(
+        body["organic"].as_array(),
+        "title",
+        "link",
+        "snippet",
+        limit,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_engine_responses() {
+        let searxng = serde_json::json!({
+            "results": [
+                {"title": "Rust", "url": "https://www.rust-lang.org", "content": "A language"},
+                {"title": "Crates", "url": "https://crates.io", "content": "Registry"}
+            ]
+        });
+        let results = parse_searxng(&searxng, 1);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet, "A language");
+
+        let brave = serde_json::json!({
+            "web": {"results": [{"title": "Tokio", "url": "https://tokio.rs", "description": "Async"}]}
+        });
+        assert_eq!(parse_brave(&brave, 5)[0].url, "https://tokio.rs");
+
+        let serper = serde_json::json!({
+            "organic": [{"title": "Iroh", "link": "https://iroh.computer", "snippet": "P2P"}]
+        });
+        assert_eq!(parse_serper(&serper, 5)[0].title, "Iroh");
+    }
+
+    #[test]
+    fn test_results_without_url_are_skipped() {
+        let body = serde_json::json!({"results": [{"title": "no url"}]});
+        assert!(parse_searxng(&body, 5).is_empty());
+    }
+
+    #[test]
+    fn test_clamp_limit() {
+        let config = WebSearchConfig::new("searxng").with_max_limit(10);
+        assert_eq!(config.clamp_limit(None), 5);
+        assert_eq!(config.clamp_limit(Some(0)), 1);
+        assert_eq!(config.clamp_limit(Some(50)), 10);
+    }
+
+    #[test]
+    fn test_build_engine_requires_secrets() {
+        assert!(WebSearchConfig::new("brave").build_engine().is_err());
+        assert!(WebSearchConfig::new("searxng").build_engine().is_err());
+        assert!(WebSearchConfig::new("unknown").build_engine().is_err());
+        assert!(
+            WebSearchConfig::new("serper")
+                .with_api_key("key")
+                .build_engine()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_format_results() {
+        let results = vec![SearchResult {
+            title: "Rust".to_string(),
+            url: "https://www.rust-lang.org".to_string(),
+            snippet: "A language".to_string(),
+        }];
+        let text = format_results("rust", &results);
+        assert!(text.contains("1. Rust"));
+        assert!(format_results("nothing", &[]).contains("未找到"));
+    }
+}
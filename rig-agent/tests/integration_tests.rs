@@ -1,46 +1,90 @@
 //! Agent 模块集成测试
 
+#[path = "support/mock_provider.rs"]
+mod mock_provider;
+
 use rig_agent::{
     core::{
-        agent::AgentManager,
-        types::{AgentConfig, AgentRole, MessageType},
+        agent::{AgentManager, ClientRegistry},
+        types::{AgentConfig, AgentRole, MessageType, ToolCall},
     },
-    tools::{ToolManager, BuiltinTools, ToolCall},
+    tools::{ToolManager, BuiltinTools},
+    tools::weather::{TemperatureUnit, WeatherConfig, WeatherProvider, WeatherReport},
     error::AgentError,
+    ClientConfig,
 };
-use std::env;
-use tokio;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_test::traced_test;
 
-/// 测试环境设置
-fn setup_test_env() {
-    // 设置测试用的环境变量（如果没有的话）
-    if env::var("OPENAI_API_KEY").is_err() {
-        env::set_var("OPENAI_API_KEY", "test-key-for-testing");
+/// 测试环境设置：启动模拟提供商并注册为 openai 客户端，测试不依赖真实的 API 密钥
+async fn setup_test_env() -> ClientRegistry {
+    let addr = mock_provider::spawn(mock_provider::MockConfig {
+        latency: Duration::ZERO,
+        chunks: 1,
+        streaming: true,
+        echo: false,
+    })
+    .await;
+    let mut registry = ClientRegistry::empty();
+    registry
+        .register_openai(
+            ClientConfig::new("openai", "gpt-3.5-turbo")
+                .with_api_key("test-key-for-testing")
+                .with_base_url(format!("http://{}/v1", addr)),
+        )
+        .unwrap();
+    registry
+}
+
+/// 测试用的固定天气提供者，避免依赖网络
+struct StaticWeatherProvider;
+
+#[async_trait::async_trait]
+impl WeatherProvider for StaticWeatherProvider {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    async fn current_weather(
+        &self,
+        city: &str,
+        unit: TemperatureUnit,
+    ) -> rig_agent::AgentResult<WeatherReport> {
+        Ok(WeatherReport {
+            location: city.to_string(),
+            latitude: 39.9,
+            longitude: 116.4,
+            temperature: 25.0,
+            apparent_temperature: None,
+            humidity: Some(40.0),
+            wind_speed: None,
+            weather_code: Some(0),
+            description: "晴朗".to_string(),
+            unit,
+            observed_at: None,
+            source: "static".to_string(),
+            fetched_at: chrono::Utc::now(),
+        })
     }
 }
 
 /// 创建测试用的 Agent 配置
 fn create_test_config() -> AgentConfig {
-    AgentConfig {
-        model: "gpt-3.5-turbo".to_string(),
-        provider: Some("openai".to_string()),
-        system_prompt: Some("你是一个测试助手。".to_string()),
-        temperature: Some(0.7),
-        max_tokens: Some(100),
-        enable_tools: true,
-        history_limit: Some(10),
-        extra_params: std::collections::HashMap::new(),
-    }
+    let mut config = AgentConfig::new("openai", "gpt-3.5-turbo");
+    config.preamble = Some("你是一个测试助手。".to_string());
+    config.temperature = Some(0.7);
+    config.max_tokens = Some(100);
+    config.enable_tools = true;
+    config.history_limit = Some(10);
+    config
 }
 
 #[tokio::test]
 #[traced_test]
 async fn test_agent_manager_lifecycle() {
-    setup_test_env();
-    
     let config = create_test_config();
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     
     // 测试初始状态
     let agents = manager.list_agents().await;
@@ -48,37 +92,26 @@ async fn test_agent_manager_lifecycle() {
     
     // 测试创建 Agent
     let agent_id = "test_lifecycle_agent";
-    let result = manager.create_agent(agent_id.to_string(), None).await;
-    
-    // 如果没有有效的 API 密钥，这个测试会失败，这是预期的
-    match result {
-        Ok(_) => {
-            // 验证 Agent 已创建
-            let agents = manager.list_agents().await;
-            assert_eq!(agents.len(), 1);
-            assert!(agents.contains(&agent_id.to_string()));
-            
-            // 测试删除 Agent
-            let removed = manager.remove_agent(agent_id).await;
-            assert!(removed);
-            
-            let agents = manager.list_agents().await;
-            assert_eq!(agents.len(), 0);
-        }
-        Err(e) => {
-            // 如果是因为 API 密钥问题导致的失败，这是可以接受的
-            println!("Agent 创建失败（可能是 API 密钥问题）: {}", e);
-        }
-    }
+    manager.create_agent(agent_id.to_string(), None).await.unwrap();
+    
+    // 验证 Agent 已创建
+    let agents = manager.list_agents().await;
+    assert_eq!(agents.len(), 1);
+    assert!(agents.contains(&agent_id.to_string()));
+    
+    // 测试删除 Agent
+    let removed = manager.remove_agent(agent_id).await;
+    assert!(removed);
+    
+    let agents = manager.list_agents().await;
+    assert_eq!(agents.len(), 0);
 }
 
 #[tokio::test]
 #[traced_test]
 async fn test_agent_configuration_management() {
-    setup_test_env();
-    
     let config = create_test_config();
-    let mut manager = AgentManager::new(config.clone());
+    let manager = AgentManager::new(config.clone());
     
     let agent_id = "test_config_agent";
     
@@ -105,10 +138,10 @@ async fn test_agent_configuration_management() {
 #[tokio::test]
 #[traced_test]
 async fn test_conversation_history_management() {
-    setup_test_env();
+    let registry = setup_test_env().await;
     
     let config = create_test_config();
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     
     let agent_id = "test_history_agent";
     
@@ -118,7 +151,7 @@ async fn test_conversation_history_management() {
         assert_eq!(history.messages.len(), 0);
         
         // 模拟对话（注意：这里可能会因为 API 调用失败而跳过）
-        if let Ok(response) = manager.chat(agent_id, "你好").await {
+        if let Ok(response) = manager.chat(&registry, agent_id, "你好").await {
             assert!(!response.content.is_empty());
             
             // 检查历史记录
@@ -165,7 +198,8 @@ async fn test_tool_manager_functionality() {
 #[tokio::test]
 #[traced_test]
 async fn test_builtin_tools_execution() {
-    let builtin_tools = BuiltinTools::new();
+    let builtin_tools =
+        BuiltinTools::with_weather_provider(Arc::new(StaticWeatherProvider), WeatherConfig::default());
     
     // 测试计算器工具
     let calculator_call = ToolCall {
@@ -202,21 +236,33 @@ async fn test_builtin_tools_execution() {
     let result = builtin_tools.execute_tool(&weather_call).await.unwrap();
     assert!(result.success);
     assert!(result.result.contains("北京"));
+    assert!(result.result.contains("°C"));
+
+    // 非法单位应返回失败结果而不是 panic
+    let bad_unit_call = ToolCall {
+        id: "test_weather_2".to_string(),
+        name: "weather".to_string(),
+        arguments: r#"{"city": "北京", "unit": "kelvin"}"#.to_string(),
+        timestamp: chrono::Utc::now(),
+    };
+
+    let result = builtin_tools.execute_tool(&bad_unit_call).await.unwrap();
+    assert!(!result.success);
 }
 
 #[tokio::test]
 #[traced_test]
 async fn test_error_handling() {
-    setup_test_env();
+    let registry = setup_test_env().await;
     
     let config = create_test_config();
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     
     // 测试访问不存在的 Agent
     let result = manager.get_agent_config("nonexistent_agent").await;
     assert!(matches!(result, Err(AgentError::AgentNotFound(_))));
     
-    let result = manager.chat("nonexistent_agent", "hello").await;
+    let result = manager.chat(&registry, "nonexistent_agent", "hello").await;
     assert!(matches!(result, Err(AgentError::AgentNotFound(_))));
     
     let result = manager.clear_conversation_history("nonexistent_agent").await;
@@ -233,12 +279,12 @@ async fn test_error_handling() {
 #[tokio::test]
 #[traced_test]
 async fn test_agent_with_tools_enabled() {
-    setup_test_env();
+    let registry = setup_test_env().await;
     
     let mut config = create_test_config();
     config.enable_tools = true;
     
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     let agent_id = "test_tools_agent";
     
     if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
@@ -248,7 +294,7 @@ async fn test_agent_with_tools_enabled() {
         
         // 尝试发送可能触发工具调用的消息
         // 注意：这需要真实的 API 调用才能完全测试
-        if let Ok(response) = manager.chat(agent_id, "计算 2+3").await {
+        if let Ok(response) = manager.chat(&registry, agent_id, "计算 2+3").await {
             assert!(!response.content.is_empty());
             // 如果工具被调用，response.tool_calls 应该不为空
             // 但这取决于 AI 模型是否决定调用工具
@@ -259,18 +305,18 @@ async fn test_agent_with_tools_enabled() {
 #[tokio::test]
 #[traced_test]
 async fn test_conversation_history_limits() {
-    setup_test_env();
+    let registry = setup_test_env().await;
     
     let mut config = create_test_config();
     config.history_limit = Some(4); // 限制为 4 条消息
     
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     let agent_id = "test_limit_agent";
     
     if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
         // 发送多条消息来测试历史限制
         for i in 1..=3 {
-            let _ = manager.chat(agent_id, &format!("消息 {}", i)).await;
+            let _ = manager.chat(&registry, agent_id, &format!("消息 {}", i)).await;
         }
         
         let history = manager.get_conversation_history(agent_id).await.unwrap();
@@ -284,46 +330,46 @@ async fn test_conversation_history_limits() {
 #[tokio::test]
 #[traced_test]
 async fn test_concurrent_agent_operations() {
-    setup_test_env();
-    
     let config = create_test_config();
-    let mut manager = AgentManager::new(config);
+    let manager = Arc::new(AgentManager::new(config));
     
-    // 并发创建多个 Agent
+    // 并发创建多个 Agent，create_agent 只需要 &self
     let mut handles = vec![];
     
     for i in 0..5 {
+        let manager = manager.clone();
         let agent_id = format!("concurrent_agent_{}", i);
-        // 注意：这里我们不能直接并发调用 create_agent，因为它需要 &mut self
-        // 在实际应用中，可能需要使用 Arc<Mutex<AgentManager>> 或类似的并发安全结构
-        if manager.create_agent(agent_id, None).await.is_ok() {
-            // Agent 创建成功
-        }
+        handles.push(tokio::spawn(async move {
+            manager.create_agent(agent_id, None).await
+        }));
+    }
+    
+    for handle in handles {
+        handle.await.unwrap().unwrap();
     }
     
     let agents = manager.list_agents().await;
-    // 根据 API 密钥的可用性，agents 的数量可能为 0 或 5
-    println!("并发测试创建了 {} 个 Agent", agents.len());
+    assert_eq!(agents.len(), 5);
 }
 
 /// 集成测试：完整的对话流程
 #[tokio::test]
 #[traced_test]
 async fn test_complete_conversation_flow() {
-    setup_test_env();
+    let registry = setup_test_env().await;
     
     let config = create_test_config();
-    let mut manager = AgentManager::new(config);
+    let manager = AgentManager::new(config);
     let agent_id = "complete_flow_agent";
     
     if manager.create_agent(agent_id.to_string(), None).await.is_ok() {
         // 1. 发送初始消息
-        if let Ok(response1) = manager.chat(agent_id, "你好，我是测试用户").await {
+        if let Ok(response1) = manager.chat(&registry, agent_id, "你好，我是测试用户").await {
             assert!(!response1.content.is_empty());
             assert_eq!(response1.agent_id, agent_id);
             
             // 2. 继续对话
-            if let Ok(response2) = manager.chat(agent_id, "请告诉我今天的日期").await {
+            if let Ok(response2) = manager.chat(&registry, agent_id, "请告诉我今天的日期").await {
                 assert!(!response2.content.is_empty());
                 
                 // 3. 检查对话历史
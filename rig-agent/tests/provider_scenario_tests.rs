@@ -0,0 +1,124 @@
+//! 提供商基准测试场景的正确性测试，保证 `provider_benchmarks` 测量的是预期的请求路径
+
+#[path = "support/mock_provider.rs"]
+mod mock_provider;
+
+use rig_agent::{
+    AgentConfig, AgentManager, ChatOptions, ClientConfig, ToolSelection, core::ClientRegistry,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const LATENCY: Duration = Duration::from_millis(50);
+
+/// 启动带延迟的模拟提供商并注册为 openai 客户端
+async fn setup(chunks: usize) -> (ClientRegistry, Arc<mock_provider::RequestLog>) {
+    let (addr, log) = mock_provider::spawn_recording(mock_provider::MockConfig {
+        latency: LATENCY,
+        chunks,
+        streaming: true,
+        echo: false,
+    })
+    .await;
+    let mut registry = ClientRegistry::empty();
+    registry
+        .register_openai(
+            ClientConfig::new("openai", "mock")
+                .with_api_key("mock-key")
+                .with_base_url(format!("http://{}/v1", addr)),
+        )
+        .unwrap();
+    (registry, log)
+}
+
+fn config() -> AgentConfig {
+    AgentConfig {
+        history_limit: Some(10),
+        ..AgentConfig::new("openai", "mock")
+    }
+}
+
+#[tokio::test]
+async fn test_chat_and_streaming_scenarios() {
+    let (registry, log) = setup(16).await;
+    let manager = AgentManager::new(config());
+    manager
+        .create_agent("bench".to_string(), None)
+        .await
+        .unwrap();
+
+    // 普通聊天：一次模型往返，耗时不少于模拟延迟
+    let start = Instant::now();
+    let response = manager.chat(&registry, "bench", "你好").await.unwrap();
+    assert!(start.elapsed() >= LATENCY);
+    assert_eq!(response.content, "模拟提供商的响应");
+    assert_eq!(log.spans().len(), 1);
+
+    // 流式聊天：按配置的分段数回调，拼接后与完整响应一致
+    let mut chunks = Vec::new();
+    let response = manager
+        .chat_stream(
+            &registry,
+            "bench",
+            "再来一次",
+            ChatOptions::default().with_bypass_cache(true),
+            |chunk| chunks.push(chunk.to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(chunks.len(), 16);
+    assert_eq!(chunks.concat(), response.content);
+    assert!(response.content.starts_with("token0 "));
+    assert_eq!(log.spans().len(), 2);
+}
+
+#[tokio::test]
+async fn test_tool_loop_scenario() {
+    let (registry, log) = setup(1).await;
+    let mut config = config();
+    config.enable_tools = true;
+    config.tool_selection = ToolSelection::only(["math"]);
+    let manager = AgentManager::new(config);
+    manager
+        .create_agent("tools".to_string(), None)
+        .await
+        .unwrap();
+
+    // 工具循环：先返回工具调用，执行工具后再请求一次得到最终响应
+    let response = manager
+        .chat(&registry, "tools", "计算 123+456")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "模拟提供商的响应");
+    assert_eq!(log.spans().len(), 2);
+}
+
+#[tokio::test]
+async fn test_concurrent_chat_scenario() {
+    let (registry, log) = setup(1).await;
+    let manager = Arc::new(AgentManager::new(config()));
+    for i in 0..4 {
+        manager
+            .create_agent(format!("concurrent_{}", i), None)
+            .await
+            .unwrap();
+    }
+
+    // 不同 Agent 的请求并发到达提供商，不被管理器串行化
+    let chats = (0..4).map(|i| {
+        let manager = manager.clone();
+        let registry = &registry;
+        async move {
+            manager
+                .chat(registry, &format!("concurrent_{}", i), "你好")
+                .await
+                .unwrap()
+        }
+    });
+    futures::future::join_all(chats).await;
+    let spans = log.spans();
+    assert_eq!(spans.len(), 4);
+    assert!(spans.iter().all(|span| span.overlaps(&spans[0])));
+}
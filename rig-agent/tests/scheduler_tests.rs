@@ -0,0 +1,65 @@
+//! 请求调度并发集成测试
+
+#[path = "support/mock_provider.rs"]
+mod mock_provider;
+
+use rig_agent::{
+    AgentConfig, AgentManager, ChatOptions, ClientConfig, SchedulerConfig, core::ClientRegistry,
+};
+use std::time::{Duration, Instant};
+
+const LATENCY: Duration = Duration::from_millis(800);
+
+#[tokio::test]
+async fn test_agents_on_different_providers_run_concurrently() {
+    let mut registry = ClientRegistry::empty();
+    for provider in ["alpha", "beta"] {
+        let addr = mock_provider::spawn(mock_provider::MockConfig {
+            latency: LATENCY,
+            chunks: 4,
+            streaming: true,
+            echo: false,
+        })
+        .await;
+        registry
+            .register_client(
+                provider,
+                ClientConfig::new("openai", "mock")
+                    .with_api_key("mock-key")
+                    .with_base_url(format!("http://{}/v1", addr)),
+            )
+            .unwrap();
+    }
+
+    // 每个提供商只允许一个并发请求，两个 Agent 只有在调用模型期间互不阻塞时才能同时完成
+    let manager = AgentManager::new(AgentConfig::new("alpha", "mock"))
+        .with_scheduler_config(SchedulerConfig::default().with_default_concurrency(1));
+    manager
+        .create_agent("a".to_string(), Some(AgentConfig::new("alpha", "mock")))
+        .await
+        .unwrap();
+    manager
+        .create_agent("b".to_string(), Some(AgentConfig::new("beta", "mock")))
+        .await
+        .unwrap();
+
+    let options = ChatOptions::default().with_bypass_cache(true);
+    let started = Instant::now();
+    let (a, b) = tokio::join!(
+        manager.chat_with_options(&registry, "a", "你好", options.clone()),
+        manager.chat_with_options(&registry, "b", "你好", options.clone()),
+    );
+    let elapsed = started.elapsed();
+    assert_eq!(a.unwrap().content, "模拟提供商的响应");
+    assert_eq!(b.unwrap().content, "模拟提供商的响应");
+    assert!(
+        elapsed < LATENCY * 2,
+        "两个 Agent 的请求被串行处理，耗时 {:?}",
+        elapsed
+    );
+
+    // 两轮问答都写回了各自的历史
+    for agent_id in ["a", "b"] {
+        assert_eq!(manager.get_history_snapshot(agent_id).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,86 @@
+//! 提供商不支持流式响应时的降级集成测试
+
+#[path = "support/mock_provider.rs"]
+mod mock_provider;
+
+use rig_agent::{
+    AgentConfig, AgentManager, ChatOptions, ClientConfig, StreamingMode, core::ClientRegistry,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_streaming_fallback() {
+    let addr = mock_provider::spawn(mock_provider::MockConfig {
+        latency: Duration::ZERO,
+        chunks: 4,
+        streaming: false,
+        echo: false,
+    })
+    .await;
+    let mock_client = || {
+        ClientConfig::new("openai", "mock")
+            .with_api_key("mock-key")
+            .with_base_url(format!("http://{}/v1", addr))
+    };
+    let mut registry = ClientRegistry::empty();
+    registry.register_openai(mock_client()).unwrap();
+    assert_eq!(registry.streaming_mode("openai"), StreamingMode::Auto);
+    assert_eq!(registry.streaming_support("openai"), None);
+
+    let manager = AgentManager::new(AgentConfig::new("openai", "mock"));
+    manager
+        .create_agent("buffered".to_string(), None)
+        .await
+        .unwrap();
+    let options = ChatOptions::default().with_bypass_cache(true);
+
+    // 自动模式：检测到不支持流式后改为普通调用，完整响应作为一段回调
+    let mut chunks = Vec::new();
+    let response = manager
+        .chat_stream(&registry, "buffered", "你好", options.clone(), |chunk| {
+            chunks.push(chunk.to_string())
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.content, "模拟提供商的响应");
+    assert_eq!(chunks, vec!["模拟提供商的响应".to_string()]);
+    assert_eq!(registry.streaming_support("openai"), Some(false));
+
+    // 检测结果被记住，之后直接普通调用
+    let mut chunks = Vec::new();
+    manager
+        .chat_stream(
+            &registry,
+            "buffered",
+            "再说一次",
+            options.clone(),
+            |chunk| chunks.push(chunk.to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(chunks.len(), 1);
+
+    // 强制流式时不降级，直接返回错误；重新注册会清除检测结果
+    registry
+        .register_openai(mock_client().with_streaming(StreamingMode::Always))
+        .unwrap();
+    assert_eq!(registry.streaming_support("openai"), None);
+    let result = manager
+        .chat_stream(&registry, "buffered", "你好", options.clone(), |_| {})
+        .await;
+    assert!(result.is_err());
+
+    // 强制普通调用
+    registry
+        .register_openai(mock_client().with_streaming(StreamingMode::Never))
+        .unwrap();
+    let mut chunks = Vec::new();
+    manager
+        .chat_stream(&registry, "buffered", "你好", options, |chunk| {
+            chunks.push(chunk.to_string())
+        })
+        .await
+        .unwrap();
+    assert_eq!(chunks, vec!["模拟提供商的响应".to_string()]);
+    assert_eq!(registry.streaming_support("openai"), None);
+}
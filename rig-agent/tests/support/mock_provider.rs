@@ -0,0 +1,259 @@
+//! 兼容 OpenAI Chat Completions 协议的模拟提供商，供基准测试、集成测试与压测示例共用
+
+// 各测试只用到其中一部分
+#![allow(dead_code)]
+
+use serde_json::{Value, json};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// 模拟提供商配置
+#[derive(Debug, Clone)]
+pub struct MockConfig {
+    /// 每次请求的固定延迟
+    pub latency: Duration,
+    /// 流式响应的分段数量
+    pub chunks: usize,
+    /// 是否支持流式响应，不支持时流式请求返回 400
+    pub streaming: bool,
+    /// 普通响应是否回显最后一条用户消息，便于把响应与请求对应
+    pub echo: bool,
+}
+
+/// 一次请求的处理区间
+#[derive(Debug, Clone)]
+pub struct RequestSpan {
+    /// 请求中最后一条用户消息
+    pub prompt: String,
+    pub started: Instant,
+    pub finished: Instant,
+}
+
+impl RequestSpan {
+    /// 两次请求的处理区间是否重叠
+    pub fn overlaps(&self, other: &RequestSpan) -> bool {
+        self.started < other.finished && other.started < self.finished
+    }
+}
+
+/// 模拟提供商收到的请求记录
+#[derive(Debug, Default)]
+pub struct RequestLog {
+    spans: Mutex<Vec<RequestSpan>>,
+}
+
+impl RequestLog {
+    /// 已处理完的请求
+    pub fn spans(&self) -> Vec<RequestSpan> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+/// 启动模拟提供商，返回监听地址
+pub async fn spawn(config: MockConfig) -> SocketAddr {
+    spawn_recording(config).await.0
+}
+
+/// 启动模拟提供商，同时返回请求记录
+pub async fn spawn_recording(config: MockConfig) -> (SocketAddr, Arc<RequestLog>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Arc::new(config);
+    let log = Arc::new(RequestLog::default());
+
+    let requests = log.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let config = config.clone();
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                let _ = handle(stream, &config, &requests).await;
+            });
+        }
+    });
+
+    (addr, log)
+}
+
+/// 读取一个 HTTP 请求，返回请求体
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(String::new());
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let headers = String::from_utf8_lossy(&buffer[..header_end]).to_lowercase();
+    let content_length = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buffer.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    Ok(String::from_utf8_lossy(&buffer[header_end..]).into_owned())
+}
+
+/// 取出请求中最后一条用户消息的文本，兼容字符串与分段两种格式
+fn last_user_message(body: &str) -> String {
+    let request: Value = serde_json::from_str(body).unwrap_or_default();
+    let Some(message) = request["messages"]
+        .as_array()
+        .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+    else {
+        return String::new();
+    };
+    match &message["content"] {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect(),
+        _ => String::new(),
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    config: &MockConfig,
+    log: &RequestLog,
+) -> std::io::Result<()> {
+    let body = read_request(&mut stream).await?;
+    let prompt = last_user_message(&body);
+    let started = Instant::now();
+    if !config.latency.is_zero() {
+        tokio::time::sleep(config.latency).await;
+    }
+    log.spans.lock().unwrap().push(RequestSpan {
+        prompt: prompt.clone(),
+        started,
+        finished: Instant::now(),
+    });
+
+    // 请求带工具且尚无工具结果时返回一次工具调用，模拟工具循环
+    let wants_tool = body.contains("\"tools\"") && !body.contains("\"role\":\"tool\"");
+
+    if body.contains("\"stream\":true") && !config.streaming {
+        let error = json!({
+            "error": {
+                "message": "Streaming is not supported for this model",
+                "type": "invalid_request_error"
+            }
+        })
+        .to_string();
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 400 Bad Request\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    error.len(),
+                    error
+                )
+                .as_bytes(),
+            )
+            .await?;
+    } else if body.contains("\"stream\":true") {
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n",
+            )
+            .await?;
+        for i in 0..config.chunks {
+            let event = json!({
+                "id": "chatcmpl-mock",
+                "object": "chat.completion.chunk",
+                "created": 0,
+                "model": "mock",
+                "choices": [{
+                    "index": 0,
+                    "delta": { "role": "assistant", "content": format!("token{} ", i) },
+                    "finish_reason": null
+                }]
+            });
+            stream
+                .write_all(format!("data: {}\n\n", event).as_bytes())
+                .await?;
+        }
+        let done = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "mock",
+            "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 8, "completion_tokens": config.chunks, "total_tokens": 8 + config.chunks }
+        });
+        stream
+            .write_all(format!("data: {}\n\ndata: [DONE]\n\n", done).as_bytes())
+            .await?;
+    } else {
+        let (message, finish_reason) = if wants_tool {
+            (
+                json!({
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_mock",
+                        "type": "function",
+                        "function": {
+                            "name": "calculator",
+                            "arguments": "{\"expression\":\"123+456\"}"
+                        }
+                    }]
+                }),
+                "tool_calls",
+            )
+        } else if config.echo {
+            (
+                json!({ "role": "assistant", "content": format!("echo: {}", prompt) }),
+                "stop",
+            )
+        } else {
+            (
+                json!({ "role": "assistant", "content": "模拟提供商的响应" }),
+                "stop",
+            )
+        };
+        let response = json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "mock",
+            "choices": [{ "index": 0, "message": message, "finish_reason": finish_reason }],
+            "usage": { "prompt_tokens": 8, "completion_tokens": 8, "total_tokens": 16 }
+        })
+        .to_string();
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .as_bytes(),
+            )
+            .await?;
+    }
+
+    stream.shutdown().await
+}
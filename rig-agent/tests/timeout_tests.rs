@@ -0,0 +1,60 @@
+//! 模型调用超时集成测试
+
+#[path = "support/mock_provider.rs"]
+mod mock_provider;
+
+use rig_agent::{
+    AgentConfig, AgentError, AgentManager, ChatOptions, ClientConfig, core::ClientRegistry,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_provider_call_timeout() {
+    // 模拟提供商每次请求延迟 500 毫秒
+    let addr = mock_provider::spawn(mock_provider::MockConfig {
+        latency: Duration::from_millis(500),
+        chunks: 4,
+        streaming: true,
+        echo: false,
+    })
+    .await;
+    let mut registry = ClientRegistry::empty();
+    registry
+        .register_openai(
+            ClientConfig::new("openai", "mock")
+                .with_api_key("mock-key")
+                .with_base_url(format!("http://{}/v1", addr)),
+        )
+        .unwrap();
+
+    let manager = AgentManager::new(AgentConfig::new("openai", "mock").with_timeout_ms(100));
+    manager
+        .create_agent("slow".to_string(), None)
+        .await
+        .unwrap();
+
+    // Agent 配置的超时生效
+    let error = manager.chat(&registry, "slow", "你好").await.unwrap_err();
+    assert!(matches!(error, AgentError::Timeout(100)));
+    assert_eq!(error.error_code(), "TIMEOUT");
+    assert!(error.is_retryable());
+    let error = manager.prompt(&registry, "slow", "你好").await.unwrap_err();
+    assert!(matches!(error, AgentError::Timeout(100)));
+
+    // 超时后 Agent 锁已释放，单次请求可以放宽超时
+    let options = ChatOptions::default()
+        .with_timeout_ms(5_000)
+        .with_bypass_cache(true);
+    let response = manager
+        .chat_with_options(&registry, "slow", "你好", options.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.content, "模拟提供商的响应");
+
+    // 也可以收紧超时
+    let error = manager
+        .prompt_with_options(&registry, "slow", "你好", options.with_timeout_ms(50))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, AgentError::Timeout(50)));
+}
@@ -3,6 +3,8 @@ use std::sync::Arc;
 use tauri::{async_runtime::Mutex, AppHandle, State};
 use tracing::info;
 
+use crate::notifications::{notify, NotificationKind};
+
 /// Tauri-managed state for the rig-agent.
 /// We use a Mutex-guarded Option because the AgentManager is initialized asynchronously
 /// based on frontend configuration.
@@ -34,12 +36,23 @@ pub async fn initialize_agent(
 /// Sends a message to the agent and starts the conversation stream.
 /// The agent will send back responses (AgentResponse) via Tauri events.
 #[tauri::command]
-pub async fn send_agent_message(message: String, state: State<'_, AgentState>) -> AgentResult<()> {
+pub async fn send_agent_message(
+    message: String,
+    state: State<'_, AgentState>,
+    app_handle: AppHandle,
+) -> AgentResult<()> {
     let mut guard = state.0.lock().await;
     if let Some(manager) = guard.as_mut() {
         // The `send_message` method will internally use the TauriAgentAdapter
         // to stream responses back to the frontend.
         manager.send_message(message.into()).await?;
+        notify(
+            &app_handle,
+            NotificationKind::AgentTaskFinished,
+            "Agent 任务完成",
+            "Agent 已完成回复",
+        )
+        .await;
         Ok(())
     } else {
         Err(AgentError::NotInitialized)
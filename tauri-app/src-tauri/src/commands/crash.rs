@@ -0,0 +1,33 @@
+//! 崩溃报告命令
+
+use crate::crash::CrashState;
+use iroh_node::{upload_crash_report as upload_report, CrashReport};
+use tauri::State;
+use tracing::info;
+
+/// 获取尚未处理的崩溃报告
+#[tauri::command]
+pub async fn list_crash_reports(state: State<'_, CrashState>) -> Result<Vec<CrashReport>, String> {
+    Ok(state.pending())
+}
+
+/// 忽略并删除崩溃报告
+#[tauri::command]
+pub async fn dismiss_crash_report(state: State<'_, CrashState>, id: String) -> Result<(), String> {
+    state.reporter().dismiss(&id).map_err(|e| e.to_string())
+}
+
+/// 上传崩溃报告，成功后删除本地副本
+#[tauri::command]
+pub async fn upload_crash_report(state: State<'_, CrashState>, id: String) -> Result<(), String> {
+    let url = state
+        .upload_url()
+        .ok_or_else(|| "未配置崩溃报告上传地址".to_string())?;
+    let report = state.reporter().get(&id).map_err(|e| e.to_string())?;
+
+    upload_report(url, &report)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("已上传崩溃报告: {}", id);
+    state.reporter().dismiss(&id).map_err(|e| e.to_string())
+}
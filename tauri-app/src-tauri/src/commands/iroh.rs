@@ -5,13 +5,15 @@ use iroh_node::{
         AppendFileRequest, GetBlobRequest, GetShareCodeResponse, RemoveFileRequest,
         TauriAdapter, TauriEventEmitter,
     },
-    ConfigBuilder, DownloadRequest, RemoveRequest, UploadRequest,
+    ConfigBuilder, DownloadRequest, Invite, InviteCode, RemoveRequest, UploadRequest,
 };
 use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, sync::Arc};
 use tauri::{AppHandle, Manager, Runtime, State};
 use tracing::{error, info};
 
+use crate::notifications::{notify, NotificationKind};
+
 /// Tauri应用状态
 pub struct IrohAppState {
     adapter: Arc<TauriAdapter<AppEventEmitter>>,
@@ -86,9 +88,27 @@ pub async fn get_share_code(
     })
 }
 
+/// 获取分享邀请链接与二维码，便于移动端扫码获取
+#[tauri::command]
+pub async fn get_share_invite(
+    state: State<'_, IrohAppState>,
+    name: Option<String>,
+) -> Result<InviteCode, String> {
+    let response = state
+        .adapter()
+        .get_share_code()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Invite::share(response.doc_ticket, name)
+        .and_then(|invite| invite.to_code())
+        .map_err(|e| format!("生成分享邀请失败: {}", e))
+}
+
 /// 下载文件
 #[tauri::command]
 pub async fn get_blob(
+    app: AppHandle,
     state: State<'_, IrohAppState>,
     request: GetBlobRequest,
 ) -> Result<String, String> {
@@ -97,28 +117,48 @@ pub async fn get_blob(
         download_dir: None,
     };
 
-    state
+    let result = state
         .adapter()
         .download_files(download_request)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    notify(
+        &app,
+        NotificationKind::TransferComplete,
+        "下载完成",
+        &result,
+    )
+    .await;
+    Ok(result)
 }
 
 /// 上传文件
 #[tauri::command]
 pub async fn append_file(
+    app: AppHandle,
     state: State<'_, IrohAppState>,
     request: AppendFileRequest,
 ) -> Result<(), String> {
+    let file_path = PathBuf::from(request.file_path);
     let upload_request = UploadRequest {
-        file_path: PathBuf::from(request.file_path),
+        file_path: file_path.clone(),
     };
 
     state
         .adapter()
         .upload_file(upload_request)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+
+    notify(
+        &app,
+        NotificationKind::TransferComplete,
+        "上传完成",
+        &file_path.display().to_string(),
+    )
+    .await;
+    Ok(())
 }
 
 /// 删除文件
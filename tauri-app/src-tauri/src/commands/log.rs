@@ -0,0 +1,25 @@
+//! 日志查看命令
+
+use iroh_node::{logs_to_text, LogBuffer, LogEntry, LogQuery};
+
+/// 查询最近的日志
+#[tauri::command]
+pub async fn get_logs(query: Option<LogQuery>) -> Result<Vec<LogEntry>, String> {
+    LogBuffer::global()
+        .query(&query.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// 将日志导出为纯文本，便于附在问题反馈中
+#[tauri::command]
+pub async fn export_logs(query: Option<LogQuery>) -> Result<String, String> {
+    let entries = get_logs(query).await?;
+    Ok(logs_to_text(&entries))
+}
+
+/// 清空日志缓冲区
+#[tauri::command]
+pub async fn clear_logs() -> Result<(), String> {
+    LogBuffer::global().clear();
+    Ok(())
+}
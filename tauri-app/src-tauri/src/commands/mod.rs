@@ -1,3 +1,9 @@
 pub mod agent;
+pub mod crash;
 pub mod default;
-pub mod iroh;
\ No newline at end of file
+pub mod iroh;
+pub mod log;
+pub mod notification;
+pub mod profile;
+pub mod room;
+pub mod update;
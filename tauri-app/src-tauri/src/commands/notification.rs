@@ -0,0 +1,23 @@
+//! 通知设置命令
+
+use crate::notifications::{NotificationSettings, NotificationState};
+use tauri::State;
+use tracing::info;
+
+/// 获取通知设置
+#[tauri::command]
+pub async fn get_notification_settings(
+    state: State<'_, NotificationState>,
+) -> Result<NotificationSettings, String> {
+    Ok(state.settings().await)
+}
+
+/// 更新通知设置
+#[tauri::command]
+pub async fn update_notification_settings(
+    state: State<'_, NotificationState>,
+    settings: NotificationSettings,
+) -> Result<(), String> {
+    info!("更新通知设置: {:?}", settings);
+    state.update(settings).await
+}
@@ -0,0 +1,83 @@
+//! 配置档案命令
+
+use crate::{
+    notifications::watch_active_node,
+    profiles::{Profile, ProfileState, ProfileSummary},
+    rooms::watch_room_members,
+};
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{error, info};
+
+/// 档案切换事件
+const PROFILE_SWITCHED_EVENT: &str = "profile-switched";
+
+/// 列出所有档案
+#[tauri::command]
+pub async fn list_profiles(state: State<'_, ProfileState>) -> Result<Vec<ProfileSummary>, String> {
+    let active = state.active_name().await;
+    Ok(state.store().read().await.summaries(active.as_deref()))
+}
+
+/// 获取当前激活的档案名称
+#[tauri::command]
+pub async fn get_active_profile(state: State<'_, ProfileState>) -> Result<Option<String>, String> {
+    Ok(state.active_name().await)
+}
+
+/// 创建或更新档案（API 密钥为空时保留原有密钥）
+#[tauri::command]
+pub async fn save_profile(
+    state: State<'_, ProfileState>,
+    mut profile: Profile,
+) -> Result<(), String> {
+    let mut store = state.store().write().await;
+    if profile.api_keys.is_empty() {
+        if let Some(existing) = store.get(&profile.name) {
+            profile.api_keys = existing.api_keys.clone();
+        }
+    }
+    info!("保存档案: {}", profile.name);
+    store.upsert(profile)
+}
+
+/// 设置档案的 API 密钥
+#[tauri::command]
+pub async fn set_profile_api_keys(
+    state: State<'_, ProfileState>,
+    name: String,
+    api_keys: HashMap<String, String>,
+) -> Result<(), String> {
+    let mut store = state.store().write().await;
+    let mut profile = store
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| format!("档案不存在: {}", name))?;
+    profile.api_keys = api_keys;
+    store.upsert(profile)
+}
+
+/// 删除档案，不能删除当前激活的档案
+#[tauri::command]
+pub async fn delete_profile(state: State<'_, ProfileState>, name: String) -> Result<(), String> {
+    if state.active_name().await.as_deref() == Some(name.as_str()) {
+        return Err("不能删除当前激活的档案".to_string());
+    }
+    state.store().write().await.remove(&name)
+}
+
+/// 切换到指定档案
+#[tauri::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    state: State<'_, ProfileState>,
+    name: String,
+) -> Result<(), String> {
+    state.activate(&name).await?;
+    watch_active_node(&app).await;
+    watch_room_members(&app).await;
+    if let Err(e) = app.emit(PROFILE_SWITCHED_EVENT, &name) {
+        error!("发送档案切换事件失败: {}", e);
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! 房间目录命令
+
+use crate::{
+    profiles::{ActiveProfile, ProfileState},
+    rooms::{Room, RoomUpdate},
+};
+use iroh_node::{Invite, InviteCode, InviteKind, P2PNode};
+use tauri::State;
+use tracing::{info, warn};
+
+fn active_profile(active: &mut Option<ActiveProfile>) -> Result<&mut ActiveProfile, String> {
+    active.as_mut().ok_or_else(|| "没有激活的档案".to_string())
+}
+
+fn active_node(active: &ActiveProfile) -> Result<&P2PNode, String> {
+    active
+        .node
+        .as_ref()
+        .ok_or_else(|| "当前档案未启动 P2P 节点".to_string())
+}
+
+/// 加入话题并设置房间名称作为话题标签，返回话题ID与票据
+async fn join_room_topic(
+    node: &P2PNode,
+    ticket: Option<&str>,
+    name: &str,
+) -> Result<(String, String), String> {
+    let (topic_id, ticket) = node
+        .join_topic(None, ticket)
+        .await
+        .map_err(|e| format!("加入话题失败: {}", e))?;
+    if let Err(e) = node
+        .set_topic_label(&topic_id, Some(name.to_string()))
+        .await
+    {
+        warn!("设置话题标签失败: {}", e);
+    }
+    Ok((topic_id.to_string(), ticket))
+}
+
+/// 列出房间
+#[tauri::command]
+pub async fn list_rooms(state: State<'_, ProfileState>) -> Result<Vec<Room>, String> {
+    let mut active = state.active().write().await;
+    Ok(active_profile(&mut active)?.rooms.list())
+}
+
+/// 获取房间
+#[tauri::command]
+pub async fn get_room(state: State<'_, ProfileState>, id: String) -> Result<Room, String> {
+    let mut active = state.active().write().await;
+    active_profile(&mut active)?
+        .rooms
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("房间不存在: {}", id))
+}
+
+/// 创建房间（创建新话题）
+#[tauri::command]
+pub async fn create_room(
+    state: State<'_, ProfileState>,
+    name: String,
+    description: Option<String>,
+) -> Result<Room, String> {
+    let mut active = state.active().write().await;
+    let active = active_profile(&mut active)?;
+    let (topic_id, ticket) = join_room_topic(active_node(active)?, None, &name).await?;
+
+    let room = active.rooms.add(name, description, topic_id, ticket)?;
+    info!("已创建房间: {} ({})", room.name, room.id);
+    Ok(room)
+}
+
+/// 通过票据或邀请链接加入房间并记入目录，未指定名称时使用邀请中的名称
+#[tauri::command]
+pub async fn join_room(
+    state: State<'_, ProfileState>,
+    ticket: String,
+    name: Option<String>,
+    description: Option<String>,
+) -> Result<Room, String> {
+    let (ticket, name) = match ticket.parse::<Invite>() {
+        Ok(invite) if invite.kind == InviteKind::Room => (invite.ticket, name.or(invite.name)),
+        Ok(_) => return Err("不是房间邀请链接".to_string()),
+        Err(_) => (ticket, name),
+    };
+    let name = name.ok_or_else(|| "房间名称不能为空".to_string())?;
+
+    let mut active = state.active().write().await;
+    let active = active_profile(&mut active)?;
+    let (topic_id, _) = join_room_topic(active_node(active)?, Some(&ticket), &name).await?;
+
+    let room = active.rooms.add(name, description, topic_id, ticket)?;
+    info!("已加入房间: {} ({})", room.name, room.id);
+    Ok(room)
+}
+
+/// 使用保存的票据重新加入房间（例如重启之后）
+#[tauri::command]
+pub async fn open_room(state: State<'_, ProfileState>, id: String) -> Result<Room, String> {
+    let mut active = state.active().write().await;
+    let active = active_profile(&mut active)?;
+    let room = active
+        .rooms
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("房间不存在: {}", id))?;
+
+    join_room_topic(active_node(active)?, Some(&room.ticket), &room.name).await?;
+    Ok(room)
+}
+
+/// 获取房间邀请链接与二维码
+#[tauri::command]
+pub async fn get_room_invite(
+    state: State<'_, ProfileState>,
+    id: String,
+) -> Result<InviteCode, String> {
+    let mut active = state.active().write().await;
+    let room = active_profile(&mut active)?
+        .rooms
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("房间不存在: {}", id))?;
+
+    Invite::room(room.ticket, Some(room.name))
+        .and_then(|invite| invite.to_code())
+        .map_err(|e| format!("生成房间邀请失败: {}", e))
+}
+
+/// 更新房间信息
+#[tauri::command]
+pub async fn update_room(
+    state: State<'_, ProfileState>,
+    id: String,
+    update: RoomUpdate,
+) -> Result<Room, String> {
+    let mut active = state.active().write().await;
+    let active = active_profile(&mut active)?;
+    let room = active.rooms.update(&id, update)?;
+
+    // 同步话题标签
+    if let Some(node) = active.node.as_ref() {
+        if let Ok(topic_id) = room.topic_id.parse() {
+            let _ = node
+                .set_topic_label(&topic_id, Some(room.name.clone()))
+                .await;
+        }
+    }
+    Ok(room)
+}
+
+/// 删除房间，`leave` 为 true 时同时离开话题
+#[tauri::command]
+pub async fn delete_room(
+    state: State<'_, ProfileState>,
+    id: String,
+    leave: bool,
+) -> Result<(), String> {
+    let mut active = state.active().write().await;
+    let active = active_profile(&mut active)?;
+    let room = active.rooms.remove(&id)?;
+
+    if leave {
+        if let Some(node) = active.node.as_ref() {
+            let topic_id = room
+                .topic_id
+                .parse()
+                .map_err(|e| format!("解析话题ID失败: {}", e))?;
+            node.leave_topic(&topic_id)
+                .await
+                .map_err(|e| format!("离开话题失败: {}", e))?;
+        }
+    }
+    info!("已删除房间: {} ({})", room.name, room.id);
+    Ok(())
+}
+
+/// 导出房间目录为 JSON
+#[tauri::command]
+pub async fn export_rooms(state: State<'_, ProfileState>) -> Result<String, String> {
+    let mut active = state.active().write().await;
+    active_profile(&mut active)?.rooms.export()
+}
+
+/// 导入房间目录，返回新增的房间数量
+#[tauri::command]
+pub async fn import_rooms(state: State<'_, ProfileState>, data: String) -> Result<usize, String> {
+    let mut active = state.active().write().await;
+    let imported = active_profile(&mut active)?.rooms.import(&data)?;
+    info!("已导入 {} 个房间", imported);
+    Ok(imported)
+}
@@ -0,0 +1,27 @@
+//! 更新检查命令
+
+use crate::updates::{check_and_notify, UpdateInfo, UpdateSettings, UpdateState};
+use tauri::{AppHandle, State};
+use tracing::info;
+
+/// 获取更新设置
+#[tauri::command]
+pub async fn get_update_settings(state: State<'_, UpdateState>) -> Result<UpdateSettings, String> {
+    Ok(state.settings().await)
+}
+
+/// 更新更新设置，清单签名公钥在构建时内置，不能通过设置修改
+#[tauri::command]
+pub async fn update_update_settings(
+    state: State<'_, UpdateState>,
+    settings: UpdateSettings,
+) -> Result<(), String> {
+    info!("更新更新设置: {:?}", settings);
+    state.update(settings).await
+}
+
+/// 立即检查更新，有新版本时同时发送 `update-available` 事件
+#[tauri::command]
+pub async fn check_for_updates(app: AppHandle) -> Result<Option<UpdateInfo>, String> {
+    check_and_notify(&app).await
+}
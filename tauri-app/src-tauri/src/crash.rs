@@ -0,0 +1,69 @@
+//! 崩溃报告
+//!
+//! 启动时安装panic钩子，并检查上次运行留下的崩溃报告，通知前端询问用户是否分享
+
+use iroh_node::{CrashReport, CrashReporter};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{error, warn};
+
+/// 发现未处理崩溃报告时发送的事件
+pub const CRASH_REPORTS_FOUND_EVENT: &str = "crash-reports-found";
+
+/// 崩溃报告上传地址的环境变量，未设置时不提供上传
+const CRASH_UPLOAD_URL_ENV: &str = "YOCHAT_CRASH_UPLOAD_URL";
+
+/// 崩溃报告状态
+pub struct CrashState {
+    reporter: CrashReporter,
+    upload_url: Option<String>,
+}
+
+impl CrashState {
+    /// 在应用数据目录下安装崩溃报告钩子
+    pub fn install<R: Runtime>(handle: &AppHandle<R>) -> Result<Self, String> {
+        let root = handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+
+        let reporter = CrashReporter::new(root);
+        reporter.install_panic_hook(handle.package_info().version.to_string());
+        Ok(Self {
+            reporter,
+            upload_url: std::env::var(CRASH_UPLOAD_URL_ENV)
+                .ok()
+                .filter(|url| !url.is_empty()),
+        })
+    }
+
+    /// 崩溃报告管理
+    pub fn reporter(&self) -> &CrashReporter {
+        &self.reporter
+    }
+
+    /// 上传地址
+    pub fn upload_url(&self) -> Option<&str> {
+        self.upload_url.as_deref()
+    }
+
+    /// 尚未处理的崩溃报告
+    pub fn pending(&self) -> Vec<CrashReport> {
+        self.reporter.pending().unwrap_or_else(|e| {
+            warn!("读取崩溃报告失败: {}", e);
+            Vec::new()
+        })
+    }
+}
+
+/// 存在上次运行留下的崩溃报告时通知前端
+pub fn notify_pending_reports<R: Runtime>(handle: &AppHandle<R>) {
+    let pending = handle.state::<CrashState>().pending();
+    if pending.is_empty() {
+        return;
+    }
+
+    warn!("发现 {} 份未处理的崩溃报告", pending.len());
+    if let Err(e) = handle.emit(CRASH_REPORTS_FOUND_EVENT, &pending) {
+        error!("发送崩溃报告事件失败: {}", e);
+    }
+}
@@ -1,29 +1,105 @@
-use tauri::Manager;
-use tracing::{error, info, Level};
+use iroh_node::{init_logging, LoggingConfig};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{error, info};
 
 // `commands/mod.rs` 现在负责管理所有命令模块
 mod commands;
+mod crash;
+mod notifications;
+mod profiles;
+mod rooms;
+mod updates;
 use commands::{
     // 引入 agent 相关的命令和状态
     agent::{initialize_agent, send_agent_message, AgentState},
     // 保留现有的 default 和 iroh 命令
+    crash::{dismiss_crash_report, list_crash_reports, upload_crash_report},
     default::{read, write},
-    iroh::{append_file, get_blob, get_share_code, remove_file, setup_iroh_state},
+    iroh::{
+        append_file, get_blob, get_share_code, get_share_invite, remove_file, setup_iroh_state,
+    },
+    log::{clear_logs, export_logs, get_logs},
+    notification::{get_notification_settings, update_notification_settings},
+    profile::{
+        delete_profile, get_active_profile, list_profiles, save_profile, set_profile_api_keys,
+        switch_profile,
+    },
+    room::{
+        create_room, delete_room, export_rooms, get_room, get_room_invite, import_rooms,
+        join_room, list_rooms, open_room, update_room,
+    },
+    update::{check_for_updates, get_update_settings, update_update_settings},
 };
+use crash::{notify_pending_reports, CrashState};
+use notifications::{watch_active_node, NotificationState};
+use profiles::{startup_profile_arg, ProfileState};
+use rooms::watch_room_members;
+use updates::{spawn_update_checker, UpdateState};
+
+/// 日志配置文件名（位于应用数据目录）
+const LOGGING_CONFIG_FILE: &str = "logging.json";
+
+/// 按应用数据目录中的日志配置初始化日志，文件日志默认写入应用日志目录
+fn setup_logging<R: Runtime>(handle: &AppHandle<R>) -> Result<(), String> {
+    let config_path = handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("无法获取应用数据目录: {}", e))?
+        .join(LOGGING_CONFIG_FILE);
+    let log_dir = handle
+        .path()
+        .app_log_dir()
+        .map_err(|e| format!("无法获取应用日志目录: {}", e))?;
+
+    let config = LoggingConfig::load(&config_path)
+        .map_err(|e| e.to_string())?
+        .with_default_directory(log_dir);
+    init_logging(&config).map_err(|e| e.to_string())
+}
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // 初始化日志
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .init();
-
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         // 管理新的 AgentState
         .manage(AgentState::new())
         .setup(|app| {
             let handle = app.handle().clone();
+
+            // 初始化日志；配置无效时退回默认配置，保证日志可用
+            if let Err(err) = setup_logging(&handle) {
+                let _ = init_logging(&LoggingConfig::default());
+                error!("初始化日志失败，使用默认配置: {}", err);
+            }
+
+            // 安装崩溃报告钩子，并提示上次运行留下的崩溃报告
+            app.manage(CrashState::install(&handle)?);
+            notify_pending_reports(&handle);
+
+            app.manage(UpdateState::load(&handle)?);
+            spawn_update_checker(&handle);
+
+            app.manage(NotificationState::load(&handle)?);
+
+            // 加载配置档案，并激活命令行/环境变量指定的档案或上次使用的档案
+            let profile_state = ProfileState::load(&handle)?;
+            app.manage(profile_state);
+            let profile_handle = handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = profile_handle.state::<ProfileState>();
+                let name = match startup_profile_arg() {
+                    Some(name) => name,
+                    None => state.store().read().await.last_active(),
+                };
+                match state.activate(&name).await {
+                    Ok(()) => {
+                        watch_active_node(&profile_handle).await;
+                        watch_room_members(&profile_handle).await;
+                    }
+                    Err(err) => error!("激活档案 {} 失败: {}", name, err),
+                }
+            });
             
             // 异步初始化 iroh 状态 (保留现有功能)
             tauri::async_runtime::spawn(async move {
@@ -48,12 +124,46 @@ pub fn run() {
             write,
             // Iroh commands
             get_share_code,
+            get_share_invite,
             get_blob,
             append_file,
             remove_file,
             // Agent commands
             initialize_agent,
-            send_agent_message
+            send_agent_message,
+            // Profile commands
+            list_profiles,
+            get_active_profile,
+            save_profile,
+            set_profile_api_keys,
+            delete_profile,
+            switch_profile,
+            // Room commands
+            list_rooms,
+            get_room,
+            create_room,
+            join_room,
+            open_room,
+            get_room_invite,
+            update_room,
+            delete_room,
+            export_rooms,
+            import_rooms,
+            // Notification commands
+            get_notification_settings,
+            update_notification_settings,
+            // Log commands
+            get_logs,
+            export_logs,
+            clear_logs,
+            // Crash report commands
+            list_crash_reports,
+            dismiss_crash_report,
+            upload_crash_report,
+            // Update commands
+            get_update_settings,
+            update_update_settings,
+            check_for_updates
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,291 @@
+//! 系统通知
+//!
+//! 在聊天中被提及、Agent 任务完成、文件传输完成时弹出系统通知。
+//! 每类事件可单独开关，并支持免打扰时段；设置保存在应用数据目录中。
+
+use chrono::{Local, NaiveTime};
+use iroh_node::NodeEvent;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf};
+use tauri::{async_runtime::RwLock, AppHandle, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+use tracing::{debug, info, warn};
+
+use crate::profiles::ProfileState;
+
+/// 通知设置文件名
+const SETTINGS_FILE: &str = "notifications.json";
+
+/// 通知事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// 聊天中被提及
+    ChatMention,
+    /// Agent 任务完成
+    AgentTaskFinished,
+    /// 文件传输完成
+    TransferComplete,
+}
+
+impl NotificationKind {
+    /// 所有通知事件类型
+    pub const ALL: [NotificationKind; 3] = [
+        NotificationKind::ChatMention,
+        NotificationKind::AgentTaskFinished,
+        NotificationKind::TransferComplete,
+    ];
+}
+
+/// 免打扰时段（本地时间），开始时间晚于结束时间时表示跨越午夜
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// 开始时间
+    pub start: NaiveTime,
+    /// 结束时间
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// 指定时间是否处于免打扰时段
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// 通知设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// 总开关
+    pub enabled: bool,
+    /// 各事件类型的开关，未列出的类型默认开启
+    pub events: HashMap<NotificationKind, bool>,
+    /// 免打扰时段
+    pub quiet_hours: Option<QuietHours>,
+    /// 除 `@节点名称` 外，视为提及的关键词
+    pub mention_keywords: Vec<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            events: NotificationKind::ALL
+                .iter()
+                .map(|&kind| (kind, true))
+                .collect(),
+            quiet_hours: None,
+            mention_keywords: Vec::new(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// 指定事件当前是否应该弹出通知
+    pub fn should_notify(&self, kind: NotificationKind) -> bool {
+        if !self.enabled || !self.events.get(&kind).copied().unwrap_or(true) {
+            return false;
+        }
+        !self
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet| quiet.contains(Local::now().time()))
+    }
+
+    /// 聊天消息是否提及本节点
+    pub fn is_mention(&self, text: &str, node_name: Option<&str>) -> bool {
+        let text = text.to_lowercase();
+        node_name
+            .map(|name| format!("@{}", name.to_lowercase()))
+            .into_iter()
+            .chain(self.mention_keywords.iter().map(|k| k.to_lowercase()))
+            .any(|needle| !needle.is_empty() && text.contains(&needle))
+    }
+}
+
+/// Tauri 托管的通知状态
+pub struct NotificationState {
+    path: PathBuf,
+    settings: RwLock<NotificationSettings>,
+}
+
+impl NotificationState {
+    /// 从应用数据目录加载通知设置，不存在时使用默认设置
+    pub fn load<R: Runtime>(handle: &AppHandle<R>) -> Result<Self, String> {
+        let root = handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+        fs::create_dir_all(&root).map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+
+        let path = root.join(SETTINGS_FILE);
+        let settings = if path.exists() {
+            let data = fs::read(&path).map_err(|e| format!("读取通知设置失败: {}", e))?;
+            serde_json::from_slice(&data).map_err(|e| format!("解析通知设置失败: {}", e))?
+        } else {
+            NotificationSettings::default()
+        };
+
+        Ok(Self {
+            path,
+            settings: RwLock::new(settings),
+        })
+    }
+
+    /// 获取通知设置
+    pub async fn settings(&self) -> NotificationSettings {
+        self.settings.read().await.clone()
+    }
+
+    /// 更新并保存通知设置
+    pub async fn update(&self, settings: NotificationSettings) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(&settings)
+            .map_err(|e| format!("序列化通知设置失败: {}", e))?;
+        fs::write(&self.path, data).map_err(|e| format!("写入通知设置失败: {}", e))?;
+        *self.settings.write().await = settings;
+        Ok(())
+    }
+}
+
+/// 按设置弹出系统通知，被关闭或处于免打扰时段时忽略
+pub async fn notify<R: Runtime>(
+    app: &AppHandle<R>,
+    kind: NotificationKind,
+    title: &str,
+    body: &str,
+) {
+    let Some(state) = app.try_state::<NotificationState>() else {
+        return;
+    };
+    if !state.settings.read().await.should_notify(kind) {
+        debug!("跳过 {:?} 通知", kind);
+        return;
+    }
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        warn!("发送系统通知失败: {}", e);
+    }
+}
+
+/// 监听当前激活档案的 P2P 节点，在聊天中被提及时弹出通知，节点停止后自动结束
+pub async fn watch_active_node<R: Runtime>(app: &AppHandle<R>) {
+    let profiles = app.state::<ProfileState>();
+    let (mut receiver, node_name) = {
+        let active = profiles.active().read().await;
+        let Some(node) = active.as_ref().and_then(|a| a.node.as_ref()) else {
+            return;
+        };
+        let store = profiles.store().read().await;
+        let node_name = active
+            .as_ref()
+            .and_then(|a| store.get(&a.name))
+            .and_then(|profile| profile.node_config.name.clone());
+        (node.subscribe_events(), node_name)
+    };
+
+    info!("开始监听聊天提及通知");
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(NodeEvent::ChatReceived { entry, .. }) => {
+                    let mentioned = match app.try_state::<NotificationState>() {
+                        Some(state) => state
+                            .settings
+                            .read()
+                            .await
+                            .is_mention(&entry.text, node_name.as_deref()),
+                        None => false,
+                    };
+                    if mentioned {
+                        let from: String = entry.from.chars().take(8).collect();
+                        notify(
+                            &app,
+                            NotificationKind::ChatMention,
+                            &format!("{} 提到了你", from),
+                            &entry.text,
+                        )
+                        .await;
+                    }
+                }
+                Ok(NodeEvent::Stopped { .. }) => break,
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("通知监听落后，丢弃 {} 条节点事件", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        debug!("聊天提及通知监听结束");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let daytime = QuietHours {
+            start: time(12, 0),
+            end: time(14, 0),
+        };
+        assert!(daytime.contains(time(12, 0)));
+        assert!(daytime.contains(time(13, 30)));
+        assert!(!daytime.contains(time(14, 0)));
+        assert!(!daytime.contains(time(8, 0)));
+
+        // 跨越午夜
+        let overnight = QuietHours {
+            start: time(22, 0),
+            end: time(7, 0),
+        };
+        assert!(overnight.contains(time(23, 30)));
+        assert!(overnight.contains(time(0, 0)));
+        assert!(overnight.contains(time(6, 59)));
+        assert!(!overnight.contains(time(7, 0)));
+        assert!(!overnight.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn test_notification_toggles() {
+        let settings = NotificationSettings::default();
+        assert!(NotificationKind::ALL
+            .iter()
+            .all(|&kind| settings.should_notify(kind)));
+
+        // 未列出的事件类型默认开启
+        let settings: NotificationSettings =
+            serde_json::from_str(r#"{"events": {"agent_task_finished": false}}"#).unwrap();
+        assert!(settings.enabled);
+        assert!(!settings.should_notify(NotificationKind::AgentTaskFinished));
+        assert!(settings.should_notify(NotificationKind::ChatMention));
+
+        let disabled = NotificationSettings {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(!disabled.should_notify(NotificationKind::ChatMention));
+    }
+
+    #[test]
+    fn test_is_mention() {
+        let settings = NotificationSettings {
+            mention_keywords: vec!["Release".to_string(), String::new()],
+            ..Default::default()
+        };
+        assert!(settings.is_mention("@Alice 看一下", Some("alice")));
+        assert!(!settings.is_mention("alice 看一下", Some("alice")));
+        assert!(settings.is_mention("今晚 release", None));
+        assert!(!settings.is_mention("随便聊聊", Some("alice")));
+    }
+}
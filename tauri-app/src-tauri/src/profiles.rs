@@ -0,0 +1,470 @@
+//! 多配置档案（Profile）
+//!
+//! 每个档案拥有独立的 Agent 配置、API 密钥、节点身份与数据目录，
+//! 同一时间只有一个档案处于激活状态，切换时会释放上一个档案的运行时状态。
+
+use iroh_node::{NodeConfig, P2PNode};
+use rig_agent::{
+    core::ClientRegistry, AgentConfig, ClientConfig, SessionStore, StandaloneAgentAdapter,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tauri::{async_runtime::RwLock, AppHandle, Manager, Runtime};
+use tracing::{info, warn};
+
+use crate::rooms::RoomDirectory;
+
+/// 默认档案名称
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// 档案列表文件名
+const PROFILES_FILE: &str = "profiles.json";
+
+/// 档案数据目录中的地址簿文件名
+const ADDRESS_BOOK_FILE: &str = "address_book.json";
+
+/// 档案数据目录中的共享记忆文件名
+const SHARED_MEMORY_FILE: &str = "shared_memory.json";
+
+/// 档案数据目录中的会话数据库文件名
+const SESSIONS_FILE: &str = "sessions.db";
+
+/// 提供商与其默认模型，用于注册档案密钥对应的客户端
+const PROVIDER_DEFAULT_MODELS: &[(&str, &str)] = &[
+    ("openai", "gpt-3.5-turbo"),
+    ("anthropic", "claude-3-sonnet-20240229"),
+    ("gemini", "gemini-pro"),
+    ("cohere", "command-r"),
+];
+
+/// 配置档案
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// 档案名称
+    pub name: String,
+    /// Agent 默认配置
+    pub agent_config: AgentConfig,
+    /// 各提供商的 API 密钥
+    #[serde(default)]
+    pub api_keys: HashMap<String, String>,
+    /// 节点配置（首次启动后会写入生成的密钥，保持节点身份稳定）
+    #[serde(default)]
+    pub node_config: NodeConfig,
+    /// 是否在激活时启动 P2P 节点
+    #[serde(default)]
+    pub start_node: bool,
+}
+
+impl Profile {
+    /// 创建档案
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            agent_config: AgentConfig::default(),
+            api_keys: HashMap::new(),
+            node_config: NodeConfig::default(),
+            start_node: false,
+        }
+    }
+
+    /// 档案名称只允许字母、数字、`-` 和 `_`，以便直接作为目录名
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("无效的档案名称: {}", name));
+        }
+        Ok(())
+    }
+}
+
+/// 档案摘要（不包含 API 密钥）
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSummary {
+    /// 档案名称
+    pub name: String,
+    /// 提供商
+    pub provider: String,
+    /// 模型
+    pub model: String,
+    /// 已配置密钥的提供商
+    pub providers_with_keys: Vec<String>,
+    /// 数据目录
+    pub data_root: PathBuf,
+    /// 是否为当前激活档案
+    pub active: bool,
+}
+
+/// 档案存储文件格式
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfilesFile {
+    /// 上次激活的档案
+    last_active: Option<String>,
+    /// 所有档案
+    profiles: Vec<Profile>,
+}
+
+/// 档案存储
+pub struct ProfileStore {
+    /// 档案根目录（每个档案的数据目录位于其下）
+    root: PathBuf,
+    file: ProfilesFile,
+}
+
+impl ProfileStore {
+    /// 从目录加载档案，不存在时创建默认档案
+    pub fn load(root: impl Into<PathBuf>) -> Result<Self, String> {
+        let root = root.into();
+        fs::create_dir_all(&root).map_err(|e| format!("创建档案目录失败: {}", e))?;
+
+        let path = root.join(PROFILES_FILE);
+        let mut file: ProfilesFile = if path.exists() {
+            let data = fs::read(&path).map_err(|e| format!("读取档案文件失败: {}", e))?;
+            serde_json::from_slice(&data).map_err(|e| format!("解析档案文件失败: {}", e))?
+        } else {
+            ProfilesFile::default()
+        };
+
+        if file.profiles.is_empty() {
+            file.profiles.push(Profile::new(DEFAULT_PROFILE));
+        }
+
+        let store = Self { root, file };
+        store.save()?;
+        Ok(store)
+    }
+
+    /// 保存档案文件
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(&self.file)
+            .map_err(|e| format!("序列化档案失败: {}", e))?;
+        fs::write(self.root.join(PROFILES_FILE), data)
+            .map_err(|e| format!("写入档案文件失败: {}", e))
+    }
+
+    /// 档案的数据目录
+    pub fn data_root(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    /// 获取档案
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.file.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// 档案名称列表
+    pub fn names(&self) -> Vec<String> {
+        self.file.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// 上次激活的档案，不存在时返回第一个档案
+    pub fn last_active(&self) -> String {
+        self.file
+            .last_active
+            .clone()
+            .filter(|name| self.get(name).is_some())
+            .unwrap_or_else(|| self.file.profiles[0].name.clone())
+    }
+
+    /// 记录激活的档案
+    pub fn set_last_active(&mut self, name: &str) -> Result<(), String> {
+        self.file.last_active = Some(name.to_string());
+        self.save()
+    }
+
+    /// 新增或更新档案
+    pub fn upsert(&mut self, profile: Profile) -> Result<(), String> {
+        Profile::validate_name(&profile.name)?;
+        match self.file.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.file.profiles.push(profile),
+        }
+        self.save()
+    }
+
+    /// 删除档案（保留其数据目录）
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        if self.file.profiles.len() == 1 {
+            return Err("至少需要保留一个档案".to_string());
+        }
+        let before = self.file.profiles.len();
+        self.file.profiles.retain(|p| p.name != name);
+        if self.file.profiles.len() == before {
+            return Err(format!("档案不存在: {}", name));
+        }
+        self.save()
+    }
+
+    /// 档案摘要
+    pub fn summaries(&self, active: Option<&str>) -> Vec<ProfileSummary> {
+        self.file
+            .profiles
+            .iter()
+            .map(|profile| {
+                let mut providers_with_keys: Vec<String> =
+                    profile.api_keys.keys().cloned().collect();
+                providers_with_keys.sort();
+                ProfileSummary {
+                    name: profile.name.clone(),
+                    provider: profile.agent_config.provider.clone(),
+                    model: profile.agent_config.model.clone(),
+                    providers_with_keys,
+                    data_root: self.data_root(&profile.name),
+                    active: active == Some(profile.name.as_str()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 激活档案的运行时状态，切换档案时整体丢弃
+pub struct ActiveProfile {
+    /// 档案名称
+    pub name: String,
+    /// 数据目录
+    pub data_root: PathBuf,
+    /// Agent 适配器，对话历史保存在数据目录的会话数据库中
+    pub agent: StandaloneAgentAdapter,
+    /// P2P 节点
+    pub node: Option<P2PNode>,
+    /// 房间目录
+    pub rooms: RoomDirectory,
+}
+
+/// Tauri 托管的档案状态
+pub struct ProfileState {
+    store: RwLock<ProfileStore>,
+    active: RwLock<Option<ActiveProfile>>,
+}
+
+impl ProfileState {
+    /// 从应用配置目录加载档案
+    pub fn load<R: Runtime>(handle: &AppHandle<R>) -> Result<Self, String> {
+        let root = handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("无法获取应用数据目录: {}", e))?
+            .join("profiles");
+
+        Ok(Self {
+            store: RwLock::new(ProfileStore::load(root)?),
+            active: RwLock::new(None),
+        })
+    }
+
+    /// 档案存储
+    pub fn store(&self) -> &RwLock<ProfileStore> {
+        &self.store
+    }
+
+    /// 当前激活档案
+    pub fn active(&self) -> &RwLock<Option<ActiveProfile>> {
+        &self.active
+    }
+
+    /// 当前激活档案名称
+    pub async fn active_name(&self) -> Option<String> {
+        self.active.read().await.as_ref().map(|a| a.name.clone())
+    }
+
+    /// 激活档案：释放当前档案的运行时，再按新档案创建 Agent 与节点
+    pub async fn activate(&self, name: &str) -> Result<(), String> {
+        let mut active = self.active.write().await;
+        let mut store = self.store.write().await;
+        let mut profile = store
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("档案不存在: {}", name))?;
+
+        if let Some(previous) = active.take() {
+            info!("停用档案: {}", previous.name);
+            if let Some(node) = previous.node {
+                if let Err(e) = node.stop().await {
+                    warn!("停止档案 {} 的节点失败: {}", previous.name, e);
+                }
+            }
+        }
+
+        let data_root = store.data_root(name);
+        fs::create_dir_all(&data_root).map_err(|e| format!("创建档案数据目录失败: {}", e))?;
+        let rooms = RoomDirectory::load(&data_root)?;
+
+        let agent = StandaloneAgentAdapter::with_registry(
+            profile.agent_config.clone(),
+            client_registry(&profile)?,
+        );
+        let sessions = SessionStore::open(data_root.join(SESSIONS_FILE))
+            .map_err(|e| format!("打开档案会话数据库失败: {}", e))?;
+        agent.set_session_store(Arc::new(sessions)).await;
+
+        let node = if profile.start_node {
+            let node = start_profile_node(&profile.node_config, &data_root).await?;
+            // 首次启动时保存生成的密钥，保证节点身份在重启后不变
+            if profile.node_config.secret_key.is_none() {
+                profile.node_config.secret_key = Some(node.secret_key().to_string());
+                store.upsert(profile.clone())?;
+            }
+            Some(node)
+        } else {
+            None
+        };
+
+        store.set_last_active(name)?;
+        *active = Some(ActiveProfile {
+            name: name.to_string(),
+            data_root,
+            agent,
+            node,
+            rooms,
+        });
+
+        info!("已激活档案: {}", name);
+        Ok(())
+    }
+}
+
+/// 启动档案的 P2P 节点，未指定地址簿路径时保存在档案数据目录中
+async fn start_profile_node(config: &NodeConfig, data_root: &Path) -> Result<P2PNode, String> {
+    let mut config = config.clone();
+    if config.address_book_path.is_none() {
+        config.address_book_path = Some(data_root.join(ADDRESS_BOOK_FILE));
+    }
+    if let Some(memory) = config.shared_memory.as_mut().filter(|memory| memory.path.is_none()) {
+        memory.path = Some(data_root.join(SHARED_MEMORY_FILE));
+    }
+
+    let mut node = P2PNode::new(config.clone())
+        .await
+        .map_err(|e| format!("创建节点失败: {}", e))?;
+    if let Some(name) = &config.name {
+        node.set_name(name.clone());
+    }
+    node.start()
+        .await
+        .map_err(|e| format!("启动节点失败: {}", e))?;
+    Ok(node)
+}
+
+/// 按档案的 API 密钥创建客户端注册表，密钥只保存在注册表的客户端配置中，不写入进程环境。
+/// 未配置任何密钥的档案沿用系统环境变量；否则只注册配置了密钥的提供商，避免沿用其他档案或系统的密钥
+fn client_registry(profile: &Profile) -> Result<ClientRegistry, String> {
+    if profile.api_keys.is_empty() {
+        return Ok(ClientRegistry::new());
+    }
+    let mut registry = ClientRegistry::empty();
+    for (provider, key) in &profile.api_keys {
+        registry
+            .register_client(
+                provider,
+                ClientConfig::new(provider.clone(), default_model(profile, provider))
+                    .with_api_key(key.clone()),
+            )
+            .map_err(|e| format!("注册 {} 客户端失败: {}", provider, e))?;
+    }
+    Ok(registry)
+}
+
+/// 提供商的默认模型：档案使用的提供商取档案的模型
+fn default_model(profile: &Profile, provider: &str) -> String {
+    if profile.agent_config.provider == provider {
+        return profile.agent_config.model.clone();
+    }
+    PROVIDER_DEFAULT_MODELS
+        .iter()
+        .find(|(name, _)| *name == provider)
+        .map_or_else(
+            || profile.agent_config.model.clone(),
+            |(_, model)| model.to_string(),
+        )
+}
+
+/// 从命令行参数（`--profile <name>`）或环境变量 `TAURI_APP_PROFILE` 读取启动档案
+pub fn startup_profile_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_string());
+        }
+        if arg == "--profile" {
+            return args.next();
+        }
+    }
+    std::env::var("TAURI_APP_PROFILE").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "tauri-app-profiles-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn test_profile_store_persists_profiles() {
+        let root = temp_root("store");
+        let mut store = ProfileStore::load(&root).unwrap();
+        assert_eq!(store.names(), [DEFAULT_PROFILE]);
+        assert_eq!(store.last_active(), DEFAULT_PROFILE);
+
+        let mut work = Profile::new("work");
+        work.api_keys
+            .insert("openai".to_string(), "sk-work".to_string());
+        store.upsert(work).unwrap();
+        assert!(store.upsert(Profile::new("../escape")).is_err());
+        store.set_last_active("work").unwrap();
+
+        // 重新加载后档案与上次激活的档案不变，各档案的数据目录互不重叠
+        let mut store = ProfileStore::load(&root).unwrap();
+        assert_eq!(store.names(), [DEFAULT_PROFILE, "work"]);
+        assert_eq!(store.last_active(), "work");
+        assert_ne!(store.data_root("work"), store.data_root(DEFAULT_PROFILE));
+        let summaries = store.summaries(Some("work"));
+        assert_eq!(summaries[1].providers_with_keys, ["openai"]);
+        assert!(summaries[1].active);
+        assert!(!summaries[0].active);
+
+        // 删除上次激活的档案后回退到第一个档案，最后一个档案不能删除
+        store.remove("work").unwrap();
+        assert_eq!(store.last_active(), DEFAULT_PROFILE);
+        assert!(store.remove("work").is_err());
+        assert!(store.remove(DEFAULT_PROFILE).is_err());
+
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_client_registry_uses_only_profile_keys() {
+        let mut profile = Profile::new("work");
+        profile.agent_config = AgentConfig::new("anthropic", "claude-3-haiku-20240307");
+        profile
+            .api_keys
+            .insert("anthropic".to_string(), "sk-ant".to_string());
+        profile
+            .api_keys
+            .insert("openai".to_string(), "sk-openai".to_string());
+
+        let registry = client_registry(&profile).unwrap();
+        let anthropic = registry.get_client_config("anthropic").unwrap();
+        assert_eq!(anthropic.api_key.as_deref(), Some("sk-ant"));
+        assert_eq!(anthropic.default_model, "claude-3-haiku-20240307");
+        let openai = registry.get_client_config("openai").unwrap();
+        assert_eq!(openai.api_key.as_deref(), Some("sk-openai"));
+        assert_eq!(openai.default_model, "gpt-3.5-turbo");
+        // 没有配置密钥的提供商不会沿用系统环境变量中的密钥
+        assert!(registry.get_client_config("gemini").is_none());
+    }
+}
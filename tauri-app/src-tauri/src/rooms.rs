@@ -0,0 +1,407 @@
+//! 房间目录
+//!
+//! 在档案数据目录中持久化“我的房间”：名称、描述、话题、票据与成员名称，
+//! 重启后仍可列出并通过票据重新加入。支持导出为 JSON 并在其他设备导入。
+
+use chrono::{DateTime, Utc};
+use iroh_node::NodeEvent;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tauri::{AppHandle, Manager, Runtime};
+use tracing::{debug, info, warn};
+
+use crate::profiles::ProfileState;
+
+/// 房间目录文件名
+const ROOMS_FILE: &str = "rooms.json";
+
+/// 导出格式版本
+const EXPORT_VERSION: u32 = 1;
+
+/// 房间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Room {
+    /// 房间ID
+    pub id: String,
+    /// 房间名称
+    pub name: String,
+    /// 描述
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 话题ID
+    pub topic_id: String,
+    /// 加入房间用的票据
+    pub ticket: String,
+    /// 见过的成员名称
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// 创建时间
+    pub created_at: DateTime<Utc>,
+}
+
+/// 房间信息更新，未设置的字段保持不变
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RoomUpdate {
+    /// 房间名称
+    pub name: Option<String>,
+    /// 描述，传入空字符串时清除
+    pub description: Option<String>,
+    /// 成员名称
+    pub members: Option<Vec<String>>,
+}
+
+/// 导出文件格式
+#[derive(Debug, Serialize, Deserialize)]
+struct RoomExport {
+    version: u32,
+    exported_at: DateTime<Utc>,
+    rooms: Vec<Room>,
+}
+
+/// 房间目录
+#[derive(Debug)]
+pub struct RoomDirectory {
+    path: PathBuf,
+    rooms: Vec<Room>,
+}
+
+impl RoomDirectory {
+    /// 从档案数据目录加载，不存在时为空目录
+    pub fn load(data_root: &Path) -> Result<Self, String> {
+        let path = data_root.join(ROOMS_FILE);
+        let rooms = if path.exists() {
+            let data = fs::read(&path).map_err(|e| format!("读取房间目录失败: {}", e))?;
+            serde_json::from_slice(&data).map_err(|e| format!("解析房间目录失败: {}", e))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, rooms })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(&self.rooms)
+            .map_err(|e| format!("序列化房间目录失败: {}", e))?;
+        fs::write(&self.path, data).map_err(|e| format!("写入房间目录失败: {}", e))
+    }
+
+    /// 所有房间，按创建时间排序
+    pub fn list(&self) -> Vec<Room> {
+        let mut rooms = self.rooms.clone();
+        rooms.sort_by_key(|room| room.created_at);
+        rooms
+    }
+
+    /// 获取房间
+    pub fn get(&self, id: &str) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.id == id)
+    }
+
+    /// 按话题查找房间
+    pub fn find_by_topic(&self, topic_id: &str) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.topic_id == topic_id)
+    }
+
+    /// 添加房间；话题已在目录中时返回已有房间
+    pub fn add(
+        &mut self,
+        name: String,
+        description: Option<String>,
+        topic_id: String,
+        ticket: String,
+    ) -> Result<Room, String> {
+        if let Some(room) = self.find_by_topic(&topic_id) {
+            return Ok(room.clone());
+        }
+        validate_name(&name)?;
+
+        let room = Room {
+            id: self.next_id(),
+            name,
+            description: description.filter(|d| !d.trim().is_empty()),
+            topic_id,
+            ticket,
+            members: Vec::new(),
+            created_at: Utc::now(),
+        };
+        self.rooms.push(room.clone());
+        self.save()?;
+        Ok(room)
+    }
+
+    /// 更新房间信息
+    pub fn update(&mut self, id: &str, update: RoomUpdate) -> Result<Room, String> {
+        if let Some(name) = &update.name {
+            validate_name(name)?;
+        }
+        let room = self
+            .rooms
+            .iter_mut()
+            .find(|room| room.id == id)
+            .ok_or_else(|| format!("房间不存在: {}", id))?;
+
+        if let Some(name) = update.name {
+            room.name = name;
+        }
+        if let Some(description) = update.description {
+            room.description = Some(description).filter(|d| !d.trim().is_empty());
+        }
+        if let Some(members) = update.members {
+            room.members = members;
+        }
+        let room = room.clone();
+        self.save()?;
+        Ok(room)
+    }
+
+    /// 记录话题中出现的成员名称，有新成员时返回 true
+    pub fn record_member(&mut self, topic_id: &str, member: &str) -> Result<bool, String> {
+        let Some(room) = self.rooms.iter_mut().find(|room| room.topic_id == topic_id) else {
+            return Ok(false);
+        };
+        if room.members.iter().any(|m| m == member) {
+            return Ok(false);
+        }
+        room.members.push(member.to_string());
+        self.save()?;
+        Ok(true)
+    }
+
+    /// 删除房间
+    pub fn remove(&mut self, id: &str) -> Result<Room, String> {
+        let index = self
+            .rooms
+            .iter()
+            .position(|room| room.id == id)
+            .ok_or_else(|| format!("房间不存在: {}", id))?;
+        let room = self.rooms.remove(index);
+        self.save()?;
+        Ok(room)
+    }
+
+    /// 导出为 JSON
+    pub fn export(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&RoomExport {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            rooms: self.list(),
+        })
+        .map_err(|e| format!("导出房间目录失败: {}", e))
+    }
+
+    /// 导入导出的 JSON，按话题去重，返回新增的房间数量
+    pub fn import(&mut self, data: &str) -> Result<usize, String> {
+        let export: RoomExport =
+            serde_json::from_str(data).map_err(|e| format!("解析房间导出数据失败: {}", e))?;
+        if export.version > EXPORT_VERSION {
+            return Err(format!("不支持的导出版本: {}", export.version));
+        }
+
+        let mut imported = 0;
+        for mut room in export.rooms {
+            if self.find_by_topic(&room.topic_id).is_some() {
+                continue;
+            }
+            validate_name(&room.name)?;
+            if self.get(&room.id).is_some() {
+                room.id = self.next_id();
+            }
+            self.rooms.push(room);
+            imported += 1;
+        }
+        if imported > 0 {
+            self.save()?;
+        }
+        Ok(imported)
+    }
+
+    fn next_id(&self) -> String {
+        let mut stamp = Utc::now().timestamp_micros();
+        loop {
+            let id = format!("room-{:x}", stamp);
+            if self.get(&id).is_none() {
+                return id;
+            }
+            stamp += 1;
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("房间名称不能为空".to_string());
+    }
+    Ok(())
+}
+
+/// 监听当前激活档案的 P2P 节点，把房间中出现的成员名称记入目录，节点停止后自动结束
+pub async fn watch_room_members<R: Runtime>(app: &AppHandle<R>) {
+    let mut receiver = {
+        let profiles = app.state::<ProfileState>();
+        let active = profiles.active().read().await;
+        let Some(node) = active.as_ref().and_then(|a| a.node.as_ref()) else {
+            return;
+        };
+        node.subscribe_events()
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(NodeEvent::PeerInfoUpdated { topic_id, peer }) => {
+                    let Some(name) = peer.name else {
+                        continue;
+                    };
+                    let profiles = app.state::<ProfileState>();
+                    let mut active = profiles.active().write().await;
+                    let Some(active) = active.as_mut() else {
+                        break;
+                    };
+                    match active.rooms.record_member(&topic_id, &name) {
+                        Ok(true) => info!("房间成员: {} 加入话题 {}", name, topic_id),
+                        Ok(false) => {}
+                        Err(e) => warn!("记录房间成员失败: {}", e),
+                    }
+                }
+                Ok(NodeEvent::Stopped { .. }) => break,
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("房间成员监听落后，丢弃 {} 条节点事件", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        debug!("房间成员监听结束");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("tauri-app-rooms-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_room_directory_crud() {
+        let root = temp_root("crud");
+        let mut rooms = RoomDirectory::load(&root).unwrap();
+        assert!(rooms.list().is_empty());
+
+        let room = rooms
+            .add(
+                "开发组".to_string(),
+                Some(" ".to_string()),
+                "topic-a".to_string(),
+                "ticket-a".to_string(),
+            )
+            .unwrap();
+        assert_eq!(room.description, None);
+        // 同一话题只保留一个房间，空名称被拒绝
+        let again = rooms
+            .add(
+                "重复".to_string(),
+                None,
+                "topic-a".to_string(),
+                "ticket-a".to_string(),
+            )
+            .unwrap();
+        assert_eq!(again.id, room.id);
+        assert!(rooms
+            .add(
+                " ".to_string(),
+                None,
+                "topic-b".to_string(),
+                "ticket-b".to_string()
+            )
+            .is_err());
+
+        let updated = rooms
+            .update(
+                &room.id,
+                RoomUpdate {
+                    name: Some("产品组".to_string()),
+                    description: Some("每周例会".to_string()),
+                    members: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.name, "产品组");
+        assert_eq!(updated.description.as_deref(), Some("每周例会"));
+        assert!(rooms.update("missing", RoomUpdate::default()).is_err());
+
+        assert!(rooms.record_member("topic-a", "alice").unwrap());
+        assert!(!rooms.record_member("topic-a", "alice").unwrap());
+        assert!(!rooms.record_member("topic-unknown", "bob").unwrap());
+
+        // 重新加载后内容不变
+        let mut rooms = RoomDirectory::load(&root).unwrap();
+        let room = rooms.find_by_topic("topic-a").unwrap().clone();
+        assert_eq!(room.name, "产品组");
+        assert_eq!(room.members, ["alice"]);
+
+        rooms.remove(&room.id).unwrap();
+        assert!(rooms.remove(&room.id).is_err());
+        assert!(RoomDirectory::load(&root).unwrap().list().is_empty());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_room_export_import() {
+        let root = temp_root("export");
+        let mut source = RoomDirectory::load(&root).unwrap();
+        source
+            .add(
+                "甲".to_string(),
+                None,
+                "topic-a".to_string(),
+                "ticket-a".to_string(),
+            )
+            .unwrap();
+        source
+            .add(
+                "乙".to_string(),
+                None,
+                "topic-b".to_string(),
+                "ticket-b".to_string(),
+            )
+            .unwrap();
+        let export = source.export().unwrap();
+
+        // 按话题去重：目录中已有的话题不重复导入
+        let other = temp_root("import");
+        let mut target = RoomDirectory::load(&other).unwrap();
+        target
+            .add(
+                "甲".to_string(),
+                None,
+                "topic-a".to_string(),
+                "ticket-a".to_string(),
+            )
+            .unwrap();
+        assert_eq!(target.import(&export).unwrap(), 1);
+        assert_eq!(target.import(&export).unwrap(), 0);
+        assert_eq!(target.list().len(), 2);
+        assert_eq!(RoomDirectory::load(&other).unwrap().list().len(), 2);
+
+        // 更新版本的导出文件被拒绝
+        let newer = export.replacen(
+            &format!("\"version\": {}", EXPORT_VERSION),
+            &format!("\"version\": {}", EXPORT_VERSION + 1),
+            1,
+        );
+        assert!(target.import(&newer).is_err());
+        assert!(target.import("不是 JSON").is_err());
+
+        fs::remove_dir_all(&root).ok();
+        fs::remove_dir_all(&other).ok();
+    }
+}
@@ -0,0 +1,336 @@
+//! 更新检查
+//!
+//! 定期读取可配置的发布清单，校验清单签名后按语义化版本比较当前版本，
+//! 有新版本时发送 `update-available` 事件，由前端或 tauri-updater 处理后续下载安装。
+//! 清单的签名为对清单原始字节的 Ed25519 签名（Base64），放在 `<清单地址>.sig`。
+//! 校验用的公钥在构建时通过环境变量 `UPDATE_PUBLIC_KEY` 内置，运行时无法修改；
+//! 未内置公钥的构建不检查更新。
+
+use std::{collections::HashMap, fs, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tauri::{async_runtime::RwLock, AppHandle, Emitter, Manager, Runtime};
+use tracing::{debug, error, info, warn};
+
+/// 更新设置文件名
+const SETTINGS_FILE: &str = "updates.json";
+
+/// 有新版本时发送的事件
+pub const UPDATE_AVAILABLE_EVENT: &str = "update-available";
+
+/// 请求清单的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 构建时内置的清单签名公钥（Base64编码的 Ed25519 公钥）
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("UPDATE_PUBLIC_KEY");
+
+/// 发布清单的最大字节数
+const MAX_MANIFEST_BYTES: usize = 1024 * 1024;
+
+/// 清单签名的最大字节数
+const MAX_SIGNATURE_BYTES: usize = 1024;
+
+/// 更新设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    /// 是否自动检查更新
+    pub enabled: bool,
+    /// 发布清单地址
+    pub manifest_url: Option<String>,
+    /// 发布渠道，例如 `stable`、`beta`
+    pub channel: String,
+    /// 自动检查间隔（小时）
+    pub check_interval_hours: u64,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            manifest_url: None,
+            channel: "stable".to_string(),
+            check_interval_hours: 24,
+        }
+    }
+}
+
+/// 单个平台的安装包
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformRelease {
+    /// 下载地址
+    pub url: String,
+    /// 安装包签名，交给 tauri-updater 校验
+    pub signature: String,
+}
+
+/// 某个渠道的最新发布
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelRelease {
+    /// 版本号
+    pub version: String,
+    /// 发布说明
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// 发布时间
+    #[serde(default)]
+    pub pub_date: Option<DateTime<Utc>>,
+    /// 各平台的安装包，键与 tauri-updater 一致，例如 `darwin-aarch64`
+    #[serde(default)]
+    pub platforms: HashMap<String, PlatformRelease>,
+}
+
+/// 发布清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseManifest {
+    /// 各渠道的最新发布
+    pub channels: HashMap<String, ChannelRelease>,
+}
+
+impl ReleaseManifest {
+    /// 指定渠道中比当前版本更新的发布，没有该渠道或已是最新时返回 `None`
+    pub fn update_for(
+        &self,
+        channel: &str,
+        current_version: &str,
+    ) -> Result<Option<UpdateInfo>, String> {
+        let Some(release) = self.channels.get(channel) else {
+            debug!("发布清单中没有渠道 {}", channel);
+            return Ok(None);
+        };
+
+        let current = Version::parse(current_version)
+            .map_err(|e| format!("解析当前版本 {} 失败: {}", current_version, e))?;
+        let latest = Version::parse(release.version.trim_start_matches('v'))
+            .map_err(|e| format!("解析发布版本 {} 失败: {}", release.version, e))?;
+        if latest <= current {
+            return Ok(None);
+        }
+
+        Ok(Some(UpdateInfo {
+            current_version: current.to_string(),
+            version: latest.to_string(),
+            channel: channel.to_string(),
+            notes: release.notes.clone(),
+            pub_date: release.pub_date,
+            platform: release.platforms.get(&platform_key()).cloned(),
+        }))
+    }
+}
+
+/// 可用更新
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateInfo {
+    /// 当前版本
+    pub current_version: String,
+    /// 新版本
+    pub version: String,
+    /// 发布渠道
+    pub channel: String,
+    /// 发布说明
+    pub notes: Option<String>,
+    /// 发布时间
+    pub pub_date: Option<DateTime<Utc>>,
+    /// 当前平台的安装包，清单中没有当前平台时为空
+    pub platform: Option<PlatformRelease>,
+}
+
+/// Tauri 托管的更新状态
+pub struct UpdateState {
+    path: PathBuf,
+    settings: RwLock<UpdateSettings>,
+}
+
+impl UpdateState {
+    /// 从应用数据目录加载更新设置，不存在时使用默认设置
+    pub fn load<R: Runtime>(handle: &AppHandle<R>) -> Result<Self, String> {
+        let root = handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("无法获取应用数据目录: {}", e))?;
+        fs::create_dir_all(&root).map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+
+        let path = root.join(SETTINGS_FILE);
+        let settings = if path.exists() {
+            let data = fs::read(&path).map_err(|e| format!("读取更新设置失败: {}", e))?;
+            serde_json::from_slice(&data).map_err(|e| format!("解析更新设置失败: {}", e))?
+        } else {
+            UpdateSettings::default()
+        };
+
+        Ok(Self {
+            path,
+            settings: RwLock::new(settings),
+        })
+    }
+
+    /// 获取更新设置
+    pub async fn settings(&self) -> UpdateSettings {
+        self.settings.read().await.clone()
+    }
+
+    /// 更新并保存更新设置
+    pub async fn update(&self, settings: UpdateSettings) -> Result<(), String> {
+        let data = serde_json::to_vec_pretty(&settings)
+            .map_err(|e| format!("序列化更新设置失败: {}", e))?;
+        fs::write(&self.path, data).map_err(|e| format!("写入更新设置失败: {}", e))?;
+        *self.settings.write().await = settings;
+        Ok(())
+    }
+}
+
+/// tauri-updater 使用的当前平台键
+fn platform_key() -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    format!("{}-{}", os, std::env::consts::ARCH)
+}
+
+/// 校验清单签名
+fn verify_manifest(public_key: &str, manifest: &[u8], signature: &str) -> Result<(), String> {
+    let key: [u8; 32] = BASE64
+        .decode(public_key.trim().as_bytes())
+        .map_err(|e| format!("解析更新公钥失败: {}", e))?
+        .try_into()
+        .map_err(|_| "更新公钥长度无效".to_string())?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|e| format!("更新公钥无效: {}", e))?;
+
+    let signature = BASE64
+        .decode(signature.trim().as_bytes())
+        .map_err(|e| format!("解析清单签名失败: {}", e))?;
+    let signature =
+        Signature::from_slice(&signature).map_err(|e| format!("清单签名格式无效: {}", e))?;
+    key.verify(manifest, &signature)
+        .map_err(|_| "清单签名校验失败".to_string())
+}
+
+/// 下载指定地址的内容，超过 `limit` 字节时中止
+async fn fetch(client: &reqwest::Client, url: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("请求 {} 失败: {}", url, e))?;
+    if response
+        .content_length()
+        .is_some_and(|len| len > limit as u64)
+    {
+        return Err(format!("{} 的内容超过 {} 字节", url, limit));
+    }
+
+    // 服务器可能不返回或谎报长度，边读边检查
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("读取 {} 失败: {}", url, e))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(format!("{} 的内容超过 {} 字节", url, limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// 检查指定渠道是否有比当前版本更新的发布
+pub async fn check_for_update(
+    settings: &UpdateSettings,
+    current_version: &str,
+) -> Result<Option<UpdateInfo>, String> {
+    let manifest_url = settings
+        .manifest_url
+        .as_deref()
+        .ok_or_else(|| "未配置发布清单地址".to_string())?;
+    let public_key = RELEASE_PUBLIC_KEY.ok_or_else(|| "此构建未内置更新公钥".to_string())?;
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+    let manifest = fetch(&client, manifest_url, MAX_MANIFEST_BYTES).await?;
+    let signature = fetch(
+        &client,
+        &format!("{}.sig", manifest_url),
+        MAX_SIGNATURE_BYTES,
+    )
+    .await?;
+    verify_manifest(public_key, &manifest, &String::from_utf8_lossy(&signature))?;
+
+    let manifest: ReleaseManifest =
+        serde_json::from_slice(&manifest).map_err(|e| format!("解析发布清单失败: {}", e))?;
+    manifest.update_for(&settings.channel, current_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const MANIFEST: &[u8] = br#"{"channels":{"stable":{"version":"1.2.0"}}}"#;
+
+    fn sign(key: &SigningKey, manifest: &[u8]) -> (String, String) {
+        let public_key = BASE64.encode(key.verifying_key().as_bytes());
+        let signature = BASE64.encode(&key.sign(manifest).to_bytes());
+        (public_key, signature)
+    }
+
+    #[test]
+    fn test_verify_manifest_accepts_valid_signature() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let (public_key, signature) = sign(&key, MANIFEST);
+        assert!(verify_manifest(&public_key, MANIFEST, &signature).is_ok());
+        // 签名文件末尾的换行不影响校验
+        assert!(verify_manifest(&public_key, MANIFEST, &format!("{}\n", signature)).is_ok());
+    }
+
+    #[test]
+    fn test_verify_manifest_rejects_tampered_manifest() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let (public_key, signature) = sign(&key, MANIFEST);
+        let tampered = br#"{"channels":{"stable":{"version":"9.9.9"}}}"#;
+        assert!(verify_manifest(&public_key, tampered, &signature).is_err());
+    }
+
+    #[test]
+    fn test_verify_manifest_rejects_wrong_key() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let other = SigningKey::from_bytes(&[8u8; 32]);
+        let (_, signature) = sign(&key, MANIFEST);
+        let (other_public_key, _) = sign(&other, MANIFEST);
+        assert!(verify_manifest(&other_public_key, MANIFEST, &signature).is_err());
+        assert!(verify_manifest("不是公钥", MANIFEST, &signature).is_err());
+    }
+
+    #[test]
+    fn test_update_for_channel() {
+        let manifest: ReleaseManifest = serde_json::from_value(serde_json::json!({
+            "channels": {
+                "stable": {"version": "v1.2.0", "notes": "修复问题"},
+                "beta": {"version": "1.3.0-beta.1"},
+            }
+        }))
+        .unwrap();
+
+        let update = manifest.update_for("stable", "1.1.0").unwrap().unwrap();
+        assert_eq!(update.version, "1.2.0");
+        assert_eq!(update.channel, "stable");
+        assert_eq!(update.notes.as_deref(), Some("修复问题"));
+        assert!(update.platform.is_none());
+
+        // 已是最新、渠道不存在时没有更新
+        assert!(manifest.update_for("stable", "1.2.0").unwrap().is_none());
+        assert!(manifest.update_for("nightly", "1.0.0").unwrap().is_none());
+
+        let beta = manifest.update_for("beta", "1.2.0").unwrap().unwrap();
+        assert_eq!(beta.version, "1.3.0-beta.1");
+        assert!(manifest.update_for("beta", "不是版本号").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! tauri-axum-iroh-agent
+//!
+//! 统一入口：从 rig-agent 与 iroh-node 中整理出一套一致的公共API，
+//! 应用只需依赖本crate，通过特性选择需要的部分：
+//!
+//! - `p2p`（默认）：P2P节点
+//! - `axum`：Axum适配器，包含文件分享、上传与传输进度
+//! - `link-preview`：Axum适配器为聊天消息中的链接生成预览
+//! - `tauri`：Tauri适配器，同时启用 `p2p` 时包含节点的Tauri插件
+//! - `keychain`：主密钥保存在系统钥匙串中
+//!
+//! ```ignore
+//! use tauri_axum_iroh_agent::prelude::*;
+//!
+//! let manager = AgentManager::new(AgentConfig::default());
+//! let registry = ClientRegistry::new();
+//! manager.create_agent("assistant".to_string(), None).await?;
+//! let response = manager.chat(&registry, "assistant", "你好").await?;
+//! ```
+
+/// Agent：管理器、配置、消息与响应
+pub mod agent {
+    pub use rig_agent::core::ClientRegistry;
+    pub use rig_agent::{
+        AgentConfig, AgentManager, AgentMessage, AgentResponse, AgentRole, AgentSummary,
+        ChatOptions, ClientConfig, ConfigBuilder, ConversationHistory, CreativityProfile,
+        DocumentSummary, FeedbackRating, FinishReason, ListPage, MessageMetadata, PageRequest,
+        Register, ReplyLanguage, RequestContext, RequestFingerprint, RequestOrigin,
+        RequestPriority, ResponseFeedback, ResponseWarning, StreamingMode, SummarizeOptions,
+        ToolSelection, TranslateRequest, Translation,
+    };
+}
+
+/// 存储：会话记录、加密的密钥存储与数据保留策略
+pub mod storage {
+    pub use rig_agent::{
+        KeySource, MasterKey, PurgeReport, RetentionPolicy, SecretStore, SessionStore,
+    };
+}
+
+/// P2P节点
+#[cfg(feature = "p2p")]
+pub mod node {
+    pub use iroh_node::{
+        ChatCommand, ChatHistoryEntry, ChatLogConfig, CommandAction, CommandContext,
+        CommandOutcome, CommandRegistry, FileSyncStatus, FolderSyncConfig, Invite, InviteKind,
+        LinkPreview, MentionRecord, MentionSummary, MessageContent, MessageType, NodeConfig,
+        NodeStatus, OutboxConfig, OutboxEntry, P2PNode, PeerInfo, Reactions, RoomBotConfig,
+        ScanConfig, ScanPolicy, ScanResult, ScannerConfig, SyncState, TaskHealth, TaskState,
+        TopicStats, TrustLevel, UnreadSummary, NODE_SECRET_KEY_NAME,
+    };
+}
+
+/// 事件
+pub mod events {
+    pub use rig_agent::AgentEvent;
+
+    #[cfg(feature = "p2p")]
+    pub use iroh_node::{EventBus, NodeEvent};
+}
+
+/// 适配器
+pub mod adapters {
+    pub use rig_agent::{AgentAdapter, StandaloneAgentAdapter};
+
+    #[cfg(feature = "tauri")]
+    pub use rig_agent::TauriAgentAdapter;
+
+    #[cfg(all(feature = "tauri", feature = "p2p"))]
+    pub use iroh_node::adapters::{tauri_plugin, PluginConfig, TauriPluginBuilder};
+
+    #[cfg(feature = "axum")]
+    pub use iroh_node::adapters::{
+        AxumAdapter, FilePreview, FileShareConfig, FolderWatcher, FolderWatcherConfig,
+        PreviewConfig, ProgressConfig, ProgressRegistry, RequestGuardConfig, UploadConfig,
+        UploadManager, WatchFolderConfig,
+    };
+
+    #[cfg(feature = "link-preview")]
+    pub use iroh_node::adapters::{LinkPreviewConfig, LinkPreviewer};
+}
+
+/// 错误
+pub mod error {
+    pub use rig_agent::AgentError;
+
+    #[cfg(feature = "p2p")]
+    pub use iroh_node::NodeError;
+
+    /// 统一错误类型，两个crate的错误都可以用 `?` 转换过来
+    #[derive(Debug, thiserror::Error)]
+    pub enum Error {
+        /// Agent错误
+        #[error(transparent)]
+        Agent(#[from] AgentError),
+        /// 节点错误
+        #[cfg(feature = "p2p")]
+        #[error(transparent)]
+        Node(#[from] NodeError),
+    }
+
+    impl Error {
+        /// 错误代码，节点错误统一为 `NODE_ERROR`
+        pub fn code(&self) -> &'static str {
+            match self {
+                Self::Agent(error) => error.error_code(),
+                #[cfg(feature = "p2p")]
+                Self::Node(_) => "NODE_ERROR",
+            }
+        }
+    }
+
+    /// 统一结果类型
+    pub type Result<T> = std::result::Result<T, Error>;
+}
+
+pub use error::{Error, Result};
+
+/// 常用类型，`use tauri_axum_iroh_agent::prelude::*;`
+pub mod prelude {
+    pub use crate::agent::{
+        AgentConfig, AgentManager, AgentMessage, AgentResponse, ChatOptions, ClientRegistry,
+        FinishReason,
+    };
+    pub use crate::error::{Error, Result};
+
+    #[cfg(feature = "p2p")]
+    pub use crate::events::NodeEvent;
+    #[cfg(feature = "p2p")]
+    pub use crate::node::{NodeConfig, NodeStatus, P2PNode};
+
+    #[cfg(feature = "axum")]
+    pub use crate::adapters::AxumAdapter;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::error::AgentError;
+    use super::prelude::*;
+
+    #[test]
+    fn test_unified_error() {
+        fn agent_call() -> std::result::Result<(), AgentError> {
+            Err(AgentError::RateLimit)
+        }
+        fn app() -> Result<()> {
+            agent_call()?;
+            Ok(())
+        }
+
+        let error = app().unwrap_err();
+        assert_eq!(error.code(), "RATE_LIMIT");
+        assert_eq!(error.to_string(), AgentError::RateLimit.to_string());
+
+        #[cfg(feature = "p2p")]
+        {
+            let error = Error::from(super::error::NodeError::ConfigError("缺少密钥".into()));
+            assert_eq!(error.code(), "NODE_ERROR");
+        }
+    }
+}